@@ -1,12 +1,27 @@
+use std::fs;
+use std::path::Path;
+
 use json::JsonValue;
 
+use crate::bbox::BBox;
 use crate::ifs::{self, IFS};
 use crate::clusters::{self, Cluster};
-use crate::plotters::{self, Plotter};
+use crate::plotters::{self, PlotCounts, PlotResult, Plotter, SplitPlotter};
 use crate::vector::Vec3;
 use crate::half_multivector::HalfMultivector;
-use crate::point::{InternalPoint, OutputPoint};
+use crate::point::{check_id_count, InternalPoint, OutputPoint};
 use crate::fractal_metadata::FractalMetadata;
+use crate::jitter::Jitter;
+use crate::auto_stop::AutoStop;
+use crate::checkpoint::{CheckpointKind, CheckpointReader, CheckpointWriter};
+use crate::orbit_trap::OrbitTrap;
+use crate::profiling::Profile;
+use crate::progress::{parse_progress_interval, Progress};
+use crate::quality::QualityMetric;
+use crate::random;
+use crate::timeout::Timeout;
+use crate::trail::Trail;
+use crate::xforms::{self, Transform};
 
 /// A generic IFS-based rendering algorithm like the Chaos Game and other
 /// related algorithms
@@ -18,9 +33,52 @@ pub trait Algorithm {
     /// Get the complexity of the algorithm measured by number of points in
     /// the output tileset.
     fn complexity(&self) -> usize;
+    /// Turn on timing of the plotter's phases for --profile
+    fn enable_profiling(&mut self);
+    /// Record the time spent in the plotter's phases
+    fn record_profile(&self, profile: &mut Profile);
+    /// Score the output for --seed-sweep. Higher is better
+    fn quality(&self, metric: QualityMetric) -> f64;
+    /// How many points the plotter kept or discarded so far
+    fn plot_counts(&self) -> PlotCounts;
+    /// The number of points in each leaf of the plotter's octree, see
+    /// Plotter::leaf_sizes()
+    fn leaf_sizes(&self) -> Vec<usize>;
+    /// The region the plotter covers, see Plotter::bounds()
+    fn plotter_bounds(&self) -> BBox;
+    /// Instead of saving, consume the algorithm after iterate() and return
+    /// the plotted points
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint>;
+    /// The schemas of the tileset metadata and the per-point properties,
+    /// see describe_metadata()
+    fn metadata_json(&self) -> JsonValue;
+    /// Load a checkpoint written by an earlier run of the same parameter
+    /// file. The next call to iterate() continues where that run left off
+    fn resume(&mut self, fname: &str);
+}
+
+/// Describe the structure of the output for --echo-metadata as JSON of the
+/// form:
+///
+/// ```text
+/// {
+///     "tileset_schema": <the schema in tileset.json>,
+///     "point_schema": <the class of the per-point .glb properties, or null>
+/// }
+/// ```
+fn describe_metadata(metadata: &FractalMetadata, output: &dyn Plotter)
+        -> JsonValue {
+    let mut json = JsonValue::new_object();
+    json["tileset_schema"] = metadata.make_schema_json();
+    json["point_schema"] = output.point_schema_json(metadata);
+    json
 }
 
 const STARTUP_ITERS: usize = 10;
+/// Each Chaos Game iteration is quick, so only check the timeout this
+/// often. Chaos sets check every iteration, since each one transforms the
+/// whole buffer
+const TIMEOUT_CHECK_INTERVAL: usize = 1000;
 
 /// The basic Chaos Game algorithm (see Fractals Everywhere by Michael F. 
 /// Barnsley)
@@ -35,6 +93,37 @@ pub struct ChaosGame {
     output: Box<dyn Plotter>,
     /// Number of iterations to perform
     num_iters: usize,
+    /// How many iterations to run before plotting. This is STARTUP_ITERS
+    /// unless skip_startup is false
+    startup_iters: usize,
+    /// Optional noise added after each transformation
+    jitter: Jitter,
+    /// Optionally stop early once the octree stops gaining points
+    auto_stop: Option<AutoStop>,
+    /// Optionally color points by orbit trap instead of the color IFS
+    orbit_trap: Option<OrbitTrap>,
+    /// If set, start the color over from a random color every this many
+    /// iterations
+    color_reset_every: Option<usize>,
+    /// If set, write a checkpoint every this many iterations
+    checkpoint_every: Option<usize>,
+    /// Plotted points between progress updates, or None for no progress
+    progress_interval: Option<usize>,
+    /// Optionally stop iterating once this much time has passed
+    timeout: Option<Timeout>,
+    /// Where to pick up the iteration after resume()
+    resume_state: Option<ChaosGameState>,
+}
+
+/// The state of the Chaos Game between two iterations
+struct ChaosGameState {
+    /// The next value of the iteration counter, including the startup
+    /// iterations
+    next_iteration: usize,
+    /// The current position before the post-transform
+    position: HalfMultivector,
+    /// The current color before the post-transform
+    color: HalfMultivector,
 }
 
 impl ChaosGame {
@@ -46,75 +135,309 @@ impl ChaosGame {
     ///     "ifs": <IFS JSON>
     ///     "color_ifs": <IFS JSON>,
     ///     "iters": N,
-    ///     "plotter": <Plotter JSON>
+    ///     "plotter": <Plotter JSON>,
+    ///     "jitter": <Jitter JSON> (optional),
+    ///     "auto_stop": <AutoStop JSON> (optional),
+    ///     "orbit_trap": <OrbitTrap JSON> (optional),
+    ///     "color_reset_every": C (optional),
+    ///     "checkpoint_every": K (optional),
+    ///     "progress_interval": P (default 100000),
+    ///     "skip_startup": bool (default true),
+    ///     "timeout": seconds (optional),
+    ///     "warn_color_ifs": bool (default true),
+    ///     "seed": n (optional)
     /// }
     /// ```
+    ///
+    /// The first few iterations are usually not on the attractor yet, so
+    /// they are not plotted. With skip_startup false, every iteration is
+    /// plotted starting from the first one, e.g. for a seed that is
+    /// already on the attractor. Either way, iters points are plotted.
+    ///
+    /// With auto_stop, iters is the maximum number of iterations. With
+    /// orbit_trap, the color IFS is still iterated (so last_color_xform
+    /// is still meaningful) but the output colors come from the trap.
+    ///
+    /// A strongly contractive color IFS collapses the color to its fixed
+    /// point after a few iterations, making the whole fractal one color.
+    /// With color_reset_every, the color starts over from a random color
+    /// every C iterations so the variety persists over long runs.
+    ///
+    /// With checkpoint_every, the position, color and plotted points are
+    /// written to ./viewer/{id}.ckpt every K iterations, see resume(). The
+    /// IFS choosers, auto_stop and orbit_trap are not part of the
+    /// checkpoint, they start over when resuming.
+    ///
+    /// progress_interval is how many points are plotted between progress
+    /// updates. Set it to 0 to turn off the progress display.
+    ///
+    /// With timeout, the iteration stops after that many seconds even if
+    /// fewer than iters points were plotted. save() then writes the points
+    /// plotted so far.
+    ///
+    /// A warning is printed if the color IFS has many more or many fewer
+    /// transformations than the IFS, see check_color_ifs(). Set
+    /// warn_color_ifs to false to turn it off.
+    ///
+    /// With seed, every random choice (the choosers, the starting point,
+    /// jitter and any random colors or cluster points) is drawn from a
+    /// generator seeded with it, so two runs of the same parameters plot
+    /// the same points. Without it, every run is different. A seed in the
+    /// jitter JSON overrides this one for the jitter.
     pub fn from_json(json: &JsonValue) -> Self {
+        random::reseed(random::seed_from_json(&json["seed"]));
         let position_ifs = ifs::from_json(&json["ifs"]);
         let color_ifs = ifs::from_json(&json["color_ifs"]);
         let plotter = plotters::from_json(&json["plotter"]);
         let num_iters = json["iters"]
             .as_usize()
             .expect("iters must be a positive integer");
-        let metadata = FractalMetadata::from_json(&json);
+        let mut metadata = FractalMetadata::from_json(&json);
+        metadata.set_ifs(&position_ifs, &color_ifs);
+        check_color_ifs(&metadata, &json["warn_color_ifs"]);
+        let jitter = Jitter::from_json(&json["jitter"]);
+        let auto_stop = AutoStop::from_json(&json["auto_stop"]);
+        let orbit_trap = OrbitTrap::from_json(&json["orbit_trap"]);
+        let color_reset_every =
+            parse_period(&json["color_reset_every"], "color_reset_every");
+        let checkpoint_every =
+            parse_period(&json["checkpoint_every"], "checkpoint_every");
+        let progress_interval =
+            parse_progress_interval(&json["progress_interval"]);
+        let skip_startup = json["skip_startup"].as_bool().unwrap_or(true);
+        let startup_iters = if skip_startup { STARTUP_ITERS } else { 0 };
+        let timeout = Timeout::from_json(&json["timeout"])
+            .map(|timeout| timeout.with_check_every(TIMEOUT_CHECK_INTERVAL));
 
-        Self {
+        let result = Self {
             metadata,
             position_ifs,
             color_ifs,
             output: plotter,
             num_iters,
-        }
+            startup_iters,
+            jitter,
+            auto_stop,
+            orbit_trap,
+            color_reset_every,
+            checkpoint_every,
+            progress_interval,
+            timeout,
+            resume_state: None,
+        };
+        let dimension = result.position_ifs.similarity_dimension();
+        check_capacity(
+            result.complexity(), dimension, result.output.as_ref());
+        result
     }
 
     to_box!(Algorithm);
+
+    /// Write the state before iteration next_iteration to the checkpoint
+    /// file
+    fn write_checkpoint(
+            &self,
+            next_iteration: usize,
+            pos: &HalfMultivector,
+            color_vec: &HalfMultivector) {
+        let fname = checkpoint_path(&self.metadata.id);
+        let mut writer =
+            CheckpointWriter::create(&fname, CheckpointKind::ChaosGame);
+        writer.write_usize(next_iteration);
+        writer.write_multivector_point(pos);
+        writer.write_multivector_point(color_vec);
+        self.output.write_checkpoint(&mut writer);
+        writer.finish();
+        info!(
+            "Wrote checkpoint {} after {} iterations",
+            fname,
+            next_iteration - self.startup_iters);
+    }
 }
 
 impl Algorithm for ChaosGame {
     fn iterate(&mut self) {
-        // Start with a random position and color
-        let mut pos = HalfMultivector::from_vec3(&Vec3::random());
-        let mut color_vec = HalfMultivector::from_vec3(&Vec3::random_color());
-        const UPDATE_FREQ: usize = 100000;
-        let complexity = self.complexity() / UPDATE_FREQ;
+        // Start with a random position and color, unless resuming
+        let (start, mut pos, mut color_vec) = match self.resume_state.take() {
+            Some(state) => (state.next_iteration, state.position, state.color),
+            None => (
+                0,
+                HalfMultivector::from_vec3(&Vec3::random()),
+                HalfMultivector::from_vec3(&Vec3::random_color()))
+        };
+        let mut progress = Progress::new(
+            self.num_iters,
+            start.saturating_sub(self.startup_iters),
+            self.progress_interval);
+        if let Some(timeout) = &mut self.timeout {
+            timeout.start();
+        }
 
         // For the basic chaos game, everything is the same feature
         let cluster_coordinates: Vec3 = Vec3::zero();
 
-        for i in 0..(STARTUP_ITERS + self.num_iters) {
+        for i in start..(self.startup_iters + self.num_iters) {
+            let transformed = self.position_ifs.post_transform(&pos);
+            let escaped = transformed.try_to_vec3().is_none();
+            let position = if escaped {
+                // plot_escaped() expects the direction instead
+                transformed.escape_direction()
+            } else {
+                transformed.to_vec3()
+            };
+            if let Some(trap) = &mut self.orbit_trap {
+                if !escaped {
+                    trap.record(&position);
+                }
+            }
+
             // Skip the first few iterations as they are often not on 
             // the fractal.
-            if i >= STARTUP_ITERS {
+            if i >= self.startup_iters {
+                let color = match &self.orbit_trap {
+                    Some(trap) => trap.color(),
+                    None => self.color_ifs.post_transform(&color_vec).to_vec3()
+                };
                 let point = OutputPoint {
-                    position: pos.to_vec3(),
-                    color: color_vec.to_vec3(),
+                    position,
+                    color,
                     cluster_coordinates,
                     iteration: i as u64,
                     cluster_copy: 0,
                     cluster_id: 0,
                     point_id: 0,
                     last_xform: self.position_ifs.get_last_xform(),
-                    last_color_xform: self.color_ifs.get_last_xform()
+                    last_xform_group: self.position_ifs.get_last_xform_group(),
+                    last_color_xform: self.color_ifs.get_last_xform(),
+                    xform_address: self.position_ifs.get_xform_address(),
+                    cga_pn: transformed.conformal_components(),
+                    weight: 1
                 };
 
-                self.output.plot_point(point);
+                let result = if escaped {
+                    self.output.plot_escaped(point)
+                } else {
+                    self.output.plot_point(point)
+                };
+                // Only points that reached a full leaf say the plot is
+                // saturated. Clipped, masked or escaped points say nothing
+                // about it, so they are left out.
+                let kept = match result {
+                    PlotResult::Accepted => Some(true),
+                    PlotResult::Full => Some(false),
+                    PlotResult::Filtered => None
+                };
+                if let (Some(auto_stop), Some(accepted)) =
+                        (&mut self.auto_stop, kept) {
+                    if auto_stop.record(accepted) {
+                        progress.interrupt();
+                        info!(
+                            "Auto-stop after {} iterations, {:.2}% kept",
+                            i - self.startup_iters + 1,
+                            100.0 * auto_stop.acceptance_rate());
+                        break;
+                    }
+                }
             }
 
             pos = self.position_ifs.transform(&pos);
             color_vec = self.color_ifs.transform(&color_vec);
+            pos = self.jitter.jitter_position(&pos);
+            color_vec = self.jitter.jitter_color(&color_vec);
+            if is_period_end(self.color_reset_every, i) {
+                color_vec = HalfMultivector::from_vec3(&Vec3::random_color());
+            }
+
+            // The last iteration is followed by save(), so there is no
+            // need for a checkpoint
+            let startup = self.startup_iters;
+            let is_last = i + 1 == startup + self.num_iters;
+            if i >= startup && !is_last &&
+                    is_period_end(self.checkpoint_every, i - startup) {
+                progress.interrupt();
+                self.write_checkpoint(i + 1, &pos, &color_vec);
+            }
+
+            // In the Chaos Game, every iteration plots one point
+            if i >= self.startup_iters {
+                let iterations = i + 1 - self.startup_iters;
+                progress.update(iterations, iterations);
+            }
 
-            // Show progress every UPDATE_FREQ iterations
-            if i > STARTUP_ITERS && i % UPDATE_FREQ == STARTUP_ITERS {
-                println!(
-                    "Completed ~{}/{} chunks of 100K iterations", 
-                    (i - STARTUP_ITERS) / UPDATE_FREQ, 
-                    complexity);
+            if let Some(timeout) = &self.timeout {
+                if timeout.is_expired(i) {
+                    progress.interrupt();
+                    info!(
+                        "Timeout after {:.1}s, {} of {} iterations completed",
+                        timeout.elapsed().as_secs_f64(),
+                        (i + 1).saturating_sub(self.startup_iters),
+                        self.num_iters);
+                    break;
+                }
             }
         }
+        progress.finish();
     }
 
     fn save(&mut self) {
         self.output.save(&self.metadata.id, &self.metadata);
+        if self.checkpoint_every.is_some() {
+            remove_checkpoint(&self.metadata.id);
+        }
+    }
+
+    fn enable_profiling(&mut self) {
+        self.output.enable_profiling();
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        self.output.record_profile(profile);
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        self.output.quality(metric)
+    }
+
+    fn plot_counts(&self) -> PlotCounts {
+        self.output.counts()
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.output.leaf_sizes()
+    }
+
+    fn plotter_bounds(&self) -> BBox {
+        self.output.bounds().clone()
+    }
+
+    fn metadata_json(&self) -> JsonValue {
+        describe_metadata(&self.metadata, self.output.as_ref())
+    }
+
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.output.into_points()
+    }
+
+    fn resume(&mut self, fname: &str) {
+        let mut reader = CheckpointReader::open(fname, CheckpointKind::ChaosGame);
+        let next_iteration = reader.read_usize();
+        if next_iteration > self.startup_iters + self.num_iters {
+            panic!(
+                "{} is past the end of the {} iterations", fname, self.num_iters);
+        }
+        let position = reader.read_multivector_point();
+        let color = reader.read_multivector_point();
+        self.output.read_checkpoint(&mut reader);
+        info!(
+            "Resuming {} after {} iterations",
+            fname,
+            next_iteration.saturating_sub(self.startup_iters));
+
+        self.resume_state = Some(ChaosGameState {
+            next_iteration,
+            position,
+            color
+        });
     }
 
     /// The complexity of the basic chaos game is O(n) where n is the number
@@ -144,35 +467,152 @@ pub struct ChaosSets {
     output: Box<dyn Plotter>,
     /// Number of iterations to perform.
     num_iters: usize,
+    /// If set, a fresh copy of the cluster joins the buffer every k
+    /// iterations, so it is transformed along with the points already
+    /// there. This is how Barnsley's condensation sets accumulate the seed
+    /// shape at all scales.
+    condensation_period: Option<usize>,
+    /// Optional noise added after each transformation
+    jitter: Jitter,
+    /// If set, older iterations are plotted dimmer
+    trail: Option<Trail>,
+    /// If set, copy k of the cluster is moved by this transformation
+    /// applied k times before iterating
+    copy_xform: Option<Box<dyn Transform>>,
+    /// Whether copy_xform is applied about the origin or each copy's
+    /// centroid
+    copy_frame: CopyFrame,
+    /// If set, copy k of the cluster is recolored to palette[k % len]
+    copy_palette: Option<Vec<Vec3>>,
+    /// If set, write a checkpoint every this many iterations
+    checkpoint_every: Option<usize>,
+    /// Plotted points between progress updates, or None for no progress
+    progress_interval: Option<usize>,
+    /// Optionally stop iterating once this much time has passed
+    timeout: Option<Timeout>,
+    /// Where to pick up the iteration after resume()
+    resume_state: Option<ChaosSetsState>,
+    /// While iterate_streaming() runs, the points plotted for the current
+    /// copy of the cluster go here instead of the plotter
+    stream_buffer: Option<Vec<OutputPoint>>,
+}
+
+/// Called with each copy number and the points plotted for that copy, see
+/// ChaosSets::iterate_streaming()
+pub type CopyCallback<'a> = &'a mut dyn FnMut(u16, &[OutputPoint]);
+
+/// Parse the optional copy_palette, a non-empty list of "#rrggbb" colors
+fn parse_copy_palette(json: &JsonValue) -> Option<Vec<Vec3>> {
+    if json.is_null() {
+        return None;
+    }
+    if !json.is_array() || json.is_empty() {
+        panic!("copy_palette must be a non-empty array of \"#rrggbb\" colors");
+    }
+
+    let palette = json.members().map(|color| {
+        let hex = color
+            .as_str()
+            .expect("copy_palette colors must be strings");
+        Vec3::from_hex(hex)
+            .unwrap_or_else(|err| panic!("copy_palette: {}", err))
+    }).collect();
+    Some(palette)
+}
+
+/// Where copy_xform is centered
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CopyFrame {
+    /// Apply the transformation as written, i.e. about the world origin
+    Origin,
+    /// Apply the transformation about the centroid of the copy's points,
+    /// so e.g. a rotation spins each copy in place
+    Centroid,
+}
+
+impl CopyFrame {
+    /// Parse "origin" or "centroid" (default "origin")
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str().unwrap_or("origin") {
+            "origin" => Self::Origin,
+            "centroid" => Self::Centroid,
+            _ => panic!("copy_frame must be either origin or centroid")
+        }
+    }
+}
+
+/// Pick the plotter radius for a Chaos Sets run without one: the root
+/// holds the ball around every copy of the seed cluster and its orbit,
+/// see IFS::invariant_radius(), plus AUTO_FIT_MARGIN. Jitter is not
+/// accounted for. None if a transformation doesn't contract.
+fn fit_seed_radius(
+        seed_bounds: &BBox,
+        copy_xform: Option<&dyn Transform>,
+        copy_frame: CopyFrame,
+        cluster_copies: usize,
+        position_ifs: &IFS) -> Option<f32> {
+    let seed_radius = seed_bounds.origin_ball_radius() as f64;
+    // With copy_frame "centroid", copies are c + f^k(x - c) where the
+    // centroid c is within the seed radius
+    let (shift, mut radius) = match copy_frame {
+        CopyFrame::Origin => (0.0, seed_radius),
+        CopyFrame::Centroid => (seed_radius, 2.0 * seed_radius)
+    };
+    let mut copies_radius = seed_radius;
+    if let Some(xform) = copy_xform {
+        for _ in 1..cluster_copies {
+            radius = xform.reach(radius)?;
+            copies_radius = copies_radius.max(shift + radius);
+        }
+    }
+
+    let radius = position_ifs.invariant_radius(copies_radius)?;
+    Some(radius as f32 * (1.0 + AUTO_FIT_MARGIN))
+}
+
+/// The state of Chaos Sets between two iterations
+struct ChaosSetsState {
+    /// Which copy of the cluster is being iterated
+    cluster_copy: u16,
+    /// The next iteration of that copy
+    next_iteration: usize,
+    /// The current buffer, before the post-transforms
+    buffer: Vec<InternalPoint>,
 }
 
 impl ChaosSets {
 
-    /// Apply the position/color IFS to a buffer, and produce a new buffer
+    /// Apply the position/color IFS to a buffer in place. Only the
+    /// position, color, and bookkeeping fields change, the rest of each
+    /// point is left alone.
     pub fn transform_cluster(
-            &mut self, points: Vec<InternalPoint>, iteration: u64
-            ) -> Vec<InternalPoint> {
+            &mut self, points: &mut [InternalPoint], iteration: u64) {
+        // All positions are jittered before any colors so the random
+        // numbers are drawn in the same order as jittering each list
+        // separately
+        let position_xform = self.position_ifs.choose_xform();
+        for point in points.iter_mut() {
+            point.position = position_xform.transform(&point.position);
+            self.jitter.jitter_position_in_place(&mut point.position);
+        }
 
-        let old_positions = points.iter().map(|x| x.position.clone()).collect();
-        let old_colors = points.iter().map(|x| x.color.clone()).collect();
-        let new_positions = 
-            self.position_ifs.transform_points(&old_positions);
-        let new_colors = self.color_ifs.transform_points(&old_colors);
+        let color_xform = self.color_ifs.choose_xform();
+        for point in points.iter_mut() {
+            point.color = color_xform.transform(&point.color);
+            self.jitter.jitter_color_in_place(&mut point.color);
+        }
 
         let last_xform = self.position_ifs.get_last_xform();
+        let last_xform_group = self.position_ifs.get_last_xform_group();
         let last_color_xform = self.color_ifs.get_last_xform();
-
-        points.iter().enumerate().map(|(i, point)| InternalPoint {
-            position: new_positions[i].clone(),
-            color: new_colors[i].clone(),
-            cluster_coordinates: point.cluster_coordinates.clone(),
-            iteration,
-            cluster_copy: point.cluster_copy,
-            cluster_id: point.cluster_id,
-            point_id: point.point_id,
-            last_xform,
-            last_color_xform
-        }).collect()
+        let xform_address = self.position_ifs.get_xform_address();
+        for point in points.iter_mut() {
+            point.iteration = iteration;
+            point.last_xform = last_xform;
+            point.last_xform_group = last_xform_group;
+            point.last_color_xform = last_color_xform;
+            point.xform_address = xform_address;
+        }
     }
 
     /// Parse a Chaos Sets instance from JSON of the form:
@@ -185,27 +625,135 @@ impl ChaosSets {
     ///     "ifs": <IFS JSON>,
     ///     "color_ifs": <IFS JSON>,
     ///     "plotter": <Plotter JSON>,
-    ///     "iters": M
+    ///     "iters": M,
+    ///     "condensation": { "every": k } (optional),
+    ///     "jitter": <Jitter JSON> (optional),
+    ///     "trail": <Trail JSON> (optional),
+    ///     "copy_xform": <Transform JSON> (optional),
+    ///     "copy_frame": "origin" | "centroid" (default "origin"),
+    ///     "copy_palette": ["#rrggbb", ...] (optional),
+    ///     "split_subclusters": bool (default false),
+    ///     "checkpoint_every": K (optional),
+    ///     "progress_interval": P (default 100000),
+    ///     "timeout": seconds (optional),
+    ///     "warn_color_ifs": bool (default true),
+    ///     "seed": n (optional)
     /// }
     /// ```
+    ///
+    /// If the plotter has no radius, the root is sized to hold the seed
+    /// cluster, its copies and their orbits, see fit_seed_radius(). This
+    /// needs every transformation of the IFS to contract.
+    ///
+    /// With condensation, a fresh copy of the cluster is added to the
+    /// buffer at every k-th iteration, before it is transformed. Only the
+    /// initial copy is plotted untransformed, each later one is plotted at
+    /// a larger scale than the ones before it and the buffer grows by one
+    /// cluster for each, see condense().
+    ///
+    /// With copy_xform, copy k of the cluster starts out transformed by
+    /// copy_xform k times, e.g. a rotation fans the copies out. With
+    /// copy_frame "centroid", the transformation is conjugated by a
+    /// translation to the centroid of the copy's points, so copies are
+    /// rotated or scaled in place rather than about the origin.
+    ///
+    /// With copy_palette, copy k of the cluster starts out with the solid
+    /// color palette[k % len] instead of the cluster's colors, so the
+    /// copies can be told apart without a color IFS.
+    ///
+    /// With trail, each point's color is dimmed by its age relative to the
+    /// last iteration M - 1, see Trail. The buffer keeps its full colors.
+    ///
+    /// With split_subclusters, each subcluster of a "many" cluster is
+    /// plotted and saved as its own tileset under one parent tileset, see
+    /// SplitPlotter. The plotter must be a scatter plot.
+    ///
+    /// With checkpoint_every, the buffer and plotted points are written to
+    /// ./viewer/{id}.ckpt every K iterations, counting the iterations of
+    /// all the cluster copies, see resume().
+    ///
+    /// progress_interval, timeout, warn_color_ifs and seed work the same as
+    /// for the Chaos Game.
     pub fn from_json(json: &JsonValue) -> Self {
+        random::reseed(random::seed_from_json(&json["seed"]));
         let position_ifs = ifs::from_json(&json["ifs"]);
         let color_ifs = ifs::from_json(&json["color_ifs"]);
         let cluster = clusters::from_json(&json["cluster"]);
-        let plotter = plotters::from_json(&json["plotter"]);
         let cluster_copies: usize = json["cluster_copies"]
             .as_usize()
             .expect("initial_copies must be a positive integer");
+        check_id_count(
+            cluster_copies, u16::MAX as usize, "cluster copies",
+            "cluster_copy");
+        let copy_xform = match &json["copy_xform"] {
+            JsonValue::Null => None,
+            xform_json => Some(xforms::from_json(xform_json))
+        };
+        let copy_frame = CopyFrame::from_json(&json["copy_frame"]);
+
+        let seed_bounds = cluster.bounding_box();
+        let mut plotter_json = json["plotter"].clone();
+        if plotter_json["radius"].is_null() {
+            let radius = fit_seed_radius(
+                &seed_bounds,
+                copy_xform.as_deref(),
+                copy_frame,
+                cluster_copies,
+                &position_ifs);
+            if let Some(radius) = radius {
+                info!("Sizing the root to fit the seed, radius {}", radius);
+                plotter_json["radius"] = radius.into();
+            }
+        }
+        let split_subclusters =
+            json["split_subclusters"].as_bool().unwrap_or(false);
+        let plotter = if split_subclusters {
+            SplitPlotter::from_json(
+                &plotter_json, cluster.subcluster_count()).to_box()
+        } else {
+            plotters::from_json(&plotter_json)
+        };
         let num_iters = json["iters"]
             .as_usize()
             .expect("iters must be a positive integer");
+        let condensation_period = parse_condensation(&json["condensation"]);
+        let jitter = Jitter::from_json(&json["jitter"]);
+        let last_iteration = num_iters.saturating_sub(1) as u64;
+        let trail = Trail::from_json(&json["trail"], last_iteration);
+        let checkpoint_every =
+            parse_period(&json["checkpoint_every"], "checkpoint_every");
+        let progress_interval =
+            parse_progress_interval(&json["progress_interval"]);
+        let copy_palette = parse_copy_palette(&json["copy_palette"]);
+        let timeout = Timeout::from_json(&json["timeout"]);
+
+        // Check the initial set up front rather than discovering after
+        // the run that points were discarded
+        let fits = plotter.grows_to_fit() ||
+            plotter.bounds().contains_box(&seed_bounds);
+        if !fits {
+            info!(
+                "Warning: the cluster {:?} extends outside the plotter {:?}. \
+                Some points will be discarded. Consider increasing the radius",
+                seed_bounds,
+                plotter.bounds());
+        }
+
+        check_id_count(
+            cluster.point_count(), u32::MAX as usize, "cluster points",
+            "point_id");
+        check_id_count(
+            cluster.subcluster_count(), u8::MAX as usize, "subclusters",
+            "subcluster_count");
         let mut metadata = FractalMetadata::from_json(json);
-        metadata.cluster_point_count = cluster.point_count() as u16;
+        metadata.cluster_point_count = cluster.point_count() as u32;
         metadata.subcluster_max_point_count = 
-            cluster.subcluster_max_point_count() as u16;
+            cluster.subcluster_max_point_count() as u32;
         metadata.subcluster_count = cluster.subcluster_count() as u8;
+        metadata.set_ifs(&position_ifs, &color_ifs);
+        check_color_ifs(&metadata, &json["warn_color_ifs"]);
 
-        Self {
+        let result = Self {
             metadata,
             position_ifs,
             color_ifs,
@@ -213,13 +761,137 @@ impl ChaosSets {
             cluster_copies,
             output: plotter,
             num_iters,
-        }
+            condensation_period,
+            jitter,
+            trail,
+            copy_xform,
+            copy_frame,
+            copy_palette,
+            checkpoint_every,
+            progress_interval,
+            timeout,
+            resume_state: None,
+            stream_buffer: None,
+        };
+        // Every intermediate copy of the cluster is plotted, not just the
+        // attractor, so the points can fill space even when the IFS
+        // contracts everything to a point. Only the octree size applies.
+        check_capacity(result.complexity(), 3.0, result.output.as_ref());
+        result
     }
 
     to_box!(Algorithm);
 
-    /// Iterate a single cluster
-    fn iterate_cluster(&mut self, cluster_copy: u16) {
+    /// Generate the initial points of a copy of the cluster, moved by
+    /// copy_xform once per copy number and recolored by copy_palette
+    fn generate_copy(&mut self, cluster_copy: u16) -> Vec<InternalPoint> {
+        let mut points = self.cluster.generate(cluster_copy, 0);
+        if let Some(palette) = &self.copy_palette {
+            let color = palette[cluster_copy as usize % palette.len()];
+            let color = HalfMultivector::from_vec3(&color);
+            for point in points.iter_mut() {
+                point.color = color.clone();
+            }
+        }
+
+        let copy_xform = match &self.copy_xform {
+            Some(xform) if cluster_copy > 0 => xform,
+            _ => return points
+        };
+
+        let centroid = match self.copy_frame {
+            CopyFrame::Origin => None,
+            CopyFrame::Centroid => Some(clusters::centroid(&points))
+        };
+        for point in points.iter_mut() {
+            let mut position = match &centroid {
+                Some(center) => translate(&point.position, &-*center),
+                None => point.position.clone()
+            };
+            for _ in 0..cluster_copy {
+                position = copy_xform.transform(&position);
+            }
+            point.position = match &centroid {
+                Some(center) => translate(&position, center),
+                None => position
+            };
+        }
+        points
+    }
+
+    /// For condensation sets, add a freshly generated copy of the cluster
+    /// to the buffer at every k-th iteration, before the buffer is
+    /// transformed. The initial buffer already plotted the seed itself,
+    /// so the fresh copy is only plotted once it has been transformed.
+    fn condense(
+            &mut self,
+            cluster_copy: u16,
+            iteration: usize,
+            buffer: &mut Vec<InternalPoint>) {
+        let period = match self.condensation_period {
+            Some(period) => period,
+            None => return
+        };
+
+        if iteration == 0 || !iteration.is_multiple_of(period) {
+            return;
+        }
+
+        buffer.extend(self.generate_copy(cluster_copy));
+    }
+
+    /// Plot a buffer of points, applying the IFS post-transforms and the
+    /// trail if there are any. The buffer itself is left alone so the
+    /// post-transforms don't feed back into the iteration.
+    fn plot_buffer(&mut self, buffer: &[InternalPoint]) {
+        let framed: Vec<InternalPoint>;
+        let points = if self.position_ifs.has_post_transform() ||
+                self.color_ifs.has_post_transform() ||
+                self.trail.is_some() {
+            framed = buffer
+                .iter()
+                .map(|point| self.frame_point(point))
+                .collect();
+            &framed[..]
+        } else {
+            buffer
+        };
+
+        if let Some(stream) = &mut self.stream_buffer {
+            // Points at infinity have no position to stream
+            let finite = points
+                .iter()
+                .filter(|point| point.position.try_to_vec3().is_some())
+                .map(|point| OutputPoint::from(point.clone()));
+            stream.extend(finite);
+            return;
+        }
+
+        self.output.plot_points(points);
+    }
+
+    /// Apply the post-transforms and the trail to a single point
+    fn frame_point(&self, point: &InternalPoint) -> InternalPoint {
+        let mut color = self.color_ifs.post_transform(&point.color);
+        if let Some(trail) = &self.trail {
+            color = trail.fade(&color, point.iteration);
+        }
+        InternalPoint {
+            position: self.position_ifs.post_transform(&point.position),
+            color,
+            ..point.clone()
+        }
+    }
+
+    /// Iterate a single cluster. If resuming, the buffer and iteration
+    /// come from the checkpoint instead of a fresh copy of the cluster.
+    /// Returns false if the timeout ran out, so no more clusters should be
+    /// iterated
+    fn iterate_cluster(
+            &mut self,
+            cluster_copy: u16,
+            resume: Option<ChaosSetsState>,
+            progress: &mut Progress) -> bool {
         // Some IFS choosers are stateful, so reset the state to ensure
         // each cluster gets a unique path
         // NOTE: for the future: this is not thread-safe. If I want to
@@ -227,26 +899,193 @@ impl ChaosSets {
         self.position_ifs.reset();
         self.color_ifs.reset();
 
-        let mut buffer = self.cluster.generate(cluster_copy, 0);
-        self.output.plot_points(&buffer);
+        let (start, mut buffer) = match resume {
+            Some(state) => (state.next_iteration, state.buffer),
+            None => {
+                let buffer = self.generate_copy(cluster_copy);
+                self.plot_buffer(&buffer);
+                (0, buffer)
+            }
+        };
+
+        for i in start..self.num_iters {
+            self.condense(cluster_copy, i, &mut buffer);
+            self.transform_cluster(&mut buffer, i as u64);
+            self.plot_buffer(&buffer);
 
-        for i in 0..self.num_iters {
-            let new_buffer = self.transform_cluster(buffer, i as u64);
-            self.output.plot_points(&new_buffer);
-            buffer = new_buffer;
+            let total_iters = cluster_copy as usize * self.num_iters + i;
+            progress.update(total_iters + 1, self.output.counts().total());
+
+            let is_last = total_iters + 1 == self.cluster_copies * self.num_iters;
+            if !is_last && is_period_end(self.checkpoint_every, total_iters) {
+                progress.interrupt();
+                self.write_checkpoint(cluster_copy, i + 1, &buffer);
+            }
+
+            if let Some(timeout) = &self.timeout {
+                if timeout.is_expired(total_iters) {
+                    progress.interrupt();
+                    info!(
+                        "Timeout after {:.1}s, {} of {} iterations completed",
+                        timeout.elapsed().as_secs_f64(),
+                        total_iters + 1,
+                        self.cluster_copies * self.num_iters);
+                    return false;
+                }
+            }
         }
+        true
+    }
+
+    /// Iterate each copy of the cluster in turn. If a callback is given,
+    /// it's called with the points of each copy once the copy is done.
+    fn iterate_copies(&mut self, mut on_copy_complete: Option<CopyCallback>) {
+        let mut resume = self.resume_state.take();
+        let first_copy = resume
+            .as_ref()
+            .map(|state| state.cluster_copy as usize)
+            .unwrap_or(0);
+        let first_iteration = first_copy * self.num_iters + resume
+            .as_ref()
+            .map(|state| state.next_iteration)
+            .unwrap_or(0);
+        let mut progress = Progress::new(
+            self.cluster_copies * self.num_iters,
+            first_iteration,
+            self.progress_interval);
+        if let Some(timeout) = &mut self.timeout {
+            timeout.start();
+        }
+        for i in first_copy..self.cluster_copies {
+            let finished =
+                self.iterate_cluster(i as u16, resume.take(), &mut progress);
+            if let Some(callback) = on_copy_complete.as_mut() {
+                let points = self.stream_buffer.as_mut().unwrap();
+                callback(i as u16, points);
+                points.clear();
+            }
+            if !finished {
+                break;
+            }
+        }
+        progress.finish();
+    }
+
+    /// Like iterate(), but rather than plotting, the points of each copy of
+    /// the cluster are passed to on_copy_complete once that copy has been
+    /// iterated, after the post-transforms. Points at infinity are dropped.
+    /// Only one copy is held in memory at a time, so this is for streaming
+    /// very large multi-copy renders to some other output. The plotter is
+    /// left empty, so there is nothing to save() afterwards. If the timeout
+    /// runs out, the callback still gets the partial copy.
+    pub fn iterate_streaming(
+            &mut self, on_copy_complete: CopyCallback) {
+        if self.checkpoint_every.is_some() {
+            panic!("checkpoint_every is not supported when streaming, the \
+                streamed points would not be in the checkpoint");
+        }
+
+        self.stream_buffer = Some(Vec::new());
+        self.iterate_copies(Some(on_copy_complete));
+        self.stream_buffer = None;
+    }
+
+    /// Write the state before the given iteration of a cluster copy to
+    /// the checkpoint file
+    fn write_checkpoint(
+            &self,
+            cluster_copy: u16,
+            next_iteration: usize,
+            buffer: &[InternalPoint]) {
+        let fname = checkpoint_path(&self.metadata.id);
+        let mut writer =
+            CheckpointWriter::create(&fname, CheckpointKind::ChaosSets);
+        writer.write_u16(cluster_copy);
+        writer.write_usize(next_iteration);
+        writer.write_usize(buffer.len());
+        for point in buffer.iter() {
+            writer.write_internal_point(point);
+        }
+        self.output.write_checkpoint(&mut writer);
+        writer.finish();
+        info!(
+            "Wrote checkpoint {} after {} iterations of cluster copy {}",
+            fname,
+            next_iteration,
+            cluster_copy);
     }
 }
 
 impl Algorithm for ChaosSets {
     fn iterate(&mut self) {
-        for i in 0..self.cluster_copies {
-            self.iterate_cluster(i as u16);
-        }
+        self.iterate_copies(None);
     }
 
     fn save(&mut self) {
         self.output.save(&self.metadata.id, &self.metadata);
+        if self.checkpoint_every.is_some() {
+            remove_checkpoint(&self.metadata.id);
+        }
+    }
+
+    fn enable_profiling(&mut self) {
+        self.output.enable_profiling();
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        self.output.record_profile(profile);
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        self.output.quality(metric)
+    }
+
+    fn plot_counts(&self) -> PlotCounts {
+        self.output.counts()
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.output.leaf_sizes()
+    }
+
+    fn plotter_bounds(&self) -> BBox {
+        self.output.bounds().clone()
+    }
+
+    fn metadata_json(&self) -> JsonValue {
+        describe_metadata(&self.metadata, self.output.as_ref())
+    }
+
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.output.into_points()
+    }
+
+    fn resume(&mut self, fname: &str) {
+        let mut reader = CheckpointReader::open(fname, CheckpointKind::ChaosSets);
+        let cluster_copy = reader.read_u16();
+        let next_iteration = reader.read_usize();
+        if cluster_copy as usize >= self.cluster_copies || 
+                next_iteration > self.num_iters {
+            panic!(
+                "{} is past the end of the {} cluster copies of {} iterations",
+                fname, self.cluster_copies, self.num_iters);
+        }
+        let buffer_len = reader.read_usize();
+        let buffer: Vec<InternalPoint> = (0..buffer_len)
+            .map(|_| reader.read_internal_point())
+            .collect();
+        self.output.read_checkpoint(&mut reader);
+        info!(
+            "Resuming {} after {} iterations of cluster copy {}",
+            fname,
+            next_iteration,
+            cluster_copy);
+
+        self.resume_state = Some(ChaosSetsState {
+            cluster_copy,
+            next_iteration,
+            buffer
+        });
     }
 
     /// Complexity in this case is O(m * n * p) where m is the points each 
@@ -256,9 +1095,279 @@ impl Algorithm for ChaosSets {
         let points_per_buf = self.cluster.point_count();
         let points_per_iter = points_per_buf * self.cluster_copies;
        
-        // Add in the size of a single buffer to account for the 0-th
-        // iteration.
-        points_per_iter * self.num_iters + points_per_buf
+        // Condensation sets add a fresh cluster at every k-th iteration,
+        // and it is plotted at every iteration from then on
+        let condensation_iters = match self.condensation_period {
+            Some(period) => {
+                let full_periods = self.num_iters / period;
+                let tail = self.num_iters - full_periods * period;
+                period * full_periods * full_periods.saturating_sub(1) / 2 +
+                    full_periods * tail
+            },
+            None => 0
+        };
+       
+        // Each copy also plots its initial buffer before the first
+        // iteration
+        points_per_iter * (self.num_iters + condensation_iters + 1)
+    }
+}
+
+/// Before iterating, warn if the algorithm will produce more points than
+/// the plotter can hold when the points lie on a set of the given
+/// dimension. A low-dimensional attractor fills only a small fraction of
+/// the octree leaves. Past that point the extra iterations only cost time,
+/// as the plotter discards the points.
+fn check_capacity(complexity: usize, dimension: f64, plotter: &dyn Plotter) {
+    let capacity = plotter.capacity(dimension);
+    if complexity > capacity {
+        info!(
+            "Warning: about {} points will be generated, but the plotter can \
+            only hold about {} points on an attractor of dimension {:.2}. \
+            The rest will be discarded. Consider reducing iters (or \
+            cluster_copies), or increasing node_capacity or max_depth",
+            complexity,
+            capacity,
+            dimension);
+    }
+}
+
+/// The color IFS is chosen independently of the IFS, so its size does
+/// not have to match. Still, a color IFS with at least this many times
+/// more or fewer transformations is usually a mistake, like a palette
+/// from a different parameter file
+const COLOR_IFS_MISMATCH_RATIO: usize = 3;
+
+/// Check if the color IFS size looks like a mistake, see
+/// COLOR_IFS_MISMATCH_RATIO. A color IFS with a single transformation is
+/// a solid color, which is fine for any IFS.
+fn is_color_ifs_mismatch(ifs_xform_count: usize, color_xform_count: usize)
+        -> bool {
+    if color_xform_count <= 1 {
+        return false;
+    }
+    let smaller = ifs_xform_count.min(color_xform_count).max(1);
+    let larger = ifs_xform_count.max(color_xform_count);
+    larger >= COLOR_IFS_MISMATCH_RATIO * smaller
+}
+
+/// Before iterating, warn if the color IFS has a very different number of
+/// transformations than the IFS. warn_json is the optional
+/// warn_color_ifs flag, which turns off the warning when false.
+fn check_color_ifs(metadata: &FractalMetadata, warn_json: &JsonValue) {
+    let warn = match warn_json {
+        JsonValue::Null => true,
+        _ => warn_json.as_bool().expect("warn_color_ifs must be a boolean")
+    };
+    let ifs_xform_count = metadata.ifs_xform_count as usize;
+    let color_xform_count = metadata.color_ifs_xform_count as usize;
+    if warn && is_color_ifs_mismatch(ifs_xform_count, color_xform_count) {
+        info!(
+            "Warning: the IFS has {} transformations but the color IFS has \
+            {}. Check that the color_ifs belongs to this fractal, or set \
+            warn_color_ifs to false",
+            ifs_xform_count,
+            color_xform_count);
+    }
+}
+
+/// Parse the condensation settings from JSON of the form:
+///
+/// ```text
+/// {
+///     "every": k
+/// }
+/// ```
+///
+/// This returns the period k, or None if the JSON is null
+fn parse_condensation(json: &JsonValue) -> Option<usize> {
+    match json {
+        JsonValue::Null => None,
+        JsonValue::Object(_) => {
+            let period = json["every"]
+                .as_usize()
+                .expect("condensation.every must be a positive integer");
+            if period == 0 {
+                panic!("condensation.every must be a positive integer");
+            }
+            Some(period)
+        },
+        _ => panic!("condensation must be an object or null")
+    }
+}
+
+/// Translate a point by an offset
+fn translate(point: &HalfMultivector, offset: &Vec3) -> HalfMultivector {
+    let (x, y, z) = point.coordinates();
+    HalfMultivector::point(
+        x + *offset.x() as f64,
+        y + *offset.y() as f64,
+        z + *offset.z() as f64)
+}
+
+/// Parse a number of iterations between events like checkpoint_every, or
+/// None if the JSON is null. name is the parameter for error messages.
+fn parse_period(json: &JsonValue, name: &str) -> Option<usize> {
+    if json.is_null() {
+        return None;
+    }
+
+    match json.as_usize() {
+        Some(period) if period > 0 => Some(period),
+        _ => panic!("{} must be a positive integer", name)
+    }
+}
+
+/// Check if a periodic event (e.g. a checkpoint) is due after the given
+/// iteration (counting from 0)
+fn is_period_end(period: Option<usize>, iteration: usize) -> bool {
+    match period {
+        Some(period) => (iteration + 1).is_multiple_of(period),
+        None => false
+    }
+}
+
+/// Checkpoints are written next to the tileset as ./viewer/{id}.ckpt
+fn checkpoint_path(id: &str) -> String {
+    fs::create_dir_all("./viewer").expect("could not create directory");
+    format!("./viewer/{}.ckpt", id)
+}
+
+/// Once the tileset is saved, the checkpoint is no longer needed
+fn remove_checkpoint(id: &str) {
+    let fname = format!("./viewer/{}.ckpt", id);
+    if Path::new(&fname).exists() {
+        fs::remove_file(&fname).unwrap_or_else(
+            |err| panic!("Could not remove checkpoint {}: {}", fname, err));
+    }
+}
+
+/// How much larger than the explored bounds the auto_fit radius is, as a
+/// fraction of the radius
+const AUTO_FIT_MARGIN: f32 = 0.05;
+
+/// Two passes for a fractal of unknown extent, from "auto_fit": true in
+/// the plotter JSON. The first pass iterates with a BoundsPlotter, which
+/// only tracks the bounding box of the points, so nothing is discarded and
+/// hardly any memory is used. The second pass builds the real plotter with
+/// a radius that fits that box, then iterates again. This doubles the
+/// iteration time.
+///
+/// The root of the octree stays centered at the origin, so radial_clip,
+/// infinity and color_by_position keep their meaning.
+///
+/// Both passes start from the same seed, a random one if the parameters
+/// have none, so the second pass retraces the orbit of the first and every
+/// point fits. AUTO_FIT_MARGIN only keeps the extreme points off the edge
+/// of the root.
+pub struct AutoFit {
+    /// The parameters without auto_fit
+    params: JsonValue,
+    /// The first pass until iterate() finds the radius, then the second
+    /// pass. Both make the same number of points, so complexity() is the
+    /// same either way
+    chaos: Box<dyn Algorithm>,
+    /// Whether to turn on profiling for the second pass once it is built
+    profiling: bool,
+}
+
+impl AutoFit {
+    /// Wrap the algorithm described by the parameters. The plotter radius
+    /// is optional since it is replaced.
+    pub fn from_json(json: &JsonValue) -> Self {
+        let mut params = json.clone();
+        params["plotter"].remove("auto_fit");
+        if params["seed"].is_null() {
+            params["seed"] = random::random_seed().into();
+        }
+        if !params["checkpoint_every"].is_null() {
+            panic!("auto_fit can't be used with checkpoint_every, the first \
+                pass has no octree to save");
+        }
+
+        let mut explore_params = params.clone();
+        explore_params["plotter"] = object!{"type" => "bounds"};
+        // This needs scatter plots
+        explore_params.remove("split_subclusters");
+
+        Self {
+            chaos: from_json(&explore_params),
+            params,
+            profiling: false,
+        }
+    }
+
+    to_box!(Algorithm);
+}
+
+impl Algorithm for AutoFit {
+    fn iterate(&mut self) {
+        info!("auto_fit: finding the bounds of the fractal");
+        self.chaos.iterate();
+        if self.chaos.plot_counts().accepted == 0 {
+            panic!("auto_fit: the first pass plotted no points");
+        }
+
+        let bounds = self.chaos.plotter_bounds();
+        let radius = bounds.origin_radius() * (1.0 + AUTO_FIT_MARGIN);
+        info!(
+            "auto_fit: the points fit in {:?}, using radius {}",
+            bounds, radius);
+
+        let mut params = self.params.clone();
+        params["plotter"]["radius"] = radius.into();
+        self.chaos = from_json(&params);
+        if self.profiling {
+            self.chaos.enable_profiling();
+        }
+        self.chaos.iterate();
+    }
+
+    fn save(&mut self) {
+        self.chaos.save();
+    }
+
+    /// Only counts the second pass, the first pass takes the same time but
+    /// saves no points
+    fn complexity(&self) -> usize {
+        self.chaos.complexity()
+    }
+
+    fn enable_profiling(&mut self) {
+        self.profiling = true;
+        self.chaos.enable_profiling();
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        self.chaos.record_profile(profile);
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        self.chaos.quality(metric)
+    }
+
+    fn plot_counts(&self) -> PlotCounts {
+        self.chaos.plot_counts()
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.chaos.leaf_sizes()
+    }
+
+    fn plotter_bounds(&self) -> BBox {
+        self.chaos.plotter_bounds()
+    }
+
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.chaos.into_points()
+    }
+
+    fn metadata_json(&self) -> JsonValue {
+        self.chaos.metadata_json()
+    }
+
+    fn resume(&mut self, _fname: &str) {
+        panic!("auto_fit runs can't be resumed, they don't write checkpoints");
     }
 }
 
@@ -270,7 +1379,14 @@ impl Algorithm for ChaosSets {
 ///     ...params
 /// }
 /// ```
+///
+/// If the plotter has "auto_fit": true, the algorithm is wrapped in an
+/// AutoFit so the radius is found by a first pass.
 pub fn from_json(json: &JsonValue) -> Box<dyn Algorithm> {
+    if json["plotter"]["auto_fit"].as_bool().unwrap_or(false) {
+        return AutoFit::from_json(json).to_box();
+    }
+
     let valid_algorithms: Vec<&str> = vec!["chaos", "chaos_sets"];
     let algorithm_id = &json["algorithm"]
         .as_str()
@@ -282,3 +1398,294 @@ pub fn from_json(json: &JsonValue) -> Box<dyn Algorithm> {
         _ => panic!("Algorithm must be one of, {:?}", valid_algorithms)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chaos sets with 3 copies of a grid of points off to the side of
+    /// the origin
+    fn copies_params(copy_frame: &str) -> JsonValue {
+        object!{
+            "id" => "test",
+            "name" => "Test",
+            "description" => "Rotated copies",
+            "algorithm" => "chaos_sets",
+            "iters" => 1,
+            "cluster_copies" => 3,
+            "cluster" => object!{
+                "type" => "box",
+                "center" => array![2.0, 1.0, 0.0],
+                "dims" => array![0.5, 0.25, 0.25],
+                "num_points" => 27
+            },
+            "copy_xform" => array!["rotate", 0, 0, 1, 90],
+            "copy_frame" => copy_frame,
+            "plotter" => object!{ "radius" => 4.0 },
+            "ifs" => object!{ "xforms" => array![array!["scale", 0.5]] },
+            "color_ifs" => object!{ "xforms" => array![array!["scale", 1.0]] }
+        }
+    }
+
+    /// The Chaos Game for a Sierpinski triangle with corners at (-1, 0, 0),
+    /// (1, 0, 0) and (0, 1, 0), plotted in a tree with room for every point
+    fn sierpinski_params(iters: usize) -> JsonValue {
+        let corner = |x: f64, y: f64| array![
+            "chain", array![array!["scale", 0.5], array!["translate", x, y, 0]]
+        ];
+        object!{
+            "id" => "test",
+            "name" => "Test",
+            "description" => "Sierpinski triangle",
+            "algorithm" => "chaos",
+            "iters" => iters,
+            "plotter" => object!{ "radius" => 2.0, "node_capacity" => 100000 },
+            "ifs" => object!{
+                "xforms" => array![
+                    corner(-0.5, 0.0), corner(0.5, 0.0), corner(0.0, 0.5)
+                ]
+            },
+            "color_ifs" => object!{ "xforms" => array![array!["scale", 1.0]] }
+        }
+    }
+
+    fn assert_vec_eq(actual: &Vec3, expected: (f32, f32, f32)) {
+        let (x, y, z) = expected;
+        let error = (*actual.x() - x).abs() + (*actual.y() - y).abs() +
+            (*actual.z() - z).abs();
+        assert!(error < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_color_ifs_mismatch() {
+        // A solid color is fine for any IFS
+        assert!(!is_color_ifs_mismatch(12, 1));
+        assert!(!is_color_ifs_mismatch(3, 3));
+        assert!(!is_color_ifs_mismatch(3, 5));
+        assert!(is_color_ifs_mismatch(3, 9));
+        assert!(is_color_ifs_mismatch(12, 2));
+    }
+
+    #[test]
+    fn test_chaos_sets_complexity_counts_each_copy() {
+        // Each of the 3 copies plots its initial buffer and then one
+        // iteration
+        let mut chaos = ChaosSets::from_json(&copies_params("origin"));
+        let n = chaos.cluster.point_count();
+        assert_eq!(chaos.complexity(), 3 * 2 * n);
+        chaos.iterate();
+        let counts = chaos.plot_counts();
+        assert_eq!(counts.accepted + counts.discarded, 3 * 2 * n);
+    }
+
+    #[test]
+    fn test_condensation_adds_copies_to_buffer() {
+        let mut params = copies_params("origin");
+        params.remove("copy_xform");
+        params["cluster_copies"] = 1.into();
+        params["iters"] = 4.into();
+        params["condensation"] = object!{ "every" => 2 };
+        params["plotter"]["node_capacity"] = 1000.into();
+        let mut chaos = ChaosSets::from_json(&params);
+        let n = chaos.cluster.point_count();
+        let complexity = chaos.complexity();
+        chaos.iterate();
+
+        // The initial cluster, then the buffer is scaled by 1/2 four
+        // times, with a fresh copy added before the third
+        let points = chaos.to_box().into_points();
+        assert_eq!(points.len(), 7 * n);
+        assert_eq!(complexity, 7 * n);
+        let count_at_scale = |scale: f32| {
+            points
+                .iter()
+                .filter(|point| {
+                    let x = *point.position.x() / scale;
+                    x > 1.7 && x < 2.3
+                })
+                .count()
+        };
+        assert_eq!(count_at_scale(1.0), n);
+        assert_eq!(count_at_scale(0.5), 2 * n);
+        assert_eq!(count_at_scale(0.25), 2 * n);
+        assert_eq!(count_at_scale(0.125), n);
+        assert_eq!(count_at_scale(0.0625), n);
+    }
+
+    #[test]
+    fn test_chaos_sets_fits_seed_radius() {
+        let mut params = copies_params("origin");
+        params["iters"] = 5.into();
+        params["plotter"].remove("radius");
+        let mut chaos = ChaosSets::from_json(&params);
+
+        // The seed reaches |(2.25, 1.125, 0.125)| from the origin, and the
+        // copies are rotated about it
+        let radius = chaos.plotter_bounds().origin_radius();
+        let seed_radius = 2.25f32.hypot(1.125).hypot(0.125);
+        assert!(radius >= seed_radius, "{}", radius);
+        assert!(radius < seed_radius * 1.1, "{}", radius);
+
+        chaos.iterate();
+        let counts = chaos.plot_counts();
+        assert_eq!(counts.discarded, 0, "{:?}", counts);
+    }
+
+    #[test]
+    fn test_copy_frame_centroid_keeps_centroid_fixed() {
+        let mut chaos = ChaosSets::from_json(&copies_params("centroid"));
+        let original = chaos.generate_copy(0);
+        let center = clusters::centroid(&original);
+        assert_vec_eq(&center, (2.0, 1.0, 0.0));
+
+        for copy in 1..3 {
+            let rotated = chaos.generate_copy(copy);
+            assert_vec_eq(&clusters::centroid(&rotated), (2.0, 1.0, 0.0));
+        }
+
+        // The box is longer in x, so rotating it 90 degrees in place
+        // swaps its extent in x and y
+        let rotated = chaos.generate_copy(1);
+        let max_x = rotated.iter()
+            .map(|point| point.position.coordinates().0)
+            .fold(f64::MIN, f64::max);
+        assert!((max_x - 2.125).abs() < 1e-5, "{}", max_x);
+    }
+
+    #[test]
+    fn test_copy_frame_origin_rotates_about_origin() {
+        let mut chaos = ChaosSets::from_json(&copies_params("origin"));
+        let quarter_turn = chaos.generate_copy(1);
+        assert_vec_eq(&clusters::centroid(&quarter_turn), (-1.0, 2.0, 0.0));
+        let half_turn = chaos.generate_copy(2);
+        assert_vec_eq(&clusters::centroid(&half_turn), (-2.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn test_iterate_streaming() {
+        let mut params = copies_params("origin");
+        params["iters"] = 2.into();
+        let mut chaos = ChaosSets::from_json(&params);
+        let n = chaos.cluster.point_count();
+        let mut copies = Vec::new();
+        chaos.iterate_streaming(&mut |copy, points| {
+            assert!(points.iter().all(|point| point.cluster_copy == copy));
+            copies.push((copy, points.len()));
+        });
+
+        // The initial cluster and 2 iterations of it for each copy
+        assert_eq!(copies, vec![(0, 3 * n), (1, 3 * n), (2, 3 * n)]);
+        assert_eq!(chaos.plot_counts().total(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "65536 cluster copies is too many")]
+    fn test_too_many_cluster_copies() {
+        let mut params = copies_params("origin");
+        params["cluster_copies"] = 65536.into();
+        ChaosSets::from_json(&params);
+    }
+
+    #[test]
+    fn test_skip_startup_false_plots_every_iteration() {
+        let plotted = |params: &JsonValue| {
+            let mut chaos = ChaosGame::from_json(params);
+            chaos.iterate();
+            Box::new(chaos).into_points()
+        };
+
+        let first_iteration = |points: &[OutputPoint]| {
+            points.iter().map(|point| point.iteration).min()
+        };
+
+        let mut params = sierpinski_params(500);
+        let skipped = plotted(&params);
+        assert_eq!(skipped.len(), 500);
+        assert_eq!(first_iteration(&skipped), Some(STARTUP_ITERS as u64));
+
+        params["skip_startup"] = false.into();
+        let points = plotted(&params);
+        assert_eq!(points.len(), 500);
+        assert_eq!(first_iteration(&points), Some(0));
+    }
+
+    #[test]
+    fn test_color_reset_keeps_colors_varied() {
+        let mut params = sierpinski_params(500);
+        // This collapses every color to black after a few iterations
+        params["color_ifs"] = object!{
+            "xforms" => array![array!["scale", 0.1]]
+        };
+        let brightest = |params: &JsonValue| {
+            let mut chaos = ChaosGame::from_json(params);
+            chaos.iterate();
+            Box::new(chaos)
+                .into_points()
+                .iter()
+                .map(|point| point.color.length())
+                .fold(0.0, f32::max)
+        };
+
+        assert!(brightest(&params) < 1e-6);
+        params["color_reset_every"] = 10.into();
+        assert!(brightest(&params) > 0.01);
+    }
+
+    #[test]
+    fn test_auto_fit_radius() {
+        // The corners of the triangle are far outside this radius
+        let mut params = sierpinski_params(2000);
+        params["plotter"]["radius"] = 0.25.into();
+        params["plotter"]["auto_fit"] = true.into();
+
+        let mut chaos = from_json(&params);
+        chaos.iterate();
+        let radius = chaos.plotter_bounds().origin_radius();
+        assert!(radius > 0.9 && radius < 1.0 + AUTO_FIT_MARGIN, "{}", radius);
+
+        // The second pass retraces the first, so none of the points land
+        // outside the fitted root
+        let counts = chaos.plot_counts();
+        assert_eq!(counts.accepted, 2000, "{:?}", counts);
+    }
+
+    #[test]
+    fn test_auto_stop_ignores_clipped_points() {
+        // Only a small box near the bottom corner of the triangle is
+        // plotted, so most points are masked out. None of the leaves are
+        // ever full, so this must not look like the tree is saturated.
+        let mut params = sierpinski_params(20000);
+        params["plotter"]["plot_mask"] = object!{
+            "type" => "box",
+            "center" => array![-0.9, 0.05, 0.0],
+            "dims" => array![0.2, 0.2, 0.2]
+        };
+        params["auto_stop"] = object!{ "threshold" => 0.5, "window" => 100 };
+        let mut chaos = ChaosGame::from_json(&params);
+        chaos.iterate();
+
+        let counts = chaos.plot_counts();
+        assert_eq!(counts.total(), 20000);
+        assert!(counts.discarded > 10 * counts.accepted, "{:?}", counts);
+        assert!(counts.accepted > 0);
+    }
+
+    #[test]
+    fn test_auto_stop_on_full_leaves() {
+        // With a single leaf that fills up after 10 points, nearly every
+        // point lands in a full leaf
+        let mut params = sierpinski_params(20000);
+        params["plotter"] = object!{
+            "radius" => 2.0,
+            "node_capacity" => 10,
+            "max_depth" => 0
+        };
+        params["auto_stop"] = object!{ "threshold" => 0.5, "window" => 100 };
+        let mut chaos = ChaosGame::from_json(&params);
+        chaos.iterate();
+
+        let counts = chaos.plot_counts();
+        assert_eq!(counts.accepted, 10);
+        assert!(counts.total() < 20000, "{:?}", counts);
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::VecDeque;
+
+use json::JsonValue;
+
+/// Convergence check for the chaos game. Once the attractor is "filled",
+/// nearly every new point lands in a leaf of the octree that is already
+/// full at the maximum depth, so it gets discarded. This keeps track of
+/// whether the most recent points were kept, and signals to stop once the
+/// fraction kept stays below a threshold for a whole window of points.
+/// Only points that reached the octree count, see PlotResult. Points that
+/// were clipped, masked or escaped don't say anything about saturation.
+pub struct AutoStop {
+    /// Stop when the fraction of points kept drops below this value
+    threshold: f64,
+    /// How many of the most recent points to consider
+    window: usize,
+    /// Whether each of the most recent points was kept, oldest first
+    history: VecDeque<bool>,
+    /// Number of true values in history
+    accepted_count: usize,
+}
+
+impl AutoStop {
+    pub fn new(threshold: f64, window: usize) -> Self {
+        if !(0.0..=1.0).contains(&threshold) {
+            panic!("auto_stop: threshold must be in [0, 1]");
+        }
+
+        if window == 0 {
+            panic!("auto_stop: window must be a positive integer");
+        }
+
+        Self {
+            threshold,
+            window,
+            history: VecDeque::with_capacity(window),
+            accepted_count: 0,
+        }
+    }
+
+    /// Parse from JSON of the form:
+    ///
+    /// ```text
+    /// {
+    ///     "threshold": fraction (default 0.01),
+    ///     "window": n (default 100000)
+    /// }
+    /// ```
+    ///
+    /// Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        match json {
+            JsonValue::Null => None,
+            JsonValue::Object(_) => {
+                let threshold = json["threshold"].as_f64().unwrap_or(0.01);
+                let window = json["window"].as_usize().unwrap_or(100000);
+                Some(Self::new(threshold, window))
+            },
+            _ => panic!("auto_stop must be an object or null")
+        }
+    }
+
+    /// Record whether a point was kept by the plotter. Returns true if
+    /// the algorithm should stop.
+    pub fn record(&mut self, accepted: bool) -> bool {
+        if self.history.len() == self.window {
+            let oldest = self.history.pop_front().unwrap();
+            if oldest {
+                self.accepted_count -= 1;
+            }
+        }
+
+        self.history.push_back(accepted);
+        if accepted {
+            self.accepted_count += 1;
+        }
+
+        self.history.len() == self.window
+            && self.acceptance_rate() < self.threshold
+    }
+
+    /// Fraction of the recent points that were kept
+    pub fn acceptance_rate(&self) -> f64 {
+        if self.history.is_empty() {
+            return 1.0;
+        }
+
+        self.accepted_count as f64 / self.history.len() as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_waits_for_full_window() {
+        let mut auto_stop = AutoStop::new(0.5, 4);
+        for _ in 0..3 {
+            assert!(!auto_stop.record(false));
+        }
+        assert!(auto_stop.record(false));
+    }
+
+    #[test]
+    fn test_keeps_going_while_accepting() {
+        let mut auto_stop = AutoStop::new(0.5, 4);
+        for i in 0..100 {
+            // half of the points are kept, which is not below the threshold
+            assert!(!auto_stop.record(i % 2 == 0));
+        }
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let mut auto_stop = AutoStop::new(0.3, 4);
+        for _ in 0..4 {
+            assert!(!auto_stop.record(true));
+        }
+
+        // 1/4 kept is below the threshold once the accepted points
+        // slide out of the window
+        assert!(!auto_stop.record(false));
+        assert!(!auto_stop.record(false));
+        assert!(auto_stop.record(false));
+        assert_eq!(auto_stop.acceptance_rate(), 0.25);
+    }
+}
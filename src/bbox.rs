@@ -4,12 +4,16 @@ use json::JsonValue;
 
 use crate::vector::Vec3;
 
+/// 3 bits per level must fit in a u64 Morton code
+pub const MAX_MORTON_DEPTH: u8 = 21;
+
 /**
  * Bounding box structure
  *
  * This contains two vectors, one for the minimum extent (left, front, bottom)
  * and the maximum extent (right, back, top)
  */
+#[derive(Clone)]
 pub struct BBox {
     /// Minimum coordinate (left, front, bottom)
     min: Vec3,
@@ -31,10 +35,137 @@ impl BBox {
         }
     }
 
+    /// Get the minimum corner (left, front, bottom)
+    pub fn min(&self) -> &Vec3 {
+        &self.min
+    }
+
+    /// Get the maximum corner (right, back, top)
+    pub fn max(&self) -> &Vec3 {
+        &self.max
+    }
+
+    /// Create a bounding box from its center and half-widths in each
+    /// direction
+    pub fn from_center(center: &Vec3, half_widths: &Vec3) -> Self {
+        debug_assert!(
+            center.is_finite() && half_widths.is_finite(),
+            "BBox: the center {:?} and half-widths {:?} must be finite",
+            center, half_widths);
+        Self {
+            min: *center - *half_widths,
+            max: *center + *half_widths,
+        }
+    }
+
+    /// Grow the box by padding on every side. Since contains() excludes the
+    /// max faces, a little padding keeps points that land exactly on them.
+    pub fn padded(&self, padding: f32) -> Self {
+        let pad = Vec3::new(padding, padding, padding);
+        Self {
+            min: self.min - pad,
+            max: self.max + pad,
+        }
+    }
+
+    /// Half the width of the smallest cube centered at the origin that
+    /// contains this box
+    pub fn origin_radius(&self) -> f32 {
+        [self.min, self.max]
+            .iter()
+            .flat_map(|corner| [*corner.x(), *corner.y(), *corner.z()])
+            .fold(0.0, |radius, x| radius.max(x.abs()))
+    }
+
+    /// The radius of the smallest ball centered at the origin that contains
+    /// this box
+    pub fn origin_ball_radius(&self) -> f32 {
+        let far = |min: f32, max: f32| min.abs().max(max.abs());
+        Vec3::new(
+            far(*self.min.x(), *self.max.x()),
+            far(*self.min.y(), *self.max.y()),
+            far(*self.min.z(), *self.max.z())).length()
+    }
+
+    /// Compute the smallest box that contains all the given points. If there
+    /// are no points, an empty box at the origin is returned.
+    pub fn from_points(points: &[Vec3]) -> Self {
+        let first = match points.first() {
+            Some(point) => *point,
+            None => return Self::from_center(&Vec3::zero(), &Vec3::zero())
+        };
+
+        let mut result = Self::from_center(&first, &Vec3::zero());
+        for point in points.iter().skip(1) {
+            result.expand_to_include(point);
+        }
+        result
+    }
+
+    /// Compute the smallest box that contains all the given points, or None
+    /// if there are no points. Unlike from_points(), this takes any iterator
+    /// so the points don't need to be collected first.
+    pub fn tight_bounds<'a, I>(points: I) -> Option<Self>
+            where I: IntoIterator<Item = &'a Vec3> {
+        let mut points = points.into_iter();
+        let mut result = Self::from_center(points.next()?, &Vec3::zero());
+        for point in points {
+            result.expand_to_include(point);
+        }
+        Some(result)
+    }
+
+    /// Grow this box just enough to include the given point
+    pub fn expand_to_include(&mut self, point: &Vec3) {
+        debug_assert!(
+            point.is_finite(),
+            "BBox: can't expand to include the non-finite point {:?}", point);
+        self.min = Vec3::min(&self.min, point);
+        self.max = Vec3::max(&self.max, point);
+    }
+
+    /// Double the size of this box in each direction, extending towards
+    /// the given point. This returns the larger box and the octant of it
+    /// that this box occupies, so the result is compatible with
+    /// subdivide().
+    pub fn double_toward(&self, point: &Vec3) -> (Self, usize) {
+        let (x1, x2, x_bit) = 
+            extend_toward(*point.x(), *self.min.x(), *self.max.x());
+        let (y1, y2, y_bit) = 
+            extend_toward(*point.y(), *self.min.y(), *self.max.y());
+        let (z1, z2, z_bit) = 
+            extend_toward(*point.z(), *self.min.z(), *self.max.z());
+        let octant = (z_bit << 2) | (y_bit << 1) | x_bit;
+
+        (Self::new(x1, x2, y1, y2, z1, z2), octant)
+    }
+
+    /// Compute the smallest box containing both this box and another one
+    pub fn union(&self, other: &Self) -> Self {
+        Self::from_points(&[self.min, self.max, other.min, other.max])
+    }
+
+    /// Check if two boxes have any points in common. Like contains_box(),
+    /// the bounds are inclusive on both ends, so boxes that only touch
+    /// overlap.
+    pub fn overlaps(&self, other: &Self) -> bool {
+        *self.min.x() <= *other.max.x() && *other.min.x() <= *self.max.x() &&
+        *self.min.y() <= *other.max.y() && *other.min.y() <= *self.max.y() &&
+        *self.min.z() <= *other.max.z() && *other.min.z() <= *self.max.z()
+    }
+
+    /// Check if another box is entirely inside this one. Unlike contains(),
+    /// the bounds are inclusive on both ends.
+    pub fn contains_box(&self, other: &Self) -> bool {
+        *self.min.x() <= *other.min.x() && *other.max.x() <= *self.max.x() &&
+        *self.min.y() <= *other.min.y() && *other.max.y() <= *self.max.y() &&
+        *self.min.z() <= *other.min.z() && *other.max.z() <= *self.max.z()
+    }
+
     /// The center of the box is the midpoint in each direction, that is
     /// (min + max) / 2
     pub fn center(&self) -> Vec3 {
-        (self.min + self.max).scale(0.5)
+        (self.min + self.max) * 0.5
     }
 
     /// Compute the length of the diagonal of this bounding box. This is
@@ -44,6 +175,19 @@ impl BBox {
         diagonal.length()
     }
 
+    /// The point of this box closest to the given point, found by clamping
+    /// each component to the bounds. Points inside the box map to themselves.
+    /// Like contains_box(), the bounds are inclusive on both ends.
+    pub fn closest_point(&self, point: &Vec3) -> Vec3 {
+        Vec3::min(&Vec3::max(point, &self.min), &self.max)
+    }
+
+    /// The distance from a point to the nearest point of this box, or 0 if
+    /// the point is inside.
+    pub fn distance_to(&self, point: &Vec3) -> f32 {
+        (*point - self.closest_point(point)).length()
+    }
+
     /// Format this box in JSON format as used in the Cesium 3D Tiles Spec
     pub fn to_json(&self) -> JsonValue{
         let center = self.center();
@@ -61,6 +205,17 @@ impl BBox {
         }
     }
 
+    /// Format the sphere around this box in JSON format as used in the
+    /// Cesium 3D Tiles Spec. The sphere is centered on the box and passes
+    /// through its corners, so its radius is half the diagonal.
+    pub fn to_sphere_json(&self) -> JsonValue {
+        let center = self.center();
+        let radius = 0.5 * self.diagonal_len();
+        object!{
+            "sphere" => array![*center.x(), *center.y(), *center.z(), radius]
+        }
+    }
+
     /// Subdivide this bounding box into 8 octants, evenly divided along
     /// each axis. The children bounding boxes are returned in a vector
     /// ordered by quadrant number from 0b000 to 0b111
@@ -90,6 +245,35 @@ impl BBox {
         ]
     }
 
+    /// Get the 8 corners of the box. Like subdivide(), they are ordered
+    /// by octant number 0bZYX where a 1 bit means the max in that direction
+    pub fn corners(&self) -> Vec<Vec3> {
+        (0..8).map(|i| {
+            let pick = |bit: usize, min: f32, max: f32| 
+                if i & bit == 0 { min } else { max };
+            Vec3::new(
+                pick(1, *self.min.x(), *self.max.x()),
+                pick(2, *self.min.y(), *self.max.y()),
+                pick(4, *self.min.z(), *self.max.z()))
+        }).collect()
+    }
+
+    /// Get the 12 edges of the box as pairs of corners. This is helpful
+    /// for drawing wireframes.
+    pub fn edges(&self) -> Vec<(Vec3, Vec3)> {
+        let corners = self.corners();
+        let mut edges = Vec::new();
+        // Corners that differ in exactly one bit share an edge
+        for i in 0..8 {
+            for bit in [1, 2, 4].iter() {
+                if i & bit == 0 {
+                    edges.push((corners[i], corners[i | bit]));
+                }
+            }
+        }
+        edges
+    }
+
     /// Check if a point is contained inside this box. The minimum bounds
     /// are inclusive and the maximum bounds are exclusive, much like
     /// typical range checks when indexing.
@@ -111,28 +295,302 @@ impl BBox {
         bottom <= z && z < top
     }
 
+    /// Quantize a point to a grid of 2^depth cells along each axis of this
+    /// box, and interleave the bits of the integer coordinates as
+    /// ...zyxzyx. The top 3 bits are the octant at the first level of
+    /// subdivision, the next 3 bits are the octant at the second level, and
+    /// so on, matching the order of subdivide(). Points outside the box are
+    /// clamped to the nearest cell. The cells are exact, while the centers
+    /// of subdivided boxes are rounded to f32, so a point right on a split
+    /// plane may get a different octant than find_octant().
+    ///
+    /// depth must be at most MAX_MORTON_DEPTH so the code fits in a u64.
+    pub fn morton_code(&self, vec: &Vec3, depth: u8) -> u64 {
+        assert!(
+            depth <= MAX_MORTON_DEPTH,
+            "morton_code: depth must be at most {}", MAX_MORTON_DEPTH);
+
+        let x = quantize(*vec.x(), *self.min.x(), *self.max.x(), depth);
+        let y = quantize(*vec.y(), *self.min.y(), *self.max.y(), depth);
+        let z = quantize(*vec.z(), *self.min.z(), *self.max.z(), depth);
+
+        spread_bits(z) << 2 | spread_bits(y) << 1 | spread_bits(x)
+    }
+
     /// Determine which octant a point is in. There are 8 octants, numbered
     /// from 0 to 7, but it's better to think about them in binary.
     ///
     /// 0bZYX
     ///
-    /// where Z = 1 if the z coordinate is greater than center z
-    ///       Y = 1 if the y coordinate is greater than center y
-    ///       X = 1 if the x coordinate is greater than center x
+    /// where Z = 1 if the z coordinate is at least center z
+    ///       Y = 1 if the y coordinate is at least center y
+    ///       X = 1 if the x coordinate is at least center x
+    ///
+    /// This matches the order of subdivide(). Points exactly on the center
+    /// plane go to the upper octant since contains() includes the minimum
+    /// bounds but not the maximum bounds.
     pub fn find_octant(&self, vec: &Vec3) -> usize {
         let from_center = *vec - self.center();
 
-        let x_positive = (*from_center.x() > 0.0) as usize;
-        let y_positive = (*from_center.y() > 0.0) as usize;
-        let z_positive = (*from_center.z() > 0.0) as usize;
+        let x_positive = (*from_center.x() >= 0.0) as usize;
+        let y_positive = (*from_center.y() >= 0.0) as usize;
+        let z_positive = (*from_center.z() >= 0.0) as usize;
 
         (z_positive << 2) | (y_positive << 1) | x_positive
     }
 }
 
+/// Quantize a coordinate in [min, max) to an integer in [0, 2^depth)
+fn quantize(coord: f32, min: f32, max: f32, depth: u8) -> u64 {
+    let cells = (1u64 << depth) as f64;
+    let t = (coord as f64 - min as f64) / (max as f64 - min as f64);
+    let cell = (t * cells).floor();
+    cell.clamp(0.0, cells - 1.0) as u64
+}
+
+/// Spread out the lower 21 bits of a number so there are two 0 bits between
+/// each one, e.g. 0b1011 -> 0b001000001001. This is the usual bit trick for
+/// computing Morton codes without looping over the bits.
+fn spread_bits(value: u64) -> u64 {
+    let mut x = value & 0x1f_ffff;
+    x = (x | x << 32) & 0x001f_0000_0000_ffff;
+    x = (x | x << 16) & 0x001f_0000_ff00_00ff;
+    x = (x | x << 8) & 0x100f_00f0_0f00_f00f;
+    x = (x | x << 4) & 0x10c3_0c30_c30c_30c3;
+    x = (x | x << 2) & 0x1249_2492_4924_9249;
+    x
+}
+
+/// Double the interval [min, max] towards coord. This returns the new
+/// interval and 1 if the original interval is the upper half, 0 if it is
+/// the lower half.
+fn extend_toward(coord: f32, min: f32, max: f32) -> (f32, f32, usize) {
+    let size = max - min;
+    let middle = 0.5 * (min + max);
+    if coord < middle {
+        (min - size, max, 1)
+    } else {
+        (min, max + size, 0)
+    }
+}
+
 /// Debug format: (min, max)
 impl Debug for BBox {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "(min: {:?}, max: {:?})", self.min, self.max)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_origin_ball_radius() {
+        let bbox = BBox::new(-3.0, 1.0, 0.0, 4.0, 0.0, 0.0);
+        assert_eq!(bbox.origin_ball_radius(), 5.0);
+        assert_eq!(bbox.origin_radius(), 4.0);
+    }
+
+    #[test]
+    fn test_find_octant_matches_subdivide() {
+        let bbox = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let children = bbox.subdivide();
+        for (octant, child) in children.iter().enumerate() {
+            let sign = |bit: usize| if octant & bit == 0 { -0.5 } else { 0.5 };
+            let point = Vec3::new(sign(1), sign(2), sign(4));
+            assert_eq!(bbox.find_octant(&point), octant);
+            assert!(child.contains(&point));
+        }
+    }
+
+    #[test]
+    fn test_find_octant_center_plane() {
+        // Points on the center planes must land in a child that contains
+        // them
+        let bbox = BBox::new(0.0, 2.0, 0.0, 2.0, 0.0, 2.0);
+        let children = bbox.subdivide();
+        let points = [
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(1.0, 0.5, 1.5),
+            Vec3::new(0.0, 1.0, 0.0),
+        ];
+        for point in points.iter() {
+            let octant = bbox.find_octant(point);
+            assert!(children[octant].contains(point), "{:?}", point);
+        }
+    }
+
+    #[test]
+    fn test_morton_code_matches_find_octant() {
+        let root = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let points = [
+            Vec3::new(0.3, -0.7, 0.1),
+            Vec3::new(-0.99, 0.99, -0.01),
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.126, 0.874, -0.5),
+        ];
+        let depth = 6;
+        for point in points.iter() {
+            // Descend the octree the slow way, one level at a time
+            let code = root.morton_code(point, depth);
+            let mut bounds = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+            for level in 0..depth {
+                let octant = bounds.find_octant(point);
+                let shift = 3 * (depth - 1 - level);
+                assert_eq!(((code >> shift) & 7) as usize, octant);
+                bounds = bounds.subdivide().swap_remove(octant);
+            }
+        }
+    }
+
+    #[test]
+    fn test_spread_bits() {
+        assert_eq!(spread_bits(0b1011), 0b001_000_001_001);
+        assert_eq!(spread_bits(0x1f_ffff), 0x1249_2492_4924_9249);
+    }
+
+    #[test]
+    fn test_morton_code_clamps() {
+        let bbox = BBox::new(0.0, 1.0, 0.0, 1.0, 0.0, 1.0);
+        assert_eq!(bbox.morton_code(&Vec3::new(-5.0, -5.0, -5.0), 2), 0);
+        assert_eq!(bbox.morton_code(&Vec3::new(5.0, 5.0, 5.0), 2), 0b111111);
+    }
+
+    #[test]
+    fn test_expand_to_include() {
+        let mut bbox = BBox::new(0.0, 1.0, 0.0, 1.0, 0.0, 1.0);
+        bbox.expand_to_include(&Vec3::new(-1.0, 0.5, 3.0));
+        let expected = BBox::new(-1.0, 1.0, 0.0, 1.0, 0.0, 3.0);
+        assert!(bbox.contains_box(&expected) && expected.contains_box(&bbox));
+    }
+
+    #[test]
+    fn test_double_toward() {
+        let bbox = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let (bigger, octant) = bbox.double_toward(&Vec3::new(5.0, -5.0, 0.5));
+        let expected = BBox::new(-1.0, 3.0, -3.0, 1.0, -1.0, 3.0);
+        assert!(bigger.contains_box(&expected) && expected.contains_box(&bigger));
+
+        // The original box must be exactly one of the octants
+        let child = &bigger.subdivide()[octant];
+        assert!(child.contains_box(&bbox) && bbox.contains_box(child));
+    }
+
+    #[test]
+    fn test_edges() {
+        let bbox = BBox::new(0.0, 1.0, 0.0, 2.0, 0.0, 3.0);
+        let edges = bbox.edges();
+        assert_eq!(edges.len(), 12);
+
+        // 4 edges parallel to each axis
+        let mut lengths: Vec<f32> = edges.iter()
+            .map(|(start, end)| (*end - *start).length())
+            .collect();
+        lengths.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let expected = [1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0, 3.0, 3.0, 3.0, 3.0];
+        assert_eq!(lengths, expected);
+    }
+
+    #[test]
+    fn test_padded_contains_max_faces() {
+        let bbox = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let corner = Vec3::new(1.0, 1.0, 1.0);
+        assert!(!bbox.contains(&corner));
+        let padded = bbox.padded(1e-3);
+        assert!(padded.contains(&corner));
+        assert!((*padded.min().x() + 1.001).abs() < 1e-6);
+        assert!((*padded.max().z() - 1.001).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let bbox = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let inside = BBox::new(-0.5, 0.5, -0.5, 0.5, -0.5, 0.5);
+        let touching = BBox::new(1.0, 2.0, 0.0, 1.0, 0.0, 1.0);
+        let apart = BBox::new(1.5, 2.0, -1.0, 1.0, -1.0, 1.0);
+        assert!(bbox.overlaps(&inside) && inside.overlaps(&bbox));
+        assert!(bbox.overlaps(&touching));
+        assert!(!bbox.overlaps(&apart) && !apart.overlaps(&bbox));
+    }
+
+    #[test]
+    fn test_sphere_contains_corners() {
+        let bbox = BBox::new(-1.0, 3.0, 0.0, 0.5, -2.0, 2.0);
+        let sphere = &bbox.to_sphere_json()["sphere"];
+        let center = Vec3::new(
+            sphere[0].as_f32().unwrap(),
+            sphere[1].as_f32().unwrap(),
+            sphere[2].as_f32().unwrap());
+        let radius = sphere[3].as_f32().unwrap();
+        assert!((radius - 0.5 * bbox.diagonal_len()).abs() < 1e-6);
+        for (a, b) in bbox.edges() {
+            assert!((a - center).length() <= radius * (1.0 + 1e-6));
+            assert!((b - center).length() <= radius * (1.0 + 1e-6));
+        }
+    }
+
+    #[test]
+    fn test_tight_bounds() {
+        assert!(BBox::tight_bounds(&[]).is_none());
+        let points = [
+            Vec3::new(0.5, -1.0, 2.0),
+            Vec3::new(-0.5, 1.0, 0.0),
+        ];
+        let bounds = BBox::tight_bounds(&points).unwrap();
+        let expected = BBox::new(-0.5, 0.5, -1.0, 1.0, 0.0, 2.0);
+        assert!(bounds.contains_box(&expected) && expected.contains_box(&bounds));
+    }
+
+    fn assert_vec_eq(actual: &Vec3, expected: (f32, f32, f32)) {
+        let (x, y, z) = expected;
+        let error = (*actual.x() - x).abs() + (*actual.y() - y).abs() +
+            (*actual.z() - z).abs();
+        assert!(error < 1e-5, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_closest_point_inside() {
+        let bbox = BBox::new(0.0, 2.0, 0.0, 2.0, 0.0, 2.0);
+        let point = Vec3::new(0.5, 1.0, 1.5);
+        assert_vec_eq(
+            &bbox.closest_point(&point), (*point.x(), *point.y(), *point.z()));
+        assert_eq!(bbox.distance_to(&point), 0.0);
+    }
+
+    #[test]
+    fn test_closest_point_on_faces() {
+        let bbox = BBox::new(0.0, 2.0, 0.0, 2.0, 0.0, 2.0);
+        let points = [
+            Vec3::new(0.0, 1.0, 1.0),
+            Vec3::new(1.0, 2.0, 1.0),
+            Vec3::new(2.0, 2.0, 0.0),
+        ];
+        for point in points.iter() {
+            assert_vec_eq(
+                &bbox.closest_point(point),
+                (*point.x(), *point.y(), *point.z()));
+            assert_eq!(bbox.distance_to(point), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_closest_point_outside() {
+        let bbox = BBox::new(0.0, 2.0, 0.0, 2.0, 0.0, 2.0);
+
+        // Closest to a face
+        let point = Vec3::new(1.0, 1.0, 5.0);
+        assert_vec_eq(&bbox.closest_point(&point), (1.0, 1.0, 2.0));
+        assert_eq!(bbox.distance_to(&point), 3.0);
+
+        // Closest to an edge
+        let point = Vec3::new(-3.0, 1.0, 6.0);
+        assert_vec_eq(&bbox.closest_point(&point), (0.0, 1.0, 2.0));
+        assert_eq!(bbox.distance_to(&point), 5.0);
+
+        // Closest to a corner
+        let point = Vec3::new(3.0, 4.0, -2.0);
+        assert_vec_eq(&bbox.closest_point(&point), (2.0, 2.0, 0.0));
+        assert_eq!(bbox.distance_to(&point), 3.0);
+    }
+}
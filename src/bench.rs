@@ -0,0 +1,185 @@
+//! Microbenchmarks for the transformation hot path. These measure how many
+//! transformed points per second each primitive transformation and a full
+//! IFS can produce, as a baseline for optimizing the geometric product.
+//! The octree insertion benchmarks compare the two ways of finding a
+//! point's leaf.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use json::JsonValue;
+use rand::Rng;
+
+use crate::half_multivector::HalfMultivector;
+use crate::ifs;
+use crate::octrees::OctNode;
+use crate::point::CompactPoint;
+use crate::random;
+use crate::vector::Vec3;
+use crate::xforms::{self, Transform};
+
+/// Octree depths to compare the two insertion paths at
+const INSERTION_DEPTHS: [u8; 2] = [14, 20];
+/// Leaf capacity for the insertion benchmarks. This is small so the
+/// leaves split all the way down, like for a large render
+const INSERTION_CAPACITY: usize = 16;
+
+/// How long one benchmark took
+pub struct BenchResult {
+    /// What was measured, e.g. the transformation name
+    pub name: String,
+    /// How many points were transformed
+    pub iterations: usize,
+    /// Total time for all the iterations
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    /// Throughput in millions of transformed points per second
+    pub fn millions_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        self.iterations as f64 / seconds / 1e6
+    }
+}
+
+/// One of each primitive transformation. The versor-based ones are all a
+/// single sandwich product, but rotors and translators take a fast path.
+fn primitive_xforms() -> Vec<JsonValue> {
+    vec![
+        array!["translate", 1, -2, 0.5],
+        array!["rotate", 1, 2, 3, 60],
+        array!["scale", 0.5],
+        array!["reflect", 0, 1, 0],
+        array!["rotoreflect", 1, 0, 1, 72],
+        array!["invert"],
+        array!["invert_sphere", 1, 2, 3, 2],
+        array!["sct", 0.5, -1, 2],
+        array!["poloidal", 0, 0, 1, 45],
+        array!["chain", array![
+            array!["scale", 0.5],
+            array!["rotate", 1, 1, 0, 30],
+            array!["translate", 0.5, 0, -1]
+        ]]
+    ]
+}
+
+/// Transform the same point over and over. black_box() keeps the compiler
+/// from hoisting the work out of the loop.
+fn bench_xform(xform: &dyn Transform, iterations: usize) -> BenchResult {
+    let point = HalfMultivector::point(0.3, -0.2, 0.7);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        black_box(xform.transform(black_box(&point)));
+    }
+
+    BenchResult {
+        name: xform.name(),
+        iterations,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Iterate the IFS like the chaos game does, feeding each point back in.
+/// This includes choosing the transformation.
+fn bench_ifs(ifs_json: &JsonValue, iterations: usize) -> BenchResult {
+    let mut ifs = ifs::from_json(ifs_json);
+    let mut point = HalfMultivector::point(0.3, -0.2, 0.7);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        point = ifs.transform(black_box(&point));
+    }
+    black_box(&point);
+
+    BenchResult {
+        name: "ifs".to_string(),
+        iterations,
+        elapsed: start.elapsed(),
+    }
+}
+
+/// Insert points into an empty octree, finding the octant at every level
+/// with add_point() or all at once with add_point_morton(). The points lie
+/// on a line, which is low-dimensional like most attractors, so the tree
+/// gets deep. They are generated before the timer starts.
+fn bench_insertion(max_depth: u8, morton: bool, iterations: usize)
+        -> BenchResult {
+    let mut rng = random::rng();
+    let points: Vec<CompactPoint> = (0..iterations)
+        .map(|_| {
+            let t = rng.gen_range(-1.0, 1.0);
+            CompactPoint {
+                position: Vec3::new(t, 0.5 * t, -0.25 * t),
+                color: Vec3::ones(),
+                weight: 1,
+            }
+        })
+        .collect();
+
+    let mut root = OctNode::root_node(1.0, INSERTION_CAPACITY, 0);
+    let start = Instant::now();
+    for point in points {
+        if morton {
+            black_box(root.add_point_morton(point, max_depth));
+        } else {
+            black_box(root.add_point(point, max_depth));
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let method = if morton { "morton" } else { "octant" };
+    BenchResult {
+        name: format!("octree insert, {} depth {}", method, max_depth),
+        iterations,
+        elapsed,
+    }
+}
+
+/// Time each primitive transformation and then the given IFS (the "ifs"
+/// of a parameter file), transforming iterations points for each one.
+/// Then time inserting as many points into an octree, see
+/// bench_insertion().
+pub fn run(ifs_json: &JsonValue, iterations: usize) -> Vec<BenchResult> {
+    let mut results: Vec<BenchResult> = primitive_xforms()
+        .iter()
+        .map(|desc| bench_xform(xforms::from_json(desc).as_ref(), iterations))
+        .collect();
+    results.push(bench_ifs(ifs_json, iterations));
+    for max_depth in INSERTION_DEPTHS.iter() {
+        for morton in [false, true].iter() {
+            results.push(bench_insertion(*max_depth, *morton, iterations));
+        }
+    }
+    results
+}
+
+/// Print a table of the results
+pub fn print_results(results: &[BenchResult]) {
+    println!("{:<48} {:>14}", "Benchmark", "M points/s");
+    for result in results {
+        println!(
+            "{:<48} {:>14.2}", result.name, result.millions_per_second());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run() {
+        let ifs_json = object!{
+            "xforms" => array![
+                array!["scale", 0.5],
+                array!["rotate", 0, 0, 1, 90]
+            ]
+        };
+        let results = run(&ifs_json, 100);
+        let xform_count = primitive_xforms().len();
+        assert_eq!(results.len(), xform_count + 1 + 4);
+        assert_eq!(results[0].name, "translate(1, -2, 0.5)");
+        assert_eq!(results[xform_count].name, "ifs");
+        assert_eq!(
+            results.last().unwrap().name, "octree insert, morton depth 20");
+        assert!(results.iter().all(|result| result.iterations == 100));
+    }
+}
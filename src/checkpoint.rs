@@ -0,0 +1,357 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::bbox::BBox;
+use crate::half_multivector::HalfMultivector;
+use crate::point::{InternalPoint, OutputPoint};
+use crate::vector::Vec3;
+
+/// Every checkpoint file starts with these bytes
+const MAGIC: &[u8; 4] = b"ckpt";
+/// Bump this whenever the layout of the file changes
+const VERSION: u32 = 4;
+
+/// Which algorithm wrote a checkpoint, so resuming with the wrong
+/// parameter file fails early
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CheckpointKind {
+    ChaosGame = 0,
+    ChaosSets = 1,
+}
+
+/// Write a checkpoint file. A checkpoint is a little-endian binary dump of
+/// the algorithm's iteration state followed by the plotter's points. The
+/// layout is up to the caller, the reader must read the same values in the
+/// same order.
+///
+/// The file is first written to {fname}.tmp and moved into place by
+/// finish(), so a crash while writing never leaves a truncated checkpoint.
+pub struct CheckpointWriter {
+    file: BufWriter<File>,
+    fname: String,
+    tmp_fname: String,
+}
+
+impl CheckpointWriter {
+    pub fn create(fname: &str, kind: CheckpointKind) -> Self {
+        let tmp_fname = format!("{}.tmp", fname);
+        let file = File::create(&tmp_fname).unwrap_or_else(
+            |err| panic!("Could not create checkpoint {}: {}", tmp_fname, err));
+        let mut result = Self {
+            file: BufWriter::new(file),
+            fname: fname.to_string(),
+            tmp_fname,
+        };
+        result.write_bytes(MAGIC);
+        result.write_u32(VERSION);
+        result.write_u8(kind as u8);
+        result
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.file.write_all(bytes).expect("Could not write checkpoint");
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.write_bytes(&[value]);
+    }
+
+    pub fn write_u16(&mut self, value: u16) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f64(&mut self, value: f64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    /// usize is written as a u64 so checkpoints are portable
+    pub fn write_usize(&mut self, value: usize) {
+        self.write_u64(value as u64);
+    }
+
+    pub fn write_vec3(&mut self, vec: &Vec3) {
+        self.write_f32(*vec.x());
+        self.write_f32(*vec.y());
+        self.write_f32(*vec.z());
+    }
+
+    pub fn write_bbox(&mut self, bbox: &BBox) {
+        self.write_vec3(bbox.min());
+        self.write_vec3(bbox.max());
+    }
+
+    /// Write a point in 3D space in double precision, so resuming the
+    /// iteration doesn't lose precision
+    pub fn write_multivector_point(&mut self, point: &HalfMultivector) {
+        let (x, y, z) = point.coordinates();
+        self.write_f64(x);
+        self.write_f64(y);
+        self.write_f64(z);
+    }
+
+    /// Write the metadata fields shared by both point types
+    fn write_point_metadata<T>(&mut self, point: &crate::point::Point<T>) {
+        self.write_vec3(&point.cluster_coordinates);
+        self.write_u64(point.iteration);
+        self.write_u16(point.cluster_copy);
+        self.write_u16(point.cluster_id);
+        self.write_u32(point.point_id);
+        self.write_u8(point.last_xform);
+        self.write_u8(point.last_xform_group);
+        self.write_u8(point.last_color_xform);
+        self.write_u32(point.xform_address);
+        self.write_u32(point.weight);
+    }
+
+    pub fn write_output_point(&mut self, point: &OutputPoint) {
+        self.write_vec3(&point.position);
+        self.write_vec3(&point.color);
+        self.write_point_metadata(point);
+        self.write_f32(point.cga_pn[0]);
+        self.write_f32(point.cga_pn[1]);
+    }
+
+    pub fn write_internal_point(&mut self, point: &InternalPoint) {
+        self.write_multivector_point(&point.position);
+        self.write_multivector_point(&point.color);
+        self.write_point_metadata(point);
+    }
+
+    /// Flush the file and move it into place, replacing any older
+    /// checkpoint
+    pub fn finish(self) {
+        let tmp_fname = &self.tmp_fname;
+        let fname = &self.fname;
+        let file = self.file.into_inner().unwrap_or_else(
+            |_| panic!("Could not write checkpoint {}", tmp_fname));
+        file.sync_all().expect("Could not write checkpoint");
+        fs::rename(tmp_fname, fname).unwrap_or_else(
+            |err| panic!("Could not move checkpoint to {}: {}", fname, err));
+    }
+}
+
+/// Read a checkpoint written by CheckpointWriter
+pub struct CheckpointReader {
+    file: BufReader<File>,
+    fname: String,
+}
+
+impl CheckpointReader {
+    /// Open a checkpoint and check that it was written by this version
+    /// of the program for the given algorithm
+    pub fn open(fname: &str, kind: CheckpointKind) -> Self {
+        let file = File::open(fname).unwrap_or_else(
+            |err| panic!("Could not open checkpoint {}: {}", fname, err));
+        let mut result = Self {
+            file: BufReader::new(file),
+            fname: fname.to_string(),
+        };
+
+        let mut magic = [0u8; 4];
+        result.read_bytes(&mut magic);
+        if &magic != MAGIC {
+            panic!("{} is not a checkpoint file", fname);
+        }
+        let version = result.read_u32();
+        if version != VERSION {
+            panic!(
+                "{} is a version {} checkpoint, expected version {}",
+                fname, version, VERSION);
+        }
+        let file_kind = result.read_u8();
+        if file_kind != kind as u8 {
+            panic!(
+                "{} was not written by the {:?} algorithm, check that the \
+                parameter file matches the checkpoint",
+                fname, kind);
+        }
+        result
+    }
+
+    fn read_bytes(&mut self, bytes: &mut [u8]) {
+        let fname = &self.fname;
+        self.file.read_exact(bytes).unwrap_or_else(
+            |err| panic!("Could not read checkpoint {}: {}", fname, err));
+    }
+
+    pub fn read_u8(&mut self) -> u8 {
+        let mut bytes = [0u8; 1];
+        self.read_bytes(&mut bytes);
+        bytes[0]
+    }
+
+    pub fn read_u16(&mut self) -> u16 {
+        let mut bytes = [0u8; 2];
+        self.read_bytes(&mut bytes);
+        u16::from_le_bytes(bytes)
+    }
+
+    pub fn read_u32(&mut self) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.read_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn read_u64(&mut self) -> u64 {
+        let mut bytes = [0u8; 8];
+        self.read_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    pub fn read_f32(&mut self) -> f32 {
+        let mut bytes = [0u8; 4];
+        self.read_bytes(&mut bytes);
+        f32::from_le_bytes(bytes)
+    }
+
+    pub fn read_f64(&mut self) -> f64 {
+        let mut bytes = [0u8; 8];
+        self.read_bytes(&mut bytes);
+        f64::from_le_bytes(bytes)
+    }
+
+    pub fn read_usize(&mut self) -> usize {
+        self.read_u64() as usize
+    }
+
+    pub fn read_vec3(&mut self) -> Vec3 {
+        let x = self.read_f32();
+        let y = self.read_f32();
+        let z = self.read_f32();
+        Vec3::new(x, y, z)
+    }
+
+    pub fn read_bbox(&mut self) -> BBox {
+        let min = self.read_vec3();
+        let max = self.read_vec3();
+        BBox::new(*min.x(), *max.x(), *min.y(), *max.y(), *min.z(), *max.z())
+    }
+
+    pub fn read_multivector_point(&mut self) -> HalfMultivector {
+        let x = self.read_f64();
+        let y = self.read_f64();
+        let z = self.read_f64();
+        HalfMultivector::point(x, y, z)
+    }
+
+    pub fn read_output_point(&mut self) -> OutputPoint {
+        let position = self.read_vec3();
+        let color = self.read_vec3();
+        let mut point = OutputPoint {
+            position,
+            color,
+            cluster_coordinates: self.read_vec3(),
+            iteration: self.read_u64(),
+            cluster_copy: self.read_u16(),
+            cluster_id: self.read_u16(),
+            point_id: self.read_u32(),
+            last_xform: self.read_u8(),
+            last_xform_group: self.read_u8(),
+            last_color_xform: self.read_u8(),
+            xform_address: self.read_u32(),
+            cga_pn: [0.0, 0.0],
+            weight: self.read_u32()
+        };
+        // The CGA components are written after the shared metadata
+        point.cga_pn = [self.read_f32(), self.read_f32()];
+        point
+    }
+
+    pub fn read_internal_point(&mut self) -> InternalPoint {
+        let position = self.read_multivector_point();
+        let color = self.read_multivector_point();
+        InternalPoint {
+            position,
+            color,
+            cluster_coordinates: self.read_vec3(),
+            iteration: self.read_u64(),
+            cluster_copy: self.read_u16(),
+            cluster_id: self.read_u16(),
+            point_id: self.read_u32(),
+            last_xform: self.read_u8(),
+            last_xform_group: self.read_u8(),
+            last_color_xform: self.read_u8(),
+            xform_address: self.read_u32(),
+            cga_pn: [0.0, 0.0],
+            weight: self.read_u32()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let fname = std::env::temp_dir()
+            .join(format!("chaos-game-3d-test-{}.ckpt", std::process::id()));
+        let fname = fname.to_str().unwrap();
+
+        let point = InternalPoint {
+            position: HalfMultivector::point(0.1, -2.5, 1e-9),
+            color: HalfMultivector::point(1.0, 0.5, 0.25),
+            cluster_coordinates: Vec3::new(0.5, 0.5, 0.0),
+            iteration: 1 << 40,
+            cluster_copy: 3,
+            cluster_id: 2,
+            point_id: 70000,
+            last_xform: 1,
+            last_xform_group: 0,
+            last_color_xform: 4,
+            xform_address: 21,
+            cga_pn: [0.0, 0.0],
+            weight: 5
+        };
+        let mut writer = CheckpointWriter::create(fname, CheckpointKind::ChaosSets);
+        writer.write_usize(42);
+        writer.write_bbox(&BBox::new(-1.0, 2.0, -3.0, 4.0, -5.0, 6.0));
+        writer.write_internal_point(&point);
+        writer.write_output_point(&OutputPoint::from(point.clone()));
+        writer.finish();
+
+        let mut reader = CheckpointReader::open(fname, CheckpointKind::ChaosSets);
+        assert_eq!(reader.read_usize(), 42);
+        let bbox = reader.read_bbox();
+        assert_eq!(*bbox.min().y(), -3.0);
+        assert_eq!(*bbox.max().z(), 6.0);
+        let internal = reader.read_internal_point();
+        assert!(internal.position.approx_eq(&point.position, 1e-12));
+        assert_eq!(internal.iteration, 1 << 40);
+        assert_eq!(internal.point_id, 70000);
+        let output = reader.read_output_point();
+        assert_eq!(*output.position.y(), -2.5);
+        assert_eq!(output.weight, 5);
+        assert_eq!(output.xform_address, 21);
+        assert_eq!(output.cga_pn, point.position.conformal_components());
+
+        fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "was not written by the ChaosGame algorithm")]
+    fn test_wrong_algorithm() {
+        let fname = std::env::temp_dir()
+            .join(format!("chaos-game-3d-kind-{}.ckpt", std::process::id()));
+        let fname = fname.to_str().unwrap();
+        CheckpointWriter::create(fname, CheckpointKind::ChaosSets).finish();
+        let result = std::panic::catch_unwind(
+            || CheckpointReader::open(fname, CheckpointKind::ChaosGame));
+        fs::remove_file(fname).unwrap();
+        if let Err(err) = result {
+            std::panic::resume_unwind(err);
+        }
+    }
+}
@@ -1,9 +1,12 @@
 use std::fmt::{Debug, Formatter, Result};
 
 use rand::Rng;
-use rand::prelude::ThreadRng;
 use json::JsonValue;
 
+use crate::ifs::moran_dimension;
+use crate::random::{self, RenderRng};
+use crate::xforms::Transform;
+
 pub trait Chooser: Debug {
     fn choose(&mut self) -> usize;
 
@@ -13,14 +16,14 @@ pub trait Chooser: Debug {
 }
 
 pub struct UniformChooser {
-    rng: ThreadRng,
+    rng: RenderRng,
     num_xforms: usize,
 }
 
 impl UniformChooser {
     pub fn new(n: usize) -> Self {
         Self {
-            rng: rand::thread_rng(),
+            rng: random::rng(),
             num_xforms: n
         }
     }
@@ -47,7 +50,7 @@ pub struct NoBacktrackingChooser {
     // The total number of transformations
     num_xforms: usize,
     // The random number generator
-    rng: ThreadRng,
+    rng: RenderRng,
 }
 
 impl NoBacktrackingChooser {
@@ -57,7 +60,7 @@ impl NoBacktrackingChooser {
         }
 
         Self {
-            rng: rand::thread_rng(),
+            rng: random::rng(),
             last_selection: n + 1,
             num_xforms: n
         }
@@ -125,6 +128,11 @@ impl Debug for NoBacktrackingChooser {
 /// 
 /// For increased control, there's also one extra row for initial weights.
 /// This is a probability distribution for the first transformation
+///
+/// A matrix with an absorbing state (e.g. a row with only a diagonal
+/// weight) collapses the attractor to a point. Setting max_repeats escapes
+/// this: once the same transformation has been chosen that many times in
+/// a row, the next one is drawn from the initial weights without it.
 pub struct MarkovChooser {
     /// cumulative probabilities for the initial iteration.
     /// This is useful for hinting where to start
@@ -135,8 +143,13 @@ pub struct MarkovChooser {
     num_xforms: usize,
     // The last transformation that was applied
     last_selection: usize,
+    /// If set, the most times the same transformation can be chosen in a
+    /// row
+    max_repeats: Option<usize>,
+    /// How many times in a row last_selection has been chosen
+    repeats: usize,
     // The random number generator.
-    rng: ThreadRng,
+    rng: RenderRng,
 }
 
 impl MarkovChooser {
@@ -149,7 +162,8 @@ impl MarkovChooser {
     ///       [w00, w01, ...],
     ///       [w10, w11, ...],
     ///       ...
-    ///     ]
+    ///     ],
+    ///     "max_repeats": M (optional)
     /// }
     pub fn from_json(json: &JsonValue) -> Self {
         let weights = Self::parse_weights(json);
@@ -175,13 +189,67 @@ impl MarkovChooser {
             cumulative_probabilities.push(cumulative_row);
         }
 
+        let max_repeats = match &json["max_repeats"] {
+            JsonValue::Null => None,
+            x => {
+                let max_repeats = x.as_usize()
+                    .filter(|max_repeats| *max_repeats > 0)
+                    .expect("max_repeats must be a positive integer");
+                if n < 2 {
+                    panic!("max_repeats needs at least 2 transformations");
+                }
+                Some(max_repeats)
+            }
+        };
+
         Self {
             initial_probabilities,
             cumulative_probabilities,
             num_xforms: n,
             last_selection: n + 1,
-            rng: rand::thread_rng(),
+            max_repeats,
+            repeats: 0,
+            rng: random::rng(),
+        }
+    }
+
+    /// Check if the chain has chosen the same transformation too many
+    /// times in a row
+    fn is_stuck(&self) -> bool {
+        match self.max_repeats {
+            Some(max_repeats) => self.repeats >= max_repeats,
+            None => false
+        }
+    }
+
+    /// Choose from the initial weights, but never the excluded
+    /// transformation. If the initial weights only allow the excluded one,
+    /// the others are chosen uniformly.
+    fn choose_initial_excluding(&mut self, excluded: usize) -> usize {
+        let probabilities = &self.initial_probabilities;
+        let before = if excluded == 0 { 0.0 } else { probabilities[excluded - 1] };
+        let excluded_probability = probabilities[excluded] - before;
+        let remaining = 1.0 - excluded_probability;
+
+        if remaining <= 0.0 {
+            let selection = self.rng.gen_range(0usize, self.num_xforms - 1);
+            return if selection >= excluded { selection + 1 } else { selection };
+        }
+
+        // Pick a point in the distribution with the excluded interval cut
+        // out, then shift past it
+        let mut value: f64 = self.rng.gen_range(0.0, remaining);
+        if value >= before {
+            value += excluded_probability;
+        }
+        for (i, probability) in probabilities.iter().enumerate() {
+            if i != excluded && value <= *probability {
+                return i;
+            }
         }
+
+        // Rounding error at the very end of the distribution
+        (0..self.num_xforms).rev().find(|i| *i != excluded).unwrap()
     }
 
     fn parse_weights(json: &JsonValue) -> Vec<Vec<f64>> {
@@ -222,6 +290,12 @@ impl Chooser for MarkovChooser {
     fn choose(&mut self) -> usize {
         let n = self.num_xforms;
 
+        if self.is_stuck() {
+            let selection = self.choose_initial_excluding(self.last_selection);
+            self.last_selection = selection;
+            self.repeats = 1;
+            return selection;
+        }
 
         let probabilities;
         if self.last_selection == n + 1 {
@@ -236,6 +310,11 @@ impl Chooser for MarkovChooser {
         let value: f64 = self.rng.gen_range(0.0, 1.0);
         for (i, probability) in probabilities.iter().enumerate() {
             if value <= *probability {
+                if i == self.last_selection {
+                    self.repeats += 1;
+                } else {
+                    self.repeats = 1;
+                }
                 self.last_selection = i;
                 return i;
             }
@@ -246,6 +325,7 @@ impl Chooser for MarkovChooser {
 
     fn reset(&mut self) {
         self.last_selection = self.num_xforms + 1;
+        self.repeats = 0;
     }
 }
 
@@ -258,6 +338,115 @@ impl Debug for MarkovChooser {
     }
 }
 
+/// A chooser that replays a fixed list of transformation indices, starting
+/// over when it runs out. This is handy for reproducing a specific orbit
+/// and for deterministic tests.
+pub struct SequenceChooser {
+    /// The transformation indices to choose in order
+    indices: Vec<usize>,
+    /// Position of the next choice in indices
+    next: usize,
+}
+
+impl SequenceChooser {
+    /// Parse a sequence chooser from JSON of the form:
+    ///
+    /// {
+    ///     "type": "sequence",
+    ///     "indices": [i0, i1, ...]
+    /// }
+    ///
+    /// where each index must be less than n, the number of transformations.
+    pub fn from_json(json: &JsonValue, n: usize) -> Self {
+        let indices: Vec<usize> = json["indices"].members().map(|index| {
+            index
+                .as_usize()
+                .expect("sequence: indices must be non-negative integers")
+        }).collect();
+        if indices.is_empty() {
+            panic!("sequence: indices must be a non-empty array");
+        }
+        if let Some(index) = indices.iter().find(|index| **index >= n) {
+            panic!(
+                "sequence: index {} is out of range for {} transformations",
+                index, n);
+        }
+
+        Self {
+            indices,
+            next: 0,
+        }
+    }
+}
+
+impl Chooser for SequenceChooser {
+    fn choose(&mut self) -> usize {
+        let selection = self.indices[self.next];
+        self.next = (self.next + 1) % self.indices.len();
+        selection
+    }
+
+    fn reset(&mut self) {
+        self.next = 0;
+    }
+}
+
+impl Debug for SequenceChooser {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "SequenceChooser({:?}, {})", self.indices, self.next)
+    }
+}
+
+/// A chooser that weights each transformation by how much of the attractor
+/// it covers so the points fill the attractor evenly. For a self-similar
+/// set of dimension D, a map with contraction factor r covers r^D of the
+/// attractor. When the maps fill space, D is 3 and this is proportional to
+/// the determinant, the classic choice for the chaos game.
+pub struct AutoChooser {
+    /// Cumulative probabilities of choosing each transformation
+    cumulative_probabilities: Vec<f64>,
+    rng: RenderRng,
+}
+
+impl AutoChooser {
+    /// Create the chooser from the contraction_estimate() of each
+    /// transformation
+    pub fn new(contractions: &[f64]) -> Self {
+        let dimension = moran_dimension(contractions);
+        let weights: Vec<f64> = contractions
+            .iter()
+            .map(|r| r.powf(dimension))
+            .collect();
+        if !weights.iter().any(|weight| *weight > 0.0) {
+            panic!("auto: at least one transformation must not collapse space");
+        }
+
+        Self {
+            cumulative_probabilities:
+                MarkovChooser::weights_to_cumulative_probabilities(&weights),
+            rng: random::rng(),
+        }
+    }
+}
+
+impl Chooser for AutoChooser {
+    fn choose(&mut self) -> usize {
+        let value: f64 = self.rng.gen_range(0.0, 1.0);
+        let probabilities = &self.cumulative_probabilities;
+        probabilities
+            .iter()
+            .position(|probability| value < *probability)
+            // Rounding error at the very end of the distribution
+            .unwrap_or(probabilities.len() - 1)
+    }
+}
+
+impl Debug for AutoChooser {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "AutoChooser({:?})", self.cumulative_probabilities)
+    }
+}
+
 /// Parse a transformation chooser from the IFS JSON
 /// 
 /// ```text
@@ -266,13 +455,161 @@ impl Debug for MarkovChooser {
 ///     ...params
 /// }
 /// ```
-pub fn from_json(json: &JsonValue, n: usize) -> Box<dyn Chooser> {
+///
+/// The "auto" type needs no params, see AutoChooser.
+pub fn from_json(json: &JsonValue, xforms: &[Box<dyn Transform>])
+        -> Box<dyn Chooser> {
+    let n = xforms.len();
     let chooser_type = json["type"].as_str().unwrap_or("uniform");
 
     match &chooser_type[..] {
         "uniform" => Box::new(UniformChooser::new(n)),
         "no_backtracking" => Box::new(NoBacktrackingChooser::new(n)),
         "markov" => Box::new(MarkovChooser::from_json(json)),
+        "sequence" => Box::new(SequenceChooser::from_json(json, n)),
+        "auto" => {
+            let contractions: Vec<f64> = xforms
+                .iter()
+                .map(|xform| xform.contraction_estimate())
+                .collect();
+            Box::new(AutoChooser::new(&contractions))
+        },
         _ => panic!("Invalid chooser type")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::xforms;
+
+    /// The longest run of the same transformation in a sequence
+    fn longest_run(choices: &[usize]) -> usize {
+        let mut longest = 0;
+        let mut run = 0;
+        for (i, choice) in choices.iter().enumerate() {
+            if i > 0 && choices[i - 1] == *choice {
+                run += 1;
+            } else {
+                run = 1;
+            }
+            longest = longest.max(run);
+        }
+        longest
+    }
+
+    #[test]
+    fn test_markov_max_repeats_escapes_absorbing_state() {
+        // Transformation 1 is absorbing. Once it is chosen, the chain
+        // would never leave it
+        let json = object!{
+            "type" => "markov",
+            "initial_weights" => array![1, 1, 0],
+            "weights" => array![
+                array![1, 1, 1],
+                array![0, 1, 0],
+                array![1, 1, 1]
+            ],
+            "max_repeats" => 5
+        };
+        let mut chooser = MarkovChooser::from_json(&json);
+        let choices: Vec<usize> = (0..1000).map(|_| chooser.choose()).collect();
+
+        assert!(longest_run(&choices) <= 5);
+        // The only way out of transformation 1 is the escape, so it must
+        // have fired after a run of exactly 5
+        let escaped = choices
+            .windows(6)
+            .any(|window| window[..5].iter().all(|x| *x == 1) && window[5] != 1);
+        assert!(escaped);
+    }
+
+    #[test]
+    fn test_markov_escape_avoids_only_initial_choice() {
+        // The initial weights only allow transformation 0 and it is
+        // absorbing, so the escape has to pick another one uniformly
+        let json = object!{
+            "type" => "markov",
+            "initial_weights" => array![1, 0, 0],
+            "weights" => array![
+                array![1, 0, 0],
+                array![1, 0, 0],
+                array![1, 0, 0]
+            ],
+            "max_repeats" => 3
+        };
+        let mut chooser = MarkovChooser::from_json(&json);
+        let choices: Vec<usize> = (0..100).map(|_| chooser.choose()).collect();
+        assert_eq!(&choices[0..3], &[0, 0, 0]);
+        assert_ne!(choices[3], 0);
+        assert!(longest_run(&choices) <= 3);
+    }
+
+    #[test]
+    fn test_markov_without_max_repeats_stays_stuck() {
+        let json = object!{
+            "type" => "markov",
+            "initial_weights" => array![1, 0],
+            "weights" => array![array![1, 0], array![0, 1]]
+        };
+        let mut chooser = MarkovChooser::from_json(&json);
+        assert!((0..100).all(|_| chooser.choose() == 0));
+    }
+
+    #[test]
+    fn test_sequence_cycles_and_resets() {
+        let json = object!{
+            "type" => "sequence",
+            "indices" => array![0, 1, 0, 2]
+        };
+        let xforms: Vec<Box<dyn Transform>> = (0..3)
+            .map(|_| xforms::from_json(&array!["scale", 0.5]))
+            .collect();
+        let mut chooser = from_json(&json, &xforms);
+        let choices: Vec<usize> = (0..6).map(|_| chooser.choose()).collect();
+        assert_eq!(choices, vec![0, 1, 0, 2, 0, 1]);
+
+        chooser.reset();
+        assert_eq!(chooser.choose(), 0);
+        assert_eq!(chooser.choose(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "index 3 is out of range for 3 transformations")]
+    fn test_sequence_index_out_of_range() {
+        let json = object!{
+            "type" => "sequence",
+            "indices" => array![0, 3]
+        };
+        SequenceChooser::from_json(&json, 3);
+    }
+
+    #[test]
+    fn test_auto_weights_by_attractor_coverage() {
+        // Split the unit interval into a half and two quarters. The
+        // attractor has dimension 1, so the maps cover 1/2, 1/4 and 1/4
+        // of it
+        let xforms: Vec<Box<dyn Transform>> = vec![
+            xforms::from_json(&array!["scale", 0.5]),
+            xforms::from_json(&array!["chain", array![
+                array!["scale", 0.25], array!["translate", 0.5, 0, 0]]]),
+            xforms::from_json(&array!["chain", array![
+                array!["scale", 0.25], array!["translate", 0.75, 0, 0]]])
+        ];
+        let mut chooser = from_json(&object!{"type" => "auto"}, &xforms);
+
+        let samples = 20000;
+        let mut counts = [0; 3];
+        for _ in 0..samples {
+            counts[chooser.choose()] += 1;
+        }
+        let expected = [0.5, 0.25, 0.25];
+        for (count, probability) in counts.iter().zip(expected.iter()) {
+            let frequency = *count as f64 / samples as f64;
+            assert!(
+                (frequency - probability).abs() < 0.02,
+                "{:?} is not close to {:?}", counts, expected);
+        }
+    }
+}
@@ -1,11 +1,14 @@
 use std::f64::consts::PI;
 use rand::Rng;
-use rand::prelude::ThreadRng;
 use json::JsonValue;
 
+use crate::bbox::BBox;
 use crate::vector::Vec3;
 use crate::half_multivector::HalfMultivector;
 use crate::point::InternalPoint;
+use crate::random::{self, RenderRng};
+use crate::seed_files;
+use crate::xforms::{self, Transform};
 
 /// This trait is used to arrange a set of points to represent an initial
 /// set that will be sent through a Chaos Game algorithm. Typically, this is
@@ -13,7 +16,9 @@ use crate::point::InternalPoint;
 /// box or line.
 pub trait Cluster {
     /// Generate a set of points. This may be called several times, and each
-    /// time it must produce a new set of points.
+    /// time it must produce a new set of points. Every component of the
+    /// cluster coordinates must be in [0, 1] so shaders can style any
+    /// cluster type the same way.
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
         -> Vec<InternalPoint>;
     /// How many subclusters. normally 1, but for ManyClusters this is the
@@ -28,17 +33,318 @@ pub trait Cluster {
     fn subcluster_max_point_count(&self) -> usize {
         self.point_count()
     }
+    /// Get the axis-aligned bounding box of the points this cluster will
+    /// generate without generating them. This must be conservative, i.e.
+    /// every generated point must be inside the box. If the extent is not
+    /// known, a generous box is returned.
+    fn bounding_box(&self) -> BBox {
+        unknown_bounds()
+    }
+}
+
+/// Half-width of the box returned by Cluster::bounding_box() when the
+/// extent of a cluster is not known
+const UNKNOWN_BOUNDS_RADIUS: f32 = 1000.0;
+
+/// The generous box for clusters with an unknown extent, see
+/// UNKNOWN_BOUNDS_RADIUS
+fn unknown_bounds() -> BBox {
+    let half_width = UNKNOWN_BOUNDS_RADIUS;
+    BBox::new(
+        -half_width, half_width,
+        -half_width, half_width,
+        -half_width, half_width)
+}
+
+/// Compute the bounding box of an ellipse (or the disk inside it) with the
+/// given center and semi-axes. Along each coordinate axis, the extent from
+/// the center is sqrt(a_i^2 + b_i^2)
+fn ellipse_bounding_box(center: &Vec3, a: &Vec3, b: &Vec3) -> BBox {
+    let squared = *a * *a + *b * *b;
+    let half_widths = Vec3::new(
+        squared.x().sqrt(),
+        squared.y().sqrt(),
+        squared.z().sqrt());
+    BBox::from_center(center, &half_widths)
+}
+
+/// Parse a count of points like num_points. A cluster with no points would
+/// leave the octree bounds and radius: auto with nothing to measure, so 0
+/// is rejected here rather than failing confusingly later.
+fn parse_count(json: &JsonValue, key: &str, cluster_name: &str) -> usize {
+    match json[key].as_usize() {
+        Some(count) if count > 0 => count,
+        _ => panic!("{}: {} must be a positive integer", cluster_name, key)
+    }
+}
+
+/// Parse the side_points of a triangle or tetrahedron grid. The grid has a
+/// point at every corner, so a single point per side would leave no
+/// spacing between them.
+fn parse_side_points(json: &JsonValue, cluster_name: &str) -> usize {
+    match json["side_points"].as_usize() {
+        Some(count) if count >= 2 => count,
+        _ => panic!(
+            "{}: side_points must be an integer of at least 2", cluster_name)
+    }
+}
+
+/// Parse the radius of a round cluster, default 1. A radius of 0 puts every
+/// point at the center, which is allowed but almost always a mistake.
+fn parse_radius(json: &JsonValue, cluster_name: &str) -> f64 {
+    let radius = json["radius"].as_f64().unwrap_or(1.0);
+    if radius == 0.0 {
+        info!(
+            "Warning: {}: radius is 0, so every point is at the center",
+            cluster_name);
+    }
+    radius
+}
+
+/// Fibonacci lattices spread n points evenly over a shape, but a single
+/// point covers nothing, so at least 2 are needed
+fn check_fibonacci_count(num_points: usize, cluster_name: &str) {
+    if num_points < 2 {
+        panic!(
+            "{}: the fibonacci distribution needs num_points of at least 2",
+            cluster_name);
+    }
+}
+
+/// How the points of a shape cluster are spread over the shape, set with
+/// "distribution": "grid" | "random" | "fibonacci" in the cluster JSON.
+/// Each shape supports the distributions that make sense for it, and the
+/// first one it lists is the default.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Distribution {
+    /// Evenly spaced points. The spacing depends on the shape, so each
+    /// shape lays out its own grid
+    Grid,
+    /// Uniformly random points, different each time the cluster generates
+    Random,
+    /// Points placed by the golden ratio. These are spread out almost as
+    /// evenly as a grid, but without rows that line up
+    Fibonacci,
+}
+
+impl Distribution {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Grid => "grid",
+            Self::Random => "random",
+            Self::Fibonacci => "fibonacci",
+        }
+    }
+}
+
+/// Which part of a solid shape the points cover, set with
+/// "fill": "solid" | "surface" | "wireframe" in the cluster JSON
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Fill {
+    /// Fill the whole volume
+    Solid,
+    /// Only the faces
+    Surface,
+    /// Only the edges, for seeds that look like architecture
+    Wireframe,
+}
+
+impl Fill {
+    fn from_json(json: &JsonValue) -> Self {
+        match json.as_str().unwrap_or("solid") {
+            "solid" => Self::Solid,
+            "surface" => Self::Surface,
+            "wireframe" => Self::Wireframe,
+            _ => panic!("fill must be one of solid, surface or wireframe")
+        }
+    }
+}
+
+/// Spread points along the edges of a wireframe by arc length, so the
+/// spacing is the same on every edge. Each parameter in [0, 1) is a
+/// fraction of the total length. Returns the position of each point and
+/// its cluster coordinates: the parameter along its edge, and the index
+/// of the edge scaled to [0, 1].
+fn wireframe_points<I>(edges: &[(Vec3, Vec3)], parameters: I)
+        -> Vec<(Vec3, Vec3)> where I: IntoIterator<Item = f64> {
+    let lengths: Vec<f64> = edges
+        .iter()
+        .map(|(start, end)| (*end - *start).length() as f64)
+        .collect();
+    let total: f64 = lengths.iter().sum();
+    let last_edge = (edges.len() - 1).max(1) as f32;
+
+    parameters.into_iter().map(|parameter| {
+        let mut distance = parameter * total;
+        let mut edge = 0;
+        while edge < edges.len() - 1 && distance >= lengths[edge] {
+            distance -= lengths[edge];
+            edge += 1;
+        }
+        let t = if lengths[edge] > 0.0 {
+            (distance / lengths[edge]).min(1.0)
+        } else {
+            0.0
+        };
+
+        let (start, end) = edges[edge];
+        let position = start + (end - start) * t as f32;
+        (position, Vec3::new(t as f32, edge as f32 / last_edge, 0.0))
+    }).collect()
+}
+
+/// Pick the parameters of each point of a shape from its Distribution.
+/// Shapes map these from the unit interval or square onto themselves, so
+/// every shape shares the same random and Fibonacci sampling.
+struct Sampler {
+    distribution: Distribution,
+    /// Random number generator for Distribution::Random
+    rng: RenderRng,
+}
+
+impl Sampler {
+    /// Parse the distribution of a shape. supported lists the distributions
+    /// the shape can use, the first one is the default.
+    fn from_json(
+            json: &JsonValue, shape: &str, supported: &[Distribution]) -> Self {
+        let distribution = match json["distribution"].as_str() {
+            None => supported[0],
+            Some("grid") => Distribution::Grid,
+            Some("random") => Distribution::Random,
+            Some("fibonacci") => Distribution::Fibonacci,
+            Some(name) => panic!(
+                "distribution {} must be one of grid, random or fibonacci",
+                name)
+        };
+        if !supported.contains(&distribution) {
+            let names: Vec<&str> =
+                supported.iter().map(|x| x.name()).collect();
+            panic!(
+                "{} clusters only support the distributions {:?}",
+                shape, names);
+        }
+
+        Self {
+            distribution,
+            rng: random::rng(),
+        }
+    }
+
+    /// A uniformly random number in [0, 1)
+    fn random(&mut self) -> f64 {
+        self.rng.gen_range(0.0, 1.0)
+    }
+
+    /// The parameter in [0, 1) of the i-th point of a 1D shape. Grids are
+    /// laid out by the shapes themselves
+    fn sample(&mut self, i: usize) -> f64 {
+        match self.distribution {
+            Distribution::Random => self.random(),
+            Distribution::Fibonacci => {
+                // Golden ratio
+                let phi = (1.0 + (5.0f64).sqrt()) / 2.0;
+                (i as f64 / phi) % 1.0
+            },
+            Distribution::Grid =>
+                unreachable!("grids are laid out by each shape")
+        }
+    }
+
+    /// The parameters (u, v) in [0, 1)^2 of the i-th of n points of a 2D
+    /// shape. For the Fibonacci lattice, v = i / n, see
+    /// http://extremelearning.com.au/how-to-evenly-distribute-points-on-a-sphere-more-effectively-than-the-canonical-fibonacci-lattice/
+    fn sample_pair(&mut self, i: usize, n: usize) -> (f64, f64) {
+        let u = self.sample(i);
+        let v = match self.distribution {
+            Distribution::Fibonacci => i as f64 / n as f64,
+            _ => self.random()
+        };
+        (u, v)
+    }
+}
+
+/// The legacy rand_* types are shapes with the random distribution
+fn random_alias(json: &JsonValue) -> JsonValue {
+    let mut json = json.clone();
+    json["distribution"] = "random".into();
+    json
 }
 
 /// A set of specific points to start with
 pub struct Points {
     // The points to use
     positions: Vec<Vec3>,
-    // The initial color of the points
-    color: Vec3
+    // The initial color of each point
+    colors: Vec<Vec3>,
+    // How to compute the cluster coordinates
+    coordinate_mode: CoordinateMode
+}
+
+/// How a Points cluster assigns cluster coordinates
+enum CoordinateMode {
+    /// (i / (n + 1), 0, 0) for the ith point
+    Index,
+    /// The position normalized to [0, 1]^3 within the bounding box of all
+    /// the points
+    Position,
+}
+
+impl CoordinateMode {
+    fn from_json(json: &JsonValue) -> Self {
+        match json.as_str().unwrap_or("index") {
+            "index" => Self::Index,
+            "position" => Self::Position,
+            _ => panic!("coordinate_mode must be either index or position")
+        }
+    }
 }
 
 impl Points {
+    pub fn new(positions: Vec<Vec3>, colors: Vec<Vec3>) -> Self {
+        if positions.len() != colors.len() {
+            panic!("Points: there must be one color per position");
+        }
+
+        Self {
+            positions,
+            colors,
+            coordinate_mode: CoordinateMode::Index
+        }
+    }
+
+    fn with_coordinate_mode(mut self, coordinate_mode: CoordinateMode) -> Self {
+        self.coordinate_mode = coordinate_mode;
+        self
+    }
+
+    /// Compute the cluster coordinates for every point. In position mode,
+    /// an axis where the points have no extent gets a coordinate of 0.5
+    fn cluster_coordinates(&self) -> Vec<Vec3> {
+        let n = self.positions.len() as f32;
+        match self.coordinate_mode {
+            CoordinateMode::Index => (0..self.positions.len())
+                .map(|i| Vec3::new((i as f32) / (n + 1.0), 0.0, 0.0))
+                .collect(),
+            CoordinateMode::Position => {
+                let bounds = BBox::from_points(&self.positions);
+                let min = *bounds.min();
+                let size = *bounds.max() - min;
+                let normalize = |value: f32, start: f32, extent: f32| {
+                    if extent > 0.0 {
+                        (value - start) / extent
+                    } else {
+                        0.5
+                    }
+                };
+                self.positions.iter().map(|position| Vec3::new(
+                    normalize(*position.x(), *min.x(), *size.x()),
+                    normalize(*position.y(), *min.y(), *size.y()),
+                    normalize(*position.z(), *min.z(), *size.z())
+                )).collect()
+            }
+        }
+    }
+
     /// Parse a list of points from JSON of the form
     /// ```text
     /// {
@@ -48,21 +354,27 @@ impl Points {
     ///         [x2, y2, z2],
     ///         ...
     ///     ],
-    ///     "color": [r, g, b] // 0.0 to 1.0
+    ///     "color": [r, g, b] | str, // see Vec3::color_from_json()
+    ///     "coordinate_mode": "index" | "position" (default "index")
     /// }
     /// ```
+    ///
+    /// coordinate_mode "position" sets the cluster coordinates from where
+    /// each point is in the bounding box of the points, so shaders can
+    /// color by the original location. "index" is a ramp from the order of
+    /// the points.
     pub fn from_json(json: &JsonValue) -> Self {
         let mut positions = Vec::new();
         for position_json in json["positions"].members() {
             let position = Vec3::from_json(position_json, Vec3::zero());
             positions.push(position);
         }
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let colors = vec![color; positions.len()];
+        let coordinate_mode =
+            CoordinateMode::from_json(&json["coordinate_mode"]);
 
-        Self {
-            positions,
-            color: color
-        }
+        Self::new(positions, colors).with_coordinate_mode(coordinate_mode)
     }
 
     to_box!(Cluster);
@@ -72,20 +384,23 @@ impl Cluster for Points {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
             -> Vec<InternalPoint> {
         let mut points = Vec::new();
-        let color = HalfMultivector::from_vec3(&self.color);
-        let n = self.positions.len() as f32;
-        for (i, position) in self.positions.iter().enumerate() {
-            let u = (i as f32) / (n + 1.0);
+        let coordinates = self.cluster_coordinates();
+        let pairs = self.positions.iter().zip(self.colors.iter());
+        for (i, (position, color)) in pairs.enumerate() {
             let point = InternalPoint {
                 position: HalfMultivector::from_vec3(&position),
-                color: color.clone(),
-                cluster_coordinates: Vec3::new(u, 0.0, 0.0),
+                color: HalfMultivector::from_vec3(color),
+                cluster_coordinates: coordinates[i],
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
             points.push(point);
         }
@@ -96,91 +411,78 @@ impl Cluster for Points {
     fn point_count(&self) -> usize {
         self.positions.len()
     }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::from_points(&self.positions)
+    }
 }
 
-/// Evenly spaced points along a line
-pub struct Line {
-    /// Start point
-    start: Vec3,
-    /// End point
-    end: Vec3,
-    /// The line is a solid color
-    color: Vec3,
-    /// Generate N points
-    num_points: usize,
+/// Points loaded from a file produced elsewhere, e.g. a scan or another
+/// generator. Once loaded, this works just like Points.
+pub struct FileCluster {
+    /// The loaded points
+    points: Points,
 }
 
-impl Line {
-    /// Parse a RandomLine generator from JSON of the form:
+impl FileCluster {
+    /// Load the points from a file, given JSON of the form
     /// ```text
     /// {
-    ///     "type": "line",
-    ///     "start": [x, y, z],
-    ///     "end": [x, y, z],
-    ///     "color": [r, g, b] // 0.0 to 1.0
-    ///     "num_points": N
+    ///     "type": "file",
+    ///     "path": "seed.csv" | "seed.ply",
+    ///     "color": [r, g, b] | str (optional), // see Vec3::color_from_json()
+    ///     "coordinate_mode": "index" | "position" (default "index")
     /// }
     /// ```
+    ///
+    /// See the seed_files module for the supported formats. If color is
+    /// given, it replaces any colors from the file. If neither is given,
+    /// the points are white. coordinate_mode works the same as for Points.
     pub fn from_json(json: &JsonValue) -> Self {
-        let start = Vec3::from_json(&json["start"], Vec3::zero());
-        let end = Vec3::from_json(&json["end"], Vec3::new(1.0, 0.0, 0.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be an integer");
+        let path = json["path"]
+            .as_str()
+            .expect("file cluster: path must be a string");
+        let seed = seed_files::load_seed_points(path);
+        if seed.positions.is_empty() {
+            panic!("file cluster: {} contains no points", path);
+        }
+
+        let colors = match (&json["color"], seed.colors) {
+            (JsonValue::Null, Some(colors)) => colors,
+            (color_json, _) => {
+                let color = Vec3::color_from_json(color_json, Vec3::ones());
+                vec![color; seed.positions.len()]
+            }
+        };
 
+        let coordinate_mode =
+            CoordinateMode::from_json(&json["coordinate_mode"]);
         Self {
-            start,
-            end,
-            color,
-            num_points: *num_points
+            points: Points::new(seed.positions, colors)
+                .with_coordinate_mode(coordinate_mode)
         }
     }
 
     to_box!(Cluster);
 }
 
-impl Cluster for Line {
-    fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
+impl Cluster for FileCluster {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
             -> Vec<InternalPoint> {
-        let mut points = Vec::new();
-        let color = HalfMultivector::from_vec3(&self.color);
-
-        let n = self.num_points as f32;
-
-        // Generate N random points, uniformly distributed over the 
-        // line segment
-        for i in 0..self.num_points {
-            let index = i as f32;
-            let t = index / (n - 1.0);
-            let position_vec3 = Vec3::lerp(&self.start, &self.end, t);
-            let position = HalfMultivector::from_vec3(&position_vec3);
-
-            let point = InternalPoint {
-                position,
-                color: color.clone(),
-                cluster_coordinates: Vec3::new(t, 0.0, 0.0),
-                iteration: 0,
-                cluster_copy,
-                cluster_id,
-                point_id: i as u16,
-                last_xform: 0,
-                last_color_xform: 0
-            };
-
-            points.push(point);
-        }
-
-        points
+        self.points.generate(cluster_copy, cluster_id)
     }
 
     fn point_count(&self) -> usize {
-        self.num_points
+        self.points.point_count()
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.points.bounding_box()
     }
 }
 
-/// Random points arranged in a line segment from start to end
-pub struct RandomLine {
+/// Points along a line segment from start to end, evenly spaced by default
+pub struct Line {
     /// Start point
     start: Vec3,
     /// End point
@@ -189,59 +491,59 @@ pub struct RandomLine {
     color: Vec3,
     /// Generate N points
     num_points: usize,
-    /// Random number generator for arranging points
-    rng: ThreadRng,
+    /// How the points are spread along the line
+    sampler: Sampler,
 }
 
-impl RandomLine {
-    pub fn new(
-            start: Vec3, 
-            end: Vec3, 
-            color: Vec3, 
-            num_points: usize) -> Self {
-        Self {
-            start, 
-            end,
-            color,
-            num_points,
-            rng: rand::thread_rng(), 
-        }
-    }
-
-    /// Parse a RandomLine generator from JSON of the form:
+impl Line {
+    /// Parse a Line generator from JSON of the form:
     /// ```text
     /// {
     ///     "type": "line",
     ///     "start": [x, y, z],
     ///     "end": [x, y, z],
-    ///     "color": [r, g, b] // 0.0 to 1.0
-    ///     "num_points": N
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "num_points": N,
+    ///     "distribution": "grid" | "random" | "fibonacci" // default grid
     /// }
     /// ```
     pub fn from_json(json: &JsonValue) -> Self {
         let start = Vec3::from_json(&json["start"], Vec3::zero());
         let end = Vec3::from_json(&json["end"], Vec3::new(1.0, 0.0, 0.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be an integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &parse_count(json, "num_points", "line");
+        let sampler = Sampler::from_json(json, "line", &[
+            Distribution::Grid,
+            Distribution::Random,
+            Distribution::Fibonacci
+        ]);
 
-        Self::new(start, end, color, *num_points)
+        Self {
+            start,
+            end,
+            color,
+            num_points: *num_points,
+            sampler,
+        }
     }
 
     to_box!(Cluster);
 }
 
-impl Cluster for RandomLine {
+impl Cluster for Line {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
             -> Vec<InternalPoint> {
         let mut points = Vec::new();
         let color = HalfMultivector::from_vec3(&self.color);
 
-        // Generate N random points, uniformly distributed over the 
-        // line segment
+        let n = self.num_points as f32;
+
         for i in 0..self.num_points {
-            let t = self.rng.gen_range(0.0, 1.0);
+            let t = match self.sampler.distribution {
+                // Include both endpoints
+                Distribution::Grid => i as f32 / (n - 1.0),
+                _ => self.sampler.sample(i) as f32
+            };
             let position_vec3 = Vec3::lerp(&self.start, &self.end, t);
             let position = HalfMultivector::from_vec3(&position_vec3);
 
@@ -252,9 +554,13 @@ impl Cluster for RandomLine {
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
 
             points.push(point);
@@ -266,9 +572,13 @@ impl Cluster for RandomLine {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::from_points(&[self.start, self.end])
+    }
 }
 
-/// Evenly spaced points along a circle
+/// Points along a circle, evenly spaced by default
 pub struct Circle {
     /// center point
     center: Vec3,
@@ -282,29 +592,35 @@ pub struct Circle {
     color: Vec3,
     /// Generate N points along the circle
     num_points: usize,
+    /// How the points are spread around the circle
+    sampler: Sampler,
 }
 
 impl Circle {
-    /// Parse a RandomLine generator from JSON of the form:
+    /// Parse a Circle generator from JSON of the form:
     /// ```text
     /// {
     ///     "type": "circle",
     ///     "center": [x, y, z],
     ///     "x_dir": [xx, xy, xz],
     ///     "y_dir": [yx, yy, yz],
-    ///     "color": [r, g, b] // 0.0 to 1.0
-    ///     "num_points": N
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "num_points": N,
+    ///     "distribution": "grid" | "random" | "fibonacci" // default grid
     /// }
     /// ```
     pub fn from_json(json: &JsonValue) -> Self {
         let center = Vec3::from_json(&json["center"], Vec3::zero());
-        let radius = &json["radius"].as_f64().unwrap_or(1.0);
+        let radius = &parse_radius(json, "circle");
         let x_dir = Vec3::from_json(&json["x_dir"], Vec3::new(1.0, 0.0, 0.0));
         let y_dir = Vec3::from_json(&json["y_dir"], Vec3::new(0.0, 1.0, 0.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be an integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &parse_count(json, "num_points", "circle");
+        let sampler = Sampler::from_json(json, "circle", &[
+            Distribution::Grid,
+            Distribution::Random,
+            Distribution::Fibonacci
+        ]);
 
         Self {
             center,
@@ -312,7 +628,8 @@ impl Circle {
             x_dir,
             y_dir,
             color,
-            num_points: *num_points
+            num_points: *num_points,
+            sampler,
         }
     }
 
@@ -327,11 +644,12 @@ impl Cluster for Circle {
         let n = self.num_points as f64;
         let r = self.radius;
 
-        // Generate N random points, uniformly distributed over the 
-        // line segment
         for i in 0..self.num_points {
-            let index = i as f64;
-            let t = 2.0 * PI * index / n;
+            let fraction = match self.sampler.distribution {
+                Distribution::Grid => i as f64 / n,
+                _ => self.sampler.sample(i)
+            };
+            let t = 2.0 * PI * fraction;
             let x = (r * t.cos()) as f32;
             let y = (r * t.sin()) as f32;
 
@@ -341,13 +659,19 @@ impl Cluster for Circle {
             let point = InternalPoint {
                 position,
                 color: color.clone(),
-                cluster_coordinates: Vec3::new(t as f32, 0.0, 0.0),
+                // The fraction of the way around, not the angle, to keep
+                // the coordinates in [0, 1]
+                cluster_coordinates: Vec3::new(fraction as f32, 0.0, 0.0),
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
 
             points.push(point);
@@ -359,17 +683,27 @@ impl Cluster for Circle {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        let r = self.radius as f32;
+        ellipse_bounding_box(&self.center, &(self.x_dir * r), &(self.y_dir * r))
+    }
 }
 
-/// Points arranged in a triangle grid
+/// Points in a triangle, arranged in a triangle grid by default
 pub struct Triangle {
     /// The three vertices of the triangle.
     vertices: [Vec3; 3],
-    /// How many points on each side of the triangle. The total number
-    /// of points will be triangle_number(side_points)
+    /// For the grid distribution, how many points on each side of the
+    /// triangle.
     side_points: usize,
+    /// The total number of points. For the grid distribution this is
+    /// triangle_number(side_points)
+    num_points: usize,
     /// The triangle starts with a solid color
     color: Vec3,
+    /// How the points are spread over the triangle
+    sampler: Sampler,
 }
 
 impl Triangle {
@@ -383,9 +717,13 @@ impl Triangle {
     ///         [Cx, Cy, Cz]
     ///     ],
     ///     "side_points": N // total points will be N(N + 1)/2
-    ///     "color": [r, g, b] // 0.0 to 1.0
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "distribution": "grid" | "random" | "fibonacci" // default grid
     /// }
     /// ```
+    ///
+    /// The random and fibonacci distributions use "num_points": N rather
+    /// than side_points.
     pub fn from_json(json: &JsonValue) -> Self {
         let vertices_json = &json["vertices"];
         let vertex_a = Vec3::from_json(&vertices_json[0], Vec3::zero());
@@ -394,23 +732,33 @@ impl Triangle {
         let vertex_c = Vec3::from_json(
             &vertices_json[2], Vec3::new(0.0, 1.0, 0.0));
         let vertices = [vertex_a, vertex_b, vertex_c];
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let side_points = &json["side_points"]
-            .as_usize()
-            .expect("side_points must be a positive integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let sampler = Sampler::from_json(json, "triangle", &[
+            Distribution::Grid,
+            Distribution::Random,
+            Distribution::Fibonacci
+        ]);
+        let (side_points, num_points) = match sampler.distribution {
+            Distribution::Grid => {
+                let n = parse_side_points(json, "triangle");
+                (n, (n * (n + 1)) / 2)
+            },
+            _ => {
+                let num_points = parse_count(json, "num_points", "triangle");
+                (0, num_points)
+            }
+        };
 
         Self {
             vertices,
-            side_points: *side_points,
+            side_points,
+            num_points,
             color,
+            sampler,
         }
     }
 
-    to_box!(Cluster);
-}
-
-impl Cluster for Triangle {
-    fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
+    fn generate_grid(&self, cluster_copy: u16, cluster_id: u16)
             -> Vec<InternalPoint> {
         
         let n = self.side_points;
@@ -438,9 +786,13 @@ impl Cluster for Triangle {
                     iteration: 0,
                     cluster_copy,
                     cluster_id,
-                    point_id: i as u16,
+                    point_id: grid.len() as u32,
                     last_xform: 0,
-                    last_color_xform: 0
+                    last_xform_group: 0,
+                    last_color_xform: 0,
+                    xform_address: 0,
+                    cga_pn: [0.0, 0.0],
+                    weight: 1
                 };
     
                 grid.push(point);
@@ -450,120 +802,337 @@ impl Cluster for Triangle {
         grid
     }
 
-    fn point_count(&self) -> usize {
-        let n = self.side_points;
-        (n * (n + 1)) / 2
-    }
-}
-
-/// A 2D quad of evenly-spaced points.
-pub struct GridQuad {
-    /// Center of the quad
-    center: Vec3,
-    /// x-axis of the quad. You're going to make this a unit vector, right? ;)
-    x_dir: Vec3,
-    /// y-axis of the quad. You're going to make this a unit vector orthogonal to x_dir, right? ;)
-    y_dir: Vec3,
-    /// Width of the quad in the x direction
-    width: f64,
-    /// height of the quad in the y-direction
-    height: f64,
-    /// The quad starts with a solid color
-    color: Vec3,
-    // The actual number of points in the x direction
-    x_count: usize,
-    // The actual number of points in the y direction.
-    y_count: usize,
-    /// The actual number of points in the grid (may be less than the value in 
-    /// the JSON)
-    num_points: usize,
-}
-
-
-impl GridQuad {
-    /// Parse a GridQuad generator from JSON of the form:
-    /// ```text
-    /// {
-    ///     "type": "quad",
-    ///     "center": [x, y, z],
-    ///     "dims": [width, height],
-    ///     "x_dir": [xx, xy, xz],
-    ///     "y_dir": [yx, yy, yz],
-    ///     "color": [r, g, b] // 0.0 to 1.0
-    ///     "num_points": N
-    /// }
-    /// ```
-    pub fn from_json(json: &JsonValue) -> Self {
-        let center = Vec3::from_json(&json["center"], Vec3::zero());
-        let dims = &json["dims"];
-        let width = dims[0].as_f64().unwrap_or(1.0);
-        let height = dims[1].as_f64().unwrap_or(1.0);
-        let x_dir = Vec3::from_json(&json["x_dir"], Vec3::new(1.0, 0.0, 0.0));
-        let y_dir = Vec3::from_json(&json["y_dir"], Vec3::new(0.0, 1.0, 0.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_f64()
-            .expect("num_points must be a positive integer");
-
-        // Compute the effective grid size. If n points cannot be evenly
-        // divided into the same ratio as width/height, the grid may be
-        // slightly smaller
-        // See https://www.desmos.com/calculator/ltvzx6ezec
-        let n = *num_points;
-        let area = width * height;
-        let density = n / area;
-        let sqrt_density = density.sqrt();
-        let x_count = (width * sqrt_density).floor() as usize;
-        let y_count = (height * sqrt_density).floor() as usize;
-        let m = x_count * y_count;
-
-        Self {
-            center,
-            x_dir,
-            y_dir,
-            width,
-            height,
-            color,
-            x_count,
-            y_count,
-            num_points: m,
-        }
-    }
-
     to_box!(Cluster);
 }
 
-impl Cluster for GridQuad {
+impl Cluster for Triangle {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
             -> Vec<InternalPoint> {
-        let mut grid = Vec::new();
+        if self.sampler.distribution == Distribution::Grid {
+            return self.generate_grid(cluster_copy, cluster_id);
+        }
+
         let color = HalfMultivector::from_vec3(&self.color);
+        let [a, b, c] = self.vertices;
 
+        let mut points = Vec::new();
         for i in 0..self.num_points {
-            let row = i / self.x_count;
-            let col = i % self.x_count;
-            let u = (col as f64) / ((self.x_count - 1) as f64);
-            let v = (row as f64) / ((self.y_count - 1) as f64);
-
-            let x = (self.width * u - 0.5 * self.width) as f32;
-            let y = (self.height * v - 0.5 * self.height) as f32;
+            let (mut u, mut v) = self.sampler.sample_pair(i, self.num_points);
+            // Half of the unit square is outside the triangle. Rotating it
+            // back inside keeps the points uniform
+            if u + v > 1.0 {
+                u = 1.0 - u;
+                v = 1.0 - v;
+            }
+            let (u, v) = (u as f32, v as f32);
+            let w = (1.0 - u - v).max(0.0);
 
-            let position_vec3 = self.center + self.x_dir * x + self.y_dir * y;
+            let position_vec3 = a * u + b * v + c * w;
             let position = HalfMultivector::from_vec3(&position_vec3);
 
             let point = InternalPoint {
                 position,
                 color: color.clone(),
-                cluster_coordinates: Vec3::new(u as f32, v as f32, 0.0),
+                cluster_coordinates: Vec3::new(u, v, w),
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
 
-            grid.push(point);
+            points.push(point);
+        }
+
+        points
+    }
+
+    fn point_count(&self) -> usize {
+        self.num_points
+    }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::from_points(&self.vertices)
+    }
+}
+
+/// Randomly sample N points uniformly over the surface of a triangle mesh
+/// loaded from a file. The mesh is a solid color.
+pub struct MeshSurface {
+    /// The triangles of the mesh
+    triangles: Vec<[Vec3; 3]>,
+    /// Cumulative sum of the triangle areas, for picking a triangle with
+    /// probability proportional to its area
+    area_cdf: Vec<f32>,
+    /// The mesh starts off with a solid color (RGB from 0 to 1)
+    color: Vec3,
+    /// Number of points to sample
+    num_points: usize,
+    /// Random number generator for sampling points
+    rng: RenderRng,
+}
+
+impl MeshSurface {
+    pub fn new(triangles: Vec<[Vec3; 3]>, color: Vec3, num_points: usize)
+            -> Self {
+        let mut total_area = 0.0;
+        let area_cdf: Vec<f32> = triangles.iter().map(|[a, b, c]| {
+            total_area += 0.5 * (*b - *a).cross(&(*c - *a)).length();
+            total_area
+        }).collect();
+        if total_area <= 0.0 {
+            panic!("mesh cluster: the mesh has no surface area");
+        }
+
+        Self {
+            triangles,
+            area_cdf,
+            color,
+            num_points,
+            rng: random::rng(),
+        }
+    }
+
+    /// Load a mesh cluster from JSON of the form:
+    /// ```text
+    /// {
+    ///     "type": "mesh",
+    ///     "path": "model.obj",
+    ///     "num_points": N,
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    /// }
+    /// ```
+    ///
+    /// See seed_files::load_seed_mesh() for what parts of the file are
+    /// used.
+    pub fn from_json(json: &JsonValue) -> Self {
+        let path = json["path"]
+            .as_str()
+            .expect("mesh cluster: path must be a string");
+        let mesh = seed_files::load_seed_mesh(path);
+        if mesh.triangles.is_empty() {
+            panic!("mesh cluster: {} contains no faces", path);
+        }
+        let num_points = parse_count(json, "num_points", "mesh cluster");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+
+        Self::new(mesh.triangles, color, num_points)
+    }
+
+    to_box!(Cluster);
+
+    /// Pick a triangle with probability proportional to its area
+    fn choose_triangle(&mut self) -> &[Vec3; 3] {
+        let total_area = *self.area_cdf.last().unwrap();
+        let target = self.rng.gen_range(0.0, total_area);
+        let index = self.area_cdf
+            .partition_point(|area| *area <= target)
+            .min(self.triangles.len() - 1);
+        &self.triangles[index]
+    }
+}
+
+impl Cluster for MeshSurface {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
+            -> Vec<InternalPoint> {
+        let color = HalfMultivector::from_vec3(&self.color);
+        let mut points = Vec::new();
+        for i in 0..self.num_points {
+            let mut u: f32 = self.rng.gen_range(0.0, 1.0);
+            let mut v: f32 = self.rng.gen_range(0.0, 1.0);
+            // Reflect points in the far half of the parallelogram back
+            // into the triangle so the distribution is uniform
+            if u + v > 1.0 {
+                u = 1.0 - u;
+                v = 1.0 - v;
+            }
+            let w = 1.0 - u - v;
+            let [a, b, c] = *self.choose_triangle();
+            let position = a * u + b * v + c * w;
+
+            points.push(InternalPoint {
+                position: HalfMultivector::from_vec3(&position),
+                color: color.clone(),
+                // Barycentric coordinates within the triangle, like
+                // Triangle
+                cluster_coordinates: Vec3::new(u, v, w),
+                iteration: 0,
+                cluster_copy,
+                cluster_id,
+                point_id: i as u32,
+                last_xform: 0,
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
+            });
+        }
+
+        points
+    }
+
+    fn point_count(&self) -> usize {
+        self.num_points
+    }
+
+    fn bounding_box(&self) -> BBox {
+        let vertices: Vec<Vec3> =
+            self.triangles.iter().flat_map(|triangle| triangle.to_vec()).collect();
+        BBox::from_points(&vertices)
+    }
+}
+
+/// A 2D quad of points, evenly spaced by default.
+pub struct GridQuad {
+    /// Center of the quad
+    center: Vec3,
+    /// x-axis of the quad. You're going to make this a unit vector, right? ;)
+    x_dir: Vec3,
+    /// y-axis of the quad. You're going to make this a unit vector orthogonal to x_dir, right? ;)
+    y_dir: Vec3,
+    /// Width of the quad in the x direction
+    width: f64,
+    /// height of the quad in the y-direction
+    height: f64,
+    /// The quad starts with a solid color
+    color: Vec3,
+    // The actual number of points in the x direction
+    x_count: usize,
+    // The actual number of points in the y direction.
+    y_count: usize,
+    /// The actual number of points in the grid (may be less than the value in 
+    /// the JSON)
+    num_points: usize,
+    /// How the points are spread over the quad
+    sampler: Sampler,
+}
+
+
+impl GridQuad {
+    /// Parse a GridQuad generator from JSON of the form:
+    /// ```text
+    /// {
+    ///     "type": "quad",
+    ///     "center": [x, y, z],
+    ///     "dims": [width, height],
+    ///     "x_dir": [xx, xy, xz],
+    ///     "y_dir": [yx, yy, yz],
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "num_points": N,
+    ///     "distribution": "grid" | "random" | "fibonacci" // default grid
+    /// }
+    /// ```
+    pub fn from_json(json: &JsonValue) -> Self {
+        let center = Vec3::from_json(&json["center"], Vec3::zero());
+        let dims = &json["dims"];
+        let width = dims[0].as_f64().unwrap_or(1.0);
+        let height = dims[1].as_f64().unwrap_or(1.0);
+        let x_dir = Vec3::from_json(&json["x_dir"], Vec3::new(1.0, 0.0, 0.0));
+        let y_dir = Vec3::from_json(&json["y_dir"], Vec3::new(0.0, 1.0, 0.0));
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &(parse_count(json, "num_points", "quad") as f64);
+        let sampler = Sampler::from_json(json, "quad", &[
+            Distribution::Grid,
+            Distribution::Random,
+            Distribution::Fibonacci
+        ]);
+        if sampler.distribution != Distribution::Grid {
+            return Self {
+                center,
+                x_dir,
+                y_dir,
+                width,
+                height,
+                color,
+                x_count: 0,
+                y_count: 0,
+                num_points: *num_points as usize,
+                sampler,
+            };
+        }
+
+        // Compute the effective grid size. If n points cannot be evenly
+        // divided into the same ratio as width/height, the grid may be
+        // slightly smaller
+        // See https://www.desmos.com/calculator/ltvzx6ezec
+        let n = *num_points;
+        let area = width * height;
+        let density = n / area;
+        let sqrt_density = density.sqrt();
+        let x_count = (width * sqrt_density).floor() as usize;
+        let y_count = (height * sqrt_density).floor() as usize;
+        let m = x_count * y_count;
+        if m == 0 {
+            panic!(
+                "quad: num_points {} is too few for a {} by {} grid",
+                n, width, height);
+        }
+
+        Self {
+            center,
+            x_dir,
+            y_dir,
+            width,
+            height,
+            color,
+            x_count,
+            y_count,
+            num_points: m,
+            sampler,
+        }
+    }
+
+    to_box!(Cluster);
+}
+
+impl Cluster for GridQuad {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
+            -> Vec<InternalPoint> {
+        let mut grid = Vec::new();
+        let color = HalfMultivector::from_vec3(&self.color);
+
+        for i in 0..self.num_points {
+            let (u, v) = match self.sampler.distribution {
+                Distribution::Grid => {
+                    let row = i / self.x_count;
+                    let col = i % self.x_count;
+                    (
+                        (col as f64) / ((self.x_count - 1) as f64),
+                        (row as f64) / ((self.y_count - 1) as f64),
+                    )
+                },
+                _ => self.sampler.sample_pair(i, self.num_points)
+            };
+
+            let x = (self.width * u - 0.5 * self.width) as f32;
+            let y = (self.height * v - 0.5 * self.height) as f32;
+
+            let position_vec3 = self.center + self.x_dir * x + self.y_dir * y;
+            let position = HalfMultivector::from_vec3(&position_vec3);
+
+            let point = InternalPoint {
+                position,
+                color: color.clone(),
+                cluster_coordinates: Vec3::new(u as f32, v as f32, 0.0),
+                iteration: 0,
+                cluster_copy,
+                cluster_id,
+                point_id: i as u32,
+                last_xform: 0,
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
+            };
+
+            grid.push(point);
         }
 
         grid
@@ -572,10 +1141,21 @@ impl Cluster for GridQuad {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        let half_x = self.x_dir * (0.5 * self.width as f32);
+        let half_y = self.y_dir * (0.5 * self.height as f32);
+        BBox::from_points(&[
+            self.center - half_x - half_y,
+            self.center + half_x - half_y,
+            self.center - half_x + half_y,
+            self.center + half_x + half_y,
+        ])
+    }
 }
 
-/// Points in a disk arranged in a fibonacci lattice, similar to a sunflower's
-/// seed arangement
+/// Points in a disk arranged in a fibonacci lattice by default, similar to a
+/// sunflower's seed arangement
 pub struct FibonacciDisk {
     /// center point
     center: Vec3,
@@ -589,18 +1169,36 @@ pub struct FibonacciDisk {
     color: Vec3,
     /// Generate N points along the circle
     num_points: usize,
+    /// How the points are spread over the disk
+    sampler: Sampler,
 }
 
 impl FibonacciDisk {
+    /// Parse a FibonacciDisk generator from JSON of the form:
+    /// ```text
+    /// {
+    ///     "type": "disk",
+    ///     "center": [x, y, z],
+    ///     "radius": r,
+    ///     "x_dir": [xx, xy, xz],
+    ///     "y_dir": [yx, yy, yz],
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "num_points": N,
+    ///     "distribution": "fibonacci" | "random" // default fibonacci
+    /// }
+    /// ```
     pub fn from_json(json: &JsonValue) -> Self {
         let center = Vec3::from_json(&json["center"], Vec3::zero());
-        let radius = &json["radius"].as_f64().unwrap_or(1.0);
+        let radius = &parse_radius(json, "disk");
         let x_dir = Vec3::from_json(&json["x_dir"], Vec3::new(1.0, 0.0, 0.0));
         let y_dir = Vec3::from_json(&json["y_dir"], Vec3::new(0.0, 1.0, 0.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be a positive integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &parse_count(json, "num_points", "disk");
+        let sampler = Sampler::from_json(
+            json, "disk", &[Distribution::Fibonacci, Distribution::Random]);
+        if sampler.distribution == Distribution::Fibonacci {
+            check_fibonacci_count(*num_points, "disk");
+        }
 
         Self {
             center,
@@ -608,7 +1206,8 @@ impl FibonacciDisk {
             x_dir,
             y_dir,
             num_points: *num_points,
-            color
+            color,
+            sampler,
         }
     }
 
@@ -618,20 +1217,14 @@ impl FibonacciDisk {
 impl Cluster for FibonacciDisk {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
             -> Vec<InternalPoint> {
-        // Golden ratio
-        let phi = (1.0 + (5.0f64).sqrt()) / 2.0;
-        let n = self.num_points as f64;
         let color = HalfMultivector::from_vec3(&self.color);
         let r = self.radius;
         let mut lattice = Vec::new();
 
-        // Generate points based on the Fibonacci lattice.
-        // See here:
-        // http://extremelearning.com.au/how-to-evenly-distribute-points-on-a-sphere-more-effectively-than-the-canonical-fibonacci-lattice/
+        // Map the unit square onto the disk so the points are spread
+        // evenly by area. See Sampler::sample_pair() for the lattice
         for i in 0..self.num_points {
-            let index = i as f64;
-            let u = (index / phi) % 1.0;
-            let v = index / n;
+            let (u, v) = self.sampler.sample_pair(i, self.num_points);
 
             // theta in the article
             let azimuth = 2.0 * PI * u;
@@ -651,9 +1244,13 @@ impl Cluster for FibonacciDisk {
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
             lattice.push(point);
         }
@@ -664,6 +1261,45 @@ impl Cluster for FibonacciDisk {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        let r = self.radius as f32;
+        ellipse_bounding_box(&self.center, &(self.x_dir * r), &(self.y_dir * r))
+    }
+}
+
+/// How to color the points of a FibonacciSphere
+enum SphereColor {
+    /// Every point is the same color
+    Solid(Vec3),
+    /// Color each point by its direction, see harmonic_color()
+    Harmonic,
+}
+
+/// Color a direction on the sphere using three of the real l = 2
+/// spherical harmonics, one per channel:
+///
+/// - red: sin^2(zenith) sin(2 azimuth)
+/// - green: sin(2 zenith) cos(azimuth)
+/// - blue: (3 cos^2(zenith) - 1) / 2
+///
+/// Each is rescaled from its range to [0, 1]. Unlike the l = 1 harmonics
+/// (which are just x, y and z) these vary in a way that isn't aligned with
+/// the axes, so it's easy to see how a color IFS mixes them.
+fn harmonic_color(azimuth: f64, zenith: f64) -> Vec3 {
+    let sin_zenith = zenith.sin();
+    let cos_zenith = zenith.cos();
+
+    // both in [-1, 1]
+    let red = sin_zenith * sin_zenith * (2.0 * azimuth).sin();
+    let green = (2.0 * zenith).sin() * azimuth.cos();
+    // in [-1/2, 1]
+    let blue = 0.5 * (3.0 * cos_zenith * cos_zenith - 1.0);
+
+    Vec3::new(
+        (0.5 * (red + 1.0)) as f32,
+        (0.5 * (green + 1.0)) as f32,
+        ((blue + 0.5) / 1.5) as f32)
 }
 
 /// Similar to FibonacciDisk but arranged on a sphere
@@ -674,8 +1310,8 @@ struct FibonacciSphere {
     radius: f64,
     /// number of points to put on the surface of the sphere
     num_points: usize,
-    /// The sphere starts off with a solid color
-    color: Vec3,
+    /// The sphere starts off with a solid color or a spherical harmonic
+    color: SphereColor,
 }
 
 impl FibonacciSphere {
@@ -685,17 +1321,23 @@ impl FibonacciSphere {
     ///     "type": "sphere",
     ///     "center": [x, y, z],
     ///     "radius": r,
-    ///     "color": [r, g, b] // 0.0 to 1.0
+    ///     "color": [r, g, b] | str | "harmonic" // see Vec3::color_from_json()
     ///     "num_points": N
     /// }
     /// ```
+    ///
+    /// With "harmonic", each point is colored by its direction using a few
+    /// spherical harmonics. This is helpful for debugging color IFSs.
     pub fn from_json(json: &JsonValue) -> Self {
         let center = Vec3::from_json(&json["center"], Vec3::zero());
-        let radius = &json["radius"].as_f64().unwrap_or(1.0);
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be a positive integer");
+        let radius = &parse_radius(json, "sphere");
+        let color = match json["color"].as_str() {
+            Some("harmonic") => SphereColor::Harmonic,
+            _ => SphereColor::Solid(
+                Vec3::color_from_json(&json["color"], Vec3::ones()))
+        };
+        let num_points = &parse_count(json, "num_points", "sphere");
+        check_fibonacci_count(*num_points, "sphere");
 
         Self {
             center,
@@ -718,7 +1360,6 @@ impl Cluster for FibonacciSphere {
         let cx = *self.center.x() as f64;
         let cy = *self.center.y() as f64;
         let cz = *self.center.z() as f64;
-        let color = HalfMultivector::from_vec3(&self.color);
 
         let mut lattice = Vec::new();
 
@@ -745,16 +1386,25 @@ impl Cluster for FibonacciSphere {
             let z = r * cos_zenith + cz;
 
             let position = HalfMultivector::point(x, y, z);
+            let color = match &self.color {
+                SphereColor::Solid(color) => HalfMultivector::from_vec3(color),
+                SphereColor::Harmonic => HalfMultivector::from_vec3(
+                    &harmonic_color(azimuth, zenith))
+            };
             let point = InternalPoint {
                 position,
-                color: color.clone(),
+                color,
                 cluster_coordinates: Vec3::new(u as f32, v as f32, 1.0),
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
             lattice.push(point);
         }
@@ -765,6 +1415,11 @@ impl Cluster for FibonacciSphere {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        let r = self.radius as f32;
+        BBox::from_center(&self.center, &Vec3::new(r, r, r))
+    }
 }
 
 /// Points arranged in a tetrahedron grid
@@ -776,13 +1431,17 @@ pub struct Tetrahedron {
     side_points: usize,
     /// The triangle starts with a solid color
     color: Vec3,
+    /// Fill the volume, or only the faces or edges
+    fill: Fill,
+    /// Number of points along the edges for Fill::Wireframe
+    num_points: usize,
 }
 
 impl Tetrahedron {
     /// Parse a Triangle generator from JSON of the form:
     /// ```text
     /// {
-    ///     "type": "triangle",
+    ///     "type": "tetrahedron",
     ///     "vertices": [
     ///         [Ax, Ay, Az],
     ///         [Bx, By, Bz],
@@ -790,9 +1449,16 @@ impl Tetrahedron {
     ///         [Dx, Dy, Dz],
     ///     ],
     ///     "side_points": N // total points will be tetrahedral_number(N)
-    ///     "color": [r, g, b] // 0.0 to 1.0
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "fill": "solid" | "surface" | "wireframe" // default solid
+    ///     "num_points": N // wireframe only, instead of side_points
     /// }
     /// ```
+    ///
+    /// surface keeps the points of the grid that are on the faces. The
+    /// wireframe has num_points evenly spaced along the 6 edges, and its
+    /// cluster coordinates are the parameter along the edge and which edge
+    /// it is, see wireframe_points().
     pub fn from_json(json: &JsonValue) -> Self {
         let vertices_json = &json["vertices"];
         let vertex_a = Vec3::from_json(&vertices_json[0], Vec3::zero());
@@ -803,16 +1469,39 @@ impl Tetrahedron {
         let vertex_d = Vec3::from_json(
             &vertices_json[3], Vec3::new(0.0, 0.0, 1.0));
         let vertices = [vertex_a, vertex_b, vertex_c, vertex_d];
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let side_points = &json["side_points"]
-            .as_usize()
-            .expect("side_points must be a positive integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let fill = Fill::from_json(&json["fill"]);
+        if fill == Fill::Wireframe {
+            let num_points = parse_count(json, "num_points", "tetrahedron");
+            return Self {
+                vertices,
+                side_points: 0,
+                color,
+                fill,
+                num_points,
+            };
+        }
+
+        let side_points = parse_side_points(json, "tetrahedron");
 
         Self {
             vertices,
-            side_points: *side_points,
+            side_points,
             color,
+            fill,
+            num_points: 0,
+        }
+    }
+
+    /// The 6 edges between each pair of vertices
+    fn edges(&self) -> Vec<(Vec3, Vec3)> {
+        let mut edges = Vec::new();
+        for i in 0..4 {
+            for j in (i + 1)..4 {
+                edges.push((self.vertices[i], self.vertices[j]));
+            }
         }
+        edges
     }
 
     to_box!(Cluster);
@@ -822,10 +1511,33 @@ impl Cluster for Tetrahedron {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16) 
             -> Vec<InternalPoint> {
         
-        let n = self.side_points;
-        let denominator = (n - 1) as f32;
         let color = HalfMultivector::from_vec3(&self.color);
+        if self.fill == Fill::Wireframe {
+            let n = self.num_points;
+            let parameters = (0..n).map(|i| i as f64 / n as f64);
+            return wireframe_points(&self.edges(), parameters)
+                .into_iter()
+                .enumerate()
+                .map(|(i, (position, cluster_coordinates))| InternalPoint {
+                    position: HalfMultivector::from_vec3(&position),
+                    color: color.clone(),
+                    cluster_coordinates,
+                    iteration: 0,
+                    cluster_copy,
+                    cluster_id,
+                    point_id: i as u32,
+                    last_xform: 0,
+                    last_xform_group: 0,
+                    last_color_xform: 0,
+                    xform_address: 0,
+                    cga_pn: [0.0, 0.0],
+                    weight: 1
+                })
+                .collect();
+        }
 
+        let n = self.side_points;
+        let denominator = (n - 1) as f32;
         let [a, b, c, d] = self.vertices;
 
         let mut grid = Vec::new();
@@ -834,6 +1546,13 @@ impl Cluster for Tetrahedron {
             for j in 0..(n - i) {
                 let q = (j as f32) / denominator;
                 for k in 0..(n - i - j) {
+                    // On a face, one of the barycentric coordinates is 0
+                    let l = n - 1 - i - j - k;
+                    let on_face = i == 0 || j == 0 || k == 0 || l == 0;
+                    if self.fill == Fill::Surface && !on_face {
+                        continue;
+                    }
+
                     let r = (k as f32) / denominator;
                     let s = 1.0 - p - q - r;
 
@@ -850,9 +1569,13 @@ impl Cluster for Tetrahedron {
                         iteration: 0,
                         cluster_copy,
                         cluster_id,
-                        point_id: i as u16,
+                        point_id: grid.len() as u32,
                         last_xform: 0,
-                        last_color_xform: 0
+                        last_xform_group: 0,
+                        last_color_xform: 0,
+                        xform_address: 0,
+                        cga_pn: [0.0, 0.0],
+                        weight: 1
                     };
         
                     grid.push(point);
@@ -864,12 +1587,24 @@ impl Cluster for Tetrahedron {
     }
 
     fn point_count(&self) -> usize {
+        let tetrahedral_number = |n: usize| (n * (n + 1) * (n + 2)) / 6;
         let n = self.side_points;
-        (n * (n + 1) * (n + 2)) / 6
+        match self.fill {
+            Fill::Solid => tetrahedral_number(n),
+            // The points inside form a tetrahedron grid with 4 fewer
+            // points on each side
+            Fill::Surface =>
+                tetrahedral_number(n) - tetrahedral_number(n.saturating_sub(4)),
+            Fill::Wireframe => self.num_points
+        }
+    }
+
+    fn bounding_box(&self) -> BBox {
+        BBox::from_points(&self.vertices)
     }
 }
 
-/// Generate a box of evenly-spaced points
+/// Generate a box of points, evenly spaced by default
 pub struct GridBox {
     /// Center of the box
     center: Vec3,
@@ -892,6 +1627,10 @@ pub struct GridBox {
     /// Number of points in the box. This may be smaller than the value
     /// in the JSON
     num_points: usize,
+    /// How the points are spread through the box
+    sampler: Sampler,
+    /// Fill the volume, or only the faces or edges
+    fill: Fill,
 }
 
 impl GridBox {
@@ -904,20 +1643,47 @@ impl GridBox {
     ///     "y_dir": [yx, yy, yz],
     ///     "z_dir": [zx, zy, zz],
     ///     "dims": [x, y, z],
-    ///     "color": [r, g, b] // 0.0 to 1.0
-    ///     "num_points": N
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
+    ///     "num_points": N,
+    ///     "distribution": "grid" | "random" // default grid
+    ///     "fill": "solid" | "surface" | "wireframe" // default solid
     /// }
     /// ```
+    ///
+    /// With the grid distribution, surface keeps the points of the volume
+    /// grid that are on the faces, so there are fewer than num_points.
+    /// Random surface points are spread over the faces by area. The
+    /// wireframe has num_points along the 12 edges, evenly spaced for the
+    /// grid distribution. Its cluster coordinates are the parameter along
+    /// the edge and which edge it is, see wireframe_points().
     pub fn from_json(json: &JsonValue) -> Self {
         let center = Vec3::from_json(&json["center"], Vec3::zero());
         let dimensions = Vec3::from_json(&json["dims"], Vec3::ones());
         let x_dir = Vec3::from_json(&json["x_dir"], Vec3::new(1.0, 0.0, 0.0));
         let y_dir = Vec3::from_json(&json["y_dir"], Vec3::new(0.0, 1.0, 0.0));
         let z_dir = Vec3::from_json(&json["z_dir"], Vec3::new(0.0, 0.0, 1.0));
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_f64()
-            .expect("num_points must be a positive integer");
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &(parse_count(json, "num_points", "box") as f64);
+        let sampler = Sampler::from_json(
+            json, "box", &[Distribution::Grid, Distribution::Random]);
+        let fill = Fill::from_json(&json["fill"]);
+        if sampler.distribution != Distribution::Grid ||
+                fill == Fill::Wireframe {
+            return Self {
+                center,
+                dimensions,
+                x_dir,
+                y_dir,
+                z_dir,
+                color,
+                x_count: 0,
+                y_count: 0,
+                z_count: 0,
+                num_points: *num_points as usize,
+                sampler,
+                fill,
+            };
+        }
 
         let n = num_points;
 
@@ -932,7 +1698,16 @@ impl GridBox {
         let x_count = (dims_x * cbrt_density).floor() as usize;
         let y_count = (dims_y * cbrt_density).floor() as usize;
         let z_count = (dims_z * cbrt_density).floor() as usize;
-        let m = x_count * y_count * z_count;
+        let mut m = x_count * y_count * z_count;
+        if m == 0 {
+            panic!(
+                "box: num_points {} is too few for a {} by {} by {} grid",
+                n, dims_x, dims_y, dims_z);
+        }
+        if fill == Fill::Surface {
+            let inside = |count: usize| count.saturating_sub(2);
+            m -= inside(x_count) * inside(y_count) * inside(z_count);
+        }
 
         Self {
             center,
@@ -945,150 +1720,290 @@ impl GridBox {
             y_count,
             z_count,
             num_points: m,
+            sampler,
+            fill,
         }
     }
 
     to_box!(Cluster);
-}
 
-impl Cluster for GridBox {
-    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
-            -> Vec<InternalPoint> {
-        let mut grid = Vec::new();
-        let color = HalfMultivector::from_vec3(&self.color);
+    /// The position of the box coordinates (u, v, w) in [0, 1]^3
+    fn box_position(&self, u: f64, v: f64, w: f64) -> Vec3 {
+        let x = (*self.dimensions.x() as f64 * (u - 0.5)) as f32;
+        let y = (*self.dimensions.y() as f64 * (v - 0.5)) as f32;
+        let z = (*self.dimensions.z() as f64 * (w - 0.5)) as f32;
+        self.center + self.x_dir * x + self.y_dir * y + self.z_dir * z
+    }
+
+    /// The 12 edges between corners that differ in one coordinate
+    fn edges(&self) -> Vec<(Vec3, Vec3)> {
+        let corner = |i: usize| self.box_position(
+            (i & 1) as f64, ((i >> 1) & 1) as f64, ((i >> 2) & 1) as f64);
+        let mut edges = Vec::new();
+        for i in 0..8 {
+            for bit in [1, 2, 4].iter() {
+                if i & bit == 0 {
+                    edges.push((corner(i), corner(i | bit)));
+                }
+            }
+        }
+        edges
+    }
+
+    /// The box coordinates of the grid points in order, row by row and
+    /// layer by layer. For Fill::Surface, only the points on the faces
+    fn grid_coordinates(&self) -> Vec<(f64, f64, f64)> {
+        let on_side = |index: usize, count: usize|
+            index == 0 || index == count - 1;
+        let mut coordinates = Vec::new();
+        for layer in 0..self.z_count {
+            for row in 0..self.y_count {
+                for col in 0..self.x_count {
+                    let on_face = on_side(col, self.x_count) ||
+                        on_side(row, self.y_count) ||
+                        on_side(layer, self.z_count);
+                    if self.fill == Fill::Surface && !on_face {
+                        continue;
+                    }
+                    coordinates.push((
+                        (col as f64) / ((self.x_count - 1) as f64),
+                        (row as f64) / ((self.y_count - 1) as f64),
+                        (layer as f64) / ((self.z_count - 1) as f64),
+                    ));
+                }
+            }
+        }
+        coordinates
+    }
 
+    /// Random box coordinates on one of the faces, picking the face by
+    /// its area
+    fn random_surface_coordinates(&mut self) -> (f64, f64, f64) {
         let dims_x = *self.dimensions.x() as f64;
         let dims_y = *self.dimensions.y() as f64;
         let dims_z = *self.dimensions.z() as f64;
+        // The area of the faces perpendicular to x, y and z
+        let areas = [dims_y * dims_z, dims_x * dims_z, dims_x * dims_y];
+        let total: f64 = areas.iter().sum();
+
+        let mut choice = self.sampler.random() * total;
+        let mut axis = 0;
+        while axis < 2 && choice >= areas[axis] {
+            choice -= areas[axis];
+            axis += 1;
+        }
+        let side = if self.sampler.random() < 0.5 { 0.0 } else { 1.0 };
+        let a = self.sampler.random();
+        let b = self.sampler.random();
+        match axis {
+            0 => (side, a, b),
+            1 => (a, side, b),
+            _ => (a, b, side)
+        }
+    }
+}
 
-        for i in 0..self.num_points {
-            let layer = i / (self.x_count * self.y_count);
-            let row = (i / self.x_count) % self.y_count;
-            let col = i % self.x_count;
-            let u = (col as f64) / ((self.x_count - 1) as f64);
-            let v = (row as f64) / ((self.y_count - 1) as f64);
-            let w = (layer as f64) / ((self.z_count - 1) as f64);
-
-            let x = (dims_x * (u - 0.5)) as f32;
-            let y = (dims_y * (v - 0.5)) as f32;
-            let z = (dims_z * (w - 0.5)) as f32;
-
-            let position_vec3 = 
-                self.center + 
-                self.x_dir * x +
-                self.y_dir * y + 
-                self.z_dir * z;
-            let position = HalfMultivector::from_vec3(&position_vec3);
+impl Cluster for GridBox {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
+            -> Vec<InternalPoint> {
+        let color = HalfMultivector::from_vec3(&self.color);
+        let n = self.num_points;
+
+        // Each point's position and cluster coordinates
+        let placed: Vec<(Vec3, Vec3)> = match self.fill {
+            Fill::Wireframe => {
+                let parameters: Vec<f64> = match self.sampler.distribution {
+                    Distribution::Grid =>
+                        (0..n).map(|i| i as f64 / n as f64).collect(),
+                    _ => (0..n).map(|_| self.sampler.random()).collect()
+                };
+                wireframe_points(&self.edges(), parameters)
+            },
+            fill => {
+                let coordinates = match self.sampler.distribution {
+                    Distribution::Grid => self.grid_coordinates(),
+                    _ => (0..n).map(|_| match fill {
+                        Fill::Surface => self.random_surface_coordinates(),
+                        _ => (
+                            self.sampler.random(),
+                            self.sampler.random(),
+                            self.sampler.random()
+                        )
+                    }).collect()
+                };
+                coordinates.into_iter().map(|(u, v, w)| (
+                    self.box_position(u, v, w),
+                    Vec3::new(u as f32, v as f32, w as f32)
+                )).collect()
+            }
+        };
 
-            let point = InternalPoint {
-                position,
+        placed
+            .into_iter()
+            .enumerate()
+            .map(|(i, (position, cluster_coordinates))| InternalPoint {
+                position: HalfMultivector::from_vec3(&position),
                 color: color.clone(),
-                cluster_coordinates: Vec3::new(u as f32, v as f32, w as f32),
+                cluster_coordinates,
                 iteration: 0,
-                cluster_copy, 
+                cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
-            };
-
-            grid.push(point);
-        }
-
-        grid
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
+            })
+            .collect()
     }
 
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        let half_x = self.x_dir * (0.5 * *self.dimensions.x());
+        let half_y = self.y_dir * (0.5 * *self.dimensions.y());
+        let half_z = self.z_dir * (0.5 * *self.dimensions.z());
+        let mut corners = Vec::new();
+        for i in 0..8 {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+            corners.push(
+                self.center +
+                half_x * sign(1) +
+                half_y * sign(2) +
+                half_z * sign(4));
+        }
+        BBox::from_points(&corners)
+    }
 }
 
-/// Randomly generate N points constrained to a cuboid. The box is a solid
-/// color.
-pub struct RandomBox {
-    /// Center of the box
+/// Randomly generate N points on the lateral surface of a cylinder, or
+/// inside its volume. The cylinder is a solid color.
+pub struct RandomCylinder {
+    /// Center of the cylinder (halfway along the axis)
     center: Vec3,
-    /// width in the x, y, and z directions.
-    dimensions: Vec3,
-    /// The box starts off with a solid color (RGB from 0 to 1)
+    /// Unit vector along the axis of the cylinder
+    axis: Vec3,
+    /// Unit vector perpendicular to the axis
+    x_dir: Vec3,
+    /// Unit vector perpendicular to both the axis and x_dir
+    y_dir: Vec3,
+    /// Radius of the cylinder
+    radius: f64,
+    /// Height of the cylinder along the axis
+    height: f64,
+    /// If true, fill the volume. Otherwise, only the lateral surface
+    solid: bool,
+    /// The cylinder starts off with a solid color (RGB from 0 to 1)
     color: Vec3,
-    /// Number of points per box
+    /// Number of points per cylinder
     num_points: usize,
     /// Random number generator for generating points
-    rng: ThreadRng,
+    rng: RenderRng,
 }
 
-impl RandomBox {
-    pub fn new(
-            center: Vec3, 
-            dimensions: Vec3, 
-            color: Vec3, 
-            num_points: usize) -> Self {
-        Self {
-            center,
-            dimensions,
-            color,
-            num_points,
-            rng: rand::thread_rng(), 
-        }
-    }
-
-    to_box!(Cluster);
-
-    /// Parse a RandomBox generator from JSON of the form:
+impl RandomCylinder {
+    /// Parse a RandomCylinder generator from JSON of the form:
     /// ```text
     /// {
-    ///     "type": "box",
+    ///     "type": "cylinder",
     ///     "center": [x, y, z],
-    ///     "dimensions": [x, y, z],
-    ///     "color": [r, g, b] // 0.0 to 1.0
+    ///     "axis": [ax, ay, az], // does not need to be normalized
+    ///     "radius": r,
+    ///     "height": h,
+    ///     "solid": bool (default false)
+    ///     "color": [r, g, b] | str // see Vec3::color_from_json()
     ///     "num_points": N
     /// }
     /// ```
     pub fn from_json(json: &JsonValue) -> Self {
         let center = Vec3::from_json(&json["center"], Vec3::zero());
-        let dimensions = Vec3::from_json(&json["dims"], Vec3::ones());
-        let color = Vec3::from_json(&json["color"], Vec3::ones());
-        let num_points = &json["num_points"]
-            .as_usize()
-            .expect("num_points must be a positive integer");
+        let axis_json = Vec3::from_json(&json["axis"], Vec3::new(0.0, 0.0, 1.0));
+        let radius = parse_radius(json, "cylinder");
+        let height = json["height"].as_f64().unwrap_or(1.0);
+        let solid = json["solid"].as_bool().unwrap_or(false);
+        let color = Vec3::color_from_json(&json["color"], Vec3::ones());
+        let num_points = &parse_count(json, "num_points", "cylinder");
+
+        if axis_json.length() == 0.0 {
+            panic!("cylinder axis must not be the zero vector");
+        }
+        let axis = axis_json.normalize();
+
+        // Build an orthonormal frame around the axis. Start from whichever
+        // coordinate axis is least parallel to the cylinder axis
+        let helper = if axis.x().abs() < 0.9 {
+            Vec3::new(1.0, 0.0, 0.0)
+        } else {
+            Vec3::new(0.0, 1.0, 0.0)
+        };
+        let x_dir = helper.cross(&axis).normalize();
+        let y_dir = axis.cross(&x_dir);
 
-        Self::new(center, dimensions, color, *num_points)
+        Self {
+            center,
+            axis,
+            x_dir,
+            y_dir,
+            radius,
+            height,
+            solid,
+            color,
+            num_points: *num_points,
+            rng: random::rng(),
+        }
     }
+
+    to_box!(Cluster);
 }
 
-impl Cluster for RandomBox {
+impl Cluster for RandomCylinder {
     fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
             -> Vec<InternalPoint> {
         let mut points = Vec::new();
-
-        // Find the bounding box for generating points
-        let half_dims = self.dimensions.scale(0.5);
-        let min = self.center - half_dims;
-        let max = self.center + half_dims;
         let color = HalfMultivector::from_vec3(&self.color);
 
-        // Generate N random points, uniformly distributed over the box.
         for i in 0..self.num_points {
-            let u = self.rng.gen_range(0.0, 1.0);
-            let v = self.rng.gen_range(0.0, 1.0);
-            let w = self.rng.gen_range(0.0, 1.0);
-            let x = (1.0 - u) * min.x() + u * max.x();
-            let y = (1.0 - v) * min.y() + v * max.y();
-            let z = (1.0 - w) * min.z() + w * max.z();
-            
-            let position = HalfMultivector::point(x as f64, y as f64, z as f64);
+            let u: f64 = self.rng.gen_range(0.0, 1.0);
+            let v: f64 = self.rng.gen_range(0.0, 1.0);
+            let angle = 2.0 * PI * u;
+
+            // For a uniform distribution over the disk cross-section, the
+            // radius must be proportional to the square root
+            let r = if self.solid {
+                let w: f64 = self.rng.gen_range(0.0, 1.0);
+                self.radius * w.sqrt()
+            } else {
+                self.radius
+            };
+
+            let x = (r * angle.cos()) as f32;
+            let y = (r * angle.sin()) as f32;
+            let h = (self.height * (v - 0.5)) as f32;
+            let position_vec3 =
+                self.center +
+                self.x_dir * x +
+                self.y_dir * y +
+                self.axis * h;
+            let position = HalfMultivector::from_vec3(&position_vec3);
 
             let point = InternalPoint {
                 position,
                 color: color.clone(),
-                cluster_coordinates: Vec3::new(u, v, w),
+                cluster_coordinates: Vec3::new(u as f32, v as f32, 0.0),
                 iteration: 0,
                 cluster_copy,
                 cluster_id,
-                point_id: i as u16,
+                point_id: i as u32,
                 last_xform: 0,
-                last_color_xform: 0
+                last_xform_group: 0,
+                last_color_xform: 0,
+                xform_address: 0,
+                cga_pn: [0.0, 0.0],
+                weight: 1
             };
 
             points.push(point);
@@ -1100,6 +2015,18 @@ impl Cluster for RandomBox {
     fn point_count(&self) -> usize {
         self.num_points
     }
+
+    fn bounding_box(&self) -> BBox {
+        // The cylinder is the disk swept along the axis, so take the
+        // bounding boxes of the disks at either end
+        let r = self.radius as f32;
+        let half_height = self.axis * (0.5 * self.height as f32);
+        let a = self.x_dir * r;
+        let b = self.y_dir * r;
+        let top = ellipse_bounding_box(&(self.center + half_height), &a, &b);
+        let bottom = ellipse_bounding_box(&(self.center - half_height), &a, &b);
+        top.union(&bottom)
+    }
 }
 
 /// A cluster that represents the union of several sub-clusters
@@ -1114,13 +2041,43 @@ impl ManyClusters {
     /// {
     ///     "type": "many",
     ///     "clusters": [cluster_json, cluster_json, ...]
-    ///     "num_points": N
+    ///     "num_points": N (optional),
+    ///     "weights": [w, w, ...] (optional, default equal weights)
     /// }
     /// ```
-    pub fn from_json(json: &JsonValue) -> Self {
+    ///
+    /// If num_points is given, it is the total number of points. It is
+    /// divided among the sub-clusters in proportion to the weights, 
+    /// replacing their own num_points. Cluster types that don't have
+    /// a num_points parameter (e.g. points) ignore this. Every sub-cluster
+    /// must get at least one point.
+    ///
+    /// depth is how many clusters this one is nested in, see
+    /// MAX_NESTING_DEPTH.
+    pub fn from_json(json: &JsonValue, depth: usize) -> Self {
+        let cluster_count = json["clusters"].len();
+        let counts = json["num_points"].as_usize().map(|total| {
+            let weights = parse_weights(&json["weights"], cluster_count);
+            let counts = distribute_points(total, &weights);
+            // Each cluster rejects num_points: 0, so explain the cause here
+            if let Some(i) = counts.iter().position(|count| *count == 0) {
+                panic!(
+                    "many: num_points {} leaves cluster {} with no points, \
+                    use more points or a larger weight", total, i);
+            }
+            counts
+        });
+
         let mut clusters = Vec::new();
-        for cluster_json in json["clusters"].members() {
-            let cluster = from_json(&cluster_json);
+        for (i, cluster_json) in json["clusters"].members().enumerate() {
+            let cluster = match &counts {
+                Some(counts) => {
+                    let mut child_json = cluster_json.clone();
+                    child_json["num_points"] = counts[i].into();
+                    from_json_at_depth(&child_json, depth + 1)
+                },
+                None => from_json_at_depth(cluster_json, depth + 1)
+            };
             clusters.push(cluster);
         }
 
@@ -1154,6 +2111,149 @@ impl Cluster for ManyClusters {
     fn subcluster_max_point_count(&self) -> usize {
         self.clusters.iter().map(|x| x.point_count()).max().unwrap_or(0)
     }
+
+    fn bounding_box(&self) -> BBox {
+        self.clusters.iter()
+            .map(|x| x.bounding_box())
+            .reduce(|acc, bbox| acc.union(&bbox))
+            .unwrap_or_else(|| BBox::from_points(&[]))
+    }
+}
+
+/// Like ManyClusters, but each copy of the cluster is drawn from only one
+/// of the sub-clusters, cycling through the list. This is for chaos_sets
+/// renders where each copy has a different shape.
+pub struct RotateThrough {
+    /// The sub-clusters, copy i uses clusters[i % clusters.len()]
+    clusters: Vec<Box<dyn Cluster>>
+}
+
+impl RotateThrough {
+    /// Parse a Cluster generator from JSON of the form:
+    /// ```text
+    /// {
+    ///     "type": "rotate_through",
+    ///     "clusters": [cluster_json, cluster_json, ...]
+    /// }
+    /// ```
+    ///
+    /// depth works the same as for ManyClusters::from_json()
+    pub fn from_json(json: &JsonValue, depth: usize) -> Self {
+        let clusters: Vec<Box<dyn Cluster>> = json["clusters"]
+            .members()
+            .map(|cluster_json| from_json_at_depth(cluster_json, depth + 1))
+            .collect();
+        if clusters.is_empty() {
+            panic!("rotate_through: clusters must be a non-empty list");
+        }
+
+        Self {
+            clusters
+        }
+    }
+
+    to_box!(Cluster);
+}
+
+impl Cluster for RotateThrough {
+    fn generate(&mut self, cluster_copy: u16, _cluster_id: u16)
+            -> Vec<InternalPoint> {
+        let index = cluster_copy as usize % self.clusters.len();
+        self.clusters[index].generate(cluster_copy, index as u16)
+    }
+
+    fn subcluster_count(&self) -> usize {
+        self.clusters.len()
+    }
+
+    /// Different copies have different sizes, so use the largest one so
+    /// the complexity is an upper bound
+    fn point_count(&self) -> usize {
+        self.subcluster_max_point_count()
+    }
+
+    fn subcluster_max_point_count(&self) -> usize {
+        self.clusters.iter().map(|x| x.point_count()).max().unwrap_or(0)
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.clusters.iter()
+            .map(|x| x.bounding_box())
+            .reduce(|acc, bbox| acc.union(&bbox))
+            .unwrap_or_else(|| BBox::from_points(&[]))
+    }
+}
+
+/// Parse the weights for dividing points among the sub-clusters of
+/// ManyClusters. If not specified, all sub-clusters are weighted equally.
+fn parse_weights(json: &JsonValue, cluster_count: usize) -> Vec<f64> {
+    if json.is_null() {
+        return vec![1.0; cluster_count];
+    }
+
+    let weights: Vec<f64> = json.members()
+        .map(|x| x.as_f64().expect("weights must be numbers"))
+        .collect();
+
+    if weights.len() != cluster_count {
+        panic!(
+            "weights must have one entry per cluster, got {} for {} clusters",
+            weights.len(),
+            cluster_count);
+    }
+
+    if weights.iter().any(|x| *x < 0.0) {
+        panic!("weights must be non-negative");
+    }
+
+    if weights.iter().sum::<f64>() <= 0.0 {
+        panic!("at least one weight must be positive");
+    }
+
+    weights
+}
+
+/// Divide a total number of points in proportion to the weights. Each
+/// count is rounded down, and the leftover points go to the counts with
+/// the largest remainders so the total is exact.
+fn distribute_points(total: usize, weights: &[f64]) -> Vec<usize> {
+    let weight_sum: f64 = weights.iter().sum();
+    let exact: Vec<f64> = weights.iter()
+        .map(|weight| total as f64 * weight / weight_sum)
+        .collect();
+    let mut counts: Vec<usize> = 
+        exact.iter().map(|x| x.floor() as usize).collect();
+
+    let assigned: usize = counts.iter().sum();
+    let mut by_remainder: Vec<usize> = (0..counts.len()).collect();
+    by_remainder.sort_by(|a, b| {
+        let remainder_a = exact[*a] - exact[*a].floor();
+        let remainder_b = exact[*b] - exact[*b].floor();
+        remainder_b.partial_cmp(&remainder_a).unwrap()
+    });
+    for i in by_remainder.into_iter().take(total - assigned) {
+        counts[i] += 1;
+    }
+
+    counts
+}
+
+/// The average position of a set of generated points, or the origin if
+/// there are none
+pub fn centroid(points: &[InternalPoint]) -> Vec3 {
+    if points.is_empty() {
+        return Vec3::zero();
+    }
+
+    let mut sum = [0.0; 3];
+    for point in points.iter() {
+        let (x, y, z) = point.position.coordinates();
+        sum[0] += x;
+        sum[1] += y;
+        sum[2] += z;
+    }
+    let n = points.len() as f64;
+    Vec3::new((sum[0] / n) as f32, (sum[1] / n) as f32, (sum[2] / n) as f32)
 }
 
 /// Parse one of the initial set types from a JSON value of the form:
@@ -1161,55 +2261,1041 @@ impl Cluster for ManyClusters {
 /// {
 ///     "type": 
 ///         "points" | 
+///         "file" | 
 ///         "line" | 
 ///         "rand_line" | 
 ///         "circle" | 
 ///         "triangle" |
+///         "mesh" |
 ///         "quad" |
 ///         "disk" |
 ///         "sphere" | 
 ///         "tetrahedron" |
 ///         "box" |
-///         "rand_box"
+///         "rand_box" |
+///         "cylinder" |
+///         "many" |
+///         "rotate_through" |
+///         "transformed"
+///     "color_jitter": amount (optional),
 ///     ...params
 /// }
 /// ```
+///
+/// rand_line and rand_box are the same as line and box with
+/// "distribution": "random", see Distribution.
+///
+/// With color_jitter, every point's color is nudged by up to amount in
+/// each channel, see ColorJitter.
 pub fn from_json(json: &JsonValue) -> Box<dyn Cluster> {
+    from_json_at_depth(json, 0)
+}
+
+/// Mix the bits of a number so that nearby inputs give unrelated outputs.
+/// This is the splitmix64 finalizer.
+fn mix_bits(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+/// Wraps another cluster and nudges the color of each generated point by
+/// a small offset in each channel, which varies both the hue and the
+/// brightness. This breaks up solid-colored clusters without a color IFS.
+/// The offsets are a hash of the point's ids rather than random, so the
+/// same parameter file always gives the same colors.
+pub struct ColorJitter {
+    cluster: Box<dyn Cluster>,
+    /// The largest offset in each color channel
+    amount: f32,
+}
+
+impl ColorJitter {
+    pub fn new(cluster: Box<dyn Cluster>, amount: f32) -> Self {
+        Self {
+            cluster,
+            amount
+        }
+    }
+
+    /// The offset of one color channel of a point, in [-amount, amount]
+    fn offset(&self, point: &InternalPoint, channel: u64) -> f32 {
+        let ids = (point.cluster_copy as u64) << 48 |
+            (point.cluster_id as u64) << 32 |
+            point.point_id as u64;
+        let hash = mix_bits(mix_bits(ids).wrapping_add(channel));
+        // The top 24 bits fit exactly in an f32
+        let unit = (hash >> 40) as f32 / (1u64 << 24) as f32;
+        self.amount * (2.0 * unit - 1.0)
+    }
+
+    to_box!(Cluster);
+}
+
+impl Cluster for ColorJitter {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
+            -> Vec<InternalPoint> {
+        let mut points = self.cluster.generate(cluster_copy, cluster_id);
+        for point in points.iter_mut() {
+            let color = match point.color.try_to_vec3() {
+                Some(color) => color,
+                None => continue
+            };
+            let jittered = Vec3::new(
+                (color.x() + self.offset(point, 0)).clamp(0.0, 1.0),
+                (color.y() + self.offset(point, 1)).clamp(0.0, 1.0),
+                (color.z() + self.offset(point, 2)).clamp(0.0, 1.0));
+            point.color = HalfMultivector::from_vec3(&jittered);
+        }
+        points
+    }
+
+    fn subcluster_count(&self) -> usize {
+        self.cluster.subcluster_count()
+    }
+
+    fn point_count(&self) -> usize {
+        self.cluster.point_count()
+    }
+
+    fn subcluster_max_point_count(&self) -> usize {
+        self.cluster.subcluster_max_point_count()
+    }
+
+    fn bounding_box(&self) -> BBox {
+        self.cluster.bounding_box()
+    }
+}
+
+/// How far a transformed midpoint may be from the midpoint of the
+/// transformed endpoints for TransformedCluster to treat the
+/// transformation as affine
+const AFFINE_TOLERANCE: f32 = 1e-4;
+
+/// Wraps another cluster and moves every generated point by a
+/// transformation, so any shape can be placed and oriented with the same
+/// transformations as the IFS. Only the positions change; the colors and
+/// cluster coordinates are the inner cluster's.
+pub struct TransformedCluster {
+    cluster: Box<dyn Cluster>,
+    xform: Box<dyn Transform>,
+}
+
+impl TransformedCluster {
+    pub fn new(cluster: Box<dyn Cluster>, xform: Box<dyn Transform>) -> Self {
+        Self {
+            cluster,
+            xform
+        }
+    }
+
+    /// Parse a Cluster generator from JSON of the form:
+    /// ```text
+    /// {
+    ///     "type": "transformed",
+    ///     "xform": <Transform JSON>,
+    ///     "cluster": cluster_json
+    /// }
+    /// ```
+    ///
+    /// If num_points is given (e.g. by a many cluster), it replaces the
+    /// inner cluster's num_points. depth works the same as for
+    /// ManyClusters::from_json()
+    pub fn from_json(json: &JsonValue, depth: usize) -> Self {
+        if !json["cluster"].is_object() {
+            panic!("transformed: cluster must be a cluster object");
+        }
+        if json["xform"].is_null() {
+            panic!("transformed: xform is required");
+        }
+
+        let cluster = match &json["num_points"] {
+            JsonValue::Null => from_json_at_depth(&json["cluster"], depth + 1),
+            num_points => {
+                let mut child_json = json["cluster"].clone();
+                child_json["num_points"] = num_points.clone();
+                from_json_at_depth(&child_json, depth + 1)
+            }
+        };
+        let xform = xforms::from_json(&json["xform"]);
+        Self::new(cluster, xform)
+    }
+
+    /// Check whether the transformation looks affine over a box: for every
+    /// pair of corners, the midpoint must map to the midpoint of the
+    /// transformed corners. The pairs cover the edges, the face diagonals
+    /// and the center, which catches inversions, warps and other
+    /// transformations that bend lines.
+    fn is_affine_over(&self, bbox: &BBox) -> bool {
+        let transform = |point: &Vec3| -> Option<Vec3> {
+            self.xform
+                .transform(&HalfMultivector::from_vec3(point))
+                .try_to_vec3()
+        };
+
+        let corners = bbox.corners();
+        for (i, a) in corners.iter().enumerate() {
+            for b in corners.iter().skip(i + 1) {
+                let midpoint = (*a + *b) * 0.5;
+                let (fa, fb, fm) = match (
+                        transform(a), transform(b), transform(&midpoint)) {
+                    (Some(fa), Some(fb), Some(fm)) => (fa, fb, fm),
+                    _ => return false
+                };
+                let error = (fm - (fa + fb) * 0.5).length();
+                let scale = 1.0 + (fa - fb).length();
+                if error > AFFINE_TOLERANCE * scale {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    to_box!(Cluster);
+}
+
+impl Cluster for TransformedCluster {
+    fn generate(&mut self, cluster_copy: u16, cluster_id: u16)
+            -> Vec<InternalPoint> {
+        let mut points = self.cluster.generate(cluster_copy, cluster_id);
+        for point in points.iter_mut() {
+            point.position = self.xform.transform(&point.position);
+        }
+        points
+    }
+
+    fn subcluster_count(&self) -> usize {
+        self.cluster.subcluster_count()
+    }
+
+    fn point_count(&self) -> usize {
+        self.cluster.point_count()
+    }
+
+    fn subcluster_max_point_count(&self) -> usize {
+        self.cluster.subcluster_max_point_count()
+    }
+
+    /// An affine transformation maps the inner box to a parallelepiped
+    /// spanned by the transformed corners, so their bounding box holds
+    /// every point. Other transformations can bulge past the corners, so
+    /// they get the generous default box.
+    fn bounding_box(&self) -> BBox {
+        let inner = self.cluster.bounding_box();
+        if !self.is_affine_over(&inner) {
+            return unknown_bounds();
+        }
+
+        let corners: Vec<Vec3> = inner
+            .corners()
+            .iter()
+            .map(|corner| self.xform
+                .transform(&HalfMultivector::from_vec3(corner))
+                .to_vec3())
+            .collect();
+        BBox::from_points(&corners)
+    }
+}
+
+/// How deeply many, rotate_through and transformed clusters can be nested.
+/// Parsing recurses into the sub-clusters, so without a limit a deeply
+/// nested parameter file would overflow the stack rather than give an
+/// error.
+const MAX_NESTING_DEPTH: usize = 32;
+
+/// Parse a cluster that is nested inside depth other clusters
+fn from_json_at_depth(json: &JsonValue, depth: usize) -> Box<dyn Cluster> {
+    if depth > MAX_NESTING_DEPTH {
+        panic!(
+            "clusters can be nested at most {} levels deep",
+            MAX_NESTING_DEPTH);
+    }
+
     let valid_types: Vec<&str> = vec![
         "points",
+        "file",
         "line",
         "rand_line",
         "circle",
         "triangle",
+        "mesh",
         "quad",
         "disk",
         "sphere",
         "tetrahedron",
         "box",
-        "rand_box"
+        "rand_box",
+        "cylinder",
+        "many",
+        "rotate_through",
+        "transformed"
     ];
     let type_id = &json["type"]
         .as_str()
         .expect("type must be a string");
 
-    match &type_id[..] {
-        "many" => ManyClusters::from_json(&json).to_box(),
+    let cluster = match &type_id[..] {
+        "many" => ManyClusters::from_json(&json, depth).to_box(),
+        "rotate_through" => RotateThrough::from_json(json, depth).to_box(),
+        "transformed" =>
+            TransformedCluster::from_json(json, depth).to_box(),
         // 0-dimensional
         "points" => Points::from_json(&json).to_box(),
+        "file" => FileCluster::from_json(json).to_box(),
         // 1-dimensional
         "line" => Line::from_json(&json).to_box(),
-        "rand_line" => RandomLine::from_json(&json).to_box(),
+        "rand_line" => Line::from_json(&random_alias(json)).to_box(),
         "circle" => Circle::from_json(&json).to_box(),
         // 2-dimensional
         "triangle" => Triangle::from_json(&json).to_box(),
+        "mesh" => MeshSurface::from_json(json).to_box(),
         "quad" => GridQuad::from_json(&json).to_box(),
         "disk" => FibonacciDisk::from_json(&json).to_box(),
         "sphere" => FibonacciSphere::from_json(&json).to_box(),
         // 3-dimensional
         "tetrahedron" => Tetrahedron::from_json(&json).to_box(),
         "box" => GridBox::from_json(&json).to_box(),
-        "rand_box" => RandomBox::from_json(&json).to_box(),
+        "rand_box" => GridBox::from_json(&random_alias(json)).to_box(),
+        "cylinder" => RandomCylinder::from_json(&json).to_box(),
         _ => panic!(
             "Initial set type {} must be one of {:?}", type_id, valid_types)
+    };
+
+    match &json["color_jitter"] {
+        JsonValue::Null => cluster,
+        amount => {
+            let amount = amount
+                .as_f32()
+                .filter(|amount| *amount >= 0.0)
+                .expect("color_jitter must be a non-negative number");
+            ColorJitter::new(cluster, amount).to_box()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn assert_unique_ids(points: &[InternalPoint]) {
+        let ids: HashSet<u32> = points.iter().map(|x| x.point_id).collect();
+        assert_eq!(ids.len(), points.len());
+    }
+
+    fn assert_same_box(actual: &BBox, expected: &BBox) {
+        assert!(
+            actual.contains_box(expected) && expected.contains_box(actual),
+            "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_harmonic_sphere_colors() {
+        let mut sphere = FibonacciSphere::from_json(&object!{
+            "type" => "sphere",
+            "color" => "harmonic",
+            "num_points" => 500
+        });
+        let points = sphere.generate(0, 0);
+        let colors: Vec<Vec3> = points
+            .iter()
+            .map(|point| point.color.to_vec3())
+            .collect();
+
+        for color in colors.iter() {
+            for channel in [color.x(), color.y(), color.z()].iter() {
+                assert!(**channel >= -1e-6 && **channel <= 1.0 + 1e-6);
+            }
+        }
+
+        // The colors are not all the same
+        let first = colors[0];
+        assert!(colors.iter().any(|color| (*color - first).length() > 0.5));
+
+        // At the north pole, only the zonal harmonic is nonzero
+        let pole = harmonic_color(0.0, 0.0);
+        assert!((pole - Vec3::new(0.5, 0.5, 1.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_points_position_coordinates() {
+        let json = object!{
+            "type" => "points",
+            "positions" => array![
+                array![-1.0, 2.0, 5.0],
+                array![3.0, 2.0, 5.0],
+                array![0.0, 4.0, 5.0]
+            ],
+            "coordinate_mode" => "position"
+        };
+        let mut cluster = Points::from_json(&json);
+        let points = cluster.generate(0, 0);
+        let expected = [
+            (0.0, 0.0, 0.5),
+            (1.0, 0.0, 0.5),
+            (0.25, 1.0, 0.5),
+        ];
+        for (point, (x, y, z)) in points.iter().zip(expected.iter()) {
+            let error = (point.cluster_coordinates - Vec3::new(*x, *y, *z))
+                .length();
+            assert!(error < 1e-6, "{:?}", point.cluster_coordinates);
+        }
+
+        // The default is still the index ramp
+        let mut json = json;
+        json.remove("coordinate_mode");
+        let points = Points::from_json(&json).generate(0, 0);
+        assert_eq!(*points[2].cluster_coordinates.x(), 0.5);
+        assert_eq!(*points[2].cluster_coordinates.y(), 0.0);
+    }
+
+    #[test]
+    fn test_mesh_surface_is_area_weighted() {
+        // Two triangles in separate planes, the second has 3 times the
+        // area of the first
+        let small = [
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0)
+        ];
+        let large = [
+            Vec3::new(0.0, 0.0, 1.0),
+            Vec3::new(3.0, 0.0, 1.0),
+            Vec3::new(0.0, 1.0, 1.0)
+        ];
+        let mut mesh = MeshSurface::new(vec![small, large], Vec3::ones(), 4000);
+        let points = mesh.generate(0, 0);
+        assert_eq!(points.len(), 4000);
+
+        let bounds = mesh.bounding_box();
+        let mut large_count = 0;
+        for point in points.iter() {
+            let position = point.position.to_vec3();
+            assert!(bounds.contains_box(&BBox::from_points(&[position])));
+            let z = *position.z();
+            assert!(z.abs() < 1e-5 || (z - 1.0).abs() < 1e-5);
+            if z > 0.5 {
+                large_count += 1;
+            }
+        }
+        let fraction = large_count as f32 / 4000.0;
+        assert!((fraction - 0.75).abs() < 0.05, "{}", fraction);
+    }
+
+    #[test]
+    fn test_distribute_points() {
+        assert_eq!(distribute_points(10, &[1.0, 2.0, 1.0]), vec![3, 5, 2]);
+        assert_eq!(distribute_points(9, &[1.0, 1.0, 1.0]), vec![3, 3, 3]);
+        assert_eq!(distribute_points(5, &[0.0, 1.0]), vec![0, 5]);
+    }
+
+    #[test]
+    fn test_many_clusters_total_points() {
+        let json = object!{
+            "type" => "many",
+            "num_points" => 1000,
+            "weights" => array![3, 1],
+            "clusters" => array![
+                object!{
+                    "type" => "rand_line",
+                    "num_points" => 5
+                },
+                object!{
+                    "type" => "rand_box"
+                }
+            ]
+        };
+        let cluster = ManyClusters::from_json(&json, 0);
+        assert_eq!(cluster.point_count(), 1000);
+        assert_eq!(cluster.clusters[0].point_count(), 750);
+        assert_eq!(cluster.clusters[1].point_count(), 250);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_many_clusters_weights_length() {
+        let json = object!{
+            "type" => "many",
+            "num_points" => 100,
+            "weights" => array![1],
+            "clusters" => array![
+                object!{ "type" => "rand_line" },
+                object!{ "type" => "rand_box" }
+            ]
+        };
+        ManyClusters::from_json(&json, 0);
+    }
+
+    /// A point cluster wrapped in the given number of many clusters
+    fn nested_many(levels: usize) -> JsonValue {
+        let mut json = object!{
+            "type" => "points",
+            "positions" => array![array![0, 0, 0]]
+        };
+        for _ in 0..levels {
+            let mut many = object!{ "type" => "many" };
+            many["clusters"] = array![json];
+            json = many;
+        }
+        json
+    }
+
+    #[test]
+    fn test_nesting_at_limit() {
+        let cluster = from_json(&nested_many(MAX_NESTING_DEPTH));
+        assert_eq!(cluster.point_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "clusters can be nested at most 32 levels deep")]
+    fn test_nesting_too_deep() {
+        from_json(&nested_many(1000));
+    }
+
+    #[test]
+    fn test_rotate_through_cycles_clusters() {
+        let json = object!{
+            "type" => "rotate_through",
+            "clusters" => array![
+                object!{
+                    "type" => "points",
+                    "positions" => array![array![0.0, 0.0, 0.0]]
+                },
+                object!{
+                    "type" => "rand_line",
+                    "num_points" => 5
+                }
+            ]
+        };
+        let mut cluster = RotateThrough::from_json(&json, 0);
+        assert_eq!(cluster.point_count(), 5);
+        assert_eq!(cluster.subcluster_count(), 2);
+
+        let sizes: Vec<usize> = (0..4)
+            .map(|copy| cluster.generate(copy, 0).len())
+            .collect();
+        assert_eq!(sizes, vec![1, 5, 1, 5]);
+        let second = cluster.generate(3, 0);
+        assert!(second.iter().all(|x| x.cluster_id == 1 && x.cluster_copy == 3));
+    }
+
+    #[test]
+    fn test_circle_bounding_box() {
+        let json = object!{
+            "type" => "circle",
+            "center" => array![1.0, 0.0, 0.0],
+            "radius" => 2.0,
+            "num_points" => 10
+        };
+        let circle = Circle::from_json(&json);
+        let expected = BBox::new(-1.0, 3.0, -2.0, 2.0, 0.0, 0.0);
+        assert_same_box(&circle.bounding_box(), &expected);
+    }
+
+    #[test]
+    fn test_bounding_box_contains_generated_points() {
+        let json = object!{
+            "type" => "box",
+            "center" => array![0.5, 0.0, -1.0],
+            "dims" => array![1.0, 2.0, 3.0],
+            "num_points" => 1000
+        };
+        let mut grid = GridBox::from_json(&json);
+        let bounds = grid.bounding_box();
+        for point in grid.generate(0, 0) {
+            let position = point.position.to_vec3();
+            let point_box = BBox::from_points(&[position]);
+            assert!(bounds.contains_box(&point_box));
+        }
+    }
+
+    #[test]
+    fn test_many_clusters_bounding_box_is_union() {
+        let json = object!{
+            "type" => "many",
+            "clusters" => array![
+                object!{
+                    "type" => "line",
+                    "start" => array![-1.0, 0.0, 0.0],
+                    "end" => array![0.0, 0.0, 0.0],
+                    "num_points" => 2
+                },
+                object!{
+                    "type" => "sphere",
+                    "center" => array![2.0, 0.0, 0.0],
+                    "radius" => 1.0,
+                    "num_points" => 10
+                }
+            ]
+        };
+        let many = ManyClusters::from_json(&json, 0);
+        let expected = BBox::new(-1.0, 3.0, -1.0, 1.0, -1.0, 1.0);
+        assert_same_box(&many.bounding_box(), &expected);
+    }
+
+    #[test]
+    fn test_point_ids_unique_past_u16() {
+        let json = object!{
+            "type" => "rand_box",
+            "num_points" => 70000
+        };
+        let mut random_box = from_json(&json);
+        let points = random_box.generate(0, 0);
+        assert_eq!(points.len(), 70000);
+        assert_unique_ids(&points);
+        assert_eq!(points.last().unwrap().point_id, 69999);
+    }
+
+    /// Distance from a point to the closest of the line segments
+    fn distance_to_edges(position: &Vec3, edges: &[(Vec3, Vec3)]) -> f32 {
+        edges.iter().map(|(a, b)| {
+            let edge = *b - *a;
+            let t = ((*position - *a).dot(&edge) / edge.dot(&edge))
+                .clamp(0.0, 1.0);
+            (*position - (*a + edge * t)).length()
+        }).fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn test_wireframe_points_lie_on_edges() {
+        for distribution in ["grid", "random"].iter() {
+            let json = object!{
+                "type" => "box",
+                "center" => array![1.0, -1.0, 0.5],
+                "dims" => array![2.0, 1.0, 0.5],
+                "num_points" => 500,
+                "distribution" => *distribution,
+                "fill" => "wireframe"
+            };
+            let mut cluster = GridBox::from_json(&json);
+            let edges = cluster.edges();
+            assert_eq!(edges.len(), 12);
+            let points = cluster.generate(0, 0);
+            assert_eq!(points.len(), 500);
+            assert_eq!(cluster.point_count(), 500);
+            assert_unique_ids(&points);
+            for point in points.iter() {
+                let position = point.position.to_vec3();
+                assert!(distance_to_edges(&position, &edges) < 1e-5);
+            }
+        }
+
+        let json = object!{
+            "type" => "tetrahedron",
+            "num_points" => 300,
+            "fill" => "wireframe"
+        };
+        let mut cluster = Tetrahedron::from_json(&json);
+        let edges = cluster.edges();
+        let points = cluster.generate(0, 0);
+        assert_eq!(points.len(), 300);
+        for point in points.iter() {
+            let position = point.position.to_vec3();
+            assert!(distance_to_edges(&position, &edges) < 1e-5);
+            let coordinates = point.cluster_coordinates;
+            assert!((0.0..=1.0).contains(coordinates.x()));
+            assert!((0.0..=1.0).contains(coordinates.y()));
+        }
+    }
+
+    #[test]
+    fn test_wireframe_grid_is_evenly_spaced() {
+        // A unit cube has 12 edges of length 1, so 24 points land on every
+        // corner and every midpoint
+        let json = object!{
+            "type" => "box",
+            "num_points" => 24,
+            "fill" => "wireframe"
+        };
+        let points = GridBox::from_json(&json).generate(0, 0);
+        for point in points.iter() {
+            let t = *point.cluster_coordinates.x();
+            assert!(t.abs() < 1e-5 || (t - 0.5).abs() < 1e-5, "{}", t);
+        }
+    }
+
+    #[test]
+    fn test_surface_points_lie_on_faces() {
+        for distribution in ["grid", "random"].iter() {
+            let json = object!{
+                "type" => "box",
+                "num_points" => 1000,
+                "distribution" => *distribution,
+                "fill" => "surface"
+            };
+            let mut cluster = GridBox::from_json(&json);
+            let points = cluster.generate(0, 0);
+            assert_eq!(points.len(), cluster.point_count());
+            for point in points.iter() {
+                let position = point.position.to_vec3();
+                let on_face = [position.x(), position.y(), position.z()]
+                    .iter()
+                    .any(|x| (x.abs() - 0.5).abs() < 1e-5);
+                assert!(on_face, "{:?}", position);
+            }
+        }
+
+        let json = object!{
+            "type" => "tetrahedron",
+            "side_points" => 6,
+            "fill" => "surface"
+        };
+        let mut cluster = Tetrahedron::from_json(&json);
+        let points = cluster.generate(0, 0);
+        // 56 points in the grid, and 4 inside it
+        assert_eq!(points.len(), 52);
+        assert_eq!(cluster.point_count(), 52);
+    }
+
+    #[test]
+    fn test_distributions() {
+        let shapes = [
+            ("line", vec!["grid", "random", "fibonacci"]),
+            ("circle", vec!["grid", "random", "fibonacci"]),
+            ("triangle", vec!["random", "fibonacci"]),
+            ("quad", vec!["grid", "random", "fibonacci"]),
+            ("disk", vec!["fibonacci", "random"]),
+            ("box", vec!["grid", "random"]),
+        ];
+        for (shape, distributions) in shapes.iter() {
+            for distribution in distributions {
+                let json = object!{
+                    "type" => *shape,
+                    "center" => array![1.0, -1.0, 0.5],
+                    "start" => array![1.0, 2.0, 3.0],
+                    "dims" => array![2.0, 1.0, 0.5],
+                    "radius" => 2.0,
+                    "num_points" => 100,
+                    "distribution" => *distribution
+                };
+                let mut cluster = from_json(&json);
+                let points = cluster.generate(0, 0);
+                assert_eq!(points.len(), cluster.point_count());
+                if *distribution == "grid" {
+                    assert!(points.len() <= 100);
+                } else {
+                    assert_eq!(points.len(), 100, "{} {}", shape, distribution);
+                }
+                assert_unique_ids(&points);
+
+                // Allow for rounding
+                let bounds = cluster.bounding_box();
+                let epsilon = Vec3::new(1e-5, 1e-5, 1e-5);
+                let bounds = BBox::from_points(
+                    &[*bounds.min() - epsilon, *bounds.max() + epsilon]);
+                for point in points.iter() {
+                    let position = point.position.to_vec3();
+                    assert!(
+                        bounds.contains(&position),
+                        "{} {}: {:?} outside {:?}",
+                        shape, distribution, position, bounds);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_triangle_inside() {
+        let json = object!{
+            "type" => "triangle",
+            "num_points" => 1000,
+            "distribution" => "random"
+        };
+        let mut triangle = Triangle::from_json(&json);
+        for point in triangle.generate(0, 0) {
+            let (x, y, _) = point.position.coordinates();
+            assert!(x >= -1e-6 && y >= -1e-6 && x + y <= 1.0 + 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_rand_aliases() {
+        let json = object!{
+            "type" => "rand_box",
+            "dims" => array![1.0, 1.0, 1.0],
+            "num_points" => 10
+        };
+        let first = from_json(&json).generate(0, 0);
+        let second = from_json(&json).generate(0, 0);
+        assert_eq!(first.len(), 10);
+        // Random points differ from run to run, grid points don't
+        assert!(first.iter().zip(second.iter()).any(
+            |(a, b)| a.position.coordinates() != b.position.coordinates()));
+    }
+
+    #[test]
+    #[should_panic(expected = "box clusters only support the distributions")]
+    fn test_unsupported_distribution() {
+        from_json(&object!{
+            "type" => "box",
+            "num_points" => 10,
+            "distribution" => "fibonacci"
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "sphere: num_points must be a positive integer")]
+    fn test_zero_points_rejected() {
+        from_json(&object!{
+            "type" => "sphere",
+            "num_points" => 0
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "tetrahedron: side_points must be an integer")]
+    fn test_zero_side_points_rejected() {
+        from_json(&object!{
+            "type" => "tetrahedron",
+            "side_points" => 0
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "triangle: side_points must be an integer of")]
+    fn test_single_side_point_rejected() {
+        from_json(&object!{
+            "type" => "triangle",
+            "side_points" => 1
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "disk: the fibonacci distribution needs")]
+    fn test_single_point_fibonacci_rejected() {
+        from_json(&object!{
+            "type" => "disk",
+            "num_points" => 1
+        });
+    }
+
+    #[test]
+    fn test_single_point_random_disk() {
+        let mut disk = from_json(&object!{
+            "type" => "disk",
+            "num_points" => 1,
+            "distribution" => "random"
+        });
+        assert_eq!(disk.generate(0, 0).len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "quad: num_points 3 is too few for a 4 by 1")]
+    fn test_empty_grid_rejected() {
+        from_json(&object!{
+            "type" => "quad",
+            "dims" => array![4.0, 1.0],
+            "num_points" => 3
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "many: num_points 1 leaves cluster 1 with no")]
+    fn test_many_clusters_too_few_points() {
+        from_json(&object!{
+            "type" => "many",
+            "clusters" => array![
+                object!{ "type" => "line" },
+                object!{ "type" => "line" }
+            ],
+            "num_points" => 1
+        });
+    }
+
+    #[test]
+    fn test_triangle_point_ids_unique() {
+        let json = object!{
+            "type" => "triangle",
+            "side_points" => 10
+        };
+        let mut triangle = Triangle::from_json(&json);
+        assert_unique_ids(&triangle.generate(0, 0));
+    }
+
+    #[test]
+    fn test_cylinder_surface_points_at_radius() {
+        let json = object!{
+            "type" => "cylinder",
+            "center" => array![1.0, 2.0, 3.0],
+            "axis" => array![1.0, 1.0, 0.0],
+            "radius" => 2.0,
+            "height" => 4.0,
+            "num_points" => 100
+        };
+        let mut cylinder = RandomCylinder::from_json(&json);
+        let center = Vec3::new(1.0, 2.0, 3.0);
+        let axis = Vec3::new(1.0, 1.0, 0.0).normalize();
+        for point in cylinder.generate(0, 0) {
+            let offset = point.position.to_vec3() - center;
+            let along = offset * axis;
+            let h = *along.x() + *along.y() + *along.z();
+            let radial = offset - axis * h;
+            assert!(h.abs() <= 2.0 + 1e-4);
+            assert!((radial.length() - 2.0).abs() < 1e-4);
+        }
+    }
+
+    fn colors(points: &[InternalPoint]) -> Vec<[f32; 3]> {
+        points
+            .iter()
+            .map(|point| {
+                let color = point.color.try_to_vec3().unwrap();
+                [*color.x(), *color.y(), *color.z()]
+            })
+            .collect()
+    }
+
+    fn jitter_box(color_jitter: JsonValue) -> Box<dyn Cluster> {
+        from_json(&object!{
+            "type" => "box",
+            "num_points" => 27,
+            "color" => array![0.5, 0.25, 1.0],
+            "color_jitter" => color_jitter
+        })
+    }
+
+    #[test]
+    fn test_zero_color_jitter_keeps_base_color() {
+        let points = jitter_box(0.0.into()).generate(3, 0);
+        for color in colors(&points) {
+            assert_eq!(color, [0.5, 0.25, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_color_jitter_is_deterministic() {
+        let mut cluster = jitter_box(0.1.into());
+        let first = colors(&cluster.generate(0, 0));
+        assert_eq!(first, colors(&cluster.generate(0, 0)));
+        assert_ne!(first, colors(&cluster.generate(1, 0)));
+
+        let unique: HashSet<String> =
+            first.iter().map(|color| format!("{:?}", color)).collect();
+        assert_eq!(unique.len(), first.len());
+        for [r, g, b] in first {
+            assert!((r - 0.5).abs() <= 0.1);
+            assert!((g - 0.25).abs() <= 0.1);
+            // Clamped to 1
+            assert!((0.9..=1.0).contains(&b));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "color_jitter must be a non-negative number")]
+    fn test_color_jitter_must_be_non_negative() {
+        jitter_box((-0.1).into());
+    }
+
+    #[test]
+    fn test_cluster_coordinates_are_normalized() {
+        let clusters = vec![
+            object!{
+                "type" => "points",
+                "positions" => array![array![0, 0, 0], array![1, 2, 3]],
+                "coordinate_mode" => "position"
+            },
+            object!{ "type" => "line" },
+            object!{ "type" => "rand_line" },
+            object!{ "type" => "circle" },
+            object!{ "type" => "circle", "distribution" => "random" },
+            object!{ "type" => "triangle", "side_points" => 8 },
+            object!{ "type" => "triangle", "distribution" => "random" },
+            object!{ "type" => "quad" },
+            object!{ "type" => "disk" },
+            object!{ "type" => "sphere" },
+            object!{ "type" => "tetrahedron", "side_points" => 5 },
+            object!{ "type" => "box" },
+            object!{ "type" => "rand_box" },
+            object!{ "type" => "cylinder" }
+        ];
+        for cluster_json in clusters.iter() {
+            let mut cluster_json = cluster_json.clone();
+            if cluster_json["type"] != "points" {
+                cluster_json["num_points"] = 30.into();
+            }
+            let mut cluster = from_json(&cluster_json);
+            for point in cluster.generate(0, 0) {
+                let coordinates = &point.cluster_coordinates;
+                let components =
+                    [*coordinates.x(), *coordinates.y(), *coordinates.z()];
+                assert!(
+                    components.iter().all(|x| (-1e-6..=1.0 + 1e-6).contains(x)),
+                    "{}: {:?}", cluster_json["type"], coordinates);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transformed_moves_positions() {
+        let line = object!{
+            "type" => "line",
+            "start" => array![0.0, 0.0, 0.0],
+            "end" => array![1.0, 0.0, 0.0],
+            "num_points" => 5
+        };
+        let json = object!{
+            "type" => "transformed",
+            "xform" => array!["translate", 2.0, 0.0, 1.0],
+            "cluster" => line.clone()
+        };
+        let mut transformed = from_json(&json);
+        assert_eq!(transformed.point_count(), 5);
+
+        let expected = Line::from_json(&line).generate(0, 0);
+        let points = transformed.generate(0, 0);
+        assert_eq!(points.len(), expected.len());
+        for (point, original) in points.iter().zip(expected.iter()) {
+            let offset =
+                point.position.to_vec3() - original.position.to_vec3();
+            let error = (offset - Vec3::new(2.0, 0.0, 1.0)).length();
+            assert!(error < 1e-5, "{:?}", offset);
+            assert_eq!(point.point_id, original.point_id);
+        }
+
+        let bounds = transformed.bounding_box();
+        let expected_bounds = BBox::new(2.0, 3.0, 0.0, 0.0, 1.0, 1.0);
+        assert!(bounds.padded(1e-4).contains_box(&expected_bounds));
+        assert!(expected_bounds.padded(1e-4).contains_box(&bounds));
+    }
+
+    #[test]
+    fn test_transformed_inversion_has_unknown_bounds() {
+        let json = object!{
+            "type" => "transformed",
+            "xform" => array!["invert"],
+            "cluster" => object!{
+                "type" => "box",
+                "center" => array![2.0, 0.0, 0.0],
+                "num_points" => 8
+            }
+        };
+        let transformed = from_json(&json);
+        assert_same_box(&transformed.bounding_box(), &unknown_bounds());
+    }
+
+    #[test]
+    fn test_transformed_inside_many_gets_num_points() {
+        let json = object!{
+            "type" => "many",
+            "num_points" => 10,
+            "clusters" => array![
+                object!{
+                    "type" => "transformed",
+                    "xform" => array!["scale", 0.5],
+                    "cluster" => object!{
+                        "type" => "rand_line",
+                        "num_points" => 100
+                    }
+                },
+                object!{
+                    "type" => "rand_line",
+                    "num_points" => 100
+                }
+            ]
+        };
+        let mut many = from_json(&json);
+        assert_eq!(many.point_count(), 10);
+        assert_eq!(many.generate(0, 0).len(), 10);
     }
 }
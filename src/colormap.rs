@@ -0,0 +1,258 @@
+use json::JsonValue;
+
+use crate::vector::Vec3;
+
+/// Viridis sampled at t = 0, 1/8, ..., 1
+const VIRIDIS: [[u8; 3]; 9] = [
+    [0x44, 0x01, 0x54],
+    [0x48, 0x28, 0x78],
+    [0x3e, 0x49, 0x89],
+    [0x31, 0x68, 0x8e],
+    [0x26, 0x82, 0x8e],
+    [0x1f, 0x9e, 0x89],
+    [0x35, 0xb7, 0x79],
+    [0x6e, 0xce, 0x58],
+    [0xfd, 0xe7, 0x25],
+];
+
+/// Magma sampled at t = 0, 1/8, ..., 1
+const MAGMA: [[u8; 3]; 9] = [
+    [0x00, 0x00, 0x04],
+    [0x1c, 0x10, 0x44],
+    [0x4f, 0x12, 0x7b],
+    [0x81, 0x25, 0x81],
+    [0xb5, 0x36, 0x7a],
+    [0xe5, 0x50, 0x64],
+    [0xfb, 0x88, 0x61],
+    [0xfe, 0xc2, 0x87],
+    [0xfc, 0xfd, 0xbf],
+];
+
+/// Hue in degrees at the end of the rainbow. This stops at violet rather
+/// than wrapping back around to red.
+const RAINBOW_END_HUE: f32 = 270.0;
+
+/// A gradient that maps a value in [0, 1] to an RGB color, for coloring
+/// by density or by some other scalar.
+pub enum ColorMap {
+    /// Perceptually uniform dark blue -> green -> yellow
+    Viridis,
+    /// Perceptually uniform black -> purple -> orange -> pale yellow
+    Magma,
+    /// Black to white
+    Grayscale,
+    /// Black through red and yellow to white, one channel at a time
+    Heat,
+    /// Fully saturated hues from red to violet. This is not perceptually
+    /// uniform, but it makes small differences easy to see
+    Rainbow,
+    /// Piecewise linear between (t, color) stops, sorted by t
+    Custom(Vec<(f64, Vec3)>),
+}
+
+impl ColorMap {
+    /// Parse from JSON of one of these forms:
+    ///
+    /// ```text
+    /// "viridis" | "magma" | "grayscale" | "heat" | "rainbow"
+    ///
+    /// OR
+    ///
+    /// {
+    ///     "stops": [[t, color], ...]
+    /// }
+    /// ```
+    ///
+    /// Each color is an [r, g, b] in [0, 1] or a string accepted by
+    /// Vec3::color_from_json(). There must be at least two stops, with t
+    /// strictly increasing. Values of t before the first stop or after the
+    /// last use the color of that stop.
+    pub fn from_json(json: &JsonValue) -> Self {
+        if let Some(name) = json.as_str() {
+            return match name {
+                "viridis" => Self::Viridis,
+                "magma" => Self::Magma,
+                "grayscale" => Self::Grayscale,
+                "heat" => Self::Heat,
+                "rainbow" => Self::Rainbow,
+                _ => panic!(
+                    "colormap must be one of viridis, magma, grayscale, \
+                    heat, rainbow or an object with stops")
+            };
+        }
+
+        let stops_json = &json["stops"];
+        if !stops_json.is_array() || stops_json.len() < 2 {
+            panic!("colormap: stops must be an array of at least 2 [t, color]");
+        }
+        let stops: Vec<(f64, Vec3)> = stops_json.members().map(|stop| {
+            let t = stop[0]
+                .as_f64()
+                .expect("colormap: each stop must be [t, color]");
+            if stop[1].is_null() {
+                panic!("colormap: each stop must be [t, color]");
+            }
+            (t, Vec3::color_from_json(&stop[1], Vec3::zero()))
+        }).collect();
+        if stops.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            panic!("colormap: the t values of the stops must increase");
+        }
+        Self::Custom(stops)
+    }
+
+    /// The color at t, clamped to [0, 1]
+    pub fn sample(&self, t: f64) -> Vec3 {
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        match self {
+            Self::Viridis => sample_table(&VIRIDIS, t),
+            Self::Magma => sample_table(&MAGMA, t),
+            Self::Grayscale => {
+                let value = t as f32;
+                Vec3::new(value, value, value)
+            },
+            Self::Heat => {
+                let scaled = 3.0 * t as f32;
+                Vec3::new(
+                    scaled.clamp(0.0, 1.0),
+                    (scaled - 1.0).clamp(0.0, 1.0),
+                    (scaled - 2.0).clamp(0.0, 1.0))
+            },
+            Self::Rainbow => Vec3::from_hsv(
+                RAINBOW_END_HUE * t as f32, 1.0, 1.0),
+            Self::Custom(stops) => sample_stops(stops, t),
+        }
+    }
+}
+
+/// Interpolate a table of evenly spaced 8-bit colors
+fn sample_table(table: &[[u8; 3]], t: f64) -> Vec3 {
+    let to_color = |[r, g, b]: [u8; 3]| Vec3::new(
+        r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0);
+
+    let scaled = t * (table.len() - 1) as f64;
+    let index = (scaled as usize).min(table.len() - 2);
+    let fraction = (scaled - index as f64) as f32;
+    Vec3::lerp(
+        &to_color(table[index]), &to_color(table[index + 1]), fraction)
+}
+
+/// Interpolate between the two stops around t
+fn sample_stops(stops: &[(f64, Vec3)], t: f64) -> Vec3 {
+    let (first_t, first_color) = &stops[0];
+    if t <= *first_t {
+        return *first_color;
+    }
+
+    for pair in stops.windows(2) {
+        let (start_t, start_color) = &pair[0];
+        let (end_t, end_color) = &pair[1];
+        if t <= *end_t {
+            let fraction = ((t - start_t) / (end_t - start_t)) as f32;
+            return Vec3::lerp(start_color, end_color, fraction);
+        }
+    }
+
+    stops[stops.len() - 1].1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_color_eq(actual: Vec3, expected: (f32, f32, f32)) {
+        let (r, g, b) = expected;
+        assert!(
+            (actual.x() - r).abs() < 1e-6 &&
+            (actual.y() - g).abs() < 1e-6 &&
+            (actual.z() - b).abs() < 1e-6,
+            "{:?} != {:?}", actual, expected);
+    }
+
+    /// Relative luminance, to check that a gradient gets brighter
+    fn luminance(color: Vec3) -> f32 {
+        0.2126 * color.x() + 0.7152 * color.y() + 0.0722 * color.z()
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let viridis = ColorMap::from_json(&"viridis".into());
+        assert_color_eq(
+            viridis.sample(0.0), (68.0 / 255.0, 1.0 / 255.0, 84.0 / 255.0));
+        assert_color_eq(
+            viridis.sample(1.0), (253.0 / 255.0, 231.0 / 255.0, 37.0 / 255.0));
+
+        let magma = ColorMap::from_json(&"magma".into());
+        assert_color_eq(magma.sample(0.0), (0.0, 0.0, 4.0 / 255.0));
+
+        let grayscale = ColorMap::from_json(&"grayscale".into());
+        assert_color_eq(grayscale.sample(0.0), (0.0, 0.0, 0.0));
+        assert_color_eq(grayscale.sample(1.0), (1.0, 1.0, 1.0));
+
+        let heat = ColorMap::from_json(&"heat".into());
+        assert_color_eq(heat.sample(0.0), (0.0, 0.0, 0.0));
+        assert_color_eq(heat.sample(0.5), (1.0, 0.5, 0.0));
+        assert_color_eq(heat.sample(1.0), (1.0, 1.0, 1.0));
+
+        let rainbow = ColorMap::from_json(&"rainbow".into());
+        assert_color_eq(rainbow.sample(0.0), (1.0, 0.0, 0.0));
+        assert_color_eq(rainbow.sample(1.0), (0.5, 0.0, 1.0));
+
+        let custom = ColorMap::from_json(&object!{
+            "stops" => array![
+                array![0.0, array![0.0, 0.0, 1.0]],
+                array![0.5, "red"],
+                array![1.0, array![1.0, 1.0, 0.0]]
+            ]
+        });
+        assert_color_eq(custom.sample(0.0), (0.0, 0.0, 1.0));
+        assert_color_eq(custom.sample(0.5), (1.0, 0.0, 0.0));
+        assert_color_eq(custom.sample(1.0), (1.0, 1.0, 0.0));
+        assert_color_eq(custom.sample(0.25), (0.5, 0.0, 0.5));
+
+        // Out of range values are clamped
+        assert_color_eq(grayscale.sample(-1.0), (0.0, 0.0, 0.0));
+        assert_color_eq(grayscale.sample(2.0), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn test_interpolation_is_monotone() {
+        let maps = ["viridis", "magma", "grayscale", "heat"];
+        for name in maps.iter() {
+            let colormap = ColorMap::from_json(&(*name).into());
+            let mut previous = luminance(colormap.sample(0.0));
+            for i in 1..=100 {
+                let current = luminance(colormap.sample(i as f64 / 100.0));
+                assert!(current >= previous, "{} at step {}", name, i);
+                previous = current;
+            }
+        }
+
+        // Between two stops, each channel moves steadily from one to the
+        // other
+        let custom = ColorMap::from_json(&object!{
+            "stops" => array![
+                array![0.2, array![0.0, 1.0, 0.5]],
+                array![0.8, array![1.0, 0.0, 0.5]]
+            ]
+        });
+        let mut previous = custom.sample(0.0);
+        for i in 1..=100 {
+            let current = custom.sample(i as f64 / 100.0);
+            assert!(current.x() >= previous.x());
+            assert!(current.y() <= previous.y());
+            assert_eq!(current.z(), previous.z());
+            previous = current;
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "t values of the stops must increase")]
+    fn test_stops_must_increase() {
+        ColorMap::from_json(&object!{
+            "stops" => array![
+                array![0.5, array![0.0, 0.0, 0.0]],
+                array![0.5, array![1.0, 1.0, 1.0]]
+            ]
+        });
+    }
+}
@@ -0,0 +1,413 @@
+//! Check that two runs produced the same point cloud, e.g. to make sure a
+//! refactor of the math didn't change the output. Each side is either a
+//! tileset that was already written or a parameter file to render in
+//! process. Only the positions are compared.
+
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use json::JsonValue;
+
+use crate::generate_points;
+use crate::parameters::load_json_file;
+use crate::vector::Vec3;
+
+/// glTF componentType for 32-bit floats
+const GLTF_FLOAT: u32 = 5126;
+
+/// Points of one side that have no match on the other side
+pub struct Mismatch {
+    /// Which side the point came from, "A" or "B"
+    pub side: &'static str,
+    /// Index of the point after sorting its side
+    pub index: usize,
+    pub position: Vec3,
+}
+
+/// The result of compare_points()
+pub struct Comparison {
+    pub count_a: usize,
+    pub count_b: usize,
+    /// How many points of either side have no match within epsilon
+    pub mismatch_count: usize,
+    /// The first few of the unmatched points, A before B
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl Comparison {
+    /// The point clouds match if every point was paired with one on the
+    /// other side
+    pub fn is_match(&self) -> bool {
+        self.count_a == self.count_b && self.mismatch_count == 0
+    }
+
+    pub fn print_report(&self) {
+        println!(
+            "A has {} points, B has {} points", self.count_a, self.count_b);
+        for mismatch in self.mismatches.iter() {
+            println!(
+                "{} point {} at {:?} has no match",
+                mismatch.side,
+                mismatch.index,
+                mismatch.position);
+        }
+        if self.mismatch_count > self.mismatches.len() {
+            println!(
+                "... and {} more",
+                self.mismatch_count - self.mismatches.len());
+        }
+
+        if self.is_match() {
+            println!("The point clouds match");
+        } else {
+            println!(
+                "The point clouds differ, {} points have no match",
+                self.mismatch_count);
+        }
+    }
+}
+
+/// Load the positions of a point cloud. A directory is read as a tileset
+/// (see read_tileset()), anything else as a parameter file to render.
+/// A parameter file must have a "seed", otherwise the chaos game lands on
+/// different points every run and the comparison means nothing.
+pub fn load_points(path: &str) -> Vec<Vec3> {
+    if Path::new(path).is_dir() {
+        return read_tileset(Path::new(path));
+    }
+
+    let params = load_json_file(path).unwrap_or_else(|err| panic!("{}", err));
+    if params["seed"].is_null() {
+        panic!(
+            "{} has no seed, so its points change every run. Add a seed or \
+            compare a tileset directory", path);
+    }
+    generate_points(&params)
+        .into_iter()
+        .map(|point| point.position)
+        .collect()
+}
+
+/// Read the points of a tileset directory. This is points.csv for the csv
+/// format, otherwise the .pnts or .glb contents of the leaf tiles, since
+/// the leaves hold every point exactly once. Positions are in the
+/// tileset's frame, before the root transform.
+///
+/// For implicit tiling, the subtree files are not read. A tile's content
+/// is only written if it has points, and then so does its parent, so the
+/// leaves are the content files with none of their children's.
+pub fn read_tileset(dir: &Path) -> Vec<Vec3> {
+    let csv = dir.join("points.csv");
+    if csv.exists() {
+        return read_csv(&csv);
+    }
+
+    let fname = dir.join("tileset.json");
+    let text = fs::read_to_string(&fname).unwrap_or_else(
+        |err| panic!("Could not read {}: {}", fname.display(), err));
+    let tileset = json::parse(&text).unwrap_or_else(
+        |err| panic!("Could not parse {}: {}", fname.display(), err));
+    let root = &tileset["root"];
+    let mut positions = Vec::new();
+    if root["implicitTiling"].is_null() {
+        read_leaf_tiles(root, dir, &mut positions);
+        return positions;
+    }
+
+    let template = root["content"]["uri"]
+        .as_str()
+        .unwrap_or_else(|| panic!(
+            "{}: the implicit root has no content template",
+            fname.display()));
+    let root_tile = ImplicitTile { level: 0, x: 0, y: 0, z: 0 };
+    if root_tile.path(dir, template).exists() {
+        read_implicit_leaves(root_tile, template, dir, &mut positions);
+    }
+    positions
+}
+
+/// The coordinates of a tile of an implicit octree
+#[derive(Clone, Copy)]
+struct ImplicitTile {
+    level: u32,
+    x: u32,
+    y: u32,
+    z: u32,
+}
+
+impl ImplicitTile {
+    /// Fill in the content template, e.g. "{level}/{x}/{y}/{z}.glb"
+    fn path(&self, dir: &Path, template: &str) -> PathBuf {
+        let uri = template
+            .replace("{level}", &self.level.to_string())
+            .replace("{x}", &self.x.to_string())
+            .replace("{y}", &self.y.to_string())
+            .replace("{z}", &self.z.to_string());
+        dir.join(uri)
+    }
+
+    fn children(&self) -> Vec<Self> {
+        (0..8).map(|i| Self {
+            level: self.level + 1,
+            x: 2 * self.x + (i & 1),
+            y: 2 * self.y + ((i >> 1) & 1),
+            z: 2 * self.z + ((i >> 2) & 1),
+        }).collect()
+    }
+}
+
+fn read_implicit_leaves(
+        tile: ImplicitTile,
+        template: &str,
+        dir: &Path,
+        positions: &mut Vec<Vec3>) {
+    let children: Vec<ImplicitTile> = tile
+        .children()
+        .into_iter()
+        .filter(|child| child.path(dir, template).exists())
+        .collect();
+    if children.is_empty() {
+        positions.extend(read_tile(&tile.path(dir, template)));
+    }
+    for child in children {
+        read_implicit_leaves(child, template, dir, positions);
+    }
+}
+
+/// Read the positions of a .pnts or .glb tile
+fn read_tile(path: &Path) -> Vec<Vec3> {
+    let bytes = fs::read(path).unwrap_or_else(
+        |err| panic!("Could not read {}: {}", path.display(), err));
+    match &bytes[..4] {
+        b"pnts" => read_pnts(&bytes),
+        b"glTF" => read_glb(&bytes),
+        _ => panic!("{}: not a .pnts or .glb tile", path.display())
+    }
+}
+
+fn read_leaf_tiles(tile: &JsonValue, dir: &Path, positions: &mut Vec<Vec3>) {
+    if tile["children"].is_empty() {
+        if let Some(uri) = tile["content"]["uri"].as_str() {
+            positions.extend(read_tile(&dir.join(uri)));
+        }
+    }
+
+    for child in tile["children"].members() {
+        read_leaf_tiles(child, dir, positions);
+    }
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+/// Read count vec3s of floats starting at the given offset
+fn read_vec3s(bytes: &[u8], offset: usize, count: usize) -> Vec<Vec3> {
+    let f32_at = |offset: usize|
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    (0..count).map(|i| {
+        let start = offset + 12 * i;
+        Vec3::new(f32_at(start), f32_at(start + 4), f32_at(start + 8))
+    }).collect()
+}
+
+/// Read the positions of a .pnts tile. The feature table JSON comes right
+/// after the 28-byte header, followed by its binary body
+fn read_pnts(bytes: &[u8]) -> Vec<Vec3> {
+    let json_length = u32_at(bytes, 12) as usize;
+    let table_text = std::str::from_utf8(&bytes[28..28 + json_length])
+        .expect("the .pnts feature table must be UTF-8");
+    let table = json::parse(table_text)
+        .expect("the .pnts feature table must be JSON");
+    let count = table["POINTS_LENGTH"]
+        .as_usize()
+        .expect("the .pnts feature table must have POINTS_LENGTH");
+    let offset = table["POSITION"]["byteOffset"]
+        .as_usize()
+        .expect("only .pnts tiles with float POSITION can be compared");
+    read_vec3s(bytes, 28 + json_length + offset, count)
+}
+
+/// Read the POSITION accessor of the first primitive of a .glb tile. The
+/// JSON chunk starts after the 12-byte header and the binary chunk
+/// follows it
+fn read_glb(bytes: &[u8]) -> Vec<Vec3> {
+    let json_length = u32_at(bytes, 12) as usize;
+    let json_text = std::str::from_utf8(&bytes[20..20 + json_length])
+        .expect("the .glb JSON chunk must be UTF-8");
+    let gltf = json::parse(json_text).expect("the .glb JSON chunk must parse");
+    let binary_start = 20 + json_length + 8;
+
+    let attributes = &gltf["meshes"][0]["primitives"][0]["attributes"];
+    let accessor = &gltf["accessors"][attributes["POSITION"]
+        .as_usize()
+        .expect("the .glb tile has no POSITION attribute")];
+    if accessor["componentType"].as_u32() != Some(GLTF_FLOAT) {
+        panic!("only .glb tiles with float positions can be compared");
+    }
+    let buffer_view = &gltf["bufferViews"][accessor["bufferView"]
+        .as_usize()
+        .expect("the POSITION accessor has no buffer view")];
+    let offset = binary_start +
+        buffer_view["byteOffset"].as_usize().unwrap_or(0) +
+        accessor["byteOffset"].as_usize().unwrap_or(0);
+    let count = accessor["count"]
+        .as_usize()
+        .expect("the POSITION accessor has no count");
+    read_vec3s(bytes, offset, count)
+}
+
+/// Read the x, y, z columns of a points.csv file
+fn read_csv(path: &Path) -> Vec<Vec3> {
+    let text = fs::read_to_string(path).unwrap_or_else(
+        |err| panic!("Could not read {}: {}", path.display(), err));
+    text.lines().skip(1).map(|line| {
+        let mut columns = line.split(',').map(|column| {
+            column.parse::<f32>().unwrap_or_else(|_| panic!(
+                "{}: {} is not a number", path.display(), column))
+        });
+        let mut next = || columns
+            .next()
+            .unwrap_or_else(|| panic!("{}: missing column", path.display()));
+        Vec3::new(next(), next(), next())
+    }).collect()
+}
+
+/// Sort positions by x, then y, then z so the reports don't depend on the
+/// order the points were written in
+fn sort_positions(positions: &mut [Vec3]) {
+    positions.sort_by(|a, b| {
+        a.x().total_cmp(b.x())
+            .then(a.y().total_cmp(b.y()))
+            .then(a.z().total_cmp(b.z()))
+    });
+}
+
+/// Pair up the points of a and b that are within epsilon of each other,
+/// each point used at most once. The points of b are binned in a spatial
+/// hash with cells of size epsilon, so each point of a only checks the
+/// neighboring cells. Unlike comparing the sorted lists element by
+/// element, a tiny difference in one coordinate can't shift every point
+/// after it. Up to max_reported unmatched points are listed.
+pub fn compare_points(
+        mut a: Vec<Vec3>,
+        mut b: Vec<Vec3>,
+        epsilon: f32,
+        max_reported: usize) -> Comparison {
+    if epsilon <= 0.0 {
+        panic!("epsilon must be positive");
+    }
+    sort_positions(&mut a);
+    sort_positions(&mut b);
+
+    let cell_of = |position: &Vec3| -> (i64, i64, i64) {
+        (
+            (position.x() / epsilon).floor() as i64,
+            (position.y() / epsilon).floor() as i64,
+            (position.z() / epsilon).floor() as i64
+        )
+    };
+    let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+    for (i, position) in b.iter().enumerate() {
+        cells.entry(cell_of(position)).or_default().push(i);
+    }
+
+    let mut used = vec![false; b.len()];
+    let mut unmatched_a = Vec::new();
+    for (i, position) in a.iter().enumerate() {
+        let (cx, cy, cz) = cell_of(position);
+        let mut found = None;
+        'search: for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let cell = (cx + dx, cy + dy, cz + dz);
+                    let candidates = match cells.get(&cell) {
+                        Some(candidates) => candidates,
+                        None => continue
+                    };
+                    for &j in candidates {
+                        let distance = (b[j] - *position).length();
+                        if !used[j] && distance <= epsilon {
+                            found = Some(j);
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        match found {
+            Some(j) => used[j] = true,
+            None => unmatched_a.push(i)
+        }
+    }
+
+    let unmatched_b: Vec<usize> =
+        (0..b.len()).filter(|&j| !used[j]).collect();
+    let mismatches = unmatched_a
+        .iter()
+        .map(|&i| Mismatch { side: "A", index: i, position: a[i] })
+        .chain(unmatched_b
+            .iter()
+            .map(|&j| Mismatch { side: "B", index: j, position: b[j] }))
+        .take(max_reported)
+        .collect();
+
+    Comparison {
+        count_a: a.len(),
+        count_b: b.len(),
+        mismatch_count: unmatched_a.len() + unmatched_b.len(),
+        mismatches,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(n: usize) -> Vec<Vec3> {
+        (0..n)
+            .map(|i| Vec3::new(i as f32 * 0.1, (i % 7) as f32, 0.5))
+            .collect()
+    }
+
+    #[test]
+    fn test_shuffled_points_match() {
+        let a = grid(100);
+        let mut b: Vec<Vec3> = a.iter()
+            .rev()
+            .map(|position| *position + Vec3::new(1e-7, -1e-7, 0.0))
+            .collect();
+        b.swap(3, 50);
+        let comparison = compare_points(a, b, 1e-5, 10);
+        assert!(comparison.is_match());
+        assert!(comparison.mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_mismatches_are_reported() {
+        let a = grid(100);
+        let mut b = grid(100);
+        b[10] = Vec3::new(100.0, 0.0, 0.0);
+        b.push(Vec3::new(-5.0, 0.0, 0.0));
+        let comparison = compare_points(a, b, 1e-5, 2);
+        assert!(!comparison.is_match());
+        assert_eq!(comparison.count_b, 101);
+        // b[10] has no match in A, and a[10] and the extra point have
+        // none in B
+        assert_eq!(comparison.mismatch_count, 3);
+        assert_eq!(comparison.mismatches.len(), 2);
+        assert_eq!(comparison.mismatches[0].side, "A");
+        assert_eq!(*comparison.mismatches[0].position.x(), 1.0);
+    }
+
+    #[test]
+    fn test_coincident_points_are_paired_once() {
+        let point = Vec3::new(0.25, 0.5, 0.75);
+        let comparison =
+            compare_points(vec![point, point], vec![point], 1e-5, 10);
+        assert_eq!(comparison.mismatch_count, 1);
+    }
+}
@@ -0,0 +1,58 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+use crate::point::OutputPoint;
+
+/// Column names for the header row
+const HEADER: &str = "x,y,z,r,g,b,iteration,cluster_id,point_id,last_xform";
+
+/// Writer for a flat CSV file of points, one row per point. This is not a
+/// 3D Tiles format, but it is the simplest way to get the points into
+/// other tools for data analysis.
+pub struct CsvWriter {
+    /// How many rows have been written, not including the header
+    row_count: usize,
+}
+
+impl CsvWriter {
+    pub fn new() -> Self {
+        Self {
+            row_count: 0,
+        }
+    }
+
+    /// Write the points to a CSV file with a header row
+    pub fn write(&mut self, fname: &str, points: &[OutputPoint]) {
+        let error_msg = format!("Cannot open {}", fname);
+        let file = File::create(fname).expect(&error_msg);
+        let mut writer = BufWriter::new(file);
+
+        let error_msg = "could not write CSV row";
+        writeln!(writer, "{}", HEADER).expect(error_msg);
+        for point in points {
+            let position = &point.position;
+            let color = &point.color;
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{}",
+                position.x(),
+                position.y(),
+                position.z(),
+                color.x(),
+                color.y(),
+                color.z(),
+                point.iteration,
+                point.cluster_id,
+                point.point_id,
+                point.last_xform).expect(error_msg);
+            self.row_count += 1;
+        }
+        writer.flush().expect(error_msg);
+    }
+
+    /// Get the number of points written so far
+    pub fn get_row_count(&self) -> usize {
+        self.row_count
+    }
+}
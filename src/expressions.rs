@@ -0,0 +1,365 @@
+use std::f64::consts::{E, PI};
+
+/// Functions of one argument that can be called in an expression
+const UNARY_FUNCTIONS: [&str; 14] = [
+    "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh",
+    "exp", "ln", "sqrt", "abs", "floor",
+];
+/// Functions of two arguments that can be called in an expression
+const BINARY_FUNCTIONS: [&str; 4] = ["atan2", "pow", "min", "max"];
+
+/// A parsed arithmetic expression in the variables x, y and z, e.g.
+/// "x * cos(z)". Expressions are parsed once and then evaluated for every
+/// point, so evaluation is just a walk of the syntax tree.
+///
+/// The grammar is the usual one: + - * / and ^ (right associative, binds
+/// tighter than unary minus so -x^2 is -(x^2)), parentheses, the
+/// constants pi and e, and the functions in UNARY_FUNCTIONS and
+/// BINARY_FUNCTIONS.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    /// x, y or z as an index 0, 1 or 2
+    Variable(usize),
+    Negate(Box<Expr>),
+    Add(Box<Expr>, Box<Expr>),
+    Subtract(Box<Expr>, Box<Expr>),
+    Multiply(Box<Expr>, Box<Expr>),
+    Divide(Box<Expr>, Box<Expr>),
+    Power(Box<Expr>, Box<Expr>),
+    /// A function from UNARY_FUNCTIONS
+    Call(String, Box<Expr>),
+    /// A function from BINARY_FUNCTIONS
+    Call2(String, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression. On failure, the error describes what went
+    /// wrong and where, e.g. "unknown variable w at column 5"
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenize(text)?;
+        let mut parser = Parser { tokens, position: 0 };
+        let expr = parser.parse_sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some((token, column)) => Err(format!(
+                "unexpected {} at column {}", token.describe(), column))
+        }
+    }
+
+    /// Evaluate the expression at a point
+    pub fn evaluate(&self, variables: &[f64; 3]) -> f64 {
+        match self {
+            Self::Number(value) => *value,
+            Self::Variable(index) => variables[*index],
+            Self::Negate(a) => -a.evaluate(variables),
+            Self::Add(a, b) => a.evaluate(variables) + b.evaluate(variables),
+            Self::Subtract(a, b) =>
+                a.evaluate(variables) - b.evaluate(variables),
+            Self::Multiply(a, b) =>
+                a.evaluate(variables) * b.evaluate(variables),
+            Self::Divide(a, b) => a.evaluate(variables) / b.evaluate(variables),
+            Self::Power(a, b) =>
+                a.evaluate(variables).powf(b.evaluate(variables)),
+            Self::Call(name, a) => {
+                let a = a.evaluate(variables);
+                match &name[..] {
+                    "sin" => a.sin(),
+                    "cos" => a.cos(),
+                    "tan" => a.tan(),
+                    "asin" => a.asin(),
+                    "acos" => a.acos(),
+                    "atan" => a.atan(),
+                    "sinh" => a.sinh(),
+                    "cosh" => a.cosh(),
+                    "tanh" => a.tanh(),
+                    "exp" => a.exp(),
+                    "ln" => a.ln(),
+                    "sqrt" => a.sqrt(),
+                    "abs" => a.abs(),
+                    "floor" => a.floor(),
+                    // Names are checked when parsing
+                    _ => unreachable!()
+                }
+            },
+            Self::Call2(name, a, b) => {
+                let a = a.evaluate(variables);
+                let b = b.evaluate(variables);
+                match &name[..] {
+                    "atan2" => a.atan2(b),
+                    "pow" => a.powf(b),
+                    "min" => a.min(b),
+                    "max" => a.max(b),
+                    _ => unreachable!()
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Name(String),
+    Operator(char),
+    LeftParen,
+    RightParen,
+    Comma,
+}
+
+impl Token {
+    /// Describe the token for error messages
+    fn describe(&self) -> String {
+        match self {
+            Self::Number(value) => format!("number {}", value),
+            Self::Name(name) => format!("name {}", name),
+            Self::Operator(op) => format!("'{}'", op),
+            Self::LeftParen => "'('".to_string(),
+            Self::RightParen => "')'".to_string(),
+            Self::Comma => "','".to_string(),
+        }
+    }
+}
+
+/// Split the text into tokens, each paired with its column (from 1)
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Scientific notation, e.g. 1e-3
+            if i < chars.len() && chars[i] == 'e' {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let number: String = chars[start..i].iter().collect();
+            let value = number.parse::<f64>().map_err(
+                |_| format!("bad number {} at column {}", number, column))?;
+            tokens.push((Token::Number(value), column));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() &&
+                    (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            tokens.push((Token::Name(name), column));
+        } else {
+            let token = match c {
+                '+' | '-' | '*' | '/' | '^' => Token::Operator(c),
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                ',' => Token::Comma,
+                _ => return Err(
+                    format!("unexpected character '{}' at column {}", c, column))
+            };
+            tokens.push((token, column));
+            i += 1;
+        }
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent parser, one method per precedence level
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&(Token, usize)> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    /// Check if the next token is the given operator, and if so skip it
+    fn accept_operator(&mut self, op: char) -> bool {
+        if let Some((Token::Operator(next), _)) = self.peek() {
+            if *next == op {
+                self.position += 1;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Skip the expected token, or report what was found instead
+    fn expect(&mut self, expected: Token) -> Result<(), String> {
+        match self.next() {
+            Some((token, _)) if token == expected => Ok(()),
+            Some((token, column)) => Err(format!(
+                "expected {} but found {} at column {}",
+                expected.describe(), token.describe(), column)),
+            None => Err(format!(
+                "expected {} but the expression ended", expected.describe()))
+        }
+    }
+
+    /// sum = product (('+' | '-') product)*
+    fn parse_sum(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_product()?;
+        loop {
+            if self.accept_operator('+') {
+                let rhs = self.parse_product()?;
+                result = Expr::Add(Box::new(result), Box::new(rhs));
+            } else if self.accept_operator('-') {
+                let rhs = self.parse_product()?;
+                result = Expr::Subtract(Box::new(result), Box::new(rhs));
+            } else {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// product = unary (('*' | '/') unary)*
+    fn parse_product(&mut self) -> Result<Expr, String> {
+        let mut result = self.parse_unary()?;
+        loop {
+            if self.accept_operator('*') {
+                let rhs = self.parse_unary()?;
+                result = Expr::Multiply(Box::new(result), Box::new(rhs));
+            } else if self.accept_operator('/') {
+                let rhs = self.parse_unary()?;
+                result = Expr::Divide(Box::new(result), Box::new(rhs));
+            } else {
+                return Ok(result);
+            }
+        }
+    }
+
+    /// unary = '-' unary | power
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.accept_operator('-') {
+            let operand = self.parse_unary()?;
+            return Ok(Expr::Negate(Box::new(operand)));
+        }
+        self.parse_power()
+    }
+
+    /// power = atom ('^' unary)?
+    fn parse_power(&mut self) -> Result<Expr, String> {
+        let base = self.parse_atom()?;
+        if self.accept_operator('^') {
+            let exponent = self.parse_unary()?;
+            return Ok(Expr::Power(Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    /// atom = number | variable | constant | function '(' args ')'
+    ///      | '(' sum ')'
+    fn parse_atom(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some((Token::Number(value), _)) => Ok(Expr::Number(value)),
+            Some((Token::LeftParen, _)) => {
+                let inner = self.parse_sum()?;
+                self.expect(Token::RightParen)?;
+                Ok(inner)
+            },
+            Some((Token::Name(name), column)) => self.parse_name(name, column),
+            Some((token, column)) => Err(format!(
+                "unexpected {} at column {}", token.describe(), column)),
+            None => Err("the expression ended unexpectedly".to_string())
+        }
+    }
+
+    /// A variable, constant or function call
+    fn parse_name(&mut self, name: String, column: usize)
+            -> Result<Expr, String> {
+        match &name[..] {
+            "x" => return Ok(Expr::Variable(0)),
+            "y" => return Ok(Expr::Variable(1)),
+            "z" => return Ok(Expr::Variable(2)),
+            "pi" => return Ok(Expr::Number(PI)),
+            "e" => return Ok(Expr::Number(E)),
+            _ => {}
+        }
+
+        let is_unary = UNARY_FUNCTIONS.contains(&&name[..]);
+        let is_binary = BINARY_FUNCTIONS.contains(&&name[..]);
+        if !is_unary && !is_binary {
+            return Err(format!(
+                "unknown name {} at column {}, expected x, y, z, pi, e or \
+                one of the functions {:?} {:?}",
+                name, column, UNARY_FUNCTIONS, BINARY_FUNCTIONS));
+        }
+
+        self.expect(Token::LeftParen)?;
+        let first = self.parse_sum()?;
+        let result = if is_unary {
+            Expr::Call(name, Box::new(first))
+        } else {
+            self.expect(Token::Comma)?;
+            let second = self.parse_sum()?;
+            Expr::Call2(name, Box::new(first), Box::new(second))
+        };
+        self.expect(Token::RightParen)?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(text: &str, x: f64, y: f64, z: f64) -> f64 {
+        Expr::parse(text).unwrap().evaluate(&[x, y, z])
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eq!(eval("1 + 2 * 3", 0.0, 0.0, 0.0), 7.0);
+        assert_eq!(eval("(1 + 2) * 3", 0.0, 0.0, 0.0), 9.0);
+        assert_eq!(eval("2 ^ 3 ^ 2", 0.0, 0.0, 0.0), 512.0);
+        assert_eq!(eval("-x^2", 3.0, 0.0, 0.0), -9.0);
+        assert_eq!(eval("8 / 4 / 2", 0.0, 0.0, 0.0), 1.0);
+        assert_eq!(eval("x - y - z", 1.0, 2.0, 3.0), -4.0);
+        assert_eq!(eval("2.5e-1 * 4", 0.0, 0.0, 0.0), 1.0);
+    }
+
+    #[test]
+    fn test_functions() {
+        let value = eval("x * cos(z) + atan2(y, 1) - max(pi, e)", 2.0, 0.0, 0.0);
+        assert!((value - (2.0 - PI)).abs() < 1e-12);
+        assert_eq!(eval("sqrt(abs(-16))", 0.0, 0.0, 0.0), 4.0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("x +", "ended unexpectedly"),
+            ("w * 2", "unknown name w at column 1"),
+            ("sin x", "expected '(' but found name x at column 5"),
+            ("pow(x)", "expected ','"),
+            ("(x + 1", "expected ')' but the expression ended"),
+            ("x $ y", "unexpected character '$' at column 3"),
+            ("x y", "unexpected name y at column 3"),
+        ];
+        for (text, expected) in cases.iter() {
+            let err = Expr::parse(text).unwrap_err();
+            assert!(err.contains(expected), "{}: {}", text, err);
+        }
+    }
+}
@@ -1,5 +1,65 @@
 use json::JsonValue;
 
+use crate::ifs::IFS;
+use crate::xforms::Classification;
+
+/// The default glTF asset.generator, the crate name and version
+pub const GENERATOR: &str =
+    concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// Who made a fractal, for publishing tilesets. The author and license
+/// go in the tileset metadata, the generator and copyright go in the
+/// asset of each glTF file.
+#[derive(Clone, Default)]
+pub struct Attribution {
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub copyright: Option<String>,
+    /// Overrides the glTF generator, see GENERATOR
+    pub generator: Option<String>,
+}
+
+impl Attribution {
+    /// Parse an optional JSON object of the form
+    ///
+    /// ```text
+    /// {
+    ///     "author": str (optional),
+    ///     "license": str (optional),
+    ///     "copyright": str (optional),
+    ///     "generator": str (default: crate name and version)
+    /// }
+    /// ```
+    ///
+    /// If the JSON is null, every field is left unset.
+    pub fn from_json(json: &JsonValue) -> Self {
+        if json.is_null() {
+            return Self::default();
+        }
+
+        let field = |key: &str| match &json[key] {
+            JsonValue::Null => None,
+            value => Some(value
+                .as_str()
+                .unwrap_or_else(
+                    || panic!("attribution.{} must be a string", key))
+                .to_string())
+        };
+
+        Self {
+            author: field("author"),
+            license: field("license"),
+            copyright: field("copyright"),
+            generator: field("generator"),
+        }
+    }
+
+    /// The glTF asset.generator
+    pub fn generator(&self) -> &str {
+        self.generator.as_deref().unwrap_or(GENERATOR)
+    }
+}
+
 /// Metadata about the fractal. This will be included in the tileset
 /// when generating 3D Tiles Next for styling and other purposes
 #[derive(Clone)]
@@ -18,18 +78,32 @@ pub struct FractalMetadata {
     /// number of internal clusters.
     pub subcluster_count: u8,
     /// How many points in the initial set in total
-    pub cluster_point_count: u16,
+    pub cluster_point_count: u32,
     /// For ManyClusters, what is the maximum number of points in any
     /// sub cluster
-    pub subcluster_max_point_count: u16,
+    pub subcluster_max_point_count: u32,
     /// How many transformations are in the IFS
     pub ifs_xform_count: u8,
+    /// How many groups of transformations are in the IFS. A transformation
+    /// and its inverse from ["+inverse"] count as a single group
+    pub ifs_xform_group_count: u8,
     /// How many transformations are in the color IFS. Default is 1 (identity)
     pub color_ifs_xform_count: u8,
+    /// Human-readable name of each transformation in the IFS, indexed
+    /// by last_xform
+    pub xform_names: Vec<String>,
+    /// Whether each transformation in the IFS preserves angles, in the same
+    /// order as xform_names
+    pub xform_classifications: Vec<Classification>,
+    /// How many transformations are packed into each point's address, or
+    /// 0 if the address is not tracked. See IFS::get_xform_address()
+    pub xform_address_depth: u32,
     /// The algorithm that was used
     pub algorithm: String,
     /// how many points are stored in each octree node
-    pub node_capacity: u16
+    pub node_capacity: u16,
+    /// Optional author, license and copyright for published tilesets
+    pub attribution: Attribution,
 }
 
 impl FractalMetadata {
@@ -51,16 +125,6 @@ impl FractalMetadata {
         let plotter = &json["plotter"];
         let node_capacity = &plotter["node_capacity"].as_u16().unwrap_or(5000);
 
-        let ifs = &json["ifs"];
-        let ifs_xform_count = &ifs["xforms"].len();
-        let color_ifs = &json["color_ifs"];
-        let color_ifs_xform_count = match color_ifs {
-            // if not present, the identity IFS is used, which has 1 xform
-            JsonValue::Null => 1,
-            JsonValue::Object(_) => color_ifs["xforms"].len(),
-            _ => panic!("color_ifs must be an object")
-        };
-
         Self {
             id: id.to_string(),
             name: name.to_string(),
@@ -71,13 +135,40 @@ impl FractalMetadata {
             subcluster_count: 1,
             cluster_point_count: 0,
             subcluster_max_point_count: 0,
-            ifs_xform_count: *ifs_xform_count as u8,
-            color_ifs_xform_count: color_ifs_xform_count as u8,
+            // these will be determined once the IFSs are parsed, see
+            // set_ifs(). Until then, both are the identity IFS
+            ifs_xform_count: 1,
+            ifs_xform_group_count: 1,
+            color_ifs_xform_count: 1,
+            xform_names: Vec::new(),
+            xform_classifications: Vec::new(),
+            xform_address_depth: 0,
             algorithm: algorithm.to_string(),
             node_capacity: *node_capacity,
+            attribution: Attribution::from_json(&json["attribution"]),
         }
     }
 
+    /// Fill in the transformation counts, names and classifications from
+    /// the parsed IFSs. These come from the IFSs rather than the JSON since
+    /// shortcuts like ["schottky", ...] expand to many transformations.
+    pub fn set_ifs(&mut self, position_ifs: &IFS, color_ifs: &IFS) {
+        self.ifs_xform_count = position_ifs.xform_count() as u8;
+        self.ifs_xform_group_count = position_ifs.xform_group_count() as u8;
+        self.color_ifs_xform_count = color_ifs.xform_count() as u8;
+        self.xform_names = position_ifs.xform_names();
+        self.xform_classifications = position_ifs.xform_classifications();
+        self.xform_address_depth = position_ifs.address_depth();
+    }
+
+    /// How many transformations in the IFS have this classification
+    fn count_xforms(&self, classification: Classification) -> u8 {
+        self.xform_classifications
+            .iter()
+            .filter(|x| **x == classification)
+            .count() as u8
+    }
+
     /// Generate the metadata for 3D Tiles 1.1 metadata schema
     pub fn make_schema_json(&self) -> JsonValue {
         object!{
@@ -110,26 +201,56 @@ impl FractalMetadata {
                         },
                         "cluster_point_count" => object!{
                             "type" => "SCALAR",
-                            "componentType" => "UINT16"
+                            "componentType" => "UINT32"
                         },
                         "subcluster_max_point_count" => object!{
                             "type" => "SCALAR",
-                            "componentType" => "UINT16"
+                            "componentType" => "UINT32"
                         },
                         "ifs_xform_count" => object!{
                             "type" => "SCALAR",
                             "componentType" => "UINT8"
                         },
+                        "ifs_xform_group_count" => object!{
+                            "type" => "SCALAR",
+                            "componentType" => "UINT8"
+                        },
                         "color_ifs_xform_count" => object!{
                             "type" => "SCALAR",
                             "componentType" => "UINT8"
                         },
+                        "xform_names" => object!{
+                            "type" => "STRING",
+                            "array" => true
+                        },
+                        "xform_classifications" => object!{
+                            "type" => "STRING",
+                            "array" => true
+                        },
+                        "conformal_xform_count" => object!{
+                            "type" => "SCALAR",
+                            "componentType" => "UINT8"
+                        },
+                        "anti_conformal_xform_count" => object!{
+                            "type" => "SCALAR",
+                            "componentType" => "UINT8"
+                        },
+                        "non_conformal_xform_count" => object!{
+                            "type" => "SCALAR",
+                            "componentType" => "UINT8"
+                        },
                         "algorithm" => object!{
                             "type" => "STRING"
                         },
                         "node_capacity" => object!{
                             "type" => "SCALAR",
                             "componentType" => "UINT16"
+                        },
+                        "author" => object!{
+                            "type" => "STRING"
+                        },
+                        "license" => object!{
+                            "type" => "STRING"
                         }
                     }
                 }
@@ -139,7 +260,11 @@ impl FractalMetadata {
 
     /// Generate tileset metadata using the 3D Tiles 1.1 schema
     pub fn make_metadata_json(&self) -> JsonValue {
-        object!{
+        let classification_names: Vec<&str> = self.xform_classifications
+            .iter()
+            .map(|classification| classification.name())
+            .collect();
+        let mut metadata = object!{
             "class" => "tileset",
             "properties" => object!{
                 "id" => self.id.clone(),
@@ -151,10 +276,143 @@ impl FractalMetadata {
                 "cluster_point_count" => self.cluster_point_count,
                 "subcluster_max_point_count" => self.subcluster_max_point_count,
                 "ifs_xform_count" => self.ifs_xform_count,
+                "ifs_xform_group_count" => self.ifs_xform_group_count,
                 "color_ifs_xform_count" => self.color_ifs_xform_count,
+                "xform_names" => self.xform_names.clone(),
+                "xform_classifications" => classification_names,
+                "conformal_xform_count" =>
+                    self.count_xforms(Classification::Conformal),
+                "anti_conformal_xform_count" =>
+                    self.count_xforms(Classification::AntiConformal),
+                "non_conformal_xform_count" =>
+                    self.count_xforms(Classification::NonConformal),
                 "algorithm" => self.algorithm.clone(),
                 "node_capacity" => self.node_capacity,
             }
+        };
+
+        // These properties are optional, so leave them out if not set
+        let attribution = &self.attribution;
+        if let Some(author) = &attribution.author {
+            metadata["properties"]["author"] = author.as_str().into();
         }
+        if let Some(license) = &attribution.license {
+            metadata["properties"]["license"] = license.as_str().into();
+        }
+        metadata
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_json(attribution: JsonValue) -> JsonValue {
+        object!{
+            "id" => "test",
+            "name" => "Test",
+            "algorithm" => "chaos",
+            "iters" => 10,
+            "ifs" => object!{ "xforms" => array![array!["scale", 0.5]] },
+            "attribution" => attribution
+        }
+    }
+
+    #[test]
+    fn test_attribution_metadata() {
+        let metadata = FractalMetadata::from_json(&make_json(object!{
+            "author" => "Someone",
+            "license" => "CC-BY-4.0"
+        }));
+        let properties = &metadata.make_metadata_json()["properties"];
+        assert_eq!(properties["author"], "Someone");
+        assert_eq!(properties["license"], "CC-BY-4.0");
+        assert!(metadata.attribution.copyright.is_none());
+        assert_eq!(metadata.attribution.generator(), GENERATOR);
+
+        let metadata = FractalMetadata::from_json(&make_json(JsonValue::Null));
+        let properties = &metadata.make_metadata_json()["properties"];
+        assert!(properties["author"].is_null());
+        assert!(properties["license"].is_null());
+    }
+
+    #[test]
+    fn test_xform_counts_from_schottky() {
+        let json = object!{
+            "xforms" => array![
+                array!["schottky", array![
+                    object!{ "center" => array![2, 0, 0], "radius" => 0.5 },
+                    object!{ "center" => array![-2, 0, 0], "radius" => 0.5 },
+                    object!{ "center" => array![0, 2, 0], "radius" => 0.5 },
+                    object!{ "center" => array![0, -2, 0], "radius" => 0.5 }
+                ]],
+                array!["scale", 0.5],
+                array!["+inverse"]
+            ]
+        };
+        let position_ifs = crate::ifs::from_json(&json);
+        let mut metadata = FractalMetadata::from_json(&make_json(
+            JsonValue::Null));
+        metadata.set_ifs(&position_ifs, &IFS::identity());
+
+        // 4 inversions, a scale and its inverse from 3 JSON entries
+        assert_eq!(metadata.ifs_xform_count, 6);
+        assert_eq!(metadata.ifs_xform_group_count, 5);
+        assert_eq!(metadata.color_ifs_xform_count, 1);
+        assert_eq!(metadata.xform_names.len(), 6);
+    }
+
+    #[test]
+    fn test_xform_counts_from_tiling_group() {
+        // Mirrors in the 3 coordinate planes make 7 elements besides the
+        // identity, each in its own group
+        let position_ifs = crate::ifs::from_json(&object!{
+            "xforms" => array![
+                array!["tiling_group", object!{
+                    "planes" => array![
+                        object!{ "normal" => array![1, 0, 0] },
+                        object!{ "normal" => array![0, 1, 0] },
+                        object!{ "normal" => array![0, 0, 1] }
+                    ],
+                    "max_word_length" => 3
+                }]
+            ]
+        });
+        let color_xforms: Vec<JsonValue> =
+            (0..7).map(|_| array!["scale", 0.5]).collect();
+        let color_ifs = crate::ifs::from_json(&object!{
+            "xforms" => JsonValue::Array(color_xforms)
+        });
+        let mut metadata = FractalMetadata::from_json(&make_json(
+            JsonValue::Null));
+        metadata.set_ifs(&position_ifs, &color_ifs);
+        assert_eq!(metadata.ifs_xform_count, 7);
+        assert_eq!(metadata.ifs_xform_group_count, 7);
+        assert_eq!(metadata.color_ifs_xform_count, 7);
+    }
+
+    #[test]
+    fn test_xform_counts_from_sphere_lattice() {
+        let position_ifs = crate::ifs::from_json(&object!{
+            "xforms" => array![
+                array!["sphere_lattice", object!{
+                    "spacing" => 4.0,
+                    "radius" => 1.0,
+                    "counts" => array![3, 2, 1]
+                }],
+                array!["scale", 0.5]
+            ]
+        });
+        let mut metadata = FractalMetadata::from_json(&make_json(
+            JsonValue::Null));
+        metadata.set_ifs(&position_ifs, &IFS::identity());
+        assert_eq!(metadata.ifs_xform_count, 7);
+        assert_eq!(metadata.ifs_xform_group_count, 7);
+    }
+
+    #[test]
+    #[should_panic(expected = "attribution.author must be a string")]
+    fn test_attribution_must_be_strings() {
+        Attribution::from_json(&object!{ "author" => 3 });
     }
 }
@@ -1,20 +1,27 @@
 use std::fs::File;
 use std::io::prelude::*;
 use std::collections::HashSet;
+use std::convert::TryFrom;
 
 use chrono::{Datelike, Utc};
 use json::JsonValue;
 
+use crate::fractal_metadata::{Attribution, FractalMetadata};
 use crate::point::OutputPoint;
+use crate::vector::Vec3;
 
 /// glTF version number. 2.0 is the latest as of this writing.
 const GLTF_VERSION: u32 = 2;
 /// glTF mode for point clouds
 const GLTF_POINTS: u32 = 0;
+/// glTF mode for line segments (each pair of vertices is a line)
+const GLTF_LINES: u32 = 1;
 /// glTF constant for FLOAT component type
 const GLTF_FLOAT: u32 = 5126;
 /// glTF constant for UNSIGNED_BYTE component type
 const GLTF_UNSIGNED_BYTE: u32 = 5121;
+/// glTF constant for UNSIGNED_INT component type
+const GLTF_UNSIGNED_INT: u32 = 5125;
 /// Length of the glTF header
 const GLTF_HEADER_LENGTH: u32 = 12;
 /// Length of a chunk header (length + type)
@@ -22,8 +29,12 @@ const GLTF_CHUNK_HEADER_LENGTH: u32 = 8;
 
 /// Size of a single-precision float
 const SIZE_FLOAT: u32 = 4;
+/// Size of a 32-bit unsigned integer
+const SIZE_UINT: u32 = 4;
 /// Size of a vec3
 const SIZE_VEC3: u32 = 3 * SIZE_FLOAT;
+/// Size of a vec4
+const SIZE_VEC4: u32 = 4 * SIZE_FLOAT;
 /// Size of an RGB color encoded as unsigned bytes
 const SIZE_COLOR_RGB: u32 = 3;
 
@@ -37,6 +48,22 @@ const PADDING_BINARY: u8 = 0x00;
 /// Padding character for the JSON chunk
 const PADDING_JSON: u8 = ' ' as u8;
 
+/// The points are z-up like 3D Tiles but glTF is y-up, so the node of each
+/// .glb rotates z-up to y-up, (x, y, z) -> (x, z, -y). Column-major.
+const Z_UP_TO_Y_UP: [f64; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 0.0, -1.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 0.0, 1.0
+];
+
+const IDENTITY_MATRIX: [f64; 16] = [
+    1.0, 0.0, 0.0, 0.0,
+    0.0, 1.0, 0.0, 0.0,
+    0.0, 0.0, 1.0, 0.0,
+    0.0, 0.0, 0.0, 1.0
+];
+
 /// A struct for keeping track of the size of a buffer view within the buffer 
 struct BufferView {
     /// Human-readable name for the buffer view
@@ -138,6 +165,59 @@ impl Chunk {
     }
 }
 
+/// The featureCount of each feature ID set, in the same order as the
+/// _FEATURE_ID_n attributes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FeatureCounts {
+    pub iterations: usize,
+    pub cluster_copies: usize,
+    pub cluster_ids: usize,
+    pub point_ids: usize,
+    pub last_xform_groups: usize,
+}
+
+impl FeatureCounts {
+    /// Count the unique IDs in a single tile
+    fn from_points(buffer: &[OutputPoint]) -> Self {
+        let mut iterations: HashSet<u64> = HashSet::new();
+        let mut cluster_copies: HashSet<u16> = HashSet::new();
+        let mut cluster_ids: HashSet<u16> = HashSet::new();
+        let mut point_ids: HashSet<u32> = HashSet::new();
+        let mut last_xform_groups: HashSet<u8> = HashSet::new();
+        for point in buffer.iter() {
+            iterations.insert(point.iteration);
+            cluster_copies.insert(point.cluster_copy);
+            cluster_ids.insert(point.cluster_id);
+            point_ids.insert(point.point_id);
+            last_xform_groups.insert(point.last_xform_group);
+        }
+
+        Self {
+            iterations: iterations.len(),
+            cluster_copies: cluster_copies.len(),
+            cluster_ids: cluster_ids.len(),
+            point_ids: point_ids.len(),
+            last_xform_groups: last_xform_groups.len(),
+        }
+    }
+
+    /// The number of possible IDs over the whole fractal. Every tile
+    /// reports the same counts, so styles that scale a color ramp by
+    /// featureCount look the same across tiles. featureCount must be
+    /// positive, so each count is at least 1.
+    pub fn from_metadata(metadata: &FractalMetadata) -> Self {
+        let at_least_one = |count: usize| count.max(1);
+        Self {
+            iterations: at_least_one(metadata.iterations as usize),
+            cluster_copies: at_least_one(metadata.cluster_copies as usize),
+            cluster_ids: at_least_one(metadata.subcluster_count as usize),
+            point_ids: at_least_one(metadata.cluster_point_count as usize),
+            last_xform_groups:
+                at_least_one(metadata.ifs_xform_group_count as usize),
+        }
+    }
+}
+
 /// An object that can write a point cloud as a binary GLTF (GLB) file.
 pub struct GlbWriter {
     /// Number of points in this glTF point cloud
@@ -154,6 +234,23 @@ pub struct GlbWriter {
     accessors: Vec<Accessor>,
     /// The glTF buffer views.
     buffer_views: Vec<BufferView>,
+    /// Optional unit normals, one per point
+    normals: Option<Vec<Vec3>>,
+    /// If true, store the cluster coordinates as a vec4 with the
+    /// 4th barycentric coordinate included
+    full_barycentric: bool,
+    /// If true, include the weight of each point from deduplication
+    weights: bool,
+    /// If true, include the transformation address of each point
+    xform_address: bool,
+    /// If true, include the CGA components of each point before it was
+    /// homogenized
+    cga: bool,
+    /// If set, report these feature counts rather than counting the IDs
+    /// in each tile
+    feature_counts: Option<FeatureCounts>,
+    /// The generator and copyright for the glTF asset
+    attribution: Attribution,
     // The final JSON string that will be written to the JSON chunk
     json: String,
 }
@@ -168,12 +265,88 @@ impl GlbWriter {
             buffer_length: 0,
             accessors: Vec::new(),
             buffer_views: Vec::new(),
+            normals: None,
+            full_barycentric: false,
+            weights: false,
+            xform_address: false,
+            cga: false,
+            feature_counts: None,
+            attribution: Attribution::default(),
             json: String::new(),
         }
     }
 
+    /// Include a NORMAL attribute in the next write(). There must be
+    /// exactly one normal per point.
+    pub fn set_normals(&mut self, normals: Vec<Vec3>) {
+        self.normals = Some(normals);
+    }
+
+    /// Store _CLUSTER_COORDINATES as a VEC4 (u, v, w, 1 - u - v - w).
+    /// Tetrahedron clusters only store 3 of their 4 barycentric
+    /// coordinates, so this completes them. For triangles the 4th
+    /// component is 0, and for other cluster types it is meaningless.
+    pub fn set_full_barycentric(&mut self, full_barycentric: bool) {
+        self.full_barycentric = full_barycentric;
+    }
+
+    /// Include a _WEIGHT attribute with how many coincident points
+    /// each point stands for. This is only meaningful when the plotter
+    /// merges duplicate points.
+    pub fn set_weights(&mut self, weights: bool) {
+        self.weights = weights;
+    }
+
+    /// Include a _XFORM_ADDRESS attribute with the last few transformation
+    /// indices packed into a float, see IFS::get_xform_address()
+    pub fn set_xform_address(&mut self, xform_address: bool) {
+        self.xform_address = xform_address;
+    }
+
+    /// Include the CGA vector (x, y, z, p, n) of each point before it was
+    /// homogenized, for studying the conformal weight n - p. As glTF has
+    /// no 5-component type, this is split into a VEC3 _CGA_XYZ and a VEC2
+    /// _CGA_PN. Only p and n are stored per point, the raw x, y, z are the
+    /// position times n - p. For escaped points the position is only the
+    /// escape direction, so _CGA_XYZ is not meaningful for them.
+    pub fn set_cga(&mut self, cga: bool) {
+        self.cga = cga;
+    }
+
+    /// Report the same featureCount in every tile rather than the number
+    /// of unique IDs in this tile, see FeatureCounts::from_metadata()
+    pub fn set_feature_counts(&mut self, feature_counts: FeatureCounts) {
+        self.feature_counts = Some(feature_counts);
+    }
+
+    /// Set the generator and copyright of the glTF asset. Without a
+    /// copyright, the default Cesium copyright is used.
+    pub fn set_attribution(&mut self, attribution: Attribution) {
+        self.attribution = attribution;
+    }
+
+    /// The glTF/metadata type of the cluster coordinates
+    fn cluster_coordinates_type(&self) -> &'static str {
+        if self.full_barycentric {
+            "VEC4"
+        } else {
+            "VEC3"
+        }
+    }
+
+    /// Pack the cluster coordinates of a point, adding the 4th
+    /// barycentric coordinate if requested
+    fn pack_cluster_coordinates(&self, coordinates: &Vec3) -> Vec<u8> {
+        let mut packed = coordinates.pack().to_vec();
+        if self.full_barycentric {
+            let s = 1.0 - coordinates.x() - coordinates.y() - coordinates.z();
+            packed.extend_from_slice(&s.to_le_bytes());
+        }
+        packed
+    }
+
     /// Write a list of points to disk in GLB format
-    pub fn write(&mut self, fname: &str, buffer: &Vec<OutputPoint>) {
+    pub fn write(&mut self, fname: &str, buffer: &[OutputPoint]) {
         self.compute_layout(&buffer);
         self.make_json(&buffer);
 
@@ -191,7 +364,7 @@ impl GlbWriter {
     }
 
     /// Compute the layout of the .glb file (byte offsets and lengths)
-    fn compute_layout(&mut self, buffer: &Vec<OutputPoint>) {
+    fn compute_layout(&mut self, buffer: &[OutputPoint]) {
         let point_count = buffer.len() as u32;
         self.point_count = point_count;
 
@@ -221,8 +394,8 @@ impl GlbWriter {
                 "name" => "Positions",
                 "bufferView" => bv_id,
                 "count" => point_count,
-                "min" => min,
-                "max" => max,
+                "min" => min.to_json(),
+                "max" => max.to_json(),
                 "type" => "VEC3",
                 "componentType" => GLTF_FLOAT
             }
@@ -258,8 +431,9 @@ impl GlbWriter {
         );
         self.accessors.push(color_accessor);
         
-        // vec3 _CLUSTER_COORDINATES (aka uvw coordinates) ------------------
-        let uvw_length = point_count * SIZE_VEC3;
+        // vec3/vec4 _CLUSTER_COORDINATES (aka uvw coordinates) -------------
+        let uvw_size = if self.full_barycentric { SIZE_VEC4 } else { SIZE_VEC3 };
+        let uvw_length = point_count * uvw_size;
         let uvw_bv = BufferView::new(
             "Cluster Coordinates",
             self.buffer_views.len() as u32,
@@ -277,14 +451,14 @@ impl GlbWriter {
                 "name" => "Cluster Coordinates",
                 "bufferView" => bv_id,
                 "count" => point_count,
-                "type" => "VEC3",
+                "type" => self.cluster_coordinates_type(),
                 "componentType" => GLTF_FLOAT
             }
         );
         self.accessors.push(uvw_accessor);
 
-        // float _FEATURE_ID_0 (iterations) ----------------------------------
-        let iteration_length = point_count * SIZE_FLOAT;
+        // uint _FEATURE_ID_0 (iterations) -----------------------------------
+        let iteration_length = point_count * SIZE_UINT;
         let iteration_bv = BufferView::new(
             "Feature ID 0 (iterations)",
             self.buffer_views.len() as u32,
@@ -303,7 +477,7 @@ impl GlbWriter {
                 "bufferView" => bv_id,
                 "count" => point_count,
                 "type" => "SCALAR",
-                "componentType" => GLTF_FLOAT
+                "componentType" => GLTF_UNSIGNED_INT
             }
         );
         self.accessors.push(iteration_accessor);
@@ -358,8 +532,8 @@ impl GlbWriter {
         );
         self.accessors.push(cluster_id_accessor);
 
-        // float _FEATURE_ID_3 (point id) ----------------------------------
-        let point_id_length = point_count * SIZE_FLOAT;
+        // uint _FEATURE_ID_3 (point id) -----------------------------------
+        let point_id_length = point_count * SIZE_UINT;
         let point_id_bv = BufferView::new(
             "Feature ID 3 (point id)",
             self.buffer_views.len() as u32,
@@ -378,11 +552,36 @@ impl GlbWriter {
                 "bufferView" => bv_id,
                 "count" => point_count,
                 "type" => "SCALAR",
-                "componentType" => GLTF_FLOAT
+                "componentType" => GLTF_UNSIGNED_INT
             }
         );
         self.accessors.push(point_id_accessor);
 
+        // float _FEATURE_ID_4 (last xform group) ---------------------------
+        let last_xform_group_length = point_count * SIZE_FLOAT;
+        let last_xform_group_bv = BufferView::new(
+            "Feature ID 4 (last xform group)",
+            self.buffer_views.len() as u32,
+            next_bv_offset,
+            last_xform_group_length
+        );
+        bv_id = last_xform_group_bv.id;
+        next_bv_offset = last_xform_group_bv.after_offset();
+        self.buffer_views.push(last_xform_group_bv);
+
+        let last_xform_group_accessor = Accessor::new(
+            "_FEATURE_ID_4",
+            self.accessors.len() as u32,
+            object!{
+                "name" => "Feature ID 4 (last xform group)",
+                "bufferView" => bv_id,
+                "count" => point_count,
+                "type" => "SCALAR",
+                "componentType" => GLTF_FLOAT
+            }
+        );
+        self.accessors.push(last_xform_group_accessor);
+
         // float _LAST_XFORM ----------------------------------------------
         let last_xform_length = point_count * SIZE_FLOAT;
         let last_xform_bv = BufferView::new(
@@ -433,6 +632,142 @@ impl GlbWriter {
         );
         self.accessors.push(last_color_xform_accessor);
 
+        // uint _WEIGHT (optional) -----------------------------------------
+        if self.weights {
+            let weight_length = point_count * SIZE_UINT;
+            let weight_bv = BufferView::new(
+                "Weights",
+                self.buffer_views.len() as u32,
+                next_bv_offset,
+                weight_length
+            );
+            bv_id = weight_bv.id;
+            next_bv_offset = weight_bv.after_offset();
+            self.buffer_views.push(weight_bv);
+
+            let weight_accessor = Accessor::new(
+                "_WEIGHT",
+                self.accessors.len() as u32,
+                object!{
+                    "name" => "Weights",
+                    "bufferView" => bv_id,
+                    "count" => point_count,
+                    "type" => "SCALAR",
+                    "componentType" => GLTF_UNSIGNED_INT
+                }
+            );
+            self.accessors.push(weight_accessor);
+        }
+
+        // float _XFORM_ADDRESS (optional) ---------------------------------
+        if self.xform_address {
+            let address_length = point_count * SIZE_FLOAT;
+            let address_bv = BufferView::new(
+                "Xform addresses",
+                self.buffer_views.len() as u32,
+                next_bv_offset,
+                address_length
+            );
+            bv_id = address_bv.id;
+            next_bv_offset = address_bv.after_offset();
+            self.buffer_views.push(address_bv);
+
+            let address_accessor = Accessor::new(
+                "_XFORM_ADDRESS",
+                self.accessors.len() as u32,
+                object!{
+                    "name" => "Xform addresses",
+                    "bufferView" => bv_id,
+                    "count" => point_count,
+                    "type" => "SCALAR",
+                    "componentType" => GLTF_FLOAT
+                }
+            );
+            self.accessors.push(address_accessor);
+        }
+
+        // vec3 _CGA_XYZ and vec2 _CGA_PN (optional) ------------------------
+        if self.cga {
+            let xyz_length = point_count * SIZE_VEC3;
+            let xyz_bv = BufferView::new(
+                "CGA xyz",
+                self.buffer_views.len() as u32,
+                next_bv_offset,
+                xyz_length
+            );
+            bv_id = xyz_bv.id;
+            next_bv_offset = xyz_bv.after_offset();
+            self.buffer_views.push(xyz_bv);
+
+            let xyz_accessor = Accessor::new(
+                "_CGA_XYZ",
+                self.accessors.len() as u32,
+                object!{
+                    "name" => "CGA xyz",
+                    "bufferView" => bv_id,
+                    "count" => point_count,
+                    "type" => "VEC3",
+                    "componentType" => GLTF_FLOAT
+                }
+            );
+            self.accessors.push(xyz_accessor);
+
+            let pn_length = point_count * 2 * SIZE_FLOAT;
+            let pn_bv = BufferView::new(
+                "CGA pn",
+                self.buffer_views.len() as u32,
+                next_bv_offset,
+                pn_length
+            );
+            bv_id = pn_bv.id;
+            next_bv_offset = pn_bv.after_offset();
+            self.buffer_views.push(pn_bv);
+
+            let pn_accessor = Accessor::new(
+                "_CGA_PN",
+                self.accessors.len() as u32,
+                object!{
+                    "name" => "CGA pn",
+                    "bufferView" => bv_id,
+                    "count" => point_count,
+                    "type" => "VEC2",
+                    "componentType" => GLTF_FLOAT
+                }
+            );
+            self.accessors.push(pn_accessor);
+        }
+
+        // vec3 NORMAL (optional) ------------------------------------------
+        if let Some(normals) = &self.normals {
+            if normals.len() != buffer.len() {
+                panic!("GlbWriter: there must be one normal per point");
+            }
+
+            let normal_length = point_count * SIZE_VEC3;
+            let normal_bv = BufferView::new(
+                "Normals",
+                self.buffer_views.len() as u32,
+                next_bv_offset,
+                normal_length
+            );
+            bv_id = normal_bv.id;
+            next_bv_offset = normal_bv.after_offset();
+            self.buffer_views.push(normal_bv);
+
+            let normal_accessor = Accessor::new(
+                "NORMAL",
+                self.accessors.len() as u32,
+                object!{
+                    "name" => "Normals",
+                    "bufferView" => bv_id,
+                    "count" => point_count,
+                    "type" => "VEC3",
+                    "componentType" => GLTF_FLOAT
+                }
+            );
+            self.accessors.push(normal_accessor);
+        }
+
         // binary chunk layout ---------------------------------------------
 
         // The offset after the last buffer view is equal to the length of
@@ -445,8 +780,76 @@ impl GlbWriter {
         self.buffer_length = buffer_length;
     }
 
+    /// The EXT_structural_metadata class of the per-point properties, i.e.
+    /// the type of each property this writer includes
+    pub fn make_class_json(&self) -> JsonValue {
+        let mut class = object!{
+            "name" => "Fractal",
+            "description" => "Per-point fractal properties",
+            "properties" => object!{
+                "cluster_coordinates" => object!{
+                    "type" => self.cluster_coordinates_type(),
+                    "componentType" => "FLOAT32"
+                },
+                "iteration" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "UINT32"
+                },
+                "cluster_copy" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "FLOAT32"
+                },
+                "cluster_id" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "FLOAT32"
+                },
+                "point_id" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "UINT32"
+                },
+                "last_xform" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "FLOAT32"
+                },
+                "last_xform_group" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "FLOAT32"
+                },
+                "last_color_xform" => object!{
+                    "type" => "SCALAR",
+                    "componentType" => "FLOAT32"
+                }
+            }
+        };
+
+        let properties = &mut class["properties"];
+        if self.weights {
+            properties["weight"] = object!{
+                "type" => "SCALAR",
+                "componentType" => "UINT32"
+            };
+        }
+        if self.xform_address {
+            properties["xform_address"] = object!{
+                "type" => "SCALAR",
+                "componentType" => "FLOAT32"
+            };
+        }
+        if self.cga {
+            properties["cga_xyz"] = object!{
+                "type" => "VEC3",
+                "componentType" => "FLOAT32"
+            };
+            properties["cga_pn"] = object!{
+                "type" => "VEC2",
+                "componentType" => "FLOAT32"
+            };
+        }
+        class
+    }
+
     /// Create the glTF JSON for the JSON chunk
-    fn make_json(&mut self, buffer: &Vec<OutputPoint>) {
+    fn make_json(&mut self, buffer: &[OutputPoint]) {
         let accessors: Vec<JsonValue> = 
             self.accessors.iter().map(|x| x.json.clone()).collect();
         let buffer_views: Vec<JsonValue> =
@@ -458,13 +861,13 @@ impl GlbWriter {
                 JsonValue::Number(accessor.accessor_id.into());
         }
         
-        let copyright = format!("© {} Cesium GS, Inc.", Utc::now().year());
-        let generator = 
-            "Chaos Game 3D fractal generator from https://github.com/ptrgags/chaos-game-3d";
+        let copyright = self.attribution.copyright.clone().unwrap_or_else(
+            || format!("© {} Cesium GS, Inc.", Utc::now().year()));
+        let generator = self.attribution.generator();
 
         let feature_id_json = self.compute_feature_id_json(&buffer);
 
-        let json = object!{
+        let mut json = object!{
             "asset" => object!{
                 "version" => "2.0",
                 "generator" => generator, 
@@ -474,40 +877,7 @@ impl GlbWriter {
                 "EXT_structural_metadata" => object!{
                     "schema" => object!{
                         "classes" => object!{
-                            "fractal" => object!{
-                                "name" => "Fractal",
-                                "description" => "Per-point fractal properties",
-                                "properties" => object!{
-                                    "cluster_coordinates" => object!{
-                                        "type" => "VEC3",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "iteration" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "cluster_copy" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "cluster_id" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "point_id" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "last_xform" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    },
-                                    "last_color_xform" => object!{
-                                        "type" => "SCALAR",
-                                        "componentType" => "FLOAT32"
-                                    }
-                                }
-                            }
+                            "fractal" => self.make_class_json()
                         }
                     },
                     "propertyMappings" => object!{
@@ -533,6 +903,9 @@ impl GlbWriter {
                             "last_xform" => object!{
                                 "attribute" => "_LAST_XFORM"
                             },
+                            "last_xform_group" => object!{
+                                "attribute" => "_FEATURE_ID_4"
+                            },
                             "last_color_xform" => object!{
                                 "attribute" => "_LAST_COLOR_XFORM"
                             }
@@ -549,12 +922,7 @@ impl GlbWriter {
             "nodes" => array![
                 object!{
                     "mesh" => 0,
-                    "matrix" => array![
-                        1, 0, 0, 0,
-                        0, 0, -1, 0,
-                        0, 1, 0, 0,
-                        0, 0, 0, 1
-                    ]
+                    "matrix" => Z_UP_TO_Y_UP.to_vec()
                 }
             ],
             "meshes" => array![
@@ -584,6 +952,20 @@ impl GlbWriter {
             ]
         };
 
+        let metadata = &mut json["extensions"]["EXT_structural_metadata"];
+        let mappings = &mut metadata["propertyMappings"]["properties"];
+        if self.weights {
+            mappings["weight"] = object!{ "attribute" => "_WEIGHT" };
+        }
+        if self.xform_address {
+            mappings["xform_address"] =
+                object!{ "attribute" => "_XFORM_ADDRESS" };
+        }
+        if self.cga {
+            mappings["cga_xyz"] = object!{ "attribute" => "_CGA_XYZ" };
+            mappings["cga_pn"] = object!{ "attribute" => "_CGA_PN" };
+        }
+
         let json_str = json::stringify(json);
         let length = json_str.as_bytes().len() as u32;
         self.json = json_str;
@@ -591,40 +973,37 @@ impl GlbWriter {
         self.json_chunk.padding_length = compute_padding_length(length, ALIGNMENT);
     }
 
-    fn compute_feature_id_json(&self, buffer: &Vec<OutputPoint>) -> JsonValue {
-        // Count the number of unique IDs for each of the feature Ids
-        // since featureCount is required.
-        let mut iterations: HashSet<u64> = HashSet::new();
-        let mut cluster_copies: HashSet<u16> = HashSet::new();
-        let mut cluster_ids: HashSet<u16> = HashSet::new();
-        let mut point_id: HashSet<u16> = HashSet::new();
-        for point in buffer.iter() {
-            iterations.insert(point.iteration);
-            cluster_copies.insert(point.cluster_copy);
-            cluster_ids.insert(point.cluster_id);
-            point_id.insert(point.point_id);
-        }
+    fn compute_feature_id_json(&self, buffer: &[OutputPoint]) -> JsonValue {
+        // featureCount is required. Unless global counts were given, count
+        // the number of unique IDs for each of the feature IDs
+        let counts = self.feature_counts
+            .unwrap_or_else(|| FeatureCounts::from_points(buffer));
 
         array![
             object!{
                 "label" => "iteration",
-                "featureCount" => iterations.len(),
+                "featureCount" => counts.iterations,
                 "attribute" => 0
             },
             object!{
                 "label" => "cluster_copy",
-                "featureCount" => cluster_copies.len(),
+                "featureCount" => counts.cluster_copies,
                 "attribute" => 1
             },
             object!{
                 "label" => "cluster_id",
-                "featureCount" => cluster_ids.len(),
+                "featureCount" => counts.cluster_ids,
                 "attribute" => 2
             },
             object!{
                 "label" => "point_id",
-                "featureCount" => point_id.len(),
+                "featureCount" => counts.point_ids,
                 "attribute" => 3
+            },
+            object!{
+                "label" => "last_xform_group",
+                "featureCount" => counts.last_xform_groups,
+                "attribute" => 4
             }
         ]
     }
@@ -648,7 +1027,7 @@ impl GlbWriter {
     }
 
     /// Write the binary chunk to the file
-    fn write_binary_chunk(&self, file: &mut File, buffer: &Vec<OutputPoint>) {
+    fn write_binary_chunk(&self, file: &mut File, buffer: &[OutputPoint]) {
         let error_msg = "could not write binary chunk";
         file.write_all(&self.binary_chunk.data_length().to_le_bytes()).expect(error_msg);
         file.write_all(b"BIN\0").expect(error_msg);
@@ -656,34 +1035,54 @@ impl GlbWriter {
     }
 
     /// Write the binary buffer from a list of points
-    fn write_buffer(&self, file: &mut File, buffer: &Vec<OutputPoint>) {
-        let mut positions: Vec<u8> = Vec::new();
+    fn write_buffer(&self, file: &mut File, buffer: &[OutputPoint]) {
+        let bv_data = self.pack_buffer_views(buffer);
+        let n = bv_data.len();
+        for i in 0..n {
+            let buffer_view = &self.buffer_views[i];
+            let data = &bv_data[i];
+            let padding = 
+                make_padding(buffer_view.padding_length, PADDING_BINARY);
+            
+            let message = format!("Could not write bufferView {}", buffer_view.name);
+            file.write_all(&data).expect(&message);
+            
+            let padding_message = format!("could not write padding for bufferView {}", buffer_view.name);
+            file.write_all(&padding).expect(&padding_message);
+        }
+    }
+
+    /// Pack the data of each buffer view, in the same order as
+    /// compute_layout() made them
+    fn pack_buffer_views(&self, buffer: &[OutputPoint]) -> Vec<Vec<u8>> {
+        let positions =
+            Vec3::pack_all(buffer.iter().map(|point| &point.position));
         let mut colors: Vec<u8> = Vec::new();
         let mut cluster_coordinates: Vec<u8> = Vec::new();
         let mut iterations: Vec<u8> = Vec::new();
         let mut cluster_copies: Vec<u8> = Vec::new();
         let mut cluster_ids: Vec<u8> = Vec::new();
         let mut point_ids: Vec<u8> = Vec::new();
+        let mut last_xform_groups: Vec<u8> = Vec::new();
         let mut last_xforms: Vec<u8> = Vec::new();
         let mut last_color_xforms: Vec<u8> = Vec::new();
         
 
         for point in buffer {
-            positions.extend_from_slice(&point.position.pack());
-
             // colors is a normalized vec3. Since each one is only 3 bytes,
             // we need to add a fourth byte of padding.
             colors.extend_from_slice(&point.color.to_color().pack());
             colors.push(0x00);
 
             cluster_coordinates.extend_from_slice(
-                &point.cluster_coordinates.pack());
+                &self.pack_cluster_coordinates(&point.cluster_coordinates));
 
-            // all of the ids, though they are actually integers, are stored
-            // in the glTF as FLOAT accessors since this is easiest for use
-            // on the GPU.
-            let iteration = point.iteration as f32;
-            iterations.extend_from_slice(&iteration.to_le_bytes());
+            iterations.extend_from_slice(&pack_iteration(point.iteration));
+
+            // the rest of the ids except the point id, though they are
+            // actually integers, are stored in the glTF as FLOAT accessors
+            // since this is easiest for use on the GPU. They are small
+            // enough to be exact in f32.
 
             let cluster_copy = point.cluster_copy as f32;
             cluster_copies.extend_from_slice(&cluster_copy.to_le_bytes());
@@ -691,8 +1090,13 @@ impl GlbWriter {
             let cluster_id = point.cluster_id as f32;
             cluster_ids.extend_from_slice(&cluster_id.to_le_bytes());
 
-            let point_id = point.point_id as f32;
-            point_ids.extend_from_slice(&point_id.to_le_bytes());
+            // Point IDs are unique across a whole cluster, which can have
+            // more than the 2^24 points f32 represents exactly
+            point_ids.extend_from_slice(&point.point_id.to_le_bytes());
+
+            let last_xform_group = point.last_xform_group as f32;
+            last_xform_groups.extend_from_slice(
+                &last_xform_group.to_le_bytes());
 
             let last_xform = point.last_xform as f32;
             last_xforms.extend_from_slice(&last_xform.to_le_bytes());
@@ -702,7 +1106,7 @@ impl GlbWriter {
         }
 
         // Make a parallel vector of data to match the buffer views
-        let bv_data = vec![
+        let mut bv_data = vec![
             positions,
             colors,
             cluster_coordinates,
@@ -710,24 +1114,252 @@ impl GlbWriter {
             cluster_copies,
             cluster_ids,
             point_ids,
+            last_xform_groups,
             last_xforms,
             last_color_xforms,
         ];
 
-        let n = bv_data.len();
-        for i in 0..n {
-            let buffer_view = &self.buffer_views[i];
-            let data = &bv_data[i];
+        if self.weights {
+            let mut weight_data: Vec<u8> = Vec::new();
+            for point in buffer {
+                weight_data.extend_from_slice(&point.weight.to_le_bytes());
+            }
+            bv_data.push(weight_data);
+        }
+
+        if self.xform_address {
+            let mut address_data: Vec<u8> = Vec::new();
+            for point in buffer {
+                // Addresses are at most 2^24 so they are exact in f32
+                let address = point.xform_address as f32;
+                address_data.extend_from_slice(&address.to_le_bytes());
+            }
+            bv_data.push(address_data);
+        }
+
+        if self.cga {
+            let mut xyz_data: Vec<u8> = Vec::new();
+            let mut pn_data: Vec<u8> = Vec::new();
+            for point in buffer {
+                let [p, n] = point.cga_pn;
+                xyz_data.extend_from_slice(&(point.position * (n - p)).pack());
+                pn_data.extend_from_slice(&p.to_le_bytes());
+                pn_data.extend_from_slice(&n.to_le_bytes());
+            }
+            bv_data.push(xyz_data);
+            bv_data.push(pn_data);
+        }
+
+        if let Some(normals) = &self.normals {
+            bv_data.push(Vec3::pack_all(normals));
+        }
+
+        bv_data
+    }
+}
+
+/// Writer for a simple GLB of colored line segments. This is used for
+/// debugging, e.g. drawing the octree as wireframe boxes. Unlike GlbWriter,
+/// there is no metadata, just positions and colors.
+pub struct LineGlbWriter {
+    /// Total length of binary glTF, including header and all chunks
+    total_length: u32,
+    /// The layout of the JSON chunk
+    json_chunk: Chunk,
+    /// The layout of the BIN chunk
+    binary_chunk: Chunk,
+    /// The glTF buffer views (positions then colors)
+    buffer_views: Vec<BufferView>,
+    // The final JSON string that will be written to the JSON chunk
+    json: String,
+    /// Only the generator is used, there is no copyright
+    attribution: Attribution,
+    /// Column-major transform from the octree's coordinates to 3D Tiles
+    /// coordinates, see set_transform()
+    transform: [f64; 16],
+}
+
+impl LineGlbWriter {
+    pub fn new() -> Self {
+        Self {
+            total_length: 0,
+            json_chunk: Chunk::new(),
+            binary_chunk: Chunk::new(),
+            buffer_views: Vec::new(),
+            json: String::new(),
+            attribution: Attribution::default(),
+            transform: IDENTITY_MATRIX,
+        }
+    }
+
+    /// The lines are not part of the tileset, so they don't get the root
+    /// tile's transform. Apply it here instead so the lines line up with
+    /// the tiles, see tileset_writer::Orientation.
+    pub fn set_transform(&mut self, transform: [f64; 16]) {
+        self.transform = transform;
+    }
+
+    /// Set the generator of the glTF asset, see GlbWriter::set_attribution()
+    pub fn set_attribution(&mut self, attribution: Attribution) {
+        self.attribution = attribution;
+    }
+
+    /// Write line segments to disk in GLB format. Each line is a pair of
+    /// endpoints with a single color
+    pub fn write(&mut self, fname: &str, lines: &[(Vec3, Vec3)], colors: &[Vec3]) {
+        assert_eq!(
+            lines.len(), colors.len(), "each line must have one color");
+        let vertex_count = 2 * lines.len() as u32;
+
+        let position_bv = BufferView::new(
+            "Positions", 0, 0, vertex_count * SIZE_VEC3);
+        let color_bv = BufferView::new(
+            "Colors", 1, position_bv.after_offset(), vertex_count * SIZE_VEC3);
+        let buffer_length = color_bv.after_offset();
+        self.buffer_views = vec![position_bv, color_bv];
+        self.binary_chunk.chunk_length = buffer_length;
+        // Since the buffer views are already padded, no extra padding is needed
+        self.binary_chunk.padding_length = 0;
+
+        let endpoints: Vec<Vec3> = lines.iter()
+            .flat_map(|(start, end)| vec![*start, *end])
+            .collect();
+        self.make_json(&endpoints, buffer_length);
+
+        self.total_length = 
+            GLTF_HEADER_LENGTH + 
+            self.json_chunk.total_length() + 
+            self.binary_chunk.total_length();
+
+        let error_msg = format!("Cannot open {}", fname);
+        let mut file = File::create(fname).expect(&error_msg);
+        let error_msg = "could not write line glb";
+        file.write_all(b"glTF").expect(error_msg);
+        file.write_all(&GLTF_VERSION.to_le_bytes()).expect(error_msg);
+        file.write_all(&self.total_length.to_le_bytes()).expect(error_msg);
+
+        file.write_all(&self.json_chunk.data_length().to_le_bytes())
+            .expect(error_msg);
+        file.write_all(b"JSON").expect(error_msg);
+        file.write_all(self.json.as_bytes()).expect(error_msg);
+        let padding = make_padding(self.json_chunk.padding_length, PADDING_JSON);
+        file.write_all(&padding).expect(error_msg);
+
+        file.write_all(&self.binary_chunk.data_length().to_le_bytes())
+            .expect(error_msg);
+        file.write_all(b"BIN\0").expect(error_msg);
+
+        let mut positions: Vec<u8> = Vec::new();
+        for endpoint in endpoints.iter() {
+            positions.extend_from_slice(&endpoint.pack());
+        }
+        let mut vertex_colors: Vec<u8> = Vec::new();
+        for color in colors.iter() {
+            // Both endpoints are the same color
+            vertex_colors.extend_from_slice(&color.pack());
+            vertex_colors.extend_from_slice(&color.pack());
+        }
+
+        let bv_data = [positions, vertex_colors];
+        for (buffer_view, data) in self.buffer_views.iter().zip(bv_data.iter()) {
+            file.write_all(data).expect(error_msg);
             let padding = 
                 make_padding(buffer_view.padding_length, PADDING_BINARY);
-            
-            let message = format!("Could not write bufferView {}", buffer_view.name);
-            file.write_all(&data).expect(&message);
-            
-            let padding_message = format!("could not write padding for bufferView {}", buffer_view.name);
-            file.write_all(&padding).expect(&padding_message);
+            file.write_all(&padding).expect(error_msg);
+        }
+    }
+
+    /// Create the glTF JSON for the JSON chunk
+    fn make_json(&mut self, endpoints: &[Vec3], buffer_length: u32) {
+        let vertex_count = endpoints.len() as u32;
+        let (min, max) = compute_vec3_min_max(endpoints.iter());
+        let buffer_views: Vec<JsonValue> =
+            self.buffer_views.iter().map(|x| x.to_json()).collect();
+        let position_accessor = object!{
+            "name" => "Positions",
+            "bufferView" => 0,
+            "count" => vertex_count,
+            "min" => min.to_json(),
+            "max" => max.to_json(),
+            "type" => "VEC3",
+            "componentType" => GLTF_FLOAT
+        };
+        let color_accessor = object!{
+            "name" => "Colors",
+            "bufferView" => 1,
+            "count" => vertex_count,
+            "type" => "VEC3",
+            "componentType" => GLTF_FLOAT
+        };
+
+        let json = object!{
+            "asset" => object!{
+                "version" => "2.0",
+                "generator" => self.attribution.generator()
+            },
+            "scene" => 0,
+            "scenes" => array![
+                object!{
+                    "nodes" => array![0]
+                }
+            ],
+            "nodes" => array![
+                object!{
+                    "name" => "Lines",
+                    "mesh" => 0,
+                    "matrix" => multiply_matrices(
+                        &Z_UP_TO_Y_UP, &self.transform).to_vec()
+                }
+            ],
+            "meshes" => array![
+                object!{
+                    "primitives" => array![
+                        object!{
+                            "attributes" => object!{
+                                "POSITION" => 0,
+                                "COLOR_0" => 1
+                            },
+                            "mode" => GLTF_LINES
+                        }
+                    ]
+                }
+            ],
+            "accessors" => array![position_accessor, color_accessor],
+            "bufferViews" => buffer_views,
+            "buffers" => array![
+                object!{
+                    "byteLength" => buffer_length
+                }
+            ]
+        };
+
+        let json_str = json::stringify(json);
+        let length = json_str.len() as u32;
+        self.json = json_str;
+        self.json_chunk.chunk_length = length;
+        self.json_chunk.padding_length = compute_padding_length(length, ALIGNMENT);
+    }
+}
+
+/// Multiply two column-major 4x4 matrices, a * b
+fn multiply_matrices(a: &[f64; 16], b: &[f64; 16]) -> [f64; 16] {
+    let mut product = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            product[4 * col + row] = (0..4)
+                .map(|k| a[4 * k + row] * b[4 * col + k])
+                .sum();
         }
     }
+    product
+}
+
+/// Iterations routinely go past 2^24, where f32 can no longer represent
+/// every integer, so they are stored as UNSIGNED_INT instead. Iterations
+/// past u32::MAX are stored as u32::MAX rather than failing the save.
+fn pack_iteration(iteration: u64) -> [u8; 4] {
+    let iteration = u32::try_from(iteration).unwrap_or(u32::MAX);
+    iteration.to_le_bytes()
 }
 
 /// Compute the number of padding bytes needed to meet an alignment requirement
@@ -748,21 +1380,321 @@ fn make_padding(byte_len: u32, pad_char: u8) -> Vec<u8> {
 }
 
 /// Iterate over a list of points and compute the min/max position
-fn compute_min_max(points: &Vec<OutputPoint>) -> (Vec<f32>, Vec<f32>) {
-    let mut min = [f32::MAX; 3];
-    let mut max = [f32::MIN; 3];
-    for point in points.iter() {
-        let position = point.position;
-        let x = *position.x();
-        let y = *position.y();
-        let z = *position.z();
-        min[0] = min[0].min(x);
-        min[1] = min[1].min(y);
-        min[2] = min[2].min(z);
-        max[0] = max[0].max(x);
-        max[1] = max[1].max(y);
-        max[2] = max[2].max(z);
-    }
-
-    (min.to_vec(), max.to_vec())
+fn compute_min_max(points: &[OutputPoint]) -> (Vec3, Vec3) {
+    compute_vec3_min_max(points.iter().map(|point| &point.position))
+}
+
+/// Iterate over a list of positions and compute the component-wise
+/// min/max. If there are no positions, min is all f32::MAX and max is all
+/// f32::MIN.
+fn compute_vec3_min_max<'a, I>(positions: I) -> (Vec3, Vec3)
+        where I: IntoIterator<Item = &'a Vec3> {
+    let start = (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN));
+    positions.into_iter().fold(start, |(min, max), position| {
+        (Vec3::min(&min, position), Vec3::max(&max, position))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::convert::TryInto;
+
+    use crate::fractal_metadata::GENERATOR;
+
+    fn make_point(iteration: u64) -> OutputPoint {
+        OutputPoint {
+            position: Vec3::zero(),
+            color: Vec3::ones(),
+            cluster_coordinates: Vec3::zero(),
+            iteration,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        }
+    }
+
+    #[test]
+    fn test_large_iterations_are_distinct() {
+        // 2^24 + 1 rounds to 2^24 in f32
+        let big = 1u64 << 24;
+        assert_eq!((big as f32) as u64, ((big + 1) as f32) as u64);
+
+        let stored: Vec<u64> = (big..big + 4)
+            .map(|i| u32::from_le_bytes(pack_iteration(i)) as u64)
+            .collect();
+        assert_eq!(stored, vec![big, big + 1, big + 2, big + 3]);
+    }
+
+    #[test]
+    fn test_huge_iterations_saturate() {
+        let max = u32::MAX as u64;
+        let stored: Vec<u32> = [max - 1, max, max + 1, u64::MAX]
+            .iter()
+            .map(|i| u32::from_le_bytes(pack_iteration(*i)))
+            .collect();
+        assert_eq!(stored, vec![u32::MAX - 1, u32::MAX, u32::MAX, u32::MAX]);
+    }
+
+    #[test]
+    fn test_iteration_feature_count() {
+        let big = 1u64 << 24;
+        let buffer: Vec<OutputPoint> = (big..big + 10).map(make_point).collect();
+        let writer = GlbWriter::new();
+        let feature_ids = writer.compute_feature_id_json(&buffer);
+        assert_eq!(feature_ids[0]["label"], "iteration");
+        assert_eq!(feature_ids[0]["featureCount"], 10);
+    }
+
+    #[test]
+    fn test_global_feature_counts() {
+        let counts = FeatureCounts {
+            iterations: 100,
+            cluster_copies: 2,
+            cluster_ids: 1,
+            point_ids: 50,
+            last_xform_groups: 3,
+        };
+        let tile_a: Vec<OutputPoint> = (0..3).map(make_point).collect();
+        let tile_b: Vec<OutputPoint> = (40..48).map(make_point).collect();
+
+        let mut writer = GlbWriter::new();
+        assert_ne!(
+            writer.compute_feature_id_json(&tile_a),
+            writer.compute_feature_id_json(&tile_b));
+
+        writer.set_feature_counts(counts);
+        let feature_ids_a = writer.compute_feature_id_json(&tile_a);
+        let feature_ids_b = writer.compute_feature_id_json(&tile_b);
+        assert_eq!(feature_ids_a, feature_ids_b);
+        assert_eq!(feature_ids_a[0]["featureCount"], 100);
+        assert_eq!(feature_ids_a[3]["featureCount"], 50);
+    }
+
+    #[test]
+    fn test_compute_min_max() {
+        let mut a = make_point(0);
+        a.position = Vec3::new(1.0, -2.0, 3.0);
+        let mut b = make_point(1);
+        b.position = Vec3::new(-1.0, 2.0, 0.0);
+        let (min, max) = compute_min_max(&[a, b]);
+        assert_eq!(min.to_json(), array![-1.0, -2.0, 0.0]);
+        assert_eq!(max.to_json(), array![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn test_iteration_accessor_is_uint() {
+        let buffer: Vec<OutputPoint> = (0..3).map(make_point).collect();
+        let mut writer = GlbWriter::new();
+        writer.compute_layout(&buffer);
+        let accessor = writer.accessors
+            .iter()
+            .find(|accessor| accessor.semantic == "_FEATURE_ID_0")
+            .unwrap();
+        assert_eq!(accessor.json["componentType"], GLTF_UNSIGNED_INT);
+    }
+
+    #[test]
+    fn test_large_point_ids_are_exact() {
+        // 2^24 + 1 is the first integer f32 can't represent
+        let mut point = make_point(0);
+        point.point_id = 16_777_217;
+        let buffer = vec![point];
+
+        let mut writer = GlbWriter::new();
+        writer.compute_layout(&buffer);
+        let accessor = writer.accessors
+            .iter()
+            .find(|accessor| accessor.semantic == "_FEATURE_ID_3")
+            .unwrap();
+        assert_eq!(accessor.json["componentType"], GLTF_UNSIGNED_INT);
+
+        let bv_id = accessor.json["bufferView"].as_usize().unwrap();
+        assert_eq!(writer.buffer_views[bv_id].byte_length, SIZE_UINT);
+        let bv_data = writer.pack_buffer_views(&buffer);
+        assert_eq!(bv_data[bv_id], 16_777_217u32.to_le_bytes());
+    }
+
+    #[test]
+    fn test_cga_accessors() {
+        let mut point = make_point(0);
+        point.position = Vec3::new(1.0, 2.0, 3.0);
+        point.cga_pn = [1.5, 3.5];
+        let buffer = vec![point];
+
+        let mut writer = GlbWriter::new();
+        writer.compute_layout(&buffer);
+        let has_cga = |writer: &GlbWriter| writer.accessors
+            .iter()
+            .any(|accessor| accessor.semantic.starts_with("_CGA"));
+        assert!(!has_cga(&writer));
+
+        let mut writer = GlbWriter::new();
+        writer.set_cga(true);
+        writer.compute_layout(&buffer);
+        let semantics: Vec<&str> = writer.accessors
+            .iter()
+            .map(|accessor| accessor.semantic.as_str())
+            .filter(|semantic| semantic.starts_with("_CGA"))
+            .collect();
+        assert_eq!(semantics, vec!["_CGA_XYZ", "_CGA_PN"]);
+    }
+
+    #[test]
+    fn test_cga_buffer_views() {
+        let mut point = make_point(0);
+        point.cga_pn = [1.5, 3.5];
+        let buffer = vec![point.clone(), point];
+        let total = |views: &[Vec<u8>]| -> usize {
+            views.iter().map(|view| view.len()).sum()
+        };
+
+        let writer = GlbWriter::new();
+        let plain = writer.pack_buffer_views(&buffer);
+
+        let mut writer = GlbWriter::new();
+        writer.set_cga(true);
+        let with_cga = writer.pack_buffer_views(&buffer);
+        assert_eq!(with_cga.len(), plain.len() + 2);
+        assert_eq!(total(&with_cga), total(&plain) + 2 * (12 + 8));
+
+        let pn: Vec<u8> = [1.5f32, 3.5, 1.5, 3.5]
+            .iter()
+            .flat_map(|x| x.to_le_bytes().to_vec())
+            .collect();
+        assert_eq!(with_cga.last().unwrap(), &pn);
+    }
+
+    #[test]
+    fn test_attribution() {
+        let buffer = vec![make_point(0)];
+        let asset = |writer: &mut GlbWriter| {
+            writer.compute_layout(&buffer);
+            writer.make_json(&buffer);
+            json::parse(&writer.json).unwrap()["asset"].clone()
+        };
+
+        let default_asset = asset(&mut GlbWriter::new());
+        assert_eq!(default_asset["generator"], GENERATOR);
+        assert!(default_asset["copyright"]
+            .as_str()
+            .unwrap()
+            .ends_with("Cesium GS, Inc."));
+
+        let mut writer = GlbWriter::new();
+        writer.set_attribution(Attribution::from_json(&object!{
+            "author" => "Someone",
+            "copyright" => "CC0",
+            "generator" => "my fork"
+        }));
+        let custom_asset = asset(&mut writer);
+        assert_eq!(custom_asset["generator"], "my fork");
+        assert_eq!(custom_asset["copyright"], "CC0");
+    }
+
+    /// Multiply a column-major 4x4 matrix by a point
+    fn transform_point(matrix: &[f64; 16], point: [f64; 3]) -> [f64; 3] {
+        let mut result = [matrix[12], matrix[13], matrix[14]];
+        for (row, component) in result.iter_mut().enumerate() {
+            for (col, coordinate) in point.iter().enumerate() {
+                *component += matrix[4 * col + row] * coordinate;
+            }
+        }
+        result
+    }
+
+    fn node_matrix(json: &str) -> [f64; 16] {
+        let gltf = json::parse(json).unwrap();
+        let matrix: Vec<f64> = gltf["nodes"][0]["matrix"]
+            .members()
+            .map(|x| x.as_f64().unwrap())
+            .collect();
+        matrix.try_into().unwrap()
+    }
+
+    #[test]
+    fn test_line_corners_match_tiles() {
+        // The viewer turns glTF y-up back to z-up, (x, y, z) -> (x, -z, y)
+        let y_up_to_z_up = [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, -1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ];
+        // A root tile transform for y-up, left-handed fractals,
+        // (x, y, z) -> (-x, -z, y)
+        let transform = [
+            -1.0, 0.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, -1.0, 0.0, 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ];
+        let corner = [1.0, 2.0, 3.0];
+
+        let buffer = vec![make_point(0)];
+        let mut tile_writer = GlbWriter::new();
+        tile_writer.compute_layout(&buffer);
+        tile_writer.make_json(&buffer);
+        let in_tile = transform_point(&y_up_to_z_up, transform_point(
+            &node_matrix(&tile_writer.json), corner));
+        let tile_corner = transform_point(&transform, in_tile);
+
+        let mut line_writer = LineGlbWriter::new();
+        line_writer.set_transform(transform);
+        line_writer.make_json(&[Vec3::zero(), Vec3::ones()], 24);
+        let line_corner = transform_point(&y_up_to_z_up, transform_point(
+            &node_matrix(&line_writer.json), corner));
+
+        assert_eq!(line_corner, tile_corner);
+        assert_eq!(tile_corner, [-1.0, -3.0, 2.0]);
+    }
+
+    #[test]
+    fn test_line_attribution() {
+        let lines = [(Vec3::zero(), Vec3::ones())];
+        let generator = |writer: &mut LineGlbWriter| {
+            writer.make_json(&[lines[0].0, lines[0].1], 24);
+            json::parse(&writer.json).unwrap()["asset"]["generator"].clone()
+        };
+
+        assert_eq!(generator(&mut LineGlbWriter::new()), GENERATOR);
+
+        let mut writer = LineGlbWriter::new();
+        writer.set_attribution(Attribution::from_json(&object!{
+            "generator" => "my fork"
+        }));
+        assert_eq!(generator(&mut writer), "my fork");
+    }
+
+    #[test]
+    fn test_full_barycentric() {
+        let mut point = make_point(0);
+        point.cluster_coordinates = Vec3::new(0.1, 0.2, 0.3);
+        let buffer = vec![point];
+
+        let mut writer = GlbWriter::new();
+        writer.compute_layout(&buffer);
+        assert_eq!(writer.pack_cluster_coordinates(&Vec3::zero()).len(), 12);
+
+        let mut writer = GlbWriter::new();
+        writer.set_full_barycentric(true);
+        writer.compute_layout(&buffer);
+        let accessor = writer.accessors
+            .iter()
+            .find(|accessor| accessor.semantic == "_CLUSTER_COORDINATES")
+            .unwrap();
+        assert_eq!(accessor.json["type"], "VEC4");
+
+        let packed = writer.pack_cluster_coordinates(&buffer[0].cluster_coordinates);
+        assert_eq!(packed.len(), 16);
+        let s = f32::from_le_bytes([packed[12], packed[13], packed[14], packed[15]]);
+        assert!((s - 0.4).abs() < 1e-6);
+    }
 }
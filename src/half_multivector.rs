@@ -1,3 +1,5 @@
+#[cfg(feature = "op_counts")]
+use std::cell::Cell;
 use std::cmp::Eq;
 use std::fmt::{Debug, Formatter, Result};
 
@@ -7,11 +9,33 @@ use crate::vector::Vec3;
 /// component even (scalar = 0, bivector = 2, quadvector = 4) or odd
 /// (vector = 1, trivector = 3, 5-vector = 5)?
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-enum Parity {
+pub enum Parity {
     Even,
     Odd
 }
 
+#[cfg(feature = "op_counts")]
+thread_local! {
+    /// Multiply-adds done by geometric_product() on this thread
+    static PRODUCT_OPS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// How many multiply-adds of components geometric_product() has done on
+/// this thread. Unlike timing, this doesn't depend on the hardware, so it
+/// shows how much of the general product the fast paths avoid. This is
+/// only counted with the op_counts feature, so normal builds don't pay
+/// for it.
+#[cfg(feature = "op_counts")]
+pub fn product_op_count() -> Option<u64> {
+    Some(PRODUCT_OPS.with(Cell::get))
+}
+
+/// Without the op_counts feature, nothing is counted
+#[cfg(not(feature = "op_counts"))]
+pub fn product_op_count() -> Option<u64> {
+    None
+}
+
 /// the geometric product will be even if the parities match, and
 /// odd if the parities are opposite
 fn get_product_parity(left: &Parity, right: &Parity) -> Parity {
@@ -50,6 +74,11 @@ const QUADVECTOR_END: usize = 6;
 const BIVECTOR_START: usize = 6;
 const BIVECTOR_END: usize = 16;
 
+/// Points further than this from the origin are considered to have escaped
+/// to infinity, see HalfMultivector::try_to_vec3(). This is squared since
+/// it is compared with the squared distance.
+const ESCAPE_RADIUS_SQR: f64 = 1e18;
+
 
 // Odd multivectors:
 // [xyzpn, x, y, z, p, n, zpn, ypn, yzn, yzp, xpn, xzn, xzp, xyn, xyp, xyz]
@@ -82,6 +111,12 @@ const TRIVECTOR_START: usize = 6;
 #[allow(dead_code)]
 const TRIVECTOR_END: usize = 16;
 
+/// Tolerance for treating non-rotor components as zero in log() and exp()
+const ROTOR_EPSILON: f64 = 1e-12;
+
+/// Relative tolerance for checking that V~V is a nonzero scalar in inverse()
+const VERSOR_NORM_TOLERANCE: f64 = 1e-9;
+
 type ComponentLUT = [[usize; 16]; 16];
 
 const COMPONENTS_EVEN_EVEN: ComponentLUT = [
@@ -232,6 +267,20 @@ fn get_sign_table(left: &Parity, right: &Parity) -> SignLUT {
     }
 }
 
+/// The grade of each even component, in the order listed above
+const GRADES_EVEN: [usize; 16] =
+    [0, 4, 4, 4, 4, 4, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2];
+/// The grade of each odd component, in the order listed above
+const GRADES_ODD: [usize; 16] =
+    [5, 1, 1, 1, 1, 1, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3];
+
+fn get_grades(parity: &Parity) -> [usize; 16] {
+    match parity {
+        Parity::Even => GRADES_EVEN,
+        Parity::Odd => GRADES_ODD,
+    }
+}
+
 /// Find the first non-zero component
 fn find_start(array: &[f64]) -> usize {
     match array.iter().position(|&x| x != 0.0) {
@@ -248,6 +297,28 @@ fn find_end(array: &[f64]) -> usize {
     }
 }
 
+/// Special cases of versors that have a fast path in sandwich_product()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum VersorKind {
+    /// Scalar + Euclidean bivector (xy, xz, yz)
+    Rotor,
+    /// 1 + (translation vector) * infinity
+    Translator,
+}
+
+/// The product of two rotors is a rotor, and the product of two translators
+/// is a translator. Anything else needs the general path.
+fn combine_kinds(left: Option<VersorKind>, right: Option<VersorKind>)
+        -> Option<VersorKind> {
+    match (left, right) {
+        (Some(VersorKind::Rotor), Some(VersorKind::Rotor)) =>
+            Some(VersorKind::Rotor),
+        (Some(VersorKind::Translator), Some(VersorKind::Translator)) =>
+            Some(VersorKind::Translator),
+        _ => None
+    }
+}
+
 /// A multivector in 3D CGA has 32 components. However, I noticed that for
 /// spatial transformations, you only ever use all odd or all even components
 /// So I only store a half at a time by parity.
@@ -256,12 +327,15 @@ fn find_end(array: &[f64]) -> usize {
 /// transformations (often a scalar + bivector) while reflections and most
 /// other anti-conformal (preserves angles but not orientation) are even
 /// (often just a vector)
-#[derive(Clone, PartialEq)]
+#[derive(Clone)]
 pub struct HalfMultivector {
     components: [f64; 16],
     parity: Parity,
     start_index: usize,
-    end_index: usize
+    end_index: usize,
+    /// If this is known to be a rotor or translator, sandwich products
+    /// with points can skip the general geometric product
+    kind: Option<VersorKind>,
 }
 
 impl HalfMultivector {
@@ -274,7 +348,8 @@ impl HalfMultivector {
             components,
             parity: Parity::Even,
             start_index,
-            end_index
+            end_index,
+            kind: None
         }
     }
 
@@ -288,8 +363,33 @@ impl HalfMultivector {
             components,
             parity: Parity::Odd,
             start_index,
-            end_index
+            end_index,
+            kind: None
+        }
+    }
+
+    /// Create a multivector from all 16 components, in the order listed at
+    /// the top of this file for the given parity. Unlike even() and odd(),
+    /// the start and end indices are computed from the components.
+    ///
+    /// This is the lowest-level way to make a transformation, so it checks
+    /// that the components are finite and form a versor (see
+    /// versor_norm()).
+    pub fn from_components(components: [f64; 16], parity: Parity)
+            -> std::result::Result<Self, String> {
+        if components.iter().any(|x| !x.is_finite()) {
+            return Err("components must be finite".to_string());
         }
+
+        let result = Self {
+            components,
+            parity,
+            start_index: find_start(&components),
+            end_index: find_end(&components),
+            kind: None
+        };
+        result.versor_norm()?;
+        Ok(result)
     }
 
     /// The do-nothing operation
@@ -300,7 +400,8 @@ impl HalfMultivector {
             components,
             parity: Parity::Even,
             start_index: SCALAR_START,
-            end_index: SCALAR_END
+            end_index: SCALAR_END,
+            kind: Some(VersorKind::Rotor)
         }
     }
 
@@ -343,7 +444,8 @@ impl HalfMultivector {
             components,
             parity: Parity::Even,
             start_index: SCALAR_START,
-            end_index: YZ + 1
+            end_index: YZ + 1,
+            kind: Some(VersorKind::Rotor)
         }
     }
 
@@ -379,12 +481,21 @@ impl HalfMultivector {
             components,
             parity: Parity::Even,
             start_index: SCALAR_START,
-            end_index: ZP + 1
+            end_index: ZP + 1,
+            kind: None
         }
     }
 
-    /// Uniform scaling
+    /// Uniform scaling. The scale factor must be positive, since the versor
+    /// is built from ln(scale_factor). Zero or negative factors would make
+    /// every later product inf or NaN.
     pub fn scale(scale_factor: f64) -> Self {
+        if scale_factor.is_nan() || scale_factor <= 0.0 {
+            panic!(
+                "scale: scale factor must be positive, got {}. A CGA \
+                dilation can't flip signs, use reflect for that",
+                scale_factor);
+        }
         let half_log_scale = scale_factor.ln() / 2.0;
         let c = half_log_scale.cosh();
         let s = half_log_scale.sinh();
@@ -397,6 +508,7 @@ impl HalfMultivector {
             parity: Parity::Even,
             start_index: SCALAR,
             end_index: BIVECTOR_END,
+            kind: None
         }
     }
 
@@ -421,7 +533,8 @@ impl HalfMultivector {
             components,
             parity: Parity::Even,
             start_index: SCALAR_START,
-            end_index: ZN + 1
+            end_index: ZN + 1,
+            kind: Some(VersorKind::Translator)
         }
     }
 
@@ -435,10 +548,60 @@ impl HalfMultivector {
             components,
             parity: Parity::Odd,
             start_index: VECTOR_START,
-            end_index: Z + 1
+            end_index: Z + 1,
+            kind: None
         }
     }
 
+    /// Reflection in the plane n.x = offset where n = (nx, ny, nz) is a
+    /// unit normal. This moves the plane to the origin, reflects, and moves
+    /// it back.
+    pub fn plane_reflection(nx: f64, ny: f64, nz: f64, offset: f64) -> Self {
+        let to_plane = Self::translation(offset * nx, offset * ny, offset * nz);
+        let from_plane =
+            Self::translation(-offset * nx, -offset * ny, -offset * nz);
+        to_plane
+            .geometric_product(&Self::reflection(nx, ny, nz))
+            .geometric_product(&from_plane)
+    }
+
+    /// Rotoreflection (improper rotation): rotate counterclockwise around
+    /// the axis (nx, ny, nz) by the given angle in radians, then reflect in
+    /// the plane perpendicular to the axis. The rotation and reflection
+    /// commute, so the order does not matter.
+    ///
+    /// This is an odd versor, so like reflection it is anti-conformal.
+    pub fn rotoreflection(nx: f64, ny: f64, nz: f64, angle_rad: f64) -> Self {
+        // rotation() will panic for the null vector, so normalizing here
+        // is safe.
+        let rotation = Self::rotation(nx, ny, nz, angle_rad);
+        let magnitude = (nx * nx + ny * ny + nz * nz).sqrt();
+        let reflection = Self::reflection(
+            nx / magnitude, ny / magnitude, nz / magnitude);
+        reflection.geometric_product(&rotation)
+    }
+
+    /// Spiral inversion: invert in the unit sphere, then rotate around the
+    /// axis (nx, ny, nz) by the given angle in radians, then scale by k:
+    ///
+    /// x -> k R(x) / |x|^2
+    ///
+    /// The inversion commutes with the rotation but not with the scale.
+    /// This is an odd versor, so it is anti-conformal. Applying it twice
+    /// cancels both the inversion and the scale, leaving a rotation by
+    /// twice the angle. The inverse is the spiral inversion with the
+    /// negated angle and the same scale.
+    pub fn spiral_inversion(
+            nx: f64,
+            ny: f64,
+            nz: f64,
+            angle_rad: f64,
+            scale_factor: f64) -> Self {
+        let dilation = Self::scale(scale_factor)
+            .geometric_product(&Self::rotation(nx, ny, nz, angle_rad));
+        dilation.geometric_product(&Self::inversion())
+    }
+
     /// Sphere inversion maps points to points with a reciprocal distance from
     /// the origin. This swaps 0 and infinity. 
     /// This is an anti-conformal operation (preserves angles but not 
@@ -450,10 +613,44 @@ impl HalfMultivector {
             components,
             parity: Parity::Odd,
             start_index: P,
-            end_index: P + 1
+            end_index: P + 1,
+            kind: None
         }
     }
 
+    /// Inversion in the sphere with center c = (cx, cy, cz) and radius r.
+    /// This moves the sphere to the unit sphere, inverts, and moves it back:
+    ///
+    /// x -> c + r^2 (x - c) / |x - c|^2
+    ///
+    /// Points on the sphere are fixed, and like inversion() this is its
+    /// own inverse.
+    pub fn sphere_inversion(cx: f64, cy: f64, cz: f64, r: f64) -> Self {
+        let to_sphere = Self::translation(cx, cy, cz)
+            .geometric_product(&Self::scale(r));
+        let from_sphere = Self::scale(1.0 / r)
+            .geometric_product(&Self::translation(-cx, -cy, -cz));
+        to_sphere
+            .geometric_product(&Self::inversion())
+            .geometric_product(&from_sphere)
+    }
+
+    /// Special conformal transformation (also called a transversion) with
+    /// vector parameter b = (bx, by, bz). This is an inversion, followed by
+    /// a translation by -b, followed by another inversion:
+    ///
+    /// x -> (x - x^2 b) / (1 - 2 b.x + b^2 x^2)
+    ///
+    /// The origin is fixed, but straight lines are bent into circles.
+    /// The inverse is the special conformal transformation with -b.
+    pub fn special_conformal(bx: f64, by: f64, bz: f64) -> Self {
+        let inversion = Self::inversion();
+        let translation = Self::translation(-bx, -by, -bz);
+        inversion
+            .geometric_product(&translation)
+            .geometric_product(&inversion)
+    }
+
     /// convert a point v = (x, y, z) into its higher-dimensional
     /// representation P = v + 1/2 v^2 inf + origin. I've seen this notated
     /// as up()
@@ -476,10 +673,15 @@ impl HalfMultivector {
             components,
             parity: Parity::Odd,
             start_index: find_start(&components),
-            end_index: find_end(&components)
+            end_index: find_end(&components),
+            kind: None
         }
     }
 
+    pub fn parity(&self) -> Parity {
+        self.parity.clone()
+    }
+
     /// Reverse the order of blades. in 3D CGA, this negates any bivector
     /// and trivector components while leaving everything else the same
     /// 
@@ -499,7 +701,49 @@ impl HalfMultivector {
             components,
             parity: self.parity.clone(),
             start_index: self.start_index,
-            end_index: self.end_index
+            end_index: self.end_index,
+            kind: self.kind
+        }
+    }
+
+    /// Compute V ~V, which is a nonzero scalar for any versor V. It is 1
+    /// for rotors and translators, but scales and inversions have other
+    /// norms. Returns an error if this is not a versor.
+    pub fn versor_norm(&self) -> std::result::Result<f64, String> {
+        let norm_product = self.geometric_product(&self.reverse());
+        let norm = norm_product.components[SCALAR];
+        if norm.abs() < VERSOR_NORM_TOLERANCE {
+            return Err("versor has zero norm".to_string());
+        }
+        let mut scalar_part = [0.0; 16];
+        scalar_part[SCALAR] = norm;
+        let scalar = Self::even(scalar_part, SCALAR, SCALAR + 1);
+        if !norm_product.approx_eq(&scalar, VERSOR_NORM_TOLERANCE * norm.abs()) {
+            return Err("not a versor, V~V is not a scalar".to_string());
+        }
+        Ok(norm)
+    }
+
+    /// The inverse versor V^-1 = ~V / (V ~V). For rotors and translators
+    /// V ~V = 1 so this is just the reverse, but versors built from scales
+    /// or inversions (e.g. sphere inversions, special conformal
+    /// transformations) have other norms, and the norm can even be negative.
+    ///
+    /// This panics if V ~V is not a nonzero scalar, i.e. this is not a
+    /// versor.
+    pub fn inverse(&self) -> Self {
+        let norm = self.versor_norm().unwrap_or_else(
+            |err| panic!("inverse: {}", err));
+
+        let reverse = self.reverse();
+        let mut components = reverse.components;
+        for component in components.iter_mut() {
+            *component /= norm;
+        }
+
+        Self {
+            components,
+            ..reverse
         }
     }
 
@@ -511,6 +755,13 @@ impl HalfMultivector {
         let component_table = get_component_table(&self.parity, &other.parity);
         let parity = get_product_parity(&self.parity, &other.parity);
         let mut result = [0.0; 16];
+        // Every pair of components in range is one multiply-add
+        #[cfg(feature = "op_counts")]
+        PRODUCT_OPS.with(|ops| {
+            let pairs = self.end_index.saturating_sub(self.start_index) *
+                other.end_index.saturating_sub(other.start_index);
+            ops.set(ops.get() + pairs as u64);
+        });
         for i in self.start_index..self.end_index {
             let a = self.components[i];
             for j in other.start_index..other.end_index {
@@ -533,15 +784,316 @@ impl HalfMultivector {
             components: result,
             parity,
             start_index: start,
-            end_index: end
+            end_index: end,
+            kind: combine_kinds(self.kind, other.kind)
+        }
+    }
+
+    /// The unit pseudoscalar I = xyzpn. I^2 = -1, so I^-1 = -I
+    #[allow(dead_code)]
+    pub fn pseudoscalar() -> Self {
+        let mut components = [0.0; 16];
+        components[XYZPN] = 1.0;
+        Self {
+            components,
+            parity: Parity::Odd,
+            start_index: PSEUDOSCALAR_START,
+            end_index: PSEUDOSCALAR_END,
+            kind: None
+        }
+    }
+
+    /// The outer (wedge) product a ∧ b. This is the part of the geometric
+    /// product where the grades add, i.e. the pairs of blades that share
+    /// no basis vectors. For blades, this spans the subspaces of both, and
+    /// it is 0 if they have a direction in common, e.g. P ∧ Q ∧ inf is the
+    /// line through the points P and Q (see join()).
+    #[allow(dead_code)]
+    pub fn outer_product(&self, other: &Self) -> Self {
+        let sign_table = get_sign_table(&self.parity, &other.parity);
+        let component_table = get_component_table(&self.parity, &other.parity);
+        let parity = get_product_parity(&self.parity, &other.parity);
+        let left_grades = get_grades(&self.parity);
+        let right_grades = get_grades(&other.parity);
+        let result_grades = get_grades(&parity);
+        let mut result = [0.0; 16];
+        for i in self.start_index..self.end_index {
+            for j in other.start_index..other.end_index {
+                let index = component_table[i][j];
+                if result_grades[index] != left_grades[i] + right_grades[j] {
+                    continue;
+                }
+                let sign = sign_table[i][j] as f64;
+                let product = self.components[i] * other.components[j];
+                result[index] += sign * product;
+            }
+        }
+
+        Self {
+            components: result,
+            parity,
+            start_index: find_start(&result),
+            end_index: find_end(&result),
+            kind: None
+        }
+    }
+
+    /// The dual A* = A I^-1, which swaps the two ways of representing a
+    /// shape in CGA: as the blade of points it spans (e.g. the plane
+    /// P ∧ Q ∧ R ∧ inf) or as the vector or blade its points are
+    /// orthogonal to (e.g. a plane n + d inf). The grade goes from k to
+    /// 5 - k, so the parity flips.
+    #[allow(dead_code)]
+    pub fn dual(&self) -> Self {
+        self.geometric_product(&Self::pseudoscalar().inverse())
+    }
+
+    /// The inverse of dual(), A I
+    #[allow(dead_code)]
+    pub fn undual(&self) -> Self {
+        self.geometric_product(&Self::pseudoscalar())
+    }
+
+    /// The meet a ∨ b = (a* ∧ b*) I, the intersection of two shapes given
+    /// as the blades they span. E.g. the meet of two planes is the line
+    /// where they cross, and the meet of a line and a plane is a flat
+    /// point. Parallel planes still meet, in a line at infinity. As usual
+    /// in CGA, the result is only defined up to a scale factor.
+    #[allow(dead_code)]
+    pub fn meet(&self, other: &Self) -> Self {
+        self.dual().outer_product(&other.dual()).undual()
+    }
+
+    /// The join a ∧ b, the smallest shape containing both as blades. This
+    /// is just the outer product, so it is only the join when the two
+    /// shapes have nothing in common, e.g. two distinct points. Otherwise
+    /// the result is 0.
+    #[allow(dead_code)]
+    pub fn join(&self, other: &Self) -> Self {
+        self.outer_product(other)
+    }
+
+    /// Check if this is a rotor, i.e. an even multivector with only scalar
+    /// and Euclidean bivector (xy, xz, yz) components.
+    fn is_rotor(&self) -> bool {
+        if self.parity != Parity::Even {
+            return false;
+        }
+
+        (self.start_index..self.end_index)
+            .filter(|i| ![SCALAR, XY, XZ, YZ].contains(i))
+            .all(|i| self.components[i].abs() < ROTOR_EPSILON)
+    }
+
+    /// Logarithm of a rotor R = cos(a) + sin(a)B where B is a unit
+    /// Euclidean bivector. The result is the bivector aB, so
+    /// exp(log(R)) = R.
+    ///
+    /// Only rotors are supported for now. This panics for odd versors and
+    /// for motors with p or n components (translations, scaling, etc.)
+    pub fn log(&self) -> Self {
+        if !self.is_rotor() {
+            panic!("log: only rotors (scalar + xy, xz, yz) are supported");
+        }
+
+        let xy = self.components[XY];
+        let xz = self.components[XZ];
+        let yz = self.components[YZ];
+        let bivector_len = (xy * xy + xz * xz + yz * yz).sqrt();
+
+        // A pure scalar rotor is the identity, so the log is 0.
+        let mut components = [0.0; 16];
+        if bivector_len > 0.0 {
+            let angle = bivector_len.atan2(self.components[SCALAR]);
+            let factor = angle / bivector_len;
+            components[XY] = factor * xy;
+            components[XZ] = factor * xz;
+            components[YZ] = factor * yz;
+        }
+
+        Self {
+            components,
+            parity: Parity::Even,
+            start_index: BIVECTOR_START,
+            end_index: YZ + 1,
+            kind: None
+        }
+    }
+
+    /// Exponential of a Euclidean bivector aB (where B is a unit bivector),
+    /// producing the rotor cos(a) + sin(a)B. This is the inverse of log().
+    ///
+    /// Like log(), this panics for anything other than a Euclidean bivector.
+    pub fn exp(&self) -> Self {
+        if !self.is_rotor() || self.components[SCALAR].abs() >= ROTOR_EPSILON {
+            panic!("exp: only Euclidean bivectors (xy, xz, yz) are supported");
+        }
+
+        let xy = self.components[XY];
+        let xz = self.components[XZ];
+        let yz = self.components[YZ];
+        let angle = (xy * xy + xz * xz + yz * yz).sqrt();
+
+        // sin(a)/a -> 1 as a -> 0
+        let factor = if angle > 0.0 {
+            angle.sin() / angle
+        } else {
+            1.0
+        };
+
+        let mut components = [0.0; 16];
+        components[SCALAR] = angle.cos();
+        components[XY] = factor * xy;
+        components[XZ] = factor * xz;
+        components[YZ] = factor * yz;
+        Self {
+            components,
+            parity: Parity::Even,
+            start_index: SCALAR_START,
+            end_index: YZ + 1,
+            kind: Some(VersorKind::Rotor)
+        }
+    }
+
+    /// Multiply every component by a scalar
+    fn scale_components(&self, scalar: f64) -> Self {
+        let mut result = self.clone();
+        for component in result.components.iter_mut() {
+            *component *= scalar;
+        }
+        result
+    }
+
+    /// Interpolate between two rotors a and b. t = 0 gives a, t = 1 gives b
+    /// and values in between rotate at a constant rate along the shortest
+    /// path. This is useful for animating an IFS.
+    ///
+    /// Only rotors are supported for now, see log()
+    pub fn slerp_motor(a: &Self, b: &Self, t: f64) -> Self {
+        // The ratio b~a is the rotation that takes a to b
+        let mut ratio = b.geometric_product(&a.reverse());
+
+        // R and -R are the same rotation, but -R goes the long way around
+        if ratio.components[SCALAR] < 0.0 {
+            ratio = ratio.scale_components(-1.0);
         }
+
+        let step = ratio.log().scale_components(t);
+        step.exp().geometric_product(a)
     }
 
     /// Multiply self * other * ~self. This is how transformations are
     /// always applied in 3D CGA
+    ///
+    /// Rotors and translators applied to vectors are very common and only
+    /// touch a few components, so they have a fast path that skips the
+    /// general geometric product.
     pub fn sandwich_product(&self, other: &Self) -> Self {
+        if other.is_vector() {
+            match self.kind {
+                Some(VersorKind::Rotor) => return self.rotor_sandwich(other),
+                Some(VersorKind::Translator) => 
+                    return self.translator_sandwich(other),
+                None => {}
+            }
+        }
+
+        self.general_sandwich_product(other)
+    }
+
+    /// sandwich_product() without any fast paths
+    fn general_sandwich_product(&self, other: &Self) -> Self {
         let reverse = self.reverse();
-        self.geometric_product(&other).geometric_product(&reverse)
+        self.geometric_product(other).geometric_product(&reverse)
+    }
+
+    /// Check if this is an odd multivector with only vector components
+    /// (x, y, z, p, n) in range
+    fn is_vector(&self) -> bool {
+        self.parity == Parity::Odd
+            && self.start_index >= VECTOR_START
+            && self.end_index <= VECTOR_END
+    }
+
+    /// Make a vector from its 5 components
+    fn vector(x: f64, y: f64, z: f64, p: f64, n: f64) -> Self {
+        let mut components = [0.0; 16];
+        components[X] = x;
+        components[Y] = y;
+        components[Z] = z;
+        components[P] = p;
+        components[N] = n;
+        Self {
+            components,
+            parity: Parity::Odd,
+            start_index: find_start(&components),
+            end_index: find_end(&components),
+            kind: None
+        }
+    }
+
+    /// R v ~R for a rotor R = s + a xy + b xz + c yz. This rotates the
+    /// Euclidean part of the vector. R ~R = s^2 + a^2 + b^2 + c^2 scales
+    /// every component, which is 1 for unit rotors.
+    fn rotor_sandwich(&self, vector: &Self) -> Self {
+        let s = self.components[SCALAR];
+        let a = self.components[XY];
+        let b = self.components[XZ];
+        let c = self.components[YZ];
+        let x = vector.components[X];
+        let y = vector.components[Y];
+        let z = vector.components[Z];
+
+        let (ss, aa, bb, cc) = (s * s, a * a, b * b, c * c);
+        let norm_sqr = ss + aa + bb + cc;
+        let rx = 
+            (ss - aa - bb + cc) * x + 
+            2.0 * (a * s - b * c) * y + 
+            2.0 * (a * c + b * s) * z;
+        let ry = 
+            -2.0 * (a * s + b * c) * x + 
+            (ss - aa + bb - cc) * y + 
+            2.0 * (c * s - a * b) * z;
+        let rz =
+            2.0 * (a * c - b * s) * x - 
+            2.0 * (a * b + c * s) * y + 
+            (ss + aa - bb - cc) * z;
+
+        Self::vector(
+            rx,
+            ry,
+            rz,
+            norm_sqr * vector.components[P],
+            norm_sqr * vector.components[N])
+    }
+
+    /// T v ~T for a translator T = k(1 - 1/2 t inf). For a point with
+    /// weight w = n - p, the Euclidean part moves by w t and both p and n
+    /// change by the same amount. The whole result is scaled by k^2.
+    fn translator_sandwich(&self, vector: &Self) -> Self {
+        let k = self.components[SCALAR];
+        // See translation() for the signs
+        let tx = -2.0 * self.components[XP] / k;
+        let ty = -2.0 * self.components[YP] / k;
+        let tz = -2.0 * self.components[ZP] / k;
+
+        let x = vector.components[X];
+        let y = vector.components[Y];
+        let z = vector.components[Z];
+        let p = vector.components[P];
+        let n = vector.components[N];
+        let weight = n - p;
+        let shift = 
+            (x * tx + y * ty + z * tz) + 
+            0.5 * weight * (tx * tx + ty * ty + tz * tz);
+
+        let scale = k * k;
+        Self::vector(
+            scale * (x + weight * tx),
+            scale * (y + weight * ty),
+            scale * (z + weight * tz),
+            scale * (p + shift),
+            scale * (n + shift))
     }
 
     /// Sometimes near-zero components appear when performing geometric product.
@@ -595,9 +1147,88 @@ impl HalfMultivector {
         )
     }
 
+    /// Convert a point to Euclidean coordinates. In debug builds, this
+    /// checks that the result is finite, since a NaN or infinite position
+    /// would silently spread to everything computed from it. Use
+    /// try_to_vec3() for points that might have escaped to infinity.
     pub fn to_vec3(&self) -> Vec3 {
+        let (x, y, z) = self.coordinates();
+        let result = Vec3::new(x as f32, y as f32, z as f32);
+        debug_assert!(
+            result.is_finite(),
+            "to_vec3: the point ({}, {}, {}) is not finite, did it escape to \
+            infinity?",
+            x, y, z);
+        result
+    }
+
+    /// Like to_vec3(), but returns None if the point is null or so close
+    /// to null that it is more than ESCAPE_RADIUS from the origin. This
+    /// happens when a non-contractive IFS sends a point to infinity.
+    pub fn try_to_vec3(&self) -> Option<Vec3> {
         if self.parity != Parity::Odd {
-            panic!("to_vec3: Vectors must have odd parity!");
+            panic!("try_to_vec3: Vectors must have odd parity!");
+        }
+
+        let scale_factor = self.components[N] - self.components[P];
+        if scale_factor == 0.0 {
+            return None;
+        }
+
+        let x = self.components[X] / scale_factor;
+        let y = self.components[Y] / scale_factor;
+        let z = self.components[Z] / scale_factor;
+        let distance_sqr = x * x + y * y + z * z;
+        if !distance_sqr.is_finite() || distance_sqr > ESCAPE_RADIUS_SQR {
+            return None;
+        }
+
+        Some(Vec3::new(x as f32, y as f32, z as f32))
+    }
+
+    /// For a point that escaped to infinity (see try_to_vec3()), get the
+    /// unit direction it escaped in. A point at infinity with no direction
+    /// (the null vector for infinity itself) is given the +z direction.
+    pub fn escape_direction(&self) -> Vec3 {
+        if self.parity != Parity::Odd {
+            panic!("escape_direction: Vectors must have odd parity!");
+        }
+
+        // Dividing by the scale factor flips the direction if it is
+        // negative, but only the sign matters here. Far from the origin, the
+        // scale factor n - p often cancels out to 0, but n + p is the scale
+        // factor times the length squared, so it has the same sign.
+        let p = self.components[P];
+        let n = self.components[N];
+        let signed = if n + p != 0.0 { n + p } else { n - p };
+        let sign = if signed < 0.0 { -1.0 } else { 1.0 };
+        let x = sign * self.components[X];
+        let y = sign * self.components[Y];
+        let z = sign * self.components[Z];
+        let length = (x * x + y * y + z * z).sqrt();
+        if length == 0.0 || !length.is_finite() {
+            return Vec3::new(0.0, 0.0, 1.0);
+        }
+
+        Vec3::new((x / length) as f32, (y / length) as f32, (z / length) as f32)
+    }
+
+    /// Get the p and n components of a point without homogenizing.
+    /// n - p is the conformal weight that homogenize() divides out, so
+    /// the raw x, y and z components are the coordinates times n - p.
+    pub fn conformal_components(&self) -> [f32; 2] {
+        if self.parity != Parity::Odd {
+            panic!("conformal_components: Vectors must have odd parity!");
+        }
+
+        [self.components[P] as f32, self.components[N] as f32]
+    }
+
+    /// Get the Euclidean (x, y, z) coordinates of a point in double
+    /// precision
+    pub fn coordinates(&self) -> (f64, f64, f64) {
+        if self.parity != Parity::Odd {
+            panic!("coordinates: Vectors must have odd parity!");
         }
 
         // streamlined homogenize()
@@ -607,42 +1238,61 @@ impl HalfMultivector {
 
         if scale_factor == 0.0 {
             // a null vector typically means the point blew up to infinity
-            println!("warning - point went to infinity?");
+            info!("warning - point went to infinity?");
             Vec3::new(1e9, 1e9, 1e9);
         }
 
         let x = self.components[X] / scale_factor;
         let y = self.components[Y] / scale_factor;
         let z = self.components[Z] / scale_factor;
-        Vec3::new(x as f32, y as f32, z as f32)
+        (x, y, z)
     }
 
-    #[cfg(test)]
-    pub fn almost_equal(&self, other: &Self, epsilon: f64) -> bool {
+    /// Compare two multivectors with a tolerance. Every component must be
+    /// within epsilon. Use this rather than == for the results of
+    /// calculations, since == compares the floats exactly.
+    ///
+    /// The start and end index may not be exactly the same (e.g. if one
+    /// multivector is the result of a calculation and the other is
+    /// created from the point constructor). So this just takes the most
+    /// general range and makes sure the values match up.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
         if self.parity != other.parity {
-            println!("parity doesn't match!");
             return false;
         }
 
-        // start and end index may not be exactly the same (e.g. if one
-        // multivector is the result of a calculation and the other is
-        // created from the point constructor). So just take the most general
-        // range and make sure the values match up
         let start_index = self.start_index.min(other.start_index);
         let end_index = self.end_index.max(other.end_index);
+        (start_index..end_index).all(
+            |i| (self.components[i] - other.components[i]).abs() < epsilon)
+    }
 
-        for i in start_index..end_index {
-            if !((self.components[i] - other.components[i]).abs() < epsilon) {
-                println!(
-                    "components don't match! {:?}, {:?}",
-                    self.components,
-                    other.components
-                );
-                return false;
-            }
+    /// Same as approx_eq(), but prints the components when they
+    /// don't match. Used in unit tests
+    #[cfg(test)]
+    pub fn almost_equal(&self, other: &Self, epsilon: f64) -> bool {
+        let result = self.approx_eq(other, epsilon);
+        if !result {
+            println!("multivectors don't match! {:?}, {:?}", self, other);
         }
+        result
+    }
+}
 
-        true
+/// The kind is only a hint for sandwich_product(), so it is not part of
+/// equality.
+///
+/// This compares the floats exactly, so it is only reliable for values
+/// built directly from constructors. After any arithmetic, round-off
+/// (and the start/end index, which depends on which components are
+/// nonzero) can make equal multivectors compare unequal, so use
+/// approx_eq() instead.
+impl PartialEq for HalfMultivector {
+    fn eq(&self, other: &Self) -> bool {
+        self.components == other.components
+            && self.parity == other.parity
+            && self.start_index == other.start_index
+            && self.end_index == other.end_index
     }
 }
 
@@ -913,6 +1563,102 @@ mod tests {
         assert_eq!(original.reverse(), expected);
     }
 
+    #[test]
+    fn test_approx_eq() {
+        // The rotation and its round trip differ by round-off, so they
+        // are only approximately equal
+        let rotation = HalfMultivector::rotation(1.0, 2.0, 3.0, 1.0);
+        let round_trip = rotation
+            .geometric_product(&HalfMultivector::translation(1.0, 0.0, 0.0))
+            .geometric_product(&HalfMultivector::translation(-1.0, 0.0, 0.0));
+        assert!(round_trip.approx_eq(&rotation, 1e-9));
+        assert!(!round_trip.approx_eq(&rotation.reverse(), 1e-9));
+
+        // different parity is never equal
+        let point = HalfMultivector::point(0.0, 0.0, 0.0);
+        assert!(!point.approx_eq(&HalfMultivector::identity(), 1.0));
+    }
+
+    #[test]
+    fn test_inverse() {
+        // Scaling makes V~V != 1, so the reverse alone is not the inverse
+        let versors = [
+            HalfMultivector::rotation(1.0, 2.0, 3.0, 1.0),
+            HalfMultivector::translation(1.0, -2.0, 0.5),
+            HalfMultivector::scale(3.0),
+            HalfMultivector::sphere_inversion(1.0, 2.0, 3.0, 2.0),
+            HalfMultivector::special_conformal(0.5, -1.0, 2.0),
+        ];
+        let identity = HalfMultivector::identity();
+        for versor in versors.iter() {
+            let product = versor.geometric_product(&versor.inverse());
+            assert!(product.almost_equal(&identity, 1e-9));
+        }
+    }
+
+    #[test]
+    fn test_from_components() {
+        let rotation = HalfMultivector::rotation(0.0, 0.0, 1.0, 1.0);
+        let copy = HalfMultivector::from_components(
+            rotation.components, Parity::Even).unwrap();
+        assert!(copy.approx_eq(&rotation, 1e-12));
+        assert_eq!(copy.start_index, SCALAR);
+        assert_eq!(copy.end_index, XY + 1);
+
+        let mut components = [0.0; 16];
+        components[SCALAR] = 2.0;
+        components[YZPN] = 1.0;
+        let err = HalfMultivector::from_components(components, Parity::Even)
+            .unwrap_err();
+        assert!(err.contains("not a versor"), "{}", err);
+
+        let err = HalfMultivector::from_components([0.0; 16], Parity::Odd)
+            .unwrap_err();
+        assert!(err.contains("zero norm"), "{}", err);
+
+        components[SCALAR] = f64::NAN;
+        assert!(
+            HalfMultivector::from_components(components, Parity::Even).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "scale factor must be positive, got 0")]
+    fn test_scale_zero() {
+        HalfMultivector::scale(0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "scale factor must be positive, got -1")]
+    fn test_scale_negative() {
+        HalfMultivector::scale(-1.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "the point (NaN, NaN, NaN) is not finite")]
+    fn test_to_vec3_checks_finite() {
+        // Inverting the origin sends it to the point at infinity, which
+        // has no Euclidean coordinates
+        let origin = HalfMultivector::point(0.0, 0.0, 0.0);
+        let mut infinity =
+            HalfMultivector::inversion().sandwich_product(&origin);
+        infinity.expect_vector();
+        infinity.homogenize();
+        infinity.to_vec3();
+    }
+
+    #[test]
+    #[should_panic(expected = "not a versor")]
+    fn test_inverse_not_a_versor() {
+        // Quadvectors are unchanged by the reverse, so for 2 + yzpn,
+        // V~V = (2 + yzpn)^2 = 3 + 4 yzpn has a quadvector part
+        let mut components = [0.0; 16];
+        components[SCALAR] = 2.0;
+        components[YZPN] = 1.0;
+        let multivector = HalfMultivector::even(components, SCALAR, YZPN + 1);
+        multivector.inverse();
+    }
+
     #[test]
     fn test_identity_xform() {
         let xform = HalfMultivector::identity();
@@ -939,6 +1685,20 @@ mod tests {
         assert!(result.almost_equal(&expected, 1e-9));
     }
 
+    #[test]
+    fn test_conformal_components() {
+        let xform = HalfMultivector::scale(2.0);
+        let point = HalfMultivector::point(1.0, 1.0, 1.0);
+        let result = xform.sandwich_product(&point);
+
+        // Homogenizing divides the raw components by n - p
+        let [p, n] = result.conformal_components();
+        let weight = (n - p) as f64;
+        assert!((weight - 1.0).abs() > 1e-3);
+        let (x, _, _) = result.coordinates();
+        assert!((x * weight - result.components[X]).abs() < 1e-6);
+    }
+
     #[test]
     fn test_rotation_xform() {
         let xform = HalfMultivector::rotation(0.0, 0.0, 1.0, 0.5 * PI);
@@ -989,4 +1749,324 @@ mod tests {
         assert!(rot_z.almost_equal(&x, 1e-9));
         assert!(rot_zero.almost_equal(&zero, 1e-9));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_special_conformal_fixes_origin() {
+        let xform = HalfMultivector::special_conformal(0.0, 1.0, 0.0);
+        let origin = HalfMultivector::point(0.0, 0.0, 0.0);
+        let mut result = xform.sandwich_product(&origin);
+        result.homogenize();
+        assert!(result.almost_equal(&origin, 1e-9));
+    }
+
+    #[test]
+    fn test_special_conformal_bends_x_axis() {
+        // b = (0, 1, 0), x = (1, 0, 0)
+        // x' = (x - x^2 b) / (1 - 2 b.x + b^2 x^2)
+        //    = (1, -1, 0) / 2
+        let xform = HalfMultivector::special_conformal(0.0, 1.0, 0.0);
+        let point = HalfMultivector::point(1.0, 0.0, 0.0);
+        let expected = HalfMultivector::point(0.5, -0.5, 0.0);
+        let mut result = xform.sandwich_product(&point);
+        result.homogenize();
+        assert!(result.almost_equal(&expected, 1e-9));
+    }
+
+    #[test]
+    fn test_special_conformal_inverse() {
+        let xform = HalfMultivector::special_conformal(0.5, -1.0, 2.0);
+        let inverse = HalfMultivector::special_conformal(-0.5, 1.0, -2.0);
+        let point = HalfMultivector::point(1.0, 2.0, 3.0);
+        let mut result = 
+            inverse.sandwich_product(&xform.sandwich_product(&point));
+        result.homogenize();
+        assert!(result.almost_equal(&point, 1e-9));
+
+        // The reverse of the versor is the same transformation
+        let mut from_reverse = xform.reverse().sandwich_product(
+            &xform.sandwich_product(&point));
+        from_reverse.homogenize();
+        assert!(from_reverse.almost_equal(&point, 1e-9));
+    }
+
+    #[test]
+    fn test_rotoreflection_zero_angle_is_reflection() {
+        let xform = HalfMultivector::rotoreflection(0.0, 0.0, 1.0, 0.0);
+        let expected = HalfMultivector::reflection(0.0, 0.0, 1.0);
+        assert!(xform.almost_equal(&expected, 1e-9));
+    }
+
+    #[test]
+    fn test_rotoreflection_xform() {
+        // 90 degree rotation about z, then reflect in the xy-plane
+        let xform = HalfMultivector::rotoreflection(0.0, 0.0, 2.0, 0.5 * PI);
+        let point = HalfMultivector::point(1.0, 0.0, 1.0);
+        let expected = HalfMultivector::point(0.0, 1.0, -1.0);
+        let mut result = xform.sandwich_product(&point);
+        result.expect_vector();
+        result.homogenize();
+        assert!(result.almost_equal(&expected, 1e-9));
+    }
+
+    #[test]
+    fn test_spiral_inversion_twice_is_rotation() {
+        let xform = HalfMultivector::spiral_inversion(0.0, 0.0, 1.0, 0.3, 2.0);
+        let twice = xform.geometric_product(&xform);
+        let point = HalfMultivector::point(0.5, -1.0, 2.0);
+        let mut result = twice.sandwich_product(&point);
+        result.expect_vector();
+        result.homogenize();
+
+        let rotation = HalfMultivector::rotation(0.0, 0.0, 1.0, 0.6);
+        let mut expected = rotation.sandwich_product(&point);
+        expected.expect_vector();
+        expected.homogenize();
+        assert!(result.almost_equal(&expected, 1e-9));
+    }
+
+    #[test]
+    fn test_spiral_inversion_xform() {
+        // (2, 0, 0) inverts to (0.5, 0, 0), rotates to (0, 0.5, 0), then
+        // triples to (0, 1.5, 0)
+        let xform =
+            HalfMultivector::spiral_inversion(0.0, 0.0, 1.0, 0.5 * PI, 3.0);
+        let point = HalfMultivector::point(2.0, 0.0, 0.0);
+        let mut result = xform.sandwich_product(&point);
+        result.expect_vector();
+        result.homogenize();
+        let expected = HalfMultivector::point(0.0, 1.5, 0.0);
+        assert!(result.almost_equal(&expected, 1e-9));
+    }
+
+    #[test]
+    fn test_slerp_rotor_halfway() {
+        let identity = HalfMultivector::identity();
+        let rotor90 = HalfMultivector::rotation(0.0, 0.0, 1.0, 0.5 * PI);
+        let rotor45 = HalfMultivector::rotation(0.0, 0.0, 1.0, 0.25 * PI);
+
+        let result = HalfMultivector::slerp_motor(&identity, &rotor90, 0.5);
+        assert!(result.almost_equal(&rotor45, 1e-9));
+    }
+
+    #[test]
+    fn test_slerp_rotor_endpoints() {
+        let a = HalfMultivector::rotation(1.0, 0.0, 0.0, 0.3);
+        let b = HalfMultivector::rotation(1.0, 2.0, 3.0, 1.2);
+        let start = HalfMultivector::slerp_motor(&a, &b, 0.0);
+        let end = HalfMultivector::slerp_motor(&a, &b, 1.0);
+        assert!(start.almost_equal(&a, 1e-9));
+        assert!(end.almost_equal(&b, 1e-9));
+    }
+
+    #[test]
+    fn test_exp_log_rotor() {
+        let rotor = HalfMultivector::rotation(1.0, -1.0, 2.0, 2.0);
+        let result = rotor.log().exp();
+        assert!(result.almost_equal(&rotor, 1e-9));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_log_translation_unsupported() {
+        HalfMultivector::translation(1.0, 0.0, 0.0).log();
+    }
+
+    fn test_points() -> Vec<HalfMultivector> {
+        vec![
+            HalfMultivector::point(1.0, 2.0, 3.0),
+            HalfMultivector::point(0.0, -0.5, 0.25),
+            HalfMultivector::point(0.0, 0.0, 0.0),
+            // Points don't have to be homogenized
+            HalfMultivector::point(-3.0, 1.0, 2.0).scale_components(2.5),
+        ]
+    }
+
+    /// Check that the fast path in sandwich_product() matches the general
+    /// geometric product
+    fn assert_fast_path_matches(versor: &HalfMultivector) {
+        assert!(versor.kind.is_some());
+        for point in test_points() {
+            let fast = versor.sandwich_product(&point);
+            let general = versor.general_sandwich_product(&point);
+            assert!(fast.almost_equal(&general, 1e-9));
+        }
+    }
+
+    #[cfg(feature = "op_counts")]
+    #[test]
+    fn test_product_op_count() {
+        // Points have all 5 vector components, and a translator has the
+        // scalar and xp, xn, yp, yn, zp, zn, so 15 components from s to zn
+        let point = HalfMultivector::point(1.0, 2.0, 3.0);
+        let translator = HalfMultivector::translation(1.0, 2.0, 3.0);
+        let before = product_op_count().unwrap();
+        point.geometric_product(&point);
+        assert_eq!(product_op_count().unwrap() - before, 25);
+        translator.geometric_product(&point);
+        assert_eq!(product_op_count().unwrap() - before, 25 + 15 * 5);
+
+        // The translator fast path skips the product entirely
+        translator.sandwich_product(&point);
+        assert_eq!(product_op_count().unwrap() - before, 25 + 15 * 5);
+    }
+
+    #[test]
+    fn test_rotor_fast_path() {
+        assert_fast_path_matches(&HalfMultivector::identity());
+        assert_fast_path_matches(&HalfMultivector::rotation(1.0, 0.0, 0.0, 0.3));
+        assert_fast_path_matches(&HalfMultivector::rotation(0.0, 1.0, 0.0, 2.0));
+        assert_fast_path_matches(&HalfMultivector::rotation(1.0, -2.0, 3.0, 1.1));
+
+        // Products and reverses of rotors are rotors
+        let a = HalfMultivector::rotation(1.0, 1.0, 0.0, 0.7);
+        let b = HalfMultivector::rotation(0.0, 1.0, 1.0, -1.9);
+        assert_fast_path_matches(&a.geometric_product(&b));
+        assert_fast_path_matches(&a.reverse());
+        assert_fast_path_matches(&HalfMultivector::slerp_motor(&a, &b, 0.4));
+
+        // Non-unit rotors scale the whole vector
+        assert_fast_path_matches(&a.scale_components(-1.5));
+    }
+
+    #[test]
+    fn test_translator_fast_path() {
+        let a = HalfMultivector::translation(1.0, -2.0, 0.5);
+        let b = HalfMultivector::translation(0.0, 3.0, -1.0);
+        assert_fast_path_matches(&a);
+        assert_fast_path_matches(&a.reverse());
+        assert_fast_path_matches(&a.geometric_product(&b));
+        assert_fast_path_matches(&b.scale_components(2.0));
+    }
+
+    #[test]
+    fn test_mixed_versors_use_general_path() {
+        let rotor = HalfMultivector::rotation(0.0, 0.0, 1.0, 1.0);
+        let translator = HalfMultivector::translation(1.0, 0.0, 0.0);
+        assert_eq!(rotor.geometric_product(&translator).kind, None);
+        assert_eq!(HalfMultivector::scale(2.0).kind, None);
+        assert_eq!(
+            HalfMultivector::identity()
+                .geometric_product(&HalfMultivector::scale(2.0))
+                .kind,
+            None);
+    }
+
+    #[test]
+    fn test_try_to_vec3_finite_point() {
+        let position = HalfMultivector::point(1.0, -2.0, 3.0)
+            .scale_components(-4.0)
+            .try_to_vec3()
+            .expect("point should not escape");
+        assert!((position - Vec3::new(1.0, -2.0, 3.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_try_to_vec3_escaped_points() {
+        let far = HalfMultivector::point(0.0, -1e10, 0.0);
+        assert!(far.try_to_vec3().is_none());
+        let direction = far.escape_direction();
+        assert!((direction - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-6);
+
+        // Negating the whole vector is the same point, so the same
+        // direction
+        let flipped = far.scale_components(-1.0).escape_direction();
+        assert!((flipped - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-6);
+
+        // A null vector is exactly at infinity
+        let null = HalfMultivector::vector(3.0, 4.0, 0.0, 1.0, 1.0);
+        assert!(null.try_to_vec3().is_none());
+        let direction = null.escape_direction();
+        assert!((direction - Vec3::new(0.6, 0.8, 0.0)).length() < 1e-6);
+    }
+
+    /// The point at infinity, n + p
+    fn infinity() -> HalfMultivector {
+        let mut components = [0.0; 16];
+        components[P] = 1.0;
+        components[N] = 1.0;
+        HalfMultivector::odd(components, P, N + 1)
+    }
+
+    /// Check that a is a nonzero multiple of b
+    fn assert_proportional(a: &HalfMultivector, b: &HalfMultivector) {
+        let largest = (0..16)
+            .max_by(|i, j|
+                b.components[*i].abs().total_cmp(&b.components[*j].abs()))
+            .unwrap();
+        let ratio = a.components[largest] / b.components[largest];
+        assert!(ratio.abs() > 1e-9, "{:?} is zero", a);
+        for i in 0..16 {
+            assert!(
+                (a.components[i] - ratio * b.components[i]).abs() < 1e-9,
+                "{:?} is not a multiple of {:?}", a, b);
+        }
+    }
+
+    fn is_zero(a: &HalfMultivector) -> bool {
+        a.components.iter().all(|x| x.abs() < 1e-9)
+    }
+
+    #[test]
+    fn test_pseudoscalar_squares_to_minus_one() {
+        let i = HalfMultivector::pseudoscalar();
+        let mut minus_one = HalfMultivector::identity();
+        minus_one.components[SCALAR] = -1.0;
+        assert!(i.geometric_product(&i).almost_equal(&minus_one, 1e-12));
+    }
+
+    #[test]
+    fn test_outer_product() {
+        let x = HalfMultivector::reflection(1.0, 0.0, 0.0);
+        let y = HalfMultivector::reflection(0.0, 1.0, 0.0);
+        let xy = x.outer_product(&y);
+        let mut expected = [0.0; 16];
+        expected[XY] = 1.0;
+        let expected = HalfMultivector::even(expected, XY, XY + 1);
+        assert!(xy.almost_equal(&expected, 1e-12));
+        assert!(is_zero(&y.outer_product(&x).outer_product(&y)));
+
+        // Unlike the geometric product, the inner part is dropped
+        let diagonal = HalfMultivector::reflection(1.0, 1.0, 0.0);
+        assert!(is_zero(&x.outer_product(&diagonal).outer_product(&y)));
+        assert!(x.outer_product(&diagonal).almost_equal(&xy, 1e-12));
+        assert!(is_zero(&x.outer_product(&x)));
+    }
+
+    #[test]
+    fn test_dual_round_trip() {
+        let point = HalfMultivector::point(1.0, -2.0, 0.5);
+        let dual = point.dual();
+        assert_eq!(dual.parity(), Parity::Even);
+        assert!(dual.undual().almost_equal(&point, 1e-12));
+
+        // A point lies on the line it spans with another point
+        let q = HalfMultivector::point(0.0, 1.0, 0.0);
+        let line = point.join(&q).join(&infinity());
+        assert!(is_zero(&line.outer_product(&point)));
+    }
+
+    #[test]
+    fn test_meet_of_planes_is_line() {
+        let up = |x: f64, y: f64, z: f64| HalfMultivector::point(x, y, z);
+        let plane = |a: HalfMultivector, b, c| {
+            a.join(&b).join(&c).join(&infinity())
+        };
+
+        // The planes x = 1 and y = 2 cross in the vertical line through
+        // (1, 2, 0)
+        let plane_x =
+            plane(up(1.0, 0.0, 0.0), up(1.0, 1.0, 0.0), up(1.0, 0.0, 1.0));
+        let plane_y =
+            plane(up(0.0, 2.0, 0.0), up(1.0, 2.0, 0.0), up(0.0, 2.0, 1.0));
+        let line = plane_x.meet(&plane_y);
+        let expected = up(1.0, 2.0, 0.0)
+            .join(&up(1.0, 2.0, 1.0))
+            .join(&infinity());
+        assert_proportional(&line, &expected);
+
+        for z in [-3.0, 0.0, 0.5, 10.0].iter() {
+            assert!(is_zero(&line.outer_product(&up(1.0, 2.0, *z))));
+        }
+        assert!(!is_zero(&line.outer_product(&up(1.0, 0.0, 0.0))));
+    }
+}
@@ -1,12 +1,18 @@
 use json::JsonValue;
 
-use crate::xforms::{self, Xform};
+use crate::xforms::{self, Classification, Transform, Xform};
 use crate::choosers::{self, Chooser, UniformChooser};
 use crate::half_multivector::HalfMultivector;
+use crate::point::check_id_count;
+use crate::vector::Vec3;
 
 // Type aliases for brevity
 pub type XformSelector = Box<dyn Chooser>;
 
+/// Addresses are written to glTF as floats, which hold integers up to 2^24
+/// exactly
+const MAX_ADDRESS: u64 = 1 << 24;
+
 /// An Iterated Function System is a set of functions (transformations) that 
 /// can be applied over and over to the same input set in various combinations. 
 /// often these functions form a group or at least a semigroup under composition.
@@ -19,28 +25,59 @@ pub type XformSelector = Box<dyn Chooser>;
 ///
 pub struct IFS {
     /// A list of transformations to include
-    xforms: Vec<Xform>,
+    xforms: Vec<Box<dyn Transform>>,
     /// The chooser determines the method for selecting a transformation
     /// randomly. Often this is a uniform distribution, but it could also
     /// be a Markov chain or weighted probability distribution.
     chooser: XformSelector,
+    /// For each transformation, the index of its group. A transformation
+    /// and an inverse added via ["+inverse"] share the same group, so
+    /// they can be colored the same.
+    xform_groups: Vec<usize>,
     /// The index of the last transform applied
     last_xform: usize,
+    /// How many of the most recent transforms are packed into the address,
+    /// or 0 to not track the address
+    address_depth: u32,
+    /// The most recent transform indices as digits of a base-N number
+    /// where N is the number of transforms. See get_xform_address()
+    address: u32,
+    /// Optional transformation applied to points on output, e.g. to center
+    /// and orient the attractor. This is not part of the random selection,
+    /// and the points are not fed back into the iteration, so it doesn't
+    /// change the dynamics of the IFS.
+    post_transform: Option<Box<dyn Transform>>,
 }
 
 impl IFS {
-    pub fn new(xforms: Vec<Xform>, chooser: XformSelector) -> Self {
-        Self { xforms, chooser, last_xform: 0}
+    pub fn new(
+            xforms: Vec<Box<dyn Transform>>,
+            xform_groups: Vec<usize>,
+            chooser: XformSelector,
+            post_transform: Option<Box<dyn Transform>>) -> Self {
+        Self {
+            xforms,
+            xform_groups,
+            chooser,
+            last_xform: 0,
+            address_depth: 0,
+            address: 0,
+            post_transform
+        }
     }
 
     /// Create the simplest possible IFS: the identity transformation
     /// and a unfiorm chooser
     pub fn identity() -> Self {
-        let identity_xform = Xform::identity();
+        let identity_xform = Xform::identity().to_box();
         Self {
             xforms: vec![identity_xform],
+            xform_groups: vec![0],
             chooser: Box::new(UniformChooser::new(1)),
             last_xform: 0,
+            address_depth: 0,
+            address: 0,
+            post_transform: None,
         }
     }
 
@@ -50,46 +87,221 @@ impl IFS {
         self.last_xform as u8
     }
 
+    /// Get the group index of the last transformation applied. This is the
+    /// same as get_last_xform() except a transformation and its inverse
+    /// (from the ["+inverse"] shortcut) report the same value.
+    pub fn get_last_xform_group(&self) -> u8 {
+        self.xform_groups[self.last_xform] as u8
+    }
+
+    /// Pack the last address_depth transformation indices into the address
+    /// returned by get_xform_address(). The address must fit in the mantissa
+    /// of a float, i.e. N^address_depth <= 2^24 for N transformations. See
+    /// max_address_depth() for the largest depth allowed.
+    pub fn set_address_depth(&mut self, address_depth: u32) {
+        let max_depth = max_address_depth(self.xforms.len());
+        if address_depth > max_depth {
+            panic!(
+                "address_depth must be at most {} for {} xforms so the \
+                address fits in a float",
+                max_depth,
+                self.xforms.len());
+        }
+        self.address_depth = address_depth;
+        self.address = 0;
+    }
+
+    /// How many transformations are packed into the address. 0 means the
+    /// address is not tracked
+    pub fn address_depth(&self) -> u32 {
+        self.address_depth
+    }
+
+    /// Get the address of the current point: the last address_depth
+    /// transformation indices as digits of a base-N number, where N is the
+    /// number of transformations. The most recent transformation is the most
+    /// significant digit, so address / N^address_depth is in [0, 1) and
+    /// points in the same piece of the attractor have nearby values. This
+    /// makes it work well as a 1D colormap lookup. Before address_depth
+    /// transformations are applied, the missing digits are 0.
+    pub fn get_xform_address(&self) -> u32 {
+        self.address
+    }
+
+    /// The number of transformations, after expanding shortcuts like
+    /// ["schottky", ...] into their transformations
+    pub fn xform_count(&self) -> usize {
+        self.xforms.len()
+    }
+
+    /// The number of groups of transformations, see get_last_xform_group()
+    pub fn xform_group_count(&self) -> usize {
+        self.xform_groups.iter().max().map_or(0, |group| group + 1)
+    }
+
+    /// Get the name of each transformation, in the same order as the
+    /// indices from get_last_xform()
+    pub fn xform_names(&self) -> Vec<String> {
+        self.xforms.iter().map(|xform| xform.name()).collect()
+    }
+
+    /// Describe each transformation for debugging, in the same order as
+    /// xform_names(). See Transform::describe()
+    pub fn describe_xforms(&self) -> Vec<String> {
+        self.xforms.iter().map(|xform| xform.describe()).collect()
+    }
+
+    /// Classify each transformation, in the same order as xform_names()
+    pub fn xform_classifications(&self) -> Vec<Classification> {
+        self.xforms.iter().map(|xform| xform.classification()).collect()
+    }
+
+    /// Estimate the dimension of the attractor from the contraction
+    /// factors r_i of the transformations by solving the Moran equation
+    /// sum(r_i^D) = 1. This is exact for similarities that don't overlap
+    /// too much. If any transformation doesn't contract, the points are
+    /// not confined to a lower-dimensional set, so this returns 3.
+    pub fn similarity_dimension(&self) -> f64 {
+        let factors: Vec<f64> = self.xforms
+            .iter()
+            .map(|xform| xform.contraction_estimate())
+            .collect();
+        moran_dimension(&factors)
+    }
+
+    /// The radius of a ball centered at the origin that holds every point
+    /// of an orbit that starts within the given radius, after the
+    /// post-transform. Each transformation maps the ball of radius R into
+    /// itself if R >= |f(0)| / (1 - r), see Transform::reach(). This is
+    /// exact for similarities but only an estimate for other maps. None if
+    /// a transformation doesn't contract.
+    pub fn invariant_radius(&self, radius: f64) -> Option<f64> {
+        let mut invariant = radius;
+        for xform in self.xforms.iter() {
+            let contraction = xform.contraction_estimate();
+            if contraction >= 1.0 {
+                return None;
+            }
+            let offset = xform.reach(0.0)?;
+            invariant = invariant.max(offset / (1.0 - contraction));
+        }
+        match &self.post_transform {
+            Some(xform) => xform.reach(invariant),
+            None => Some(invariant)
+        }
+    }
+
     /// Transform an individual point using a randomly-selected transformation
     /// from this IFS. The Chooser determines the random distribution
     pub fn transform(&mut self, point: &HalfMultivector) -> HalfMultivector {
-        let index = self.chooser.choose();
-        let xform = &self.xforms[index];
-        self.last_xform = index;
-        xform.transform(point)
+        self.choose_xform().transform(point)
     }
 
-    /// Transform a vector containing points. This is used for transforming
-    /// the points/colors of a Buffer.
-    pub fn transform_points(
-            &mut self, points: &Vec<HalfMultivector>) -> Vec<HalfMultivector> {
+    /// Choose the next transformation without applying it. This is used
+    /// for transforming a whole buffer of points with the same
+    /// transformation.
+    pub fn choose_xform(&mut self) -> &dyn Transform {
         let index = self.chooser.choose();
-        let xform = &self.xforms[index];
         self.last_xform = index;
-        points.iter().map(|point| xform.transform(point)).collect()
+        if self.address_depth > 0 {
+            // Shift in the new index as the most significant digit. The
+            // least significant digit (the oldest transform) falls off
+            let base = self.xforms.len() as u32;
+            let place = base.pow(self.address_depth - 1);
+            self.address = self.address / base + index as u32 * place;
+        }
+        self.xforms[index].as_ref()
+    }
+
+    /// Check if there is a post-transform, so callers can skip copying
+    /// points when there is nothing to do
+    pub fn has_post_transform(&self) -> bool {
+        self.post_transform.is_some()
+    }
+
+    /// Apply the post-transform (if any) to a point that is about to be
+    /// plotted. The result should not be fed back into transform()
+    pub fn post_transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        match &self.post_transform {
+            Some(xform) => xform.transform(point),
+            None => point.clone()
+        }
     }
 
     /// Reset. This operation should be applied at the start of iterating
     /// each cluster to ensure each one takes a different path through the tree
     pub fn reset(&mut self) {
         self.chooser.reset();
+        self.address = 0;
     }
 }
 
+/// Solve the Moran equation sum(r_i^D) = 1 for the contraction factors r_i,
+/// see IFS::similarity_dimension(). This returns 3 if any factor is not a
+/// contraction or the maps overlap enough to fill space.
+pub fn moran_dimension(factors: &[f64]) -> f64 {
+    // Allow for roundoff in the estimate of isometries
+    if factors.iter().any(|r| *r >= 1.0 - 1e-6) {
+        return 3.0;
+    }
+
+    let moran_sum = |d: f64| -> f64 {
+        factors.iter().map(|r| r.powf(d)).sum()
+    };
+    if moran_sum(3.0) >= 1.0 {
+        return 3.0;
+    }
+
+    // The sum decreases as D increases, so bisect on [0, 3]
+    let mut low = 0.0;
+    let mut high = 3.0;
+    for _ in 0..50 {
+        let mid = 0.5 * (low + high);
+        if moran_sum(mid) > 1.0 {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    0.5 * (low + high)
+}
+
 /// Parse an IFS from JSON of the form:
 /// ```text
 /// {
 ///     "chooser": <Chooser JSON>,
 ///     "xforms": [<XFormJson>, ...],
+///     "post_transform": <XFormJson> (optional),
+///     "address_depth": K (default 0)
 /// }
 /// ```
+///
+/// address_depth turns on the transformation address, see
+/// IFS::set_address_depth(). With N transformations, K can be at most
+/// floor(24 / log2(N)), e.g. 24 for 2 transformations, 12 for 4 or 7
+/// for 10.
 pub fn from_json(json: &JsonValue) -> IFS {
     match json {
         JsonValue::Null => IFS::identity(),
         JsonValue::Object(_) => {
-            let xforms = parse_xforms(&json["xforms"]);
-            let chooser = choosers::from_json(&json["chooser"], xforms.len());
-            IFS::new(xforms, chooser)
+            let (xforms, xform_groups) = parse_xforms(&json["xforms"]);
+            // The tileset metadata also stores the count as a UINT8
+            check_id_count(
+                xforms.len(), u8::MAX as usize, "transformations",
+                "last_xform");
+            let chooser = choosers::from_json(&json["chooser"], &xforms);
+            let post_transform = match &json["post_transform"] {
+                JsonValue::Null => None,
+                xform_desc => Some(xforms::from_json(xform_desc))
+            };
+            let mut ifs = IFS::new(xforms, xform_groups, chooser, post_transform);
+            match &json["address_depth"] {
+                JsonValue::Null => {},
+                depth => ifs.set_address_depth(depth
+                    .as_u32()
+                    .expect("address_depth must be a non-negative integer"))
+            }
+            ifs
         },
         _ => panic!("IFS JSON must be an object or null")
     }
@@ -104,30 +316,101 @@ pub fn from_json(json: &JsonValue) -> IFS {
 /// When this element is encountered, the previous transformation's inverse
 /// is added. This is a handy shortcut since often I want to describe groups
 /// of transformations which requires specifying their inverses.
-fn parse_xforms(xform_arr: &JsonValue) -> Vec<Xform> {
+///
+/// Another shortcut generates the inversions for a Schottky group:
+/// ["schottky", [{"center": [x, y, z], "radius": r}, ...]]
+///
+/// This adds one ["invert_sphere", x, y, z, r] per sphere. Sphere inversions
+/// are their own inverses, so unlike Mobius generators there is no need
+/// for ["+inverse"].
+///
+/// For kaleidoscopic tilings, another shortcut generates the products of
+/// reflections in the mirror planes of a fundamental domain:
+/// ["tiling_group", {
+///     "planes": [{"normal": [nx, ny, nz], "offset": d}, ...],
+///     "max_word_length": k
+/// }]
+///
+/// Each plane is n.x = d (offset defaults to 0). This adds every distinct
+/// product of at most k reflections, see parse_tiling_group().
+///
+/// For foam-like patterns, another shortcut inverts in a grid of spheres:
+/// ["sphere_lattice", {
+///     "spacing": s,
+///     "radius": r,
+///     "counts": [nx, ny, nz]
+/// }]
+///
+/// This adds ["invert_sphere", x, y, z, r] for each of the nx * ny * nz
+/// points of a grid centered at the origin, see parse_sphere_lattice().
+///
+/// Alongside the transformations, this returns the group index of each
+/// transformation. An inverse added this way is in the same group as
+/// the transformation before it. Each Schottky or lattice inversion and
+/// each tiling group element gets its own group.
+fn parse_xforms(xform_arr: &JsonValue)
+        -> (Vec<Box<dyn Transform>>, Vec<usize>) {
     let mut result = Vec::new();
+    let mut groups = Vec::new();
+    let mut group_count = 0;
  
     for xform_desc in xform_arr.members() {
-        let type_name = xform_desc[0]
-            .as_str()
-            .expect("xforms: transformation type must be a string");
+        // Warped transformations are objects, see xforms::from_json()
+        let type_name = match xform_desc {
+            JsonValue::Object(_) => "warp",
+            _ => xform_desc[0]
+                .as_str()
+                .expect("xforms: transformation type must be a string")
+        };
 
         match type_name {
-            "+inverse" => add_inverse(&mut result),
+            "+inverse" => {
+                add_inverse(&mut result);
+                // add_inverse() already checked that the list is nonempty
+                groups.push(group_count - 1);
+            },
+            "schottky" => {
+                for xform in parse_schottky(&xform_desc[1]) {
+                    result.push(xform);
+                    groups.push(group_count);
+                    group_count += 1;
+                }
+            },
+            "sphere_lattice" => {
+                for xform in parse_sphere_lattice(&xform_desc[1]) {
+                    result.push(xform);
+                    groups.push(group_count);
+                    group_count += 1;
+                }
+            },
+            "tiling_group" => {
+                for xform in parse_tiling_group(&xform_desc[1]) {
+                    result.push(xform);
+                    groups.push(group_count);
+                    group_count += 1;
+                }
+            },
             _ => {
                 let xform = xforms::from_json(&xform_desc);
                 result.push(xform);
+                groups.push(group_count);
+                group_count += 1;
             }
         };
     }
 
-    result
+    (result, groups)
 }
 
 /// For brevity, instead of typing out a function and its inverses, just
 /// add the shortcut ["+inverse"] after a transformation, and its inverse
 /// will be added to the list
-fn add_inverse(results: &mut Vec<Xform>) {
+///
+/// A chain of versors is multiplied out into a single versor, and its true
+/// inverse (see HalfMultivector::inverse()) undoes each step in reverse
+/// order, even for scales, translations and inversions. Other chains are
+/// inverted step by step, see Chain::inverse().
+fn add_inverse(results: &mut Vec<Box<dyn Transform>>) {
     if results.is_empty() {
         panic!(concat!(
             "[\"+inverse\"] should be listed after a transformation in the ",
@@ -135,6 +418,648 @@ fn add_inverse(results: &mut Vec<Xform>) {
     }
 
     let n = results.len();
-    let inv = results[n - 1].inverse();
+    let inv = results[n - 1]
+        .inverse()
+        .expect("[\"+inverse\"]: the previous transformation is not invertible");
     results.push(inv);
 }
+
+/// Parse the spheres of a ["schottky", spheres] shortcut and make the
+/// inversion in each one
+fn parse_schottky(spheres_json: &JsonValue) -> Vec<Box<dyn Transform>> {
+    let spheres: Vec<(Vec3, f32)> = spheres_json.members().map(|sphere| {
+        let center = Vec3::from_json(&sphere["center"], Vec3::zero());
+        let radius = sphere["radius"]
+            .as_f32()
+            .expect("schottky: radius must be a number");
+        (center, radius)
+    }).collect();
+
+    if spheres.is_empty() {
+        panic!("schottky: at least one sphere is required");
+    }
+    check_schottky_spheres(&spheres);
+
+    spheres.iter().map(|(center, radius)| {
+        let desc = array![
+            "invert_sphere",
+            *center.x(),
+            *center.y(),
+            *center.z(),
+            *radius
+        ];
+        xforms::from_json(&desc)
+    }).collect()
+}
+
+/// Parse a ["sphere_lattice", {...}] shortcut and make the inversion in
+/// each sphere of the lattice. Along each axis, the n centers are spacing
+/// apart and centered on the origin. Unlike Schottky groups, overlapping
+/// spheres are allowed since they make interesting foams, but they only
+/// get a warning since the bubbles no longer nest nicely. A spacing of 0
+/// would put several spheres in the same place, so repeated centers are
+/// only added once.
+fn parse_sphere_lattice(json: &JsonValue) -> Vec<Box<dyn Transform>> {
+    let spacing = json["spacing"]
+        .as_f32()
+        .filter(|spacing| *spacing >= 0.0)
+        .expect("sphere_lattice: spacing must be a non-negative number");
+    let radius = json["radius"]
+        .as_f32()
+        .filter(|radius| *radius > 0.0)
+        .expect("sphere_lattice: radius must be a positive number");
+    let counts: Vec<usize> = json["counts"]
+        .members()
+        .map(|count| count
+            .as_usize()
+            .filter(|count| *count > 0)
+            .expect("sphere_lattice: counts must be positive integers"))
+        .collect();
+    if counts.len() != 3 {
+        panic!("sphere_lattice: counts must be [nx, ny, nz]");
+    }
+
+    let centers = lattice_centers(spacing, &counts);
+    let total: usize = counts.iter().product();
+    if centers.len() < total {
+        info!(
+            "Warning: sphere_lattice: skipped {} repeated spheres",
+            total - centers.len());
+    }
+    if centers.len() > 1 && 2.0 * radius > spacing {
+        info!(
+            "Warning: sphere_lattice: spheres of radius {} overlap at \
+            spacing {}", radius, spacing);
+    }
+
+    centers.iter().map(|center| {
+        let desc = array![
+            "invert_sphere",
+            *center.x(),
+            *center.y(),
+            *center.z(),
+            radius
+        ];
+        xforms::from_json(&desc)
+    }).collect()
+}
+
+/// The distinct centers of a lattice with the given spacing and number of
+/// points along each axis, centered on the origin
+fn lattice_centers(spacing: f32, counts: &[usize]) -> Vec<Vec3> {
+    // Offset of the i-th of n points along one axis
+    let offset =
+        |i: usize, n: usize| (i as f32 - (n - 1) as f32 / 2.0) * spacing;
+
+    let mut centers: Vec<Vec3> = Vec::new();
+    for i in 0..counts[0] {
+        for j in 0..counts[1] {
+            for k in 0..counts[2] {
+                let center = Vec3::new(
+                    offset(i, counts[0]),
+                    offset(j, counts[1]),
+                    offset(k, counts[2]));
+                let repeated = centers
+                    .iter()
+                    .any(|other| (*other - center).length() < 1e-6);
+                if !repeated {
+                    centers.push(center);
+                }
+            }
+        }
+    }
+    centers
+}
+
+/// Generic points used to tell tiling group elements apart. Two versors
+/// are the same transformation if they move all of these to the same place.
+/// This also handles V and -V, which are the same transformation.
+const TILING_SAMPLE_POINTS: [(f64, f64, f64); 4] = [
+    (0.13, 0.27, 0.31),
+    (1.3, -0.7, 0.4),
+    (-0.6, 0.9, -1.1),
+    (0.5, 0.55, -0.2)
+];
+/// Tolerance for considering two tiling group elements the same
+const TILING_TOLERANCE: f64 = 1e-6;
+
+/// Where a versor moves each of the TILING_SAMPLE_POINTS
+fn tiling_fingerprint(versor: &HalfMultivector) -> Vec<(f64, f64, f64)> {
+    TILING_SAMPLE_POINTS.iter().map(|(x, y, z)| {
+        versor
+            .sandwich_product(&HalfMultivector::point(*x, *y, *z))
+            .coordinates()
+    }).collect()
+}
+
+/// Check if two fingerprints from tiling_fingerprint() match
+fn same_fingerprint(a: &[(f64, f64, f64)], b: &[(f64, f64, f64)]) -> bool {
+    a.iter().zip(b.iter()).all(|((ax, ay, az), (bx, by, bz))| {
+        (ax - bx).abs() < TILING_TOLERANCE &&
+        (ay - by).abs() < TILING_TOLERANCE &&
+        (az - bz).abs() < TILING_TOLERANCE
+    })
+}
+
+/// Parse a ["tiling_group", {...}] shortcut. Starting from the reflections
+/// in each plane, words are built up one reflection at a time (breadth
+/// first) up to max_word_length. Words that give the same transformation
+/// as a shorter word, or the identity, are skipped. The odd-length words
+/// are reflections (or rotoreflections) and the even-length words are
+/// rotations (or translations for parallel planes).
+///
+/// The transformations are named by their word, e.g. "tiling(p0 p2)"
+/// reflects in plane 2 and then plane 0.
+fn parse_tiling_group(json: &JsonValue) -> Vec<Box<dyn Transform>> {
+    let generators: Vec<HalfMultivector> = json["planes"]
+        .members()
+        .map(|plane| {
+            let normal = Vec3::from_json(&plane["normal"], Vec3::zero());
+            let length = normal.length() as f64;
+            if length == 0.0 {
+                panic!("tiling_group: plane normals must be nonzero");
+            }
+            let offset = plane["offset"].as_f64().unwrap_or(0.0);
+            HalfMultivector::plane_reflection(
+                *normal.x() as f64 / length,
+                *normal.y() as f64 / length,
+                *normal.z() as f64 / length,
+                offset)
+        })
+        .collect();
+    if generators.is_empty() {
+        panic!("tiling_group: at least one plane is required");
+    }
+
+    let max_word_length = json["max_word_length"]
+        .as_usize()
+        .filter(|length| *length > 0)
+        .expect("tiling_group: max_word_length must be a positive integer");
+
+    let mut fingerprints = vec![tiling_fingerprint(&HalfMultivector::identity())];
+    let mut elements: Vec<(HalfMultivector, Vec<usize>)> = Vec::new();
+    let mut frontier = vec![(HalfMultivector::identity(), Vec::new())];
+    for _ in 0..max_word_length {
+        let mut next_frontier = Vec::new();
+        for (versor, word) in frontier.iter() {
+            for (i, generator) in generators.iter().enumerate() {
+                // Each reflection is its own inverse
+                if word.first() == Some(&i) {
+                    continue;
+                }
+
+                let product = generator.geometric_product(versor);
+                let fingerprint = tiling_fingerprint(&product);
+                let is_new = !fingerprints
+                    .iter()
+                    .any(|other| same_fingerprint(other, &fingerprint));
+                if is_new {
+                    let mut new_word = vec![i];
+                    new_word.extend_from_slice(word);
+                    fingerprints.push(fingerprint);
+                    next_frontier.push((product, new_word));
+                }
+            }
+        }
+        elements.extend(next_frontier.iter().cloned());
+        frontier = next_frontier;
+    }
+
+    elements.into_iter().map(|(versor, word)| {
+        let letters: Vec<String> =
+            word.iter().map(|i| format!("p{}", i)).collect();
+        let name = format!("tiling({})", letters.join(" "));
+        Xform::new(versor, &name).to_box()
+    }).collect()
+}
+
+/// The largest address_depth such that xform_count^address_depth fits
+/// exactly in a float. With a single transformation, the address is always
+/// 0 so any depth works.
+pub fn max_address_depth(xform_count: usize) -> u32 {
+    if xform_count < 2 {
+        return u32::MAX;
+    }
+
+    let mut depth = 0;
+    let mut size: u64 = 1;
+    while size * (xform_count as u64) <= MAX_ADDRESS {
+        size *= xform_count as u64;
+        depth += 1;
+    }
+    depth
+}
+
+/// The spheres of a Schottky group must not overlap, otherwise the limit
+/// set is degenerate. Tangent spheres are allowed. Nested spheres are
+/// allowed too, but they usually aren't what was intended, so warn about
+/// them.
+fn check_schottky_spheres(spheres: &[(Vec3, f32)]) {
+    const TOLERANCE: f32 = 1e-5;
+    for (i, (center_a, radius_a)) in spheres.iter().enumerate() {
+        for (j, (center_b, radius_b)) in spheres.iter().enumerate().skip(i + 1) {
+            let distance = (*center_a - *center_b).length();
+            if distance <= (radius_a - radius_b).abs() + TOLERANCE {
+                info!(
+                    "Warning: schottky spheres {} and {} are nested", i, j);
+            } else if distance < radius_a + radius_b - TOLERANCE {
+                panic!("schottky: spheres {} and {} overlap", i, j);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiling_ifs(planes: JsonValue, max_word_length: usize) -> IFS {
+        let mut group = object!{ "max_word_length" => max_word_length };
+        group["planes"] = planes;
+        let mut shortcut = array!["tiling_group"];
+        shortcut.push(group).unwrap();
+        from_json(&object!{
+            "xforms" => array![shortcut]
+        })
+    }
+
+    #[test]
+    fn test_tiling_group_finite() {
+        // Mirrors in the 3 coordinate planes commute, so there are only
+        // 2^3 = 8 elements, and one of them is the identity
+        let planes = array![
+            object!{ "normal" => array![1, 0, 0] },
+            object!{ "normal" => array![0, 1, 0] },
+            object!{ "normal" => array![0, 0, 1] }
+        ];
+        let ifs = tiling_ifs(planes, 6);
+        assert_eq!(ifs.xforms.len(), 7);
+        assert_eq!(ifs.xform_groups, vec![0, 1, 2, 3, 4, 5, 6]);
+        assert_eq!(&ifs.xform_names()[..4], &[
+            "tiling(p0)", "tiling(p1)", "tiling(p2)", "tiling(p1 p0)"]);
+
+        // Two mirrors at 60 degrees generate the symmetries of a triangle
+        let half_sqrt3 = 3.0f64.sqrt() / 2.0;
+        let planes = array![
+            object!{ "normal" => array![0, 1, 0] },
+            object!{ "normal" => array![half_sqrt3, -0.5, 0] }
+        ];
+        assert_eq!(tiling_ifs(planes, 10).xforms.len(), 5);
+    }
+
+    #[test]
+    fn test_tiling_group_parallel_planes() {
+        // Reflecting in x = 0 then x = 1 translates by 2
+        let planes = array![
+            object!{ "normal" => array![1, 0, 0] },
+            object!{ "normal" => array![1, 0, 0], "offset" => 1 }
+        ];
+        let ifs = tiling_ifs(planes, 2);
+        assert_eq!(
+            ifs.xform_names(),
+            vec!["tiling(p0)", "tiling(p1)", "tiling(p1 p0)", "tiling(p0 p1)"]);
+
+        let point = HalfMultivector::point(0.25, 0.5, 0.0);
+        let (x, y, _) = ifs.xforms[1].transform(&point).coordinates();
+        assert!((x - 1.75).abs() < 1e-9);
+        assert!((y - 0.5).abs() < 1e-9);
+        let (x, _, _) = ifs.xforms[2].transform(&point).coordinates();
+        assert!((x - 2.25).abs() < 1e-9);
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one plane")]
+    fn test_tiling_group_needs_planes() {
+        tiling_ifs(array![], 2);
+    }
+
+    #[test]
+    fn test_invariant_radius() {
+        // Sierpinski triangle with corners at (-1, 0), (1, 0) and (0, 1)
+        let corner = |x: f64, y: f64| array![
+            "chain", array![array!["scale", 0.5], array!["translate", x, y, 0]]
+        ];
+        let triangle = from_json(&object!{
+            "xforms" => array![
+                corner(-0.5, 0.0), corner(0.5, 0.0), corner(0.0, 0.5)
+            ]
+        });
+        let radius = triangle.invariant_radius(0.0).unwrap();
+        assert!((radius - 1.0).abs() < 1e-6, "{}", radius);
+        // A larger seed is already mapped into itself
+        let radius = triangle.invariant_radius(3.0).unwrap();
+        assert!((radius - 3.0).abs() < 1e-6, "{}", radius);
+
+        let expansion = from_json(&object!{
+            "xforms" => array![array!["scale", 2.0]]
+        });
+        assert!(expansion.invariant_radius(1.0).is_none());
+    }
+
+    #[test]
+    fn test_similarity_dimension() {
+        // Sierpinski tetrahedron: 4 copies at half scale
+        let tetrahedron = from_json(&object!{
+            "xforms" => array![
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", 1, 1, 1]]],
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", 1, -1, -1]]],
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", -1, 1, -1]]],
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", -1, -1, 1]]]
+            ]
+        });
+        assert!((tetrahedron.similarity_dimension() - 2.0).abs() < 1e-3);
+
+        // A single contraction converges to a point
+        let point = from_json(&object!{
+            "xforms" => array![array!["scale", 0.5]]
+        });
+        assert!(point.similarity_dimension() < 1e-3);
+
+        // Rotations don't contract at all
+        let rotation = from_json(&object!{
+            "xforms" => array![array!["rotate", 0, 0, 1, 10]]
+        });
+        assert_eq!(rotation.similarity_dimension(), 3.0);
+    }
+
+    #[test]
+    fn test_post_transform_shifts_output() {
+        let json = object!{
+            "xforms" => array![
+                array!["scale", 0.5]
+            ],
+            "post_transform" => array!["translate", 1, 2, 3]
+        };
+        let mut ifs = from_json(&json);
+        assert!(ifs.has_post_transform());
+
+        let mut point = HalfMultivector::point(0.5, -1.0, 2.0);
+        for _ in 0..5 {
+            // The iteration itself is unaffected
+            point = ifs.transform(&point);
+            let (x, y, z) = point.coordinates();
+
+            let (px, py, pz) = ifs.post_transform(&point).coordinates();
+            assert!((px - x - 1.0).abs() < 1e-9);
+            assert!((py - y - 2.0).abs() < 1e-9);
+            assert!((pz - z - 3.0).abs() < 1e-9);
+        }
+
+        // After 5 halvings, the point is not translated at all
+        let (x, y, z) = point.coordinates();
+        assert!((x - 0.5 / 32.0).abs() < 1e-9);
+        assert!((y + 1.0 / 32.0).abs() < 1e-9);
+        assert!((z - 2.0 / 32.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_schottky() {
+        let json = object!{
+            "xforms" => array![
+                array!["scale", 0.5],
+                array!["schottky", array![
+                    object!{ "center" => array![2, 0, 0], "radius" => 1 },
+                    object!{ "center" => array![-2, 0, 0], "radius" => 1 }
+                ]]
+            ]
+        };
+        let ifs = from_json(&json);
+        assert_eq!(ifs.xform_names(), vec![
+            "scale(0.5)",
+            "invert_sphere((2, 0, 0), 1)",
+            "invert_sphere((-2, 0, 0), 1)"
+        ]);
+        assert_eq!(ifs.xform_groups, vec![0, 1, 2]);
+
+        // Each inversion is its own inverse
+        let point = HalfMultivector::point(0.5, 0.25, 0.0);
+        for xform in ifs.xforms[1..].iter() {
+            let round_trip = xform.transform(&xform.transform(&point));
+            let (x, y, z) = round_trip.coordinates();
+            assert!((x - 0.5).abs() < 1e-9);
+            assert!((y - 0.25).abs() < 1e-9);
+            assert!(z.abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "256 transformations is too many")]
+    fn test_too_many_xforms() {
+        let xforms: Vec<JsonValue> =
+            (0..256).map(|_| array!["scale", 0.5]).collect();
+        from_json(&object!{ "xforms" => xforms });
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap")]
+    fn test_schottky_overlap() {
+        let json = object!{
+            "xforms" => array![
+                array!["schottky", array![
+                    object!{ "center" => array![0.5, 0, 0], "radius" => 1 },
+                    object!{ "center" => array![-0.5, 0, 0], "radius" => 1 }
+                ]]
+            ]
+        };
+        from_json(&json);
+    }
+
+    #[test]
+    fn test_schottky_tangent_and_nested() {
+        // Tangent and nested spheres are allowed
+        let spheres = vec![
+            (Vec3::new(1.0, 0.0, 0.0), 1.0),
+            (Vec3::new(-1.0, 0.0, 0.0), 1.0),
+            (Vec3::new(1.0, 0.0, 0.0), 0.25)
+        ];
+        check_schottky_spheres(&spheres);
+    }
+
+    #[test]
+    fn test_chain_inverse_fixes_points() {
+        let json = array![
+            array!["chain", array![
+                array!["translate", 1, 0, 0],
+                array!["scale", 2]
+            ]],
+            array!["+inverse"],
+            array!["chain", array![
+                array!["translate", 0.5, -1, 0],
+                array!["invert"],
+                array!["mobius", 1, 0, 0.5, 0, 0, 0, 1, 0]
+            ]],
+            array!["+inverse"]
+        ];
+        let (xforms, _) = parse_xforms(&json);
+        let point = HalfMultivector::point(0.5, 0.25, -2.0);
+
+        // translate then scale, so (0.5, 0, 0) would go to (3, 0, 0)
+        let (x, y, z) = xforms[0].transform(&point).coordinates();
+        assert!((x - 3.0).abs() < 1e-9 && (y - 0.5).abs() < 1e-9);
+        assert!((z + 4.0).abs() < 1e-9);
+
+        for pair in xforms.chunks(2) {
+            let round_trip = pair[1].transform(&pair[0].transform(&point));
+            let (x, y, z) = round_trip.coordinates();
+            assert!((x - 0.5).abs() < 1e-9, "{}: {}", pair[0].name(), x);
+            assert!((y - 0.25).abs() < 1e-9, "{}: {}", pair[0].name(), y);
+            assert!((z + 2.0).abs() < 1e-9, "{}: {}", pair[0].name(), z);
+        }
+    }
+
+    #[test]
+    fn test_sphere_lattice() {
+        let json = object!{
+            "xforms" => array![
+                array!["sphere_lattice", object!{
+                    "spacing" => 2,
+                    "radius" => 0.5,
+                    "counts" => array![2, 1, 1]
+                }],
+                array!["+inverse"]
+            ]
+        };
+        let ifs = from_json(&json);
+        assert_eq!(ifs.xform_names(), vec![
+            "invert_sphere((-1, 0, 0), 0.5)",
+            "invert_sphere((1, 0, 0), 0.5)",
+            "inverse(invert_sphere((1, 0, 0), 0.5))"
+        ]);
+        assert_eq!(ifs.xform_groups, vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn test_lattice_centers_skip_repeats() {
+        let centers = lattice_centers(1.0, &[3, 2, 1]);
+        assert_eq!(centers.len(), 6);
+        let first = &centers[0];
+        assert_eq!((*first.x(), *first.y(), *first.z()), (-1.0, -0.5, 0.0));
+        let last = &centers[5];
+        assert_eq!((*last.x(), *last.y(), *last.z()), (1.0, 0.5, 0.0));
+
+        // With no spacing, every sphere is in the same place
+        let centers = lattice_centers(0.0, &[3, 2, 2]);
+        assert_eq!(centers.len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "counts must be [nx, ny, nz]")]
+    fn test_sphere_lattice_needs_three_counts() {
+        parse_sphere_lattice(&object!{
+            "spacing" => 1,
+            "radius" => 0.25,
+            "counts" => array![2, 2]
+        });
+    }
+
+    #[test]
+    fn test_no_post_transform() {
+        let ifs = IFS::identity();
+        assert!(!ifs.has_post_transform());
+        let point = HalfMultivector::point(1.0, 2.0, 3.0);
+        let (x, y, z) = ifs.post_transform(&point).coordinates();
+        assert_eq!((x, y, z), (1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn test_inverse_shares_group() {
+        let json = object!{
+            "xforms" => array![
+                array!["translate", 1, 0, 0],
+                array!["+inverse"],
+                array!["scale", 0.5],
+                array!["rotate", 0, 0, 1, 90],
+                array!["+inverse"]
+            ],
+            // Select each transformation in turn
+            "chooser" => object!{
+                "type" => "markov",
+                "initial_weights" => array![1, 0, 0, 0, 0],
+                "weights" => array![
+                    array![0, 1, 0, 0, 0],
+                    array![0, 0, 1, 0, 0],
+                    array![0, 0, 0, 1, 0],
+                    array![0, 0, 0, 0, 1],
+                    array![1, 0, 0, 0, 0]
+                ]
+            }
+        };
+        let mut ifs = from_json(&json);
+        assert_eq!(ifs.xform_groups, vec![0, 0, 1, 2, 2]);
+        assert_eq!(ifs.xform_count(), 5);
+        assert_eq!(ifs.xform_group_count(), 3);
+
+        let selected: Vec<(u8, u8)> = (0..5).map(|_| {
+            ifs.choose_xform();
+            (ifs.get_last_xform(), ifs.get_last_xform_group())
+        }).collect();
+        assert_eq!(selected, vec![(0, 0), (1, 0), (2, 1), (3, 2), (4, 2)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "should be listed after a transformation")]
+    fn test_inverse_needs_previous_xform() {
+        from_json(&object!{
+            "xforms" => array![array!["+inverse"], array!["scale", 0.5]]
+        });
+    }
+
+    #[test]
+    fn test_xform_address() {
+        // The chooser cycles through 0, 1, 2, 0, ...
+        let json = object!{
+            "xforms" => array![
+                array!["identity"],
+                array!["identity"],
+                array!["identity"]
+            ],
+            "chooser" => object!{
+                "type" => "markov",
+                "initial_weights" => array![1, 0, 0],
+                "weights" => array![
+                    array![0, 1, 0],
+                    array![0, 0, 1],
+                    array![1, 0, 0]
+                ]
+            },
+            "address_depth" => 2
+        };
+        let mut ifs = from_json(&json);
+        let addresses: Vec<u32> = (0..4).map(|_| {
+            ifs.choose_xform();
+            ifs.get_xform_address()
+        }).collect();
+        // The most recent transform is the most significant digit, so
+        // these are 00, 10, 21, 02 in base 3
+        assert_eq!(addresses, vec![0, 3, 7, 2]);
+
+        ifs.reset();
+        assert_eq!(ifs.get_xform_address(), 0);
+    }
+
+    #[test]
+    fn test_max_address_depth() {
+        assert_eq!(max_address_depth(2), 24);
+        assert_eq!(max_address_depth(3), 15);
+        assert_eq!(max_address_depth(10), 7);
+        assert_eq!(max_address_depth(1), u32::MAX);
+    }
+
+    #[test]
+    #[should_panic(expected = "address_depth must be at most 12")]
+    fn test_address_depth_too_large() {
+        from_json(&object!{
+            "xforms" => array![
+                array!["identity"],
+                array!["identity"],
+                array!["identity"],
+                array!["identity"]
+            ],
+            "address_depth" => 13
+        });
+    }
+}
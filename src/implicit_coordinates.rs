@@ -47,4 +47,72 @@ impl ImplicitCoordinates {
             z
         }
     }
+
+    /// Check if this tile is the root of a subtree. Subtrees start every
+    /// subtree_levels levels, starting from the root.
+    pub fn is_subtree_root(&self) -> bool {
+        self.level.is_multiple_of(self.subtree_levels)
+    }
+
+    /// How many levels below the given ancestor tile this tile is.
+    pub fn relative_level(&self, ancestor: &Self) -> usize {
+        assert!(
+            self.level >= ancestor.level,
+            "relative_level: ancestor must not be deeper than this tile");
+        self.level - ancestor.level
+    }
+
+    /// Compute the Morton index of this tile within its level, relative
+    /// to an ancestor tile (typically the root of a subtree). The bits of
+    /// the local x, y, z coordinates are interleaved as ...zyxzyx
+    /// which matches the child index ordering 0bZYX used by the octree.
+    pub fn local_morton_index(&self, ancestor: &Self) -> usize {
+        let relative_level = self.relative_level(ancestor);
+        let local_x = self.x - (ancestor.x << relative_level);
+        let local_y = self.y - (ancestor.y << relative_level);
+        let local_z = self.z - (ancestor.z << relative_level);
+
+        let mut index = 0;
+        for i in 0..relative_level {
+            let x_bit = (local_x >> i) & 1;
+            let y_bit = (local_y >> i) & 1;
+            let z_bit = (local_z >> i) & 1;
+            index |= (z_bit << 2 | y_bit << 1 | x_bit) << (3 * i);
+        }
+
+        index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_child_morton_index_matches_child_index() {
+        let root = ImplicitCoordinates::root(3);
+        for child_index in 0..8 {
+            let child = root.get_child_coordinates(child_index);
+            assert_eq!(child.local_morton_index(&root), child_index);
+        }
+    }
+
+    #[test]
+    fn test_grandchild_morton_index() {
+        let root = ImplicitCoordinates::root(3);
+        let child = root.get_child_coordinates(0b101);
+        let grandchild = child.get_child_coordinates(0b011);
+        assert_eq!(grandchild.local_morton_index(&root), 0b101_011);
+        assert_eq!(grandchild.local_morton_index(&child), 0b011);
+    }
+
+    #[test]
+    fn test_is_subtree_root() {
+        let root = ImplicitCoordinates::root(2);
+        let child = root.get_child_coordinates(1);
+        let grandchild = child.get_child_coordinates(2);
+        assert!(root.is_subtree_root());
+        assert!(!child.is_subtree_root());
+        assert!(grandchild.is_subtree_root());
+    }
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+//! The distribution of points per octree leaf for
+//! --points-per-tile-histogram. The summary printed after saving only has
+//! the min, mean and max, which hides whether node_capacity and max_depth
+//! give mostly full leaves or a long tail of nearly empty ones.
+
+/// Width of the longest bar of the histogram in characters
+const BAR_WIDTH: usize = 40;
+/// The percentiles to print below the histogram
+const PERCENTILES: [f64; 5] = [10.0, 25.0, 50.0, 90.0, 99.0];
+
+/// A histogram of leaf sizes in power of two buckets: 0, 1, 2-3, 4-7, ...
+pub struct LeafHistogram {
+    /// The number of points in every leaf, sorted
+    sizes: Vec<usize>,
+}
+
+impl LeafHistogram {
+    pub fn new(mut sizes: Vec<usize>) -> Self {
+        sizes.sort_unstable();
+        Self { sizes }
+    }
+
+    /// Each bucket as (smallest size, largest size, number of leaves), up
+    /// to the bucket of the fullest leaf. Empty buckets are included so
+    /// gaps in the distribution show up.
+    pub fn buckets(&self) -> Vec<(usize, usize, usize)> {
+        let max_size = match self.sizes.last() {
+            Some(size) => *size,
+            None => return Vec::new()
+        };
+
+        let mut buckets = vec![(0, 0, 0)];
+        let mut low = 1;
+        while low <= max_size {
+            buckets.push((low, 2 * low - 1, 0));
+            low *= 2;
+        }
+        for size in self.sizes.iter() {
+            let index = match size {
+                0 => 0,
+                _ => (usize::BITS - size.leading_zeros()) as usize
+            };
+            buckets[index].2 += 1;
+        }
+        buckets
+    }
+
+    /// The nearest-rank percentile of the leaf sizes, or 0 if there are no
+    /// leaves
+    pub fn percentile(&self, percent: f64) -> usize {
+        if self.sizes.is_empty() {
+            return 0;
+        }
+        let rank = (percent / 100.0 * self.sizes.len() as f64).ceil() as usize;
+        self.sizes[rank.clamp(1, self.sizes.len()) - 1]
+    }
+
+    pub fn print(&self) {
+        println!("Points per leaf ({} leaves)", self.sizes.len());
+        let buckets = self.buckets();
+        let max_count = buckets
+            .iter()
+            .map(|(_, _, count)| *count)
+            .max()
+            .unwrap_or(0);
+        for (low, high, count) in buckets {
+            let label = if low == high {
+                low.to_string()
+            } else {
+                format!("{}-{}", low, high)
+            };
+            let bar_length = if max_count > 0 {
+                (count * BAR_WIDTH).div_ceil(max_count)
+            } else {
+                0
+            };
+            println!(
+                "{:>13} {:>8} {}", label, count, "#".repeat(bar_length));
+        }
+
+        let percentiles: Vec<String> = PERCENTILES
+            .iter()
+            .map(|percent| format!("p{} {}", percent, self.percentile(*percent)))
+            .collect();
+        println!("{}", percentiles.join(", "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buckets() {
+        let histogram = LeafHistogram::new(vec![5, 0, 1, 2, 3, 0, 17, 4]);
+        assert_eq!(histogram.buckets(), vec![
+            (0, 0, 2),
+            (1, 1, 1),
+            (2, 3, 2),
+            (4, 7, 2),
+            (8, 15, 0),
+            (16, 31, 1)
+        ]);
+        assert!(LeafHistogram::new(Vec::new()).buckets().is_empty());
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let histogram = LeafHistogram::new((1..=100).rev().collect());
+        assert_eq!(histogram.percentile(50.0), 50);
+        assert_eq!(histogram.percentile(99.0), 99);
+        assert_eq!(histogram.percentile(0.0), 1);
+        assert_eq!(histogram.percentile(100.0), 100);
+        assert_eq!(LeafHistogram::new(Vec::new()).percentile(50.0), 0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Generate 3D fractals using the Chaos Game and related algorithms. The
+//! `chaos-game-3d` binary reads a parameter file and writes a 3D Tiles
+//! tileset, but the generator can also be run in-process with
+//! `generate_points()`.
+
+#[macro_use]
+extern crate json;
+extern crate rand;
+
+#[macro_use]
+mod macros;
+
+pub mod algorithms;
+mod auto_stop;
+mod bbox;
+pub mod bench;
+mod checkpoint;
+mod choosers;
+mod clusters;
+pub mod colormap;
+pub mod compare;
+mod csv_writer;
+mod expressions;
+mod fractal_metadata;
+mod glb_writer;
+mod ifs;
+mod implicit_coordinates;
+mod jitter;
+pub mod leaf_histogram;
+mod live_stream;
+pub mod log;
+mod normals;
+mod half_multivector;
+mod octrees;
+mod orbit_trap;
+pub mod parameters;
+pub mod plotters;
+mod png_writer;
+mod pnts_writer;
+pub mod point;
+pub mod profiling;
+mod progress;
+pub mod quality;
+pub mod random;
+mod seed_files;
+mod subtree_writer;
+pub mod tileset_merge;
+mod tileset_writer;
+mod timeout;
+mod trail;
+pub mod vector;
+mod xforms;
+
+use json::JsonValue;
+
+use crate::point::OutputPoint;
+
+/// Run the algorithm described by a parameter JSON object (the same format
+/// as the parameter files, see the `params/` directory) and return the
+/// points that the plotter kept, without writing anything to disk. The
+/// plotter must be a scatter plot.
+pub fn generate_points(params: &JsonValue) -> Vec<OutputPoint> {
+    let mut chaos = algorithms::from_json(params);
+    chaos.iterate();
+    chaos.into_points()
+}
+
+/// Run a Chaos Sets parameter JSON object and pass the points of each
+/// copy of the cluster to on_copy_complete as soon as that copy is done,
+/// rather than collecting them all, see ChaosSets::iterate_streaming().
+pub fn stream_copies(
+        params: &JsonValue,
+        on_copy_complete: algorithms::CopyCallback) {
+    if params["algorithm"] != "chaos_sets" {
+        panic!("stream_copies requires the chaos_sets algorithm");
+    }
+    let mut chaos = algorithms::ChaosSets::from_json(params);
+    chaos.iterate_streaming(on_copy_complete);
+}
+
+/// Run the algorithm described by a parameter JSON object like
+/// generate_points(), and describe the output for building viewer styles:
+/// the schema of the tileset metadata, the per-point properties of .glb
+/// tiles, and the range of each property over the points that were kept
+/// (see point::property_ranges()). Nothing is written to disk. The
+/// plotter must be a scatter plot.
+pub fn echo_metadata(params: &JsonValue) -> JsonValue {
+    let mut chaos = algorithms::from_json(params);
+    chaos.iterate();
+    let mut json = chaos.metadata_json();
+    let points = chaos.into_points();
+    json["ranges"] = point::property_ranges(&points);
+    json
+}
+
+/// Describe each transformation in the parameter file's IFS, including
+/// the versor it was resolved to, see Transform::describe(). This is for
+/// debugging a chain or power that doesn't do what was expected.
+pub fn describe_xforms(params: &JsonValue) -> Vec<String> {
+    ifs::from_json(&params["ifs"]).describe_xforms()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parameters for a Sierpinski triangle in the z = 0 plane
+    fn sierpinski_params() -> JsonValue {
+        object!{
+            "id" => "test",
+            "name" => "Test",
+            "description" => "Sierpinski triangle",
+            "algorithm" => "chaos",
+            "iters" => 1000,
+            "plotter" => object!{
+                "radius" => 1.0,
+                "node_capacity" => 100
+            },
+            "ifs" => object!{
+                "xforms" => array![
+                    array!["chain", array![
+                        array!["scale", 0.5], array!["translate", -0.5, 0, 0]]],
+                    array!["chain", array![
+                        array!["scale", 0.5], array!["translate", 0.5, 0, 0]]],
+                    array!["chain", array![
+                        array!["scale", 0.5], array!["translate", 0, 0.5, 0]]]
+                ]
+            },
+            "color_ifs" => object!{
+                "xforms" => array![array!["scale", 1.0]]
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_points() {
+        let params = sierpinski_params();
+        let points = generate_points(&params);
+        assert_eq!(points.len(), 1000);
+        for point in points.iter() {
+            // The triangle is in the z = 0 plane. The random starting
+            // point is halved for each of the startup iterations, so the
+            // points start off very close to it
+            assert!(point.position.z().abs() < 1e-2);
+            assert!(point.position.length() <= 1.5);
+        }
+    }
+
+    #[test]
+    fn test_plot_mask_crops_attractor() {
+        // Keep only the right half of the triangle
+        let mut params = sierpinski_params();
+        params["plotter"]["plot_mask"] = object!{
+            "type" => "box",
+            "center" => array![0.5, 0.0, 0.0],
+            "dims" => array![1.0, 2.0, 2.0]
+        };
+
+        let points = generate_points(&params);
+        // The iteration is unaffected and the triangle is symmetric in x,
+        // so about half the points are kept
+        assert!(points.len() > 200 && points.len() < 800, "{}", points.len());
+        for point in points.iter() {
+            assert!(*point.position.x() >= 0.0);
+        }
+    }
+}
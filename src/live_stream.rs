@@ -0,0 +1,363 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+/// The interface to listen on by default. The server has no
+/// authentication, so only this machine can connect unless another host
+/// is chosen explicitly.
+pub const DEFAULT_HOST: &str = "127.0.0.1";
+
+/// Every WebSocket handshake hashes the client's key with this GUID, see
+/// RFC 6455 section 1.3
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// Opcode for a final frame of binary data
+const BINARY_FRAME: u8 = 0x82;
+/// Clients that take longer than this to send the handshake or receive a
+/// message are dropped. Only receiving a message can block for this long,
+/// waiting for the handshake doesn't stall the iteration.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+/// The handshake request must fit in this many bytes
+const MAX_REQUEST_BYTES: usize = 8192;
+
+/// A tiny WebSocket server that broadcasts binary messages to every
+/// connected client, for previewing a render live in the browser. To avoid
+/// extra dependencies, this only implements the parts of RFC 6455 a
+/// server needs to send data: the opening handshake and unmasked binary
+/// frames. Anything the clients send after the handshake is ignored.
+///
+/// The server never blocks waiting for clients. New connections are only
+/// accepted when a message is broadcast, and their handshakes are read a
+/// little at a time on each broadcast.
+pub struct LiveServer {
+    listener: TcpListener,
+    /// Clients that are still sending the handshake
+    pending: Vec<PendingClient>,
+    /// Clients that completed the handshake
+    clients: Vec<TcpStream>,
+}
+
+impl LiveServer {
+    /// Listen on the given host and port, e.g. DEFAULT_HOST for this
+    /// machine only or "0.0.0.0" for every interface. Port 0 picks any free
+    /// port, see port()
+    pub fn bind(host: &str, port: u16) -> Self {
+        let listener = TcpListener::bind((host, port))
+            .unwrap_or_else(|err| panic!(
+                "Cannot listen for WebSocket clients on {}:{}: {}",
+                host,
+                port,
+                err));
+        listener
+            .set_nonblocking(true)
+            .expect("could not make the WebSocket listener non-blocking");
+
+        Self {
+            listener,
+            pending: Vec::new(),
+            clients: Vec::new(),
+        }
+    }
+
+    /// The port the server is listening on
+    pub fn port(&self) -> u16 {
+        self.listener
+            .local_addr()
+            .expect("the WebSocket listener has no address")
+            .port()
+    }
+
+    /// Accept any waiting clients, then send a binary message to every
+    /// client. Clients that disconnected or fell behind are dropped.
+    pub fn broadcast(&mut self, message: &[u8]) {
+        self.accept_clients();
+
+        let mut frame = frame_header(message.len());
+        frame.extend_from_slice(message);
+        self.clients.retain_mut(|client| client.write_all(&frame).is_ok());
+    }
+
+    fn accept_clients(&mut self) {
+        while let Ok((stream, _)) = self.listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                self.pending.push(PendingClient {
+                    stream,
+                    request: Vec::new(),
+                    connected: Instant::now(),
+                });
+            }
+        }
+
+        for mut client in std::mem::take(&mut self.pending) {
+            match client.poll_handshake() {
+                Ok(true) => {
+                    self.clients.push(client.stream);
+                    info!(
+                        "Live preview connected ({} clients)",
+                        self.clients.len());
+                },
+                Ok(false) if client.connected.elapsed() < CLIENT_TIMEOUT =>
+                    self.pending.push(client),
+                // A client that fails the handshake or takes too long is
+                // just not added
+                _ => {}
+            }
+        }
+    }
+}
+
+/// A client that connected but hasn't finished the handshake yet
+struct PendingClient {
+    /// A non-blocking stream until the handshake is done
+    stream: TcpStream,
+    /// The part of the HTTP upgrade request received so far
+    request: Vec<u8>,
+    connected: Instant,
+}
+
+impl PendingClient {
+    /// Read as much of the upgrade request as has arrived without
+    /// blocking. Once it is complete, reply to accept it and switch to
+    /// blocking writes with a timeout for the messages. Returns true once
+    /// the handshake is done.
+    fn poll_handshake(&mut self) -> io::Result<bool> {
+        let mut buffer = [0; 1024];
+        while !self.request.ends_with(b"\r\n\r\n") {
+            let length = match self.stream.read(&mut buffer) {
+                Ok(length) => length,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock =>
+                    return Ok(false),
+                Err(err) => return Err(err)
+            };
+            if length == 0 || self.request.len() + length > MAX_REQUEST_BYTES {
+                return Err(io::ErrorKind::InvalidData.into());
+            }
+            self.request.extend_from_slice(&buffer[..length]);
+        }
+
+        self.stream.set_nonblocking(false)?;
+        self.stream.set_write_timeout(Some(CLIENT_TIMEOUT))?;
+        self.stream.set_nodelay(true)?;
+        let response = handshake_response(&self.request)?;
+        self.stream.write_all(response.as_bytes())?;
+        Ok(true)
+    }
+}
+
+/// The reply that accepts a client's HTTP upgrade request
+fn handshake_response(request: &[u8]) -> io::Result<String> {
+    let request = String::from_utf8_lossy(request);
+    let key = request
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(name, _)|
+            name.trim().eq_ignore_ascii_case("sec-websocket-key"))
+        .map(|(_, value)| value.trim())
+        .ok_or(io::ErrorKind::InvalidData)?;
+
+    Ok(format!(
+        "HTTP/1.1 101 Switching Protocols\r\n\
+        Upgrade: websocket\r\n\
+        Connection: Upgrade\r\n\
+        Sec-WebSocket-Accept: {}\r\n\r\n",
+        accept_key(key)))
+}
+
+/// The Sec-WebSocket-Accept value for a client's Sec-WebSocket-Key
+fn accept_key(key: &str) -> String {
+    let mut data = key.as_bytes().to_vec();
+    data.extend_from_slice(WEBSOCKET_GUID.as_bytes());
+    base64(&sha1(&data))
+}
+
+/// The header of an unmasked binary frame with a payload of the given
+/// length. The length takes 7 bits, or 16 or 64 bits after a marker.
+fn frame_header(length: usize) -> Vec<u8> {
+    let mut header = vec![BINARY_FRAME];
+    if length < 126 {
+        header.push(length as u8);
+    } else if length <= u16::MAX as usize {
+        header.push(126);
+        header.extend_from_slice(&(length as u16).to_be_bytes());
+    } else {
+        header.push(127);
+        header.extend_from_slice(&(length as u64).to_be_bytes());
+    }
+    header
+}
+
+/// SHA-1 hash, which the handshake requires. This is not used for
+/// anything security-related.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] =
+        [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // Pad with a 1 bit, zeros, then the length in bits to a multiple of
+    // 64 bytes
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            words[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            words[i] = (words[i - 3] ^ words[i - 8] ^ words[i - 14]
+                ^ words[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in words.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6)
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (total, value) in state.iter_mut().zip([a, b, c, d, e].iter()) {
+            *total = total.wrapping_add(*value);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (bytes, word) in digest.chunks_mut(4).zip(state.iter()) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result = String::new();
+    for chunk in data.chunks(3) {
+        let byte = |i: usize| *chunk.get(i).unwrap_or(&0);
+        let bits = u32::from_be_bytes([0, byte(0), byte(1), byte(2)]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (bits >> (18 - 6 * i)) & 0x3F;
+                result.push(ALPHABET[index as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sha1() {
+        let hex = |digest: [u8; 20]| -> String {
+            digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+        };
+        assert_eq!(
+            hex(sha1(b"abc")), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            hex(sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b"Man"), "TWFu");
+        assert_eq!(base64(b"Ma"), "TWE=");
+        assert_eq!(base64(b"M"), "TQ==");
+    }
+
+    #[test]
+    fn test_accept_key() {
+        // The example from RFC 6455
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn test_frame_header() {
+        assert_eq!(frame_header(5), vec![0x82, 5]);
+        assert_eq!(frame_header(300), vec![0x82, 126, 1, 44]);
+        assert_eq!(frame_header(70000)[..2], [0x82, 127]);
+        assert_eq!(frame_header(70000).len(), 10);
+    }
+
+    #[test]
+    fn test_binds_to_localhost_by_default() {
+        let server = LiveServer::bind(DEFAULT_HOST, 0);
+        let address = server.listener.local_addr().unwrap();
+        assert!(address.ip().is_loopback());
+    }
+
+    #[test]
+    fn test_silent_client_does_not_block() {
+        let mut server = LiveServer::bind(DEFAULT_HOST, 0);
+        let _client = TcpStream::connect(("127.0.0.1", server.port()))
+            .unwrap();
+
+        // The client never sends the handshake, so it stays pending
+        // without holding up the broadcasts
+        let start = Instant::now();
+        for _ in 0..10 {
+            server.broadcast(b"hi");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert!(start.elapsed() < CLIENT_TIMEOUT);
+        assert_eq!(server.pending.len(), 1);
+        assert!(server.clients.is_empty());
+    }
+
+    #[test]
+    fn test_broadcast_to_client() {
+        let mut server = LiveServer::bind(DEFAULT_HOST, 0);
+        let mut client = TcpStream::connect(("127.0.0.1", server.port()))
+            .unwrap();
+        client.write_all(b"GET / HTTP/1.1\r\n\
+            Host: localhost\r\n\
+            Upgrade: websocket\r\n\
+            Connection: Upgrade\r\n\
+            Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+            Sec-WebSocket-Version: 13\r\n\r\n").unwrap();
+
+        // The connection may take a moment to show up
+        for _ in 0..100 {
+            server.broadcast(b"hi");
+            if server.clients.len() == 1 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(server.clients.len(), 1);
+
+        client.set_read_timeout(Some(CLIENT_TIMEOUT)).unwrap();
+        let mut received = Vec::new();
+        let mut buffer = [0; 256];
+        while !received.ends_with(&[0x82, 2, b'h', b'i']) {
+            let length = client.read(&mut buffer).unwrap();
+            assert!(length > 0);
+            received.extend_from_slice(&buffer[..length]);
+        }
+        let text = String::from_utf8_lossy(&received);
+        assert!(text.starts_with("HTTP/1.1 101 Switching Protocols"));
+        assert!(text.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="));
+    }
+}
@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How much the binary prints about a run. Errors are panics, so they are
+/// printed at every level. Use the info!() and verbose!() macros rather
+/// than checking the level directly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    /// Errors only, from --quiet. This is for scripting many runs.
+    Quiet,
+    /// Complexity estimates, progress, warnings and a summary of each
+    /// phase. This is the default.
+    Normal,
+    /// Extra per-tile and per-phase detail, from --verbose
+    Verbose,
+}
+
+/// The level is global since messages are printed from deep inside the
+/// algorithms and writers, and it is only set once at startup.
+static LEVEL: AtomicU8 = AtomicU8::new(LogLevel::Normal as u8);
+
+pub fn set_level(level: LogLevel) {
+    LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn level() -> LogLevel {
+    match LEVEL.load(Ordering::Relaxed) {
+        0 => LogLevel::Quiet,
+        1 => LogLevel::Normal,
+        _ => LogLevel::Verbose,
+    }
+}
+
+/// Check if messages at the given level should be printed
+pub fn enabled(level: LogLevel) -> bool {
+    level <= self::level()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levels() {
+        assert_eq!(level(), LogLevel::Normal);
+        assert!(enabled(LogLevel::Quiet));
+        assert!(enabled(LogLevel::Normal));
+        assert!(!enabled(LogLevel::Verbose));
+
+        set_level(LogLevel::Verbose);
+        assert!(enabled(LogLevel::Verbose));
+        set_level(LogLevel::Quiet);
+        assert!(!enabled(LogLevel::Normal));
+        set_level(LogLevel::Normal);
+    }
+}
@@ -8,3 +8,26 @@ macro_rules! to_box {
         }
     }
 }
+
+/// info!(format, args...)
+/// println!() at the default log level. This is silenced by --quiet, see
+/// log::LogLevel.
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::LogLevel::Normal) {
+            println!($($arg)*);
+        }
+    }
+}
+
+/// verbose!(format, args...)
+/// println!() for extra detail that is only printed with --verbose
+#[macro_export]
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::LogLevel::Verbose) {
+            println!($($arg)*);
+        }
+    }
+}
@@ -1,58 +1,937 @@
-#[macro_use]
-extern crate json;
-extern crate rand;
-
-#[macro_use]
-mod macros;
-
-mod algorithms;
-mod bbox;
-mod choosers;
-mod clusters;
-mod fractal_metadata;
-mod glb_writer;
-mod ifs;
-mod implicit_coordinates;
-mod half_multivector;
-mod octrees;
-mod plotters;
-mod pnts_writer;
-mod point;
-mod tileset_writer;
-mod vector;
-mod xforms;
-
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use json::JsonValue;
+use chaos_game_3d::algorithms::{self, Algorithm};
+use chaos_game_3d::bench;
+use chaos_game_3d::leaf_histogram::LeafHistogram;
+use chaos_game_3d::compare::{compare_points, load_points};
+use chaos_game_3d::log::{self, LogLevel};
+use chaos_game_3d::{describe_xforms, echo_metadata};
+use chaos_game_3d::parameters;
+use chaos_game_3d::plotters::{MAX_MORTON_DEPTH, SCATTER_FORMATS};
+use chaos_game_3d::profiling::{product_op_count, Profile};
+use chaos_game_3d::quality::QualityMetric;
+use chaos_game_3d::random;
+use chaos_game_3d::tileset_merge::merge_tilesets;
+use chaos_game_3d::info;
 
-use json::{JsonValue, parse};
-use crate::algorithms::Algorithm;
+const USAGE: &str = "Usage: chaos-game-3d in_file [--profile] [--watch] \
+    [--seed-sweep N [--metric dimension|leaves]] [--format pnts|glb|csv] \
+    [--resume CHECKPOINT] [--count-only] [--max-depth D] [--node-capacity N] \
+    [--tween PATH START END --frames N] \
+    [--timeout SECONDS] [--bench] [--dry-run] [--echo-metadata] \
+    [--quiet|--verbose] [--points-per-tile-histogram]
+       chaos-game-3d merge-tilesets out_dir tileset_dir...
+       chaos-game-3d --compare a b [--epsilon E] [--mismatches N]";
 
-pub fn load_json_file(fname: &str) -> JsonValue {
-    let mut file = File::open(fname).expect("Could not open file");
-    let mut text = String::new();
-    file.read_to_string(&mut text).expect("Could not read file contents");
+/// How often to check the parameter file for changes in --watch mode
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(500);
+/// How many times to retry parsing a parameter file that may be only
+/// partially written
+const WATCH_PARSE_RETRIES: usize = 5;
+/// The deepest octree --max-depth allows. Past this, points are inserted
+/// without Morton codes, which is much slower
+const MAX_DEPTH_OVERRIDE: u8 = MAX_MORTON_DEPTH;
+/// How many points --bench transforms for each benchmark
+const BENCH_ITERATIONS: usize = 2_000_000;
+/// --compare pairs up points closer than this by default, which allows
+/// for rounding in the last bits of an f32
+const DEFAULT_COMPARE_EPSILON: f32 = 1e-5;
+/// How many unmatched points --compare lists by default
+const DEFAULT_COMPARE_MISMATCHES: usize = 10;
 
-    parse(&text).expect("Could not parse JSON")
+/// Plotter settings from the command line that replace the ones in the
+/// parameter file
+#[derive(Default)]
+struct PlotterOverrides {
+    /// Replaces plotter.format
+    format: Option<String>,
+    /// Replaces plotter.max_depth
+    max_depth: Option<u8>,
+    /// Replaces plotter.node_capacity
+    node_capacity: Option<u16>,
 }
 
-pub fn load_algorithm(fname: &str) -> Box<dyn Algorithm> {
-    let json = load_json_file(fname);
-    algorithms::from_json(&json)
+impl PlotterOverrides {
+    fn is_empty(&self) -> bool {
+        self.format.is_none() &&
+            self.max_depth.is_none() &&
+            self.node_capacity.is_none()
+    }
+
+    /// Replace the plotter settings in the parsed parameter file. These
+    /// only make sense for scatter plots
+    fn apply(&self, json: &mut JsonValue) {
+        if self.is_empty() {
+            return;
+        }
+        let plotter = &mut json["plotter"];
+        let plotter_type = plotter["type"].as_str().unwrap_or("scatter");
+        if plotter_type != "scatter" {
+            panic!(
+                "--format, --max-depth and --node-capacity only apply to \
+                scatter plotters");
+        }
+
+        if let Some(format) = &self.format {
+            plotter["format"] = format.as_str().into();
+        }
+        if let Some(max_depth) = self.max_depth {
+            plotter["max_depth"] = max_depth.into();
+        }
+        if let Some(node_capacity) = self.node_capacity {
+            plotter["node_capacity"] = node_capacity.into();
+        }
+    }
 }
 
-fn let_the_chaos_begin(in_fname: &str) {
-    let mut chaos = load_algorithm(in_fname);
-    println!("Estimated complexity: {} points", chaos.complexity());
+/// One step of a JsonPath
+#[derive(Debug, PartialEq)]
+enum PathStep {
+    /// Look up a key of an object
+    Key(String),
+    /// Look up an element of an array
+    Index(usize),
+}
+
+/// A path to a value inside the parameter file, e.g. `ifs.xforms[1][4]`.
+/// Object keys are separated by dots and array indices are written in
+/// square brackets. Keys cannot contain `.`, `[` or `]`.
+struct JsonPath {
+    /// The path as it was written on the command line, for error messages
+    text: String,
+    steps: Vec<PathStep>,
+}
+
+impl JsonPath {
+    fn parse(text: &str) -> Result<Self, String> {
+        let invalid = |reason: &str|
+            Err(format!("Invalid JSON path {:?}: {}", text, reason));
+
+        let mut steps = Vec::new();
+        let mut key = String::new();
+        // True at the start and after a dot, where a key must come next.
+        // False right after an index
+        let mut expect_key = true;
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    if expect_key && key.is_empty() {
+                        return invalid("empty key");
+                    }
+                    if !key.is_empty() {
+                        steps.push(PathStep::Key(std::mem::take(&mut key)));
+                    }
+                    expect_key = true;
+                },
+                '[' => {
+                    if expect_key && key.is_empty() && !steps.is_empty() {
+                        return invalid("empty key");
+                    }
+                    if !key.is_empty() {
+                        steps.push(PathStep::Key(std::mem::take(&mut key)));
+                    }
+                    let mut digits = String::new();
+                    let mut closed = false;
+                    for c in chars.by_ref() {
+                        if c == ']' {
+                            closed = true;
+                            break;
+                        }
+                        digits.push(c);
+                    }
+                    if !closed {
+                        return invalid("unterminated [");
+                    }
+                    let index = match digits.parse::<usize>() {
+                        Ok(index) => index,
+                        Err(_) => return invalid("indices must be integers")
+                    };
+                    steps.push(PathStep::Index(index));
+                    expect_key = false;
+                },
+                ']' => return invalid("unmatched ]"),
+                _ if !expect_key => return invalid("expected . or [ after ]"),
+                _ => key.push(c)
+            }
+        }
+        if expect_key && key.is_empty() {
+            return invalid("empty key");
+        }
+        if !key.is_empty() {
+            steps.push(PathStep::Key(key));
+        }
+
+        Ok(Self { text: text.to_string(), steps })
+    }
+
+    /// Replace the number at this path. The path must already exist and
+    /// hold a number, so a typo doesn't silently add an unused key.
+    fn set(&self, json: &mut JsonValue, value: f64) -> Result<(), String> {
+        let mut target = json;
+        for step in &self.steps {
+            target = match step {
+                PathStep::Key(key) if target.has_key(key) =>
+                    &mut target[key.as_str()],
+                PathStep::Index(index)
+                        if target.is_array() && *index < target.len() =>
+                    &mut target[*index],
+                _ => return Err(format!(
+                    "{} does not exist in the parameter file", self.text))
+            };
+        }
+        if !target.is_number() {
+            return Err(format!("{} is not a number", self.text));
+        }
+        *target = value.into();
+        Ok(())
+    }
+}
+
+/// Render a sequence of frames while sweeping one number in the parameter
+/// file, e.g. a rotation angle for a turntable animation
+struct Tween {
+    path: JsonPath,
+    start: f64,
+    end: f64,
+    /// Number of frames, at least 1
+    frames: usize,
+}
+
+impl Tween {
+    /// The value for a frame. The first frame uses start and the last
+    /// frame uses end
+    fn value(&self, frame: usize) -> f64 {
+        if self.frames == 1 {
+            return self.start;
+        }
+        let t = frame as f64 / (self.frames - 1) as f64;
+        self.start + t * (self.end - self.start)
+    }
+}
+
+/// Settings from the command line that replace the ones in the parameter
+/// file
+#[derive(Default)]
+struct Overrides {
+    plotter: PlotterOverrides,
+    /// Replaces timeout, the number of seconds to iterate for at most
+    timeout: Option<f64>,
+}
+
+impl Overrides {
+    fn apply(&self, json: &mut JsonValue) {
+        self.plotter.apply(json);
+        if let Some(timeout) = self.timeout {
+            json["timeout"] = timeout.into();
+        }
+    }
+}
+
+/// Extra information to print after a render
+#[derive(Clone, Copy, Default)]
+struct Reports {
+    /// Print a timing breakdown
+    profile: bool,
+    /// Print the distribution of points per octree leaf
+    histogram: bool,
+}
+
+/// Command line flags
+struct Options {
+    /// What to print after rendering
+    reports: Reports,
+    /// Re-render every time the parameter file changes
+    watch: bool,
+    /// Render this many times and only save the best run
+    seed_sweep: Option<usize>,
+    /// How to pick the best run for --seed-sweep
+    metric: QualityMetric,
+    /// Override the plotter's format, max_depth and node_capacity, and the
+    /// timeout
+    overrides: Overrides,
+    /// Continue from a checkpoint written with checkpoint_every
+    resume: Option<String>,
+    /// Iterate and report how many points were kept without saving
+    count_only: bool,
+    /// Render an animation by sweeping one value of the parameter file
+    tween: Option<Tween>,
+    /// Time the transformations instead of rendering
+    bench: bool,
+    /// Parse the parameter file and describe it without iterating
+    dry_run: bool,
+    /// Iterate, then print the metadata schemas and property ranges as
+    /// JSON instead of saving
+    echo_metadata: bool,
+    /// How much to print, from --quiet or --verbose
+    log_level: LogLevel,
+}
+
+impl Options {
+    /// Parse the flags that come after the input file name
+    fn parse(flags: &[String]) -> Self {
+        let mut options = Self {
+            reports: Reports::default(),
+            watch: false,
+            seed_sweep: None,
+            metric: QualityMetric::Dimension,
+            overrides: Overrides::default(),
+            resume: None,
+            count_only: false,
+            tween: None,
+            bench: false,
+            dry_run: false,
+            echo_metadata: false,
+            log_level: LogLevel::Normal,
+        };
+        let mut quiet = false;
+        let mut verbose = false;
+        let mut tween_range = None;
+        let mut frames = None;
+        let mut flags = flags.iter();
+        while let Some(flag) = flags.next() {
+            match &flag[..] {
+                "--profile" => options.reports.profile = true,
+                "--points-per-tile-histogram" =>
+                    options.reports.histogram = true,
+                "--count-only" => options.count_only = true,
+                "--watch" => options.watch = true,
+                "--bench" => options.bench = true,
+                "--dry-run" => options.dry_run = true,
+                "--echo-metadata" => options.echo_metadata = true,
+                "--quiet" => quiet = true,
+                "--verbose" => verbose = true,
+                "--seed-sweep" => {
+                    let runs = flags
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|runs| *runs > 0)
+                        .expect("--seed-sweep must be a positive integer");
+                    options.seed_sweep = Some(runs);
+                },
+                "--metric" => {
+                    let name = flags.next().expect("--metric needs a value");
+                    options.metric = QualityMetric::parse(name);
+                },
+                "--format" => {
+                    let format = flags.next().expect("--format needs a value");
+                    if !SCATTER_FORMATS.contains(&&format[..]) {
+                        panic!(
+                            "--format must be one of {:?}", SCATTER_FORMATS);
+                    }
+                    options.overrides.plotter.format = Some(format.clone());
+                },
+                "--max-depth" => {
+                    let max_depth = flags
+                        .next()
+                        .and_then(|value| value.parse::<u8>().ok())
+                        .filter(|depth| *depth <= MAX_DEPTH_OVERRIDE)
+                        .unwrap_or_else(|| panic!(
+                            "--max-depth must be an integer from 0 to {}",
+                            MAX_DEPTH_OVERRIDE));
+                    options.overrides.plotter.max_depth = Some(max_depth);
+                },
+                "--node-capacity" => {
+                    // The tileset metadata stores the capacity as a UINT16
+                    let node_capacity = flags
+                        .next()
+                        .and_then(|value| value.parse::<u16>().ok())
+                        .filter(|capacity| *capacity > 0)
+                        .unwrap_or_else(|| panic!(
+                            "--node-capacity must be an integer from 1 to {}",
+                            u16::MAX));
+                    options.overrides.plotter.node_capacity =
+                        Some(node_capacity);
+                },
+                "--timeout" => {
+                    let seconds = flags
+                        .next()
+                        .and_then(|value| value.parse::<f64>().ok())
+                        .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+                        .expect(
+                            "--timeout must be a positive number of seconds");
+                    options.overrides.timeout = Some(seconds);
+                },
+                "--resume" => {
+                    let fname = flags.next().expect("--resume needs a file");
+                    options.resume = Some(fname.clone());
+                },
+                "--tween" => {
+                    let path = flags.next().expect("--tween needs a path");
+                    let path = JsonPath::parse(path)
+                        .unwrap_or_else(|err| panic!("--tween: {}", err));
+                    let mut endpoint = || flags
+                        .next()
+                        .and_then(|value| value.parse::<f64>().ok())
+                        .expect("--tween needs a start and end number");
+                    let start = endpoint();
+                    let end = endpoint();
+                    tween_range = Some((path, start, end));
+                },
+                "--frames" => {
+                    let count = flags
+                        .next()
+                        .and_then(|value| value.parse::<usize>().ok())
+                        .filter(|count| *count > 0)
+                        .expect("--frames must be a positive integer");
+                    frames = Some(count);
+                },
+                _ => panic!("Unknown flag {}. {}", flag, USAGE)
+            }
+        }
+
+        options.log_level = match (quiet, verbose) {
+            (false, false) => LogLevel::Normal,
+            (true, false) => LogLevel::Quiet,
+            (false, true) => LogLevel::Verbose,
+            _ => panic!("--quiet and --verbose cannot be used together")
+        };
+
+        options.tween = match (tween_range, frames) {
+            (Some((path, start, end)), Some(frames)) =>
+                Some(Tween { path, start, end, frames }),
+            (None, None) => None,
+            _ => panic!("--tween and --frames must be used together")
+        };
+        if options.tween.is_some() && (
+                options.watch ||
+                options.seed_sweep.is_some() ||
+                options.resume.is_some() ||
+                options.count_only ||
+                options.echo_metadata) {
+            panic!(
+                "--tween cannot be used with --watch, --seed-sweep, \
+                --resume, --count-only or --echo-metadata");
+        }
+
+        if options.watch && options.seed_sweep.is_some() {
+            panic!("--watch and --seed-sweep cannot be used together");
+        }
+        if options.resume.is_some() && 
+                (options.watch || options.seed_sweep.is_some()) {
+            panic!("--resume cannot be used with --watch or --seed-sweep");
+        }
+        if options.count_only && 
+                (options.watch || options.seed_sweep.is_some()) {
+            panic!("--count-only cannot be used with --watch or --seed-sweep");
+        }
+        if options.echo_metadata && (
+                options.watch ||
+                options.seed_sweep.is_some() ||
+                options.resume.is_some() ||
+                options.count_only) {
+            panic!(
+                "--echo-metadata cannot be used with --watch, --seed-sweep, \
+                --resume or --count-only");
+        }
+        options
+    }
+}
+
+/// Print an error for the user and exit with an error code. This is for
+/// mistakes in the input like a missing parameter file, where a panic's
+/// backtrace would only get in the way.
+fn exit_with_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1);
+}
+
+/// Render the fractal once and save it. If resume is given, the iteration
+/// continues from that checkpoint
+fn let_the_chaos_begin(
+        in_fname: &str,
+        reports: Reports,
+        overrides: &Overrides,
+        resume: Option<&str>) {
+    // The overrides from the command line take precedence over the file,
+    // so one file can be rendered to several output formats or resolutions
+    let chaos =
+        parameters::load_algorithm(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    render(chaos, reports, resume);
+}
+
+/// Iterate and save an algorithm that was already loaded, see
+/// let_the_chaos_begin()
+fn render(
+        mut chaos: Box<dyn Algorithm>,
+        reports: Reports,
+        resume: Option<&str>) {
+    info!("Estimated complexity: {} points", chaos.complexity());
+    if let Some(checkpoint) = resume {
+        chaos.resume(checkpoint);
+    }
+    if reports.profile {
+        chaos.enable_profiling();
+    }
+
+    let iterate_start = Instant::now();
     chaos.iterate();
+    let iterate_time = iterate_start.elapsed();
+
+    let save_start = Instant::now();
     chaos.save();
+    let save_time = save_start.elapsed();
+
+    if reports.profile {
+        let mut summary = Profile {
+            iterate: iterate_time,
+            save: save_time,
+            ..Profile::default()
+        };
+        chaos.record_profile(&mut summary);
+        summary.print_summary();
+    }
+
+    // This is after save() so it counts the leaves that were written,
+    // after target_points_per_tile rebalanced them
+    if reports.histogram {
+        LeafHistogram::new(chaos.leaf_sizes()).print();
+    }
+
+    // Only with the op_counts feature
+    if let Some(ops) = product_op_count() {
+        println!("Geometric product multiply-adds: {}", ops);
+    }
+}
+
+/// Run the full iteration but skip decimation and writing, then report
+/// exactly how many points the plotter kept. This is the ground truth that
+/// complexity() only estimates. Checkpoints are not written either.
+fn count_only(
+        in_fname: &str, overrides: &Overrides, resume: Option<&str>) {
+    let mut params =
+        parameters::load_params(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    params.remove("checkpoint_every");
+    let mut chaos = algorithms::from_json(&params);
+    info!("Estimated complexity: {} points", chaos.complexity());
+    if let Some(checkpoint) = resume {
+        chaos.resume(checkpoint);
+    }
+
+    chaos.iterate();
+
+    let counts = chaos.plot_counts();
+    let percent_kept = if counts.total() > 0 {
+        100.0 * counts.accepted as f64 / counts.total() as f64
+    } else {
+        0.0
+    };
+    println!(
+        "Plotted {} points: {} kept ({:.2}%), {} discarded",
+        counts.total(), counts.accepted, percent_kept, counts.discarded);
+}
+
+/// Run the full iteration without saving, then print what the tileset
+/// would contain as JSON: the metadata schemas and the range of each
+/// per-point property. Progress reports and checkpoints are turned off so
+/// the output can be piped to other tools.
+fn print_metadata(in_fname: &str, overrides: &Overrides) {
+    let mut params =
+        parameters::load_params(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    params.remove("checkpoint_every");
+    params["progress_interval"] = 0.into();
+    println!("{}", json::stringify_pretty(echo_metadata(&params), 4));
+}
+
+/// Render the fractal with several seeds and save the one that scores
+/// highest on the quality metric. Run k uses seed base + k, where base is
+/// the parameter file's seed or a random one. Only the best score and its
+/// seed are kept, then the winner is rendered again from its seed and
+/// saved. The winning seed is printed so it can go in the parameter file.
+fn seed_sweep(
+        in_fname: &str,
+        runs: usize,
+        metric: QualityMetric,
+        overrides: &Overrides) {
+    let mut params =
+        parameters::load_params(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    let base_seed = params["seed"]
+        .as_u64()
+        .unwrap_or_else(random::random_seed);
+
+    let mut best: Option<(f64, u64)> = None;
+    for run in 0..runs {
+        let seed = base_seed.wrapping_add(run as u64);
+        params["seed"] = seed.into();
+        let mut chaos = algorithms::from_json(&params);
+        if run == 0 {
+            info!("Estimated complexity: {} points", chaos.complexity());
+        }
+        chaos.iterate();
+
+        let score = chaos.quality(metric);
+        info!(
+            "Run {}/{}: seed {}, {:?} = {:.4}",
+            run + 1, runs, seed, metric, score);
+        let is_better = match best {
+            Some((best_score, _)) => score > best_score,
+            None => true
+        };
+        if is_better {
+            best = Some((score, seed));
+        }
+    }
+
+    // runs is always positive, so there is a best run
+    let (score, seed) = best.unwrap();
+    info!("Saving the best run, {:?} = {:.4}", metric, score);
+    params["seed"] = seed.into();
+    let mut chaos = algorithms::from_json(&params);
+    chaos.iterate();
+    chaos.save();
+    println!("Best seed: {}", seed);
+}
+
+/// Render one tileset per frame of the tween, each with the tweened value
+/// substituted into a fresh copy of the parameter file. Frame k is written
+/// to viewer/<fractal_id>/frame_000k
+fn render_tween(
+        in_fname: &str,
+        tween: &Tween,
+        reports: Reports,
+        overrides: &Overrides) {
+    let base_params =
+        parameters::load_params(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    let id = base_params["id"]
+        .as_str()
+        .expect("id must be a string")
+        .to_string();
+    for frame in 0..tween.frames {
+        let mut params = base_params.clone();
+        let value = tween.value(frame);
+        tween.path.set(&mut params, value)
+            .unwrap_or_else(|err| panic!("--tween: {}", err));
+        params["id"] = format!("{}/frame_{:04}", id, frame).into();
+
+        info!(
+            "Frame {}/{}: {} = {}",
+            frame + 1, tween.frames, tween.path.text, value);
+        render(algorithms::from_json(&params), reports, None);
+    }
+}
+
+/// Get the last modified time of a file, or None if it cannot be read
+/// (e.g. an editor deleted it temporarily while saving)
+fn get_modified_time(fname: &str) -> Option<SystemTime> {
+    fs::metadata(fname).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Block until the file's modified time differs from last_modified, then
+/// wait until it stops changing so a burst of writes only triggers one
+/// render. Returns the new modified time.
+fn wait_for_change(fname: &str, last_modified: Option<SystemTime>)
+        -> Option<SystemTime> {
+    loop {
+        thread::sleep(WATCH_POLL_INTERVAL);
+        let modified = get_modified_time(fname);
+        if modified.is_none() || modified == last_modified {
+            continue;
+        }
+
+        // Debounce
+        let mut current = modified;
+        loop {
+            thread::sleep(WATCH_POLL_INTERVAL);
+            let next = get_modified_time(fname);
+            if next == current {
+                return current;
+            }
+            current = next;
+        }
+    }
+}
+
+/// Render the fractal once for --watch mode. Since the user is editing the
+/// parameters live, errors are reported but don't end the program. A file
+/// that doesn't parse may be in the middle of being written, so it is
+/// retried a few times first.
+fn render_watched(
+        in_fname: &str, reports: Reports, overrides: &Overrides) {
+    let mut params = parameters::load_json_file(in_fname);
+    for _ in 0..WATCH_PARSE_RETRIES {
+        if params.is_ok() {
+            break;
+        }
+        thread::sleep(WATCH_POLL_INTERVAL);
+        params = parameters::load_json_file(in_fname);
+    }
+
+    let mut params = match params {
+        Ok(params) => params,
+        Err(err) => {
+            println!("{}", err);
+            info!("Waiting for the next change");
+            return;
+        }
+    };
+    overrides.apply(&mut params);
+
+    let result = panic::catch_unwind(
+        || render(algorithms::from_json(&params), reports, None));
+    if result.is_err() {
+        println!("Rendering failed, waiting for the next change");
+    }
+}
+
+/// Load the parameter file to check it, then print the complexity estimate
+/// and each transformation of the IFS with its resolved versor. Nothing is
+/// iterated or written.
+fn dry_run(in_fname: &str, overrides: &Overrides) {
+    let params =
+        parameters::load_params(in_fname, |json| overrides.apply(json))
+            .unwrap_or_else(|err| exit_with_error(&err));
+    let chaos = algorithms::from_json(&params);
+    println!("Estimated complexity: {} points", chaos.complexity());
+    println!("Transformations:");
+    for (i, description) in describe_xforms(&params).iter().enumerate() {
+        println!("{}: {}", i, description);
+    }
+}
+
+/// Time the primitive transformations and the parameter file's IFS
+/// without rendering anything
+fn run_benchmarks(in_fname: &str) {
+    let params = parameters::load_json_file(in_fname)
+        .unwrap_or_else(|err| exit_with_error(&err));
+    let results = bench::run(&params["ifs"], BENCH_ITERATIONS);
+    bench::print_results(&results);
+}
+
+/// Render the fractal, then re-render it every time the parameter file
+/// changes on disk. The tileset is overwritten each time.
+fn watch(in_fname: &str, reports: Reports, overrides: &Overrides) {
+    let mut last_modified = get_modified_time(in_fname);
+    loop {
+        render_watched(in_fname, reports, overrides);
+        info!("Watching {} for changes...", in_fname);
+        last_modified = wait_for_change(in_fname, last_modified);
+    }
+}
+
+/// Check that two point clouds match, each either a tileset directory or a
+/// parameter file to render. This exits with an error code if they
+/// differ, so it can be used in scripts.
+fn compare(path_a: &str, path_b: &str, flags: &[String]) {
+    let mut epsilon = DEFAULT_COMPARE_EPSILON;
+    let mut max_reported = DEFAULT_COMPARE_MISMATCHES;
+    let mut flags = flags.iter();
+    while let Some(flag) = flags.next() {
+        match &flag[..] {
+            "--epsilon" => {
+                epsilon = flags
+                    .next()
+                    .and_then(|value| value.parse::<f32>().ok())
+                    .filter(|epsilon| *epsilon > 0.0)
+                    .expect("--epsilon must be a positive number");
+            },
+            "--mismatches" => {
+                max_reported = flags
+                    .next()
+                    .and_then(|value| value.parse::<usize>().ok())
+                    .expect("--mismatches must be a non-negative integer");
+            },
+            _ => panic!("{}", USAGE)
+        }
+    }
+
+    let comparison = compare_points(
+        load_points(path_a), load_points(path_b), epsilon, max_reported);
+    comparison.print_report();
+    if !comparison.is_match() {
+        std::process::exit(1);
+    }
 }
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     match args.as_slice() {
-        [_, in_file] => let_the_chaos_begin(in_file),
-        _ => panic!("Usage: chaos-game-3d in_file")
+        [_, command, out_dir, child_dirs @ ..] if command == "merge-tilesets" =>
+            merge_tilesets(out_dir, child_dirs),
+        [_, flag, path_a, path_b, flags @ ..] if flag == "--compare" =>
+            compare(path_a, path_b, flags),
+        [_, in_file, flags @ ..] => {
+            let options = Options::parse(flags);
+            log::set_level(options.log_level);
+            let overrides = &options.overrides;
+            let resume = options.resume.as_deref();
+            if options.bench {
+                run_benchmarks(in_file);
+            } else if options.dry_run {
+                dry_run(in_file, overrides);
+            } else if options.watch {
+                watch(in_file, options.reports, overrides);
+            } else if let Some(runs) = options.seed_sweep {
+                seed_sweep(in_file, runs, options.metric, overrides);
+            } else if let Some(tween) = &options.tween {
+                render_tween(in_file, tween, options.reports, overrides);
+            } else if options.count_only {
+                count_only(in_file, overrides, resume);
+            } else if options.echo_metadata {
+                print_metadata(in_file, overrides);
+            } else {
+                let_the_chaos_begin(
+                    in_file, options.reports, overrides, resume);
+            }
+        },
+        _ => panic!("{}", USAGE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plotter_overrides() {
+        let mut json = json::object!{
+            "plotter" => json::object!{
+                "radius" => 1.0,
+                "max_depth" => 10,
+                "node_capacity" => 5000
+            }
+        };
+        let overrides = PlotterOverrides {
+            max_depth: Some(6),
+            node_capacity: Some(200),
+            ..PlotterOverrides::default()
+        };
+        overrides.apply(&mut json);
+        assert_eq!(json["plotter"]["max_depth"], 6);
+        assert_eq!(json["plotter"]["node_capacity"], 200);
+        assert!(json["plotter"]["format"].is_null());
+
+        let options = Options::parse(&[
+            "--max-depth".to_string(), "4".to_string(),
+            "--format".to_string(), "pnts".to_string()
+        ]);
+        assert_eq!(options.overrides.plotter.max_depth, Some(4));
+        assert_eq!(options.overrides.plotter.format.as_deref(), Some("pnts"));
+
+        let options =
+            Options::parse(&["--timeout".to_string(), "2.5".to_string()]);
+        let mut json = json::object!{ "plotter" => json::object!{} };
+        options.overrides.apply(&mut json);
+        assert_eq!(json["timeout"], 2.5);
+    }
+
+    #[test]
+    fn test_format_override() {
+        let mut json = json::object!{
+            "plotter" => json::object!{ "radius" => 1.0, "format" => "glb" }
+        };
+        let options =
+            Options::parse(&["--format".to_string(), "csv".to_string()]);
+        options.overrides.apply(&mut json);
+        assert_eq!(json["plotter"]["format"], "csv");
+        assert_eq!(json["plotter"]["radius"], 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "--format must be one of")]
+    fn test_format_override_unsupported() {
+        Options::parse(&["--format".to_string(), "ply".to_string()]);
+    }
+
+    #[test]
+    #[should_panic(expected = "only apply to scatter plotters")]
+    fn test_format_override_needs_scatter() {
+        let mut json = json::object!{
+            "plotter" => json::object!{ "type" => "voxel" }
+        };
+        let overrides = PlotterOverrides {
+            format: Some("pnts".to_string()),
+            ..PlotterOverrides::default()
+        };
+        overrides.apply(&mut json);
+    }
+
+    #[test]
+    fn test_json_path() {
+        let path = JsonPath::parse("ifs.xforms[1][4]").unwrap();
+        assert_eq!(path.steps, [
+            PathStep::Key("ifs".to_string()),
+            PathStep::Key("xforms".to_string()),
+            PathStep::Index(1),
+            PathStep::Index(4)
+        ]);
+        let path = JsonPath::parse("[0].a").unwrap();
+        assert_eq!(path.steps, [
+            PathStep::Index(0),
+            PathStep::Key("a".to_string())
+        ]);
+
+        for invalid in &["", "a.", ".a", "a..b", "a.[0]", "a[x]", "a[0]b", "a]"] {
+            assert!(JsonPath::parse(invalid).is_err(), "{:?}", invalid);
+        }
+
+        for unterminated in &["a[0", "a[", "a[1][2"] {
+            match JsonPath::parse(unterminated) {
+                Err(error) => assert!(error.contains("unterminated [")),
+                Ok(_) => panic!("{:?} should be invalid", unterminated)
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_path_set() {
+        let mut json = json::object!{
+            "ifs" => json::object!{
+                "xforms" => json::array![
+                    json::array!["scale", 0.5],
+                    json::array!["rotate", "x", 0.0]
+                ]
+            }
+        };
+        JsonPath::parse("ifs.xforms[1][2]")
+            .unwrap()
+            .set(&mut json, 90.0)
+            .unwrap();
+        assert_eq!(json["ifs"]["xforms"][1][2], 90.0);
+
+        // Missing values and non-numbers are not replaced
+        for path in &["ifs.xforms[2][0]", "ifs.xform[0]", "ifs.xforms[1][1]"] {
+            let path = JsonPath::parse(path).unwrap();
+            assert!(path.set(&mut json, 1.0).is_err(), "{}", path.text);
+        }
+        assert_eq!(json["ifs"]["xforms"][1][1], "x");
+        assert!(!json["ifs"].has_key("xform"));
+    }
+
+    #[test]
+    fn test_tween_value() {
+        let options = Options::parse(&[
+            "--tween".to_string(), "ifs.xforms[1][4]".to_string(),
+            "0".to_string(), "360".to_string(),
+            "--frames".to_string(), "5".to_string()
+        ]);
+        let tween = options.tween.unwrap();
+        assert_eq!(tween.value(0), 0.0);
+        assert_eq!(tween.value(1), 90.0);
+        assert_eq!(tween.value(4), 360.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "--tween and --frames must be used together")]
+    fn test_tween_needs_frames() {
+        Options::parse(&[
+            "--tween".to_string(), "a".to_string(),
+            "0".to_string(), "1".to_string()
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "--max-depth must be an integer from 0 to 21")]
+    fn test_max_depth_override_too_deep() {
+        Options::parse(&["--max-depth".to_string(), "30".to_string()]);
     }
 }
@@ -0,0 +1,424 @@
+use std::collections::HashMap;
+
+use crate::vector::Vec3;
+
+/// How many neighbors (including the point itself) to use when estimating
+/// each normal
+pub const NORMAL_NEIGHBORS: usize = 16;
+/// Fewer points than this can't define a plane, so the default normal is
+/// used instead
+const MIN_POINTS: usize = 3;
+/// Normal used when there isn't enough information to estimate one. The
+/// tiles are z-up, so this points up.
+const DEFAULT_NORMAL: [f64; 3] = [0.0, 0.0, 1.0];
+/// If the covariance matrix is smaller than this, the neighborhood is a
+/// single point
+const COVARIANCE_EPSILON: f64 = 1e-20;
+/// Maximum number of Jacobi sweeps. 3x3 matrices converge in a handful.
+const MAX_SWEEPS: usize = 32;
+
+type Matrix3 = [[f64; 3]; 3];
+
+/// Estimate a normal for each point from its neighborhood. For each point,
+/// the k nearest points in the list are found, and the normal is the
+/// direction of least variance (principal component analysis). This is
+/// the eigenvector of the covariance matrix with the smallest eigenvalue.
+///
+/// The sign of an eigenvector is arbitrary, so normals are flipped to point
+/// away from the origin (the center of the fractal).
+///
+/// The nearest neighbors are found with a grid, see NeighborGrid.
+pub fn estimate_normals(positions: &[Vec3], k: usize) -> Vec<Vec3> {
+    if positions.len() < MIN_POINTS {
+        return positions.iter().map(|_| to_vec3(&DEFAULT_NORMAL)).collect();
+    }
+
+    let points: Vec<[f64; 3]> = positions.iter().map(to_array).collect();
+    let neighbor_count = k.min(points.len());
+    let grid = NeighborGrid::new(&points, neighbor_count);
+
+    let mut distances: Vec<(f64, usize)> = Vec::new();
+    points.iter().map(|point| {
+        grid.find_nearest(point, neighbor_count, &mut distances);
+        let neighbors: Vec<[f64; 3]> = distances
+            .iter()
+            .map(|(_, i)| points[*i])
+            .collect();
+        let normal = estimate_normal(&neighbors);
+        to_vec3(&orient_outward(&normal, point))
+    }).collect()
+}
+
+/// The points bucketed into a grid of cubes, so the nearest neighbors of a
+/// point are found by searching the cells around it rather than every
+/// other point.
+struct NeighborGrid<'a> {
+    points: &'a [[f64; 3]],
+    /// Side length of each cell
+    cell_size: f64,
+    /// How many cells the points span along the longest axis. No search
+    /// needs to go farther than this
+    span: i64,
+    /// The indices of the points in each nonempty cell
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+}
+
+impl<'a> NeighborGrid<'a> {
+    /// Size the cells so each one would hold about k points if the points
+    /// were spread evenly through their bounding box
+    fn new(points: &'a [[f64; 3]], k: usize) -> Self {
+        let mut min = points[0];
+        let mut max = points[0];
+        for point in points {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(point[axis]);
+                max[axis] = max[axis].max(point[axis]);
+            }
+        }
+        let extent = (0..3)
+            .map(|axis| max[axis] - min[axis])
+            .fold(0.0, f64::max);
+        let cells_per_side = (points.len() as f64 / k.max(1) as f64)
+            .cbrt()
+            .ceil()
+            .max(1.0);
+        let cell_size = if extent > 0.0 {
+            extent / cells_per_side
+        } else {
+            1.0
+        };
+
+        let mut grid = Self {
+            points,
+            cell_size,
+            span: cells_per_side as i64 + 1,
+            cells: HashMap::new(),
+        };
+        for (i, point) in points.iter().enumerate() {
+            let cell = grid.cell_of(point);
+            grid.cells.entry(cell).or_default().push(i);
+        }
+        grid
+    }
+
+    fn cell_of(&self, point: &[f64; 3]) -> (i64, i64, i64) {
+        (
+            (point[0] / self.cell_size).floor() as i64,
+            (point[1] / self.cell_size).floor() as i64,
+            (point[2] / self.cell_size).floor() as i64
+        )
+    }
+
+    /// Find the k points nearest the given point, including itself, as
+    /// (squared distance, index) pairs in no particular order. The cells
+    /// are searched in shells around the point's cell. After shell s,
+    /// every point that wasn't visited is more than s cells away, so the
+    /// search stops once the k nearest so far are all closer than that.
+    fn find_nearest(
+            &self, point: &[f64; 3], k: usize, found: &mut Vec<(f64, usize)>) {
+        found.clear();
+        let (cx, cy, cz) = self.cell_of(point);
+        for shell in 0..=self.span {
+            let mut visit = |dx: i64, dy: i64, dz: i64| {
+                let cell = (cx + dx, cy + dy, cz + dz);
+                if let Some(indices) = self.cells.get(&cell) {
+                    found.extend(indices.iter().map(|&i| {
+                        (distance_sqr(point, &self.points[i]), i)
+                    }));
+                }
+            };
+            for dx in -shell..=shell {
+                for dy in -shell..=shell {
+                    if dx.abs() == shell || dy.abs() == shell {
+                        for dz in -shell..=shell {
+                            visit(dx, dy, dz);
+                        }
+                    } else {
+                        visit(dx, dy, -shell);
+                        if shell > 0 {
+                            visit(dx, dy, shell);
+                        }
+                    }
+                }
+            }
+
+            if found.len() >= k {
+                found.select_nth_unstable_by(k - 1, |a, b| {
+                    a.0.partial_cmp(&b.0).unwrap()
+                });
+                found.truncate(k);
+                let covered = shell as f64 * self.cell_size;
+                if found[k - 1].0 <= covered * covered {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Estimate the normal of a single neighborhood of points
+fn estimate_normal(neighbors: &[[f64; 3]]) -> [f64; 3] {
+    if neighbors.len() < MIN_POINTS {
+        return DEFAULT_NORMAL;
+    }
+
+    let covariance = compute_covariance(neighbors);
+    let trace = covariance[0][0] + covariance[1][1] + covariance[2][2];
+    if trace < COVARIANCE_EPSILON {
+        return DEFAULT_NORMAL;
+    }
+
+    let (eigenvalues, eigenvectors) = symmetric_eigen(&covariance);
+    let mut smallest = 0;
+    for i in 1..3 {
+        if eigenvalues[i] < eigenvalues[smallest] {
+            smallest = i;
+        }
+    }
+
+    // eigenvectors are the columns
+    normalize(&[
+        eigenvectors[0][smallest],
+        eigenvectors[1][smallest],
+        eigenvectors[2][smallest]
+    ])
+}
+
+/// Compute the 3x3 covariance matrix of a set of points
+fn compute_covariance(points: &[[f64; 3]]) -> Matrix3 {
+    let n = points.len() as f64;
+    let mut mean = [0.0; 3];
+    for point in points {
+        for (sum, component) in mean.iter_mut().zip(point.iter()) {
+            *sum += component / n;
+        }
+    }
+
+    let mut covariance = [[0.0; 3]; 3];
+    for point in points {
+        let centered = [
+            point[0] - mean[0],
+            point[1] - mean[1],
+            point[2] - mean[2]
+        ];
+        for (row, a) in covariance.iter_mut().zip(centered.iter()) {
+            for (entry, b) in row.iter_mut().zip(centered.iter()) {
+                *entry += a * b / n;
+            }
+        }
+    }
+    covariance
+}
+
+/// Compute the eigenvalues and eigenvectors of a symmetric 3x3 matrix using
+/// the Jacobi eigenvalue algorithm. Each step applies a rotation that zeroes
+/// out one off-diagonal entry. The eigenvectors are returned as the columns
+/// of the matrix.
+fn symmetric_eigen(matrix: &Matrix3) -> ([f64; 3], Matrix3) {
+    let mut a = *matrix;
+    let mut v = [
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.0, 0.0, 1.0]
+    ];
+
+    for _ in 0..MAX_SWEEPS {
+        let off_diagonal = a[0][1].abs() + a[0][2].abs() + a[1][2].abs();
+        if off_diagonal < f64::EPSILON * trace_abs(&a) {
+            break;
+        }
+
+        for &(p, q) in [(0, 1), (0, 2), (1, 2)].iter() {
+            if a[p][q] == 0.0 {
+                continue;
+            }
+
+            // Choose the rotation angle that zeroes a[p][q]
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+
+            // A = J^T A J
+            for row in a.iter_mut() {
+                let akp = row[p];
+                let akq = row[q];
+                row[p] = c * akp - s * akq;
+                row[q] = s * akp + c * akq;
+            }
+            let row_p = a[p];
+            let row_q = a[q];
+            for (k, (apk, aqk)) in row_p.iter().zip(row_q.iter()).enumerate() {
+                a[p][k] = c * apk - s * aqk;
+                a[q][k] = s * apk + c * aqk;
+            }
+
+            // V = V J
+            for row in v.iter_mut() {
+                let vp = row[p];
+                let vq = row[q];
+                row[p] = c * vp - s * vq;
+                row[q] = s * vp + c * vq;
+            }
+        }
+    }
+
+    ([a[0][0], a[1][1], a[2][2]], v)
+}
+
+fn trace_abs(matrix: &Matrix3) -> f64 {
+    matrix[0][0].abs() + matrix[1][1].abs() + matrix[2][2].abs()
+}
+
+/// Flip the normal if needed so it points away from the origin
+fn orient_outward(normal: &[f64; 3], position: &[f64; 3]) -> [f64; 3] {
+    let dot =
+        normal[0] * position[0] +
+        normal[1] * position[1] +
+        normal[2] * position[2];
+    if dot < 0.0 {
+        [-normal[0], -normal[1], -normal[2]]
+    } else {
+        *normal
+    }
+}
+
+fn distance_sqr(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    let dx = a[0] - b[0];
+    let dy = a[1] - b[1];
+    let dz = a[2] - b[2];
+    dx * dx + dy * dy + dz * dz
+}
+
+fn normalize(vec: &[f64; 3]) -> [f64; 3] {
+    let length = (vec[0] * vec[0] + vec[1] * vec[1] + vec[2] * vec[2]).sqrt();
+    [vec[0] / length, vec[1] / length, vec[2] / length]
+}
+
+fn to_array(vec: &Vec3) -> [f64; 3] {
+    [*vec.x() as f64, *vec.y() as f64, *vec.z() as f64]
+}
+
+fn to_vec3(vec: &[f64; 3]) -> Vec3 {
+    Vec3::new(vec[0] as f32, vec[1] as f32, vec[2] as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_vec_eq(actual: &Vec3, expected: &Vec3) {
+        let difference = (*actual - *expected).length();
+        assert!(difference < 1e-4, "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_plane_normals() {
+        // A grid in the plane x = 2, so the normals should be +x
+        let mut positions = Vec::new();
+        for i in 0..5 {
+            for j in 0..5 {
+                positions.push(Vec3::new(2.0, i as f32 * 0.1, j as f32 * 0.1));
+            }
+        }
+
+        let normals = estimate_normals(&positions, NORMAL_NEIGHBORS);
+        for normal in normals.iter() {
+            assert_vec_eq(normal, &Vec3::new(1.0, 0.0, 0.0));
+        }
+    }
+
+    #[test]
+    fn test_tilted_plane_points_outward() {
+        // Points in the plane x + y + z = -3, far from the origin on
+        // the negative side
+        let mut positions = Vec::new();
+        for i in 0..4 {
+            for j in 0..4 {
+                let u = i as f32 * 0.2;
+                let v = j as f32 * 0.2;
+                positions.push(Vec3::new(u - 1.0, v - 1.0, -1.0 - u - v));
+            }
+        }
+
+        let normals = estimate_normals(&positions, 8);
+        let expected = Vec3::new(-1.0, -1.0, -1.0).normalize();
+        for normal in normals.iter() {
+            assert_vec_eq(normal, &expected);
+        }
+    }
+
+    #[test]
+    fn test_too_few_points_default_up() {
+        let positions = vec![Vec3::new(1.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0)];
+        let normals = estimate_normals(&positions, NORMAL_NEIGHBORS);
+        assert_eq!(normals.len(), 2);
+        for normal in normals.iter() {
+            assert_vec_eq(normal, &Vec3::new(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_coincident_points_default_up() {
+        let positions = vec![Vec3::new(0.5, 0.5, 0.5); 10];
+        let normals = estimate_normals(&positions, NORMAL_NEIGHBORS);
+        for normal in normals.iter() {
+            assert_vec_eq(normal, &Vec3::new(0.0, 0.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn test_grid_finds_nearest() {
+        // Points along a noisy helix, which leaves most cells empty
+        let points: Vec<[f64; 3]> = (0..500).map(|i| {
+            let t = i as f64 * 0.05;
+            let noise = ((i * 7919) % 101) as f64 * 1e-3;
+            [t.cos() + noise, t.sin(), 0.1 * t - noise]
+        }).collect();
+        let k = NORMAL_NEIGHBORS;
+        let grid = NeighborGrid::new(&points, k);
+
+        let mut found = Vec::new();
+        for point in points.iter() {
+            grid.find_nearest(point, k, &mut found);
+            let mut nearest: Vec<usize> =
+                found.iter().map(|(_, i)| *i).collect();
+            nearest.sort_unstable();
+
+            let mut brute_force: Vec<(f64, usize)> = points
+                .iter()
+                .enumerate()
+                .map(|(i, other)| (distance_sqr(point, other), i))
+                .collect();
+            brute_force.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+            let mut expected: Vec<usize> =
+                brute_force[..k].iter().map(|(_, i)| *i).collect();
+            expected.sort_unstable();
+            assert_eq!(nearest, expected);
+        }
+    }
+
+    #[test]
+    fn test_symmetric_eigen() {
+        let matrix = [
+            [2.0, 1.0, 0.0],
+            [1.0, 2.0, 0.0],
+            [0.0, 0.0, 5.0]
+        ];
+        let (values, vectors) = symmetric_eigen(&matrix);
+
+        // Check A v = lambda v for each eigenpair
+        for i in 0..3 {
+            for row in 0..3 {
+                let av: f64 = (0..3).map(|k| matrix[row][k] * vectors[k][i]).sum();
+                assert!((av - values[i] * vectors[row][i]).abs() < 1e-9);
+            }
+        }
+
+        let mut sorted = values;
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!((sorted[0] - 1.0).abs() < 1e-9);
+        assert!((sorted[1] - 3.0).abs() < 1e-9);
+        assert!((sorted[2] - 5.0).abs() < 1e-9);
+    }
+}
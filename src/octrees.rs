@@ -1,47 +1,119 @@
+use std::collections::HashMap;
+
 use json::JsonValue;
 
-use crate::bbox::BBox;
+use crate::bbox::{BBox, MAX_MORTON_DEPTH};
 use crate::implicit_coordinates::ImplicitCoordinates;
-use crate::point::OutputPoint;
-use crate::vector::Vec3;
+use crate::point::{OutputPoint, TreePoint};
+use crate::vector::{ColorSpace, Vec3};
+
 
+/// Where the tile content files go in the tileset directory
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ContentLayout {
+    /// points/{level}/{x}/{y}/{z}.{extension}, a directory tree that
+    /// mirrors the octree. This is the default.
+    Nested,
+    /// content/{level}_{x}_{y}_{z}.{extension}, one directory for every
+    /// tile. This is for static hosts that handle deep directory trees
+    /// poorly.
+    Flat,
+}
 
-/// Octree node
-pub struct OctNode {
+impl ContentLayout {
+    /// Parse "nested" or "flat" from the plotter JSON (default "nested")
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str() {
+            None | Some("nested") => Self::Nested,
+            Some("flat") => Self::Flat,
+            Some(_) => panic!("content_layout must be \"nested\" or \"flat\"")
+        }
+    }
+
+    /// The directory for the content files relative to the tileset
+    pub fn dirname(&self) -> &'static str {
+        match self {
+            Self::Nested => "points",
+            Self::Flat => "content",
+        }
+    }
+}
+
+/// Octree node. The points can be stored in any TreePoint layout, by
+/// default the full OutputPoint.
+pub struct OctNode<P: TreePoint = OutputPoint> {
     /// Implicit coordinates for this tile.
     implicit_coordinates: ImplicitCoordinates,
     /// 8 children. This is always either completely empty or completely full 
-    children: Vec<OctNode>,
+    children: Vec<OctNode<P>>,
     /// Bounding box for this node
     bounds: BBox,
     /// Store points in this node. They are stored as Vec3 to be more compact
     /// and because this matches the 3D Tiles spec
-    points: Vec<OutputPoint>,
+    points: Vec<P>,
     /// How many points can fit in this node
     capacity: usize,
     /// How many fits currently are in this node
     count: usize,
-    /// Total color. This can be used along with count to compute the average
-    /// color
-    color_sum: Vec3,
 }
 
-impl OctNode {
+/// Running total of colors. A deduplicated point may stand for millions of
+/// points, which loses precision (and eventually stops changing) in f32,
+/// so the sum is kept in f64 and only converted back when computing the
+/// average.
+#[derive(Clone, Copy, Default)]
+struct ColorSum {
+    /// Sum of the red, green and blue components
+    sum: [f64; 3],
+}
+
+impl ColorSum {
+    /// Add a color that stands for weight points
+    fn add_weighted(&mut self, color: &Vec3, weight: u32) {
+        let weight = weight as f64;
+        self.sum[0] += *color.x() as f64 * weight;
+        self.sum[1] += *color.y() as f64 * weight;
+        self.sum[2] += *color.z() as f64 * weight;
+    }
+
+    /// Divide by the number of colors to get the average color. If there
+    /// are no colors, black is returned.
+    fn average(&self, count: usize) -> Vec3 {
+        if count == 0 {
+            return Vec3::zero();
+        }
+
+        let n = count as f64;
+        Vec3::new(
+            (self.sum[0] / n) as f32,
+            (self.sum[1] / n) as f32,
+            (self.sum[2] / n) as f32)
+    }
+}
+
+impl<P: TreePoint> OctNode<P> {
     /// Create an empty root node surrounding the origin. The half-width "radius"
     /// of the box must be specified since all other node bounding boxes are
     /// derived from this node.
     pub fn root_node(radius: f32, capacity: usize, subtree_levels: usize) -> Self {
+        let bounds = BBox::new(
+            -radius, radius, 
+            -radius, radius, 
+            -radius, radius);
+        Self::root_with_bounds(bounds, capacity, subtree_levels)
+    }
+
+    /// Create an empty root node with arbitrary bounds. This is used to
+    /// restore a root that grew away from the origin
+    pub fn root_with_bounds(bounds: BBox, capacity: usize, subtree_levels: usize)
+            -> Self {
         Self {
             implicit_coordinates: ImplicitCoordinates::root(subtree_levels),
             children: Vec::new(),
-            bounds: BBox::new(
-                -radius, radius, 
-                -radius, radius, 
-                -radius, radius),
+            bounds,
             points: Vec::new(),
             capacity,
             count: 0,
-            color_sum: Vec3::zero(),
         }
     }
 
@@ -57,7 +129,6 @@ impl OctNode {
             points: Vec::new(),
             capacity,
             count: 0,
-            color_sum: Vec3::zero(),
         }
     }
 
@@ -82,29 +153,131 @@ impl OctNode {
         self.bounds.diagonal_len()
     }
 
+    /// Get the center of this node's bounding box
+    pub fn center(&self) -> Vec3 {
+        self.bounds.center()
+    }
+
+    /// Get this node's bounding box
+    pub fn bounds(&self) -> &BBox {
+        &self.bounds
+    }
+
     /// Format the bounding box in JSON format to match the 3D tiles spec
     pub fn bounding_volume_json(&self) -> JsonValue {
         self.bounds.to_json()
     }
 
-    pub fn get_children(&self) -> &Vec<OctNode> {
+    /// The smallest box that contains every point in this subtree, or None
+    /// if the subtree is empty. This is often much smaller than bounds()
+    /// when the points only fill a corner of the octant.
+    pub fn tight_bounds(&self) -> Option<BBox> {
+        let own = BBox::tight_bounds(
+            self.points.iter().map(|point| point.position()));
+        self.children
+            .iter()
+            .filter_map(|child| child.tight_bounds())
+            .fold(own, |result, child| match result {
+                Some(bounds) => Some(bounds.union(&child)),
+                None => Some(child)
+            })
+    }
+
+    /// Get the implicit tiling coordinates of this node
+    pub fn get_implicit_coordinates(&self) -> &ImplicitCoordinates {
+        &self.implicit_coordinates
+    }
+
+    pub fn get_children(&self) -> &Vec<OctNode<P>> {
         &self.children
     }
 
     /// Borrow the points. This is used when writing data to disk
-    pub fn get_points(&self) -> &Vec<OutputPoint> {
+    pub fn get_points(&self) -> &Vec<P> {
         &self.points
     }
 
     /// Add a point from the top of the tree down. If this overfills the node,
     /// subdivide it as necessary, up to the given max depth.
-    pub fn add_point(&mut self, point: OutputPoint, max_depth: u8) {
+    ///
+    /// This returns true if the point was added, or false if it was
+    /// discarded for being outside the grid or landing in a full leaf
+    /// at the max depth.
+    pub fn add_point(&mut self, point: P, max_depth: u8) -> bool {
         // Discard points outside the grid
-        if !self.bounds.contains(&point.position) {
-            return;
+        if !self.bounds.contains(point.position()) {
+            return false;
         } 
 
-        self.add_point_recursive(point, 0, max_depth);
+        self.add_point_recursive(point, None, 0, max_depth)
+    }
+
+    /// Same as add_point(), but the path from the root to the leaf is
+    /// computed all at once as the Morton code of the quantized point.
+    /// This avoids finding the octant at every level, which is faster.
+    /// Points on a split plane are checked against the child boxes so
+    /// they end up in the same leaf as with add_point(). This only works
+    /// when the root is fixed and max_depth is at most MAX_MORTON_DEPTH.
+    pub fn add_point_morton(&mut self, point: P, max_depth: u8)
+            -> bool {
+        // Discard points outside the grid
+        if !self.bounds.contains(point.position()) {
+            return false;
+        } 
+
+        let code = self.bounds.morton_code(point.position(), max_depth);
+        self.add_point_recursive(point, Some(code), 0, max_depth)
+    }
+
+    /// Wrap this root node in larger and larger roots until it contains the
+    /// given position. Each time, the size doubles towards the position and
+    /// the old root becomes one of the 8 children of the new root. This
+    /// gives up after max_levels, in which case the position may still be
+    /// outside the tree.
+    ///
+    /// This returns the number of levels that were added above the old
+    /// root.
+    pub fn grow_to_contain(&mut self, position: &Vec3, max_levels: usize)
+            -> usize {
+        let mut levels = 0;
+        while !self.bounds.contains(position) && levels < max_levels {
+            self.grow_toward(position);
+            levels += 1;
+        }
+        levels
+    }
+
+    /// Replace this root with one twice as large in each direction, keeping
+    /// the old root as one of the children.
+    fn grow_toward(&mut self, position: &Vec3) {
+        let (bounds, octant) = self.bounds.double_toward(position);
+        let subtree_levels = self.implicit_coordinates.subtree_levels;
+        let capacity = self.capacity;
+        let mut old_root = std::mem::replace(self, Self {
+            implicit_coordinates: ImplicitCoordinates::root(subtree_levels),
+            children: Vec::new(),
+            bounds,
+            points: Vec::new(),
+            capacity,
+            count: 0,
+        });
+
+        self.subdivide();
+        self.count = old_root.count;
+
+        // Every tile below the new root moves down one level
+        old_root.set_implicit_coordinates(
+            self.implicit_coordinates.get_child_coordinates(octant));
+        self.children[octant] = old_root;
+    }
+
+    /// Update the coordinates of this tile and all its descendants
+    fn set_implicit_coordinates(&mut self, coordinates: ImplicitCoordinates) {
+        for (child_index, child) in self.children.iter_mut().enumerate() {
+            child.set_implicit_coordinates(
+                coordinates.get_child_coordinates(child_index));
+        }
+        self.implicit_coordinates = coordinates;
     }
 
     /// Add a point to the octree recursively. If there are already many points
@@ -112,17 +285,29 @@ impl OctNode {
     ///
     /// This returns true if the point was added, or false if the point was
     /// discarded because it didn't fit.
+    ///
+    /// If a Morton code from BBox::morton_code() is given, it determines
+    /// the octant at each level, otherwise the octant is computed from the
+    /// bounding box.
     fn add_point_recursive(
             &mut self, 
-            point: OutputPoint,
+            point: P,
+            code: Option<u64>,
             depth: u8, 
             max_depth: u8) -> bool {
         let is_leaf = self.is_leaf();
         let is_full = self.is_full();
         if is_leaf && !is_full {
             // Base case 1: We're at a leaf with some space. just add the point. 
+            // The position was already checked against the bounds, which
+            // rejects NaN and infinity.
+            debug_assert!(
+                point.color().is_finite(),
+                "octree: the point at {:?} has a non-finite color {:?}, \
+                check the color IFS",
+                point.position(),
+                point.color());
             self.count += 1;
-            self.color_sum = self.color_sum + point.color;
             self.points.push(point);
             return true;
         } else if is_leaf && is_full && depth < max_depth {
@@ -130,7 +315,7 @@ impl OctNode {
             // retry the add operation on this node which is now an internal
             // node. Note that this always produces 8 children
             self.subdivide();
-            return self.add_point_recursive(point, depth, max_depth);
+            return self.add_point_recursive(point, code, depth, max_depth);
         } else if is_leaf && is_full && depth == max_depth {
             // Base case 3: We're at a full leaf but we've hit the depth
             // limit. Just discard the point to prevent infinite loops
@@ -138,14 +323,30 @@ impl OctNode {
         } else if !is_leaf {
             // Recursive case: Find the octant which the point is in, and
             // insert into the child node
-            let octant = self.bounds.find_octant(&point.position);
+            let mut code = code;
+            let octant = match code {
+                Some(bits) => {
+                    let shift = 3 * (max_depth - depth - 1);
+                    ((bits >> shift) & 0b111) as usize
+                },
+                None => self.bounds.find_octant(point.position())
+            };
+            // The Morton code is computed in f64, but the child boxes have
+            // f32 centers which may be rounded. A point right on a split
+            // plane can disagree with the boxes, so fall back to the boxes
+            // for the rest of the way down.
+            let octant = if code.is_some() &&
+                    !self.children[octant].bounds.contains(point.position()) {
+                code = None;
+                self.bounds.find_octant(point.position())
+            } else {
+                octant
+            };
             let child = &mut self.children[octant];
-            let color = point.color;
             let result = child.add_point_recursive(
-                point, depth + 1, max_depth);
+                point, code, depth + 1, max_depth);
             if result {
                 self.count += 1;
-                self.color_sum = self.color_sum + color;
             }
             return result;
         } else {
@@ -170,44 +371,963 @@ impl OctNode {
 
         // Move all the points in the current buffer to the children
         for point in self.points.iter() {
-            let quadrant = self.bounds.find_octant(&point.position);
+            let quadrant = self.bounds.find_octant(point.position());
             let child = &mut self.children[quadrant]; 
             child.points.push(point.clone());
         }
         self.points.clear();
     }
 
-    pub fn decimate(&mut self) -> Vec<OutputPoint> {
-        for child in &mut self.children {
-            let child_points = child.decimate();
-            self.points.extend(child_points);
+    /// Rebalance the leaves so each one has roughly target points. Leaves
+    /// with more than target points are split (up to max_depth), and
+    /// interior nodes whose children are all leaves with at most target
+    /// points in total are merged into a single leaf. This must be called
+    /// before decimate(), while all the points are in the leaves. Every
+    /// point is kept.
+    pub fn rebalance(&mut self, target: usize, depth: u8, max_depth: u8) {
+        if self.is_leaf() {
+            if self.points.len() <= target || depth >= max_depth {
+                return;
+            }
+            self.subdivide();
+        }
+
+        for child in self.children.iter_mut() {
+            child.rebalance(target, depth + 1, max_depth);
+        }
+
+        let all_leaves = self.children.iter().all(|child| child.is_leaf());
+        let total: usize = 
+            self.children.iter().map(|child| child.points.len()).sum();
+        if all_leaves && total <= target {
+            for child in self.children.drain(..) {
+                self.points.extend(child.points);
+            }
+        }
+    }
+
+    /// Prune sparse leaves. Wherever the children of a node are all leaves
+    /// with fewer than min_points points (empty ones included), they are
+    /// merged into that node, which becomes a single leaf with the same
+    /// points inside its larger box. This works from the bottom up, so
+    /// merged nodes can merge again with their siblings. A sparse leaf
+    /// with a sibling of min_points or more is kept: the 8 children come
+    /// and go together, and with REPLACE refinement a parent can't hold
+    /// points its children don't. This must be called before decimate(),
+    /// while all the points are in the leaves. Every point is kept.
+    pub fn merge_sparse_leaves(&mut self, min_points: usize) {
+        if self.is_leaf() {
+            return;
+        }
+
+        for child in self.children.iter_mut() {
+            child.merge_sparse_leaves(min_points);
+        }
+
+        let all_sparse = self.children
+            .iter()
+            .all(|child| child.is_leaf() && child.points.len() < min_points);
+        if all_sparse {
+            for child in self.children.drain(..) {
+                self.points.extend(child.points);
+            }
+        }
+    }
+
+    /// After decimate(), interior nodes may still have many more than
+    /// target points. The points in interior nodes are only a lower level of
+    /// detail (copies of points in the leaves), so thin them out evenly to
+    /// at most target points. Leaves are left alone.
+    pub fn cap_points(&mut self, target: usize) {
+        if self.is_leaf() {
+            return;
+        }
+
+        if self.points.len() > target {
+            let stride = self.points.len().div_ceil(target);
+            self.points = self.points
+                .iter()
+                .step_by(stride)
+                .cloned()
+                .collect();
+        }
+
+        for child in self.children.iter_mut() {
+            child.cap_points(target);
+        }
+    }
+
+    /// Sort the points of every tile in Morton (Z-order) within the
+    /// tile's box so nearby points are next to each other in the output.
+    /// This only reorders the points, so call it after decimate() as the
+    /// last step before writing.
+    pub fn sort_morton(&mut self) {
+        let bounds = &self.bounds;
+        self.points.sort_by_cached_key(
+            |point| bounds.morton_code(point.position(), MAX_MORTON_DEPTH));
+
+        for child in self.children.iter_mut() {
+            child.sort_morton();
+        }
+    }
+
+    /// Merge points in each leaf that are within epsilon of each other.
+    /// Each merged point keeps the position of the first point, the
+    /// weighted average of the colors, and the sum of the weights, so the
+    /// density is not lost. Like rebalance() this must be called before
+    /// decimate(). The node counts still count the original points.
+    ///
+    /// The colors are already encoded in color_space, so they are decoded
+    /// to linear before averaging and the average is encoded again. The
+    /// sum is kept in f64 and divided once, so merging many copies of the
+    /// same color gives back that color.
+    pub fn dedupe(&mut self, epsilon: f32, color_space: ColorSpace) {
+        if !self.is_leaf() {
+            for child in self.children.iter_mut() {
+                child.dedupe(epsilon, color_space);
+            }
+            return;
+        }
+
+        // Spatial hash with cells of size epsilon. Points within epsilon
+        // of each other are in the same or adjacent cells.
+        let cell_of = |position: &Vec3| -> (i64, i64, i64) {
+            (
+                (position.x() / epsilon).floor() as i64,
+                (position.y() / epsilon).floor() as i64,
+                (position.z() / epsilon).floor() as i64
+            )
+        };
+        let mut cells: HashMap<(i64, i64, i64), Vec<usize>> = HashMap::new();
+        let mut merged: Vec<P> = Vec::new();
+        let mut color_sums: Vec<ColorSum> = Vec::new();
+
+        for point in self.points.drain(..) {
+            let (cx, cy, cz) = cell_of(point.position());
+            let mut neighbor = None;
+            'search: for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for dz in -1..=1 {
+                        let cell = (cx + dx, cy + dy, cz + dz);
+                        let candidates = match cells.get(&cell) {
+                            Some(candidates) => candidates,
+                            None => continue
+                        };
+                        for &index in candidates {
+                            let offset =
+                                *merged[index].position() - *point.position();
+                            if offset.length() <= epsilon {
+                                neighbor = Some(index);
+                                break 'search;
+                            }
+                        }
+                    }
+                }
+            }
+
+            let linear = color_space.decode(point.color());
+            let point_weight = point.weight();
+            let index = match neighbor {
+                Some(index) => {
+                    let weight = merged[index].weight() + point_weight;
+                    merged[index].set_weight(weight);
+                    index
+                },
+                None => {
+                    cells
+                        .entry((cx, cy, cz))
+                        .or_default()
+                        .push(merged.len());
+                    color_sums.push(ColorSum::default());
+                    merged.push(point);
+                    merged.len() - 1
+                }
+            };
+            color_sums[index].add_weighted(&linear, point_weight);
+        }
+
+        for (point, color_sum) in merged.iter_mut().zip(color_sums.iter()) {
+            let average = color_sum.average(point.weight() as usize);
+            point.set_color(color_space.encode(&average));
+        }
+        self.points = merged;
+    }
+
+    /// Visit this node and its descendants depth-first, parents before
+    /// children and children in octant order. The visitor is given each
+    /// node and its depth below this one. If it returns false, the
+    /// node's children are skipped, e.g. to stop at a certain depth.
+    pub fn visit<'a, F>(&'a self, visitor: &mut F)
+            where F: FnMut(&'a OctNode<P>, usize) -> bool {
+        self.visit_recursive(visitor, 0);
+    }
+
+    fn visit_recursive<'a, F>(&'a self, visitor: &mut F, depth: usize)
+            where F: FnMut(&'a OctNode<P>, usize) -> bool {
+        if !visitor(self, depth) {
+            return;
+        }
+        for child in self.children.iter() {
+            child.visit_recursive(visitor, depth + 1);
+        }
+    }
+
+    /// Borrow every point in the tree, e.g. to write a checkpoint. Before
+    /// decimate(), all the points are in the leaves so each point appears
+    /// once.
+    pub fn collect_points<'a>(&'a self, points: &mut Vec<&'a P>) {
+        self.visit(&mut |node, _| {
+            points.extend(node.points.iter());
+            true
+        });
+    }
+
+    /// Consume the tree and collect every point in it. Before decimate(),
+    /// all the points are in the leaves so each point appears once.
+    pub fn into_points(self) -> Vec<OutputPoint> {
+        let mut points: Vec<OutputPoint> =
+            self.points.into_iter().map(P::into).collect();
+        for child in self.children {
+            points.extend(child.into_points());
+        }
+        points
+    }
+
+    /// Fill in the interior nodes with lower levels of detail, from the
+    /// bottom up. Each interior node gets every ratio-th point of its
+    /// children's points combined (after the children were decimated), so
+    /// a node holds ceil(n / ratio) of the n points of its children. A node
+    /// h levels above the leaves holds about 1 / ratio^h of the points
+    /// below it. The points are picked with a fixed stride, so the result
+    /// only depends on the order of the points. Leaves are left alone.
+    /// The kept points are copied as-is, never averaged, so their colors
+    /// are the same at every level of detail.
+    ///
+    /// If weighted is true, the points with the highest weight (i.e. the
+    /// most points merged by dedupe()) are kept instead, so dense regions
+    /// keep their detail in the coarser levels. Ties are broken by the
+    /// same stride, so with equal weights this is the same as unweighted.
+    pub fn decimate(&mut self, ratio: usize, weighted: bool) {
+        if self.is_leaf() {
+            return;
+        }
+
+        for child in self.children.iter_mut() {
+            child.decimate(ratio, weighted);
+        }
+
+        let below = self.children.iter().flat_map(|child| child.points.iter());
+        if !weighted {
+            let sampled: Vec<P> = below.step_by(ratio).cloned().collect();
+            self.points.extend(sampled);
+            return;
+        }
+
+        let below: Vec<&P> = below.collect();
+        let mut ranked: Vec<usize> = (0..below.len()).collect();
+        ranked.sort_by_key(
+            |&i| (std::cmp::Reverse(below[i].weight()), i % ratio, i / ratio));
+        let mut kept = ranked[..below.len().div_ceil(ratio)].to_vec();
+        // Keep the points in their original order
+        kept.sort_unstable();
+        self.points.extend(kept.into_iter().map(|i| below[i].clone()));
+    }
+
+    /// Count the points in the leaves of this subtree. After decimate(),
+    /// the interior nodes only hold copies of these, so this is the number
+    /// of distinct points.
+    pub fn leaf_point_count(&self) -> usize {
+        if self.is_leaf() {
+            return self.points.len();
+        }
+        self.children.iter().map(|child| child.leaf_point_count()).sum()
+    }
+
+    /// The number of points in each leaf of this subtree, empty leaves
+    /// included, in the order visit() reaches them
+    pub fn leaf_sizes(&self) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        self.visit(&mut |node, _| {
+            if node.is_leaf() {
+                sizes.push(node.points.len());
+            }
+            true
+        });
+        sizes
+    }
+
+    /// Replace the points of this node with count points sampled from the
+    /// leaves below it, for a denser overview than decimate() gives the
+    /// root. The sample is stratified: count is divided among the children
+    /// in proportion to their leaf points, and so on down to the leaves,
+    /// which contribute evenly spaced points. Every region is represented,
+    /// however the points were ordered. If count is more than the leaf
+    /// points, they are all copied. Call this after decimate(); a leaf is
+    /// left alone since it already holds every point.
+    pub fn resample_from_leaves(&mut self, count: usize) {
+        if self.is_leaf() {
+            return;
+        }
+
+        let mut sampled = Vec::with_capacity(count);
+        self.sample_leaves(count, &mut sampled);
+        self.points = sampled;
+    }
+
+    fn sample_leaves(&self, count: usize, sampled: &mut Vec<P>) {
+        if self.is_leaf() {
+            let n = self.points.len();
+            let count = count.min(n);
+            // Take the middle point of each of count equal runs
+            sampled.extend((0..count).map(
+                |i| self.points[(2 * i + 1) * n / (2 * count)].clone()));
+            return;
         }
 
-        self.points.iter()
-            .enumerate()
-            .filter(|(i, _)| i % 4 == 0)
-            .map(|(_, point)| point.clone())
-            .collect()
+        let child_totals: Vec<usize> = self.children
+            .iter()
+            .map(|child| child.leaf_point_count())
+            .collect();
+        let total: usize = child_totals.iter().sum();
+        if total == 0 {
+            return;
+        }
+
+        // Split at the rounded-down cumulative shares so the parts add up
+        // to exactly count and no child is asked for more than it has
+        let count = count.min(total);
+        let mut before = 0;
+        for (child, child_total) in self.children.iter().zip(child_totals) {
+            let start = count * before / total;
+            before += child_total;
+            let end = count * before / total;
+            child.sample_leaves(end - start, sampled);
+        }
     }
 
     /// point clouds will go in {tileset_dir}/{level}/{x}/{y}/ The z will be
-    /// part of the filename
-    pub fn get_directory_name(&self, tileset_dir: &str) -> String {
+    /// part of the filename. With a flat layout, every file goes directly
+    /// in {tileset_dir}
+    pub fn get_directory_name(&self, tileset_dir: &str, layout: ContentLayout)
+            -> String {
         let coords = &self.implicit_coordinates;
-        format!("{}/{}/{}/{}", tileset_dir, coords.level, coords.x, coords.y)
+        match layout {
+            ContentLayout::Nested => format!(
+                "{}/{}/{}/{}", tileset_dir, coords.level, coords.x, coords.y),
+            ContentLayout::Flat => tileset_dir.to_string(),
+        }
     }
 
     /// point cloud files will go in
-    /// {tileset_dir}/{level}/{x}/{y}/{z}.{extension}
-    pub fn get_file_name(&self, dirname: &str, extension: &str) -> String {
+    /// {tileset_dir}/{level}/{x}/{y}/{z}.{extension}, or
+    /// {tileset_dir}/{level}_{x}_{y}_{z}.{extension} with a flat layout.
+    /// The coordinates are unique per tile and the separators keep e.g.
+    /// level 1, x 11 apart from level 11, x 1, so flat names never collide.
+    pub fn get_file_name(
+            &self, dirname: &str, extension: &str, layout: ContentLayout)
+            -> String {
         let coords = &self.implicit_coordinates;
+        let separator = match layout {
+            ContentLayout::Nested => "/",
+            ContentLayout::Flat => "_",
+        };
         format!(
-            "{}/{}/{}/{}/{}.{}",
+            "{}/{}{sep}{}{sep}{}{sep}{}.{}",
             dirname,
             coords.level,
             coords.x,
             coords.y,
             coords.z,
-            extension)
+            extension,
+            sep = separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color_sum_many_colors() {
+        let color = Vec3::new(0.1, 0.5, 0.9);
+        let count = 10_000_000;
+        let mut sum = ColorSum::default();
+        for _ in 0..count {
+            sum.add_weighted(&color, 1);
+        }
+
+        let average = sum.average(count);
+        assert!((*average.x() - 0.1).abs() < 1e-6);
+        assert!((*average.y() - 0.5).abs() < 1e-6);
+        assert!((*average.z() - 0.9).abs() < 1e-6);
+    }
+
+    fn make_point(x: f32, y: f32, z: f32) -> OutputPoint {
+        OutputPoint {
+            position: Vec3::new(x, y, z),
+            color: Vec3::ones(),
+            cluster_coordinates: Vec3::zero(),
+            iteration: 0,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        }
+    }
+
+    /// Check that every tile's coordinates match its position in the tree
+    /// and every point is inside its tile
+    fn check_tree(node: &OctNode) {
+        for point in node.get_points() {
+            assert!(node.bounds().contains(&point.position));
+        }
+
+        for (child_index, child) in node.get_children().iter().enumerate() {
+            let expected = node.get_implicit_coordinates()
+                .get_child_coordinates(child_index);
+            let actual = child.get_implicit_coordinates();
+            assert_eq!(
+                (actual.level, actual.x, actual.y, actual.z),
+                (expected.level, expected.x, expected.y, expected.z));
+            check_tree(child);
+        }
+    }
+
+    /// Get the number of points in each leaf of the tree
+    fn leaf_point_counts(node: &OctNode) -> Vec<usize> {
+        let mut counts = Vec::new();
+        node.visit(&mut |node, _| {
+            if node.is_leaf() {
+                counts.push(node.points.len());
+            }
+            true
+        });
+        counts
+    }
+
+    /// Count the points in the leaves of the tree
+    fn count_leaf_points(node: &OctNode) -> usize {
+        leaf_point_counts(node).iter().sum()
+    }
+
+    /// Find the largest number of points in a single leaf
+    fn max_leaf_points(node: &OctNode) -> usize {
+        *leaf_point_counts(node).iter().max().unwrap()
+    }
+
+    /// Count the nodes of the tree without the visitor
+    fn count_nodes(node: &OctNode) -> usize {
+        1 + node.get_children().iter().map(count_nodes).sum::<usize>()
+    }
+
+    #[test]
+    fn test_dedupe_merges_coincident_points() {
+        let mut root = OctNode::root_node(1.0, 1000, 3);
+        // A cluster of 10 near-coincident points, half red, half blue
+        for i in 0..10 {
+            let mut point = make_point(0.5 + i as f32 * 1e-5, 0.5, 0.5);
+            point.color = if i % 2 == 0 {
+                Vec3::new(1.0, 0.0, 0.0)
+            } else {
+                Vec3::new(0.0, 0.0, 1.0)
+            };
+            root.add_point(point, 3);
+        }
+        // and a point well away from them
+        root.add_point(make_point(-0.5, -0.5, -0.5), 3);
+
+        root.dedupe(1e-3, ColorSpace::Linear);
+        let points = root.get_points();
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].weight, 10);
+        assert!((points[0].color - Vec3::new(0.5, 0.0, 0.5)).length() < 1e-6);
+        assert_eq!(points[1].weight, 1);
+
+        // The counts still include every original point
+        assert_eq!(root.count, 11);
+    }
+
+    #[test]
+    fn test_identical_colors_survive_every_level() {
+        for &color_space in [ColorSpace::Linear, ColorSpace::Srgb].iter() {
+            let color = color_space.encode(&Vec3::new(0.1, 0.35, 0.7));
+            let mut root = OctNode::root_node(1.0, 50, 3);
+            for i in 0..2000 {
+                // 200 spots with 10 coincident points each
+                let t = (i % 200) as f32 / 200.0;
+                let mut point = make_point(0.9 - t, (9.0 * t).sin() * 0.9, 0.0);
+                point.color = color;
+                root.add_point(point, 3);
+            }
+
+            root.dedupe(1e-3, color_space);
+            root.decimate(4, true);
+            let mut points = Vec::new();
+            root.collect_points(&mut points);
+            assert!(points.iter().any(|point| point.weight == 10));
+            for point in points {
+                assert_eq!(point.color.pack(), color.pack());
+            }
+        }
+    }
+
+    #[test]
+    fn test_sort_morton_preserves_points() {
+        let mut root = OctNode::root_node(1.0, 1000, 3);
+        for i in 0..200 {
+            let t = i as f32 / 200.0;
+            let y = (7.0 * t).sin() * 0.9;
+            root.add_point(make_point(0.9 - t, y, t - 0.5), 3);
+        }
+        let key = |point: &OutputPoint| {
+            let p = point.position;
+            (p.x().to_bits(), p.y().to_bits(), p.z().to_bits())
+        };
+        let mut before: Vec<_> = root.get_points().iter().map(key).collect();
+
+        root.sort_morton();
+        let codes: Vec<u64> = root.get_points().iter()
+            .map(|point| root.bounds().morton_code(
+                &point.position, MAX_MORTON_DEPTH))
+            .collect();
+        assert!(codes.windows(2).all(|pair| pair[0] <= pair[1]));
+
+        let mut after: Vec<_> = root.get_points().iter().map(key).collect();
+        before.sort_unstable();
+        after.sort_unstable();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn test_rebalance_splits_full_leaves() {
+        // With a large capacity, every point lands in the root
+        let mut root = OctNode::root_node(1.0, 1000, 3);
+        for i in 0..500 {
+            let t = i as f32 / 500.0;
+            root.add_point(make_point(t - 0.5, 0.5 - t, 0.3 * t), 10);
+        }
+        assert!(root.is_leaf());
+
+        root.rebalance(50, 0, 10);
+        assert!(!root.is_leaf());
+        assert_eq!(count_leaf_points(&root), 500);
+        assert!(max_leaf_points(&root) <= 50);
+        check_tree(&root);
+    }
+
+    #[test]
+    fn test_rebalance_merges_sparse_leaves() {
+        // With a tiny capacity, the tree subdivides even for a few points
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        for i in 0..20 {
+            let t = i as f32 / 20.0;
+            root.add_point(make_point(t - 0.5, 0.25, -0.25), 10);
+        }
+        assert!(!root.is_leaf());
+
+        root.rebalance(100, 0, 10);
+        assert!(root.is_leaf());
+        assert_eq!(root.get_points().len(), 20);
+        check_tree(&root);
+    }
+
+    #[test]
+    fn test_merge_sparse_leaves() {
+        // A dense clump in one corner and a few stray points elsewhere.
+        // The small capacity splits the tree around both.
+        let mut root = OctNode::root_node(1.0, 4, 3);
+        for i in 0..40 {
+            let t = i as f32 / 40.0;
+            root.add_point(make_point(0.6 + 0.1 * t, 0.6, 0.7 - 0.1 * t), 20);
+        }
+        for i in 0..6 {
+            let t = i as f32 / 6.0;
+            root.add_point(make_point(-0.9 + 0.3 * t, -0.5, 0.2 - t), 20);
+        }
+        assert_eq!(count_leaf_points(&root), 46);
+        let nodes_before = count_nodes(&root);
+
+        root.merge_sparse_leaves(3);
+        assert_eq!(count_leaf_points(&root), 46);
+        assert!(count_nodes(&root) < nodes_before);
+        check_tree(&root);
+
+        // Every remaining group of leaf siblings has one that is not
+        // sparse
+        root.visit(&mut |node, _| {
+            let children = node.get_children();
+            if !children.is_empty() &&
+                    children.iter().all(|child| child.is_leaf()) {
+                assert!(children.iter().any(|child| child.points.len() >= 3));
+            }
+            true
+        });
+
+        // With a high enough threshold, everything merges into the root
+        root.merge_sparse_leaves(100);
+        assert!(root.is_leaf());
+        assert_eq!(root.get_points().len(), 46);
+    }
+
+    #[test]
+    fn test_cap_points() {
+        let mut root = OctNode::root_node(1.0, 1000, 3);
+        for i in 0..500 {
+            let t = i as f32 / 500.0;
+            root.add_point(make_point(t - 0.5, 0.5 - t, 0.3 * t), 10);
+        }
+        root.rebalance(50, 0, 10);
+        root.decimate(4, false);
+        root.cap_points(50);
+
+        assert!(root.get_points().len() <= 50);
+        assert_eq!(count_leaf_points(&root), 500);
+        check_tree(&root);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_grow_to_contain() {
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        for i in 0..4 {
+            assert!(root.add_point(make_point(0.1 * i as f32, 0.5, 0.5), 5));
+        }
+
+        let far = Vec3::new(-5.0, 3.0, 0.0);
+        let levels = root.grow_to_contain(&far, 10);
+        assert_eq!(levels, 2);
+        assert!(root.bounds().contains(&far));
+        assert_eq!(root.count, 4);
+        assert!(root.add_point(make_point(-5.0, 3.0, 0.0), 5 + levels as u8));
+        check_tree(&root);
+    }
+
+    #[test]
+    fn test_add_point_morton_matches_float_path() {
+        let positions: Vec<Vec3> = (0..500).map(|i| {
+            let t = i as f32 * 0.37;
+            Vec3::new(t.sin(), (1.3 * t).cos(), (0.7 * t).sin() * 0.99)
+        }).collect();
+
+        let mut float_root = OctNode::root_node(1.0, 4, 3);
+        let mut morton_root = OctNode::root_node(1.0, 4, 3);
+        for position in positions.iter() {
+            let point = make_point(*position.x(), *position.y(), *position.z());
+            let float_result = float_root.add_point(point.clone(), 6);
+            let morton_result = morton_root.add_point_morton(point, 6);
+            assert_eq!(float_result, morton_result);
+        }
+
+        assert_eq!(float_root.count, morton_root.count);
+        check_tree(&morton_root);
+    }
+
+    #[test]
+    fn test_center_plane_points_are_kept() {
+        // A padded box whose nested centers are not exact in f32
+        let bounds =
+            || BBox::new(-1.1, 1.1, -1.1, 1.1, -1.1, 1.1).padded(0.013);
+
+        // The x coordinate of the center planes down to depth 5
+        let mut planes = Vec::new();
+        let mut boxes = vec![bounds()];
+        for _ in 0..5 {
+            let mut next = Vec::new();
+            for bbox in boxes.iter() {
+                planes.push(*bbox.center().x());
+                next.extend(bbox.subdivide().into_iter().take(2));
+            }
+            boxes = next;
+        }
+
+        for &morton in [false, true].iter() {
+            let mut root = OctNode::root_with_bounds(bounds(), 1, 3);
+            let mut count = 0;
+            for (i, &x) in planes.iter().enumerate() {
+                for (j, &y) in planes.iter().enumerate().step_by(3) {
+                    let z = planes[(i + j) % planes.len()];
+                    let point = make_point(x, y, z);
+                    let added = if morton {
+                        root.add_point_morton(point, 8)
+                    } else {
+                        root.add_point(point, 8)
+                    };
+                    assert!(added, "({}, {}, {}) was discarded", x, y, z);
+                    count += 1;
+                }
+            }
+
+            // Each point is in exactly one leaf, and it's the right one
+            assert_eq!(count_leaf_points(&root), count);
+            assert_eq!(root.count, count);
+            check_tree(&root);
+        }
+    }
+
+    #[test]
+    fn test_visit_hits_every_node_once() {
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        for i in 0..200 {
+            let t = i as f32 * 0.37;
+            root.add_point(make_point(t.sin(), (1.3 * t).cos(), 0.5 * t.sin()), 6);
+        }
+        assert!(!root.is_leaf());
+
+        let mut visited = Vec::new();
+        let mut max_depth = 0;
+        root.visit(&mut |node, depth| {
+            let coords = node.get_implicit_coordinates();
+            assert_eq!(coords.level, depth);
+            visited.push((coords.level, coords.x, coords.y, coords.z));
+            max_depth = max_depth.max(depth);
+            true
+        });
+        assert_eq!(visited.len(), count_nodes(&root));
+        let unique: std::collections::HashSet<_> = visited.iter().collect();
+        assert_eq!(unique.len(), visited.len());
+
+        // Returning false skips the children
+        let mut shallow = 0;
+        root.visit(&mut |_, depth| {
+            shallow += 1;
+            depth < 1
+        });
+        assert_eq!(shallow, 1 + root.get_children().len());
+        assert!(max_depth > 1);
+    }
+
+    #[test]
+    fn test_flat_file_names_are_unique() {
+        // Deep enough that some coordinates have two digits, so a name
+        // like 1_11_... could collide with 11_1_... without separators
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        for i in 0..2000 {
+            let t = i as f32 * 0.37;
+            root.add_point(
+                make_point(t.sin(), (1.3 * t).cos(), 0.5 * t.sin()), 8);
+        }
+
+        let mut names = Vec::new();
+        let mut max_x = 0;
+        root.visit(&mut |node, _| {
+            names.push(
+                node.get_file_name("content", "glb", ContentLayout::Flat));
+            assert_eq!(
+                node.get_directory_name("content", ContentLayout::Flat),
+                "content");
+            max_x = max_x.max(node.get_implicit_coordinates().x);
+            true
+        });
+        assert!(max_x >= 10);
+        assert_eq!(names[0], "content/0_0_0_0.glb");
+        let unique: std::collections::HashSet<_> = names.iter().collect();
+        assert_eq!(unique.len(), names.len());
+        assert_eq!(names.len(), count_nodes(&root));
+    }
+
+    #[test]
+    fn test_grow_to_contain_gives_up() {
+        let mut root: OctNode = OctNode::root_node(1.0, 2, 3);
+        let far = Vec3::new(1000.0, 0.0, 0.0);
+        assert_eq!(root.grow_to_contain(&far, 2), 2);
+        assert!(!root.bounds().contains(&far));
+    }
+
+    #[test]
+    fn test_color_sum_empty_is_black() {
+        let sum = ColorSum::default();
+        let average = sum.average(0);
+        assert_eq!(*average.x(), 0.0);
+        assert_eq!(*average.y(), 0.0);
+        assert_eq!(*average.z(), 0.0);
+    }
+
+    /// Check that a subtree's tight bounds contain all the points below it
+    /// and fit in the node's octant. Returns the points of the subtree
+    fn check_tight_bounds(node: &OctNode) -> Vec<Vec3> {
+        let mut positions: Vec<Vec3> =
+            node.get_points().iter().map(|point| point.position).collect();
+        for child in node.get_children() {
+            positions.extend(check_tight_bounds(child));
+        }
+
+        match node.tight_bounds() {
+            None => assert!(positions.is_empty()),
+            Some(tight) => {
+                for position in positions.iter() {
+                    let point = BBox::from_center(position, &Vec3::zero());
+                    assert!(tight.contains_box(&point), "{:?}", position);
+                }
+                assert!(node.bounds().contains_box(&tight));
+            }
+        }
+        positions
+    }
+
+    #[test]
+    fn test_tight_bounds() {
+        let mut root = OctNode::root_node(1.0, 4, 3);
+        // The points only fill a small corner of the root
+        for i in 0..100 {
+            let t = i as f32 * 0.37;
+            let point = make_point(
+                0.5 + 0.1 * t.sin(), 0.5 + 0.1 * t.cos(), -0.8 + 0.01 * t);
+            assert!(root.add_point(point, 6));
+        }
+        root.decimate(4, false);
+
+        check_tight_bounds(&root);
+        let tight = root.tight_bounds().unwrap();
+        assert!(tight.diagonal_len() < 0.5 * root.bounds().diagonal_len());
+    }
+
+    #[test]
+    fn test_decimate_ratio() {
+        let mut root = OctNode::root_node(1.0, 8, 3);
+        for i in 0..2000 {
+            let t = i as f32 * 0.37;
+            let point = make_point(
+                0.9 * t.sin(), 0.9 * (1.3 * t).cos(), 0.9 * (0.7 * t).sin());
+            root.add_point(point, 4);
+        }
+        let ratio = 3;
+        root.decimate(ratio, false);
+
+        // Every interior node has exactly 1 / ratio of its children's
+        // points (rounded up), and the leaves keep all of theirs
+        root.visit(&mut |node, _| {
+            if !node.is_leaf() {
+                let below: usize = node.get_children()
+                    .iter()
+                    .map(|child| child.get_points().len())
+                    .sum();
+                assert_eq!(node.get_points().len(), below.div_ceil(ratio));
+            }
+            true
+        });
+        assert_eq!(count_leaf_points(&root), root.count);
+
+        // So the interior nodes at each level have about 1 / ratio of the
+        // points one level down, off by at most 1 per node from rounding
+        let mut interior_points = [0; 6];
+        let mut interior_nodes = [0; 6];
+        let mut all_points = [0; 6];
+        root.visit(&mut |node, depth| {
+            all_points[depth] += node.get_points().len();
+            if !node.is_leaf() {
+                interior_points[depth] += node.get_points().len();
+                interior_nodes[depth] += 1;
+            }
+            true
+        });
+        for depth in (0..5).filter(|depth| interior_nodes[*depth] > 0) {
+            let expected = all_points[depth + 1] as f64 / ratio as f64;
+            let actual = interior_points[depth] as f64;
+            assert!(
+                actual >= expected &&
+                    actual < expected + interior_nodes[depth] as f64,
+                "depth {}: {:?}", depth, interior_points);
+        }
+        assert!(interior_points[0] > 0);
+    }
+
+    #[test]
+    fn test_weighted_decimate() {
+        let make_tree = |heavy: bool| {
+            let mut root = OctNode::root_node(1.0, 8, 3);
+            for i in 0..400 {
+                let t = i as f32 * 0.37;
+                let mut point = make_point(
+                    0.9 * t.sin(), 0.9 * (1.3 * t).cos(), 0.9 * (0.7 * t).sin());
+                if heavy && i % 50 == 7 {
+                    point.weight = 20;
+                }
+                root.add_point(point, 4);
+            }
+            root
+        };
+
+        // With equal weights, this is the same as the fixed stride
+        let mut weighted = make_tree(false);
+        let mut strided = make_tree(false);
+        weighted.decimate(4, true);
+        strided.decimate(4, false);
+        let xs = |node: &OctNode| -> Vec<f32> {
+            node.get_points().iter().map(|p| *p.position.x()).collect()
+        };
+        assert_eq!(xs(&weighted), xs(&strided));
+
+        // The heavy points are kept all the way up to the root, where there
+        // is room for all 8 of them
+        let mut root = make_tree(true);
+        root.decimate(4, true);
+        assert!(root.get_points().len() >= 8);
+        let heavy = root.get_points().iter().filter(|p| p.weight == 20).count();
+        assert_eq!(heavy, 8);
+    }
+
+    #[test]
+    fn test_resample_root_spreads_across_octants() {
+        let mut root = OctNode::root_node(1.0, 8, 3);
+        // Most of the points are in the +x half
+        for i in 0..3000 {
+            let t = i as f32 * 0.37;
+            let x = if i % 4 == 0 { -0.5 } else { 0.5 };
+            let point = make_point(
+                x + 0.4 * t.sin(),
+                0.9 * (1.3 * t).cos(),
+                0.9 * (0.7 * t).sin());
+            root.add_point(point, 5);
+        }
+        root.decimate(4, false);
+        let leaf_points = count_leaf_points(&root);
+
+        let count = 500;
+        root.resample_from_leaves(count);
+        assert_eq!(root.get_points().len(), count);
+        check_tree(&root);
+
+        // Each octant gets its share of the sample, off by at most 1 from
+        // rounding
+        for (octant, child) in root.get_children().iter().enumerate() {
+            let expected =
+                (count * child.leaf_point_count()) as f64 / leaf_points as f64;
+            let actual = root.get_points()
+                .iter()
+                .filter(|point| child.bounds().contains(&point.position))
+                .count();
+            assert!(
+                (actual as f64 - expected).abs() <= 1.0,
+                "octant {}: {} points, expected {}", octant, actual, expected);
+        }
+        assert_eq!(count_leaf_points(&root), leaf_points);
+    }
+
+    #[test]
+    fn test_leaf_sizes() {
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        assert_eq!(root.leaf_sizes(), vec![0]);
+
+        // The third point splits the root. Two points land in the +x +y +z
+        // octant (the last one) and one in the -x -y -z octant (the first)
+        root.add_point(make_point(0.5, 0.5, 0.5), 3);
+        root.add_point(make_point(0.6, 0.5, 0.5), 3);
+        root.add_point(make_point(-0.5, -0.5, -0.5), 3);
+        assert_eq!(root.leaf_sizes(), vec![1, 0, 0, 0, 0, 0, 0, 2]);
+    }
+
+    #[test]
+    fn test_resample_root_with_few_points() {
+        let mut root = OctNode::root_node(1.0, 2, 3);
+        for x in [-0.5, 0.5, 0.6].iter() {
+            root.add_point(make_point(*x, 0.0, 0.0), 3);
+        }
+        root.decimate(4, false);
+        root.resample_from_leaves(100);
+        assert_eq!(root.get_points().len(), 3);
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use json::JsonValue;
+
+use crate::vector::Vec3;
+
+/// Geometric shapes that can be used as an orbit trap
+pub enum TrapShape {
+    /// A single point
+    Point { center: Vec3 },
+    /// An infinite line through a point. The direction is unit length.
+    Line { center: Vec3, direction: Vec3 },
+    /// A circle in the plane with the given (unit length) normal
+    Circle { center: Vec3, normal: Vec3, radius: f32 },
+}
+
+impl TrapShape {
+    /// Distance from a point to the closest point of the shape
+    pub fn distance(&self, position: &Vec3) -> f32 {
+        match self {
+            Self::Point { center } => (*position - *center).length(),
+            Self::Line { center, direction } => {
+                let offset = *position - *center;
+                let along = *direction * offset.dot(direction);
+                (offset - along).length()
+            },
+            Self::Circle { center, normal, radius } => {
+                let offset = *position - *center;
+                let height = offset.dot(normal);
+                let in_plane = offset - *normal * height;
+                let radial = in_plane.length() - radius;
+                (radial * radial + height * height).sqrt()
+            }
+        }
+    }
+}
+
+/// Orbit trap coloring for the chaos game. Instead of using the color IFS,
+/// each point is colored by how close the recent trajectory came to a
+/// "trap" shape. Since the chaos game follows a single endless orbit, only
+/// the most recent few positions are considered, otherwise the minimum
+/// distance would quickly shrink to 0 for every point.
+pub struct OrbitTrap {
+    /// The trap to measure distances to
+    shape: TrapShape,
+    /// How many of the most recent positions to consider
+    window: usize,
+    /// Distance of each of the most recent positions to the trap, oldest
+    /// first
+    history: VecDeque<f32>,
+    /// Distances at or beyond this are colored with far_color
+    falloff: f32,
+    /// Color when the trajectory touches the trap
+    near_color: Vec3,
+    /// Color when the trajectory stays far from the trap
+    far_color: Vec3,
+}
+
+impl OrbitTrap {
+    pub fn new(
+            shape: TrapShape,
+            window: usize,
+            falloff: f32,
+            near_color: Vec3,
+            far_color: Vec3) -> Self {
+        if window == 0 {
+            panic!("orbit_trap: window must be a positive integer");
+        }
+
+        if falloff <= 0.0 {
+            panic!("orbit_trap: falloff must be positive");
+        }
+
+        Self {
+            shape,
+            window,
+            history: VecDeque::with_capacity(window),
+            falloff,
+            near_color,
+            far_color,
+        }
+    }
+
+    /// Parse from JSON of the form:
+    ///
+    /// ```text
+    /// {
+    ///     "shape": "point" | "line" | "circle",
+    ///     "center": [x, y, z] (default [0, 0, 0]),
+    ///     "direction": [x, y, z] (line only, default [0, 0, 1]),
+    ///     "normal": [x, y, z] (circle only, default [0, 0, 1]),
+    ///     "radius": r (circle only, default 1),
+    ///     "window": n (default 10),
+    ///     "falloff": distance (default 1),
+    ///     "near_color": [r, g, b] | str (default [1.0, 0.8, 0.2]),
+    ///     "far_color": [r, g, b] | str (default [0.05, 0.05, 0.2])
+    /// }
+    /// ```
+    ///
+    /// The colors can also be strings, see Vec3::color_from_json().
+    ///
+    /// Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+
+        let center = Vec3::from_json(&json["center"], Vec3::zero());
+        let up = Vec3::new(0.0, 0.0, 1.0);
+        let shape = match json["shape"].as_str() {
+            Some("point") => TrapShape::Point { center },
+            Some("line") => TrapShape::Line {
+                center,
+                direction: Vec3::from_json(&json["direction"], up).normalize()
+            },
+            Some("circle") => TrapShape::Circle {
+                center,
+                normal: Vec3::from_json(&json["normal"], up).normalize(),
+                radius: json["radius"].as_f32().unwrap_or(1.0)
+            },
+            _ => panic!("orbit_trap: shape must be point, line or circle")
+        };
+
+        let window = json["window"].as_usize().unwrap_or(10);
+        let falloff = json["falloff"].as_f32().unwrap_or(1.0);
+        let near_color = Vec3::color_from_json(
+            &json["near_color"], Vec3::new(1.0, 0.8, 0.2));
+        let far_color = Vec3::color_from_json(
+            &json["far_color"], Vec3::new(0.05, 0.05, 0.2));
+
+        Some(Self::new(shape, window, falloff, near_color, far_color))
+    }
+
+    /// Record the next position of the trajectory
+    pub fn record(&mut self, position: &Vec3) {
+        if self.history.len() == self.window {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.shape.distance(position));
+    }
+
+    /// Closest approach of the recent trajectory to the trap
+    pub fn min_distance(&self) -> f32 {
+        self.history.iter().cloned().fold(f32::INFINITY, f32::min)
+    }
+
+    /// Color for the current point based on the closest approach
+    pub fn color(&self) -> Vec3 {
+        let t = (self.min_distance() / self.falloff).min(1.0);
+        Vec3::lerp(&self.near_color, &self.far_color, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn test_distances() {
+        let position = Vec3::new(3.0, 4.0, 0.0);
+
+        let point = TrapShape::Point { center: Vec3::zero() };
+        assert_close(point.distance(&position), 5.0);
+
+        let line = TrapShape::Line {
+            center: Vec3::zero(),
+            direction: Vec3::new(1.0, 0.0, 0.0)
+        };
+        assert_close(line.distance(&position), 4.0);
+
+        // The closest point on the unit circle is (0.6, 0.8, 0)
+        let circle = TrapShape::Circle {
+            center: Vec3::zero(),
+            normal: Vec3::new(0.0, 0.0, 1.0),
+            radius: 1.0
+        };
+        assert_close(circle.distance(&position), 4.0);
+
+        // Above the center of the circle
+        let above = Vec3::new(0.0, 0.0, 1.0);
+        assert_close(circle.distance(&above), 2.0f32.sqrt());
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let shape = TrapShape::Point { center: Vec3::zero() };
+        let mut trap = OrbitTrap::new(
+            shape, 2, 1.0, Vec3::ones(), Vec3::zero());
+        trap.record(&Vec3::new(0.5, 0.0, 0.0));
+        trap.record(&Vec3::new(3.0, 0.0, 0.0));
+        assert_close(trap.min_distance(), 0.5);
+
+        // The close point slides out of the window
+        trap.record(&Vec3::new(2.0, 0.0, 0.0));
+        assert_close(trap.min_distance(), 2.0);
+    }
+
+    #[test]
+    fn test_color() {
+        let shape = TrapShape::Point { center: Vec3::zero() };
+        let mut trap = OrbitTrap::new(
+            shape, 1, 2.0, Vec3::ones(), Vec3::zero());
+
+        trap.record(&Vec3::zero());
+        assert_close(*trap.color().x(), 1.0);
+
+        trap.record(&Vec3::new(1.0, 0.0, 0.0));
+        assert_close(*trap.color().x(), 0.5);
+
+        trap.record(&Vec3::new(10.0, 0.0, 0.0));
+        assert_close(*trap.color().x(), 0.0);
+    }
+}
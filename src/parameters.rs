@@ -0,0 +1,161 @@
+//! Loading parameter files. Errors are returned as messages for the user
+//! rather than panicking, since a typo in a parameter file is the most
+//! common way for a render to fail.
+
+use std::fs;
+
+use json::JsonValue;
+
+use crate::algorithms::{self, Algorithm};
+
+/// Read and parse a JSON parameter file. The error explains what went
+/// wrong, see describe_parse_error() for malformed JSON.
+pub fn load_json_file(fname: &str) -> Result<JsonValue, String> {
+    let text = fs::read_to_string(fname)
+        .map_err(|err| format!("Could not read {}: {}", fname, err))?;
+    json::parse(&text)
+        .map_err(|err| describe_parse_error(fname, &text, &err))
+}
+
+/// Load a parameter file, then let edit change it before it is used, e.g.
+/// to apply settings from the command line. See load_json_file() for the
+/// errors.
+pub fn load_params(fname: &str, edit: impl FnOnce(&mut JsonValue))
+        -> Result<JsonValue, String> {
+    let mut json = load_json_file(fname)?;
+    edit(&mut json);
+    Ok(json)
+}
+
+/// Load the algorithm described by a parameter file, see load_params().
+/// Only problems loading the file are returned as errors, invalid
+/// parameters still panic in algorithms::from_json().
+pub fn load_algorithm(fname: &str, edit: impl FnOnce(&mut JsonValue))
+        -> Result<Box<dyn Algorithm>, String> {
+    load_params(fname, edit).map(|json| algorithms::from_json(&json))
+}
+
+/// Explain where a parameter file is malformed. For an unexpected
+/// character, this includes the line and column, the line before it for
+/// context, and a caret under the offending character, e.g.
+///
+/// ```text
+/// Could not parse JSON in params/tree.json:4:1: unexpected character '}'
+///     "iters": 1000,
+/// }
+/// ^
+/// ```
+pub fn describe_parse_error(fname: &str, text: &str, err: &json::Error)
+        -> String {
+    let lines: Vec<&str> = text.lines().collect();
+    match *err {
+        json::Error::UnexpectedCharacter { ch, line, column } => {
+            // The json crate counts the position from the text before the
+            // character, so a character at the start of a line is reported
+            // just past the end of the previous line.
+            let past_end = lines
+                .get(line - 1)
+                .map(|x| x.chars().count() == column - 1)
+                .unwrap_or(false);
+            let (line, column) = if past_end {
+                (line + 1, 1)
+            } else {
+                (line, column)
+            };
+
+            let mut message = format!(
+                "Could not parse JSON in {}:{}:{}: unexpected character {:?}",
+                fname, line, column, ch);
+            let first_line = line.saturating_sub(2);
+            for snippet_line in lines.iter().take(line).skip(first_line) {
+                message.push('\n');
+                message.push_str(snippet_line);
+            }
+            message.push('\n');
+            message.push_str(&" ".repeat(column - 1));
+            message.push('^');
+            message
+        },
+        json::Error::UnexpectedEndOfJson => format!(
+            "Could not parse JSON in {}:{}: unexpected end of file, \
+            is a closing brace or bracket missing?",
+            fname, lines.len()),
+        _ => format!("Could not parse JSON in {}: {}", fname, err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(text: &str) -> String {
+        let err = json::parse(text).unwrap_err();
+        describe_parse_error("params.json", text, &err)
+    }
+
+    #[test]
+    fn test_parse_error_position() {
+        // trailing comma before the closing brace
+        let message = parse_error("{\n    \"iters\": 1000,\n}\n");
+        assert!(
+            message.starts_with("Could not parse JSON in params.json:3:1:"),
+            "{}", message);
+        assert!(message.ends_with("    \"iters\": 1000,\n}\n^"), "{}", message);
+
+        let message = parse_error("{\"iters\": 10 00}");
+        assert!(message.contains("params.json:1:14:"), "{}", message);
+        assert!(message.ends_with("\n             ^"), "{}", message);
+    }
+
+    #[test]
+    fn test_parse_error_end_of_file() {
+        let message = parse_error("{\n    \"iters\": 1000\n");
+        assert!(message.contains("params.json:2: unexpected end of file"));
+    }
+
+    #[test]
+    fn test_load_missing_file() {
+        let fname = std::env::temp_dir()
+            .join("chaos-game-3d-no-such-params.json");
+        let fname = fname.to_str().unwrap();
+        let message = load_json_file(fname).unwrap_err();
+        assert!(
+            message.starts_with(&format!("Could not read {}:", fname)),
+            "{}", message);
+    }
+
+    #[test]
+    fn test_load_params_edit() {
+        let path = std::env::temp_dir().join(format!(
+            "chaos-game-3d-edit-{}.json", std::process::id()));
+        fs::write(&path, "{\"iters\": 1000}").unwrap();
+        let fname = path.to_str().unwrap();
+        let json = load_params(fname, |json| json["iters"] = 10.into());
+        fs::remove_file(&path).unwrap();
+        assert_eq!(json.unwrap()["iters"], 10);
+    }
+
+    #[test]
+    fn test_load_algorithm_missing_file() {
+        let fname = std::env::temp_dir()
+            .join("chaos-game-3d-no-such-algorithm.json");
+        let fname = fname.to_str().unwrap();
+        match load_algorithm(fname, |_| ()) {
+            Err(message) => assert!(
+                message.starts_with(&format!("Could not read {}:", fname)),
+                "{}", message),
+            Ok(_) => panic!("{} should not load", fname)
+        }
+    }
+
+    #[test]
+    fn test_load_malformed_file() {
+        let path = std::env::temp_dir().join(format!(
+            "chaos-game-3d-malformed-{}.json", std::process::id()));
+        fs::write(&path, "{\"iters\": 1000,}").unwrap();
+        let fname = path.to_str().unwrap();
+        let message = load_json_file(fname).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(message.contains("unexpected character '}'"), "{}", message);
+    }
+}
@@ -1,27 +1,371 @@
+use std::fs::{create_dir_all, remove_dir_all};
+use std::path::Path;
+
 use json::JsonValue;
 
+use crate::bbox::BBox;
+use crate::checkpoint::{CheckpointReader, CheckpointWriter};
+use crate::colormap::ColorMap;
 use crate::fractal_metadata::FractalMetadata;
+use crate::half_multivector::HalfMultivector;
+use crate::live_stream::{self, LiveServer};
 use crate::octrees::OctNode;
-use crate::tileset_writer::{TilesetWriter, ContentType};
-use crate::point::{InternalPoint, OutputPoint};
+use crate::tileset_merge::merge_tagged_tilesets;
+use crate::tileset_writer::{
+    BoundingVolume, ContentType, GlbOptions, TilesetOptions, TilesetWriter,
+    Tiling, TilesVersion};
+use crate::point::{
+    CompactPoint, GlbPoint, InternalPoint, OutputPoint, TreePoint};
+use crate::png_writer::PngWriter;
+use crate::profiling::{PhaseTimer, Profile};
+use crate::quality::{self, QualityMetric};
+use crate::vector::Vec3;
+use crate::xforms::{self, Transform};
 
+/// Past this max_depth, points are inserted without Morton codes, see
+/// OctNode::add_point_morton()
+pub use crate::bbox::MAX_MORTON_DEPTH;
+/// How output colors are encoded, see the color_space option
+pub use crate::vector::ColorSpace;
 
-/// Octree-based point cloud plotter. There are different types for raw
-/// scatter plots and density plots
+/// Point cloud plotter. There are different types for octree-based
+/// scatter plots and flat preview images
 pub trait Plotter {
-    /// Plot a single point
-    fn plot_point(&mut self, point: OutputPoint);
+    /// Plot a single point. Returns whether the point was kept, and if
+    /// not, why it was discarded.
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult;
 
-    /// Plot many points from a buffer. This is just an iteration of
-    /// plot_point().
-    fn plot_points(&mut self, points: &Vec<InternalPoint>) {
-        for point in points {
-            self.plot_point(OutputPoint::from(point.clone()));
-        }
+    /// Plot a point that escaped to infinity (see
+    /// HalfMultivector::try_to_vec3()). The position is the unit direction
+    /// it escaped in. By default these points are discarded.
+    fn plot_escaped(&mut self, _point: OutputPoint) -> PlotResult {
+        PlotResult::Filtered
+    }
+
+    /// Plot many points from a buffer. By default this is just an
+    /// iteration of plot_point(), or plot_escaped() for points at infinity,
+    /// see plot_each().
+    fn plot_points(&mut self, points: &[InternalPoint]) {
+        plot_each(self, points);
     }
 
+    /// How many points were kept or discarded so far
+    fn counts(&self) -> PlotCounts;
+
     /// Save the plot to a tileset with the given directory name
     fn save(&mut self, dirname: &str, metadata: &FractalMetadata);
+
+    /// Get the bounding box of the region this plotter covers. Points
+    /// outside this box are discarded. For a BoundsPlotter, this is the box
+    /// around the points plotted so far instead.
+    fn bounds(&self) -> &BBox;
+
+    /// Whether bounds() grows to fit the points rather than discarding the
+    /// ones outside it
+    fn grows_to_fit(&self) -> bool {
+        false
+    }
+
+    /// Turn on timing of the plotter's phases for --profile
+    fn enable_profiling(&mut self);
+
+    /// Record the time spent in each of the plotter's phases
+    fn record_profile(&self, profile: &mut Profile);
+
+    /// Score the plotted points for --seed-sweep. Higher is better
+    fn quality(&self, metric: QualityMetric) -> f64;
+
+    /// The number of points in each octree leaf, for
+    /// --points-per-tile-histogram. Plotters without an octree have none.
+    fn leaf_sizes(&self) -> Vec<usize> {
+        Vec::new()
+    }
+
+    /// Roughly how many points this plotter can hold before it starts
+    /// discarding them, assuming the points lie on a set of the given
+    /// dimension (0 to 3).
+    fn capacity(&self, dimension: f64) -> usize;
+
+    /// Instead of saving to disk, consume the plotter and return all the
+    /// points that were kept. For the pnts format, only the positions,
+    /// colors and weights are kept, the other fields are zero.
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint>;
+
+    /// The per-point properties this plotter writes as an
+    /// EXT_structural_metadata class, for --echo-metadata. This is null
+    /// unless the output is .glb
+    fn point_schema_json(&self, _metadata: &FractalMetadata) -> JsonValue {
+        JsonValue::Null
+    }
+
+    /// Write everything plotted so far to a checkpoint
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter);
+
+    /// Replace the plotted points with the ones from a checkpoint. The
+    /// plotter must be configured the same way as the one that wrote it.
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader);
+}
+
+/// Plot each point of a buffer with plot_point(), or plot_escaped() for
+/// points at infinity
+fn plot_each<P: Plotter + ?Sized>(plotter: &mut P, points: &[InternalPoint]) {
+    for point in points {
+        if point.position.try_to_vec3().is_some() {
+            plotter.plot_point(OutputPoint::from(point.clone()));
+        } else {
+            let mut escaped = point.clone();
+            escaped.position = HalfMultivector::from_vec3(
+                &point.position.escape_direction());
+            // Keep the CGA components from before the point escaped
+            let mut escaped = OutputPoint::from(escaped);
+            escaped.cga_pn = point.position.conformal_components();
+            plotter.plot_escaped(escaped);
+        }
+    }
+}
+
+/// What happened to a plotted point
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlotResult {
+    /// The point was kept
+    Accepted,
+    /// The point was inside the plot, but landed in an octree leaf that is
+    /// already full at the maximum depth. This is the only result that
+    /// says the plot is saturated, see AutoStop.
+    Full,
+    /// The point never reached the octree: it was outside the bounds,
+    /// clipped, masked out or escaped to infinity
+    Filtered,
+}
+
+impl PlotResult {
+    pub fn is_accepted(self) -> bool {
+        self == Self::Accepted
+    }
+}
+
+/// Running totals of how many points a plotter kept and how many it
+/// discarded (outside the bounds, clipped, or in a full leaf)
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct PlotCounts {
+    pub accepted: usize,
+    pub discarded: usize,
+}
+
+impl PlotCounts {
+    fn record(&mut self, result: PlotResult) {
+        if result.is_accepted() {
+            self.accepted += 1;
+        } else {
+            self.discarded += 1;
+        }
+    }
+
+    /// Total number of points plotted
+    pub fn total(&self) -> usize {
+        self.accepted + self.discarded
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_usize(self.accepted);
+        writer.write_usize(self.discarded);
+    }
+
+    fn read_checkpoint(reader: &mut CheckpointReader) -> Self {
+        Self {
+            accepted: reader.read_usize(),
+            discarded: reader.read_usize(),
+        }
+    }
+}
+
+/// Checkpoints record which kind of plotter wrote them
+const SCATTER_CHECKPOINT: u8 = 0;
+const IMAGE_CHECKPOINT: u8 = 1;
+const VOXEL_CHECKPOINT: u8 = 2;
+const SPLIT_CHECKPOINT: u8 = 3;
+const BOUNDS_CHECKPOINT: u8 = 4;
+
+/// Radial clipping region. Only points whose distance from the center is
+/// in the range [min, max] are kept. This is helpful for cutting a hollow
+/// shell out of an attractor.
+pub struct RadialClip {
+    /// Minimum distance from the center (inclusive)
+    min: f32,
+    /// Maximum distance from the center (inclusive)
+    max: f32,
+}
+
+impl RadialClip {
+    /// Parse a radial clip from JSON of the form:
+    ///
+    /// ```text
+    /// {
+    ///     "min": r_min (default 0.0),
+    ///     "max": r_max (default unbounded)
+    /// }
+    /// ```
+    ///
+    /// Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        match json {
+            JsonValue::Null => None,
+            JsonValue::Object(_) => {
+                let min = json["min"].as_f32().unwrap_or(0.0);
+                let max = json["max"].as_f32().unwrap_or(f32::INFINITY);
+                if min > max {
+                    panic!("radial_clip: min must not be greater than max");
+                }
+                Some(Self { min, max })
+            },
+            _ => panic!("radial_clip must be an object or null")
+        }
+    }
+
+    /// Check if a position is within the clipping region
+    pub fn contains(&self, center: &Vec3, position: &Vec3) -> bool {
+        let distance = (*position - *center).length();
+        self.min <= distance && distance <= self.max
+    }
+}
+
+/// Flag points that escape to infinity rather than discarding them. They
+/// are plotted on a sphere of radius clamp_radius around the center in a
+/// loud color, so it is obvious when an IFS is not contractive.
+pub struct InfinityFlag {
+    clamp_radius: f32,
+    color: Vec3,
+}
+
+impl InfinityFlag {
+    /// Parse the infinity flag from JSON of the form:
+    ///
+    /// ```text
+    /// {
+    ///     "clamp_radius": r,
+    ///     "color": [r, g, b] | str (default [1, 0, 1], magenta)
+    /// }
+    /// ```
+    ///
+    /// The color can also be a string, see Vec3::color_from_json().
+    ///
+    /// clamp_radius must be less than the plotter's radius so the flagged
+    /// points fit in the octree. Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue, radius: f32) -> Option<Self> {
+        match json {
+            JsonValue::Null => None,
+            JsonValue::Object(_) => {
+                let clamp_radius = json["clamp_radius"]
+                    .as_f32()
+                    .expect("infinity: clamp_radius must be a number");
+                if clamp_radius <= 0.0 || clamp_radius >= radius {
+                    panic!(
+                        "infinity: clamp_radius must be positive and less \
+                        than the radius");
+                }
+                let color = Vec3::color_from_json(
+                    &json["color"], Vec3::new(1.0, 0.0, 1.0));
+                Some(Self { clamp_radius, color })
+            },
+            _ => panic!("infinity must be an object or null")
+        }
+    }
+
+    /// Move an escaped point onto the clamp sphere and give it the flag
+    /// color. The point's position is the direction it escaped in.
+    pub fn flag(&self, center: &Vec3, point: &mut OutputPoint) {
+        point.position = *center + point.position * self.clamp_radius;
+        point.color = self.color;
+    }
+}
+
+/// A region of space to crop the plot to. Points outside the mask are not
+/// plotted, but unlike the octree radius this does not affect the
+/// iteration, so the attractor is just cut off at the mask.
+pub enum PlotMask {
+    /// Axis-aligned box
+    Box(BBox),
+    /// Solid sphere
+    Sphere { center: Vec3, radius: f32 },
+}
+
+impl PlotMask {
+    /// Parse a plot mask from JSON of one of these forms:
+    ///
+    /// ```text
+    /// {
+    ///     "type": "box",
+    ///     "center": [x, y, z] (default [0, 0, 0]),
+    ///     "dims": [width, height, depth]
+    /// }
+    /// {
+    ///     "type": "sphere",
+    ///     "center": [x, y, z] (default [0, 0, 0]),
+    ///     "radius": r
+    /// }
+    /// ```
+    ///
+    /// Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+
+        let center = Vec3::from_json(&json["center"], Vec3::zero());
+        let mask = match json["type"].as_str() {
+            Some("box") => {
+                let dims = Vec3::from_json(&json["dims"], Vec3::zero());
+                if *dims.x() < 0.0 || *dims.y() < 0.0 || *dims.z() < 0.0 {
+                    panic!("plot_mask: dims must be non-negative");
+                }
+                Self::Box(BBox::from_center(&center, &(dims * 0.5)))
+            },
+            Some("sphere") => {
+                let radius = json["radius"]
+                    .as_f32()
+                    .expect("plot_mask: radius must be a number");
+                if radius < 0.0 {
+                    panic!("plot_mask: radius must be non-negative");
+                }
+                Self::Sphere { center, radius }
+            },
+            _ => panic!("plot_mask: type must be either box or sphere")
+        };
+        Some(mask)
+    }
+
+    /// Check if a position is inside the mask (inclusive)
+    pub fn contains(&self, position: &Vec3) -> bool {
+        match self {
+            Self::Box(bounds) => bounds.contains(position),
+            Self::Sphere { center, radius } => 
+                (*position - *center).length() <= *radius
+        }
+    }
+}
+
+/// Maximum number of times a growing octree can double in size. This
+/// prevents runaway growth from points escaping to infinity.
+const MAX_GROWTH_LEVELS: usize = 10;
+/// When rebalancing tiles, leaves can be split up to this depth even past
+/// max_depth. This stops the splitting if many points are at the same
+/// position.
+const MAX_REBALANCE_DEPTH: u8 = MAX_MORTON_DEPTH;
+/// symmetrize multiplies the number of points, so no more copies are
+/// added once the point cloud would pass this many points
+const MAX_SYMMETRIZED_POINTS: usize = 1 << 28;
+/// By default each tile above the leaves holds a quarter of the points of
+/// its children
+const DEFAULT_DECIMATION_RATIO: usize = 4;
+
+/// The output formats supported by ScatterPlot
+pub const SCATTER_FORMATS: [&str; 3] = ["pnts", "glb", "csv"];
+
+/// Check that a position has no infinite or NaN coordinates
+fn is_finite(position: &Vec3) -> bool {
+    position.x().is_finite() && 
+    position.y().is_finite() && 
+    position.z().is_finite()
 }
 
 /// Scatter plots follow the usual scheme of octrees: add points to the node.
@@ -29,22 +373,199 @@ pub trait Plotter {
 ///
 /// This plotter also has a maximum depth to prevent infinite loops if
 /// a voxel at the smallest resolution becomes full of points.
-pub struct ScatterPlot {
-    root: OctNode,
+///
+/// The octree stores points in the layout P. .pnts tiles only have
+/// positions and colors, so plotters::from_json() uses CompactPoint for
+/// them to save memory. Likewise .glb tiles use GlbPoint unless emit_cga
+/// is on.
+pub struct ScatterPlot<P: TreePoint = OutputPoint> {
+    root: OctNode<P>,
     max_depth: u8,
-    tile_type: ContentType
+    /// Maximum number of points in each node
+    node_capacity: usize,
+    tile_type: ContentType,
+    /// Explicit or implicit tiling
+    tiling: Tiling,
+    /// Optionally, discard points too close to or too far from the center
+    radial_clip: Option<RadialClip>,
+    /// Optionally, discard points outside a box or sphere
+    plot_mask: Option<PlotMask>,
+    /// Optionally, plot points that escape to infinity on a sphere
+    infinity: Option<InfinityFlag>,
+    /// If true, also write boxes.glb with a wireframe of the tiles
+    debug_boxes: bool,
+    /// Extra attributes for .glb tiles
+    glb_options: GlbOptions,
+    /// If true, grow the octree to fit points outside the root rather than
+    /// discarding them
+    grow: bool,
+    /// How many levels have been added above the original root
+    growth_levels: usize,
+    /// Center of the original root. Radial clipping is measured from here
+    /// even if the root grows.
+    center: Vec3,
+    /// How to encode the output colors
+    color_space: ColorSpace,
+    /// If set, color each point by its position, normalizing by this
+    /// radius
+    color_by_position: Option<f32>,
+    /// If set, rebalance the octree before saving so each tile has
+    /// roughly this many points
+    target_points_per_tile: Option<usize>,
+    /// If set, merge groups of sibling leaves that each have fewer than
+    /// this many points into their parent before saving
+    min_leaf_points: Option<usize>,
+    /// If set, merge points closer than this before saving
+    dedupe_epsilon: Option<f32>,
+    /// Each interior tile keeps 1 / decimation_ratio of its children's
+    /// points, see OctNode::decimate()
+    decimation_ratio: usize,
+    /// If true, decimation keeps the points with the highest weight
+    weighted_decimation: bool,
+    /// If set, the root tile is filled with this many points sampled from
+    /// the leaves after decimation
+    root_points: Option<usize>,
+    /// If true, sort the points of each tile in Morton order before saving
+    sort_morton: bool,
+    /// Transformations that complete the symmetry of the point cloud
+    /// before saving, see symmetrize()
+    symmetrize: Vec<Box<dyn Transform>>,
+    /// Orientation, version and refinement of the tileset
+    tileset_options: TilesetOptions,
+    /// How many points were kept or discarded
+    counts: PlotCounts,
+    /// Time spent adding points to the octree
+    insertion_timer: PhaseTimer,
+    /// Time spent decimating the octree
+    decimation_timer: PhaseTimer,
+    /// Time spent writing the tileset
+    writing_timer: PhaseTimer,
 }
 
-impl ScatterPlot {
+impl<P: TreePoint> ScatterPlot<P> {
     /// Load a plotter from JSON of the form:
     /// {
     ///     "type": "scatter",
-    ///     "format": "pnts" | "glb" (default "glb"),
+    ///     "format": "pnts" | "glb" | "csv" (default "glb"),
+    ///     "tiling": "explicit" | "implicit" (default "explicit"),
     ///     "max_depth": d (default 10),
     ///     "node_capacity: n (default 5000),
     ///     "subtree_levels": l (default 4)
     ///     "radius": r,
+    ///     "bounds_padding": p (default 0.0),
+    ///     "radial_clip": <RadialClip JSON> (optional),
+    ///     "plot_mask": <PlotMask JSON> (optional),
+    ///     "infinity": <InfinityFlag JSON> (optional),
+    ///     "debug_boxes": bool (default false),
+    ///     "estimate_normals": bool (default false, glb only),
+    ///     "full_barycentric": bool (default false, glb only),
+    ///     "global_feature_counts": bool (default false, glb only),
+    ///     "emit_cga": bool (default false, glb only),
+    ///     "grow": bool (default false),
+    ///     "auto_fit": bool (default false),
+    ///     "color_space": "linear" | "srgb" (default "linear"),
+    ///     "color_by_position": bool (default false),
+    ///     "target_points_per_tile": n (optional),
+    ///     "min_leaf_points": n (optional),
+    ///     "dedupe_epsilon": e (optional),
+    ///     "decimation_ratio": r (default 4),
+    ///     "weighted_decimation": bool (default false, needs dedupe_epsilon),
+    ///     "root_points": n (optional),
+    ///     "sort_points": "none" | "morton" (default "none"),
+    ///     "symmetrize": [<Transform JSON>, ...] (optional),
+    ///     "up_axis": "x" | "y" | "z" (default "z"),
+    ///     "handedness": "right" | "left" (default "right"),
+    ///     "tiles_version": "1.0" | "1.1" (optional),
+    ///     "refine": "ADD" | "REPLACE" (default "REPLACE"),
+    ///     "tight_bounds": bool (default false, explicit tiling only),
+    ///     "bounding_volume": "box" | "sphere" (default "box", explicit
+    ///         tiling only),
+    ///     "content_layout": "nested" | "flat" (default "nested")
     /// }
+    ///
+    /// The root box includes its min faces but not its max faces, so a
+    /// point exactly at x = radius would be discarded. bounds_padding grows
+    /// the root by a small amount on every side to keep such points, e.g.
+    /// for a box cluster sized to the radius.
+    ///
+    /// With grow, the root doubles in size whenever a point lands outside
+    /// of it, up to MAX_GROWTH_LEVELS times. max_depth is increased each
+    /// time so the smallest tiles stay the same size.
+    ///
+    /// With auto_fit, the radius is optional and is found by first
+    /// iterating without an octree, at twice the cost, see
+    /// algorithms::AutoFit.
+    ///
+    /// With target_points_per_tile, crowded leaves are split and sparse
+    /// siblings are merged before saving, so the tiles are more uniform in
+    /// size. Leaves may be split past max_depth.
+    ///
+    /// With min_leaf_points, near-empty tiles are pruned before saving:
+    /// wherever sibling leaves all have fewer than n points, they are
+    /// merged into their parent, see OctNode::merge_sparse_leaves(). This
+    /// happens after target_points_per_tile.
+    ///
+    /// With dedupe_epsilon, points in the same leaf that are within epsilon
+    /// of each other are merged before saving. The merged point has a
+    /// weight (the number of points merged) which is written to .glb tiles
+    /// as _WEIGHT so the density is not lost.
+    ///
+    /// decimation_ratio sets the levels of detail: each tile above the
+    /// leaves holds 1 / r of the points of its children combined. Larger
+    /// ratios make smaller tiles near the root. With weighted_decimation,
+    /// these tiles keep the points that merged the most points in dedupe
+    /// rather than every r-th point, so the dense regions stay detailed.
+    ///
+    /// With root_points, the root tile holds n points sampled evenly from
+    /// every part of the tree instead of the r-th of its children, so the
+    /// first view of the tileset is not too sparse. See
+    /// OctNode::resample_from_leaves(). With target_points_per_tile, n is
+    /// capped at the target like every other tile.
+    ///
+    /// With sort_points set to morton, the points of each tile are sorted
+    /// in Z-order within the tile so nearby points are contiguous in the
+    /// output, which helps rendering and compression.
+    ///
+    /// symmetrize completes a symmetric figure after the iteration rather
+    /// than in the IFS: for each transformation, a transformed copy of
+    /// every point is added, e.g. ["reflect", 1, 0, 0] mirrors the points
+    /// across x = 0. Each transformation copies the plotted points, not the
+    /// other copies, so list every symmetry except the identity. This
+    /// multiplies the number of points, so copies stop
+    /// once the point cloud would pass MAX_SYMMETRIZED_POINTS.
+    ///
+    /// By default, each .glb tile's featureCount is the number of unique
+    /// IDs in that tile. With global_feature_counts, every tile reports
+    /// the counts for the whole fractal instead, so styles scaled by
+    /// featureCount match from tile to tile.
+    ///
+    /// color_by_position replaces each point's color with its position,
+    /// mapping x, y and z from [-radius, radius] to red, green and blue in
+    /// [0, 1]. Points outside that range (e.g. with grow) are clamped.
+    /// Escaped points keep the infinity flag color.
+    ///
+    /// emit_cga adds each point's CGA vector from before it was
+    /// homogenized to the .glb tiles, see GlbWriter::set_cga(). This is
+    /// meant for studying the conformal weight of the dynamics.
+    ///
+    /// tiles_version "1.0" writes a plain 3D Tiles 1.0 tileset with no
+    /// extensions (and so no fractal metadata) for older viewers. This
+    /// requires pnts format and explicit tiling.
+    ///
+    /// refine sets how tiles are refined in the viewer, see Refine for how
+    /// this interacts with decimation.
+    ///
+    /// tight_bounds shrinks each tile's bounding volume to the points in its
+    /// subtree, and bounding_volume sets its shape, see
+    /// TilesetOptions::from_json().
+    ///
+    /// content_layout "flat" writes every tile's content to a single
+    /// content/ directory named by its implicit coordinates, instead of a
+    /// points/ directory tree that mirrors the octree.
+    ///
+    /// With infinity, points that escape to infinity are plotted on a
+    /// sphere in a flag color instead of being discarded. They skip the
+    /// radial clip and plot mask so they are always visible.
     pub fn from_json(json: &JsonValue) -> Self {
         let format = json["format"]
             .as_str()
@@ -52,7 +573,17 @@ impl ScatterPlot {
         let tile_type = match format {
             "pnts" => ContentType::Pnts,
             "glb" => ContentType::Glb,
-            _ => panic!("format must be either pnts or glb")
+            "csv" => ContentType::Csv,
+            _ => panic!("format must be one of {:?}", SCATTER_FORMATS)
+        };
+
+        let tiling_type = json["tiling"]
+            .as_str()
+            .unwrap_or("explicit");
+        let tiling = match tiling_type {
+            "explicit" => Tiling::Explicit,
+            "implicit" => Tiling::Implicit,
+            _ => panic!("tiling must be either explicit or implicit")
         };
 
         let max_depth = json["max_depth"].as_u8().unwrap_or(10);
@@ -61,51 +592,2308 @@ impl ScatterPlot {
             .as_f32()
             .expect("radius must be a float");
         let subtree_levels = json["subtree_levels"].as_usize().unwrap_or(5);
+        let radial_clip = RadialClip::from_json(&json["radial_clip"]);
+        let plot_mask = PlotMask::from_json(&json["plot_mask"]);
+        let infinity = InfinityFlag::from_json(&json["infinity"], radius);
+        let debug_boxes = json["debug_boxes"].as_bool().unwrap_or(false);
+        let estimate_normals = 
+            json["estimate_normals"].as_bool().unwrap_or(false);
+        if estimate_normals && tile_type != ContentType::Glb {
+            panic!("estimate_normals is only supported for glb format");
+        }
+        let full_barycentric =
+            json["full_barycentric"].as_bool().unwrap_or(false);
+        if full_barycentric && tile_type != ContentType::Glb {
+            panic!("full_barycentric is only supported for glb format");
+        }
+        let global_feature_counts =
+            json["global_feature_counts"].as_bool().unwrap_or(false);
+        if global_feature_counts && tile_type != ContentType::Glb {
+            panic!("global_feature_counts is only supported for glb format");
+        }
+        let emit_cga = json["emit_cga"].as_bool().unwrap_or(false);
+        if emit_cga && tile_type != ContentType::Glb {
+            panic!("emit_cga is only supported for glb format");
+        }
+        let dedupe_epsilon = json["dedupe_epsilon"].as_f32();
+        if let Some(epsilon) = dedupe_epsilon {
+            if epsilon <= 0.0 {
+                panic!("dedupe_epsilon must be positive");
+            }
+        }
+        let decimation_ratio = json["decimation_ratio"]
+            .as_usize()
+            .unwrap_or(DEFAULT_DECIMATION_RATIO);
+        if decimation_ratio < 2 {
+            panic!("decimation_ratio must be an integer of at least 2");
+        }
+        let weighted_decimation =
+            json["weighted_decimation"].as_bool().unwrap_or(false);
+        if weighted_decimation && dedupe_epsilon.is_none() {
+            panic!("weighted_decimation requires dedupe_epsilon, otherwise \
+                every point has weight 1");
+        }
+        let root_points = match &json["root_points"] {
+            JsonValue::Null => None,
+            x => Some(x
+                .as_usize()
+                .filter(|root_points| *root_points > 0)
+                .expect("root_points must be a positive integer"))
+        };
+        let sort_morton = match json["sort_points"].as_str().unwrap_or("none") {
+            "none" => false,
+            "morton" => true,
+            _ => panic!("sort_points must be either none or morton")
+        };
+        let symmetrize = json["symmetrize"]
+            .members()
+            .map(xforms::from_json)
+            .collect();
+        let tileset_options = TilesetOptions::from_json(json);
+        if tileset_options.tiles_version == Some(TilesVersion::Legacy) {
+            if tile_type != ContentType::Pnts {
+                panic!("tiles_version 1.0 requires pnts format, \
+                    glb content needs 3D Tiles 1.1");
+            }
+            if tiling != Tiling::Explicit {
+                panic!("tiles_version 1.0 requires explicit tiling, \
+                    implicit tiling needs 3D Tiles 1.1");
+            }
+        }
+        if tileset_options.tight_bounds && tiling != Tiling::Explicit {
+            panic!("tight_bounds requires explicit tiling, implicit tiles \
+                are always the octants");
+        }
+        if tileset_options.bounding_volume == BoundingVolume::Sphere &&
+                tiling != Tiling::Explicit {
+            panic!("sphere bounding volumes require explicit tiling, \
+                implicit tiles are always boxes");
+        }
+        let glb_options = GlbOptions {
+            estimate_normals,
+            full_barycentric,
+            weights: dedupe_epsilon.is_some(),
+            global_feature_counts,
+            emit_cga,
+        };
+
+        let grow = json["grow"].as_bool().unwrap_or(false);
+        let color_space = ColorSpace::from_json(&json["color_space"]);
+        let color_by_position =
+            if json["color_by_position"].as_bool().unwrap_or(false) {
+                Some(radius)
+            } else {
+                None
+            };
+        let target_points_per_tile = json["target_points_per_tile"].as_usize();
+        if target_points_per_tile == Some(0) {
+            panic!("target_points_per_tile must be a positive integer");
+        }
+        // The root is a tile too, so it is capped like the others
+        let root_points = match (root_points, target_points_per_tile) {
+            (Some(n), Some(target)) => Some(n.min(target)),
+            (n, _) => n
+        };
+        let min_leaf_points = match &json["min_leaf_points"] {
+            JsonValue::Null => None,
+            x => Some(x
+                .as_usize()
+                .filter(|min_points| *min_points > 0)
+                .expect("min_leaf_points must be a positive integer"))
+        };
 
-        let root = OctNode::root_node(radius, capacity, subtree_levels);
+        let bounds_padding = json["bounds_padding"].as_f32().unwrap_or(0.0);
+        if bounds_padding < 0.0 {
+            panic!("bounds_padding must be non-negative");
+        }
+        let bounds = BBox::new(
+            -radius, radius,
+            -radius, radius,
+            -radius, radius).padded(bounds_padding);
+        let root = OctNode::root_with_bounds(bounds, capacity, subtree_levels);
+        let center = root.center();
 
         Self {
             root,
             max_depth,
-            tile_type
+            node_capacity: capacity,
+            tile_type,
+            tiling,
+            radial_clip,
+            plot_mask,
+            infinity,
+            debug_boxes,
+            glb_options,
+            grow,
+            growth_levels: 0,
+            center,
+            color_space,
+            color_by_position,
+            target_points_per_tile,
+            min_leaf_points,
+            dedupe_epsilon,
+            decimation_ratio,
+            weighted_decimation,
+            root_points,
+            sort_morton,
+            symmetrize,
+            tileset_options,
+            counts: PlotCounts::default(),
+            insertion_timer: PhaseTimer::new(false),
+            decimation_timer: PhaseTimer::new(false),
+            writing_timer: PhaseTimer::new(false),
         }
     }
 
     to_box!(Plotter);
+
+    /// Add a point to the octree once it has passed the clipping and
+    /// its color is encoded
+    fn insert(&mut self, point: P) -> PlotResult {
+        // The octree discards points outside the root too, but those don't
+        // mean a leaf is full
+        if !self.root.bounds().contains(point.position()) {
+            return PlotResult::Filtered;
+        }
+
+        // The Morton code path is faster, but not for a growing root
+        let accepted = if self.grow || self.max_depth > MAX_MORTON_DEPTH {
+            self.root.add_point(point, self.max_depth)
+        } else {
+            self.root.add_point_morton(point, self.max_depth)
+        };
+        if accepted {
+            PlotResult::Accepted
+        } else {
+            PlotResult::Full
+        }
+    }
+
+    /// Apply the radial clip and plot mask, and grow the root to fit the
+    /// position if enabled. Returns false if the point must be discarded.
+    fn clip(&mut self, position: &Vec3) -> bool {
+        if let Some(clip) = &self.radial_clip {
+            if !clip.contains(&self.center, position) {
+                return false;
+            }
+        }
+
+        if let Some(mask) = &self.plot_mask {
+            if !mask.contains(position) {
+                return false;
+            }
+        }
+
+        if self.grow && is_finite(position) {
+            let levels = self.root.grow_to_contain(
+                position, MAX_GROWTH_LEVELS - self.growth_levels);
+            self.growth_levels += levels;
+            self.max_depth += levels as u8;
+        }
+        true
+    }
+
+    /// Clip the point and add it to the octree
+    fn clip_and_insert(&mut self, mut point: OutputPoint) -> PlotResult {
+        if !self.clip(&point.position) {
+            return PlotResult::Filtered;
+        }
+
+        if let Some(radius) = self.color_by_position {
+            point.color = position_color(&point.position, radius);
+        }
+        point.color = self.color_space.encode(&point.color);
+
+        let start = self.insertion_timer.start();
+        let accepted = self.insert(P::from(point));
+        self.insertion_timer.stop(start);
+        accepted
+    }
+
+    /// Check if every point of a buffer would be discarded for being
+    /// outside the root. This stops at the first point inside it. Escaped
+    /// points count as outside since they are discarded too. This is never
+    /// the case when the root grows or escaped points are flagged, since
+    /// those keep points outside the root.
+    fn is_outside_root(&self, points: &[InternalPoint]) -> bool {
+        if self.grow || self.infinity.is_some() {
+            return false;
+        }
+
+        let bounds = self.root.bounds();
+        !points
+            .iter()
+            .filter_map(|point| point.position.try_to_vec3())
+            .any(|position| bounds.contains(&position))
+    }
+
+    /// Add a transformed copy of every plotted point for each of the
+    /// symmetrize transformations. The copies go through the same clipping
+    /// as plotted points, but their colors are already encoded. This only
+    /// runs once, the transformations are used up.
+    fn symmetrize(&mut self) {
+        let xforms = std::mem::take(&mut self.symmetrize);
+        if xforms.is_empty() {
+            return;
+        }
+
+        let mut kept = Vec::new();
+        self.root.collect_points(&mut kept);
+        let originals: Vec<OutputPoint> =
+            kept.into_iter().map(|point| point.clone().into()).collect();
+
+        for xform in xforms.iter() {
+            let total = self.counts.accepted + originals.len();
+            if total > MAX_SYMMETRIZED_POINTS {
+                info!(
+                    "Warning: symmetrize stopped before {} to keep the \
+                    point cloud under {} points",
+                    xform.name(), MAX_SYMMETRIZED_POINTS);
+                break;
+            }
+
+            for original in originals.iter() {
+                let position = HalfMultivector::from_vec3(&original.position);
+                let result = match xform.transform(&position).try_to_vec3() {
+                    Some(position) => self.insert_copy(OutputPoint {
+                        position,
+                        ..original.clone()
+                    }),
+                    None => PlotResult::Filtered
+                };
+                self.counts.record(result);
+            }
+        }
+    }
+
+    /// Clip a symmetrized copy of a point and add it to the octree
+    fn insert_copy(&mut self, mut point: OutputPoint) -> PlotResult {
+        if !self.clip(&point.position) {
+            return PlotResult::Filtered;
+        }
+
+        if let Some(radius) = self.color_by_position {
+            let color = position_color(&point.position, radius);
+            point.color = self.color_space.encode(&color);
+        }
+
+        let start = self.insertion_timer.start();
+        let accepted = self.insert(P::from(point));
+        self.insertion_timer.stop(start);
+        accepted
+    }
+
+    /// Flag an escaped point and add it to the octree, if enabled
+    fn insert_escaped(&mut self, mut point: OutputPoint) -> PlotResult {
+        let flag = match &self.infinity {
+            Some(flag) => flag,
+            None => return PlotResult::Filtered
+        };
+        flag.flag(&self.center, &mut point);
+        point.color = self.color_space.encode(&point.color);
+
+        let start = self.insertion_timer.start();
+        let accepted = self.insert(P::from(point));
+        self.insertion_timer.stop(start);
+        accepted
+    }
+}
+
+/// Map a position in [-radius, radius]^3 to a color in [0, 1]^3
+fn position_color(position: &Vec3, radius: f32) -> Vec3 {
+    let color = (*position * (0.5 / radius)) + Vec3::splat(0.5);
+    Vec3::max(&Vec3::min(&color, &Vec3::ones()), &Vec3::zero())
 }
 
-impl Plotter for ScatterPlot {
-    fn plot_point(&mut self, point: OutputPoint) {
-        self.root.add_point(point, self.max_depth);
+impl<P: TreePoint> Plotter for ScatterPlot<P> {
+    /// Like the default, but a buffer that is entirely outside the root is
+    /// discarded all at once without inserting each point. For chaos sets,
+    /// this is common where the IFS expands space.
+    fn plot_points(&mut self, points: &[InternalPoint]) {
+        if self.is_outside_root(points) {
+            self.counts.discarded += points.len();
+            return;
+        }
+        plot_each(self, points);
+    }
+
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        let result = self.clip_and_insert(point);
+        self.counts.record(result);
+        result
+    }
+
+    fn plot_escaped(&mut self, point: OutputPoint) -> PlotResult {
+        let result = self.insert_escaped(point);
+        self.counts.record(result);
+        result
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.counts
     }
 
     /// Save the tileset into a directory of the given name. This creates
     /// the directory if it does not already exist
     fn save(&mut self, tileset_id: &str, metadata: &FractalMetadata) {
+        self.symmetrize();
+
         // Decimate the mesh recursively to generate LODs
-        self.root.decimate();
+        let start = self.decimation_timer.start();
+        if let Some(epsilon) = self.dedupe_epsilon {
+            self.root.dedupe(epsilon, self.color_space);
+            verbose!(
+                "Deduplicated to {} points", self.root.leaf_point_count());
+        }
+        if let Some(target) = self.target_points_per_tile {
+            let max_depth = self.max_depth.max(MAX_REBALANCE_DEPTH);
+            self.root.rebalance(target, 0, max_depth);
+            verbose!("Rebalanced to {} points per tile", target);
+        }
+        if let Some(min_points) = self.min_leaf_points {
+            self.root.merge_sparse_leaves(min_points);
+            verbose!(
+                "Merged sparse leaves, {} leaves left",
+                self.root.leaf_sizes().len());
+        }
+        self.root.decimate(self.decimation_ratio, self.weighted_decimation);
+        verbose!("Decimated with ratio {}", self.decimation_ratio);
+        if let Some(target) = self.target_points_per_tile {
+            self.root.cap_points(target);
+            verbose!("Capped tiles at {} points", target);
+        }
+        if let Some(root_points) = self.root_points {
+            self.root.resample_from_leaves(root_points);
+            verbose!(
+                "Resampled {} root points from the leaves", root_points);
+        }
+        if self.sort_morton {
+            self.root.sort_morton();
+            verbose!("Sorted tile points in Morton order");
+        }
+        self.decimation_timer.stop(start);
+
         let writer = TilesetWriter::new(
             tileset_id,
             self.tile_type.clone(),
-            metadata.clone());
+            self.tiling.clone(),
+            metadata.clone(),
+            self.glb_options.clone(),
+            self.tileset_options);
+        let start = self.writing_timer.start();
         writer.save(&self.root);
+        if self.debug_boxes {
+            writer.save_debug_boxes(&self.root);
+        }
+        self.writing_timer.stop(start);
+    }
+
+    fn bounds(&self) -> &BBox {
+        self.root.bounds()
+    }
+
+    fn enable_profiling(&mut self) {
+        self.insertion_timer = PhaseTimer::new(true);
+        self.decimation_timer = PhaseTimer::new(true);
+        self.writing_timer = PhaseTimer::new(true);
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        profile.insertion = self.insertion_timer.elapsed();
+        profile.decimation = self.decimation_timer.elapsed();
+        profile.writing = self.writing_timer.elapsed();
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        match metric {
+            QualityMetric::Dimension => {
+                let mut positions = Vec::new();
+                quality::collect_positions(&self.root, &mut positions);
+                quality::box_counting_dimension(&positions, self.root.bounds())
+            },
+            QualityMetric::FilledLeaves =>
+                quality::count_filled_leaves(&self.root) as f64
+        }
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.root.leaf_sizes()
+    }
+
+    fn into_points(mut self: Box<Self>) -> Vec<OutputPoint> {
+        self.symmetrize();
+        self.root.into_points()
+    }
+
+    fn point_schema_json(&self, metadata: &FractalMetadata) -> JsonValue {
+        match self.tile_type {
+            ContentType::Glb =>
+                self.glb_options.make_writer(metadata).make_class_json(),
+            _ => JsonValue::Null
+        }
+    }
+
+    /// The octree is stored as its root bounds (which may have grown) and
+    /// the points in its leaves, with colors already encoded. The tree
+    /// structure is rebuilt by inserting the points again.
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_u8(SCATTER_CHECKPOINT);
+        writer.write_bbox(self.root.bounds());
+        writer.write_u8(self.max_depth);
+        writer.write_usize(self.growth_levels);
+        self.counts.write_checkpoint(writer);
+
+        let mut points = Vec::new();
+        self.root.collect_points(&mut points);
+        writer.write_usize(points.len());
+        for point in points {
+            writer.write_output_point(&point.clone().into());
+        }
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        if reader.read_u8() != SCATTER_CHECKPOINT {
+            panic!("The checkpoint was not written by a scatter plotter");
+        }
+        let bounds = reader.read_bbox();
+        let subtree_levels =
+            self.root.get_implicit_coordinates().subtree_levels;
+        self.root = OctNode::root_with_bounds(
+            bounds, self.node_capacity, subtree_levels);
+        self.max_depth = reader.read_u8();
+        self.growth_levels = reader.read_usize();
+        self.counts = PlotCounts::read_checkpoint(reader);
+
+        let count = reader.read_usize();
+        for _ in 0..count {
+            let point = reader.read_output_point();
+            self.insert(P::from(point));
+        }
+    }
+
+    /// A set of dimension D touches about (2^max_depth)^D of the deepest
+    /// leaves, and each leaf holds node_capacity points. For D = 3 this is
+    /// the full node_capacity * 8^max_depth
+    fn capacity(&self, dimension: f64) -> usize {
+        let leaves = 2.0f64.powf(self.max_depth as f64 * dimension);
+        let capacity = self.node_capacity as f64 * leaves;
+        if capacity >= usize::MAX as f64 {
+            usize::MAX
+        } else {
+            capacity as usize
+        }
     }
 }
 
-/// Parse a point cloud plotter from a JSON object of the form:
+/// How an ImagePlotter turns the accumulated points into pixel colors
+pub enum ColorMode {
+    /// The average color of the points in each pixel, brightened by
+    /// density
+    PointColor,
+    /// Ignore the point colors and color by density through a gradient
+    Gradient(ColorMap),
+}
+
+impl ColorMode {
+    /// Parse "color" or anything ColorMap::from_json() accepts
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str() {
+            None if json.is_null() => Self::PointColor,
+            Some("color") => Self::PointColor,
+            _ => Self::Gradient(ColorMap::from_json(json))
+        }
+    }
+}
+
+/// Orthographic projection of the fractal into a flat PNG image, good for
+/// quick previews and thumbnails without a 3D viewer. Points that land in
+/// the same pixel are blended additively, and the result is tone mapped
+/// by log density (like flame fractals) so sparse regions are still
+/// visible.
 ///
-/// ```text
-/// {
-///     "type": "scatter" (default "scatter"),
-///     ...params
-/// }
-/// ```
-pub fn from_json(json: &JsonValue) -> Box<dyn Plotter> {
-    let valid_plotters: Vec<&str> = vec!["scatter"];
-    let plotter_type = &json["type"].as_str().unwrap_or("scatter");
+/// This doesn't use the octree at all.
+pub struct ImagePlotter {
+    /// Width of the image in pixels
+    width: usize,
+    /// Height of the image in pixels
+    height: usize,
+    /// Which axis to look down (0, 1, 2 for x, y, z)
+    axis: usize,
+    /// The region of space being imaged. Points outside are discarded.
+    bounds: BBox,
+    /// Half the height of the image in world units. The half-width is
+    /// scaled by the aspect ratio
+    radius: f32,
+    /// How to color the pixels
+    color_mode: ColorMode,
+    /// Sum of the colors of the points in each pixel
+    color_sums: Vec<[f64; 3]>,
+    /// Number of points in each pixel
+    counts: Vec<u32>,
+    /// How many points landed in the image or were discarded
+    plot_counts: PlotCounts,
+    /// Time spent adding points to the image
+    insertion_timer: PhaseTimer,
+    /// Time spent writing the image
+    writing_timer: PhaseTimer,
+}
 
-    match &plotter_type[..] {
-        "scatter" => ScatterPlot::from_json(&json).to_box(),
-        _ => panic!("Plotter type must be one of, {:?}", valid_plotters)
+impl ImagePlotter {
+    /// Load a plotter from JSON of the form:
+    /// {
+    ///     "type": "image",
+    ///     "width": w (default 512),
+    ///     "height": h (default 512),
+    ///     "axis": "x" | "y" | "z" (default "z", i.e. top-down),
+    ///     "radius": r,
+    ///     "colormap": "color" | colormap (default "color")
+    /// }
+    ///
+    /// Any other colormap, e.g. "heat", colors by density only, see
+    /// ColorMap::from_json()
+    ///
+    /// The image is saved to ./viewer/{id}/preview.png
+    pub fn from_json(json: &JsonValue) -> Self {
+        let width = json["width"].as_usize().unwrap_or(512);
+        let height = json["height"].as_usize().unwrap_or(512);
+        if width == 0 || height == 0 {
+            panic!("width and height must be positive");
+        }
+
+        let axis = match json["axis"].as_str().unwrap_or("z") {
+            "x" => 0,
+            "y" => 1,
+            "z" => 2,
+            _ => panic!("axis must be one of x, y or z")
+        };
+        let radius = json["radius"]
+            .as_f32()
+            .expect("radius must be a float");
+        let color_mode = ColorMode::from_json(&json["colormap"]);
+
+        // The image covers a slab that is as deep as it is tall. The
+        // horizontal axis of the image is stretched by the aspect ratio.
+        let aspect = width as f32 / height as f32;
+        let mut half_widths = [radius; 3];
+        half_widths[(axis + 1) % 3] = radius * aspect;
+        let bounds = BBox::from_center(
+            &Vec3::zero(),
+            &Vec3::new(half_widths[0], half_widths[1], half_widths[2]));
+
+        Self {
+            width,
+            height,
+            axis,
+            bounds,
+            radius,
+            color_mode,
+            color_sums: vec![[0.0; 3]; width * height],
+            counts: vec![0; width * height],
+            plot_counts: PlotCounts::default(),
+            insertion_timer: PhaseTimer::new(false),
+            writing_timer: PhaseTimer::new(false),
+        }
+    }
+
+    /// Project a position onto the image. The two axes perpendicular to
+    /// the view axis are used in cyclic order, so looking down z the image
+    /// is (x, y), looking down x it is (y, z) and looking down y it is
+    /// (z, x). Returns None if the point is outside the image.
+    fn project(&self, position: &Vec3) -> Option<usize> {
+        if !self.bounds.contains(position) {
+            return None;
+        }
+
+        let coords = [*position.x(), *position.y(), *position.z()];
+        let u = coords[(self.axis + 1) % 3];
+        let v = coords[(self.axis + 2) % 3];
+
+        let aspect = self.width as f32 / self.height as f32;
+        let s = (u / (self.radius * aspect) + 1.0) * 0.5;
+        // Rows go from top to bottom
+        let t = (1.0 - v / self.radius) * 0.5;
+
+        let column = ((s * self.width as f32) as usize).min(self.width - 1);
+        let row = ((t * self.height as f32) as usize).min(self.height - 1);
+        Some(row * self.width + column)
+    }
+
+    /// Tone map the accumulated points into pixel colors
+    fn make_pixels(&self) -> Vec<[u8; 3]> {
+        let max_count = self.counts.iter().cloned().max().unwrap_or(0);
+        let log_max = (1.0 + max_count as f64).ln();
+
+        self.counts.iter().zip(self.color_sums.iter()).map(|(count, sum)| {
+            if *count == 0 {
+                return [0, 0, 0];
+            }
+
+            let brightness = (1.0 + *count as f64).ln() / log_max;
+            let color = match &self.color_mode {
+                ColorMode::PointColor => {
+                    let n = *count as f64;
+                    [
+                        brightness * sum[0] / n,
+                        brightness * sum[1] / n,
+                        brightness * sum[2] / n
+                    ]
+                },
+                ColorMode::Gradient(gradient) => {
+                    let color = gradient.sample(brightness);
+                    [*color.x() as f64, *color.y() as f64, *color.z() as f64]
+                }
+            };
+            [to_byte(color[0]), to_byte(color[1]), to_byte(color[2])]
+        }).collect()
+    }
+
+    to_box!(Plotter);
+}
+
+impl Plotter for ImagePlotter {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        let start = self.insertion_timer.start();
+        let index = self.project(&point.position);
+        if let Some(pixel) = index {
+            self.counts[pixel] += 1;
+            let sum = &mut self.color_sums[pixel];
+            sum[0] += *point.color.x() as f64;
+            sum[1] += *point.color.y() as f64;
+            sum[2] += *point.color.z() as f64;
+        }
+        self.insertion_timer.stop(start);
+        let result = match index {
+            Some(_) => PlotResult::Accepted,
+            None => PlotResult::Filtered
+        };
+        self.plot_counts.record(result);
+        result
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.plot_counts
+    }
+
+    fn save(&mut self, tileset_id: &str, _metadata: &FractalMetadata) {
+        let start = self.writing_timer.start();
+        let dirname = format!("./viewer/{}", tileset_id);
+        create_dir_all(&dirname).expect("could not create directory");
+
+        let fname = format!("{}/preview.png", dirname);
+        info!("Generating preview image {}", fname);
+        let writer = PngWriter::new(self.width as u32, self.height as u32);
+        writer.write(&fname, &self.make_pixels());
+        self.writing_timer.stop(start);
+    }
+
+    fn bounds(&self) -> &BBox {
+        &self.bounds
+    }
+
+    fn enable_profiling(&mut self) {
+        self.insertion_timer = PhaseTimer::new(true);
+        self.writing_timer = PhaseTimer::new(true);
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        profile.insertion = self.insertion_timer.elapsed();
+        profile.writing = self.writing_timer.elapsed();
+    }
+
+    /// For images, the filled "leaves" are the non-empty pixels
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        let occupied: Vec<(u32, u32)> = self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, _)| ((i % self.width) as u32, (i / self.width) as u32))
+            .collect();
+
+        match metric {
+            QualityMetric::Dimension => {
+                let levels = self.width.min(self.height).ilog2() as u8;
+                quality::image_box_counting_dimension(&occupied, levels)
+            },
+            QualityMetric::FilledLeaves => occupied.len() as f64
+        }
+    }
+
+    /// Images only accumulate counts per pixel, so they never run out
+    /// of room
+    fn capacity(&self, _dimension: f64) -> usize {
+        usize::MAX
+    }
+
+    /// Only the pixel totals are kept, not the points themselves
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        panic!("image plotters do not keep the individual points");
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_u8(IMAGE_CHECKPOINT);
+        self.plot_counts.write_checkpoint(writer);
+        writer.write_usize(self.counts.len());
+        for (count, sum) in self.counts.iter().zip(self.color_sums.iter()) {
+            writer.write_u32(*count);
+            for component in sum.iter() {
+                writer.write_f64(*component);
+            }
+        }
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        if reader.read_u8() != IMAGE_CHECKPOINT {
+            panic!("The checkpoint was not written by an image plotter");
+        }
+        self.plot_counts = PlotCounts::read_checkpoint(reader);
+        let pixels = reader.read_usize();
+        if pixels != self.counts.len() {
+            panic!(
+                "The checkpoint has {} pixels but the image has {}",
+                pixels, self.counts.len());
+        }
+        for (count, sum) in self.counts.iter_mut().zip(self.color_sums.iter_mut()) {
+            *count = reader.read_u32();
+            for component in sum.iter_mut() {
+                *component = reader.read_f64();
+            }
+        }
+    }
+}
+
+/// Voxel grids larger than this many bytes are refused rather than risk
+/// running out of memory partway through the run. This is a u64 since
+/// 4 GiB doesn't fit in a 32-bit usize.
+const MAX_VOXEL_BYTES: u64 = 4 << 30;
+
+/// The points that landed in one cell of a VoxelPlot
+#[derive(Clone, Copy, Default)]
+struct Voxel {
+    /// Number of points in this voxel
+    count: u32,
+    /// Sum of the colors of the points in this voxel. Single precision
+    /// keeps the grid small, which matters more here than for images.
+    color_sum: [f32; 3],
+}
+
+/// Dense voxel grid of N^3 cells covering the cube [-r, r]^3. Each point
+/// is binned in constant time, so this is good for sampling a uniform
+/// density field, e.g. for a volume renderer.
+///
+/// Like ImagePlotter, this doesn't use the octree. On save, the occupied
+/// voxels are either written as points (one per voxel) with a scatter
+/// plotter, or as a raw volume of counts.
+pub struct VoxelPlot {
+    /// Number of voxels along each axis
+    resolution: usize,
+    /// The cube being sampled. Points outside are discarded.
+    bounds: BBox,
+    /// The voxels in x-major order, i.e. index (z * N + y) * N + x
+    voxels: Vec<Voxel>,
+    /// Writes the occupied voxels as points on save, or None to write a
+    /// raw volume
+    points: Option<Box<dyn Plotter>>,
+    /// How many points landed in the grid or were discarded
+    plot_counts: PlotCounts,
+    /// Time spent binning points
+    insertion_timer: PhaseTimer,
+    /// Time spent writing the voxels
+    writing_timer: PhaseTimer,
+}
+
+impl VoxelPlot {
+    /// Load a plotter from JSON of the form:
+    /// {
+    ///     "type": "voxel",
+    ///     "radius": r,
+    ///     "resolution": N,
+    ///     "format": "raw" | <ScatterPlot format> (default "glb"),
+    ///     ...<ScatterPlot params>
+    /// }
+    ///
+    /// With the "raw" format, the counts are saved to
+    /// ./viewer/{id}/density.raw as N^3 little-endian u32 in x-major
+    /// order. Otherwise, each occupied voxel becomes a point at its center
+    /// with the average color and a weight of its count, and these are
+    /// saved like a scatter plot with the same JSON.
+    ///
+    /// The grid takes 16 bytes per voxel, it is refused if that comes to
+    /// more than MAX_VOXEL_BYTES.
+    pub fn from_json(json: &JsonValue) -> Self {
+        let radius = json["radius"]
+            .as_f32()
+            .expect("radius must be a float");
+        let resolution = json["resolution"]
+            .as_usize()
+            .filter(|resolution| *resolution > 0)
+            .expect("resolution must be a positive integer");
+        let voxel_count = resolution
+            .checked_pow(3)
+            .filter(|count| {
+                count.checked_mul(std::mem::size_of::<Voxel>())
+                    .is_some_and(|bytes| bytes as u64 <= MAX_VOXEL_BYTES)
+            })
+            .unwrap_or_else(|| panic!(
+                "a voxel grid of resolution {} needs more than the {} MiB \
+                limit",
+                resolution,
+                MAX_VOXEL_BYTES >> 20));
+        info!(
+            "Voxel grid of {}^3 voxels uses about {:.1} MiB",
+            resolution,
+            (voxel_count * std::mem::size_of::<Voxel>()) as f64 / 1048576.0);
+
+        let points = match json["format"].as_str() {
+            Some("raw") => None,
+            _ => Some(scatter_from_json(json))
+        };
+
+        Self {
+            resolution,
+            bounds: BBox::from_center(&Vec3::zero(), &Vec3::splat(radius)),
+            voxels: vec![Voxel::default(); voxel_count],
+            points,
+            plot_counts: PlotCounts::default(),
+            insertion_timer: PhaseTimer::new(false),
+            writing_timer: PhaseTimer::new(false),
+        }
+    }
+
+    /// Find the voxel a position lands in, or None if the position is
+    /// outside the grid
+    fn voxel_index(&self, position: &Vec3) -> Option<usize> {
+        if !self.bounds.contains(position) {
+            return None;
+        }
+
+        let n = self.resolution;
+        let min = self.bounds.min();
+        let size = *self.bounds.max().x() - *min.x();
+        let cell = |coord: f32, min: f32| {
+            let t = (coord - min) / size;
+            ((t * n as f32) as usize).min(n - 1)
+        };
+        let x = cell(*position.x(), *min.x());
+        let y = cell(*position.y(), *min.y());
+        let z = cell(*position.z(), *min.z());
+        Some((z * n + y) * n + x)
+    }
+
+    /// The center of the voxel at the given index
+    fn voxel_center(&self, index: usize) -> Vec3 {
+        let n = self.resolution;
+        let min = self.bounds.min();
+        let size = *self.bounds.max().x() - *min.x();
+        let center = |cell: usize, min: f32|
+            min + (cell as f32 + 0.5) / n as f32 * size;
+        Vec3::new(
+            center(index % n, *min.x()),
+            center(index / n % n, *min.y()),
+            center(index / (n * n), *min.z()))
+    }
+
+    /// One point per occupied voxel, at the voxel center with the average
+    /// color. The weight is the number of points in the voxel.
+    fn make_points(&self) -> Vec<OutputPoint> {
+        self.voxels
+            .iter()
+            .enumerate()
+            .filter(|(_, voxel)| voxel.count > 0)
+            .map(|(i, voxel)| {
+                let n = voxel.count as f32;
+                let [r, g, b] = voxel.color_sum;
+                OutputPoint {
+                    position: self.voxel_center(i),
+                    color: Vec3::new(r / n, g / n, b / n),
+                    cluster_coordinates: Vec3::zero(),
+                    iteration: 0,
+                    cluster_copy: 0,
+                    cluster_id: 0,
+                    point_id: i as u32,
+                    last_xform: 0,
+                    last_xform_group: 0,
+                    last_color_xform: 0,
+                    xform_address: 0,
+                    cga_pn: [0.0, 0.0],
+                    weight: voxel.count
+                }
+            })
+            .collect()
+    }
+
+    /// Write the counts as a raw volume, see from_json()
+    fn save_raw(&self, tileset_id: &str) {
+        let dirname = format!("./viewer/{}", tileset_id);
+        create_dir_all(&dirname).expect("could not create directory");
+
+        let fname = format!("{}/density.raw", dirname);
+        info!(
+            "Generating {}^3 voxel volume {}", self.resolution, fname);
+        let bytes: Vec<u8> = self.voxels
+            .iter()
+            .flat_map(|voxel| voxel.count.to_le_bytes())
+            .collect();
+        std::fs::write(&fname, bytes).expect("could not write volume");
+    }
+
+    to_box!(Plotter);
+}
+
+impl Plotter for VoxelPlot {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        let start = self.insertion_timer.start();
+        let index = self.voxel_index(&point.position);
+        if let Some(index) = index {
+            let voxel = &mut self.voxels[index];
+            voxel.count += 1;
+            voxel.color_sum[0] += *point.color.x();
+            voxel.color_sum[1] += *point.color.y();
+            voxel.color_sum[2] += *point.color.z();
+        }
+        self.insertion_timer.stop(start);
+        let result = match index {
+            Some(_) => PlotResult::Accepted,
+            None => PlotResult::Filtered
+        };
+        self.plot_counts.record(result);
+        result
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.plot_counts
+    }
+
+    fn save(&mut self, tileset_id: &str, metadata: &FractalMetadata) {
+        let start = self.writing_timer.start();
+        let voxel_points = self.make_points();
+        match self.points.as_mut() {
+            Some(points) => {
+                for point in voxel_points {
+                    points.plot_point(point);
+                }
+                points.save(tileset_id, metadata);
+            },
+            None => self.save_raw(tileset_id)
+        }
+        self.writing_timer.stop(start);
+    }
+
+    fn bounds(&self) -> &BBox {
+        &self.bounds
+    }
+
+    fn enable_profiling(&mut self) {
+        self.insertion_timer = PhaseTimer::new(true);
+        self.writing_timer = PhaseTimer::new(true);
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        profile.insertion = self.insertion_timer.elapsed();
+        profile.writing = self.writing_timer.elapsed();
+    }
+
+    /// For voxel grids, the filled "leaves" are the occupied voxels
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        match metric {
+            QualityMetric::Dimension => {
+                let positions: Vec<Vec3> = self.make_points()
+                    .iter()
+                    .map(|point| point.position)
+                    .collect();
+                quality::box_counting_dimension(&positions, &self.bounds)
+            },
+            QualityMetric::FilledLeaves => self.voxels
+                .iter()
+                .filter(|voxel| voxel.count > 0)
+                .count() as f64
+        }
+    }
+
+    /// Voxels only accumulate counts, so they never run out of room
+    fn capacity(&self, _dimension: f64) -> usize {
+        usize::MAX
+    }
+
+    /// Only the voxel totals are kept, so this returns one point per
+    /// occupied voxel, see make_points()
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.make_points()
+    }
+
+    fn point_schema_json(&self, metadata: &FractalMetadata) -> JsonValue {
+        match &self.points {
+            Some(points) => points.point_schema_json(metadata),
+            None => JsonValue::Null
+        }
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_u8(VOXEL_CHECKPOINT);
+        self.plot_counts.write_checkpoint(writer);
+        writer.write_usize(self.voxels.len());
+        for voxel in self.voxels.iter() {
+            writer.write_u32(voxel.count);
+            for component in voxel.color_sum.iter() {
+                writer.write_f32(*component);
+            }
+        }
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        if reader.read_u8() != VOXEL_CHECKPOINT {
+            panic!("The checkpoint was not written by a voxel plotter");
+        }
+        self.plot_counts = PlotCounts::read_checkpoint(reader);
+        let voxel_count = reader.read_usize();
+        if voxel_count != self.voxels.len() {
+            panic!(
+                "The checkpoint has {} voxels but the grid has {}",
+                voxel_count, self.voxels.len());
+        }
+        for voxel in self.voxels.iter_mut() {
+            voxel.count = reader.read_u32();
+            for component in voxel.color_sum.iter_mut() {
+                *component = reader.read_f32();
+            }
+        }
+    }
+}
+
+/// Default gamma of a FlamePlot, 1 leaves the log curve as is
+const DEFAULT_FLAME_GAMMA: f64 = 1.0;
+
+/// Fractal flame tone mapping. A dense occupancy histogram in fractal
+/// flames spans many orders of magnitude, so the brightness of a voxel is
+/// the log of its count relative to the busiest voxel:
+///
+/// brightness = (log(count) / log(max_count))^(1/gamma)
+///
+/// A voxel with a single hit is black. If every voxel has at most one hit,
+/// there is no contrast to map, so everything is at full brightness.
+fn flame_brightness(count: u32, max_count: u32, gamma: f64) -> f64 {
+    if max_count <= 1 {
+        return 1.0;
+    }
+    let t = (count as f64).ln() / (max_count as f64).ln();
+    t.clamp(0.0, 1.0).powf(1.0 / gamma)
+}
+
+/// The fractal flame rendering mode. This accumulates hit counts and
+/// colors in a voxel grid like VoxelPlot, then on save the average color
+/// of each occupied voxel is scaled by the log-density tone map, see
+/// flame_brightness().
+pub struct FlamePlot {
+    /// The voxel grid doing the binning. Its scatter plotter writes the
+    /// toned points
+    grid: VoxelPlot,
+    /// Gamma correction applied after the log curve
+    gamma: f64,
+}
+
+impl FlamePlot {
+    /// Load a plotter from JSON of the form:
+    /// {
+    ///     "type": "flame",
+    ///     "radius": r,
+    ///     "resolution": N,
+    ///     "gamma": g (optional, default 1.0),
+    ///     "format": <ScatterPlot format> (default "glb"),
+    ///     ...<ScatterPlot params>
+    /// }
+    ///
+    /// Each occupied voxel becomes a point at its center with a weight of
+    /// its count, saved like a scatter plot with the same JSON.
+    pub fn from_json(json: &JsonValue) -> Self {
+        if json["format"] == "raw" {
+            panic!("flame plots are saved as points, not a raw volume");
+        }
+        let gamma = match &json["gamma"] {
+            JsonValue::Null => DEFAULT_FLAME_GAMMA,
+            gamma => gamma
+                .as_f64()
+                .filter(|gamma| *gamma > 0.0)
+                .expect("gamma must be a positive number")
+        };
+
+        Self {
+            grid: VoxelPlot::from_json(json),
+            gamma,
+        }
+    }
+
+    /// The voxel points with their colors tone mapped
+    fn make_points(&self) -> Vec<OutputPoint> {
+        let mut points = self.grid.make_points();
+        let max_count = points
+            .iter()
+            .map(|point| point.weight)
+            .max()
+            .unwrap_or(0);
+        for point in points.iter_mut() {
+            let brightness =
+                flame_brightness(point.weight, max_count, self.gamma);
+            point.color *= brightness as f32;
+        }
+        points
+    }
+
+    to_box!(Plotter);
+}
+
+impl Plotter for FlamePlot {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        self.grid.plot_point(point)
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.grid.counts()
+    }
+
+    fn save(&mut self, tileset_id: &str, metadata: &FractalMetadata) {
+        let start = self.grid.writing_timer.start();
+        let toned_points = self.make_points();
+        let points = self.grid.points
+            .as_mut()
+            .expect("flame plots always save through a scatter plot");
+        for point in toned_points {
+            points.plot_point(point);
+        }
+        points.save(tileset_id, metadata);
+        self.grid.writing_timer.stop(start);
+    }
+
+    fn bounds(&self) -> &BBox {
+        self.grid.bounds()
+    }
+
+    fn enable_profiling(&mut self) {
+        self.grid.enable_profiling();
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        self.grid.record_profile(profile);
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        self.grid.quality(metric)
+    }
+
+    fn capacity(&self, dimension: f64) -> usize {
+        self.grid.capacity(dimension)
+    }
+
+    /// One point per occupied voxel with the tone mapped color
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.make_points()
+    }
+
+    fn point_schema_json(&self, metadata: &FractalMetadata) -> JsonValue {
+        self.grid.point_schema_json(metadata)
+    }
+
+    /// The checkpoint only holds the voxel grid, the tone mapping is
+    /// applied on save
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        self.grid.write_checkpoint(writer);
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        self.grid.read_checkpoint(reader);
+    }
+}
+
+/// Plots the points of each subcluster (see Cluster::subcluster_count())
+/// with a separate scatter plot, so each one becomes its own tileset. On
+/// save, the subclusters are written to ./viewer/{id}/cluster_{i} and
+/// ./viewer/{id}/tileset.json refers to each of them as an external
+/// tileset, so they can be toggled independently in the viewer.
+pub struct SplitPlotter {
+    /// One plotter per subcluster, indexed by cluster_id
+    plotters: Vec<Box<dyn Plotter>>,
+    /// Whether the plotters grow their roots to fit the points
+    grow: bool,
+    /// The union of the plotters' bounds. Without grow, this is the same
+    /// for every plotter.
+    bounds: BBox,
+}
+
+impl SplitPlotter {
+    /// Make subcluster_count scatter plots from the same plotter JSON. Live
+    /// streaming is not supported, since every plotter would need its own
+    /// port.
+    pub fn from_json(json: &JsonValue, subcluster_count: usize) -> Self {
+        if json["type"].as_str().unwrap_or("scatter") != "scatter" {
+            panic!("split_subclusters requires a scatter plotter");
+        }
+        if !json["stream"].is_null() {
+            panic!("split_subclusters cannot be used with stream");
+        }
+
+        let plotters: Vec<Box<dyn Plotter>> =
+            (0..subcluster_count).map(|_| from_json(json)).collect();
+        let bounds = plotters[0].bounds().clone();
+        Self {
+            plotters,
+            grow: json["grow"].as_bool().unwrap_or(false),
+            bounds,
+        }
+    }
+
+    /// With grow, each plotter's root only ever gets bigger, so including
+    /// the plotter that just changed keeps the union up to date
+    fn update_bounds(&mut self, cluster_id: usize) {
+        if self.grow {
+            self.bounds = self.bounds.union(self.plotters[cluster_id].bounds());
+        }
+    }
+
+    fn plotter_for(&mut self, point: &OutputPoint) -> &mut Box<dyn Plotter> {
+        let cluster_id = point.cluster_id as usize;
+        let subcluster_count = self.plotters.len();
+        self.plotters.get_mut(cluster_id).unwrap_or_else(|| panic!(
+            "cluster_id {} is out of range for {} subclusters",
+            cluster_id, subcluster_count))
+    }
+
+    to_box!(Plotter);
+}
+
+impl Plotter for SplitPlotter {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        let cluster_id = point.cluster_id as usize;
+        let result = self.plotter_for(&point).plot_point(point);
+        self.update_bounds(cluster_id);
+        result
+    }
+
+    fn plot_escaped(&mut self, point: OutputPoint) -> PlotResult {
+        let cluster_id = point.cluster_id as usize;
+        let result = self.plotter_for(&point).plot_escaped(point);
+        self.update_bounds(cluster_id);
+        result
+    }
+
+    fn counts(&self) -> PlotCounts {
+        let mut total = PlotCounts::default();
+        for counts in self.plotters.iter().map(|plotter| plotter.counts()) {
+            total.accepted += counts.accepted;
+            total.discarded += counts.discarded;
+        }
+        total
+    }
+
+    fn save(&mut self, tileset_id: &str, metadata: &FractalMetadata) {
+        // Like TilesetWriter, replace any old tileset with this id
+        let parent_dir = format!("./viewer/{}", tileset_id);
+        if Path::new(&parent_dir).exists() {
+            remove_dir_all(&parent_dir).unwrap_or_else(|err| panic!(
+                "Could not remove old tileset in {}: {}", parent_dir, err));
+        }
+
+        let mut child_dirs = Vec::new();
+        let mut extras = Vec::new();
+        for (i, plotter) in self.plotters.iter_mut().enumerate() {
+            let child_id = format!("{}/cluster_{}", tileset_id, i);
+            plotter.save(&child_id, metadata);
+            child_dirs.push(format!("./viewer/{}", child_id));
+            extras.push(object!{ "cluster_id" => i });
+        }
+        merge_tagged_tilesets(&parent_dir, &child_dirs, &extras);
+    }
+
+    /// The box around every subcluster's bounds. These start out the same,
+    /// but with grow each subcluster's root grows to fit its own points.
+    fn bounds(&self) -> &BBox {
+        &self.bounds
+    }
+
+    fn enable_profiling(&mut self) {
+        for plotter in self.plotters.iter_mut() {
+            plotter.enable_profiling();
+        }
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        for plotter in self.plotters.iter() {
+            let mut child = Profile::default();
+            plotter.record_profile(&mut child);
+            profile.insertion += child.insertion;
+            profile.decimation += child.decimation;
+            profile.writing += child.writing;
+        }
+    }
+
+    /// The total of the filled leaves, or the mean dimension of the
+    /// subclusters
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        let total: f64 = self.plotters
+            .iter()
+            .map(|plotter| plotter.quality(metric))
+            .sum();
+        match metric {
+            QualityMetric::Dimension => total / self.plotters.len() as f64,
+            QualityMetric::FilledLeaves => total
+        }
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.plotters
+            .iter()
+            .flat_map(|plotter| plotter.leaf_sizes())
+            .collect()
+    }
+
+    fn capacity(&self, dimension: f64) -> usize {
+        self.plotters
+            .iter()
+            .map(|plotter| plotter.capacity(dimension))
+            .fold(0, usize::saturating_add)
+    }
+
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        self.plotters
+            .into_iter()
+            .flat_map(|plotter| plotter.into_points())
+            .collect()
+    }
+
+    fn point_schema_json(&self, metadata: &FractalMetadata) -> JsonValue {
+        self.plotters[0].point_schema_json(metadata)
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_u8(SPLIT_CHECKPOINT);
+        writer.write_usize(self.plotters.len());
+        for plotter in self.plotters.iter() {
+            plotter.write_checkpoint(writer);
+        }
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        if reader.read_u8() != SPLIT_CHECKPOINT {
+            panic!("The checkpoint was not written with split_subclusters");
+        }
+        let subcluster_count = reader.read_usize();
+        if subcluster_count != self.plotters.len() {
+            panic!(
+                "The checkpoint has {} subclusters but the cluster has {}",
+                subcluster_count, self.plotters.len());
+        }
+        for plotter in self.plotters.iter_mut() {
+            plotter.read_checkpoint(reader);
+        }
+        for cluster_id in 0..self.plotters.len() {
+            self.update_bounds(cluster_id);
+        }
+    }
+}
+
+/// Convert a color component in [0, 1] to a byte
+fn to_byte(component: f64) -> u8 {
+    (component.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// Plotter that only keeps the bounding box of the points, without an
+/// octree, so it takes almost no memory. This is the first pass of
+/// auto_fit, see algorithms::AutoFit. On its own, it reports the bounds
+/// when saving, which helps pick a radius for a new fractal.
+pub struct BoundsPlotter {
+    /// The box around the points plotted so far. This is an empty box at
+    /// the origin until the first point
+    bounds: BBox,
+    /// Every finite point is kept
+    counts: PlotCounts,
+}
+
+impl BoundsPlotter {
+    pub fn new() -> Self {
+        Self {
+            bounds: BBox::from_points(&[]),
+            counts: PlotCounts::default(),
+        }
+    }
+
+    to_box!(Plotter);
+}
+
+impl Default for BoundsPlotter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Plotter for BoundsPlotter {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        if self.counts.accepted == 0 {
+            self.bounds = BBox::from_points(&[point.position]);
+        } else {
+            self.bounds.expand_to_include(&point.position);
+        }
+        self.counts.record(PlotResult::Accepted);
+        PlotResult::Accepted
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.counts
+    }
+
+    fn save(&mut self, _dirname: &str, _metadata: &FractalMetadata) {
+        info!(
+            "The {} points fit in {:?}, which needs a radius of {}",
+            self.counts.accepted,
+            self.bounds,
+            self.bounds.origin_radius());
+    }
+
+    fn bounds(&self) -> &BBox {
+        &self.bounds
+    }
+
+    fn grows_to_fit(&self) -> bool {
+        true
+    }
+
+    fn enable_profiling(&mut self) {}
+
+    fn record_profile(&self, _profile: &mut Profile) {}
+
+    /// There are no points to score, so every run is as good as any other
+    fn quality(&self, _metric: QualityMetric) -> f64 {
+        0.0
+    }
+
+    fn capacity(&self, _dimension: f64) -> usize {
+        usize::MAX
+    }
+
+    /// The individual points are not kept
+    fn into_points(self: Box<Self>) -> Vec<OutputPoint> {
+        Vec::new()
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        writer.write_u8(BOUNDS_CHECKPOINT);
+        self.counts.write_checkpoint(writer);
+        writer.write_bbox(&self.bounds);
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        if reader.read_u8() != BOUNDS_CHECKPOINT {
+            panic!("The checkpoint was not written by a bounds plotter");
+        }
+        self.counts = PlotCounts::read_checkpoint(reader);
+        self.bounds = reader.read_bbox();
+    }
+}
+
+/// How many points to send in each live batch by default
+const DEFAULT_STREAM_BATCH: usize = 1000;
+/// Each streamed point is a position as 3 f32s and a color as 3 u8s
+const STREAM_POINT_BYTES: usize = 15;
+
+/// Wrapper that plots to another plotter, and also sends the kept points
+/// to WebSocket clients as they are plotted so a browser can show the
+/// fractal forming live. The inner plotter still saves the tileset as
+/// usual.
+///
+/// Each message is a batch of points, a little-endian u32 count followed
+/// by the points. Each point is its position as 3 little-endian f32s and
+/// its color as 3 u8s, before the output color space is applied. Escaped
+/// points are not streamed.
+pub struct StreamPlotter {
+    plotter: Box<dyn Plotter>,
+    server: LiveServer,
+    /// Send the points once this many are waiting
+    batch_size: usize,
+    /// The points that have not been sent yet, without the count
+    batch: Vec<u8>,
+}
+
+impl StreamPlotter {
+    /// Wrap a plotter if the JSON is of the form:
+    /// {
+    ///     "ws_port": p,
+    ///     "host": str (default "127.0.0.1"),
+    ///     "batch": b (default 1000)
+    /// }
+    /// If the JSON is null, the plotter is returned as-is.
+    ///
+    /// The stream has no authentication, so by default only this machine
+    /// can connect. Set host to "0.0.0.0" to let other machines connect.
+    pub fn wrap(plotter: Box<dyn Plotter>, json: &JsonValue)
+            -> Box<dyn Plotter> {
+        if json.is_null() {
+            return plotter;
+        }
+
+        let port = json["ws_port"]
+            .as_u16()
+            .expect("stream.ws_port must be a port number");
+        let batch_size = json["batch"]
+            .as_usize()
+            .unwrap_or(DEFAULT_STREAM_BATCH);
+        if batch_size == 0 {
+            panic!("stream.batch must be a positive integer");
+        }
+        let host = match &json["host"] {
+            JsonValue::Null => live_stream::DEFAULT_HOST,
+            host => host.as_str().expect("stream.host must be a string")
+        };
+        let server = LiveServer::bind(host, port);
+        info!("Streaming points to ws://{}:{}", host, server.port());
+
+        Box::new(Self {
+            plotter,
+            server,
+            batch_size,
+            batch: Vec::with_capacity(batch_size * STREAM_POINT_BYTES),
+        })
+    }
+
+    /// Send the waiting points, if any
+    fn flush(&mut self) {
+        let count = self.batch.len() / STREAM_POINT_BYTES;
+        if count == 0 {
+            return;
+        }
+
+        let mut message = Vec::with_capacity(4 + self.batch.len());
+        message.extend_from_slice(&(count as u32).to_le_bytes());
+        message.append(&mut self.batch);
+        self.server.broadcast(&message);
+    }
+}
+
+impl Plotter for StreamPlotter {
+    fn plot_point(&mut self, point: OutputPoint) -> PlotResult {
+        let position = point.position;
+        let color = point.color.to_color();
+        let result = self.plotter.plot_point(point);
+        if result.is_accepted() {
+            self.batch.extend_from_slice(&position.pack());
+            self.batch.extend_from_slice(&color.pack());
+            if self.batch.len() >= self.batch_size * STREAM_POINT_BYTES {
+                self.flush();
+            }
+        }
+        result
+    }
+
+    fn plot_escaped(&mut self, point: OutputPoint) -> PlotResult {
+        self.plotter.plot_escaped(point)
+    }
+
+    fn counts(&self) -> PlotCounts {
+        self.plotter.counts()
+    }
+
+    fn save(&mut self, dirname: &str, metadata: &FractalMetadata) {
+        self.flush();
+        self.plotter.save(dirname, metadata);
+    }
+
+    fn bounds(&self) -> &BBox {
+        self.plotter.bounds()
+    }
+
+    fn enable_profiling(&mut self) {
+        self.plotter.enable_profiling();
+    }
+
+    fn record_profile(&self, profile: &mut Profile) {
+        self.plotter.record_profile(profile);
+    }
+
+    fn quality(&self, metric: QualityMetric) -> f64 {
+        self.plotter.quality(metric)
+    }
+
+    fn leaf_sizes(&self) -> Vec<usize> {
+        self.plotter.leaf_sizes()
+    }
+
+    fn capacity(&self, dimension: f64) -> usize {
+        self.plotter.capacity(dimension)
+    }
+
+    fn into_points(mut self: Box<Self>) -> Vec<OutputPoint> {
+        self.flush();
+        self.plotter.into_points()
+    }
+
+    fn point_schema_json(&self, metadata: &FractalMetadata) -> JsonValue {
+        self.plotter.point_schema_json(metadata)
+    }
+
+    fn write_checkpoint(&self, writer: &mut CheckpointWriter) {
+        self.plotter.write_checkpoint(writer);
+    }
+
+    fn read_checkpoint(&mut self, reader: &mut CheckpointReader) {
+        self.plotter.read_checkpoint(reader);
+    }
+}
+
+/// Parse a scatter plot, picking the point type for the format
+fn scatter_from_json(json: &JsonValue) -> Box<dyn Plotter> {
+    match json["format"].as_str() {
+        // .pnts tiles don't have any metadata, so don't store it
+        Some("pnts") => ScatterPlot::<CompactPoint>::from_json(json).to_box(),
+        // Only emit_cga needs cga_pn
+        _ if json["emit_cga"].as_bool().unwrap_or(false) =>
+            ScatterPlot::<OutputPoint>::from_json(json).to_box(),
+        _ => ScatterPlot::<GlbPoint>::from_json(json).to_box()
+    }
+}
+
+/// Parse a point cloud plotter from a JSON object of the form:
+///
+/// ```text
+/// {
+///     "type": "scatter" | "image" | "voxel" | "flame" | "bounds"
+///         (default "scatter"),
+///     "stream": <StreamPlotter JSON> (optional),
+///     ...params
+/// }
+/// ```
+pub fn from_json(json: &JsonValue) -> Box<dyn Plotter> {
+    let valid_plotters: Vec<&str> =
+        vec!["scatter", "image", "voxel", "flame", "bounds"];
+    let plotter_type = &json["type"].as_str().unwrap_or("scatter");
+
+    let plotter = match &plotter_type[..] {
+        "scatter" => scatter_from_json(json),
+        "image" => ImagePlotter::from_json(json).to_box(),
+        "voxel" => VoxelPlot::from_json(json).to_box(),
+        "flame" => FlamePlot::from_json(json).to_box(),
+        "bounds" => BoundsPlotter::new().to_box(),
+        _ => panic!("Plotter type must be one of, {:?}", valid_plotters)
+    };
+    StreamPlotter::wrap(plotter, &json["stream"])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_point(x: f32, y: f32, z: f32) -> OutputPoint {
+        OutputPoint {
+            position: Vec3::new(x, y, z),
+            color: Vec3::ones(),
+            cluster_coordinates: Vec3::zero(),
+            iteration: 0,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        }
+    }
+
+    fn make_internal_point(x: f64, y: f64, z: f64) -> InternalPoint {
+        InternalPoint {
+            position: HalfMultivector::point(x, y, z),
+            color: HalfMultivector::point(0.5, 0.5, 0.5),
+            cluster_coordinates: Vec3::zero(),
+            iteration: 0,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        }
+    }
+
+    fn make_clipped_plotter() -> ScatterPlot {
+        let json = object!{
+            "radius" => 4.0,
+            "radial_clip" => object!{
+                "min" => 1.0,
+                "max" => 2.0
+            }
+        };
+        ScatterPlot::from_json(&json)
+    }
+
+    #[test]
+    fn test_grow_keeps_all_points() {
+        let json = object!{
+            "radius" => 1.0,
+            "node_capacity" => 4,
+            "max_depth" => 3,
+            "grow" => true
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+
+        let positions = [
+            (0.5, 0.5, 0.5),
+            (3.0, -2.0, 0.0),
+            (-10.0, 0.0, 7.5),
+            (0.0, 0.0, -40.0),
+            (100.0, 100.0, 100.0),
+            (0.25, -0.25, 0.75),
+        ];
+        for &(x, y, z) in positions.iter() {
+            assert!(plotter.plot_point(make_point(x, y, z)).is_accepted());
+        }
+
+        // escaping points are still discarded
+        assert!(!plotter
+            .plot_point(make_point(f32::INFINITY, 0.0, 0.0))
+            .is_accepted());
+    }
+
+    #[test]
+    fn test_checkpoint_keeps_grown_root() {
+        use crate::checkpoint::CheckpointKind;
+
+        let json = object!{
+            "radius" => 1.0,
+            "node_capacity" => 2,
+            "max_depth" => 3,
+            "grow" => true,
+            "color_space" => "srgb"
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        for &(x, y, z) in [(0.5, 0.5, 0.5), (3.0, -2.0, 0.0), (0.1, 0.2, 0.3)]
+                .iter() {
+            assert!(plotter.plot_point(make_point(x, y, z)).is_accepted());
+        }
+
+        let fname = std::env::temp_dir().join(
+            format!("chaos-game-3d-plotter-{}.ckpt", std::process::id()));
+        let fname = fname.to_str().unwrap();
+        let mut writer = CheckpointWriter::create(fname, CheckpointKind::ChaosGame);
+        plotter.write_checkpoint(&mut writer);
+        writer.finish();
+
+        let mut restored: ScatterPlot = ScatterPlot::from_json(&json);
+        let mut reader = CheckpointReader::open(fname, CheckpointKind::ChaosGame);
+        restored.read_checkpoint(&mut reader);
+        std::fs::remove_file(fname).unwrap();
+
+        assert_eq!(restored.growth_levels, plotter.growth_levels);
+        assert_eq!(restored.max_depth, plotter.max_depth);
+        assert!(restored.bounds().contains(&Vec3::new(3.0, -2.0, 0.0)));
+        // Colors were encoded when first plotted and are not encoded again
+        let expected = Box::new(plotter).into_points();
+        let points = Box::new(restored).into_points();
+        assert_eq!(points.len(), 3);
+        for (point, expected) in points.iter().zip(expected.iter()) {
+            assert_eq!((point.position - expected.position).length(), 0.0);
+            assert_eq!((point.color - expected.color).length(), 0.0);
+        }
+    }
+
+    #[test]
+    fn test_no_grow_discards_outside() {
+        let json = object!{
+            "radius" => 1.0
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        assert!(!plotter.plot_point(make_point(3.0, 0.0, 0.0)).is_accepted());
+    }
+
+    #[test]
+    fn test_counts_accepted_and_discarded() {
+        let mut plotter = make_clipped_plotter();
+        plotter.plot_point(make_point(1.5, 0.0, 0.0));
+        plotter.plot_point(make_point(0.0, 0.0, 0.0));
+        plotter.plot_point(make_point(9.0, 0.0, 0.0));
+        plotter.plot_escaped(make_point(1.0, 0.0, 0.0));
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 1, discarded: 3 });
+        assert_eq!(plotter.counts().total(), 4);
+    }
+
+    #[test]
+    fn test_stream_plotter_forwards_points() {
+        let json = object!{
+            "radius" => 1.0,
+            "stream" => object!{ "ws_port" => 0, "batch" => 2 }
+        };
+        let mut plotter = from_json(&json);
+        assert!(plotter.plot_point(make_point(0.5, 0.0, 0.0)).is_accepted());
+        assert!(plotter.plot_point(make_point(0.0, 0.5, 0.0)).is_accepted());
+        assert!(!plotter.plot_point(make_point(3.0, 0.0, 0.0)).is_accepted());
+        assert!(plotter.plot_point(make_point(0.0, 0.0, 0.5)).is_accepted());
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 3, discarded: 1 });
+        assert_eq!(plotter.into_points().len(), 3);
+    }
+
+    #[test]
+    fn test_symmetrize_mirrors_points() {
+        let json = object!{
+            "radius" => 1.0,
+            "symmetrize" => array![array!["reflect", 1, 0, 0]]
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        plotter.plot_point(make_point(0.5, 0.25, 0.0));
+        plotter.plot_point(make_point(0.25, -0.5, 0.75));
+        let points = Box::new(plotter).into_points();
+        assert_eq!(points.len(), 4);
+        for point in points.iter() {
+            let position = point.position;
+            let mirrored =
+                Vec3::new(-position.x(), *position.y(), *position.z());
+            assert!(points.iter().any(|other|
+                (other.position - mirrored).length() < 1e-6));
+        }
+        assert_eq!(points.iter().filter(|p| *p.position.x() < 0.0).count(), 2);
+    }
+
+    #[test]
+    fn test_cluster_outside_root_is_discarded() {
+        let mut plotter: ScatterPlot =
+            ScatterPlot::from_json(&object!{ "radius" => 1.0 });
+        let cluster: Vec<InternalPoint> = (0..10)
+            .map(|i| make_internal_point(5.0 + i as f64, 0.0, 0.0))
+            .collect();
+        plotter.plot_points(&cluster);
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 0, discarded: 10 });
+        assert!(plotter.root.is_empty());
+    }
+
+    #[test]
+    fn test_cluster_around_root_is_discarded() {
+        // The box around the cluster covers the root, but no point is in it
+        let mut plotter: ScatterPlot =
+            ScatterPlot::from_json(&object!{ "radius" => 1.0 });
+        let cluster = vec![
+            make_internal_point(-5.0, 0.0, 0.0),
+            make_internal_point(5.0, 0.0, 0.0),
+        ];
+        assert!(plotter.is_outside_root(&cluster));
+        plotter.plot_points(&cluster);
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 0, discarded: 2 });
+    }
+
+    #[test]
+    fn test_cluster_partly_inside_root() {
+        let mut plotter: ScatterPlot =
+            ScatterPlot::from_json(&object!{ "radius" => 1.0 });
+        let cluster = vec![
+            make_internal_point(0.5, 0.0, 0.0),
+            make_internal_point(5.0, 0.0, 0.0),
+            make_internal_point(0.0, -0.5, 0.25),
+        ];
+        plotter.plot_points(&cluster);
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 2, discarded: 1 });
+    }
+
+    #[test]
+    fn test_voxel_bins_points() {
+        let mut plotter = from_json(&object!{
+            "type" => "voxel",
+            "radius" => 1.0,
+            "resolution" => 4,
+            "format" => "raw"
+        });
+        let mut a = make_point(0.1, 0.1, 0.1);
+        a.color = Vec3::new(1.0, 0.0, 0.0);
+        let mut b = make_point(0.4, 0.2, 0.3);
+        b.color = Vec3::new(0.0, 0.0, 1.0);
+        assert!(plotter.plot_point(a).is_accepted());
+        assert!(plotter.plot_point(b).is_accepted());
+        assert!(plotter.plot_point(make_point(-0.9, 0.9, -0.1)).is_accepted());
+        assert!(!plotter.plot_point(make_point(1.5, 0.0, 0.0)).is_accepted());
+        assert_eq!(plotter.counts(), PlotCounts { accepted: 3, discarded: 1 });
+        assert_eq!(plotter.quality(QualityMetric::FilledLeaves), 2.0);
+
+        let mut points = plotter.into_points();
+        points.sort_by_key(|point| point.weight);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].weight, 1);
+        assert!((points[0].position - Vec3::new(-0.75, 0.75, -0.25)).length()
+            < 1e-6);
+        assert_eq!(points[1].weight, 2);
+        assert!((points[1].position - Vec3::new(0.25, 0.25, 0.25)).length()
+            < 1e-6);
+        assert!((points[1].color - Vec3::new(0.5, 0.0, 0.5)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_voxel_index_matches_center() {
+        let plotter = VoxelPlot::from_json(&object!{
+            "radius" => 2.0,
+            "resolution" => 5,
+            "format" => "raw"
+        });
+        for i in 0..plotter.voxels.len() {
+            let center = plotter.voxel_center(i);
+            assert_eq!(plotter.voxel_index(&center), Some(i));
+        }
+        // Like the octree, the max faces are outside the grid
+        assert_eq!(plotter.voxel_index(&Vec3::splat(1.999)), Some(124));
+        assert_eq!(plotter.voxel_index(&Vec3::splat(2.0)), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "a voxel grid of resolution 100000 needs more")]
+    fn test_voxel_grid_too_large() {
+        VoxelPlot::from_json(&object!{
+            "radius" => 1.0,
+            "resolution" => 100000
+        });
+    }
+
+    #[test]
+    fn test_flame_brightness_curve() {
+        assert_eq!(flame_brightness(1, 100, 1.0), 0.0);
+        assert_eq!(flame_brightness(100, 100, 1.0), 1.0);
+        assert!((flame_brightness(10, 100, 1.0) - 0.5).abs() < 1e-12);
+        // Gamma brightens the midtones without changing the ends
+        assert!((flame_brightness(10, 100, 2.0) - 0.5f64.sqrt()).abs()
+            < 1e-12);
+        assert_eq!(flame_brightness(100, 100, 2.0), 1.0);
+        // No contrast to map
+        assert_eq!(flame_brightness(1, 1, 1.0), 1.0);
+    }
+
+    #[test]
+    fn test_flame_tone_maps_voxels() {
+        let mut plotter = from_json(&object!{
+            "type" => "flame",
+            "radius" => 1.0,
+            "resolution" => 2
+        });
+        for _ in 0..9 {
+            assert!(plotter
+                .plot_point(make_point(0.5, 0.5, 0.5))
+                .is_accepted());
+        }
+        for _ in 0..3 {
+            assert!(plotter
+                .plot_point(make_point(-0.5, -0.5, -0.5))
+                .is_accepted());
+        }
+        let mut points = plotter.into_points();
+        points.sort_by_key(|point| point.weight);
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].weight, 3);
+        assert!((points[0].color - Vec3::splat(0.5)).length() < 1e-6);
+        assert_eq!(points[1].weight, 9);
+        assert!((points[1].color - Vec3::ones()).length() < 1e-6);
+    }
+
+    #[test]
+    #[should_panic(expected = "gamma must be a positive number")]
+    fn test_flame_gamma_must_be_positive() {
+        FlamePlot::from_json(&object!{
+            "radius" => 1.0,
+            "resolution" => 2,
+            "gamma" => 0.0
+        });
+    }
+
+    #[test]
+    fn test_pnts_uses_compact_points() {
+        let json = object!{
+            "format" => "pnts",
+            "radius" => 1.0
+        };
+        let mut plotter = from_json(&json);
+        let mut point = make_point(0.5, -0.25, 0.0);
+        point.color = Vec3::new(0.5, 0.0, 1.0);
+        point.iteration = 42;
+        assert!(plotter.plot_point(point).is_accepted());
+
+        // Only the position, color and weight survive
+        let points = plotter.into_points();
+        assert_eq!(points.len(), 1);
+        assert_eq!(*points[0].position.y(), -0.25);
+        assert_eq!(*points[0].color.x(), 0.5);
+        assert_eq!(points[0].weight, 1);
+        assert_eq!(points[0].iteration, 0);
+    }
+
+    #[test]
+    fn test_root_points_capped_at_target() {
+        let mut json = object!{ "radius" => 1.0, "root_points" => 500 };
+        let plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        assert_eq!(plotter.root_points, Some(500));
+
+        json["target_points_per_tile"] = 100.into();
+        let plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        assert_eq!(plotter.root_points, Some(100));
+    }
+
+    #[test]
+    #[should_panic(expected = "sphere bounding volumes require explicit")]
+    fn test_sphere_bounding_volume_rejects_implicit() {
+        let json = object!{
+            "radius" => 1.0,
+            "tiling" => "implicit",
+            "bounding_volume" => "sphere"
+        };
+        let _plotter: ScatterPlot = ScatterPlot::from_json(&json);
+    }
+
+    #[test]
+    #[should_panic(expected = "requires pnts format")]
+    fn test_tiles_version_1_0_rejects_glb() {
+        let json = object!{
+            "format" => "glb",
+            "radius" => 1.0,
+            "tiles_version" => "1.0"
+        };
+        let _plotter: ScatterPlot = ScatterPlot::from_json(&json);
+    }
+
+    #[test]
+    fn test_image_projection() {
+        let json = object!{
+            "type" => "image",
+            "width" => 4,
+            "height" => 2,
+            "radius" => 1.0
+        };
+        let mut plotter = ImagePlotter::from_json(&json);
+
+        // Looking down z, so the image spans [-2, 2] x [-1, 1] in xy
+        assert!(plotter.plot_point(make_point(-1.9, 0.9, 0.0)).is_accepted());
+        assert!(plotter.plot_point(make_point(1.9, -0.9, 0.5)).is_accepted());
+        assert!(!plotter.plot_point(make_point(0.0, 0.0, 5.0)).is_accepted());
+        assert!(!plotter.plot_point(make_point(3.0, 0.0, 0.0)).is_accepted());
+
+        let pixels = plotter.make_pixels();
+        for (i, pixel) in pixels.iter().enumerate() {
+            if i == 0 || i == 7 {
+                assert_eq!(*pixel, [255, 255, 255]);
+            } else {
+                assert_eq!(*pixel, [0, 0, 0]);
+            }
+        }
+    }
+
+    #[test]
+    fn test_image_side_view() {
+        let json = object!{
+            "type" => "image",
+            "width" => 2,
+            "height" => 2,
+            "axis" => "x",
+            "radius" => 1.0,
+            "colormap" => "heat"
+        };
+        let mut plotter = ImagePlotter::from_json(&json);
+
+        // Looking down x, the image is (y, z) so this is the top right
+        assert!(plotter.plot_point(make_point(-0.5, 0.5, 0.5)).is_accepted());
+        assert_eq!(plotter.make_pixels()[1], [255, 255, 255]);
+    }
+
+    #[test]
+    fn test_image_gradient() {
+        let json = object!{
+            "type" => "image",
+            "width" => 2,
+            "height" => 2,
+            "radius" => 1.0,
+            "colormap" => "viridis"
+        };
+        let mut plotter = ImagePlotter::from_json(&json);
+        assert!(plotter.plot_point(make_point(-0.5, 0.5, 0.0)).is_accepted());
+
+        // The densest pixel gets the end of the gradient
+        assert_eq!(plotter.make_pixels()[0], [0xfd, 0xe7, 0x25]);
+    }
+
+    #[test]
+    fn test_plot_mask() {
+        let json = object!{
+            "radius" => 4.0,
+            "plot_mask" => object!{
+                "type" => "box",
+                "center" => array![1.0, 0.0, 0.0],
+                "dims" => array![2.0, 2.0, 2.0]
+            }
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        assert_eq!(
+            plotter.plot_point(make_point(1.5, 0.5, -0.5)),
+            PlotResult::Accepted);
+        // Masked points are filtered, which is different from a full leaf
+        assert_eq!(
+            plotter.plot_point(make_point(-0.5, 0.0, 0.0)),
+            PlotResult::Filtered);
+        assert_eq!(
+            plotter.plot_point(make_point(1.0, 3.0, 0.0)),
+            PlotResult::Filtered);
+
+        // Points inside the mask are kept as-is
+        let points = Box::new(plotter).into_points();
+        assert_eq!(points.len(), 1);
+        assert_eq!(*points[0].position.x(), 1.5);
+
+        let sphere = PlotMask::from_json(&object!{
+            "type" => "sphere",
+            "radius" => 1.0
+        }).unwrap();
+        assert!(sphere.contains(&Vec3::new(0.0, 0.6, 0.8)));
+        assert!(!sphere.contains(&Vec3::new(0.0, 0.8, 0.8)));
+    }
+
+    #[test]
+    fn test_split_bounds_grow() {
+        let json = object!{ "radius" => 1.0, "grow" => true };
+        let mut plotter = SplitPlotter::from_json(&json, 2);
+        assert_eq!(*plotter.bounds().max().x(), 1.0);
+
+        let mut point = make_point(3.0, 0.0, 0.0);
+        point.cluster_id = 1;
+        assert!(plotter.plot_point(point).is_accepted());
+        assert!(plotter.bounds().contains(&Vec3::new(3.0, 0.0, 0.0)));
+        assert!(!plotter.plotters[0]
+            .bounds()
+            .contains(&Vec3::new(3.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn test_full_leaf_is_not_filtered() {
+        let json = object!{
+            "radius" => 1.0,
+            "node_capacity" => 1,
+            "max_depth" => 0
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        assert_eq!(
+            plotter.plot_point(make_point(0.5, 0.0, 0.0)),
+            PlotResult::Accepted);
+        assert_eq!(
+            plotter.plot_point(make_point(-0.5, 0.0, 0.0)),
+            PlotResult::Full);
+        assert_eq!(
+            plotter.plot_point(make_point(3.0, 0.0, 0.0)),
+            PlotResult::Filtered);
+        assert_eq!(
+            plotter.plot_escaped(make_point(1.0, 0.0, 0.0)),
+            PlotResult::Filtered);
+        assert_eq!(plotter.counts().discarded, 3);
+    }
+
+    #[test]
+    fn test_bounds_padding_keeps_max_faces() {
+        let face_points = [
+            make_point(1.0, 0.0, 0.0),
+            make_point(0.0, 1.0, 0.0),
+            make_point(0.0, 0.0, 1.0),
+            make_point(1.0, 1.0, 1.0),
+        ];
+
+        let mut plotter: ScatterPlot =
+            ScatterPlot::from_json(&object!{ "radius" => 1.0 });
+        for point in face_points.iter() {
+            assert!(!plotter.plot_point(point.clone()).is_accepted());
+        }
+
+        let mut padded: ScatterPlot = ScatterPlot::from_json(&object!{
+            "radius" => 1.0,
+            "bounds_padding" => 1e-4
+        });
+        for point in face_points.iter() {
+            assert!(padded.plot_point(point.clone()).is_accepted());
+        }
+        assert_eq!(Box::new(padded).into_points().len(), 4);
+    }
+
+    #[test]
+    fn test_infinity_flags_escaped_points() {
+        let json = object!{
+            "radius" => 4.0,
+            "plot_mask" => object!{
+                "type" => "sphere",
+                "radius" => 1.0
+            },
+            "infinity" => object!{
+                "clamp_radius" => 3.0
+            }
+        };
+        let mut plotter: ScatterPlot = ScatterPlot::from_json(&json);
+        let escaped = InternalPoint {
+            position: HalfMultivector::point(1e12, 0.0, 0.0),
+            color: HalfMultivector::point(0.5, 0.5, 0.5),
+            cluster_coordinates: Vec3::zero(),
+            iteration: 0,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        };
+        plotter.plot_points(&[escaped]);
+
+        // The flagged point is kept even though it is outside the mask
+        let points = Box::new(plotter).into_points();
+        assert_eq!(points.len(), 1);
+        let position = &points[0].position;
+        assert!((*position - Vec3::new(3.0, 0.0, 0.0)).length() < 1e-6);
+        let color = &points[0].color;
+        assert!((*color - Vec3::new(1.0, 0.0, 1.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn test_color_by_position() {
+        let json = object!{
+            "format" => "pnts",
+            "radius" => 2.0,
+            "bounds_padding" => 0.01,
+            "color_by_position" => true
+        };
+        let mut plotter = from_json(&json);
+        assert!(plotter.plot_point(make_point(2.0, 2.0, 2.0)).is_accepted());
+        assert!(plotter.plot_point(make_point(-2.0, -2.0, -2.0)).is_accepted());
+        assert!(plotter.plot_point(make_point(1.0, 0.0, -1.0)).is_accepted());
+
+        let mut colors: Vec<[f32; 3]> = plotter
+            .into_points()
+            .iter()
+            .map(|point| [*point.color.x(), *point.color.y(), *point.color.z()])
+            .collect();
+        colors.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert_eq!(colors, vec![
+            [0.0, 0.0, 0.0],
+            [0.75, 0.5, 0.25],
+            [1.0, 1.0, 1.0]
+        ]);
+    }
+
+    #[test]
+    fn test_no_infinity_discards_escaped_points() {
+        let mut plotter: ScatterPlot =
+            ScatterPlot::from_json(&object!{ "radius" => 4.0 });
+        assert!(!plotter.plot_escaped(make_point(1.0, 0.0, 0.0)).is_accepted());
+    }
+
+    #[test]
+    fn test_radial_clip_drops_inside_min() {
+        let mut plotter = make_clipped_plotter();
+        plotter.plot_point(make_point(0.5, 0.0, 0.0));
+        assert!(plotter.root.is_empty());
+    }
+
+    #[test]
+    fn test_radial_clip_drops_outside_max() {
+        let mut plotter = make_clipped_plotter();
+        plotter.plot_point(make_point(0.0, 3.0, 0.0));
+        assert!(plotter.root.is_empty());
+    }
+
+    #[test]
+    fn test_radial_clip_keeps_shell() {
+        let mut plotter = make_clipped_plotter();
+        plotter.plot_point(make_point(0.0, 0.0, 1.5));
+        assert!(!plotter.root.is_empty());
     }
 }
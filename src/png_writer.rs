@@ -0,0 +1,170 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+/// The 8 bytes at the start of every PNG file
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+/// IHDR bit depth: 8 bits per channel
+const BIT_DEPTH: u8 = 8;
+/// IHDR color type: RGB triples
+const COLOR_TYPE_RGB: u8 = 2;
+/// Each row of the image starts with a filter type byte. 0 means no filter
+const FILTER_NONE: u8 = 0;
+/// zlib header for deflate with a 32K window and no compression. The two
+/// bytes must be a multiple of 31 as a big-endian u16
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+/// Uncompressed deflate blocks can be at most this many bytes
+const MAX_STORED_BLOCK: usize = 65535;
+
+/// An object that can write an RGB image as a PNG file. To avoid
+/// extra dependencies, the image data is stored uncompressed. This is
+/// meant for small previews, so the file size doesn't matter much.
+///
+/// See https://www.w3.org/TR/png/
+pub struct PngWriter {
+    /// Width of the image in pixels
+    width: u32,
+    /// Height of the image in pixels
+    height: u32,
+}
+
+impl PngWriter {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height
+        }
+    }
+
+    /// Write the image to disk. The pixels are listed row by row from the
+    /// top left corner.
+    pub fn write(&self, fname: &str, pixels: &[[u8; 3]]) {
+        let pixel_count = (self.width * self.height) as usize;
+        if pixels.len() != pixel_count {
+            panic!(
+                "PngWriter: expected {} pixels, got {}",
+                pixel_count,
+                pixels.len());
+        }
+
+        let error_msg = format!("Cannot open {}", fname);
+        let mut file = File::create(fname).expect(&error_msg);
+        file.write_all(&PNG_SIGNATURE).expect("could not write PNG signature");
+        write_chunk(&mut file, b"IHDR", &self.make_header());
+        write_chunk(&mut file, b"IDAT", &self.make_image_data(pixels));
+        write_chunk(&mut file, b"IEND", &[]);
+    }
+
+    /// Make the contents of the IHDR chunk
+    fn make_header(&self) -> Vec<u8> {
+        let mut header = Vec::new();
+        header.extend_from_slice(&self.width.to_be_bytes());
+        header.extend_from_slice(&self.height.to_be_bytes());
+        header.push(BIT_DEPTH);
+        header.push(COLOR_TYPE_RGB);
+        // compression method, filter method, interlace method
+        header.extend_from_slice(&[0, 0, 0]);
+        header
+    }
+
+    /// Make the contents of the IDAT chunk, a zlib stream of the
+    /// filtered rows
+    fn make_image_data(&self, pixels: &[[u8; 3]]) -> Vec<u8> {
+        let mut raw = Vec::new();
+        for row in pixels.chunks(self.width as usize) {
+            raw.push(FILTER_NONE);
+            for pixel in row {
+                raw.extend_from_slice(pixel);
+            }
+        }
+
+        zlib_stored(&raw)
+    }
+}
+
+/// Write a PNG chunk: length, type, data, CRC of type + data
+fn write_chunk(file: &mut File, chunk_type: &[u8; 4], data: &[u8]) {
+    let error_msg = "could not write PNG chunk";
+    let length = data.len() as u32;
+    file.write_all(&length.to_be_bytes()).expect(error_msg);
+    file.write_all(chunk_type).expect(error_msg);
+    file.write_all(data).expect(error_msg);
+
+    let mut crc_data = chunk_type.to_vec();
+    crc_data.extend_from_slice(data);
+    file.write_all(&crc32(&crc_data).to_be_bytes()).expect(error_msg);
+}
+
+/// Wrap data in a zlib stream using uncompressed deflate blocks
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut result = ZLIB_HEADER.to_vec();
+    let block_count = data.len().div_ceil(MAX_STORED_BLOCK).max(1);
+    for i in 0..block_count {
+        let start = i * MAX_STORED_BLOCK;
+        let end = (start + MAX_STORED_BLOCK).min(data.len());
+        let block = &data[start..end];
+
+        // BFINAL is the lowest bit, BTYPE = 00 means stored
+        let is_final = i == block_count - 1;
+        result.push(is_final as u8);
+        let length = block.len() as u16;
+        result.extend_from_slice(&length.to_le_bytes());
+        result.extend_from_slice(&(!length).to_le_bytes());
+        result.extend_from_slice(block);
+    }
+
+    result.extend_from_slice(&adler32(data).to_be_bytes());
+    result
+}
+
+/// CRC-32 as used by PNG (and zip, gzip, etc.)
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Adler-32 checksum used at the end of a zlib stream
+fn adler32(data: &[u8]) -> u32 {
+    const MOD_ADLER: u32 = 65521;
+    let mut a = 1u32;
+    let mut b = 0u32;
+    for byte in data {
+        a = (a + *byte as u32) % MOD_ADLER;
+        b = (b + a) % MOD_ADLER;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+        // Every PNG ends with this IEND CRC
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+    }
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn test_zlib_stored_blocks() {
+        let data = vec![7u8; MAX_STORED_BLOCK + 10];
+        let stream = zlib_stored(&data);
+
+        // header + 2 blocks with 5 byte headers + data + checksum
+        assert_eq!(stream.len(), 2 + 2 * 5 + data.len() + 4);
+        assert_eq!(stream[2], 0);
+        assert_eq!(stream[2 + 5 + MAX_STORED_BLOCK], 1);
+    }
+}
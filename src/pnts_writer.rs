@@ -1,7 +1,8 @@
 use std::fs::File;
 use std::io::prelude::*;
 
-use crate::point::OutputPoint;
+use crate::point::TreePoint;
+use crate::vector::{Color, Vec3};
 
 /// Cesium Point Cloud format version 1.0
 const PNTS_VERSION: u32 = 1;
@@ -64,7 +65,7 @@ impl PntsWriter {
     }
 
     /// Write all the points in a buffer to a .pnts file of the given filename
-    pub fn write(&mut self, fname: &str, buffer: &Vec<OutputPoint>) {
+    pub fn write<P: TreePoint>(&mut self, fname: &str, buffer: &[P]) {
         let error_msg = format!("Cannot open {}", fname);
         let mut file = File::create(fname).expect(&error_msg);
         self.prepare_header(buffer);
@@ -74,7 +75,7 @@ impl PntsWriter {
 
     /// Create the feature table JSON and compute lengths of parts of the
     /// binary file. This updates the lengths stored in the struct for later
-    fn prepare_header(&mut self, buffer: &Vec<OutputPoint>) {
+    fn prepare_header<P: TreePoint>(&mut self, buffer: &[P]) {
         // The .pnts format stores  each feature contiguously. If the
         // the positions go first, we need to compute where the colors go
         let num_positions = buffer.len() as u32;
@@ -161,17 +162,10 @@ impl PntsWriter {
     }
 
     /// Write the body portion of the .pnts file, a packed list of points.
-    fn write_body(&self, file: &mut File, buffer: &Vec<OutputPoint>) {
-        let mut positions: Vec<u8> = Vec::new();
-        let mut colors: Vec<u8> = Vec::new();
-
-        for point in buffer {
-            let point_bytes: [u8; 12] = point.position.pack();
-            positions.extend_from_slice(&point_bytes);
-
-            let color_bytes: [u8; 3] = point.color.to_color().pack();
-            colors.extend_from_slice(&color_bytes);
-        }
+    fn write_body<P: TreePoint>(&self, file: &mut File, buffer: &[P]) {
+        let positions = Vec3::pack_all(buffer.iter().map(P::position));
+        let colors = Color::pack_all(
+            buffer.iter().map(|point| point.color().to_color()));
 
         let error_msg = "Could not write pnts file body";
         file.write_all(&positions).expect(error_msg);
@@ -1,3 +1,7 @@
+use std::borrow::Cow;
+
+use json::JsonValue;
+
 use crate::vector::Vec3;
 use crate::half_multivector::HalfMultivector;
 
@@ -19,11 +23,94 @@ pub struct Point<T> {
     /// Which sub-cluster (in the case of ManyCluster)
     pub cluster_id: u16,
     /// The ID of the point within the cluster
-    pub point_id: u16,
+    pub point_id: u32,
     /// The index of the last transformation that was applied
     pub last_xform: u8,
+    /// Like last_xform, but a transformation and its inverse (from
+    /// ["+inverse"]) have the same value
+    pub last_xform_group: u8,
     /// The index of the last color transformation that was applied
-    pub last_color_xform: u8
+    pub last_color_xform: u8,
+    /// The last few transformation indices packed into one number, see
+    /// IFS::get_xform_address(). This is 0 unless address_depth is set
+    pub xform_address: u32,
+    /// The p and n components of the position as a CGA vector before it
+    /// was homogenized, see HalfMultivector::conformal_components(). This
+    /// is only filled in for OutputPoint, InternalPoint has the whole
+    /// multivector already. The octree only keeps it with emit_cga, see
+    /// GlbPoint.
+    pub cga_pn: [f32; 2],
+    /// How many coincident points this point stands for. This is 1 unless
+    /// the plotter merged duplicates (see dedupe_epsilon in ScatterPlot)
+    pub weight: u32
+}
+
+/// Several fields of Point and of the tileset metadata are narrow integers
+/// to keep the points small. Panic if the number of things with ids in
+/// such a field is more than it can hold, rather than silently wrapping
+/// the ids around. what describes the things, e.g. "transformations"
+pub fn check_id_count(count: usize, max: usize, what: &str, field: &str) {
+    if count > max {
+        panic!(
+            "{} {} is too many, {} holds at most {}",
+            count, what, field, max);
+    }
+}
+
+/// The smallest and largest value of each component of a property over
+/// all the points, as [min, max]. Each of min and max is a number for
+/// scalar properties, or an array of components. This is null if there are
+/// no points.
+fn property_range<const N: usize>(
+        points: &[OutputPoint],
+        get: impl Fn(&OutputPoint) -> [f64; N]) -> JsonValue {
+    if points.is_empty() {
+        return JsonValue::Null;
+    }
+
+    let mut min = [f64::INFINITY; N];
+    let mut max = [f64::NEG_INFINITY; N];
+    for point in points {
+        for (i, value) in get(point).iter().enumerate() {
+            min[i] = min[i].min(*value);
+            max[i] = max[i].max(*value);
+        }
+    }
+
+    let to_json = |values: [f64; N]| -> JsonValue {
+        match N {
+            1 => values[0].into(),
+            _ => values.to_vec().into()
+        }
+    };
+    array![to_json(min), to_json(max)]
+}
+
+/// The range of each property of the points, e.g.
+/// {"iteration": [0, 999], "position": [[x, y, z], [x, y, z]], ...}, see
+/// property_range(). This tells viewer styles what values to expect.
+pub fn property_ranges(points: &[OutputPoint]) -> JsonValue {
+    let vec3 = |v: &Vec3| [*v.x() as f64, *v.y() as f64, *v.z() as f64];
+    object!{
+        "position" => property_range(points, |p| vec3(&p.position)),
+        "color" => property_range(points, |p| vec3(&p.color)),
+        "cluster_coordinates" =>
+            property_range(points, |p| vec3(&p.cluster_coordinates)),
+        "iteration" => property_range(points, |p| [p.iteration as f64]),
+        "cluster_copy" => property_range(points, |p| [p.cluster_copy as f64]),
+        "cluster_id" => property_range(points, |p| [p.cluster_id as f64]),
+        "point_id" => property_range(points, |p| [p.point_id as f64]),
+        "last_xform" => property_range(points, |p| [p.last_xform as f64]),
+        "last_xform_group" =>
+            property_range(points, |p| [p.last_xform_group as f64]),
+        "last_color_xform" =>
+            property_range(points, |p| [p.last_color_xform as f64]),
+        "xform_address" =>
+            property_range(points, |p| [p.xform_address as f64]),
+        "cga_pn" => property_range(
+            points, |p| [p.cga_pn[0] as f64, p.cga_pn[1] as f64]),
+        "weight" => property_range(points, |p| [p.weight as f64])
+    }
 }
 
 /// Internally the point is represented as a multivector in geometric algebra
@@ -46,7 +133,262 @@ impl From<InternalPoint> for OutputPoint {
             cluster_id: point.cluster_id,
             point_id: point.point_id,
             last_xform: point.last_xform,
-            last_color_xform: point.last_color_xform
+            last_xform_group: point.last_xform_group,
+            last_color_xform: point.last_color_xform,
+            xform_address: point.xform_address,
+            cga_pn: point.position.conformal_components(),
+            weight: point.weight
+        }
+    }
+}
+/// A lighter point for when only the position and color are written
+/// (i.e. .pnts tiles). This is 28 bytes instead of the 72 bytes of an
+/// OutputPoint, which adds up when the octree holds millions of points.
+#[derive(Clone)]
+pub struct CompactPoint {
+    pub position: Vec3,
+    pub color: Vec3,
+    /// See Point::weight
+    pub weight: u32,
+}
+
+impl From<OutputPoint> for CompactPoint {
+    fn from(point: OutputPoint) -> Self {
+        Self {
+            position: point.position,
+            color: point.color,
+            weight: point.weight
+        }
+    }
+}
+
+/// Converting back fills in the missing metadata with zeros
+impl From<CompactPoint> for OutputPoint {
+    fn from(point: CompactPoint) -> Self {
+        Self {
+            position: point.position,
+            color: point.color,
+            cluster_coordinates: Vec3::zero(),
+            iteration: 0,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: point.weight
+        }
+    }
+}
+
+/// The layout for .glb tiles without emit_cga. This is an OutputPoint
+/// without cga_pn, which saves 8 bytes per point since only emit_cga
+/// writes it.
+#[derive(Clone)]
+pub struct GlbPoint {
+    pub position: Vec3,
+    pub color: Vec3,
+    pub cluster_coordinates: Vec3,
+    pub iteration: u64,
+    pub cluster_copy: u16,
+    pub cluster_id: u16,
+    pub point_id: u32,
+    pub last_xform: u8,
+    pub last_xform_group: u8,
+    pub last_color_xform: u8,
+    pub xform_address: u32,
+    pub weight: u32
+}
+
+impl From<OutputPoint> for GlbPoint {
+    fn from(point: OutputPoint) -> Self {
+        Self {
+            position: point.position,
+            color: point.color,
+            cluster_coordinates: point.cluster_coordinates,
+            iteration: point.iteration,
+            cluster_copy: point.cluster_copy,
+            cluster_id: point.cluster_id,
+            point_id: point.point_id,
+            last_xform: point.last_xform,
+            last_xform_group: point.last_xform_group,
+            last_color_xform: point.last_color_xform,
+            xform_address: point.xform_address,
+            weight: point.weight
+        }
+    }
+}
+
+/// Converting back sets cga_pn to zeros
+impl From<GlbPoint> for OutputPoint {
+    fn from(point: GlbPoint) -> Self {
+        Self {
+            position: point.position,
+            color: point.color,
+            cluster_coordinates: point.cluster_coordinates,
+            iteration: point.iteration,
+            cluster_copy: point.cluster_copy,
+            cluster_id: point.cluster_id,
+            point_id: point.point_id,
+            last_xform: point.last_xform,
+            last_xform_group: point.last_xform_group,
+            last_color_xform: point.last_color_xform,
+            xform_address: point.xform_address,
+            cga_pn: [0.0, 0.0],
+            weight: point.weight
         }
     }
-}
\ No newline at end of file
+}
+
+/// A point layout that can be stored in the octree. The octree only needs
+/// the position, color and weight, everything else is carried along for
+/// the tile writers.
+pub trait TreePoint: Clone + From<OutputPoint> + Into<OutputPoint> + 'static {
+    fn position(&self) -> &Vec3;
+    fn color(&self) -> &Vec3;
+    fn set_color(&mut self, color: Vec3);
+    fn weight(&self) -> u32;
+    fn set_weight(&mut self, weight: u32);
+
+    /// View a buffer of points as full OutputPoints for the writers that
+    /// need the metadata. This only copies if the layout is different.
+    fn as_output_points(points: &[Self]) -> Cow<'_, [OutputPoint]>;
+}
+
+impl TreePoint for OutputPoint {
+    fn position(&self) -> &Vec3 {
+        &self.position
+    }
+
+    fn color(&self) -> &Vec3 {
+        &self.color
+    }
+
+    fn set_color(&mut self, color: Vec3) {
+        self.color = color;
+    }
+
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    fn as_output_points(points: &[Self]) -> Cow<'_, [OutputPoint]> {
+        Cow::Borrowed(points)
+    }
+}
+
+impl TreePoint for CompactPoint {
+    fn position(&self) -> &Vec3 {
+        &self.position
+    }
+
+    fn color(&self) -> &Vec3 {
+        &self.color
+    }
+
+    fn set_color(&mut self, color: Vec3) {
+        self.color = color;
+    }
+
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    fn as_output_points(points: &[Self]) -> Cow<'_, [OutputPoint]> {
+        Cow::Owned(points.iter().cloned().map(OutputPoint::from).collect())
+    }
+}
+
+impl TreePoint for GlbPoint {
+    fn position(&self) -> &Vec3 {
+        &self.position
+    }
+
+    fn color(&self) -> &Vec3 {
+        &self.color
+    }
+
+    fn set_color(&mut self, color: Vec3) {
+        self.color = color;
+    }
+
+    fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+
+    fn as_output_points(points: &[Self]) -> Cow<'_, [OutputPoint]> {
+        Cow::Owned(points.iter().cloned().map(OutputPoint::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::mem::size_of;
+
+    #[test]
+    fn test_compact_point_is_smaller() {
+        assert!(size_of::<CompactPoint>() * 2 <= size_of::<OutputPoint>());
+    }
+
+    #[test]
+    fn test_glb_point_drops_cga() {
+        assert_eq!(size_of::<OutputPoint>(), 72);
+        assert_eq!(size_of::<GlbPoint>(), 64);
+    }
+
+    #[test]
+    fn test_property_ranges() {
+        let make_point = |x: f32, iteration: u64| OutputPoint {
+            position: Vec3::new(x, -x, 0.5),
+            color: Vec3::ones(),
+            cluster_coordinates: Vec3::zero(),
+            iteration,
+            cluster_copy: 0,
+            cluster_id: 0,
+            point_id: 0,
+            last_xform: 0,
+            last_xform_group: 0,
+            last_color_xform: 0,
+            xform_address: 0,
+            cga_pn: [0.0, 0.0],
+            weight: 1
+        };
+        let points = [make_point(-0.25, 7), make_point(0.75, 3)];
+        let ranges = property_ranges(&points);
+        assert_eq!(ranges["iteration"], array![3, 7]);
+        assert_eq!(
+            ranges["position"],
+            array![array![-0.25, -0.75, 0.5], array![0.75, 0.25, 0.5]]);
+        assert_eq!(ranges["weight"], array![1, 1]);
+
+        assert!(property_ranges(&[])["iteration"].is_null());
+    }
+
+    #[test]
+    fn test_check_id_count() {
+        check_id_count(255, u8::MAX as usize, "transformations", "last_xform");
+    }
+
+    #[test]
+    #[should_panic(expected = "256 transformations is too many, last_xform \
+        holds at most 255")]
+    fn test_check_id_count_overflow() {
+        check_id_count(256, u8::MAX as usize, "transformations", "last_xform");
+    }
+}
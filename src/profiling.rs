@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+pub use crate::half_multivector::product_op_count;
+
+/// Accumulates the time spent in one phase of the program. The phases are
+/// often interleaved (e.g. transforming points and inserting them into
+/// the octree), so this can be started and stopped many times.
+///
+/// When disabled, this does nothing so the hot loops don't pay for calling
+/// Instant::now()
+#[derive(Clone, Copy)]
+pub struct PhaseTimer {
+    /// If false, start() and stop() are no-ops
+    enabled: bool,
+    /// Total time spent in this phase so far
+    elapsed: Duration,
+}
+
+impl PhaseTimer {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+
+    /// Start timing. Pass the result to stop()
+    pub fn start(&self) -> Option<Instant> {
+        if self.enabled {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    /// Stop timing and add the time since start() to the total
+    pub fn stop(&mut self, start: Option<Instant>) {
+        if let Some(start_time) = start {
+            self.elapsed += start_time.elapsed();
+        }
+    }
+
+    /// Get the total time spent in this phase
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Breakdown of where the time went for a single run. The iterate time
+/// includes octree insertion, so the transform time is estimated as the
+/// difference.
+#[derive(Default)]
+pub struct Profile {
+    /// Time spent in Algorithm::iterate()
+    pub iterate: Duration,
+    /// Time spent adding points to the octree (part of iterate)
+    pub insertion: Duration,
+    /// Time spent in Algorithm::save()
+    pub save: Duration,
+    /// Time spent decimating the octree to generate LODs (part of save)
+    pub decimation: Duration,
+    /// Time spent writing the tileset to disk (part of save)
+    pub writing: Duration,
+}
+
+impl Profile {
+    /// Print a summary table of the phases
+    pub fn print_summary(&self) {
+        let transforms = self.iterate.checked_sub(self.insertion)
+            .unwrap_or_default();
+        let total = self.iterate + self.save;
+
+        println!("{:<24} {:>10}", "Phase", "Time (s)");
+        print_row("iterate", self.iterate);
+        print_row("  transforms + other", transforms);
+        print_row("  octree insertion", self.insertion);
+        print_row("save", self.save);
+        print_row("  LOD decimation", self.decimation);
+        print_row("  tileset writing", self.writing);
+        print_row("total", total);
+    }
+}
+
+/// Print a single row of the summary table
+fn print_row(name: &str, duration: Duration) {
+    println!("{:<24} {:>10.3}", name, duration.as_secs_f64());
+}
@@ -0,0 +1,171 @@
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+use json::JsonValue;
+
+use crate::log::{self, LogLevel};
+
+/// Default number of plotted points between progress updates
+const DEFAULT_PROGRESS_INTERVAL: usize = 100000;
+/// When stdout is not a terminal, each update is a separate line, so only
+/// print one every this many percent
+const LOG_PERCENT_STEP: usize = 10;
+
+/// Parse progress_interval, the number of plotted points between progress
+/// updates. 0 turns progress off. Returns None when progress is off.
+pub fn parse_progress_interval(json: &JsonValue) -> Option<usize> {
+    match json {
+        JsonValue::Null => Some(DEFAULT_PROGRESS_INTERVAL),
+        _ => match json.as_usize() {
+            Some(0) => None,
+            Some(interval) => Some(interval),
+            None => panic!("progress_interval must be a non-negative integer")
+        }
+    }
+}
+
+/// Report how far along a long run is. On a terminal this is a single line
+/// that is redrawn with a carriage return. Otherwise (e.g. when logging to
+/// a file) it prints a line only every LOG_PERCENT_STEP percent so the log
+/// stays short.
+pub struct Progress {
+    /// Total number of iterations of the run
+    total: usize,
+    /// Number of plotted points between updates, or None for no output
+    interval: Option<usize>,
+    /// Print the next update once this many points were plotted
+    next_update: usize,
+    /// The iteration the run started from. This is nonzero when resuming
+    first_iteration: usize,
+    /// When the run started
+    start_time: Instant,
+    /// If true, redraw one line rather than printing new lines
+    is_terminal: bool,
+    /// For non-terminal output, the last percentage that was printed
+    last_logged_percent: Option<usize>,
+    /// True if a progress line is on the screen without a newline
+    line_open: bool,
+}
+
+impl Progress {
+    /// Start tracking a run of total iterations that begins at
+    /// first_iteration
+    pub fn new(total: usize, first_iteration: usize, interval: Option<usize>)
+            -> Self {
+        // --quiet turns progress off too
+        let interval = interval.filter(|_| log::enabled(LogLevel::Normal));
+        Self {
+            total,
+            interval,
+            next_update: interval.unwrap_or(0),
+            first_iteration,
+            start_time: Instant::now(),
+            is_terminal: io::stdout().is_terminal(),
+            last_logged_percent: None,
+            line_open: false,
+        }
+    }
+
+    /// Record that iterations are done and points have been plotted so
+    /// far. This only prints when another interval of points has passed,
+    /// so it is cheap to call every iteration.
+    pub fn update(&mut self, iterations: usize, points: usize) {
+        let interval = match self.interval {
+            Some(interval) if points >= self.next_update => interval,
+            _ => return
+        };
+        // Skip ahead in case a single iteration plotted many intervals
+        // worth of points (e.g. chaos sets)
+        self.next_update = (points / interval + 1) * interval;
+
+        let percent = self.percent(iterations);
+        let message = format!(
+            "{:5.1}% {} points, ETA {}",
+            percent,
+            points,
+            self.eta(iterations)
+                .map(format_duration)
+                .unwrap_or_else(|| "?".to_string()));
+
+        if self.is_terminal {
+            // Pad to clear any leftovers from a longer previous line
+            print!("\r{:<40}", message);
+            io::stdout().flush().unwrap_or(());
+            self.line_open = true;
+        } else {
+            let step = (percent as usize) / LOG_PERCENT_STEP * LOG_PERCENT_STEP;
+            if self.last_logged_percent.is_none_or(|last| step > last) {
+                println!("{}", message);
+                self.last_logged_percent = Some(step);
+            }
+        }
+    }
+
+    /// End the progress line so other messages start on a new line. The
+    /// next update() starts a new progress line.
+    pub fn interrupt(&mut self) {
+        if self.line_open {
+            println!();
+            self.line_open = false;
+        }
+    }
+
+    /// Call once the run is done
+    pub fn finish(&mut self) {
+        self.interrupt();
+    }
+
+    fn percent(&self, iterations: usize) -> f64 {
+        if self.total == 0 {
+            return 100.0;
+        }
+        (100.0 * iterations as f64 / self.total as f64).min(100.0)
+    }
+
+    /// Estimate the time left from how fast the iterations went so far
+    fn eta(&self, iterations: usize) -> Option<Duration> {
+        let done = iterations.saturating_sub(self.first_iteration);
+        if done == 0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(iterations);
+        let elapsed = self.start_time.elapsed().as_secs_f64();
+        Some(Duration::from_secs_f64(elapsed * remaining as f64 / done as f64))
+    }
+}
+
+/// Format a duration as h:mm:ss
+fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5)), "0:00:05");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+
+    #[test]
+    fn test_parse_progress_interval() {
+        assert_eq!(
+            parse_progress_interval(&JsonValue::Null),
+            Some(DEFAULT_PROGRESS_INTERVAL));
+        assert_eq!(parse_progress_interval(&0.into()), None);
+        assert_eq!(parse_progress_interval(&500.into()), Some(500));
+    }
+
+    #[test]
+    fn test_update_skips_ahead() {
+        let mut progress = Progress::new(10, 0, Some(100));
+        progress.update(1, 50);
+        assert_eq!(progress.next_update, 100);
+        progress.update(2, 1234);
+        assert_eq!(progress.next_update, 1300);
+        assert_eq!(progress.percent(2), 20.0);
+    }
+}
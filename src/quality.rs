@@ -0,0 +1,166 @@
+use std::collections::HashSet;
+
+use crate::bbox::BBox;
+use crate::octrees::OctNode;
+use crate::point::TreePoint;
+use crate::vector::Vec3;
+
+/// Coarsest and finest grid levels used for box counting. Level d splits
+/// the bounding box into 2^d cells along each axis. Level 0 is skipped since
+/// it always contains exactly one box.
+const MIN_BOX_LEVEL: u8 = 1;
+const MAX_BOX_LEVEL: u8 = 8;
+
+/// A measure of how "good" a render is, used to pick the best of several
+/// runs with --seed-sweep. Higher is better for every metric.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QualityMetric {
+    /// Box-counting estimate of the fractal dimension. Runs that collapse
+    /// onto a few points or curves score low
+    Dimension,
+    /// Number of non-empty leaves in the octree (or pixels in an image),
+    /// i.e. how much of space the attractor fills at the finest level
+    FilledLeaves,
+}
+
+impl QualityMetric {
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "dimension" => Self::Dimension,
+            "leaves" => Self::FilledLeaves,
+            _ => panic!("metric must be either dimension or leaves")
+        }
+    }
+}
+
+/// Collect the positions of every point stored in the octree
+pub fn collect_positions<P: TreePoint>(
+        root: &OctNode<P>, positions: &mut Vec<Vec3>) {
+    root.visit(&mut |node, _| {
+        positions.extend(node.get_points().iter().map(|point| *point.position()));
+        true
+    });
+}
+
+/// Count the leaves of the octree that contain at least one point
+pub fn count_filled_leaves<P: TreePoint>(root: &OctNode<P>) -> usize {
+    let mut count = 0;
+    root.visit(&mut |node, _| {
+        if node.is_leaf() && !node.is_empty() {
+            count += 1;
+        }
+        true
+    });
+    count
+}
+
+/// Estimate the box-counting dimension of a point cloud. The bounding box
+/// is divided into finer and finer grids, and the number of occupied cells
+/// N is counted at each level d. For a fractal of dimension D,
+/// N ~ 2^(D * d), so D is the slope of log2(N) vs. d.
+pub fn box_counting_dimension(positions: &[Vec3], bounds: &BBox) -> f64 {
+    let counts: Vec<(f64, f64)> = (MIN_BOX_LEVEL..=MAX_BOX_LEVEL)
+        .map(|level| {
+            let cells: HashSet<u64> = positions
+                .iter()
+                .filter(|position| bounds.contains(position))
+                .map(|position| bounds.morton_code(position, level))
+                .collect();
+            (level as f64, cells.len() as f64)
+        })
+        .collect();
+
+    fit_dimension(&counts)
+}
+
+/// Estimate the box-counting dimension of an image from the set of
+/// occupied pixels. Each level halves the resolution. This measures the
+/// dimension of the projection, so it is at most 2.
+pub fn image_box_counting_dimension(occupied: &[(u32, u32)], levels: u8)
+        -> f64 {
+    let counts: Vec<(f64, f64)> = (0..levels).map(|level| {
+        let shift = levels - 1 - level;
+        let cells: HashSet<(u32, u32)> = occupied
+            .iter()
+            .map(|(x, y)| (x >> shift, y >> shift))
+            .collect();
+        (level as f64, cells.len() as f64)
+    }).collect();
+
+    fit_dimension(&counts)
+}
+
+/// Least squares fit of the slope of log2(count) vs. level. Levels
+/// with no occupied cells are skipped.
+fn fit_dimension(counts: &[(f64, f64)]) -> f64 {
+    let samples: Vec<(f64, f64)> = counts
+        .iter()
+        .filter(|(_, count)| *count > 0.0)
+        .map(|(level, count)| (*level, count.log2()))
+        .collect();
+
+    if samples.len() < 2 {
+        return 0.0;
+    }
+
+    let n = samples.len() as f64;
+    let mean_x = samples.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = samples.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = samples
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = samples.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    covariance / variance
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_box() -> BBox {
+        BBox::new(0.0, 1.0, 0.0, 1.0, 0.0, 1.0)
+    }
+
+    #[test]
+    fn test_line_dimension() {
+        let positions: Vec<Vec3> = (0..10000)
+            .map(|i| Vec3::new(i as f32 / 10000.0, 0.5, 0.5))
+            .collect();
+        let dimension = box_counting_dimension(&positions, &unit_box());
+        assert!((dimension - 1.0).abs() < 0.05, "{}", dimension);
+    }
+
+    #[test]
+    fn test_plane_dimension() {
+        let mut positions = Vec::new();
+        for i in 0..300 {
+            for j in 0..300 {
+                positions.push(
+                    Vec3::new(i as f32 / 300.0, j as f32 / 300.0, 0.25));
+            }
+        }
+        let dimension = box_counting_dimension(&positions, &unit_box());
+        assert!((dimension - 2.0).abs() < 0.05, "{}", dimension);
+    }
+
+    #[test]
+    fn test_single_point_dimension() {
+        let positions = vec![Vec3::new(0.3, 0.3, 0.3); 100];
+        let dimension = box_counting_dimension(&positions, &unit_box());
+        assert!(dimension.abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_image_dimension() {
+        // A filled 64x64 image is 2 dimensional
+        let mut occupied = Vec::new();
+        for x in 0..64 {
+            for y in 0..64 {
+                occupied.push((x, y));
+            }
+        }
+        let dimension = image_box_counting_dimension(&occupied, 6);
+        assert!((dimension - 2.0).abs() < 1e-9);
+    }
+}
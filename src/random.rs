@@ -0,0 +1,153 @@
+//! The random numbers for a render. The choosers, random cluster points,
+//! random start positions and colors all draw from one generator per
+//! thread, so a "seed" in the parameter file makes the whole render
+//! reproducible, see reseed(). Like the log level, this is global since
+//! random numbers are drawn deep inside the algorithms.
+
+use std::cell::RefCell;
+
+use json::JsonValue;
+use rand::{Error, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+
+thread_local! {
+    static GENERATOR: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+/// Restart this thread's generator from the seed, or from entropy if there
+/// is none
+pub fn reseed(seed: Option<u64>) {
+    let generator = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy()
+    };
+    GENERATOR.with(|cell| *cell.borrow_mut() = generator);
+}
+
+/// Parse the optional "seed" of a parameter file
+pub fn seed_from_json(json: &JsonValue) -> Option<u64> {
+    match json {
+        JsonValue::Null => None,
+        seed => Some(seed
+            .as_u64()
+            .expect("seed must be a non-negative integer"))
+    }
+}
+
+/// A random seed, for runs that must be repeated exactly but don't have
+/// a seed of their own
+pub fn random_seed() -> u64 {
+    rng().next_u64()
+}
+
+/// A handle to this thread's generator. It has no state of its own, so it
+/// can be stored anywhere a ThreadRng would be.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RenderRng;
+
+/// Get a handle to this thread's generator
+pub fn rng() -> RenderRng {
+    RenderRng
+}
+
+impl RngCore for RenderRng {
+    fn next_u32(&mut self) -> u32 {
+        GENERATOR.with(|cell| cell.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        GENERATOR.with(|cell| cell.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        GENERATOR.with(|cell| cell.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        GENERATOR.with(|cell| cell.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+/// This thread's generator, or a generator of its own for a part of the
+/// render that has its own seed (e.g. jitter). That seed wins over the
+/// render's seed.
+pub enum OverridableRng {
+    Render(RenderRng),
+    Seeded(Box<StdRng>),
+}
+
+/// Get this thread's generator, or a separate one seeded with seed
+pub fn overridable_rng(seed: Option<u64>) -> OverridableRng {
+    match seed {
+        Some(seed) =>
+            OverridableRng::Seeded(Box::new(StdRng::seed_from_u64(seed))),
+        None => OverridableRng::Render(rng())
+    }
+}
+
+impl RngCore for OverridableRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            Self::Render(rng) => rng.next_u32(),
+            Self::Seeded(rng) => rng.next_u32(),
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            Self::Render(rng) => rng.next_u64(),
+            Self::Seeded(rng) => rng.next_u64(),
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        match self {
+            Self::Render(rng) => rng.fill_bytes(dest),
+            Self::Seeded(rng) => rng.fill_bytes(dest),
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        match self {
+            Self::Render(rng) => rng.try_fill_bytes(dest),
+            Self::Seeded(rng) => rng.try_fill_bytes(dest),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_reseed_repeats() {
+        let draw = || -> Vec<u32> {
+            (0..5).map(|_| rng().gen_range(0, 1000)).collect()
+        };
+        reseed(Some(42));
+        let first = draw();
+        reseed(Some(42));
+        assert_eq!(draw(), first);
+        reseed(Some(43));
+        assert_ne!(draw(), first);
+        reseed(None);
+    }
+
+    #[test]
+    fn test_overridable_rng() {
+        let draw = |rng: &mut OverridableRng| -> Vec<u32> {
+            (0..5).map(|_| rng.gen_range(0, 1000)).collect()
+        };
+        let mut seeded = overridable_rng(Some(7));
+        let first = draw(&mut seeded);
+
+        // The render's seed doesn't change a generator with its own seed
+        reseed(Some(42));
+        assert_eq!(draw(&mut overridable_rng(Some(7))), first);
+        let from_render = draw(&mut overridable_rng(None));
+        reseed(Some(42));
+        assert_eq!(draw(&mut overridable_rng(None)), from_render);
+        reseed(None);
+    }
+}
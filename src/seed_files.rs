@@ -0,0 +1,357 @@
+use std::fs;
+
+use crate::vector::Vec3;
+
+/// Points loaded from a file to use as a cluster
+pub struct SeedPoints {
+    /// The position of each point
+    pub positions: Vec<Vec3>,
+    /// The color of each point in [0, 1], if the file has colors
+    pub colors: Option<Vec<Vec3>>,
+}
+
+/// Load seed points from a file. The format is chosen by the extension:
+///
+/// - `.csv`: One point per line, `x,y,z` or `x,y,z,r,g,b` with colors in
+///   [0, 1]. If the first line is a header, the columns are found by
+///   name (x, y, z and optionally r, g, b), so the output of a CSV plotter
+///   can be loaded directly. Blank lines and lines starting with `#` are
+///   skipped.
+/// - `.ply`: ASCII PLY with a vertex element that has x, y, z properties
+///   and optionally red, green, blue. uchar colors are scaled from
+///   [0, 255] to [0, 1]. Other elements like faces are ignored.
+pub fn load_seed_points(path: &str) -> SeedPoints {
+    let text = fs::read_to_string(path).unwrap_or_else(
+        |err| panic!("Could not read seed file {}: {}", path, err));
+
+    let lower = path.to_lowercase();
+    if lower.ends_with(".csv") {
+        parse_csv(&text, path)
+    } else if lower.ends_with(".ply") {
+        parse_ply(&text, path)
+    } else {
+        panic!("Seed file {} must be a .csv or .ply file", path);
+    }
+}
+
+/// Parse the numbers on a line, or None if any field is not a number
+fn parse_numbers<'a>(fields: impl Iterator<Item = &'a str>) -> Option<Vec<f32>> {
+    fields.map(|field| field.trim().parse::<f32>().ok()).collect()
+}
+
+/// Make a Vec3 from three columns of a row
+fn pick(row: &[f32], columns: &[usize; 3]) -> Vec3 {
+    Vec3::new(row[columns[0]], row[columns[1]], row[columns[2]])
+}
+
+/// Parse the points from the contents of a CSV file
+fn parse_csv(text: &str, path: &str) -> SeedPoints {
+    let mut lines = text
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .peekable();
+
+    // Without a header, the columns are x, y, z, [r, g, b]
+    let mut position_columns = [0, 1, 2];
+    let mut color_columns = None;
+    let mut has_header = false;
+    if let Some(first_line) = lines.peek() {
+        if parse_numbers(first_line.split(',')).is_none() {
+            let names: Vec<&str> =
+                first_line.split(',').map(|name| name.trim()).collect();
+            let find = |name: &str| names.iter().position(|x| *x == name);
+            position_columns = match (find("x"), find("y"), find("z")) {
+                (Some(x), Some(y), Some(z)) => [x, y, z],
+                _ => panic!("{}: the header must have x, y and z columns", path)
+            };
+            color_columns = match (find("r"), find("g"), find("b")) {
+                (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+                _ => None
+            };
+            has_header = true;
+        }
+    }
+    if has_header {
+        lines.next();
+    }
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let row = parse_numbers(line.split(',')).unwrap_or_else(
+            || panic!("{}: row {} is not a list of numbers", path, i + 1));
+        if !has_header && i == 0 && row.len() >= 6 {
+            color_columns = Some([3, 4, 5]);
+        }
+
+        let needed = position_columns
+            .iter()
+            .chain(color_columns.iter().flatten())
+            .max()
+            .unwrap() + 1;
+        if row.len() < needed {
+            panic!("{}: row {} has too few columns", path, i + 1);
+        }
+
+        positions.push(pick(&row, &position_columns));
+        if let Some(columns) = &color_columns {
+            colors.push(pick(&row, columns));
+        }
+    }
+
+    SeedPoints {
+        positions,
+        colors: color_columns.map(|_| colors)
+    }
+}
+
+/// An element declared in a PLY header
+struct PlyElement {
+    /// Element name, e.g. "vertex" or "face"
+    name: String,
+    /// How many lines of data it has
+    count: usize,
+    /// Property names and types in order
+    properties: Vec<(String, String)>,
+}
+
+/// Parse the points from the contents of an ASCII PLY file
+fn parse_ply(text: &str, path: &str) -> SeedPoints {
+    let mut lines = text.lines();
+    if lines.next().map(|line| line.trim()) != Some("ply") {
+        panic!("{}: not a PLY file", path);
+    }
+
+    let mut elements: Vec<PlyElement> = Vec::new();
+    for line in &mut lines {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["end_header"] => break,
+            ["format", "ascii", _] => {},
+            ["format", format, _] =>
+                panic!("{}: only ASCII PLY is supported, not {}", path, format),
+            ["element", name, count] => {
+                let count = count.parse().unwrap_or_else(
+                    |_| panic!("{}: bad element count {}", path, count));
+                elements.push(PlyElement {
+                    name: name.to_string(),
+                    count,
+                    properties: Vec::new()
+                });
+            },
+            ["property", "list", ..] => {
+                // lists (e.g. face indices) are only skipped over, so their
+                // properties don't matter
+            },
+            ["property", data_type, name] => {
+                let element = elements.last_mut().unwrap_or_else(
+                    || panic!("{}: property before element", path));
+                element.properties.push(
+                    (name.to_string(), data_type.to_string()));
+            },
+            _ => {}
+        }
+    }
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    let mut has_colors = false;
+    for element in elements.iter() {
+        if element.name != "vertex" {
+            // Skip the data lines of other elements
+            for _ in 0..element.count {
+                lines.next();
+            }
+            continue;
+        }
+
+        let find = |name: &str| element.properties
+            .iter()
+            .position(|(property, _)| property == name);
+        let position_columns = match (find("x"), find("y"), find("z")) {
+            (Some(x), Some(y), Some(z)) => [x, y, z],
+            _ => panic!("{}: vertices must have x, y and z", path)
+        };
+        let color_columns = match (find("red"), find("green"), find("blue")) {
+            (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+            _ => None
+        };
+        // 8-bit colors are the norm, but float colors are allowed too
+        let color_scale = match color_columns {
+            Some([r, _, _]) if element.properties[r].1.contains("char") =>
+                1.0 / 255.0,
+            _ => 1.0
+        };
+        has_colors = color_columns.is_some();
+
+        for i in 0..element.count {
+            let line = lines.next().unwrap_or_else(
+                || panic!("{}: expected {} vertices", path, element.count));
+            let row = parse_numbers(line.split_whitespace()).unwrap_or_else(
+                || panic!("{}: vertex {} is not a list of numbers", path, i));
+            if row.len() < element.properties.len() {
+                panic!("{}: vertex {} has too few properties", path, i);
+            }
+
+            positions.push(pick(&row, &position_columns));
+            if let Some(columns) = &color_columns {
+                colors.push(pick(&row, columns) * color_scale);
+            }
+        }
+        break;
+    }
+
+    SeedPoints {
+        positions,
+        colors: if has_colors { Some(colors) } else { None }
+    }
+}
+
+/// A triangle mesh loaded from a file, for sampling its surface
+pub struct SeedMesh {
+    /// The three corners of each triangle
+    pub triangles: Vec<[Vec3; 3]>,
+}
+
+/// Load a triangle mesh from a Wavefront .obj file. Only the vertex
+/// positions (v) and faces (f) are used. Faces with more than 3 vertices
+/// are split into a fan of triangles around the first vertex, which is
+/// correct for the convex faces most modeling programs write.
+pub fn load_seed_mesh(path: &str) -> SeedMesh {
+    let text = fs::read_to_string(path).unwrap_or_else(
+        |err| panic!("Could not read mesh file {}: {}", path, err));
+
+    if !path.to_lowercase().ends_with(".obj") {
+        panic!("Mesh file {} must be an .obj file", path);
+    }
+    parse_obj(&text, path)
+}
+
+/// Find which vertex a face refers to. OBJ indices start at 1, and
+/// negative indices count back from the last vertex so far. The texture
+/// coordinate and normal indices (v/vt/vn) are ignored.
+fn parse_obj_index(field: &str, vertex_count: usize, path: &str) -> usize {
+    let index = field
+        .split('/')
+        .next()
+        .and_then(|x| x.parse::<i64>().ok())
+        .unwrap_or_else(|| panic!("{}: invalid face index {}", path, field));
+    let resolved = if index < 0 {
+        vertex_count as i64 + index
+    } else {
+        index - 1
+    };
+    if resolved < 0 || resolved >= vertex_count as i64 {
+        panic!("{}: face index {} is out of range", path, field);
+    }
+    resolved as usize
+}
+
+/// Parse the triangles from the contents of an OBJ file
+fn parse_obj(text: &str, path: &str) -> SeedMesh {
+    let mut vertices = Vec::new();
+    let mut triangles = Vec::new();
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        match fields.next() {
+            Some("v") => {
+                let coordinates = parse_numbers(fields.take(3))
+                    .filter(|coordinates| coordinates.len() == 3)
+                    .unwrap_or_else(
+                        || panic!("{}: invalid vertex {}", path, line));
+                vertices.push(pick(&coordinates, &[0, 1, 2]));
+            },
+            Some("f") => {
+                let face: Vec<usize> = fields
+                    .map(|field| parse_obj_index(field, vertices.len(), path))
+                    .collect();
+                if face.len() < 3 {
+                    panic!("{}: faces need at least 3 vertices", path);
+                }
+                for i in 1..(face.len() - 1) {
+                    triangles.push([
+                        vertices[face[0]],
+                        vertices[face[i]],
+                        vertices[face[i + 1]]
+                    ]);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    SeedMesh { triangles }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let plain = parse_csv("# comment\n1,2,3\n\n4,5,6\n", "test.csv");
+        assert_eq!(plain.positions.len(), 2);
+        assert_eq!(*plain.positions[1].z(), 6.0);
+        assert!(plain.colors.is_none());
+
+        // The same columns that the CSV plotter writes
+        let header = "x,y,z,r,g,b,iteration,cluster_id,point_id,last_xform\n\
+            1,2,3,0.5,0.25,1,0,0,0,0\n";
+        let seed = parse_csv(header, "test.csv");
+        assert_eq!(*seed.positions[0].y(), 2.0);
+        let colors = seed.colors.unwrap();
+        assert_eq!(*colors[0].y(), 0.25);
+    }
+
+    #[test]
+    fn test_parse_ply() {
+        let text = "ply\n\
+            format ascii 1.0\n\
+            element vertex 2\n\
+            property float x\n\
+            property float y\n\
+            property float z\n\
+            property uchar red\n\
+            property uchar green\n\
+            property uchar blue\n\
+            element face 1\n\
+            property list uchar int vertex_indices\n\
+            end_header\n\
+            0 0 1 255 0 0\n\
+            1 2 3 0 255 51\n\
+            3 0 1 2\n";
+        let seed = parse_ply(text, "test.ply");
+        assert_eq!(seed.positions.len(), 2);
+        assert_eq!(*seed.positions[1].x(), 1.0);
+        let colors = seed.colors.unwrap();
+        assert_eq!(*colors[0].x(), 1.0);
+        assert!((*colors[1].z() - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_obj() {
+        // A unit square as one quad, plus a triangle using negative
+        // indices and v/vt/vn references
+        let text = "# square\n\
+            v 0 0 0\n\
+            v 1 0 0\n\
+            v 1 1 0\n\
+            v 0 1 0\n\
+            vn 0 0 1\n\
+            f 1 2 3 4\n\
+            v 0 0 1\n\
+            f 1/1/1 2//1 -1\n";
+        let mesh = parse_obj(text, "test.obj");
+        assert_eq!(mesh.triangles.len(), 3);
+        assert_eq!(*mesh.triangles[1][1].x(), 1.0);
+        assert_eq!(*mesh.triangles[1][2].y(), 1.0);
+        assert_eq!(*mesh.triangles[2][2].z(), 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Could not read seed file")]
+    fn test_missing_file() {
+        load_seed_points("does/not/exist.csv");
+    }
+}
@@ -0,0 +1,181 @@
+use std::fs::File;
+use std::io::prelude::*;
+
+use json::JsonValue;
+
+/// Subtree format version 1 (3D Tiles 1.1)
+const SUBTREE_VERSION: u32 = 1;
+
+/// The JSON and binary chunks must be aligned to 8-byte boundaries, and so
+/// must each bitstream within the binary chunk
+const ALIGNMENT: u64 = 8;
+
+/// Padding character for the JSON chunk
+const PADDING_JSON: u8 = b' ';
+/// Padding character for the binary chunk
+const PADDING_BINARY: u8 = 0x00;
+
+/// An availability bitstream for implicit tiling. Bit i is stored in
+/// byte i / 8 at bit position i % 8 (least significant bit first).
+pub struct Bitstream {
+    /// The packed bits
+    bytes: Vec<u8>,
+    /// How many bits are set to 1. This is stored in the subtree JSON.
+    available_count: usize,
+}
+
+impl Bitstream {
+    /// Create a bitstream of the given length with all bits set to 0
+    pub fn new(bit_length: usize) -> Self {
+        let byte_length = bit_length.div_ceil(8);
+        Self {
+            bytes: vec![0; byte_length],
+            available_count: 0,
+        }
+    }
+
+    /// Set a bit to 1. Setting the same bit twice has no extra effect
+    pub fn set(&mut self, index: usize) {
+        let byte = index / 8;
+        let mask = 1u8 << (index % 8);
+        if self.bytes[byte] & mask == 0 {
+            self.bytes[byte] |= mask;
+            self.available_count += 1;
+        }
+    }
+
+    /// Borrow the packed bytes for writing to disk
+    pub fn get_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Format the availability JSON for this bitstream, which will be
+    /// stored in the given buffer view
+    pub fn to_json(&self, buffer_view: usize) -> JsonValue {
+        object!{
+            "bitstream" => buffer_view,
+            "availableCount" => self.available_count
+        }
+    }
+}
+
+/// Writer for a binary .subtree file as described in the 3D Tiles 1.1
+/// implicit tiling spec. See
+/// https://github.com/CesiumGS/3d-tiles/tree/main/specification/ImplicitTiling
+/// for more information.
+///
+/// The three availability bitstreams are stored in the internal buffer
+/// (binary chunk) in this order: tile, content, child subtree.
+pub struct SubtreeWriter {
+    /// Serialized subtree JSON
+    json: String,
+    /// Length of the JSON + padding
+    total_json_len: u64,
+    /// Length of the binary chunk including padding
+    total_bin_len: u64,
+}
+
+impl SubtreeWriter {
+    pub fn new() -> Self {
+        Self {
+            json: String::new(),
+            total_json_len: 0,
+            total_bin_len: 0,
+        }
+    }
+
+    /// Write a subtree file from its three availability bitstreams
+    pub fn write(
+            &mut self,
+            fname: &str,
+            tile_availability: &Bitstream,
+            content_availability: &Bitstream,
+            child_subtree_availability: &Bitstream) {
+        let bitstreams = [
+            tile_availability,
+            content_availability,
+            child_subtree_availability
+        ];
+        self.prepare_header(&bitstreams);
+
+        let error_msg = format!("Cannot open {}", fname);
+        let mut file = File::create(fname).expect(&error_msg);
+        self.write_header(&mut file);
+        self.write_body(&mut file, &bitstreams);
+    }
+
+    /// Lay out the buffer views and create the subtree JSON
+    fn prepare_header(&mut self, bitstreams: &[&Bitstream; 3]) {
+        let mut buffer_views = Vec::new();
+        let mut byte_offset = 0;
+        for bitstream in bitstreams.iter() {
+            let byte_length = bitstream.get_bytes().len() as u64;
+            buffer_views.push(object!{
+                "buffer" => 0,
+                "byteOffset" => byte_offset,
+                "byteLength" => byte_length
+            });
+            byte_offset += byte_length + compute_padding_len(byte_length);
+        }
+        let buffer_length = byte_offset;
+
+        let [tiles, contents, child_subtrees] = bitstreams;
+        let subtree = object!{
+            "buffers" => array![
+                object!{
+                    "byteLength" => buffer_length
+                }
+            ],
+            "bufferViews" => JsonValue::Array(buffer_views),
+            "tileAvailability" => tiles.to_json(0),
+            "contentAvailability" => array![contents.to_json(1)],
+            "childSubtreeAvailability" => child_subtrees.to_json(2)
+        };
+
+        self.json = json::stringify(subtree);
+        let json_len = self.json.len() as u64;
+        self.total_json_len = json_len + compute_padding_len(json_len);
+
+        // Each bitstream is already padded, so no extra padding is needed
+        self.total_bin_len = buffer_length;
+    }
+
+    /// Write the 24-byte header followed by the padded JSON
+    fn write_header(&self, file: &mut File) {
+        let error_msg = "could not write subtree header";
+        file.write_all(b"subt").expect(error_msg);
+        file.write_all(&SUBTREE_VERSION.to_le_bytes()).expect(error_msg);
+        file.write_all(&self.total_json_len.to_le_bytes()).expect(error_msg);
+        file.write_all(&self.total_bin_len.to_le_bytes()).expect(error_msg);
+
+        file.write_all(self.json.as_bytes()).expect(error_msg);
+        let json_padding_len = self.total_json_len - self.json.len() as u64;
+        let padding = make_padding(json_padding_len, PADDING_JSON);
+        file.write_all(&padding).expect(error_msg);
+    }
+
+    /// Write the bitstreams to the binary chunk
+    fn write_body(&self, file: &mut File, bitstreams: &[&Bitstream; 3]) {
+        let error_msg = "could not write subtree binary chunk";
+        for bitstream in bitstreams.iter() {
+            let bytes = bitstream.get_bytes();
+            file.write_all(bytes).expect(error_msg);
+            let padding_len = compute_padding_len(bytes.len() as u64);
+            let padding = make_padding(padding_len, PADDING_BINARY);
+            file.write_all(&padding).expect(error_msg);
+        }
+    }
+}
+
+/// Given a number of bytes, compute how much padding is needed to
+/// align to 8 bytes. The header is always 24 bytes, so the chunks stay
+/// aligned.
+fn compute_padding_len(num_bytes: u64) -> u64 {
+    let remainder = num_bytes % ALIGNMENT;
+    (ALIGNMENT - remainder) % ALIGNMENT
+}
+
+/// Create a padding of the given character and length
+fn make_padding(byte_len: u64, pad_char: u8) -> Vec<u8> {
+    (0..byte_len).map(|_| pad_char).collect()
+}
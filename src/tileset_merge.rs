@@ -0,0 +1,370 @@
+//! Combine tilesets that were already written into one tileset that refers
+//! to each of them as an external tileset. Only tileset.json files are read
+//! and written, the tiles themselves are left alone.
+
+use std::fs::{self, create_dir_all};
+use std::path::{Component, Path, PathBuf};
+
+use json::JsonValue;
+
+use crate::bbox::BBox;
+use crate::vector::Vec3;
+
+/// The pieces of an existing tileset needed to refer to it
+struct ChildTileset {
+    /// Path to the child's tileset.json relative to the merged tileset
+    uri: String,
+    /// The child's root bounding box in the merged tileset's frame. This
+    /// includes the child's root transform, if any
+    bounds: BBox,
+    /// The geometric error of the whole child tileset
+    geometric_error: f64,
+    /// The child's 3D Tiles version
+    version: String,
+    /// Application-specific data for the tile that refers to the child,
+    /// or null for none
+    extras: JsonValue,
+}
+
+impl ChildTileset {
+    /// Read {dirname}/tileset.json. out_dir is where the merged tileset
+    /// will be written, so the uri can be made relative to it.
+    fn load(dirname: &str, out_dir: &Path) -> Self {
+        let fname = Path::new(dirname).join("tileset.json");
+        let text = fs::read_to_string(&fname).unwrap_or_else(
+            |err| panic!("Could not read {}: {}", fname.display(), err));
+        let tileset = json::parse(&text).unwrap_or_else(
+            |err| panic!("Could not parse {}: {}", fname.display(), err));
+
+        let root = &tileset["root"];
+        let local_bounds = volume_from_json(&root["boundingVolume"])
+            .unwrap_or_else(|| panic!(
+                "{}: the root must have a box or sphere bounding volume",
+                fname.display()));
+        let bounds = match &root["transform"] {
+            JsonValue::Null => local_bounds,
+            transform => transform_box(&local_bounds, &parse_matrix(transform)),
+        };
+        let geometric_error = tileset["geometricError"]
+            .as_f64()
+            .unwrap_or_else(
+                || panic!("{}: geometricError must be a number", fname.display()));
+        let version = tileset["asset"]["version"]
+            .as_str()
+            .unwrap_or("1.0")
+            .to_string();
+
+        Self {
+            uri: relative_path(out_dir, &fname),
+            bounds,
+            geometric_error,
+            version,
+            extras: JsonValue::Null,
+        }
+    }
+
+    /// A tile whose content is the child tileset
+    fn to_tile_json(&self) -> JsonValue {
+        let mut tile = object!{
+            "boundingVolume" => self.bounds.to_json(),
+            "geometricError" => self.geometric_error,
+            "content" => object!{
+                "uri" => self.uri.clone()
+            }
+        };
+        if !self.extras.is_null() {
+            tile["extras"] = self.extras.clone();
+        }
+        tile
+    }
+}
+
+/// Write {out_dir}/tileset.json, with one child tile for each of the
+/// tilesets in child_dirs. The root bounding volume is the union of the
+/// children's root boxes.
+///
+/// A child's root transform stays in the child's tileset.json where the
+/// viewer applies it when loading the external tileset. The tile that refers
+/// to it has no transform, only a bounding box that contains the child's
+/// transformed root box. That way children with different transforms can
+/// be placed side by side.
+pub fn merge_tilesets(out_dir: &str, child_dirs: &[String]) {
+    merge_tagged_tilesets(out_dir, child_dirs, &[]);
+}
+
+/// Like merge_tilesets(), but the tile that refers to child i also gets
+/// extras[i] as its extras, e.g. to say which part of the fractal it is.
+/// extras may be shorter than child_dirs, the rest of the tiles have none.
+pub fn merge_tagged_tilesets(
+        out_dir: &str, child_dirs: &[String], extras: &[JsonValue]) {
+    if child_dirs.is_empty() {
+        panic!("merge-tilesets: at least one tileset is needed");
+    }
+
+    create_dir_all(out_dir).unwrap_or_else(
+        |err| panic!("Could not create directory {}: {}", out_dir, err));
+    let out_path = Path::new(out_dir);
+
+    let mut children: Vec<ChildTileset> = child_dirs
+        .iter()
+        .map(|dirname| ChildTileset::load(dirname, out_path))
+        .collect();
+    for (child, extras) in children.iter_mut().zip(extras.iter()) {
+        child.extras = extras.clone();
+    }
+
+    let first = &children[0].bounds;
+    let mut bounds = BBox::from_points(&[*first.min(), *first.max()]);
+    for child in children.iter().skip(1) {
+        bounds = bounds.union(&child.bounds);
+    }
+    let geometric_error = children
+        .iter()
+        .map(|child| child.geometric_error)
+        .fold(0.0, f64::max);
+    // Newer viewers can load older tilesets, but not the other way around
+    let version = if children.iter().any(|child| child.version != "1.0") {
+        "1.1"
+    } else {
+        "1.0"
+    };
+
+    let tiles: Vec<JsonValue> =
+        children.iter().map(|child| child.to_tile_json()).collect();
+    let tileset = object!{
+        "asset" => object!{
+            "version" => version,
+        },
+        "geometricError" => geometric_error,
+        "root" => object!{
+            "boundingVolume" => bounds.to_json(),
+            "geometricError" => geometric_error,
+            "refine" => "ADD",
+            "children" => JsonValue::Array(tiles)
+        }
+    };
+
+    let fname = out_path.join("tileset.json");
+    fs::write(&fname, json::stringify(tileset)).unwrap_or_else(
+        |err| panic!("Failed to write {}: {}", fname.display(), err));
+    info!(
+        "Wrote {} referencing {} tilesets", fname.display(), children.len());
+}
+
+/// Parse a 3D Tiles box or sphere bounding volume into the axis-aligned box
+/// that contains it. Regions are not supported since they are in
+/// geographic coordinates rather than the tileset's frame.
+fn volume_from_json(json: &JsonValue) -> Option<BBox> {
+    if json.has_key("box") {
+        box_from_json(&json["box"])
+    } else {
+        sphere_from_json(&json["sphere"])
+    }
+}
+
+/// Parse a 3D Tiles sphere bounding volume (a center and a radius) into
+/// the axis-aligned box that contains it
+fn sphere_from_json(json: &JsonValue) -> Option<BBox> {
+    let values: Vec<f32> = json.members().filter_map(|x| x.as_f32()).collect();
+    if values.len() != 4 {
+        return None;
+    }
+
+    let center = Vec3::new(values[0], values[1], values[2]);
+    Some(BBox::from_center(&center, &Vec3::splat(values[3])))
+}
+
+/// Parse a 3D Tiles box bounding volume (a center followed by three half
+/// axes) into the axis-aligned box that contains it
+fn box_from_json(json: &JsonValue) -> Option<BBox> {
+    let values: Vec<f32> = json.members().filter_map(|x| x.as_f32()).collect();
+    if values.len() != 12 {
+        return None;
+    }
+
+    let center = Vec3::new(values[0], values[1], values[2]);
+    let half_width = |axis: usize|
+        values[3 + axis].abs() + values[6 + axis].abs() + values[9 + axis].abs();
+    let half_widths = Vec3::new(half_width(0), half_width(1), half_width(2));
+    Some(BBox::from_center(&center, &half_widths))
+}
+
+/// Parse a 4x4 column-major transform matrix
+fn parse_matrix(json: &JsonValue) -> [f32; 16] {
+    let values: Vec<f32> = json.members().filter_map(|x| x.as_f32()).collect();
+    if values.len() != 16 {
+        panic!("transform must be an array of 16 numbers");
+    }
+    let mut matrix = [0.0; 16];
+    matrix.copy_from_slice(&values);
+    matrix
+}
+
+/// Find the axis-aligned box that contains a box after it is transformed
+/// by a column-major affine matrix
+fn transform_box(bbox: &BBox, matrix: &[f32; 16]) -> BBox {
+    let corners: Vec<Vec3> = bbox.corners().iter().map(|corner| {
+        let (x, y, z) = (*corner.x(), *corner.y(), *corner.z());
+        Vec3::new(
+            matrix[0] * x + matrix[4] * y + matrix[8] * z + matrix[12],
+            matrix[1] * x + matrix[5] * y + matrix[9] * z + matrix[13],
+            matrix[2] * x + matrix[6] * y + matrix[10] * z + matrix[14])
+    }).collect();
+    BBox::from_points(&corners)
+}
+
+/// Make an absolute version of a path without requiring it to exist
+fn absolute(path: &Path) -> PathBuf {
+    let path = match fs::canonicalize(path) {
+        Ok(canonical) => return canonical,
+        Err(_) => path,
+    };
+    if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .expect("Could not get the current directory")
+            .join(path)
+    }
+}
+
+/// Compute the path to a file relative to a directory, using forward
+/// slashes as 3D Tiles URIs do
+fn relative_path(from_dir: &Path, to_file: &Path) -> String {
+    let from = absolute(from_dir);
+    let to = absolute(to_file);
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut parts: Vec<String> = vec!["..".to_string(); from.len() - common];
+    for component in to.iter().skip(common) {
+        parts.push(component.as_os_str().to_string_lossy().to_string());
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_child(dirname: &Path, root: JsonValue) {
+        create_dir_all(dirname).unwrap();
+        let tileset = object!{
+            "asset" => object!{ "version" => "1.0" },
+            "geometricError" => 2.0,
+            "root" => root
+        };
+        fs::write(dirname.join("tileset.json"), json::stringify(tileset))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_merge_tilesets() {
+        let dir = std::env::temp_dir()
+            .join(format!("chaos-game-3d-merge-{}", std::process::id()));
+        let a = dir.join("a");
+        let b = dir.join("b");
+        write_child(&a, object!{
+            "boundingVolume" => BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
+                .to_json(),
+            "geometricError" => 2.0,
+        });
+        // The second tileset is moved 10 units along x
+        write_child(&b, object!{
+            "boundingVolume" => BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0)
+                .to_json(),
+            "geometricError" => 2.0,
+            "transform" => array![
+                1, 0, 0, 0,
+                0, 1, 0, 0,
+                0, 0, 1, 0,
+                10, 0, 0, 1
+            ]
+        });
+
+        let out = dir.join("out");
+        let children = vec![
+            a.to_str().unwrap().to_string(),
+            b.to_str().unwrap().to_string()
+        ];
+        merge_tilesets(out.to_str().unwrap(), &children);
+
+        let text = fs::read_to_string(out.join("tileset.json")).unwrap();
+        let tileset = json::parse(&text).unwrap();
+        let root = &tileset["root"];
+        let bounds = box_from_json(&root["boundingVolume"]["box"]).unwrap();
+        assert_eq!(*bounds.min().x(), -1.0);
+        assert_eq!(*bounds.max().x(), 11.0);
+        assert_eq!(*bounds.max().y(), 1.0);
+
+        let tiles = &root["children"];
+        assert_eq!(tiles.len(), 2);
+        assert_eq!(tiles[0]["content"]["uri"], "../a/tileset.json");
+        assert_eq!(tiles[1]["content"]["uri"], "../b/tileset.json");
+        let child_bounds =
+            box_from_json(&tiles[1]["boundingVolume"]["box"]).unwrap();
+        assert_eq!(*child_bounds.min().x(), 9.0);
+        assert!(tiles[1]["transform"].is_null());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_merge_sphere_roots() {
+        let dir = std::env::temp_dir().join(format!(
+            "chaos-game-3d-merge-sphere-{}", std::process::id()));
+        let a = dir.join("a");
+        write_child(&a, object!{
+            "boundingVolume" => object!{
+                "sphere" => array![1.0, 0.0, 0.0, 2.0]
+            },
+            "geometricError" => 2.0,
+        });
+
+        let out = dir.join("out");
+        merge_tilesets(
+            out.to_str().unwrap(), &[a.to_str().unwrap().to_string()]);
+        let text = fs::read_to_string(out.join("tileset.json")).unwrap();
+        let tileset = json::parse(&text).unwrap();
+        let bounds =
+            box_from_json(&tileset["root"]["boundingVolume"]["box"]).unwrap();
+        assert_eq!(*bounds.min().x(), -1.0);
+        assert_eq!(*bounds.max().x(), 3.0);
+        assert_eq!(*bounds.max().z(), 2.0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "the root must have a box or sphere")]
+    fn test_merge_rejects_regions() {
+        let dir = std::env::temp_dir().join(format!(
+            "chaos-game-3d-merge-region-{}", std::process::id()));
+        let a = dir.join("a");
+        write_child(&a, object!{
+            "boundingVolume" => object!{
+                "region" => array![0.0, 0.0, 0.1, 0.1, 0.0, 10.0]
+            },
+            "geometricError" => 2.0,
+        });
+        merge_tilesets(
+            dir.join("out").to_str().unwrap(),
+            &[a.to_str().unwrap().to_string()]);
+    }
+
+    #[test]
+    fn test_rotated_box_bounds() {
+        // A box rotated 45 degrees about z
+        let h = 0.5_f32.sqrt();
+        let json = array![0.0, 0.0, 0.0, h, h, 0.0, -h, h, 0.0, 0.0, 0.0, 1.0];
+        let bounds = box_from_json(&json).unwrap();
+        assert!((*bounds.max().x() - 2.0 * h).abs() < 1e-6);
+        assert!((*bounds.min().y() + 2.0 * h).abs() < 1e-6);
+        assert_eq!(*bounds.max().z(), 1.0);
+    }
+}
@@ -1,13 +1,20 @@
 use std::fs::{File, create_dir_all, remove_dir_all};
 use std::io::prelude::*;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use json::JsonValue;
 
+use crate::bbox::BBox;
+use crate::csv_writer::CsvWriter;
 use crate::fractal_metadata::FractalMetadata;
-use crate::octrees::OctNode;
+use crate::octrees::{ContentLayout, OctNode};
+use crate::point::{OutputPoint, TreePoint};
 use crate::pnts_writer::PntsWriter;
-use crate::glb_writer::GlbWriter;
+use crate::glb_writer::{FeatureCounts, GlbWriter, LineGlbWriter};
+use crate::normals::{self, NORMAL_NEIGHBORS};
+use crate::subtree_writer::{Bitstream, SubtreeWriter};
+use crate::vector::Vec3;
 
 /// Type of 3D Tiles content
 #[derive(Clone, PartialEq)]
@@ -16,6 +23,9 @@ pub enum ContentType {
     Pnts,
     /// .glb: Binary glTF. For 3D Tiles, this requires 3DTILES_content_gltf
     Glb,
+    /// .csv: Not a 3D Tiles format. All the points are written to a single
+    /// flat file instead of a tileset
+    Csv,
 }
 
 impl ContentType {
@@ -23,7 +33,295 @@ impl ContentType {
     pub fn get_extension(&self) -> &str {
         match self {
             Self::Pnts => "pnts",
-            Self::Glb => "glb"
+            Self::Glb => "glb",
+            Self::Csv => "csv"
+        }
+    }
+}
+
+/// Optional extra attributes for .glb content
+#[derive(Clone, Default)]
+pub struct GlbOptions {
+    /// If true, estimate a normal for each point so the point cloud can
+    /// be shaded
+    pub estimate_normals: bool,
+    /// If true, _CLUSTER_COORDINATES is a VEC4 with the complete
+    /// barycentric coordinates, see GlbWriter::set_full_barycentric()
+    pub full_barycentric: bool,
+    /// If true, include the weight of each point, see
+    /// GlbWriter::set_weights()
+    pub weights: bool,
+    /// If true, every tile reports the feature counts of the whole fractal,
+    /// see FeatureCounts::from_metadata()
+    pub global_feature_counts: bool,
+    /// If true, include the CGA components of each point, see
+    /// GlbWriter::set_cga()
+    pub emit_cga: bool,
+}
+
+impl GlbOptions {
+    /// Make a writer for .glb tiles with these options. Normals depend on
+    /// the points of each tile, so those are set separately.
+    pub fn make_writer(&self, metadata: &FractalMetadata) -> GlbWriter {
+        let mut writer = GlbWriter::new();
+        writer.set_full_barycentric(self.full_barycentric);
+        writer.set_weights(self.weights);
+        writer.set_xform_address(metadata.xform_address_depth > 0);
+        writer.set_cga(self.emit_cga);
+        writer.set_attribution(metadata.attribution.clone());
+        if self.global_feature_counts {
+            writer.set_feature_counts(FeatureCounts::from_metadata(metadata));
+        }
+        writer
+    }
+}
+
+/// Whether warn_dropped_attributes() has printed yet. A new writer is made
+/// for every tileset saved (e.g. one per cluster with SplitPlotter), so
+/// this is per run rather than per writer.
+static WARNED_DROPPED_ATTRIBUTES: AtomicBool = AtomicBool::new(false);
+
+/// Which axis of the fractal's coordinates points up in the viewer
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum UpAxis {
+    X,
+    Y,
+    /// 3D Tiles is z-up, so this needs no extra transform
+    Z,
+}
+
+/// Handedness of the fractal's coordinate system
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Handedness {
+    Right,
+    /// Left-handed coordinates are mirrored in x to match 3D Tiles
+    Left,
+}
+
+/// How the fractal's coordinates are oriented relative to the 3D Tiles
+/// (right-handed, z-up) coordinate system. This is applied as a transform
+/// on the root tile rather than in each content file so the bounding
+/// volumes are transformed too, and so it works the same for .pnts and
+/// .glb content.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Orientation {
+    pub up_axis: UpAxis,
+    pub handedness: Handedness,
+}
+
+impl Orientation {
+    /// Parse the orientation from the plotter JSON:
+    ///
+    /// ```text
+    /// {
+    ///     "up_axis": "x" | "y" | "z" (default "z"),
+    ///     "handedness": "right" | "left" (default "right")
+    /// }
+    /// ```
+    pub fn from_json(json: &JsonValue) -> Self {
+        let up_axis = match json["up_axis"].as_str().unwrap_or("z") {
+            "x" => UpAxis::X,
+            "y" => UpAxis::Y,
+            "z" => UpAxis::Z,
+            _ => panic!("up_axis must be one of x, y or z")
+        };
+        let handedness = match json["handedness"].as_str().unwrap_or("right") {
+            "right" => Handedness::Right,
+            "left" => Handedness::Left,
+            _ => panic!("handedness must be right or left")
+        };
+
+        Self {
+            up_axis,
+            handedness
+        }
+    }
+
+    /// The 4x4 matrix (column-major, as in 3D Tiles) that maps the
+    /// fractal's coordinates to 3D Tiles coordinates. The rotations are
+    /// chosen so the other two axes keep their cyclic order.
+    pub fn matrix(&self) -> [f64; 16] {
+        // columns are the images of the fractal's x, y and z axes
+        let (x_col, y_col, z_col) = match self.up_axis {
+            // (x, y, z) -> (x, y, z)
+            UpAxis::Z => ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+            // (x, y, z) -> (x, -z, y)
+            UpAxis::Y => ([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, -1.0, 0.0]),
+            // (x, y, z) -> (y, z, x)
+            UpAxis::X => ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        };
+        let mirror = match self.handedness {
+            Handedness::Right => 1.0,
+            Handedness::Left => -1.0,
+        };
+
+        [
+            mirror * x_col[0], x_col[1], x_col[2], 0.0,
+            mirror * y_col[0], y_col[1], y_col[2], 0.0,
+            mirror * z_col[0], z_col[1], z_col[2], 0.0,
+            0.0, 0.0, 0.0, 1.0
+        ]
+    }
+
+    /// Check if this orientation matches 3D Tiles
+    pub fn is_identity(&self) -> bool {
+        self.up_axis == UpAxis::Z && self.handedness == Handedness::Right
+    }
+}
+
+/// How the tile hierarchy is described in the tileset
+#[derive(Clone, PartialEq)]
+pub enum Tiling {
+    /// Every tile is listed in tileset.json
+    Explicit,
+    /// 3D Tiles 1.1 implicit octree. tileset.json only contains the root
+    /// tile and template URIs, the tile hierarchy is stored in a tree of
+    /// .subtree files. This is far more compact for deep trees.
+    Implicit,
+}
+
+/// Which version of 3D Tiles to target, from the "tiles_version" option.
+/// If not specified, the tileset is 1.0 for explicit tiling and 1.1 for
+/// implicit tiling, with 3DTILES_metadata either way.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TilesVersion {
+    /// Plain 3D Tiles 1.0 with no extensions, for older viewers. This only
+    /// supports .pnts content with explicit tiling.
+    Legacy,
+    /// 3D Tiles 1.1
+    Next,
+}
+
+impl TilesVersion {
+    /// Parse "1.0" or "1.1" from the plotter JSON, or None if the option is
+    /// not set
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        match json.as_str() {
+            None => None,
+            Some("1.0") => Some(Self::Legacy),
+            Some("1.1") => Some(Self::Next),
+            Some(_) => panic!("tiles_version must be \"1.0\" or \"1.1\"")
+        }
+    }
+}
+
+/// How a tile's content relates to its children's, from the "refine"
+/// option. Either way, the tiles above the leaves hold 1 / decimation_ratio
+/// (a quarter by default) of the points of their children after decimation
+/// (see OctNode::decimate()).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Refine {
+    /// Once the children are loaded, the parent is hidden. The children
+    /// have every point of the parent, so nothing is lost. This is the
+    /// default.
+    Replace,
+    /// The parent stays visible along with its children. Since the
+    /// parent's points are copies of its descendants' points, they are
+    /// drawn twice, which adds up to about 1 / (decimation_ratio - 1) more
+    /// points on screen (a third by default) and makes the parent's points
+    /// a little brighter where they overlap.
+    Add,
+}
+
+impl Refine {
+    /// Parse "ADD" or "REPLACE" from the plotter JSON (default "REPLACE")
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str() {
+            None | Some("REPLACE") => Self::Replace,
+            Some("ADD") => Self::Add,
+            Some(_) => panic!("refine must be \"ADD\" or \"REPLACE\"")
+        }
+    }
+
+    /// The value of the tile's refine property
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Replace => "REPLACE",
+            Self::Add => "ADD",
+        }
+    }
+}
+
+/// The shape of each tile's bounding volume
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BoundingVolume {
+    /// The tile's box. This is the default.
+    Box,
+    /// The sphere through the corners of the tile's box. This is larger
+    /// than the box, but for round attractors a viewer can cull it
+    /// faster and more tightly.
+    Sphere,
+}
+
+impl BoundingVolume {
+    /// Parse "box" or "sphere" from the plotter JSON (default "box")
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str() {
+            None | Some("box") => Self::Box,
+            Some("sphere") => Self::Sphere,
+            Some(_) => panic!("bounding_volume must be \"box\" or \"sphere\"")
+        }
+    }
+
+    /// The boundingVolume of a tile with the given bounds
+    pub fn to_json(self, bounds: &BBox) -> JsonValue {
+        match self {
+            Self::Box => bounds.to_json(),
+            Self::Sphere => bounds.to_sphere_json(),
+        }
+    }
+}
+
+/// Options that apply to the tileset as a whole rather than the content
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TilesetOptions {
+    /// How to orient the fractal in the viewer
+    pub orientation: Orientation,
+    /// If set, force this version of 3D Tiles
+    pub tiles_version: Option<TilesVersion>,
+    /// Whether child tiles replace or add to their parents
+    pub refine: Refine,
+    /// If true, each tile's bounding volume is the box around the points
+    /// in its subtree rather than its octant
+    pub tight_bounds: bool,
+    /// The shape of each tile's bounding volume
+    pub bounding_volume: BoundingVolume,
+    /// How the content files are laid out on disk
+    pub content_layout: ContentLayout,
+}
+
+impl TilesetOptions {
+    /// Parse the options from the plotter JSON:
+    ///
+    /// ```text
+    /// {
+    ///     "up_axis": "x" | "y" | "z" (default "z"),
+    ///     "handedness": "right" | "left" (default "right"),
+    ///     "tiles_version": "1.0" | "1.1" (optional),
+    ///     "refine": "ADD" | "REPLACE" (default "REPLACE"),
+    ///     "tight_bounds": bool (default false),
+    ///     "bounding_volume": "box" | "sphere" (default "box"),
+    ///     "content_layout": "nested" | "flat" (default "nested")
+    /// }
+    /// ```
+    ///
+    /// With tight_bounds, the viewer can cull tiles whose points only fill
+    /// a small corner of their octant. This only works with explicit
+    /// tiling since implicit tiles are always the octants. For the same
+    /// reason, sphere bounding volumes need explicit tiling.
+    ///
+    /// content_layout only moves the content files. Subtree files for
+    /// implicit tiling are always nested.
+    pub fn from_json(json: &JsonValue) -> Self {
+        Self {
+            orientation: Orientation::from_json(json),
+            tiles_version: TilesVersion::from_json(&json["tiles_version"]),
+            refine: Refine::from_json(&json["refine"]),
+            tight_bounds: json["tight_bounds"].as_bool().unwrap_or(false),
+            bounding_volume:
+                BoundingVolume::from_json(&json["bounding_volume"]),
+            content_layout:
+                ContentLayout::from_json(&json["content_layout"]),
         }
     }
 }
@@ -32,6 +330,8 @@ impl ContentType {
 pub struct TilesetWriter {
     /// The type of content to store in each tile
     content_type: ContentType,
+    /// Whether to use explicit or implicit tiling
+    tiling: Tiling,
     /// Metadata to include in the tileset when using 3D Tiles Next 
     /// (.glb content)
     metadata: FractalMetadata,
@@ -39,43 +339,139 @@ pub struct TilesetWriter {
     /// ./viewer/{tileset_id}
     tileset_dir: String,
     /// The directory where the point files will go, 
-    /// ./viewer/{tileset_id}/points
+    /// ./viewer/{tileset_id}/points (or content for a flat layout)
     points_dir: String,
+    /// The directory where the subtree files go when using implicit tiling,
+    /// ./viewer/{tileset_id}/subtrees
+    subtrees_dir: String,
+    /// Extra attributes to include (.glb content only)
+    glb_options: GlbOptions,
+    /// Orientation, version and refinement of the tileset
+    options: TilesetOptions,
 }
 
 impl TilesetWriter {
     pub fn new(
             tileset_id: &str,
             content_type: ContentType,
-            metadata: FractalMetadata)
+            tiling: Tiling,
+            metadata: FractalMetadata,
+            glb_options: GlbOptions,
+            options: TilesetOptions)
             -> Self {
         Self {
             content_type,
+            tiling,
             metadata,
             tileset_dir: format!("./viewer/{}", tileset_id),
-            points_dir: format!("./viewer/{}/points", tileset_id)
+            points_dir: format!(
+                "./viewer/{}/{}",
+                tileset_id,
+                options.content_layout.dirname()),
+            subtrees_dir: format!("./viewer/{}/subtrees", tileset_id),
+            glb_options,
+            options,
         }
     }
 
     /// Save the tileset to disk in the specified directory. The directory will
     /// be removed first if it exists, so use with care!
-    pub fn save(&self, root: &OctNode) {
+    pub fn save<P: TreePoint>(&self, root: &OctNode<P>) {
+        if self.content_type == ContentType::Csv {
+            self.save_csv(root);
+            return;
+        }
+
         self.init_directories();
 
-        println!("Generating tileset JSON in {}", &self.tileset_dir);
+        info!("Generating tileset JSON in {}", &self.tileset_dir);
         self.make_tileset_json(root);
 
-        println!("Generating point cloud files in {}", self.points_dir);
+        if self.tiling == Tiling::Implicit {
+            info!("Generating subtree files in {}", self.subtrees_dir);
+            self.make_subtrees(root);
+        }
+
+        if self.content_type == ContentType::Pnts {
+            self.warn_dropped_attributes();
+        }
+
+        info!("Generating point cloud files in {}", self.points_dir);
         self.make_contents(root);
+        report_tile_sizes(root);
     }
 
-    fn init_directories(&self) {
-        // Remove the old directory
-        if Path::new(&self.tileset_dir).exists() {
-            let message = format!(
-                "Could not remove old tileset in {}", self.tileset_dir);
-            remove_dir_all(&self.tileset_dir).expect(&message);
+    /// .pnts tiles only have positions and colors, so the per-point
+    /// metadata that .glb tiles carry as feature IDs is silently lost.
+    /// Say so, since styling by these attributes won't work in the viewer.
+    /// This only prints the first time it is called.
+    fn warn_dropped_attributes(&self) {
+        if WARNED_DROPPED_ATTRIBUTES.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let mut dropped = vec![
+            "iteration",
+            "cluster_copy",
+            "cluster_id",
+            "point_id",
+            "cluster_coordinates",
+            "last_xform",
+            "last_color_xform",
+        ];
+        if self.glb_options.weights {
+            dropped.push("_WEIGHT (from dedupe_epsilon)");
+        }
+        if self.metadata.xform_address_depth > 0 {
+            dropped.push("_XFORM_ADDRESS (from address_depth)");
         }
+        info!(
+            "Warning: .pnts tiles only store positions and colors. The \
+            per-point attributes {} are only written with format glb, so \
+            they can't be used for styling",
+            dropped.join(", "));
+    }
+
+    /// Write ./viewer/{tileset_id}/boxes.glb, a wireframe of the bounding
+    /// box of every tile in the tileset colored by depth (red at the root
+    /// to blue at the deepest level). This is for debugging the tile
+    /// layout. This must be called after save() since save() clears the
+    /// directory.
+    pub fn save_debug_boxes<P: TreePoint>(&self, root: &OctNode<P>) {
+        let max_level = count_available_levels(root).saturating_sub(1);
+        let mut lines = Vec::new();
+        let mut colors = Vec::new();
+        collect_box_edges(root, max_level, &mut lines, &mut colors);
+
+        let fname = format!("{}/boxes.glb", self.tileset_dir);
+        info!("Generating debug boxes in {}", fname);
+        let mut writer = LineGlbWriter::new();
+        writer.set_attribution(self.metadata.attribution.clone());
+        writer.set_transform(self.options.orientation.matrix());
+        writer.write(&fname, &lines, &colors);
+    }
+
+    /// Skip the tile tree and write all the points to a single file,
+    /// ./viewer/{tileset_id}/points.csv
+    fn save_csv<P: TreePoint>(&self, root: &OctNode<P>) {
+        self.remove_old_tileset();
+        create_dir_all(&self.tileset_dir)
+            .expect("Could not create tileset directory");
+
+        // After decimation, interior nodes only store copies of points from
+        // their descendants, so the leaves have every point exactly once.
+        let mut points = Vec::new();
+        collect_leaf_points(root, &mut points);
+
+        let fname = format!("{}/points.csv", self.tileset_dir);
+        info!("Generating CSV file {}", fname);
+        let mut writer = CsvWriter::new();
+        writer.write(&fname, &points);
+        info!("Wrote {} points", writer.get_row_count());
+    }
+
+    fn init_directories(&self) {
+        self.remove_old_tileset();
 
         create_dir_all(&self.tileset_dir)
             .expect("Could not create tileset directory");
@@ -83,24 +479,59 @@ impl TilesetWriter {
             .expect("Could not create points directory");
     }
 
+    /// Remove the old tileset directory if it exists
+    fn remove_old_tileset(&self) {
+        if Path::new(&self.tileset_dir).exists() {
+            let message = format!(
+                "Could not remove old tileset in {}", self.tileset_dir);
+            remove_dir_all(&self.tileset_dir).expect(&message);
+        }
+    }
+
     /// Generate a tileset.json file by traversing the tree and collecting
     /// data
     ///
     /// See https://github.com/CesiumGS/3d-tiles/tree/master/specification#reference-tileset
-    fn make_tileset_json(&self, root: &OctNode) {
-        let root_tile = 
-            self.make_tileset_json_recursive(root, "points");
+    fn make_tileset_json<P: TreePoint>(&self, root: &OctNode<P>) {
+        let (mut root_tile, default_version) = match self.tiling {
+            Tiling::Explicit => 
+                (self.make_tileset_json_recursive(root), "1.0"),
+            // Implicit tiling is part of 3D Tiles 1.1
+            Tiling::Implicit => 
+                (self.make_implicit_root_json(root), "1.1"),
+        };
+        let version = match self.options.tiles_version {
+            Some(TilesVersion::Legacy) => "1.0",
+            Some(TilesVersion::Next) => "1.1",
+            None => default_version
+        };
+        if !self.options.orientation.is_identity() && root_tile.is_object() {
+            let matrix: Vec<JsonValue> = self.options.orientation
+                .matrix()
+                .iter()
+                .map(|x| (*x).into())
+                .collect();
+            root_tile["transform"] = JsonValue::Array(matrix);
+        }
         let mut tileset = object!{
             "asset" => object!{
-                "version" => "1.0",
+                "version" => version,
             },
             "geometricError" => 1e7,
             "root" => root_tile,
-            "extensionsUsed" => array!["3DTILES_metadata"],
-            "schema" => self.metadata.make_schema_json(),
-            "metadata" => self.metadata.make_metadata_json()
         };
 
+        // Plain 1.0 tilesets have no extensions at all, so the fractal
+        // metadata is left out
+        if self.options.tiles_version == Some(TilesVersion::Legacy) {
+            self.write_tileset_json(tileset);
+            return;
+        }
+
+        tileset["extensionsUsed"] = array!["3DTILES_metadata"];
+        tileset["schema"] = self.metadata.make_schema_json();
+        tileset["metadata"] = self.metadata.make_metadata_json();
+
         // If using GLB output, we also need to add the
         // 3DTILES_content_gltf extension
         if self.content_type == ContentType::Glb {
@@ -114,6 +545,11 @@ impl TilesetWriter {
             };
         }
 
+        self.write_tileset_json(tileset);
+    }
+
+    /// Write ./viewer/{tileset_id}/tileset.json
+    fn write_tileset_json(&self, tileset: JsonValue) {
         let fname = format!("{}/tileset.json", self.tileset_dir);
         let message = format!("Failed to create {}", fname);
         let mut file = File::create(fname.clone()).expect(&message);
@@ -125,17 +561,16 @@ impl TilesetWriter {
     /// Generate the tree of tiles including URIs to each .pnts file
     ///
     /// See https://github.com/CesiumGS/3d-tiles/tree/master/specification#reference-tile
-    fn make_tileset_json_recursive(&self, tree: &OctNode, content_dirname: &str)
+    fn make_tileset_json_recursive<P: TreePoint>(&self, tree: &OctNode<P>)
             -> JsonValue {
         if tree.is_leaf() && tree.is_empty() {
             JsonValue::Null
         } else if tree.is_leaf() {
-            let fname = tree.get_file_name(
-                content_dirname, self.content_type.get_extension());
+            let fname = self.content_uri(tree);
             object!{
-                "boundingVolume" => tree.bounding_volume_json(),
+                "boundingVolume" => self.bounding_volume_json(tree),
                 "geometricError" => 0.0,
-                "refine" => "REPLACE",
+                "refine" => self.options.refine.as_str(),
                 "content" => object!{
                     "uri" => fname
                 }
@@ -144,18 +579,17 @@ impl TilesetWriter {
             let mut children: Vec<JsonValue> = Vec::new();
             for child in tree.get_children().iter() {
                 let child_json = 
-                    self.make_tileset_json_recursive(child, content_dirname);
+                    self.make_tileset_json_recursive(child);
                 if child_json.is_object() {
                     children.push(child_json);
                 }
             }
 
-            let fname = tree.get_file_name(
-                content_dirname, self.content_type.get_extension());
+            let fname = self.content_uri(tree);
             object!{
-                "boundingVolume" => tree.bounding_volume_json(),
+                "boundingVolume" => self.bounding_volume_json(tree),
                 "geometricError" => tree.geometric_error(),
-                "refine" => "REPLACE",
+                "refine" => self.options.refine.as_str(),
                 "children" => JsonValue::Array(children),
                 "content" => object!{
                     "uri" => fname
@@ -164,41 +598,345 @@ impl TilesetWriter {
         }
     }
 
-    /// Traverse the tree, generating content files at leaves and directories
-    /// at interior nodes.
-    fn make_contents(&self, tree: &OctNode) {
-        if tree.is_leaf() {
-            self.make_content(tree);
-        } else {
-            for child in tree.get_children().iter() {
-                self.make_contents(child);
+    /// The URI of a tile's content relative to the tileset JSON
+    fn content_uri<P: TreePoint>(&self, tree: &OctNode<P>) -> String {
+        let layout = self.options.content_layout;
+        tree.get_file_name(
+            layout.dirname(), self.content_type.get_extension(), layout)
+    }
+
+    /// The bounding volume of a tile, see TilesetOptions::tight_bounds and
+    /// TilesetOptions::bounding_volume
+    fn bounding_volume_json<P: TreePoint>(&self, tree: &OctNode<P>)
+            -> JsonValue {
+        let volume = self.options.bounding_volume;
+        // tight_bounds() walks the whole subtree, so skip it unless needed
+        if !self.options.tight_bounds {
+            return volume.to_json(tree.bounds());
+        }
+        match tree.tight_bounds() {
+            Some(bounds) => volume.to_json(&bounds),
+            None => volume.to_json(tree.bounds())
+        }
+    }
+
+    /// Generate the root tile for implicit tiling. This is a single tile
+    /// with template URIs for the content and subtrees.
+    ///
+    /// See https://github.com/CesiumGS/3d-tiles/tree/main/specification/ImplicitTiling
+    fn make_implicit_root_json<P: TreePoint>(&self, root: &OctNode<P>)
+            -> JsonValue {
+        let subtree_levels = root.get_implicit_coordinates().subtree_levels;
+        let template = match self.options.content_layout {
+            ContentLayout::Nested => "{level}/{x}/{y}/{z}",
+            ContentLayout::Flat => "{level}_{x}_{y}_{z}",
+        };
+        let content_uri = format!(
+            "{}/{}.{}",
+            self.options.content_layout.dirname(),
+            template,
+            self.content_type.get_extension());
+
+        object!{
+            "boundingVolume" => root.bounding_volume_json(),
+            "geometricError" => root.geometric_error(),
+            "refine" => self.options.refine.as_str(),
+            "content" => object!{
+                "uri" => content_uri
+            },
+            "implicitTiling" => object!{
+                "subdivisionScheme" => "OCTREE",
+                "subtreeLevels" => subtree_levels,
+                "availableLevels" => count_available_levels(root),
+                "subtrees" => object!{
+                    "uri" => "subtrees/{level}/{x}/{y}/{z}.subtree"
+                }
             }
-            self.make_content(tree);
         }
     }
 
+    /// Write the subtree file for the subtree rooted at this node, then
+    /// recurse into any child subtrees.
+    fn make_subtrees<P: TreePoint>(&self, subtree_root: &OctNode<P>) {
+        let root_coords = subtree_root.get_implicit_coordinates();
+        assert!(
+            root_coords.is_subtree_root(),
+            "make_subtrees: node must be at the top of a subtree");
+        let subtree_levels = root_coords.subtree_levels;
+
+        // A subtree has (8^levels - 1) / 7 tiles and 8^levels
+        // child subtrees.
+        let child_subtree_count = 1 << (3 * subtree_levels);
+        let tile_count = (child_subtree_count - 1) / 7;
+
+        let mut tile_availability = Bitstream::new(tile_count);
+        let mut content_availability = Bitstream::new(tile_count);
+        let mut child_subtree_availability = 
+            Bitstream::new(child_subtree_count);
+        let mut child_subtrees = Vec::new();
+
+        // Traverse the subtree, marking which tiles and contents are
+        // available. Nodes just below the bottom of the subtree are the
+        // roots of child subtrees, these are collected for later.
+        subtree_root.visit(&mut |tree, relative_level| {
+            // Empty leaves are omitted, just like in explicit tiling
+            if !is_tile_available(tree) {
+                return false;
+            }
+
+            let morton_index = 
+                tree.get_implicit_coordinates().local_morton_index(root_coords);
+            if relative_level == subtree_levels {
+                child_subtree_availability.set(morton_index);
+                child_subtrees.push(tree);
+                return false;
+            }
+
+            // Bitstreams store each level contiguously, level i starts at
+            // index (8^i - 1) / 7
+            let level_offset = ((1 << (3 * relative_level)) - 1) / 7;
+            tile_availability.set(level_offset + morton_index);
+            if !tree.is_empty() {
+                content_availability.set(level_offset + morton_index);
+            }
+            true
+        });
+
+        let directory = subtree_root.get_directory_name(
+            &self.subtrees_dir, ContentLayout::Nested);
+        create_dir_all(&directory).expect("could not create directory");
+        let fname = subtree_root.get_file_name(
+            &self.subtrees_dir, "subtree", ContentLayout::Nested);
+        let mut writer = SubtreeWriter::new();
+        writer.write(
+            &fname,
+            &tile_availability,
+            &content_availability,
+            &child_subtree_availability);
+        verbose!("Wrote {}", fname);
+
+        for child_subtree in child_subtrees {
+            self.make_subtrees(child_subtree);
+        }
+    }
+
+    /// Traverse the tree, generating a content file for every non-empty
+    /// tile
+    fn make_contents<P: TreePoint>(&self, root: &OctNode<P>) {
+        root.visit(&mut |tree, _| {
+            self.make_content(tree);
+            true
+        });
+    }
+
     // Generate a 3D model for a tile content.
-    fn make_content(&self, tree: &OctNode) {
+    fn make_content<P: TreePoint>(&self, tree: &OctNode<P>) {
         // No need to create an empty point cloud
         if tree.is_empty() {
             return;
         }
 
         let points = tree.get_points();
-        let directory = tree.get_directory_name(&self.points_dir);
+        let layout = self.options.content_layout;
+        let directory = tree.get_directory_name(&self.points_dir, layout);
         create_dir_all(&directory).expect("could not create directory");
 
-        match self.content_type {
+        let fname = match self.content_type {
             ContentType::Pnts => {
                 let mut writer = PntsWriter::new();
-                let fname = tree.get_file_name(&self.points_dir, "pnts");
+                let fname = tree.get_file_name(
+                    &self.points_dir, "pnts", layout);
                 writer.write(&fname, points);
+                fname
             },
             ContentType::Glb => {
-                let mut writer = GlbWriter::new();
-                let fname = tree.get_file_name(&self.points_dir, "glb");
-                writer.write(&fname, points);
+                let points = P::as_output_points(points);
+                let mut writer = self.glb_options.make_writer(&self.metadata);
+                if self.glb_options.estimate_normals {
+                    let positions: Vec<Vec3> = 
+                        points.iter().map(|point| point.position).collect();
+                    writer.set_normals(
+                        normals::estimate_normals(&positions, NORMAL_NEIGHBORS));
+                }
+                let fname = tree.get_file_name(
+                    &self.points_dir, "glb", layout);
+                writer.write(&fname, &points);
+                fname
+            },
+            ContentType::Csv => 
+                panic!("CSV output is a single file, not a tileset")
+        };
+        verbose!("Wrote {} ({} points)", fname, points.len());
+    }
+}
+
+/// Gather the bounding box edges of every available tile, colored by
+/// depth
+fn collect_box_edges<P: TreePoint>(
+        root: &OctNode<P>,
+        max_level: usize,
+        lines: &mut Vec<(Vec3, Vec3)>,
+        colors: &mut Vec<Vec3>) {
+    let red = Vec3::new(1.0, 0.0, 0.0);
+    let blue = Vec3::new(0.0, 0.0, 1.0);
+    root.visit(&mut |tree, _| {
+        if !is_tile_available(tree) {
+            return false;
+        }
+
+        let level = tree.get_implicit_coordinates().level;
+        let t = if max_level == 0 {
+            0.0
+        } else {
+            level as f32 / max_level as f32
+        };
+        let color = Vec3::lerp(&red, &blue, t);
+
+        for edge in tree.bounds().edges() {
+            lines.push(edge);
+            colors.push(color);
+        }
+        true
+    });
+}
+
+/// Gather the points stored in the leaves of the tree
+fn collect_leaf_points<P: TreePoint>(
+        root: &OctNode<P>, points: &mut Vec<OutputPoint>) {
+    root.visit(&mut |tree, _| {
+        if tree.is_leaf() {
+            points.extend(P::as_output_points(tree.get_points()).iter().cloned());
+        }
+        true
+    });
+}
+
+/// Print how the points are spread over the tiles. Interior tiles hold
+/// the decimated copies, so the total is more than the number of points
+/// plotted.
+fn report_tile_sizes<P: TreePoint>(root: &OctNode<P>) {
+    let mut sizes = Vec::new();
+    root.visit(&mut |tree, _| {
+        if !tree.is_empty() {
+            sizes.push(tree.get_points().len());
+        }
+        true
+    });
+
+    if sizes.is_empty() {
+        info!("Wrote 0 tiles, no points were plotted");
+        return;
+    }
+
+    let total: usize = sizes.iter().sum();
+    info!(
+        "Wrote {} points in {} tiles, {} to {} points per tile (mean {:.0})",
+        total,
+        sizes.len(),
+        sizes.iter().min().unwrap(),
+        sizes.iter().max().unwrap(),
+        total as f64 / sizes.len() as f64);
+}
+
+/// A tile is included in the tileset unless it is an empty leaf
+fn is_tile_available<P: TreePoint>(tree: &OctNode<P>) -> bool {
+    !(tree.is_leaf() && tree.is_empty())
+}
+
+/// Count how many levels of the tree contain available tiles. This is
+/// the availableLevels property for implicit tiling
+fn count_available_levels<P: TreePoint>(root: &OctNode<P>) -> usize {
+    let mut levels = 0;
+    root.visit(&mut |tree, depth| {
+        if !is_tile_available(tree) {
+            return false;
+        }
+        levels = levels.max(depth + 1);
+        true
+    });
+    levels
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Multiply a column-major 4x4 matrix by a point
+    fn transform_point(matrix: &[f64; 16], point: [f64; 3]) -> [f64; 3] {
+        let mut result = [matrix[12], matrix[13], matrix[14]];
+        for (row, component) in result.iter_mut().enumerate() {
+            for (col, coordinate) in point.iter().enumerate() {
+                *component += matrix[4 * col + row] * coordinate;
             }
         }
+        result
+    }
+
+    #[test]
+    fn test_orientation_matrices() {
+        let point = [1.0, 2.0, 3.0];
+        let cases = vec![
+            ("z", "right", [1.0, 2.0, 3.0]),
+            ("y", "right", [1.0, -3.0, 2.0]),
+            ("x", "right", [2.0, 3.0, 1.0]),
+            ("z", "left", [-1.0, 2.0, 3.0]),
+            ("y", "left", [-1.0, -3.0, 2.0]),
+        ];
+        for (up_axis, handedness, expected) in cases {
+            let orientation = Orientation::from_json(&object!{
+                "up_axis" => up_axis,
+                "handedness" => handedness
+            });
+            assert_eq!(
+                transform_point(&orientation.matrix(), point),
+                expected,
+                "up_axis: {}, handedness: {}", up_axis, handedness);
+        }
+    }
+
+    #[test]
+    fn test_tiles_version() {
+        assert_eq!(TilesVersion::from_json(&JsonValue::Null), None);
+        assert_eq!(
+            TilesVersion::from_json(&"1.0".into()),
+            Some(TilesVersion::Legacy));
+        assert_eq!(
+            TilesVersion::from_json(&"1.1".into()),
+            Some(TilesVersion::Next));
+    }
+
+    #[test]
+    fn test_refine() {
+        let options = TilesetOptions::from_json(&object!{});
+        assert_eq!(options.refine, Refine::Replace);
+        let options = TilesetOptions::from_json(&object!{"refine" => "ADD"});
+        assert_eq!(options.refine.as_str(), "ADD");
+    }
+
+    #[test]
+    #[should_panic(expected = "refine must be")]
+    fn test_refine_rejects_lowercase() {
+        Refine::from_json(&"add".into());
+    }
+
+    #[test]
+    fn test_bounding_volume() {
+        let options = TilesetOptions::from_json(&object!{});
+        assert_eq!(options.bounding_volume, BoundingVolume::Box);
+        let options = TilesetOptions::from_json(
+            &object!{"bounding_volume" => "sphere"});
+        let bounds = BBox::new(-1.0, 1.0, -1.0, 1.0, -1.0, 1.0);
+        let json = options.bounding_volume.to_json(&bounds);
+        assert!(json["box"].is_null());
+        assert_eq!(json["sphere"].len(), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_default_orientation_is_identity() {
+        let orientation = Orientation::from_json(&object!{});
+        assert!(orientation.is_identity());
+        assert_eq!(
+            transform_point(&orientation.matrix(), [4.0, 5.0, 6.0]),
+            [4.0, 5.0, 6.0]);
+    }
+}
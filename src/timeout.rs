@@ -0,0 +1,92 @@
+use std::time::{Duration, Instant};
+
+use json::JsonValue;
+
+/// A wall-clock limit on the iteration. Once it runs out, the algorithm
+/// stops iterating and saves whatever was plotted so far, so a slow
+/// parameter file can't hang a batch of renders. Reading the clock is
+/// cheap but not free, so it's only checked every check_every iterations.
+pub struct Timeout {
+    /// How long the iteration may run
+    limit: Duration,
+    /// When the iteration started
+    start: Instant,
+    /// Only read the clock every this many iterations
+    check_every: usize,
+}
+
+impl Timeout {
+    pub fn new(limit: Duration) -> Self {
+        Self {
+            limit,
+            start: Instant::now(),
+            check_every: 1,
+        }
+    }
+
+    /// Parse the number of seconds the iteration may run. Returns None if
+    /// the JSON is null.
+    pub fn from_json(json: &JsonValue) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+
+        let seconds = json
+            .as_f64()
+            .filter(|seconds| *seconds > 0.0 && seconds.is_finite())
+            .expect("timeout must be a positive number of seconds");
+        Some(Self::new(Duration::from_secs_f64(seconds)))
+    }
+
+    /// Only read the clock every check_every iterations. This is for
+    /// algorithms where each iteration is quick
+    pub fn with_check_every(mut self, check_every: usize) -> Self {
+        self.check_every = check_every.max(1);
+        self
+    }
+
+    /// Start the clock when the iteration begins
+    pub fn start(&mut self) {
+        self.start = Instant::now();
+    }
+
+    /// Check if the time is up after the given iteration (counting from 0)
+    pub fn is_expired(&self, iteration: usize) -> bool {
+        (iteration + 1).is_multiple_of(self.check_every)
+            && self.start.elapsed() >= self.limit
+    }
+
+    /// How long the iteration has run
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_json() {
+        assert!(Timeout::from_json(&JsonValue::Null).is_none());
+        let timeout = Timeout::from_json(&1.5.into()).unwrap();
+        assert_eq!(timeout.limit, Duration::from_millis(1500));
+    }
+
+    #[test]
+    #[should_panic(expected = "timeout must be a positive number of seconds")]
+    fn test_timeout_must_be_positive() {
+        Timeout::from_json(&0.into());
+    }
+
+    #[test]
+    fn test_is_expired_only_checks_every_n() {
+        let timeout = Timeout::new(Duration::from_secs(0)).with_check_every(10);
+        assert!(!timeout.is_expired(0));
+        assert!(timeout.is_expired(9));
+        assert!(!timeout.is_expired(10));
+
+        let timeout = Timeout::new(Duration::from_secs(3600));
+        assert!(!timeout.is_expired(0));
+    }
+}
@@ -0,0 +1,95 @@
+use json::JsonValue;
+
+use crate::half_multivector::HalfMultivector;
+
+/// Fade older iterations of Chaos Sets for a comet-trail look. Each point's
+/// color is scaled by 0.5^(age / halflife), where the age is the number of
+/// iterations before the last one. Unlike coloring by iteration, this only
+/// changes the brightness, not the hue.
+pub struct Trail {
+    /// Number of iterations for the brightness to halve
+    halflife: f64,
+    /// The iteration with age 0
+    last_iteration: u64,
+}
+
+impl Trail {
+    pub fn new(halflife: f64, last_iteration: u64) -> Self {
+        if halflife.is_nan() || halflife <= 0.0 {
+            panic!("trail: halflife must be a positive number of iterations");
+        }
+
+        Self {
+            halflife,
+            last_iteration,
+        }
+    }
+
+    /// Parse from JSON of the form:
+    ///
+    /// ```text
+    /// {
+    ///     "halflife": h
+    /// }
+    /// ```
+    ///
+    /// The halflife is measured in iterations and need not be an integer.
+    /// Returns None if the JSON is null.
+    pub fn from_json(json: &JsonValue, last_iteration: u64) -> Option<Self> {
+        if json.is_null() {
+            return None;
+        }
+
+        let halflife = json["halflife"]
+            .as_f64()
+            .expect("trail: halflife must be a number");
+        Some(Self::new(halflife, last_iteration))
+    }
+
+    /// How much to scale the color of a point from the given iteration.
+    /// This is 1 for the last iteration and halves every halflife
+    /// iterations before it.
+    pub fn brightness(&self, iteration: u64) -> f64 {
+        let age = self.last_iteration.saturating_sub(iteration);
+        0.5f64.powf(age as f64 / self.halflife)
+    }
+
+    /// Dim a color by the brightness for its iteration
+    pub fn fade(&self, color: &HalfMultivector, iteration: u64)
+            -> HalfMultivector {
+        let (r, g, b) = color.coordinates();
+        let brightness = self.brightness(iteration);
+        HalfMultivector::point(r * brightness, g * brightness, b * brightness)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_brightness_halves_every_halflife() {
+        let trail = Trail::new(10.0, 100);
+        assert_eq!(trail.brightness(100), 1.0);
+        assert!((trail.brightness(90) - 0.5).abs() < 1e-12);
+        assert!((trail.brightness(80) - 0.25).abs() < 1e-12);
+        assert!((trail.brightness(95) - 0.5f64.sqrt()).abs() < 1e-12);
+        assert!((trail.brightness(0) - 0.5f64.powi(10)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_fade_scales_color() {
+        let trail = Trail::new(2.0, 4);
+        let color = HalfMultivector::point(0.8, 0.4, 0.2);
+        let (r, g, b) = trail.fade(&color, 0).coordinates();
+        assert!((r - 0.2).abs() < 1e-12);
+        assert!((g - 0.1).abs() < 1e-12);
+        assert!((b - 0.05).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "halflife must be a positive")]
+    fn test_halflife_must_be_positive() {
+        Trail::new(0.0, 10);
+    }
+}
@@ -1,5 +1,5 @@
 use std::fmt::{Display, Debug, Formatter, Result};
-use std::ops::{Add, Mul, Sub};
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
 use json::JsonValue;
 use json::JsonValue::Array;
@@ -18,6 +18,22 @@ pub type Vec3 = Vector3<f32>;
 /// which fit in a u8
 pub type Color = Vector3<u8>;
 
+/// Colors that can be given by name in color_from_json()
+const NAMED_COLORS: [(&str, [f32; 3]); 12] = [
+    ("black", [0.0, 0.0, 0.0]),
+    ("white", [1.0, 1.0, 1.0]),
+    ("gray", [0.5, 0.5, 0.5]),
+    ("red", [1.0, 0.0, 0.0]),
+    ("green", [0.0, 1.0, 0.0]),
+    ("blue", [0.0, 0.0, 1.0]),
+    ("yellow", [1.0, 1.0, 0.0]),
+    ("cyan", [0.0, 1.0, 1.0]),
+    ("magenta", [1.0, 0.0, 1.0]),
+    ("orange", [1.0, 0.5, 0.0]),
+    ("purple", [0.5, 0.0, 1.0]),
+    ("pink", [1.0, 0.5, 0.75]),
+];
+
 impl<T> Vector3<T> {
     pub fn new(x: T, y: T, z: T) -> Vector3<T> {
         Vector3 { components: [x, y, z] }
@@ -55,6 +71,11 @@ impl<T: Display> Display for Vector3<T> {
 }
 
 impl Vec3 {
+    /// Check that no component is NaN or infinite
+    pub fn is_finite(&self) -> bool {
+        self.x().is_finite() && self.y().is_finite() && self.z().is_finite()
+    }
+
     /// Length of vector, `|v| = sqrt(x^2 + y^2 + z^2)`
     pub fn length(&self) -> f32 {
         let x_sqr = self.x() * self.x();
@@ -66,7 +87,6 @@ impl Vec3 {
 
     /// Make the vector unit length.
     /// `normalize(v) = v / length(v)`
-    /*
     pub fn normalize(&self) -> Vec3 {
         let len = self.length();
         let x = self.x() / len;
@@ -75,7 +95,25 @@ impl Vec3 {
 
         Vec3::new(x, y, z)
     }
-    */
+
+    /// Dot product.
+    /// `a . b = ax bx + ay by + az bz`
+    pub fn dot(&self, other: &Vec3) -> f32 {
+        let [ax, ay, az] = self.components;
+        let [bx, by, bz] = other.components;
+        ax * bx + ay * by + az * bz
+    }
+
+    /// Cross product.
+    /// `a x b = (ay bz - az by, az bx - ax bz, ax by - ay bx)`
+    pub fn cross(&self, other: &Vec3) -> Vec3 {
+        let [ax, ay, az] = self.components;
+        let [bx, by, bz] = other.components;
+        Vec3::new(
+            ay * bz - az * by,
+            az * bx - ax * bz,
+            ax * by - ay * bx)
+    }
 
     /// Scale the vector.
     /// `S_k v = k * v
@@ -101,6 +139,24 @@ impl Vec3 {
         }
     }
 
+    /// Encode a linear color in the range [0.0, 1.0] with the sRGB
+    /// transfer function. Components outside the range are clamped.
+    pub fn linear_to_srgb(&self) -> Vec3 {
+        Vec3::new(
+            srgb_transfer(*self.x()),
+            srgb_transfer(*self.y()),
+            srgb_transfer(*self.z()))
+    }
+
+    /// Decode an sRGB color back to linear, the inverse of
+    /// linear_to_srgb(). Components outside [0.0, 1.0] are clamped.
+    pub fn srgb_to_linear(&self) -> Vec3 {
+        Vec3::new(
+            srgb_inverse_transfer(*self.x()),
+            srgb_inverse_transfer(*self.y()),
+            srgb_inverse_transfer(*self.z()))
+    }
+
     /// Pack the vector into an array of bytes in little-endian format
     /// ```text
     /// bytes  0-3: x
@@ -116,9 +172,24 @@ impl Vec3 {
         results
     }
 
+    /// Pack many vectors back to back as with pack(). This allocates the
+    /// exact number of bytes up front. It takes any iterator so
+    /// the vectors can come straight from a buffer of points, e.g.
+    /// `Vec3::pack_all(points.iter().map(|point| &point.position))`
+    pub fn pack_all<'a, I>(vectors: I) -> Vec<u8>
+            where I: IntoIterator<Item = &'a Vec3>,
+                  I::IntoIter: ExactSizeIterator {
+        let vectors = vectors.into_iter();
+        let mut results = Vec::with_capacity(12 * vectors.len());
+        for vector in vectors {
+            results.extend_from_slice(&vector.pack());
+        }
+        results
+    }
+
     /// Create a random vec3 in the range [-1.0, 1.0]^3
     pub fn random() -> Vec3 { 
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let x = rng.gen_range(-1.0, 1.0);
         let y = rng.gen_range(-1.0, 1.0);
         let z = rng.gen_range(-1.0, 1.0);
@@ -128,7 +199,7 @@ impl Vec3 {
 
     /// Create a random bright color in the range [0.5, 1.0]^3
     pub fn random_color() -> Vec3 { 
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let x = rng.gen_range(0.5, 1.0);
         let y = rng.gen_range(0.5, 1.0);
         let z = rng.gen_range(0.5, 1.0);
@@ -146,6 +217,26 @@ impl Vec3 {
         Vec3::new(1.0, 1.0, 1.0)
     }
 
+    /// Create a vector with the same value in every component
+    pub fn splat(value: f32) -> Vec3 {
+        Vec3::new(value, value, value)
+    }
+
+    /// Component-wise minimum of two vectors
+    pub fn min(a: &Vec3, b: &Vec3) -> Vec3 {
+        Vec3::new(a.x().min(*b.x()), a.y().min(*b.y()), a.z().min(*b.z()))
+    }
+
+    /// Component-wise maximum of two vectors
+    pub fn max(a: &Vec3, b: &Vec3) -> Vec3 {
+        Vec3::new(a.x().max(*b.x()), a.y().max(*b.y()), a.z().max(*b.z()))
+    }
+
+    /// Convert to a JSON array [x, y, z]
+    pub fn to_json(&self) -> JsonValue {
+        array![*self.x(), *self.y(), *self.z()]
+    }
+
     pub fn lerp(a: &Vec3, b: &Vec3, t: f32) -> Vec3 {
         let p = 1.0 - t;
         let q = t;
@@ -173,6 +264,92 @@ impl Vec3 {
         }
     }
 
+    /// Parse a color from JSON. This is the same as from_json(), but
+    /// also accepts a string of one of these forms:
+    ///
+    /// ```text
+    /// "red" (see NAMED_COLORS)
+    /// "#rrggbb"
+    /// "hsv(h, s, v)" (h in degrees, s and v in [0, 1])
+    /// ```
+    pub fn color_from_json(json: &JsonValue, default_val: Vec3) -> Vec3 {
+        match json.as_str() {
+            Some(color) => Vec3::from_color_str(color)
+                .unwrap_or_else(|err| panic!("{}", err)),
+            None => Vec3::from_json(json, default_val)
+        }
+    }
+
+    /// Parse a color name, "#rrggbb" or "hsv(h, s, v)" into RGB in the
+    /// range [0.0, 1.0]. See color_from_json()
+    pub fn from_color_str(color: &str) -> std::result::Result<Vec3, String> {
+        let color = color.trim();
+        if color.starts_with('#') {
+            return Vec3::from_hex(color);
+        }
+        if let Some(args) = color
+                .strip_prefix("hsv(")
+                .and_then(|rest| rest.strip_suffix(')')) {
+            return Vec3::parse_hsv(args)
+                .ok_or_else(|| format!(
+                    "{:?} is not of the form hsv(h, s, v) with s and v \
+                    in [0, 1]", color));
+        }
+
+        NAMED_COLORS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(color))
+            .map(|(_, [r, g, b])| Vec3::new(*r, *g, *b))
+            .ok_or_else(|| format!(
+                "{:?} is not a color name, \"#rrggbb\" or \"hsv(h, s, v)\"",
+                color))
+    }
+
+    /// Parse the "h, s, v" inside hsv(...)
+    fn parse_hsv(args: &str) -> Option<Vec3> {
+        let values: Vec<f32> = args
+            .split(',')
+            .map(|value| value.trim().parse::<f32>().ok())
+            .collect::<Option<Vec<f32>>>()?;
+        match values.as_slice() {
+            [h, s, v] if h.is_finite() &&
+                    (0.0..=1.0).contains(s) && (0.0..=1.0).contains(v) =>
+                Some(Vec3::from_hsv(*h, *s, *v)),
+            _ => None
+        }
+    }
+
+    /// Convert a color from HSV to RGB. The hue is in degrees, the
+    /// saturation and value are in [0, 1]
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Vec3 {
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let chroma = value * saturation;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x)
+        };
+        let offset = value - chroma;
+        Vec3::new(r + offset, g + offset, b + offset)
+    }
+
+    /// Parse a color of the form "#rrggbb" into the range [0.0, 1.0]
+    pub fn from_hex(hex: &str) -> std::result::Result<Vec3, String> {
+        let digits = hex.strip_prefix('#')
+            .filter(|digits| digits.len() == 6 && digits.is_ascii())
+            .ok_or_else(|| format!("{:?} is not of the form #rrggbb", hex))?;
+        let channel = |i: usize| -> std::result::Result<f32, String> {
+            u8::from_str_radix(&digits[i..(i + 2)], 16)
+                .map(|value| value as f32 / 255.0)
+                .map_err(|_| format!("{:?} is not of the form #rrggbb", hex))
+        };
+        Ok(Vec3::new(channel(0)?, channel(2)?, channel(4)?))
+    }
+
     /// Parse an array of 
     fn parse_components(components: &Vec<JsonValue>) -> Vec3 {
         let components_float: Vec<f32> = components.into_iter().map(|x| {
@@ -187,6 +364,64 @@ impl Vec3 {
     }
 }
 
+/// The sRGB transfer function for a single linear component
+fn srgb_transfer(linear: f32) -> f32 {
+    let x = linear.clamp(0.0, 1.0);
+    if x <= 0.0031308 {
+        12.92 * x
+    } else {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Undo srgb_transfer(), using the same break point
+fn srgb_inverse_transfer(encoded: f32) -> f32 {
+    let x = encoded.clamp(0.0, 1.0);
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// How output colors are encoded
+#[derive(Clone, Copy, PartialEq)]
+pub enum ColorSpace {
+    /// Colors are written as-is
+    Linear,
+    /// Colors are encoded with the sRGB transfer function, which is what
+    /// most viewers expect for vertex colors
+    Srgb,
+}
+
+impl ColorSpace {
+    /// Parse from a JSON string "linear" | "srgb". The default is linear
+    /// to match older tilesets.
+    pub fn from_json(json: &JsonValue) -> Self {
+        match json.as_str().unwrap_or("linear") {
+            "linear" => Self::Linear,
+            "srgb" => Self::Srgb,
+            _ => panic!("color_space must be either linear or srgb")
+        }
+    }
+
+    /// Encode a linear color in this color space
+    pub fn encode(&self, color: &Vec3) -> Vec3 {
+        match self {
+            Self::Linear => *color,
+            Self::Srgb => color.linear_to_srgb()
+        }
+    }
+
+    /// Decode a color in this color space back to linear, e.g. to average
+    /// colors that were already encoded
+    pub fn decode(&self, color: &Vec3) -> Vec3 {
+        match self {
+            Self::Linear => *color,
+            Self::Srgb => color.srgb_to_linear()
+        }
+    }
+}
 
 /// Add Vec3 values component-wise
 impl Add for Vec3 {
@@ -231,6 +466,7 @@ impl Mul for Vec3 {
     }
 }
 
+/// Scale a Vec3, `v * k` is the same as `v.scale(k)`
 impl Mul<f32> for Vec3 {
     type Output = Self;
     fn mul(self, scalar: f32) -> Self {
@@ -244,11 +480,218 @@ impl Mul<f32> for Vec3 {
     }
 }
 
+/// Negate every component
+impl Neg for Vec3 {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Self {
+            components: [-self.x(), -self.y(), -self.z()]
+        }
+    }
+}
+
+impl AddAssign for Vec3 {
+    fn add_assign(&mut self, other: Self) {
+        for (component, x) in self.components.iter_mut().zip(&other.components) {
+            *component += x;
+        }
+    }
+}
+
+impl SubAssign for Vec3 {
+    fn sub_assign(&mut self, other: Self) {
+        for (component, x) in self.components.iter_mut().zip(&other.components) {
+            *component -= x;
+        }
+    }
+}
+
+impl MulAssign<f32> for Vec3 {
+    fn mul_assign(&mut self, scalar: f32) {
+        for component in self.components.iter_mut() {
+            *component *= scalar;
+        }
+    }
+}
+
 impl Color {
     /// Since colors are already u8s, we can pack them as `[x, y, z]` in
     /// 3 bytes
     pub fn pack(&self) -> [u8; 3] {
         self.components.clone()
     }
+
+    /// Pack many colors back to back as with pack(), allocating the exact
+    /// number of bytes up front
+    pub fn pack_all<I>(colors: I) -> Vec<u8>
+            where I: IntoIterator<Item = Color>,
+                  I::IntoIter: ExactSizeIterator {
+        let colors = colors.into_iter();
+        let mut results = Vec::with_capacity(3 * colors.len());
+        for color in colors {
+            results.extend_from_slice(&color.pack());
+        }
+        results
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_srgb_transfer() {
+        assert_eq!(srgb_transfer(0.0), 0.0);
+        assert!((srgb_transfer(0.5) - 0.735357).abs() < 1e-5);
+        assert!((srgb_transfer(1.0) - 1.0).abs() < 1e-6);
+
+        // the linear segment near black
+        assert!((srgb_transfer(0.001) - 0.01292).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_srgb_round_trip() {
+        for &linear in [0.0, 0.002, 0.2, 0.5, 0.9, 1.0].iter() {
+            let decoded = srgb_inverse_transfer(srgb_transfer(linear));
+            assert!((decoded - linear).abs() < 1e-6, "{}", linear);
+        }
+    }
+
+    #[test]
+    fn test_linear_to_srgb_clamps() {
+        let color = Vec3::new(-0.5, 0.5, 2.0).linear_to_srgb();
+        assert_eq!(*color.x(), 0.0);
+        assert!((*color.y() - 0.735357).abs() < 1e-5);
+        assert!((*color.z() - 1.0).abs() < 1e-6);
+    }
+
+    fn components(v: &Vec3) -> [f32; 3] {
+        [*v.x(), *v.y(), *v.z()]
+    }
+
+    #[test]
+    fn test_min_max() {
+        let a = Vec3::new(1.0, -2.0, 0.5);
+        let b = Vec3::new(0.25, 4.0, 0.5);
+        assert_eq!(components(&Vec3::min(&a, &b)), [0.25, -2.0, 0.5]);
+        assert_eq!(components(&Vec3::max(&a, &b)), [1.0, 4.0, 0.5]);
+
+        // splat(MAX) is the identity for min, and vice versa
+        let start = Vec3::splat(f32::MAX);
+        assert_eq!(components(&Vec3::min(&start, &a)), components(&a));
+        let start = Vec3::splat(f32::MIN);
+        assert_eq!(components(&Vec3::max(&start, &a)), components(&a));
+    }
+
+    #[test]
+    fn test_scalar_mul_matches_scale() {
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        assert_eq!(components(&(v * 2.0)), components(&v.scale(2.0)));
+        assert_eq!(components(&-v), [-1.0, 2.0, -0.5]);
+
+        // Multiplying two vectors is still component-wise
+        let w = Vec3::new(2.0, 3.0, 4.0);
+        assert_eq!(components(&(v * w)), [2.0, -6.0, 2.0]);
+    }
+
+    #[test]
+    fn test_assign_ops_match() {
+        let v = Vec3::new(1.0, -2.0, 0.5);
+        let w = Vec3::new(0.25, 4.0, -1.0);
+
+        let mut sum = v;
+        sum += w;
+        assert_eq!(components(&sum), components(&(v + w)));
+
+        let mut difference = v;
+        difference -= w;
+        assert_eq!(components(&difference), components(&(v - w)));
+
+        let mut scaled = v;
+        scaled *= 3.0;
+        assert_eq!(components(&scaled), components(&(v * 3.0)));
+    }
+
+    #[test]
+    fn test_from_hex() {
+        let color = Vec3::from_hex("#ff8000").unwrap();
+        assert_eq!(components(&color), [1.0, 128.0 / 255.0, 0.0]);
+        assert_eq!(components(&Vec3::from_hex("#FFFFFF").unwrap()), [1.0; 3]);
+
+        assert!(Vec3::from_hex("ff8000").is_err());
+        assert!(Vec3::from_hex("#ff80").is_err());
+        assert!(Vec3::from_hex("#ff80zz").is_err());
+        // Multi-byte characters must not panic when slicing
+        assert!(Vec3::from_hex("#fé800").is_err());
+    }
+
+    #[test]
+    fn test_color_from_json() {
+        let default_val = Vec3::new(0.1, 0.2, 0.3);
+        let parse = |json: JsonValue|
+            components(&Vec3::color_from_json(&json, default_val));
+
+        // Arrays and missing values still work like from_json()
+        assert_eq!(parse(array![0.25, 0.5, 0.75]), [0.25, 0.5, 0.75]);
+        assert_eq!(parse(array![0.5]), [0.5; 3]);
+        assert_eq!(parse(JsonValue::Null), [0.1, 0.2, 0.3]);
+
+        assert_eq!(parse("red".into()), [1.0, 0.0, 0.0]);
+        assert_eq!(parse(" Orange ".into()), [1.0, 0.5, 0.0]);
+        assert_eq!(parse("#ff8000".into()), [1.0, 128.0 / 255.0, 0.0]);
+    }
+
+    #[test]
+    fn test_from_color_str_hsv() {
+        let parse =
+            |color: &str| components(&Vec3::from_color_str(color).unwrap());
+        assert_eq!(parse("hsv(0, 1, 1)"), [1.0, 0.0, 0.0]);
+        assert_eq!(parse("hsv(120,1,1)"), [0.0, 1.0, 0.0]);
+        assert_eq!(parse("hsv(240, 1, 0.5)"), [0.0, 0.0, 0.5]);
+        assert_eq!(parse("hsv(-60, 1, 1)"), [1.0, 0.0, 1.0]);
+        assert_eq!(parse("hsv(200, 0, 0.8)"), [0.8; 3]);
+        let [r, g, b] = parse("hsv(200, 0.8, 1.0)");
+        assert!((r - 0.2).abs() < 1e-6);
+        assert!((g - 0.733333).abs() < 1e-5);
+        assert!((b - 1.0).abs() < 1e-6);
+
+        assert!(Vec3::from_color_str("hsv(0, 2, 1)").is_err());
+        assert!(Vec3::from_color_str("hsv(0, 1)").is_err());
+        assert!(Vec3::from_color_str("hsv(a, b, c)").is_err());
+        assert!(Vec3::from_color_str("chartreuse").is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "\"nope\" is not a color name")]
+    fn test_color_from_json_unknown_name() {
+        Vec3::color_from_json(&"nope".into(), Vec3::ones());
+    }
+
+    #[test]
+    fn test_pack_little_endian() {
+        let v = Vec3::new(1.0, -2.5, 0.1);
+        let packed = v.pack();
+        // 1.0 is 0x3f800000, stored lowest byte first
+        assert_eq!(&packed[..4], &[0x00, 0x00, 0x80, 0x3f]);
+
+        let unpack = |i: usize| f32::from_le_bytes(
+            [packed[i], packed[i + 1], packed[i + 2], packed[i + 3]]);
+        assert_eq!([unpack(0), unpack(4), unpack(8)], components(&v));
+    }
+
+    #[test]
+    fn test_pack_all_matches_pack() {
+        let vectors = [Vec3::new(1.0, 2.0, 3.0), Vec3::new(-0.5, 0.0, 1e-7)];
+        let packed = Vec3::pack_all(&vectors);
+        assert_eq!(packed.len(), 24);
+        assert_eq!(&packed[..12], &vectors[0].pack());
+        assert_eq!(&packed[12..], &vectors[1].pack());
+        assert!(Vec3::pack_all(&[]).is_empty());
+
+        let colors = [Color::new(255, 0, 16), Color::new(1, 2, 3)];
+        let packed = Color::pack_all(colors.iter().cloned());
+        assert_eq!(packed, vec![255, 0, 16, 1, 2, 3]);
+        assert_eq!(Vec3::new(1.0, 0.0, 0.5).to_color().pack(), [255, 0, 127]);
+    }
+}
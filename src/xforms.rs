@@ -1,8 +1,65 @@
+use std::cell::{OnceCell, RefCell};
 use std::f64::consts::PI;
 
 use json::JsonValue;
+use rand::Rng;
 
-use crate::half_multivector::HalfMultivector;
+use crate::expressions::Expr;
+use crate::half_multivector::{HalfMultivector, Parity};
+use crate::random::{self, OverridableRng};
+
+/// Half the side length of the cube whose corners are used to sample
+/// contraction_estimate(). This avoids the origin and the unit axis
+/// points, which are common centers for inversions
+const CONTRACTION_SAMPLE_RADIUS: f64 = 0.5;
+/// Step size used for the finite differences in contraction_estimate()
+const CONTRACTION_STEP: f64 = 1e-4;
+
+/// How a transformation treats angles. This explains a lot about how an
+/// attractor looks: conformal maps keep small shapes intact, so details
+/// repeat as smaller copies, while other maps stretch and shear them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Classification {
+    /// Preserves angles and orientation, e.g. rotations, translations,
+    /// scales and SCTs. These are the even versors
+    Conformal,
+    /// Preserves angles but flips orientation, e.g. reflections and
+    /// inversions. These are the odd versors
+    AntiConformal,
+    /// Does not preserve angles, e.g. warps, expressions and snapping
+    NonConformal,
+}
+
+impl Classification {
+    /// Classify a versor by its parity
+    pub fn of_versor(versor: &HalfMultivector) -> Self {
+        match versor.parity() {
+            Parity::Even => Classification::Conformal,
+            Parity::Odd => Classification::AntiConformal,
+        }
+    }
+
+    /// Classify applying this transformation followed by another. Two
+    /// orientation flips cancel out.
+    pub fn then(self, other: Self) -> Self {
+        use Classification::*;
+        match (self, other) {
+            (NonConformal, _) | (_, NonConformal) => NonConformal,
+            (Conformal, next) => next,
+            (AntiConformal, Conformal) => AntiConformal,
+            (AntiConformal, AntiConformal) => Conformal,
+        }
+    }
+
+    /// The name used in the tileset metadata
+    pub fn name(&self) -> &'static str {
+        match self {
+            Classification::Conformal => "conformal",
+            Classification::AntiConformal => "anti_conformal",
+            Classification::NonConformal => "non_conformal",
+        }
+    }
+}
 
 /// Any transformation from Cl(3) -> Cl(3) (3D Clifford Algebra)
 pub trait Transform {
@@ -12,33 +69,161 @@ pub trait Transform {
     /// Compute the inverse of this transformation if it is well-defined
     /// or None if not possible.
     fn inverse(&self) -> Option<Box<dyn Transform>>;
+
+    /// A short human-readable description of this transformation, e.g.
+    /// "rotate(z, 90°)". This is included in the tileset metadata so
+    /// coloring by transformation is self-documenting.
+    fn name(&self) -> String;
+
+    /// Whether this transformation preserves angles, see Classification
+    fn classification(&self) -> Classification;
+
+    /// If this transformation is a versor, get it. Chains of versors can
+    /// be multiplied together into a single versor, which is much faster
+    /// than applying each one in turn.
+    fn versor(&self) -> Option<&HalfMultivector> {
+        None
+    }
+
+    /// A longer description for debugging: the name, the classification
+    /// and the components of the versor, if any. Chains, powers and
+    /// inverses of versors are multiplied out into a single versor when
+    /// parsed, so this shows what they resolved to.
+    fn describe(&self) -> String {
+        let versor = match self.versor() {
+            Some(versor) => format!("{:?}", versor),
+            None => "none".to_string()
+        };
+        format!(
+            "{}\n    classification: {}\n    versor: {}",
+            self.name(), self.classification().name(), versor)
+    }
+
+    /// Estimate how much this transformation shrinks space: the largest
+    /// |f(a) - f(b)| / |a - b| for nearby pairs of points around the
+    /// corners of a small cube. Below 1 means the map is a contraction.
+    /// This is exact for similarities (rotate/scale/translate) but only
+    /// a local estimate for inversions, warps, etc.
+    fn contraction_estimate(&self) -> f64 {
+        let r = CONTRACTION_SAMPLE_RADIUS;
+        let h = CONTRACTION_STEP;
+        let mut max_ratio: f64 = 0.0;
+        for i in 0..8 {
+            let sign = |bit: usize| if i & bit == 0 { -r } else { r };
+            let (x, y, z) = (sign(1), sign(2), sign(4));
+            let (fx, fy, fz) =
+                self.transform(&HalfMultivector::point(x, y, z)).coordinates();
+
+            let steps = [(h, 0.0, 0.0), (0.0, h, 0.0), (0.0, 0.0, h)];
+            for (dx, dy, dz) in steps.iter() {
+                let moved = HalfMultivector::point(x + dx, y + dy, z + dz);
+                let (gx, gy, gz) = self.transform(&moved).coordinates();
+                let distance = 
+                    ((gx - fx).powi(2) + (gy - fy).powi(2) + (gz - fz).powi(2))
+                    .sqrt();
+                let ratio = distance / h;
+                // Skip samples that landed on a pole
+                if ratio.is_finite() {
+                    max_ratio = max_ratio.max(ratio);
+                }
+            }
+        }
+        max_ratio
+    }
+
+    /// Bound how far from the origin this transformation sends a point
+    /// within the given distance of it, |f(x)| <= |f(0)| + r |x| where r
+    /// is the contraction_estimate(). This is exact for similarities. None
+    /// if the origin is sent to infinity.
+    fn reach(&self, radius: f64) -> Option<f64> {
+        let origin = HalfMultivector::point(0.0, 0.0, 0.0);
+        let (x, y, z) = self.transform(&origin).coordinates();
+        let offset = (x * x + y * y + z * z).sqrt();
+        let reach = offset + self.contraction_estimate() * radius;
+        if reach.is_finite() {
+            Some(reach)
+        } else {
+            None
+        }
+    }
+}
+
+/// A small sinusoidal deformation of space, p += amp * sin(freq * p)
+/// componentwise. This is not conformal, but layering it on top of the
+/// conformal dynamics gives interesting organic shapes.
+#[derive(Clone, Copy)]
+pub struct Warp {
+    /// Amplitude of the displacement
+    amp: f64,
+    /// Spatial frequency of the displacement
+    freq: f64,
+}
+
+impl Warp {
+    /// Parse a warp from JSON of the form {"amp": a, "freq": f}
+    pub fn from_json(json: &JsonValue) -> Self {
+        let amp = json["amp"].as_f64().expect("warp: amp must be a number");
+        let freq = json["freq"].as_f64().expect("warp: freq must be a number");
+        Self { amp, freq }
+    }
+
+    pub fn apply(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        HalfMultivector::point(
+            x + self.amp * (self.freq * x).sin(),
+            y + self.amp * (self.freq * y).sin(),
+            z + self.amp * (self.freq * z).sin())
+    }
 }
 
 pub struct Xform {
-    versor: HalfMultivector
+    versor: HalfMultivector,
+    /// Human-readable description, see Transform::name()
+    name: String,
+    /// Optional warp applied to the input point before the versor
+    warp: Option<Warp>,
+    /// The inverse of the versor, computed the first time inverse() is
+    /// called. Symmetric IFSs can ask for the inverse of the same
+    /// generator more than once.
+    inverse_versor: OnceCell<HalfMultivector>,
 }
 
 impl Xform {
-    pub fn new(versor: HalfMultivector) -> Self {
+    pub fn new(versor: HalfMultivector, name: &str) -> Self {
         Self {
-            versor
+            versor,
+            name: name.to_string(),
+            warp: None,
+            inverse_versor: OnceCell::new(),
         }
     }
 
     pub fn identity() -> Self {
-        Self {
-            versor: HalfMultivector::identity()
-        }
+        Self::new(HalfMultivector::identity(), "identity")
+    }
+
+    /// Warp the input points before applying the versor
+    pub fn with_warp(mut self, warp: Warp) -> Self {
+        self.warp = Some(warp);
+        self
     }
 
+    /// Compose two transformations. Warps are not supported since the
+    /// result must be a single versor.
     pub fn followed_by(&self, other: &Self) -> Self {
-        Self {
-            versor: other.versor.geometric_product(&self.versor)
-        }
+        assert!(
+            self.warp.is_none() && other.warp.is_none(),
+            "followed_by: warped transformations cannot be composed");
+        Self::new(
+            other.versor.geometric_product(&self.versor),
+            &format!("{} then {}", self.name, other.name))
     }
 
     pub fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
-        let mut product = self.versor.sandwich_product(point);
+        let mut product = match &self.warp {
+            Some(warp) => self.versor.sandwich_product(&warp.apply(point)),
+            None => self.versor.sandwich_product(point)
+        };
         // often multiplication produces almost-zero components, but the
         // result will always be a vector since I'm only ever transforming
         // points.
@@ -50,25 +235,701 @@ impl Xform {
         product
     }
 
+    /// Invert the versor. This ignores the warp, since warps in general
+    /// are not invertible.
     pub fn inverse(&self) -> Self {
+        let versor = self.inverse_versor.get_or_init(|| self.versor.inverse());
+        Self::new(versor.clone(), &format!("inverse({})", self.name))
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for Xform {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        Xform::transform(self, point)
+    }
+
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        match self.warp {
+            Some(_) => None,
+            None => Some(Xform::inverse(self).to_box())
+        }
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn classification(&self) -> Classification {
+        match self.warp {
+            Some(_) => Classification::NonConformal,
+            None => Classification::of_versor(&self.versor)
+        }
+    }
+
+    /// A warped transformation is no longer just a versor
+    fn versor(&self) -> Option<&HalfMultivector> {
+        match self.warp {
+            Some(_) => None,
+            None => Some(&self.versor)
+        }
+    }
+}
+
+/// Apply several transformations in order. This is only needed when the
+/// chain includes a transformation that is not a versor, otherwise the
+/// versors are multiplied together into a single Xform.
+pub struct Chain {
+    /// Transformations in the order they are applied
+    xforms: Vec<Box<dyn Transform>>,
+}
+
+impl Chain {
+    pub fn new(xforms: Vec<Box<dyn Transform>>) -> Self {
         Self {
-            versor: self.versor.reverse()
+            xforms
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for Chain {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let mut result = point.clone();
+        for xform in self.xforms.iter() {
+            result = xform.transform(&result);
+        }
+        result
+    }
+
+    /// The inverse of a chain is the inverses applied in reverse order.
+    /// If any transformation is not invertible, neither is the chain.
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        let mut inverses = Vec::new();
+        for xform in self.xforms.iter().rev() {
+            inverses.push(xform.inverse()?);
         }
+        Some(Chain::new(inverses).to_box())
+    }
+
+    fn name(&self) -> String {
+        let names: Vec<String> = 
+            self.xforms.iter().map(|xform| xform.name()).collect();
+        format!("chain({})", names.join(", "))
+    }
+
+    fn classification(&self) -> Classification {
+        self.xforms
+            .iter()
+            .map(|xform| xform.classification())
+            .fold(Classification::Conformal, Classification::then)
+    }
+}
+
+/// If the denominator of a Möbius transformation is smaller than this,
+/// treat the point as the pole
+const MOBIUS_POLE_EPSILON: f64 = 1e-12;
+/// Points near the pole are sent to this coordinate rather than infinity.
+/// This can't be too large, else the null vector's n and p components are
+/// too close to each other to homogenize the point accurately.
+const MOBIUS_POLE_VALUE: f64 = 1e6;
+
+/// A complex number, only used for Möbius transformations
+#[derive(Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+
+    fn mul(&self, other: &Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re)
+    }
+
+    fn neg(&self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+
+    fn norm_sqr(&self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// Format as a + bi. Adding 0.0 turns -0.0 into 0.0
+    fn format(&self) -> String {
+        format!("{}{:+}i", self.re + 0.0, self.im + 0.0)
+    }
+
+    /// Divide, assuming the denominator is not 0
+    fn div(&self, other: &Self) -> Self {
+        let denominator = other.norm_sqr();
+        Self::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator)
+    }
+}
+
+/// A Möbius transformation of the complex plane, w -> (aw + b) / (cw + d)
+/// where w = x + iy. The z coordinate is left unchanged, so this is mostly
+/// useful for 2D fractals in the z = 0 plane.
+///
+/// This is not a versor in 3D CGA (it doesn't extend to a conformal map of
+/// space), hence the separate struct.
+pub struct Mobius {
+    a: Complex,
+    b: Complex,
+    c: Complex,
+    d: Complex,
+}
+
+impl Mobius {
+    fn new(a: Complex, b: Complex, c: Complex, d: Complex) -> Self {
+        let determinant = a.mul(&d).add(&b.mul(&c).neg());
+        if determinant.norm_sqr() < MOBIUS_POLE_EPSILON {
+            panic!("mobius: ad - bc must not be 0");
+        }
+
+        Self { a, b, c, d }
+    }
+
+    /// Parse a Möbius transformation from JSON of the form
+    /// ["mobius", ar, ai, br, bi, cr, ci, dr, di]
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let usage = "should be [\"mobius\", ar, ai, br, bi, cr, ci, dr, di]";
+        let parameters: Vec<f64> = xform_desc.members()
+            .skip(1)
+            .map(|x| x.as_f64().expect(usage))
+            .collect();
+
+        if let [ar, ai, br, bi, cr, ci, dr, di] = parameters[..] {
+            Self::new(
+                Complex::new(ar, ai),
+                Complex::new(br, bi),
+                Complex::new(cr, ci),
+                Complex::new(dr, di))
+        } else {
+            panic!("{}", usage)
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for Mobius {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let w = Complex::new(x, y);
+        let numerator = self.a.mul(&w).add(&self.b);
+        let denominator = self.c.mul(&w).add(&self.d);
+
+        let result = if denominator.norm_sqr() < MOBIUS_POLE_EPSILON {
+            Complex::new(MOBIUS_POLE_VALUE, MOBIUS_POLE_VALUE)
+        } else {
+            numerator.div(&denominator)
+        };
+
+        HalfMultivector::point(result.re, result.im, z)
+    }
+
+    /// The inverse of the matrix [[a, b], [c, d]] is proportional to
+    /// [[d, -b], [-c, a]]
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        let inverse = Self::new(self.d, self.b.neg(), self.c.neg(), self.a);
+        Some(inverse.to_box())
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "mobius({}, {}, {}, {})",
+            self.a.format(),
+            self.b.format(),
+            self.c.format(),
+            self.d.format())
+    }
+
+    /// This is conformal in the xy plane, but leaving z alone stretches
+    /// space unevenly in 3D
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
+    }
+}
+
+/// A transformation given by a math expression for each coordinate of the
+/// (homogenized) point, e.g. ["expr", "x*cos(z)", "y", "x*sin(z)"]. This is
+/// not conformal in general, it's an escape hatch for experimenting with
+/// arbitrary maps. See Expr for the syntax.
+pub struct ExprTransform {
+    /// Expressions for the new x, y and z
+    expressions: [Expr; 3],
+    /// The expressions as written, for the name
+    texts: [String; 3],
+}
+
+impl ExprTransform {
+    /// Parse from JSON of the form ["expr", "x expr", "y expr", "z expr"].
+    /// The expressions are parsed here so mistakes are reported up front
+    /// rather than in the middle of iterating.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let usage = "should be [\"expr\", \"x expr\", \"y expr\", \"z expr\"]";
+        if xform_desc.len() != 4 {
+            panic!("expr: {}", usage);
+        }
+
+        let text = |i: usize| xform_desc[i]
+            .as_str()
+            .unwrap_or_else(|| panic!("expr: {}", usage))
+            .to_string();
+        let texts = [text(1), text(2), text(3)];
+        let parse = |i: usize| Expr::parse(&texts[i]).unwrap_or_else(
+            |err| panic!("expr: could not parse {:?}: {}", texts[i], err));
+        let expressions = [parse(0), parse(1), parse(2)];
+
+        Self { expressions, texts }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for ExprTransform {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let variables = [x, y, z];
+        HalfMultivector::point(
+            self.expressions[0].evaluate(&variables),
+            self.expressions[1].evaluate(&variables),
+            self.expressions[2].evaluate(&variables))
+    }
+
+    /// Expressions are not inverted symbolically
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        format!("expr({})", self.texts.join(", "))
+    }
+
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
+    }
+}
+
+/// Round the (homogenized) point to the nearest site of a lattice with the
+/// given spacing along each axis, e.g. ["snap", 0.1, 0.1, 0.1]. The lattice
+/// sites are the integer multiples of the spacing. Combined with
+/// contractions in an IFS, this makes pixelated (voxel-like) attractors.
+pub struct SnapTransform {
+    /// The distance between lattice sites along x, y and z
+    spacing: [f64; 3],
+}
+
+impl SnapTransform {
+    /// Parse from JSON of the form ["snap", sx, sy, sz]. The spacings must
+    /// be positive.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let usage = "should be [\"snap\", sx, sy, sz]";
+        if xform_desc.len() != 4 {
+            panic!("snap: {}", usage);
+        }
+
+        let spacing_at = |i: usize| {
+            let spacing = xform_desc[i]
+                .as_f64()
+                .unwrap_or_else(|| panic!("snap: {}", usage));
+            if spacing <= 0.0 {
+                panic!("snap: the lattice spacing must be positive");
+            }
+            spacing
+        };
+        Self { spacing: [spacing_at(1), spacing_at(2), spacing_at(3)] }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for SnapTransform {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let snap = |value: f64, spacing: f64| (value / spacing).round() * spacing;
+        HalfMultivector::point(
+            snap(x, self.spacing[0]),
+            snap(y, self.spacing[1]),
+            snap(z, self.spacing[2]))
+    }
+
+    /// Snapping maps a whole cell to a single point, so it can't be undone
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        format!(
+            "snap({}, {}, {})", self.spacing[0], self.spacing[1], self.spacing[2])
+    }
+
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
+    }
+}
+
+/// Parse the four numbers of ["project_plane", nx, ny, nz, d],
+/// ["project_sphere", cx, cy, cz, r] or ["pinch", cx, cy, cz, strength]
+fn parse_projection(xform_desc: &JsonValue, usage: &str) -> [f64; 4] {
+    let name = xform_desc[0].as_str().unwrap();
+    if xform_desc.len() != 5 {
+        panic!("{}: {}", name, usage);
+    }
+
+    let mut values = [0.0; 4];
+    for (i, value) in values.iter_mut().enumerate() {
+        *value = xform_desc[i + 1]
+            .as_f64()
+            .unwrap_or_else(|| panic!("{}: {}", name, usage));
+    }
+    values
+}
+
+/// Orthogonally project the (homogenized) point onto the plane n . p = d,
+/// e.g. ["project_plane", 0, 0, 1, 0] flattens space onto the xy plane.
+/// The normal doesn't have to be a unit vector, d is measured in units of
+/// its length. Combined with contractions in an IFS, this makes fractal
+/// dust on a plane.
+pub struct ProjectPlane {
+    /// Unit normal of the plane
+    normal: [f64; 3],
+    /// Signed distance of the plane from the origin along the normal
+    distance: f64,
+}
+
+impl ProjectPlane {
+    /// Parse from JSON of the form ["project_plane", nx, ny, nz, d]. The
+    /// normal must not be zero.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let [nx, ny, nz, d] = parse_projection(
+            xform_desc, "should be [\"project_plane\", nx, ny, nz, d]");
+        let length = (nx * nx + ny * ny + nz * nz).sqrt();
+        if length == 0.0 {
+            panic!("project_plane: the normal must not be zero");
+        }
+
+        Self {
+            normal: [nx / length, ny / length, nz / length],
+            distance: d / length,
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for ProjectPlane {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let [nx, ny, nz] = self.normal;
+        let height = nx * x + ny * y + nz * z - self.distance;
+        HalfMultivector::point(
+            x - height * nx, y - height * ny, z - height * nz)
+    }
+
+    /// Projecting flattens a whole line to a single point, so it can't be
+    /// undone
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        let [nx, ny, nz] = self.normal;
+        format!("project_plane(({}, {}, {}), {})", nx, ny, nz, self.distance)
+    }
+
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
+    }
+}
+
+/// Radially project the (homogenized) point onto a sphere, i.e. move it
+/// along the ray from the center until it is on the sphere, e.g.
+/// ["project_sphere", 0, 0, 0, 1]. The center itself has no direction, so
+/// it goes to the top of the sphere (+z).
+pub struct ProjectSphere {
+    center: [f64; 3],
+    radius: f64,
+}
+
+impl ProjectSphere {
+    /// Parse from JSON of the form ["project_sphere", cx, cy, cz, r]. The
+    /// radius must be positive.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let [cx, cy, cz, radius] = parse_projection(
+            xform_desc, "should be [\"project_sphere\", cx, cy, cz, r]");
+        if radius <= 0.0 {
+            panic!("project_sphere: the radius must be positive");
+        }
+
+        Self {
+            center: [cx, cy, cz],
+            radius,
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for ProjectSphere {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let [cx, cy, cz] = self.center;
+        let (dx, dy, dz) = (x - cx, y - cy, z - cz);
+        let length = (dx * dx + dy * dy + dz * dz).sqrt();
+        if length == 0.0 {
+            return HalfMultivector::point(cx, cy, cz + self.radius);
+        }
+
+        let scale = self.radius / length;
+        HalfMultivector::point(
+            cx + scale * dx, cy + scale * dy, cz + scale * dz)
+    }
+
+    /// Projecting flattens a whole ray to a single point, so it can't be
+    /// undone
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        let [cx, cy, cz] = self.center;
+        format!("project_sphere(({}, {}, {}), {})", cx, cy, cz, self.radius)
+    }
+
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
+    }
+}
+
+/// Pull points toward a center, or push them away, with a strength that
+/// falls off with distance, e.g. ["pinch", 0, 0, 0, 0.5]. A point at
+/// distance r from the center moves along the radial direction to
+///
+/// r' = r * (1 - strength * exp(-r^2))
+///
+/// so the displacement strength * r * exp(-r^2) vanishes both at the center
+/// and far away, and is largest at r = 1/sqrt(2). A positive strength
+/// pinches and a negative one bulges. Chain it with scales to change the
+/// falloff distance. This makes lens-like distortions layered on the IFS.
+pub struct Pinch {
+    center: [f64; 3],
+    strength: f64,
+}
+
+impl Pinch {
+    /// Parse from JSON of the form ["pinch", cx, cy, cz, strength]. The
+    /// strength must be at most 1 so no point is pulled past the center.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let [cx, cy, cz, strength] = parse_projection(
+            xform_desc, "should be [\"pinch\", cx, cy, cz, strength]");
+        if strength > 1.0 {
+            panic!("pinch: the strength must be at most 1");
+        }
+
+        Self {
+            center: [cx, cy, cz],
+            strength,
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for Pinch {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let (x, y, z) = point.coordinates();
+        let [cx, cy, cz] = self.center;
+        let (dx, dy, dz) = (x - cx, y - cy, z - cz);
+        let squared_distance = dx * dx + dy * dy + dz * dz;
+        let scale = 1.0 - self.strength * (-squared_distance).exp();
+        HalfMultivector::point(
+            cx + scale * dx, cy + scale * dy, cz + scale * dz)
+    }
+
+    /// Undoing the pinch means solving for r in r' = r(1 - s exp(-r^2)),
+    /// which has no closed form
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        let [cx, cy, cz] = self.center;
+        format!("pinch(({}, {}, {}), {})", cx, cy, cz, self.strength)
+    }
+
+    fn classification(&self) -> Classification {
+        Classification::NonConformal
     }
 }
 
+/// Each time it's applied, interpolate between two versors by a random
+/// amount t in [0, 1] with HalfMultivector::slerp_motor(), e.g.
+/// ["blend", ["rotate", 0, 0, 1, 0], ["rotate", 0, 0, 1, 30]] rotates by a
+/// random angle up to 30 degrees. This blurs what would be one attractor
+/// into a continuous family of them. The two versors must differ by a
+/// rotation.
+///
+/// Transform::transform() takes &self, so the random number generator is
+/// kept in a RefCell rather than changing the trait for every other
+/// transformation.
+pub struct Blend {
+    /// The versor for t = 0
+    start: HalfMultivector,
+    /// The versor for t = 1
+    end: HalfMultivector,
+    /// Human-readable description, see Transform::name()
+    name: String,
+    /// Random number generator for t. This is the render's generator unless
+    /// the blend has its own seed
+    rng: RefCell<OverridableRng>,
+}
+
+impl Blend {
+    /// Parse from JSON of the form ["blend", xform_a, xform_b, seed] where
+    /// the seed is optional. Without it, t is drawn from the render's
+    /// generator so it follows the parameter file's seed. With it, the
+    /// blend's own seed wins.
+    pub fn from_json(xform_desc: &JsonValue) -> Self {
+        let usage = "should be [\"blend\", xform_a, xform_b, seed (optional)]";
+        if xform_desc.len() != 3 && xform_desc.len() != 4 {
+            panic!("blend: {}", usage);
+        }
+
+        let start = from_json(&xform_desc[1]);
+        let end = from_json(&xform_desc[2]);
+        let error_msg = "blend: only versor transformations can be blended";
+        let start_versor = start.versor().expect(error_msg).clone();
+        let end_versor = end.versor().expect(error_msg).clone();
+        // slerp_motor() panics if the versors don't differ by a rotation,
+        // check that now rather than on the first point
+        HalfMultivector::slerp_motor(&start_versor, &end_versor, 0.5);
+
+        let seed = match &xform_desc[3] {
+            JsonValue::Null => None,
+            seed => Some(seed
+                .as_u64()
+                .unwrap_or_else(|| panic!("blend: {}", usage)))
+        };
+
+        Self {
+            start: start_versor,
+            end: end_versor,
+            name: format!("blend({}, {})", start.name(), end.name()),
+            rng: RefCell::new(random::overridable_rng(seed)),
+        }
+    }
+
+    to_box!(Transform);
+}
+
+impl Transform for Blend {
+    fn transform(&self, point: &HalfMultivector) -> HalfMultivector {
+        let t = self.rng.borrow_mut().gen_range(0.0, 1.0);
+        let versor = HalfMultivector::slerp_motor(&self.start, &self.end, t);
+        let mut product = versor.sandwich_product(point);
+        product.expect_vector();
+        product.homogenize();
+        product
+    }
+
+    /// Each application picks a different versor, so there is no single
+    /// transformation that undoes it
+    fn inverse(&self) -> Option<Box<dyn Transform>> {
+        None
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    /// Every blended versor is the start versor followed by a rotation
+    fn classification(&self) -> Classification {
+        Classification::of_versor(&self.start)
+    }
+}
+
+/// Units for the angle of rotate, poloidal, rotoreflect and spiral_invert
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum AngleUnit {
+    Degrees,
+    Radians,
+}
+
+impl AngleUnit {
+    /// Split the optional unit off the end of the parameters of a
+    /// transformation, e.g. the "rad" in ["rotate", 0, 0, 1, 3.14, "rad"].
+    fn split(parameters: &[JsonValue]) -> (&[JsonValue], Option<Self>) {
+        match parameters.split_last() {
+            Some((last, rest)) if last.is_string() => {
+                match last.as_str().unwrap() {
+                    "deg" => (rest, Some(Self::Degrees)),
+                    "rad" => (rest, Some(Self::Radians)),
+                    _ => panic!("angle unit must be either deg or rad")
+                }
+            },
+            _ => (parameters, None)
+        }
+    }
+
+    fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            Self::Degrees => angle * PI / 180.0,
+            Self::Radians => angle,
+        }
+    }
+
+    fn describe(self, angle: f64) -> String {
+        match self {
+            Self::Degrees => format!("{}°", angle),
+            Self::Radians => format!("{} rad", angle),
+        }
+    }
+}
+
+/// The parameters and angle unit of a versor transformation. Angles are
+/// in degrees if there is no unit.
+fn versor_parameters(versor_desc: &JsonValue) -> (Vec<f64>, AngleUnit) {
+    let components = match versor_desc {
+        JsonValue::Array(components) => &components[1..],
+        _ => &[]
+    };
+    let (components, unit) = AngleUnit::split(components);
+    let xform_type = versor_desc[0].as_str().unwrap();
+    let has_angle = matches!(
+        xform_type, "rotate" | "poloidal" | "rotoreflect" | "spiral_invert");
+    if unit.is_some() && !has_angle {
+        panic!(
+            "{}: only rotate, poloidal, rotoreflect and spiral_invert take an \
+            angle unit",
+            xform_type);
+    }
+    let parameters = components.iter().map(|x| {
+        x.as_f64()
+        .expect("sandwich:transformation parameters must be floats")
+    }).collect();
+
+    (parameters, unit.unwrap_or(AngleUnit::Degrees))
+}
+
 fn get_versor(versor_desc: &JsonValue) -> HalfMultivector {
     // We've already validated the string at this point
     let xform_type = versor_desc[0].as_str().unwrap();
-        
-    let parameters: Vec<f64> = match versor_desc {
-        JsonValue::Array(components) => 
-            components[1..].iter().map(|x| {
-                x.as_f64()
-                .expect("sandwich:transformation parameters must be floats")
-            }).collect(),
-        _ => Vec::new()
-    };
+    let (parameters, unit) = versor_parameters(versor_desc);
 
     let valid_names: Vec<&str> = vec![
         "identity",
@@ -78,6 +939,10 @@ fn get_versor(versor_desc: &JsonValue) -> HalfMultivector {
         "scale",
         "invert",
         "reflect",
+        "rotoreflect",
+        "sct",
+        "invert_sphere",
+        "spiral_invert",
     ];
 
     match xform_type {
@@ -91,23 +956,28 @@ fn get_versor(versor_desc: &JsonValue) -> HalfMultivector {
             }
         },
         "rotate" => {
-            if let [nx, ny, nz, theta_deg] = &parameters[..] {
-                let angle = *theta_deg * PI / 180.0;
+            if let [nx, ny, nz, theta] = &parameters[..] {
+                let angle = unit.to_radians(*theta);
                 HalfMultivector::rotation(*nx, *ny, *nz, angle)
             } else {
-                panic!("should be [\"rotate\", axis_x, axis_y, axis_z, theta_deg]")
+                panic!("should be [\"rotate\", axis_x, axis_y, axis_z, theta]")
             }
         },
         "poloidal" => {
-            if let [x, y, z, theta_deg] = &parameters[..] {
-                let angle = *theta_deg * PI / 180.0;
+            if let [x, y, z, theta] = &parameters[..] {
+                let angle = unit.to_radians(*theta);
                 HalfMultivector::poloidal(*x, *y, *z, angle)
             } else {
-                panic!("should be [\"poloidal\", axis_x, axis_y, axis_z, theta_deg]")
+                panic!("should be [\"poloidal\", axis_x, axis_y, axis_z, theta]")
             }
         },
         "scale" => {
             if let [k] = &parameters[..] {
+                if *k <= 0.0 {
+                    panic!(
+                        "scale: scale factor must be positive, use reflect \
+                        to flip signs");
+                }
                 HalfMultivector::scale(*k)
             } else {
                 panic!("should be [\"scale\", scale_factor]")
@@ -120,44 +990,889 @@ fn get_versor(versor_desc: &JsonValue) -> HalfMultivector {
                 panic!("should be [\"reflect_vec\", nx, ny, nz]")
             }
         },
+        "rotoreflect" => {
+            if let [nx, ny, nz, theta] = &parameters[..] {
+                let angle = unit.to_radians(*theta);
+                HalfMultivector::rotoreflection(*nx, *ny, *nz, angle)
+            } else {
+                panic!("should be [\"rotoreflect\", axis_x, axis_y, axis_z, theta]")
+            }
+        },
+        "sct" => {
+            if let [bx, by, bz] = &parameters[..] {
+                HalfMultivector::special_conformal(*bx, *by, *bz)
+            } else {
+                panic!("should be [\"sct\", bx, by, bz]")
+            }
+        },
+        "invert_sphere" => {
+            if let [cx, cy, cz, r] = &parameters[..] {
+                if *r <= 0.0 {
+                    panic!("invert_sphere: radius must be positive");
+                }
+                HalfMultivector::sphere_inversion(*cx, *cy, *cz, *r)
+            } else {
+                panic!("should be [\"invert_sphere\", cx, cy, cz, radius]")
+            }
+        },
+        "spiral_invert" => {
+            if let [nx, ny, nz, theta, k] = &parameters[..] {
+                if *k <= 0.0 {
+                    panic!("spiral_invert: scale factor must be positive");
+                }
+                let angle = unit.to_radians(*theta);
+                HalfMultivector::spiral_inversion(*nx, *ny, *nz, angle, *k)
+            } else {
+                panic!(
+                    "should be [\"spiral_invert\", axis_x, axis_y, axis_z, \
+                    theta, scale_factor]")
+            }
+        },
         _ => panic!("transformation type must be one of {:?}", valid_names)
     }
 }
 
-fn from_chain(xform_chain: &JsonValue) -> Xform {
-    let mut chain = Xform::identity();
-    for xform_json in xform_chain[1].members() {
-        let xform = from_json(&xform_json);
-        chain = chain.followed_by(&xform);
+/// Describe a versor transformation from its JSON. This assumes
+/// get_versor() already validated the parameters.
+fn describe_versor(versor_desc: &JsonValue) -> String {
+    let xform_type = versor_desc[0].as_str().unwrap();
+    let (parameters, unit) = versor_parameters(versor_desc);
+
+    match (xform_type, &parameters[..]) {
+        ("rotate", [nx, ny, nz, theta]) |
+        ("poloidal", [nx, ny, nz, theta]) |
+        ("rotoreflect", [nx, ny, nz, theta]) => format!(
+            "{}({}, {})",
+            xform_type,
+            describe_axis(*nx, *ny, *nz),
+            unit.describe(*theta)),
+        ("reflect", [nx, ny, nz]) => 
+            format!("reflect({})", describe_axis(*nx, *ny, *nz)),
+        ("invert_sphere", [cx, cy, cz, r]) =>
+            format!("invert_sphere(({}, {}, {}), {})", cx, cy, cz, r),
+        ("spiral_invert", [nx, ny, nz, theta, k]) => format!(
+            "spiral_invert({}, {}, {})",
+            describe_axis(*nx, *ny, *nz),
+            unit.describe(*theta),
+            k),
+        (_, []) => xform_type.to_string(),
+        (_, _) => {
+            let values: Vec<String> = 
+                parameters.iter().map(|x| x.to_string()).collect();
+            format!("{}({})", xform_type, values.join(", "))
+        }
+    }
+}
+
+/// Name an axis x, y or z if it's one of the coordinate axes, otherwise
+/// list the components
+fn describe_axis(x: f64, y: f64, z: f64) -> String {
+    if y == 0.0 && z == 0.0 && x > 0.0 {
+        "x".to_string()
+    } else if x == 0.0 && z == 0.0 && y > 0.0 {
+        "y".to_string()
+    } else if x == 0.0 && y == 0.0 && z > 0.0 {
+        "z".to_string()
+    } else {
+        format!("({}, {}, {})", x, y, z)
     }
-    chain
 }
 
-pub fn from_json(xform_desc: &JsonValue) -> Xform {
+fn from_chain(xform_chain: &JsonValue) -> Box<dyn Transform> {
+    let xforms: Vec<Box<dyn Transform>> = 
+        xform_chain[1].members().map(from_json).collect();
+
+    // If every transformation is a versor, multiply them together
+    if xforms.iter().all(|xform| xform.versor().is_some()) {
+        let names: Vec<String> = 
+            xforms.iter().map(|xform| xform.name()).collect();
+        let mut chain = Xform::identity();
+        for xform in xforms.iter() {
+            let versor = xform.versor().unwrap().clone();
+            chain = chain.followed_by(&Xform::new(versor, &xform.name()));
+        }
+        chain.name = format!("chain({})", names.join(", "));
+        return chain.to_box();
+    }
+
+    Chain::new(xforms).to_box()
+}
+
+/// Interpolate between two rotations, e.g. for animation frames:
+/// ["slerp", <xform>, <xform>, t]
+fn from_slerp(xform_desc: &JsonValue) -> Box<dyn Transform> {
+    let start = from_json(&xform_desc[1]);
+    let end = from_json(&xform_desc[2]);
+    let t = xform_desc[3]
+        .as_f64()
+        .expect("should be [\"slerp\", xform_a, xform_b, t]");
+    let error_msg = "slerp: only versor transformations can be interpolated";
+    let start_versor = start.versor().expect(error_msg);
+    let end_versor = end.versor().expect(error_msg);
+    let versor = HalfMultivector::slerp_motor(start_versor, end_versor, t);
+    let name = format!("slerp({}, {}, {})", start.name(), end.name(), t);
+    Xform::new(versor, &name).to_box()
+}
+
+/// Apply a versor transformation k times, e.g. for rotating by k steps of
+/// a symmetry: ["power", <xform>, k]. The result is a single versor. A
+/// negative k applies the inverse |k| times.
+fn from_power(xform_desc: &JsonValue) -> Box<dyn Transform> {
+    let inner = from_json(&xform_desc[1]);
+    let k = xform_desc[2]
+        .as_i32()
+        .expect("should be [\"power\", xform, k] with integer k");
+    let versor = inner
+        .versor()
+        .expect("power: only versor transformations can be raised to a power");
+    let base = if k < 0 { versor.inverse() } else { versor.clone() };
+
+    let mut result = HalfMultivector::identity();
+    for _ in 0..k.unsigned_abs() {
+        result = base.geometric_product(&result);
+    }
+    let name = format!("power({}, {})", inner.name(), k);
+    Xform::new(result, &name).to_box()
+}
+
+/// Use a versor given by its raw components: ["versor", parity, c0..c15]
+/// where parity is "even" or "odd". The components are in the order listed
+/// at the top of half_multivector.rs. This is an escape hatch for versors
+/// that no other transformation type can describe.
+fn from_raw_versor(xform_desc: &JsonValue) -> Box<dyn Transform> {
+    let parity = match xform_desc[1].as_str() {
+        Some("even") => Parity::Even,
+        Some("odd") => Parity::Odd,
+        _ => panic!("versor: parity must be either even or odd")
+    };
+    let values: Vec<f64> = xform_desc
+        .members()
+        .skip(2)
+        .map(|x| x.as_f64().expect("versor: components must be numbers"))
+        .collect();
+    if values.len() != 16 {
+        panic!("versor: expected 16 components, got {}", values.len());
+    }
+    let mut components = [0.0; 16];
+    components.copy_from_slice(&values);
+
+    let name = format!("versor({:?})", parity).to_lowercase();
+    let versor = HalfMultivector::from_components(components, parity)
+        .unwrap_or_else(|err| panic!("versor: {}", err));
+    Xform::new(versor, &name).to_box()
+}
+
+/// Parse a warped versor transformation from JSON of the form
+/// {"xform": <xform JSON>, "warp": {"amp": a, "freq": f}}
+fn from_warp(warp_desc: &JsonValue) -> Box<dyn Transform> {
+    let inner = from_json(&warp_desc["xform"]);
+    let versor = inner
+        .versor()
+        .expect("warp: only versor transformations can be warped")
+        .clone();
+    let warp = Warp::from_json(&warp_desc["warp"]);
+    let name = format!("warp({}, {}, {})", inner.name(), warp.amp, warp.freq);
+    Xform::new(versor, &name).with_warp(warp).to_box()
+}
+
+/// All the transformation types that from_json() understands
+const XFORM_TYPES: [&str; 22] = [
+    "chain",
+    "slerp",
+    "blend",
+    "power",
+    "versor",
+    "invert",
+    "identity",
+    "translate",
+    "rotate",
+    "poloidal",
+    "scale",
+    "reflect",
+    "rotoreflect",
+    "sct",
+    "invert_sphere",
+    "spiral_invert",
+    "mobius",
+    "expr",
+    "snap",
+    "project_plane",
+    "project_sphere",
+    "pinch",
+];
+
+/// Parse a transformation from JSON. This is usually an array like
+/// ["rotate", 0, 0, 1, 90] but an object is also allowed for adding
+/// a warp, see from_warp(). Angles are in degrees unless the array ends
+/// with "rad", e.g. ["rotate", 0, 0, 1, 1.5708, "rad"].
+pub fn from_json(xform_desc: &JsonValue) -> Box<dyn Transform> {
+    if xform_desc.is_object() {
+        return from_warp(xform_desc);
+    }
+
     let xform_type = xform_desc[0]
         .as_str()
         .expect("xforms: transformation type must be a string");
 
-    let valid_names: Vec<&str> = vec![
-        "chain",
-        "invert",
-        "identity",
-        "translate",
-        "rotate",
-        "poloidal",
-        "scale",
-        "reflect",
-    ];
-
     match &xform_type[..] {
         "chain" => from_chain(xform_desc),
+        "slerp" => from_slerp(xform_desc),
+        "blend" => Blend::from_json(xform_desc).to_box(),
+        "power" => from_power(xform_desc),
+        "versor" => from_raw_versor(xform_desc),
+        "mobius" => Mobius::from_json(xform_desc).to_box(),
+        "expr" => ExprTransform::from_json(xform_desc).to_box(),
+        "snap" => SnapTransform::from_json(xform_desc).to_box(),
+        "project_plane" => ProjectPlane::from_json(xform_desc).to_box(),
+        "project_sphere" => ProjectSphere::from_json(xform_desc).to_box(),
+        "pinch" => Pinch::from_json(xform_desc).to_box(),
         "invert" | 
         "identity" | 
         "translate" | 
         "rotate" | 
         "poloidal" |
         "reflect" |
-        "scale" => Xform::new(get_versor(xform_desc)),
-        _ => panic!("xforms: xform type must be one of {:?}", valid_names)
+        "rotoreflect" |
+        "sct" |
+        "invert_sphere" |
+        "spiral_invert" |
+        "scale" => {
+            let versor = get_versor(xform_desc);
+            Xform::new(versor, &describe_versor(xform_desc)).to_box()
+        },
+        _ => panic!("xforms: xform type must be one of {:?}", XFORM_TYPES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    fn assert_point_eq(actual: &HalfMultivector, expected: (f64, f64, f64)) {
+        let (x, y, z) = actual.coordinates();
+        let (ex, ey, ez) = expected;
+        assert!((x - ex).abs() < 1e-9, "x: {} != {}", x, ex);
+        assert!((y - ey).abs() < 1e-9, "y: {} != {}", y, ey);
+        assert!((z - ez).abs() < 1e-9, "z: {} != {}", z, ez);
+    }
+
+    #[test]
+    fn test_mobius_identity() {
+        let xform = from_json(&array!["mobius", 1, 0, 0, 0, 0, 0, 1, 0]);
+        let point = HalfMultivector::point(0.5, -2.0, 3.0);
+        assert_point_eq(&xform.transform(&point), (0.5, -2.0, 3.0));
+    }
+
+    #[test]
+    fn test_mobius_inversion() {
+        // w -> 1/w sends 2i to -i/2 and leaves z alone
+        let xform = from_json(&array!["mobius", 0, 0, 1, 0, 1, 0, 0, 0]);
+        let point = HalfMultivector::point(0.0, 2.0, 1.0);
+        assert_point_eq(&xform.transform(&point), (0.0, -0.5, 1.0));
+    }
+
+    #[test]
+    fn test_inverse_is_cached() {
+        let xform = Xform::new(
+            HalfMultivector::translation(1.0, -2.0, 0.5), "translate");
+        assert!(xform.inverse_versor.get().is_none());
+        let first = xform.inverse();
+        assert!(xform.inverse_versor.get().is_some());
+        let second = xform.inverse();
+        assert!(first.versor.approx_eq(&second.versor, 1e-15));
+        assert!(first.versor.approx_eq(&xform.versor.inverse(), 1e-15));
+        assert_eq!(second.name, "inverse(translate)");
+    }
+
+    #[test]
+    fn test_mobius_inverse_round_trip() {
+        let xform = from_json(&array!["mobius", 1, 1, 0.5, 0, -0.25, 0.5, 2, 0]);
+        let inverse = xform.inverse().unwrap();
+        let point = HalfMultivector::point(0.3, 0.7, -1.0);
+        let result = inverse.transform(&xform.transform(&point));
+        assert_point_eq(&result, (0.3, 0.7, -1.0));
+    }
+
+    #[test]
+    fn test_mobius_pole_is_clamped() {
+        // w -> 1/w at the origin
+        let xform = from_json(&array!["mobius", 0, 0, 1, 0, 1, 0, 0, 0]);
+        let point = HalfMultivector::point(0.0, 0.0, 0.0);
+        let (x, y, _) = xform.transform(&point).coordinates();
+        assert!(x.is_finite() && y.is_finite());
+    }
+
+    #[test]
+    fn test_chain_with_mobius() {
+        // translate by 1 in x, then w -> 2w
+        let chain = from_json(&array![
+            "chain",
+            array![
+                array!["translate", 1, 0, 0],
+                array!["mobius", 2, 0, 0, 0, 0, 0, 1, 0]
+            ]
+        ]);
+        assert!(chain.versor().is_none());
+        let point = HalfMultivector::point(1.0, 1.0, 0.5);
+        assert_point_eq(&chain.transform(&point), (4.0, 2.0, 0.5));
+
+        let inverse = chain.inverse().unwrap();
+        assert_point_eq(&inverse.transform(&chain.transform(&point)),
+            (1.0, 1.0, 0.5));
+    }
+
+    #[test]
+    fn test_names() {
+        let examples = [
+            (array!["rotate", 0, 0, 1, 90], "rotate(z, 90°)"),
+            (array!["rotate", 1, 1, 0, 45], "rotate((1, 1, 0), 45°)"),
+            (array!["scale", 0.5], "scale(0.5)"),
+            (array!["invert"], "invert"),
+            (array!["translate", 1, -2, 0.5], "translate(1, -2, 0.5)"),
+            (array!["reflect", 0, 1, 0], "reflect(y)"),
+            (
+                array!["chain", array![array!["scale", 2], array!["invert"]]],
+                "chain(scale(2), invert)"
+            ),
+            (
+                array!["mobius", 1, 0, 0, 0, 0, 0, 1, -1],
+                "mobius(1+0i, 0+0i, 0+0i, 1-1i)"
+            ),
+        ];
+        for (json, expected) in examples.iter() {
+            assert_eq!(from_json(json).name(), *expected);
+        }
+    }
+
+    #[test]
+    fn test_unwarped_matches_warp_amp_zero() {
+        let plain = from_json(&array!["rotate", 0, 0, 1, 90]);
+        let warped = from_json(&object!{
+            "xform" => array!["rotate", 0, 0, 1, 90],
+            "warp" => object!{ "amp" => 0.0, "freq" => 3.0 }
+        });
+        let point = HalfMultivector::point(0.3, -0.2, 0.7);
+        let expected = plain.transform(&point).coordinates();
+        assert_point_eq(&warped.transform(&point), expected);
+    }
+
+    #[test]
+    fn test_warp() {
+        let warped = from_json(&object!{
+            "xform" => array!["identity"],
+            "warp" => object!{ "amp" => 0.5, "freq" => 2.0 }
+        });
+        let point = HalfMultivector::point(0.25, 0.0, -1.0);
+        let expected = (
+            0.25 + 0.5 * 0.5f64.sin(),
+            0.0,
+            -1.0 - 0.5 * 2.0f64.sin()
+        );
+        assert_point_eq(&warped.transform(&point), expected);
+
+        assert!(warped.inverse().is_none());
+        assert!(warped.versor().is_none());
+    }
+
+    #[test]
+    fn test_chain_keeps_warp() {
+        // A chain containing a warp can't be multiplied into a single
+        // versor, so the warp must still be applied
+        let chain = from_json(&array![
+            "chain",
+            array![
+                object!{
+                    "xform" => array!["identity"],
+                    "warp" => object!{ "amp" => 0.5, "freq" => 2.0 }
+                },
+                array!["translate", 1, 0, 0]
+            ]
+        ]);
+        let point = HalfMultivector::point(0.25, 0.0, 0.0);
+        let expected = (1.25 + 0.5 * 0.5f64.sin(), 0.0, 0.0);
+        assert_point_eq(&chain.transform(&point), expected);
+    }
+
+    #[test]
+    fn test_invert_sphere() {
+        let xform = from_json(&array!["invert_sphere", 1, 2, 3, 2]);
+        assert_eq!(xform.name(), "invert_sphere((1, 2, 3), 2)");
+
+        // 4 units from the center maps to 4/4 = 1 unit from the center
+        let point = HalfMultivector::point(5.0, 2.0, 3.0);
+        assert_point_eq(&xform.transform(&point), (2.0, 2.0, 3.0));
+
+        // Points on the sphere are fixed
+        let on_sphere = HalfMultivector::point(1.0, 0.0, 3.0);
+        assert_point_eq(&xform.transform(&on_sphere), (1.0, 0.0, 3.0));
+    }
+
+    #[test]
+    fn test_contraction_estimate() {
+        let close = |xform: JsonValue, expected: f64| {
+            let estimate = from_json(&xform).contraction_estimate();
+            assert!(
+                (estimate - expected).abs() < 1e-3,
+                "expected {}, got {}", expected, estimate);
+        };
+        close(array!["scale", 0.5], 0.5);
+        close(array!["rotate", 0, 0, 1, 45], 1.0);
+        close(array!["translate", 1, 2, 3], 1.0);
+        close(array!["chain", array![
+            array!["scale", 0.5],
+            array!["translate", 1, 0, 0]
+        ]], 0.5);
+
+        // Inverting in the unit sphere stretches points inside it. The
+        // sample points are sqrt(3)/2 from the origin, so the stretch
+        // factor is 1/|p|^2 = 4/3
+        close(array!["invert"], 4.0 / 3.0);
+    }
+
+    #[test]
+    fn test_inverse_name() {
+        let xform = from_json(&array!["scale", 0.5]);
+        assert_eq!(xform.inverse().unwrap().name(), "inverse(scale(0.5))");
+    }
+
+    #[test]
+    fn test_angle_units() {
+        for xform_type in ["rotate", "poloidal", "rotoreflect"] {
+            let radians = from_json(&array![xform_type, 1, 2, 3, PI, "rad"]);
+            let degrees = from_json(&array![xform_type, 1, 2, 3, 180, "deg"]);
+            let default = from_json(&array![xform_type, 1, 2, 3, 180]);
+            assert!(radians.versor().unwrap().approx_eq(
+                degrees.versor().unwrap(), 1e-12));
+            assert!(default.versor().unwrap().approx_eq(
+                degrees.versor().unwrap(), 1e-12));
+        }
+
+        let xform = from_json(&array!["rotate", 0, 0, 1, 1.5, "rad"]);
+        assert_eq!(xform.name(), "rotate(z, 1.5 rad)");
+        let xform = from_json(&array!["rotate", 0, 0, 1, 90, "deg"]);
+        assert_eq!(xform.name(), "rotate(z, 90°)");
+    }
+
+    #[test]
+    #[should_panic(expected = "only rotate, poloidal, rotoreflect and spiral")]
+    fn test_angle_unit_needs_angle() {
+        from_json(&array!["scale", 0.5, "rad"]);
+    }
+
+    #[test]
+    fn test_spiral_invert_inverse() {
+        // The inverse keeps the scale and negates the angle
+        let xform = from_json(&array!["spiral_invert", 0, 1, 1, 40, 2]);
+        let expected = from_json(&array!["spiral_invert", 0, 1, 1, -40, 2]);
+        let inverse = xform.inverse().unwrap();
+        let point = HalfMultivector::point(0.3, -1.2, 0.8);
+        let (x, y, z) = expected.transform(&point).coordinates();
+        assert_point_eq(&inverse.transform(&point), (x, y, z));
+        assert_eq!(xform.name(), "spiral_invert((0, 1, 1), 40°, 2)");
+    }
+
+    #[test]
+    #[should_panic(expected = "use reflect")]
+    fn test_scale_must_be_positive() {
+        from_json(&array!["scale", -1]);
+    }
+
+    #[test]
+    fn test_blend_stays_between_endpoints() {
+        let xform = from_json(&array![
+            "blend",
+            array!["rotate", 0, 0, 1, 0],
+            array!["rotate", 0, 0, 1, 30],
+            1
+        ]);
+        assert_eq!(xform.name(), "blend(rotate(z, 0°), rotate(z, 30°))");
+
+        let point = HalfMultivector::point(1.0, 0.0, 0.0);
+        let angles: Vec<f64> = (0..100).map(|_| {
+            let (x, y, z) = xform.transform(&point).coordinates();
+            assert!(z.abs() < 1e-12);
+            assert!(((x * x + y * y) - 1.0).abs() < 1e-12);
+            y.atan2(x).to_degrees()
+        }).collect();
+        assert!(angles.iter().all(|angle| (0.0..=30.0).contains(angle)));
+        // The angle changes from one application to the next
+        assert!(angles.windows(2).any(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_blend_seed_is_reproducible() {
+        let blend = || from_json(&array![
+            "blend",
+            array!["scale", 0.5],
+            array!["chain", array![
+                array!["scale", 0.5],
+                array!["rotate", 1, 1, 0, 90]
+            ]],
+            42
+        ]);
+        let (a, b) = (blend(), blend());
+        let point = HalfMultivector::point(0.3, -0.2, 0.7);
+        for _ in 0..10 {
+            assert_eq!(
+                a.transform(&point).coordinates(),
+                b.transform(&point).coordinates());
+        }
+    }
+
+    #[test]
+    fn test_unseeded_blend_follows_render_seed() {
+        let point = HalfMultivector::point(1.0, 0.0, 0.0);
+        let blend_once = || {
+            random::reseed(Some(42));
+            let xform = from_json(&array![
+                "blend",
+                array!["rotate", 0, 0, 1, 0],
+                array!["rotate", 0, 0, 1, 30]
+            ]);
+            xform.transform(&point).coordinates()
+        };
+        assert_eq!(blend_once(), blend_once());
+        random::reseed(None);
+    }
+
+    #[test]
+    #[should_panic(expected = "blend: only versor transformations")]
+    fn test_blend_needs_versors() {
+        from_json(&array!["blend", array!["snap", 1, 1, 1], array!["invert"]]);
+    }
+
+    #[test]
+    fn test_power() {
+        let squared = from_json(&array!["power", array!["rotate", 0, 0, 1, 45], 2]);
+        let rotate_90 = from_json(&array!["rotate", 0, 0, 1, 90]);
+        assert!(squared.versor().unwrap().approx_eq(
+            rotate_90.versor().unwrap(), 1e-12));
+        assert_eq!(squared.name(), "power(rotate(z, 45°), 2)");
+
+        // Negative powers rotate the other way
+        let point = HalfMultivector::point(1.0, 0.0, 0.0);
+        let backwards = from_json(
+            &array!["power", array!["rotate", 0, 0, 1, 30], -3]);
+        assert_point_eq(&backwards.transform(&point), (0.0, -1.0, 0.0));
+
+        // The inverse of power k is power -k
+        let forwards = from_json(
+            &array!["power", array!["rotate", 0, 0, 1, 30], 3]);
+        assert!(forwards.inverse().unwrap().versor().unwrap().approx_eq(
+            backwards.versor().unwrap(), 1e-12));
+
+        let identity = from_json(&array!["power", array!["scale", 2], 0]);
+        assert_point_eq(&identity.transform(&point), (1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn test_raw_versor() {
+        // A rotor with only scalar and xy parts. The half angle is negated
+        // for a counterclockwise rotation, see HalfMultivector::rotation()
+        let angle = 30.0_f64.to_radians();
+        let (sin, cos) = (-0.5 * angle).sin_cos();
+        let raw = from_json(&array![
+            "versor", "even",
+            cos, 0, 0, 0, 0, 0, sin, 0, 0, 0, 0, 0, 0, 0, 0, 0
+        ]);
+        let rotation = HalfMultivector::rotation(0.0, 0.0, 1.0, angle);
+        let point = HalfMultivector::point(1.0, 2.0, 3.0);
+        let expected = Xform::new(rotation, "rotate").transform(&point);
+        assert_point_eq(&raw.transform(&point), expected.coordinates());
+        assert_eq!(raw.name(), "versor(even)");
+    }
+
+    #[test]
+    #[should_panic(expected = "versor: not a versor")]
+    fn test_raw_versor_rejects_non_versor() {
+        from_json(&array![
+            "versor", "even",
+            2, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+        ]);
+    }
+
+    #[test]
+    #[should_panic(expected = "only versor transformations")]
+    fn test_power_requires_versor() {
+        from_json(&array!["power", array!["expr", "x", "y", "z"], 2]);
+    }
+
+    /// One example of every type in XFORM_TYPES, chosen so the versor
+    /// isn't normalized where possible
+    fn example_xform(xform_type: &str) -> JsonValue {
+        match xform_type {
+            "chain" => array!["chain", array![
+                array!["scale", 2],
+                array!["rotate", 1, 1, 0, 30],
+                array!["translate", 0.5, 0, -1]
+            ]],
+            "slerp" => array![
+                "slerp",
+                array!["rotate", 0, 0, 1, 0],
+                array!["rotate", 0, 1, 0, 90],
+                0.25
+            ],
+            "blend" => array![
+                "blend",
+                array!["rotate", 0, 0, 1, 0],
+                array!["rotate", 0, 1, 0, 90],
+                7
+            ],
+            "power" => array!["power", array!["rotate", 1, 0, 1, 40], -3],
+            "versor" => array![
+                "versor", "odd",
+                0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0
+            ],
+            "invert" => array!["invert"],
+            "identity" => array!["identity"],
+            "translate" => array!["translate", 1, -2, 0.5],
+            "rotate" => array!["rotate", 1, 2, 3, 60],
+            "poloidal" => array!["poloidal", 0, 0, 1, 45],
+            "scale" => array!["scale", 3],
+            "reflect" => array!["reflect", 0, 1, 0],
+            "rotoreflect" => array!["rotoreflect", 1, 0, 1, 72],
+            "spiral_invert" => array!["spiral_invert", 1, 0, 1, 72, 1.5],
+            "sct" => array!["sct", 0.5, -1, 2],
+            "invert_sphere" => array!["invert_sphere", 1, 2, 3, 2],
+            "mobius" => array!["mobius", 1, 1, 0.5, 0, -0.25, 0.5, 2, 0],
+            "expr" => array!["expr", "x*cos(z)", "y", "x*sin(z)"],
+            "snap" => array!["snap", 0.5, 0.25, 1],
+            "project_plane" => array!["project_plane", 1, 1, 0, 2],
+            "project_sphere" => array!["project_sphere", 1, 0, -1, 0.5],
+            "pinch" => array!["pinch", 1, 0, 0, 0.5],
+            _ => panic!("no example for xform type {}", xform_type)
+        }
+    }
+
+    #[test]
+    fn test_inverse_fixes_points() {
+        let mut rng = rand::thread_rng();
+        for xform_type in XFORM_TYPES.iter() {
+            let xform = from_json(&example_xform(xform_type));
+            let inverse = match xform.inverse() {
+                Some(inverse) => inverse,
+                None => {
+                    // Expressions, snapping, projections, pinches and
+                    // blends are the only transformations that can't be
+                    // inverted
+                    assert!([
+                        "expr", "snap", "project_plane", "project_sphere",
+                        "pinch", "blend"
+                    ].contains(xform_type));
+                    continue;
+                }
+            };
+            for _ in 0..10 {
+                let (x, y, z) = (
+                    rng.gen_range(-2.0, 2.0),
+                    rng.gen_range(-2.0, 2.0),
+                    rng.gen_range(-2.0, 2.0));
+                let point = HalfMultivector::point(x, y, z);
+                let result = inverse.transform(&xform.transform(&point));
+                let (rx, ry, rz) = result.coordinates();
+                let error = (rx - x).abs() + (ry - y).abs() + (rz - z).abs();
+                assert!(
+                    error < 1e-6,
+                    "{}: ({}, {}, {}) -> ({}, {}, {})",
+                    xform_type, x, y, z, rx, ry, rz);
+
+                // Versors can also be composed into a single versor
+                if let Some(versor) = xform.versor() {
+                    let single = Xform::new(versor.clone(), xform_type);
+                    let round_trip = single.followed_by(&single.inverse());
+                    let (rx, ry, rz) = round_trip.transform(&point).coordinates();
+                    let error = (rx - x).abs() + (ry - y).abs() + (rz - z).abs();
+                    assert!(error < 1e-6, "{}: composed inverse", xform_type);
+                }
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_expr() {
+        let xform = from_json(&example_xform("expr"));
+        assert_eq!(xform.name(), "expr(x*cos(z), y, x*sin(z))");
+        let point = HalfMultivector::point(2.0, -1.0, PI / 2.0);
+        assert_point_eq(&xform.transform(&point), (0.0, -1.0, 2.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "expr: could not parse \"y +\"")]
+    fn test_expr_parse_error() {
+        from_json(&array!["expr", "x", "y +", "z"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_mobius_degenerate_panics() {
+        from_json(&array!["mobius", 1, 0, 2, 0, 1, 0, 2, 0]);
+    }
+
+    #[test]
+    fn test_classification() {
+        use Classification::*;
+        let classify = |json: JsonValue| from_json(&json).classification();
+        assert_eq!(classify(array!["rotate", 0, 0, 1, 90]), Conformal);
+        assert_eq!(classify(array!["sct", 0.1, 0, 0]), Conformal);
+        assert_eq!(classify(array!["reflect", 1, 0, 0]), AntiConformal);
+        assert_eq!(classify(array!["invert_sphere", 0, 0, 0, 2]), AntiConformal);
+        assert_eq!(classify(array!["snap", 0.1, 0.1, 0.1]), NonConformal);
+        assert_eq!(classify(array!["mobius", 1, 0, 0, 0, 0, 0, 1, 0]), NonConformal);
+
+        // Two reflections make a rotation
+        let chain = array!["chain", array![
+            array!["reflect", 1, 0, 0],
+            array!["reflect", 0, 1, 0]
+        ]];
+        assert_eq!(classify(chain), Conformal);
+        let chain = array!["chain", array![
+            array!["reflect", 1, 0, 0],
+            array!["expr", "x", "y", "z"]
+        ]];
+        assert_eq!(classify(chain), NonConformal);
+        let warped = object!{
+            "xform" => array!["scale", 0.5],
+            "warp" => object!{"amp" => 0.1, "freq" => 2.0}
+        };
+        assert_eq!(classify(warped), NonConformal);
+
+        // An even number of orientation flips undoes them
+        assert_eq!(
+            classify(array!["power", array!["reflect", 1, 0, 0], 2]), Conformal);
+    }
+
+    #[test]
+    fn test_describe() {
+        // The chain is multiplied out into a single versor
+        let chain = from_json(&array!["chain", array![
+            array!["scale", 2],
+            array!["translate", 1, 0, 0]
+        ]]);
+        let versor = format!("{:?}", chain.versor().unwrap());
+        let description = chain.describe();
+        assert!(description.starts_with(&chain.name()), "{}", description);
+        assert!(description.contains("classification: conformal"));
+        assert!(description.ends_with(&format!("versor: {}", versor)));
+
+        let expr = from_json(&array!["expr", "x", "y", "z"]);
+        assert!(expr.describe().ends_with("versor: none"));
+    }
+
+    #[test]
+    fn test_snap() {
+        let xform = from_json(&array!["snap", 0.5, 0.25, 1]);
+        assert_eq!(xform.name(), "snap(0.5, 0.25, 1)");
+        assert!(xform.inverse().is_none());
+
+        let point = HalfMultivector::point(0.7, -0.3, 2.4);
+        assert_point_eq(&xform.transform(&point), (0.5, -0.25, 2.0));
+        let point = HalfMultivector::point(-0.8, 0.13, -0.6);
+        assert_point_eq(&xform.transform(&point), (-1.0, 0.25, -1.0));
+
+        // Lattice sites stay put
+        let site = HalfMultivector::point(1.5, 0.75, -3.0);
+        assert_point_eq(&xform.transform(&site), (1.5, 0.75, -3.0));
+    }
+
+    #[test]
+    #[should_panic(expected = "snap: the lattice spacing must be positive")]
+    fn test_snap_requires_positive_spacing() {
+        from_json(&array!["snap", 0.5, 0, 1]);
+    }
+
+    #[test]
+    fn test_project_plane() {
+        let xform = from_json(&example_xform("project_plane"));
+        assert!(xform.inverse().is_none());
+        assert_eq!(xform.classification(), Classification::NonConformal);
+
+        // x + y = 2 with the normal (1, 1, 0)
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let point = HalfMultivector::point(
+                rng.gen_range(-3.0, 3.0),
+                rng.gen_range(-3.0, 3.0),
+                rng.gen_range(-3.0, 3.0));
+            let (x, y, z) = xform.transform(&point).coordinates();
+            assert!((x + y - 2.0).abs() < 1e-9);
+            // Only the component along the normal changes
+            let (px, py, pz) = point.coordinates();
+            assert!(((x - y) - (px - py)).abs() < 1e-9);
+            assert!((z - pz).abs() < 1e-12);
+        }
+
+        let flatten = from_json(&array!["project_plane", 0, 0, 2, 1]);
+        let point = HalfMultivector::point(0.5, -1.0, 3.0);
+        assert_point_eq(&flatten.transform(&point), (0.5, -1.0, 0.5));
+    }
+
+    #[test]
+    fn test_project_sphere() {
+        let xform = from_json(&example_xform("project_sphere"));
+        assert!(xform.inverse().is_none());
+        assert_eq!(xform.name(), "project_sphere((1, 0, -1), 0.5)");
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..10 {
+            let point = HalfMultivector::point(
+                rng.gen_range(-3.0, 3.0),
+                rng.gen_range(-3.0, 3.0),
+                rng.gen_range(-3.0, 3.0));
+            let (x, y, z) = xform.transform(&point).coordinates();
+            let distance =
+                ((x - 1.0).powi(2) + y.powi(2) + (z + 1.0).powi(2)).sqrt();
+            assert!((distance - 0.5).abs() < 1e-9);
+        }
+
+        // The center goes to the top of the sphere
+        let center = HalfMultivector::point(1.0, 0.0, -1.0);
+        assert_point_eq(&xform.transform(&center), (1.0, 0.0, -0.5));
+    }
+
+    #[test]
+    fn test_pinch_radial_displacement() {
+        let xform = from_json(&example_xform("pinch"));
+        assert_eq!(xform.name(), "pinch((1, 0, 0), 0.5)");
+
+        // Points move toward the center by 0.5 r exp(-r^2) at each radius
+        for r in [0.25f64, 0.5, 1.0, 2.0].iter() {
+            let expected = r * (1.0 - 0.5 * (-r * r).exp());
+            let point = HalfMultivector::point(1.0, *r, 0.0);
+            assert_point_eq(&xform.transform(&point), (1.0, expected, 0.0));
+            let point = HalfMultivector::point(1.0 - r, 0.0, 0.0);
+            assert_point_eq(
+                &xform.transform(&point), (1.0 - expected, 0.0, 0.0));
+        }
+
+        // The center is fixed and far points barely move
+        let center = HalfMultivector::point(1.0, 0.0, 0.0);
+        assert_point_eq(&xform.transform(&center), (1.0, 0.0, 0.0));
+        let far = HalfMultivector::point(1.0, 0.0, 10.0);
+        assert_point_eq(&xform.transform(&far), (1.0, 0.0, 10.0));
+
+        // A negative strength bulges outward
+        let bulge = from_json(&array!["pinch", 0, 0, 0, -1]);
+        let point = HalfMultivector::point(0.0, 0.0, 0.5);
+        let expected = 0.5 * (1.0 + (-0.25f64).exp());
+        assert_point_eq(&bulge.transform(&point), (0.0, 0.0, expected));
+    }
+
+    #[test]
+    #[should_panic(expected = "pinch: the strength must be at most 1")]
+    fn test_pinch_strength_limit() {
+        from_json(&array!["pinch", 0, 0, 0, 1.5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "project_plane: the normal must not be zero")]
+    fn test_project_plane_requires_normal() {
+        from_json(&array!["project_plane", 0, 0, 0, 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "project_sphere: should be")]
+    fn test_project_sphere_usage() {
+        from_json(&array!["project_sphere", 0, 0, 1]);
+    }
+}
@@ -0,0 +1,484 @@
+//! End-to-end tests: render tiny parameter files all the way to a tileset
+//! and check that what was written is consistent.
+
+use std::convert::TryInto;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use json::{array, object, JsonValue};
+
+use chaos_game_3d::algorithms;
+use chaos_game_3d::compare::{compare_points, load_points, read_tileset};
+use chaos_game_3d::{echo_metadata, generate_points};
+
+static SCRATCH_DIR: Once = Once::new();
+
+/// Tilesets are always written to ./viewer/{id}, so run the tests in a
+/// scratch directory rather than the repository. The working directory is
+/// shared by every test in this file, so each test uses its own id.
+fn enter_scratch_dir() {
+    SCRATCH_DIR.call_once(|| {
+        let dir = std::env::temp_dir()
+            .join(format!("chaos-game-3d-pipeline-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+    });
+}
+
+/// A Sierpinski triangle in the z = 0 plane
+fn sierpinski_params(id: &str, format: &str) -> JsonValue {
+    object!{
+        "id" => id,
+        "name" => "Sierpinski",
+        "algorithm" => "chaos",
+        "iters" => 3000,
+        "plotter" => object!{
+            "radius" => 1.0,
+            "node_capacity" => 200,
+            "max_depth" => 4,
+            "format" => format
+        },
+        "ifs" => object!{
+            "xforms" => array![
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", -0.5, 0, 0]]],
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", 0.5, 0, 0]]],
+                array!["chain", array![
+                    array!["scale", 0.5], array!["translate", 0, 0.5, 0]]]
+            ]
+        },
+        "color_ifs" => object!{
+            "xforms" => array![array!["scale", 1.0]]
+        },
+        "progress_interval" => 0
+    }
+}
+
+/// Two copies of a box of points that spiral inwards
+fn spiral_params(id: &str) -> JsonValue {
+    object!{
+        "id" => id,
+        "name" => "Spiral",
+        "algorithm" => "chaos_sets",
+        "iters" => 4,
+        "cluster_copies" => 2,
+        "cluster" => object!{
+            "type" => "box",
+            "num_points" => 300,
+            "dims" => array![1.0, 1.0, 1.0]
+        },
+        "plotter" => object!{
+            "radius" => 2.0,
+            "node_capacity" => 100,
+            "max_depth" => 5,
+            "format" => "pnts"
+        },
+        "ifs" => object!{
+            "xforms" => array![
+                array!["chain", array![
+                    array!["scale", 0.8],
+                    array!["rotate", 0, 0, 1, 30],
+                    array!["translate", 0.2, 0, 0]
+                ]]
+            ]
+        },
+        "color_ifs" => object!{
+            "xforms" => array![array!["scale", 1.0]]
+        },
+        "progress_interval" => 0
+    }
+}
+
+/// What a render wrote, and what the algorithm reported about it
+struct Render {
+    tileset_dir: PathBuf,
+    tileset: JsonValue,
+    complexity: usize,
+    accepted: usize,
+}
+
+impl Render {
+    fn run(params: &JsonValue) -> Self {
+        enter_scratch_dir();
+        let mut chaos = algorithms::from_json(params);
+        let complexity = chaos.complexity();
+        chaos.iterate();
+        let accepted = chaos.plot_counts().accepted;
+        chaos.save();
+
+        let id = params["id"].as_str().unwrap();
+        let tileset_dir = Path::new("viewer").join(id);
+        let text = fs::read_to_string(tileset_dir.join("tileset.json"))
+            .expect("tileset.json was not written");
+        let tileset = json::parse(&text).expect("tileset.json must parse");
+
+        Self { tileset_dir, tileset, complexity, accepted }
+    }
+
+    /// The content files of every tile, with whether the tile is a leaf
+    fn tile_files(&self) -> Vec<(PathBuf, bool)> {
+        let mut files = Vec::new();
+        let root = &self.tileset["root"];
+        collect_tile_files(root, &self.tileset_dir, &mut files);
+        files
+    }
+
+    fn clean_up(self) {
+        fs::remove_dir_all(self.tileset_dir).unwrap();
+    }
+}
+
+fn collect_tile_files(
+        tile: &JsonValue,
+        tileset_dir: &Path,
+        files: &mut Vec<(PathBuf, bool)>) {
+    let is_leaf = tile["children"].is_empty();
+    if let Some(uri) = tile["content"]["uri"].as_str() {
+        files.push((tileset_dir.join(uri), is_leaf));
+    }
+    for child in tile["children"].members() {
+        collect_tile_files(child, tileset_dir, files);
+    }
+}
+
+/// Read the positions from a .pnts tile
+fn read_pnts_positions(path: &Path) -> Vec<[f32; 3]> {
+    let bytes = fs::read(path).unwrap();
+    assert_eq!(&bytes[0..4], b"pnts", "{}", path.display());
+    let u32_at = |offset: usize|
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    assert_eq!(u32_at(8) as usize, bytes.len(), "{}", path.display());
+
+    // The feature table JSON comes right after the 28-byte header
+    let json_length = u32_at(12) as usize;
+    let table_text = std::str::from_utf8(&bytes[28..28 + json_length]).unwrap();
+    let table = json::parse(table_text).unwrap();
+    let count = table["POINTS_LENGTH"].as_usize().unwrap();
+    let start = 28 + json_length + table["POSITION"]["byteOffset"]
+        .as_usize()
+        .unwrap();
+
+    let f32_at = |offset: usize|
+        f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    (0..count).map(|i| {
+        let offset = start + 12 * i;
+        [f32_at(offset), f32_at(offset + 4), f32_at(offset + 8)]
+    }).collect()
+}
+
+/// Check the invariants of a .pnts tileset: every tile exists, the leaves
+/// hold every kept point, which is no more than the estimate, and each
+/// position is finite and inside the root box
+fn check_pnts_tileset(params: &JsonValue) {
+    let render = Render::run(params);
+    let radius = params["plotter"]["radius"].as_f32().unwrap();
+    assert!(render.accepted > 0);
+    assert!(
+        render.accepted <= render.complexity,
+        "{} points kept but only {} estimated",
+        render.accepted, render.complexity);
+
+    let mut leaf_points = 0;
+    for (path, is_leaf) in render.tile_files() {
+        assert!(path.exists(), "missing tile {}", path.display());
+        let positions = read_pnts_positions(&path);
+        for position in positions.iter() {
+            assert!(
+                position.iter().all(|x| x.is_finite() && x.abs() <= radius),
+                "{:?} is outside the root in {}", position, path.display());
+        }
+        if is_leaf {
+            leaf_points += positions.len();
+        }
+    }
+    assert_eq!(leaf_points, render.accepted);
+
+    render.clean_up();
+}
+
+#[test]
+fn test_chaos_game_pnts() {
+    check_pnts_tileset(&sierpinski_params("pipeline_chaos_pnts", "pnts"));
+}
+
+#[test]
+fn test_chaos_sets_pnts() {
+    check_pnts_tileset(&spiral_params("pipeline_chaos_sets_pnts"));
+}
+
+#[test]
+fn test_chaos_game_glb() {
+    let params = sierpinski_params("pipeline_chaos_glb", "glb");
+    let render = Render::run(&params);
+    assert!(render.accepted <= render.complexity);
+    let files = render.tile_files();
+    assert!(!files.is_empty());
+    for (path, _) in files.iter() {
+        let bytes = fs::read(path)
+            .unwrap_or_else(|_| panic!("missing tile {}", path.display()));
+        assert_eq!(&bytes[0..4], b"glTF", "{}", path.display());
+    }
+    render.clean_up();
+
+    // The glb tiles are harder to read back, so check the positions in
+    // process instead
+    for point in generate_points(&params) {
+        let position = point.position;
+        assert!(position.x().is_finite() && position.x().abs() <= 1.0);
+        assert!(position.y().is_finite() && position.y().abs() <= 1.0);
+        assert!(position.z().is_finite() && position.z().abs() <= 1.0);
+    }
+}
+
+#[test]
+fn test_tiles_version_1_0_has_no_extensions() {
+    let mut params = sierpinski_params("pipeline_tiles_1_0", "pnts");
+    let render = Render::run(&params);
+    assert_eq!(render.tileset["asset"]["version"], "1.0");
+    assert!(render.tileset.has_key("extensionsUsed"));
+    render.clean_up();
+
+    params["plotter"]["tiles_version"] = "1.0".into();
+    let render = Render::run(&params);
+    let tileset = &render.tileset;
+    assert_eq!(tileset["asset"]["version"], "1.0");
+    for key in &["extensionsUsed", "extensionsRequired", "extensions"] {
+        assert!(!tileset.has_key(key), "1.0 tileset has {}", key);
+    }
+    // The fractal metadata needs 3DTILES_metadata
+    assert!(!tileset.has_key("schema"));
+    assert!(!tileset.has_key("metadata"));
+    assert!(!render.tile_files().is_empty());
+    render.clean_up();
+}
+
+#[test]
+fn test_split_subclusters() {
+    let mut params = spiral_params("pipeline_split_subclusters");
+    params["cluster"] = object!{
+        "type" => "many",
+        "clusters" => array![
+            object!{ "type" => "box", "dims" => array![0.5, 0.5, 0.5] },
+            object!{ "type" => "line" },
+            object!{ "type" => "circle" }
+        ],
+        "num_points" => 300
+    };
+    params["split_subclusters"] = true.into();
+    let render = Render::run(&params);
+
+    let tiles = &render.tileset["root"]["children"];
+    assert_eq!(tiles.len(), 3);
+    for (i, tile) in tiles.members().enumerate() {
+        let uri = format!("cluster_{}/tileset.json", i);
+        assert_eq!(tile["content"]["uri"], uri.as_str());
+        assert_eq!(tile["extras"]["cluster_id"], i);
+        assert!(render.tileset_dir.join(uri).exists());
+    }
+    render.clean_up();
+}
+
+#[test]
+fn test_split_subclusters_sphere_volumes() {
+    let mut params = spiral_params("pipeline_split_spheres");
+    params["cluster"] = object!{
+        "type" => "many",
+        "clusters" => array![
+            object!{ "type" => "box", "dims" => array![0.5, 0.5, 0.5] },
+            object!{ "type" => "circle" }
+        ],
+        "num_points" => 200
+    };
+    params["split_subclusters"] = true.into();
+    params["plotter"]["bounding_volume"] = "sphere".into();
+    let render = Render::run(&params);
+
+    // The merged tileset refers to the subclusters by the box around
+    // their root spheres
+    let root = &render.tileset["root"];
+    assert_eq!(root["children"].len(), 2);
+    assert_eq!(root["boundingVolume"]["box"].len(), 12);
+    render.clean_up();
+}
+
+#[test]
+fn test_echo_metadata() {
+    // Nothing is written, so this doesn't need the scratch directory
+    let params = sierpinski_params("pipeline_echo_metadata", "glb");
+    let metadata = echo_metadata(&params);
+    assert!(metadata["tileset_schema"]["classes"].is_object());
+    let properties = &metadata["point_schema"]["properties"];
+    assert_eq!(properties["iteration"]["componentType"], "UINT32");
+
+    // The triangle is in the z = 0 plane, inside the unit square. The
+    // first points kept are only close to the attractor.
+    let ranges = &metadata["ranges"];
+    assert!(ranges["position"][0][2].as_f64().unwrap().abs() < 0.01);
+    assert!(ranges["position"][1][2].as_f64().unwrap().abs() < 0.01);
+    assert!(ranges["position"][0][0].as_f64().unwrap() >= -1.0);
+    assert!(ranges["last_xform"][1].as_f64().unwrap() <= 2.0);
+
+    let pnts = sierpinski_params("pipeline_echo_metadata", "pnts");
+    assert!(echo_metadata(&pnts)["point_schema"].is_null());
+}
+
+#[test]
+fn test_compare_tilesets() {
+    let cases = [
+        ("pnts", "explicit"),
+        ("glb", "explicit"),
+        ("glb", "implicit"),
+        ("csv", "explicit")
+    ];
+    for (format, tiling) in cases.iter() {
+        let id = format!("pipeline_compare_{}_{}", format, tiling);
+        let mut params = sierpinski_params(&id, format);
+        params["plotter"]["tiling"] = (*tiling).into();
+        enter_scratch_dir();
+        let mut chaos = algorithms::from_json(&params);
+        chaos.iterate();
+        let accepted = chaos.plot_counts().accepted;
+        chaos.save();
+
+        let dir = Path::new("viewer").join(&id);
+        let points = read_tileset(&dir);
+        assert_eq!(points.len(), accepted, "{} {}", format, tiling);
+        let comparison = compare_points(points.clone(), points, 1e-5, 10);
+        assert!(comparison.is_match());
+
+        // A different run of the chaos game lands on different points
+        let other = generate_points(&params)
+            .into_iter()
+            .map(|point| point.position)
+            .collect();
+        let comparison = compare_points(read_tileset(&dir), other, 1e-5, 3);
+        assert!(!comparison.is_match());
+        assert_eq!(comparison.mismatches.len(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
+
+#[test]
+fn test_compare_seeded_params() {
+    let id = "pipeline_compare_seeded";
+    let mut params = sierpinski_params(id, "pnts");
+    params["seed"] = 7.into();
+    enter_scratch_dir();
+    let fname = format!("{}.json", id);
+    fs::write(&fname, params.dump()).unwrap();
+
+    // The same seed renders the same points every time
+    let comparison =
+        compare_points(load_points(&fname), load_points(&fname), 1e-5, 10);
+    assert!(comparison.is_match());
+
+    let mut chaos = algorithms::from_json(&params);
+    chaos.iterate();
+    chaos.save();
+    let dir = Path::new("viewer").join(id);
+    let comparison =
+        compare_points(load_points(&fname), read_tileset(&dir), 1e-5, 10);
+    assert!(comparison.is_match());
+
+    params["seed"] = 8.into();
+    fs::write(&fname, params.dump()).unwrap();
+    let comparison =
+        compare_points(load_points(&fname), read_tileset(&dir), 1e-5, 3);
+    assert!(!comparison.is_match());
+
+    fs::remove_file(&fname).unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_compare_seeded_jitter() {
+    let id = "pipeline_compare_jitter";
+    let mut params = sierpinski_params(id, "pnts");
+    params["seed"] = 7.into();
+    // The jitter has no seed of its own, so it follows the render's
+    params["jitter"] = object!{ "position" => 0.01, "color" => 0.05 };
+    enter_scratch_dir();
+    let fname = format!("{}.json", id);
+    fs::write(&fname, params.dump()).unwrap();
+
+    let comparison =
+        compare_points(load_points(&fname), load_points(&fname), 1e-5, 10);
+    assert!(comparison.is_match());
+    fs::remove_file(&fname).unwrap();
+}
+
+#[test]
+fn test_compare_seeded_blend() {
+    let id = "pipeline_compare_blend";
+    let mut params = sierpinski_params(id, "pnts");
+    params["seed"] = 7.into();
+    // The blend has no seed of its own, so it follows the render's
+    params["ifs"]["xforms"].push(array![
+        "blend", array!["scale", 0.5], array!["chain", array![
+            array!["scale", 0.5], array!["rotate", 0, 0, 1, 10]]]
+    ]).unwrap();
+    enter_scratch_dir();
+    let fname = format!("{}.json", id);
+    fs::write(&fname, params.dump()).unwrap();
+
+    let comparison =
+        compare_points(load_points(&fname), load_points(&fname), 1e-5, 10);
+    assert!(comparison.is_match());
+    fs::remove_file(&fname).unwrap();
+}
+
+#[test]
+fn test_resume_chaos_game() {
+    let id = "pipeline_resume";
+    let mut params = sierpinski_params(id, "pnts");
+    params["seed"] = 11.into();
+    params["plotter"]["node_capacity"] = 10000.into();
+    params["checkpoint_every"] = 1000.into();
+    enter_scratch_dir();
+
+    // Without saving, the checkpoint from the last period is left behind,
+    // after 2000 of the 3000 iterations
+    let mut full = algorithms::from_json(&params);
+    full.iterate();
+    let accepted = full.plot_counts().accepted;
+    assert_eq!(accepted, 3000);
+    let checkpoint = format!("viewer/{}.ckpt", id);
+    assert!(Path::new(&checkpoint).exists());
+
+    // The same seed plots the same first 2000 points
+    let mut first_part = params.clone();
+    first_part["iters"] = 2000.into();
+    first_part.remove("checkpoint_every");
+    let before: Vec<_> = generate_points(&first_part)
+        .into_iter()
+        .map(|point| point.position)
+        .collect();
+
+    let mut resumed = algorithms::from_json(&params);
+    resumed.resume(&checkpoint);
+    resumed.iterate();
+    assert_eq!(resumed.plot_counts().accepted, accepted);
+    let after: Vec<_> = resumed
+        .into_points()
+        .into_iter()
+        .map(|point| point.position)
+        .collect();
+    assert_eq!(after.len(), 3000);
+
+    // Every point from before the checkpoint is still there, only the
+    // 1000 iterations after it are unmatched
+    let comparison = compare_points(before, after, 1e-6, 0);
+    assert_eq!(comparison.mismatch_count, 1000);
+    fs::remove_file(checkpoint).unwrap();
+}
+
+#[test]
+#[should_panic(expected = "has no seed")]
+fn test_compare_requires_seed() {
+    enter_scratch_dir();
+    let fname = "pipeline_compare_unseeded.json";
+    fs::write(fname, sierpinski_params("unseeded", "pnts").dump()).unwrap();
+    load_points(fname);
+}
@@ -120,6 +120,28 @@ SHADERS.last_xform = new Cesium.CustomShader({
     `
 });
 
+SHADERS.last_xform_group = new Cesium.CustomShader({
+    uniforms: {
+        u_xform_group_count: {
+            type: Cesium.UniformType.FLOAT,
+            value: 1
+        }
+    },
+    lightingModel: Cesium.LightingModel.UNLIT,
+    vertexShaderText: `
+    void vertexMain(VertexInput vsInput, inout czm_modelVertexOutput vsOutput) {
+        vsOutput.pointSize = 4.0;
+    }
+    `,
+    fragmentShaderText: `
+    void fragmentMain(FragmentInput fsInput, inout czm_modelMaterial material) {
+        float group_normalized = fsInput.attributes.featureId_4 / u_xform_group_count;
+        vec3 rgb = czm_HSBToRGB(vec3(group_normalized, 0.8, 1.0));
+        material.diffuse = rgb;
+    }
+    `
+});
+
 SHADERS.first = new Cesium.CustomShader({
     uniforms: {
         u_cluster_copies: {
@@ -404,6 +426,10 @@ const OPTIONS = [
         name: "Color by last xform",
         value: "last_xform"
     },
+    {
+        name: "Color by last xform (inverses matched)",
+        value: "last_xform_group"
+    },
     {
         name: "View cluster coordinates",
         value: "cluster_coordinates"
@@ -476,6 +502,9 @@ class FractalShading {
         const ifs_xform_count = metadata.getProperty("ifs_xform_count");
         SHADERS.last_xform.setUniform("u_xform_count", ifs_xform_count);
 
+        const ifs_xform_group_count = metadata.getProperty("ifs_xform_group_count");
+        SHADERS.last_xform_group.setUniform("u_xform_group_count", ifs_xform_group_count);
+
         // Usually these two are the same, but when using ManyClusters,
         // it's the smaller subcluster_max_point_count that I want.
         const cluster_point_count = metadata.getProperty("cluster_point_count");